@@ -0,0 +1,383 @@
+use std::path::PathBuf;
+
+use glam::UVec2;
+
+use crate::{
+    config_file::CONFIG_FILE_PATH,
+    graphics::{AdapterSelection, OutputFormat},
+    input::{replay::InputRecording, InputSoak},
+    logging::LogConfig,
+};
+
+/// How the window occupies the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fullscreen {
+    /// A regular window.
+    #[default]
+    Windowed,
+    /// A borderless window covering the current monitor.
+    Borderless,
+    /// Exclusive fullscreen at the current monitor's best video mode.
+    Exclusive,
+}
+
+/// How the cursor is kept within the window, see [crate::Engine::set_cursor_grab].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CursorGrab {
+    /// The cursor moves freely.
+    #[default]
+    None,
+    /// The cursor can't leave the window.
+    Confined,
+    /// The cursor stays where it is, such as for mouse look in first person games.
+    /// Platforms that can't lock the cursor confine it instead.
+    Locked,
+}
+
+/// What the [crate::Engine] does while the window is in the background, having lost
+/// focus, been minimized, or been hidden behind other windows.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BackgroundBehavior {
+    /// Keeps updating and rendering as normal.
+    #[default]
+    Run,
+    /// Keeps updating and rendering at most this many frames per second.
+    Throttle(f64),
+    /// Stops updating and rendering, sleeping until the window returns. The time spent
+    /// paused isn't simulated afterwards.
+    Pause,
+}
+
+/// Image to use as the window's icon.
+#[derive(Clone, Debug)]
+pub struct WindowIcon {
+    rgba: Vec<u8>,
+    size: UVec2,
+}
+
+/// Configuration of the application window.
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    /// Title of the window.
+    pub title: String,
+    /// Initial inner size of the window in pixels.
+    pub size: UVec2,
+    /// Smallest inner size the window can be resized to.
+    pub min_size: Option<UVec2>,
+    /// Whether the window can be resized.
+    pub resizable: bool,
+    /// How the window occupies the screen.
+    pub fullscreen: Fullscreen,
+    /// Icon of the window.
+    pub icon: Option<WindowIcon>,
+    /// On the web, id of the canvas element on the page to render to. Without one a
+    /// canvas is created and added to the end of the page's body. Ignored natively.
+    pub canvas_id: Option<String>,
+}
+
+/// Configuration used to start the [crate::Engine] with [crate::run_with_config].
+#[derive(Clone, Debug)]
+pub struct EngineConfig {
+    /// Configuration of the application window.
+    pub window: WindowConfig,
+    /// Adapter (GPU) to render with, unless [crate::Application::select_adapter]
+    /// chooses one.
+    pub adapter: AdapterSelection,
+    /// Whether presenting waits for the display's vertical sync.
+    pub vsync: bool,
+    /// Color encoding of the frames presented to the window. Surfaces that don't
+    /// support it fall back to sRGB, see [crate::graphics::RenderContext::output_format].
+    pub output_format: OutputFormat,
+    /// Most frames that can be submitted before the cpu waits for the gpu, see
+    /// [crate::graphics::RenderContext::set_max_frames_in_flight].
+    pub max_frames_in_flight: u32,
+    /// How many times per second [crate::Application::fixed_update] is called.
+    pub fixed_tick_rate: f64,
+    /// Most frames rendered per second, or `None` for no limit. Frames are otherwise
+    /// only limited by vsync, so this keeps the frame rate steady with it off.
+    pub max_fps: Option<f64>,
+    /// What the engine does while the window is in the background, see
+    /// [crate::Application::on_background_changed].
+    pub background: BackgroundBehavior,
+    /// Random input to soak test the application with, see [InputSoak].
+    pub input_soak: Option<InputSoak>,
+    /// Recorded input played in place of the window's from startup, exiting once every
+    /// frame has played, see [InputRecording].
+    pub input_playback: Option<InputRecording>,
+    /// File the input of the run is recorded to, written when the application exits.
+    pub input_recording: Option<PathBuf>,
+    /// Config file overriding these settings at startup if it exists, which is
+    /// reloaded as it changes in debug builds. Defaults to [CONFIG_FILE_PATH].
+    pub config_file: Option<PathBuf>,
+    /// Whether a [crate::graphics::LeakReport] is logged when the application exits,
+    /// as a warning if handles are still alive. Defaults to on in debug builds.
+    pub leak_report: bool,
+    /// Logger installed at startup, or `None` to leave logging to the application.
+    pub log: Option<LogConfig>,
+}
+
+impl WindowIcon {
+    /// Creates a [WindowIcon] from raw RGBA8 pixels.
+    pub fn from_rgba(rgba: Vec<u8>, size: UVec2) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            rgba.len() == (size.x * size.y * 4) as usize,
+            "expected {} bytes for a {}x{} icon, got {}",
+            size.x * size.y * 4,
+            size.x,
+            size.y,
+            rgba.len()
+        );
+        Ok(Self { rgba, size })
+    }
+
+    /// Creates a [WindowIcon] from the bytes of an image file, such as a png.
+    pub fn from_image_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let image = image::load_from_memory(bytes)?.to_rgba8();
+        let size = UVec2::new(image.width(), image.height());
+        Self::from_rgba(image.into_raw(), size)
+    }
+
+    pub(crate) fn to_winit(&self) -> Option<winit::window::Icon> {
+        winit::window::Icon::from_rgba(self.rgba.clone(), self.size.x, self.size.y).ok()
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Clockwork Engine".to_string(),
+            size: UVec2::new(800, 600),
+            min_size: None,
+            resizable: true,
+            fullscreen: Fullscreen::Windowed,
+            icon: None,
+            canvas_id: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// Creates a [WindowConfig] with default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the title of the window.
+    pub fn with_title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the initial inner size of the window in pixels.
+    pub fn with_size(mut self, size: UVec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets the smallest inner size the window can be resized to.
+    pub fn with_min_size(mut self, min_size: UVec2) -> Self {
+        self.min_size = Some(min_size);
+        self
+    }
+
+    /// Sets whether the window can be resized.
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Sets how the window occupies the screen.
+    pub fn with_fullscreen(mut self, fullscreen: Fullscreen) -> Self {
+        self.fullscreen = fullscreen;
+        self
+    }
+
+    /// Sets the icon of the window.
+    pub fn with_icon(mut self, icon: WindowIcon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Sets the id of the canvas element to render to on the web.
+    pub fn with_canvas_id<S: Into<String>>(mut self, canvas_id: S) -> Self {
+        self.canvas_id = Some(canvas_id.into());
+        self
+    }
+
+    /// Creates a winit window builder from this configuration.
+    pub(crate) fn to_window_builder(
+        &self,
+        event_loop: &winit::event_loop::EventLoop<()>,
+    ) -> winit::window::WindowBuilder {
+        let mut builder = winit::window::WindowBuilder::new()
+            .with_title(&self.title)
+            .with_inner_size(winit::dpi::PhysicalSize::new(self.size.x, self.size.y))
+            .with_resizable(self.resizable)
+            .with_fullscreen(self.fullscreen.to_winit(event_loop.primary_monitor()))
+            .with_window_icon(self.icon.as_ref().and_then(WindowIcon::to_winit));
+
+        if let Some(min_size) = self.min_size {
+            builder =
+                builder.with_min_inner_size(winit::dpi::PhysicalSize::new(min_size.x, min_size.y));
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::WindowBuilderExtWebSys;
+            builder =
+                builder.with_canvas(self.canvas_id.as_deref().and_then(crate::web::find_canvas));
+        }
+
+        builder
+    }
+}
+
+impl CursorGrab {
+    /// Converts to winit's cursor grab mode.
+    pub(crate) fn to_winit(self) -> winit::window::CursorGrabMode {
+        match self {
+            CursorGrab::None => winit::window::CursorGrabMode::None,
+            CursorGrab::Confined => winit::window::CursorGrabMode::Confined,
+            CursorGrab::Locked => winit::window::CursorGrabMode::Locked,
+        }
+    }
+}
+
+impl Fullscreen {
+    /// Converts to winit's fullscreen setting on the given monitor.
+    pub(crate) fn to_winit(
+        self,
+        monitor: Option<winit::monitor::MonitorHandle>,
+    ) -> Option<winit::window::Fullscreen> {
+        match self {
+            Fullscreen::Windowed => None,
+            Fullscreen::Borderless => Some(winit::window::Fullscreen::Borderless(monitor)),
+            Fullscreen::Exclusive => {
+                // Video modes aren't sorted, so pick the largest and fastest one.
+                let video_mode = monitor?.video_modes().max_by_key(|mode| {
+                    (
+                        mode.size().width * mode.size().height,
+                        mode.refresh_rate_millihertz(),
+                    )
+                })?;
+                Some(winit::window::Fullscreen::Exclusive(video_mode))
+            }
+        }
+    }
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            adapter: AdapterSelection::default(),
+            vsync: true,
+            output_format: OutputFormat::Srgb,
+            max_frames_in_flight: 2,
+            fixed_tick_rate: 60.0,
+            max_fps: None,
+            background: BackgroundBehavior::Run,
+            input_soak: None,
+            input_playback: None,
+            input_recording: None,
+            config_file: Some(PathBuf::from(CONFIG_FILE_PATH)),
+            leak_report: cfg!(debug_assertions),
+            log: None,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Creates an [EngineConfig] with default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the configuration of the window.
+    pub fn with_window(mut self, window: WindowConfig) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the adapter (GPU) to render with.
+    pub fn with_adapter(mut self, adapter: AdapterSelection) -> Self {
+        self.adapter = adapter;
+        self
+    }
+
+    /// Sets whether presenting waits for the display's vertical sync.
+    pub fn with_vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Sets the color encoding of the frames presented to the window.
+    pub fn with_output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    /// Sets the most frames that can be submitted before the cpu waits for the gpu.
+    pub fn with_max_frames_in_flight(mut self, max_frames_in_flight: u32) -> Self {
+        self.max_frames_in_flight = max_frames_in_flight;
+        self
+    }
+
+    /// Sets how many times per second [crate::Application::fixed_update] is called.
+    pub fn with_fixed_tick_rate(mut self, fixed_tick_rate: f64) -> Self {
+        self.fixed_tick_rate = fixed_tick_rate;
+        self
+    }
+
+    /// Sets the most frames rendered per second.
+    pub fn with_max_fps(mut self, max_fps: f64) -> Self {
+        self.max_fps = Some(max_fps);
+        self
+    }
+
+    /// Sets what the engine does while the window is in the background, such as
+    /// pausing instead of using the CPU and GPU for frames no one sees.
+    pub fn with_background(mut self, background: BackgroundBehavior) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Feeds seeded random input to the application alongside the window's, exiting
+    /// once the soak's frames have run.
+    pub fn with_input_soak(mut self, input_soak: InputSoak) -> Self {
+        self.input_soak = Some(input_soak);
+        self
+    }
+
+    /// Plays recorded input in place of the window's from startup, exiting once every
+    /// frame has played.
+    pub fn with_input_playback(mut self, recording: InputRecording) -> Self {
+        self.input_playback = Some(recording);
+        self
+    }
+
+    /// Records the input of the run to a file, written when the application exits.
+    pub fn with_input_recording(mut self, path: impl Into<PathBuf>) -> Self {
+        self.input_recording = Some(path.into());
+        self
+    }
+
+    /// Sets the config file read at startup, or `None` to not read one.
+    pub fn with_config_file(mut self, config_file: Option<PathBuf>) -> Self {
+        self.config_file = config_file;
+        self
+    }
+
+    /// Sets whether a leak report is logged when the application exits.
+    pub fn with_leak_report(mut self, leak_report: bool) -> Self {
+        self.leak_report = leak_report;
+        self
+    }
+
+    /// Installs a logger at startup, see [LogConfig].
+    pub fn with_log(mut self, log: LogConfig) -> Self {
+        self.log = Some(log);
+        self
+    }
+}
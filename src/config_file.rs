@@ -0,0 +1,318 @@
+// Settings are only reloaded while running in debug builds.
+#![cfg_attr(not(debug_assertions), allow(dead_code))]
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use glam::UVec2;
+use instant::Instant;
+use serde::Deserialize;
+
+use crate::{
+    config::{EngineConfig, Fullscreen},
+    engine::Engine,
+    graphics::{AdapterSelection, OutputFormat},
+};
+
+/// Path of the engine config file read at startup, relative to the working directory,
+/// see [EngineConfig::config_file].
+pub const CONFIG_FILE_PATH: &str = "clockwork.toml";
+
+/// How often the config file is checked for changes in debug builds.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings of an engine config file, such as:
+///
+/// ```toml
+/// [window]
+/// title = "My Game"
+/// size = [1280, 720]
+/// fullscreen = "borderless"
+///
+/// [graphics]
+/// adapter = { named = "NVIDIA GeForce RTX 3060" }
+/// vsync = false
+/// output_format = "hdr"
+///
+/// [timing]
+/// fixed_tick_rate = 30.0
+/// max_fps = 144.0
+/// ```
+///
+/// Settings left out keep the value the application configured.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ConfigFile {
+    window: WindowSection,
+    graphics: GraphicsSection,
+    timing: TimingSection,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WindowSection {
+    title: Option<String>,
+    size: Option<[u32; 2]>,
+    min_size: Option<[u32; 2]>,
+    resizable: Option<bool>,
+    fullscreen: Option<Fullscreen>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GraphicsSection {
+    adapter: Option<AdapterSelection>,
+    vsync: Option<bool>,
+    max_frames_in_flight: Option<u32>,
+    output_format: Option<OutputFormat>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TimingSection {
+    fixed_tick_rate: Option<f64>,
+    max_fps: Option<f64>,
+}
+
+impl ConfigFile {
+    /// Parses the contents of a config file.
+    pub(crate) fn parse(source: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(source)?)
+    }
+
+    /// Reads and parses a config file, or `None` if there isn't one.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        read_optional(path)?
+            .map(|source| {
+                Self::parse(&source).with_context(|| format!("invalid {}", path.display()))
+            })
+            .transpose()
+    }
+
+    /// Overrides the settings of a config with the ones in the file.
+    pub(crate) fn apply(&self, config: &mut EngineConfig) {
+        let window = &self.window;
+        if let Some(title) = &window.title {
+            config.window.title = title.clone();
+        }
+        if let Some(size) = window.size {
+            config.window.size = UVec2::from(size);
+        }
+        if let Some(min_size) = window.min_size {
+            config.window.min_size = Some(UVec2::from(min_size));
+        }
+        if let Some(resizable) = window.resizable {
+            config.window.resizable = resizable;
+        }
+        if let Some(fullscreen) = window.fullscreen {
+            config.window.fullscreen = fullscreen;
+        }
+
+        let graphics = &self.graphics;
+        if let Some(adapter) = &graphics.adapter {
+            config.adapter = adapter.clone();
+        }
+        if let Some(vsync) = graphics.vsync {
+            config.vsync = vsync;
+        }
+        if let Some(max_frames_in_flight) = graphics.max_frames_in_flight {
+            config.max_frames_in_flight = max_frames_in_flight;
+        }
+        if let Some(output_format) = graphics.output_format {
+            config.output_format = output_format;
+        }
+
+        if let Some(fixed_tick_rate) = self.timing.fixed_tick_rate {
+            config.fixed_tick_rate = fixed_tick_rate;
+        }
+        if let Some(max_fps) = self.timing.max_fps {
+            config.max_fps = Some(max_fps);
+        }
+    }
+
+    /// Applies the settings that changed since `previous` to a running engine.
+    ///
+    /// The adapter and output format are only read at startup, so changes to them wait
+    /// for a restart.
+    pub(crate) fn apply_changes(&self, previous: &ConfigFile, engine: &mut Engine) {
+        let window = &self.window;
+        if let Some(title) = changed(window.title.as_ref(), previous.window.title.as_ref()) {
+            engine.set_title(title);
+        }
+        if let Some(size) = changed(window.size, previous.window.size) {
+            engine.set_window_size(UVec2::from(size));
+        }
+        if window.min_size != previous.window.min_size {
+            engine.set_min_window_size(window.min_size.map(UVec2::from));
+        }
+        if let Some(resizable) = changed(window.resizable, previous.window.resizable) {
+            engine.set_resizable(resizable);
+        }
+        if let Some(fullscreen) = changed(window.fullscreen, previous.window.fullscreen) {
+            engine.set_fullscreen(fullscreen);
+        }
+
+        let graphics = &self.graphics;
+        if let Some(vsync) = changed(graphics.vsync, previous.graphics.vsync) {
+            engine.set_vsync(vsync);
+        }
+        if let Some(max_frames_in_flight) = changed(
+            graphics.max_frames_in_flight,
+            previous.graphics.max_frames_in_flight,
+        ) {
+            engine.set_max_frames_in_flight(max_frames_in_flight);
+        }
+
+        if let Some(fixed_tick_rate) =
+            changed(self.timing.fixed_tick_rate, previous.timing.fixed_tick_rate)
+        {
+            engine.set_fixed_tick_rate(fixed_tick_rate);
+        }
+        if self.timing.max_fps != previous.timing.max_fps {
+            engine.set_max_fps(self.timing.max_fps);
+        }
+    }
+}
+
+/// Gets a setting if it's different from before.
+fn changed<T: PartialEq>(current: Option<T>, previous: Option<T>) -> Option<T> {
+    match current != previous {
+        true => current,
+        false => None,
+    }
+}
+
+/// Reads a file to a string, or `None` if it doesn't exist or there is no file system
+/// to read it from, such as on the web.
+fn read_optional(path: &Path) -> anyhow::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => Ok(Some(source)),
+        Err(error)
+            if matches!(
+                error.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::Unsupported
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(error) => Err(error).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Watches a config file for changes to apply while the application runs.
+pub(crate) struct ConfigWatcher {
+    path: PathBuf,
+    /// Contents of the file as of the last check.
+    source: Option<String>,
+    /// Settings currently applied.
+    pub(crate) file: ConfigFile,
+    last_check: Instant,
+    interval: Duration,
+}
+
+impl ConfigWatcher {
+    /// Creates a [ConfigWatcher] for a file, with the settings read from it at startup.
+    pub(crate) fn new(path: PathBuf, file: ConfigFile) -> Self {
+        Self {
+            source: read_optional(&path).ok().flatten(),
+            path,
+            file,
+            last_check: Instant::now(),
+            interval: RELOAD_INTERVAL,
+        }
+    }
+
+    /// Checks whether the file changed since the last check, returning its new
+    /// settings if so. A deleted file has no settings.
+    pub(crate) fn poll(&mut self) -> Option<anyhow::Result<ConfigFile>> {
+        if self.last_check.elapsed() < self.interval {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let source = match read_optional(&self.path) {
+            Ok(source) => source,
+            Err(error) => return Some(Err(error)),
+        };
+        if source == self.source {
+            return None;
+        }
+        self.source = source;
+        Some(match &self.source {
+            Some(source) => ConfigFile::parse(source)
+                .with_context(|| format!("invalid {}", self.path.display())),
+            None => Ok(ConfigFile::default()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_config_file() {
+        let file = ConfigFile::parse(
+            r#"
+            [window]
+            title = "Soak"
+            size = [1280, 720]
+            fullscreen = "borderless"
+
+            [graphics]
+            adapter = "low_power"
+            output_format = "linear"
+
+            [timing]
+            max_fps = 144.0
+            "#,
+        )
+        .unwrap();
+        let mut config = EngineConfig::new().with_vsync(false);
+        file.apply(&mut config);
+        assert_eq!(config.window.title, "Soak");
+        assert_eq!(config.window.size, UVec2::new(1280, 720));
+        assert_eq!(config.window.fullscreen, Fullscreen::Borderless);
+        assert_eq!(config.adapter, AdapterSelection::LowPower);
+        assert_eq!(config.output_format, OutputFormat::Linear);
+        assert_eq!(config.max_fps, Some(144.0));
+        // Left out, so the application's setting stays.
+        assert!(!config.vsync);
+
+        let named = ConfigFile::parse("[graphics]\nadapter = { named = \"GPU\" }").unwrap();
+        assert_eq!(
+            named.graphics.adapter,
+            Some(AdapterSelection::Named("GPU".to_string()))
+        );
+
+        assert!(ConfigFile::parse("[graphics]\nvsinc = true").is_err());
+    }
+
+    #[test]
+    fn test_config_watcher() {
+        let path = std::env::temp_dir().join(format!(
+            "clockwork-config-watcher-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, "[graphics]\nvsync = true").unwrap();
+        let file = ConfigFile::load(&path).unwrap().unwrap();
+        let mut watcher = ConfigWatcher::new(path.clone(), file);
+        watcher.interval = Duration::ZERO;
+        assert!(watcher.poll().is_none());
+
+        std::fs::write(&path, "[graphics]\nvsync = false").unwrap();
+        let changed = watcher.poll().unwrap().unwrap();
+        assert_eq!(changed.graphics.vsync, Some(false));
+        assert!(watcher.poll().is_none());
+
+        std::fs::write(&path, "[graphics").unwrap();
+        assert!(watcher.poll().unwrap().is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.poll().unwrap().unwrap(), ConfigFile::default());
+        assert_eq!(ConfigFile::load(&path).unwrap(), None);
+    }
+}
@@ -0,0 +1,451 @@
+use std::{collections::VecDeque, ops::AddAssign, time::Duration};
+
+use crate::graphics::RenderStats;
+
+/// Number of recent frames frame times are measured over.
+const FRAME_HISTORY: usize = 120;
+
+/// Measurements of recent frames, see [crate::Engine::stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// Frames per second, averaged over recent frames.
+    pub fps: f64,
+    /// Average time between frames.
+    pub frame_time: Duration,
+    /// Median time between frames.
+    pub frame_time_p50: Duration,
+    /// Time between frames that 95% of recent frames were faster than.
+    pub frame_time_p95: Duration,
+    /// Time between frames that 99% of recent frames were faster than, which shows
+    /// occasional hitches the average hides.
+    pub frame_time_p99: Duration,
+    /// Slowest recent frame.
+    pub frame_time_max: Duration,
+    /// Work done by the renderer during the latest frame.
+    pub render: RenderStats,
+    /// Allocations made during the latest frame.
+    pub allocations: FrameAllocStats,
+}
+
+/// Heap allocations made during part of a frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// Number of allocations, counting reallocations as one each.
+    pub count: u64,
+    /// Bytes requested by the allocations.
+    pub bytes: u64,
+}
+
+/// Heap allocations made during the latest frame by phase of the engine, to catch
+/// regressions in per-frame allocation.
+///
+/// Allocations are only counted with the `alloc-stats` feature, which installs a
+/// counting global allocator. Without it every count is zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameAllocStats {
+    /// While handling window events between frames.
+    pub events: AllocStats,
+    /// During [crate::Application::fixed_update].
+    pub fixed_update: AllocStats,
+    /// During [crate::Application::update].
+    pub update: AllocStats,
+    /// Within [crate::graphics::RenderContext::render_pass], such as converting render
+    /// operations, which is also counted in the phase the passes were made in.
+    pub render: AllocStats,
+    /// Everything else the engine did during the frame, such as the debug UI.
+    pub engine: AllocStats,
+}
+
+impl AllocStats {
+    /// Gets the allocations made since the program started, or nothing without the
+    /// `alloc-stats` feature.
+    pub(crate) fn current() -> Self {
+        #[cfg(feature = "alloc-stats")]
+        return counting_allocator::current();
+        #[cfg(not(feature = "alloc-stats"))]
+        Self::default()
+    }
+
+    /// Gets the allocations made between an earlier measurement and this one.
+    pub(crate) fn since(self, earlier: AllocStats) -> Self {
+        Self {
+            count: self.count.saturating_sub(earlier.count),
+            bytes: self.bytes.saturating_sub(earlier.bytes),
+        }
+    }
+}
+
+impl AddAssign for AllocStats {
+    fn add_assign(&mut self, other: Self) {
+        self.count += other.count;
+        self.bytes += other.bytes;
+    }
+}
+
+impl FrameAllocStats {
+    /// Gets the allocations made across the whole frame.
+    pub fn total(&self) -> AllocStats {
+        let mut total = self.events;
+        total += self.fixed_update;
+        total += self.update;
+        total += self.engine;
+        total
+    }
+}
+
+/// Global allocator that counts allocations on top of the system allocator.
+#[cfg(feature = "alloc-stats")]
+mod counting_allocator {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    use super::AllocStats;
+
+    static COUNT: AtomicU64 = AtomicU64::new(0);
+    static BYTES: AtomicU64 = AtomicU64::new(0);
+
+    struct CountingAllocator;
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn count(bytes: usize) {
+        COUNT.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count(layout.size());
+            System.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            count(layout.size());
+            System.alloc_zeroed(layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count(new_size);
+            System.realloc(ptr, layout, new_size)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    pub(super) fn current() -> AllocStats {
+        AllocStats {
+            count: COUNT.load(Ordering::Relaxed),
+            bytes: BYTES.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Keeps the times of recent frames.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FrameTimer {
+    frame_times: VecDeque<Duration>,
+}
+
+impl FrameTimer {
+    /// Creates a new [FrameTimer].
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the time a frame took.
+    pub(crate) fn record(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /// Gets statistics of the recorded frames, with the given render stats.
+    pub(crate) fn stats(&self, render: RenderStats) -> FrameStats {
+        if self.frame_times.is_empty() {
+            return FrameStats {
+                render,
+                ..Default::default()
+            };
+        }
+
+        let mut sorted: Vec<Duration> = self.frame_times.iter().copied().collect();
+        sorted.sort();
+        let percentile = |percent: usize| {
+            // Nearest rank, so a percentile is always a frame that happened.
+            let rank = (sorted.len() * percent).div_ceil(100).max(1);
+            sorted[rank - 1]
+        };
+
+        let frame_time = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+        FrameStats {
+            fps: match frame_time.is_zero() {
+                true => 0.0,
+                false => 1.0 / frame_time.as_secs_f64(),
+            },
+            frame_time,
+            frame_time_p50: percentile(50),
+            frame_time_p95: percentile(95),
+            frame_time_p99: percentile(99),
+            frame_time_max: sorted[sorted.len() - 1],
+            render,
+            allocations: FrameAllocStats::default(),
+        }
+    }
+}
+
+/// Draws a small window of frame stats, such as for [crate::Engine::set_stats_overlay].
+#[cfg(feature = "ui")]
+pub fn stats_overlay(ctx: &crate::ui::egui::Context, stats: &FrameStats) {
+    use crate::ui::egui;
+
+    egui::Window::new("Frame stats")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+        .resizable(false)
+        .collapsible(true)
+        .show(ctx, |ui| stats_grid(ui, stats));
+}
+
+/// Draws a window listing the uniforms of every custom material, which can be
+/// edited live to tweak how the materials look, see [crate::Engine::set_material_panel].
+#[cfg(feature = "ui")]
+pub fn material_panel(
+    ctx: &crate::ui::egui::Context,
+    render_context: &mut crate::graphics::RenderContext,
+) {
+    use crate::ui::egui;
+
+    let materials: Vec<_> = render_context.material_names().collect();
+    egui::Window::new("Materials")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+        .collapsible(true)
+        .vscroll(true)
+        .show(ctx, |ui| {
+            if materials.is_empty() {
+                ui.label("No custom materials");
+            }
+            for (material_pipeline_id, name) in materials {
+                let label = match name {
+                    Some(name) => name.to_string(),
+                    None => format!("{material_pipeline_id:?}"),
+                };
+                ui.collapsing(label, |ui| {
+                    material_grid(ui, render_context, material_pipeline_id)
+                });
+            }
+        });
+}
+
+/// Lays out a material's uniforms as a grid of names and editable values.
+#[cfg(feature = "ui")]
+fn material_grid(
+    ui: &mut crate::ui::egui::Ui,
+    render_context: &mut crate::graphics::RenderContext,
+    material_pipeline_id: crate::util::repository::ResourceId<crate::graphics::MaterialPipeline>,
+) {
+    use crate::{graphics::UniformValue, ui::egui};
+
+    let fields = render_context
+        .material_uniform_fields(material_pipeline_id)
+        .to_vec();
+    egui::Grid::new(("clockwork material", material_pipeline_id)).show(ui, |ui| {
+        for field in fields {
+            ui.label(&field.name);
+            let Some(mut value) =
+                render_context.material_property(material_pipeline_id, &field.name)
+            else {
+                ui.weak("not editable");
+                ui.end_row();
+                continue;
+            };
+            let changed = match &mut value {
+                UniformValue::F32(value) => {
+                    ui.add(egui::DragValue::new(value).speed(0.01)).changed()
+                }
+                UniformValue::I32(value) => ui.add(egui::DragValue::new(value)).changed(),
+                UniformValue::U32(value) => ui.add(egui::DragValue::new(value)).changed(),
+                UniformValue::Vec3(value) if field.is_color() => {
+                    let mut rgb = value.to_array();
+                    let changed = ui.color_edit_button_rgb(&mut rgb).changed();
+                    *value = rgb.into();
+                    changed
+                }
+                UniformValue::Vec4(value) if field.is_color() => {
+                    let mut rgba = value.to_array();
+                    let changed = ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed();
+                    *value = rgba.into();
+                    changed
+                }
+                UniformValue::Vec2(value) => drag_components(ui, value.as_mut()),
+                UniformValue::Vec3(value) => drag_components(ui, value.as_mut()),
+                UniformValue::Vec4(value) => drag_components(ui, value.as_mut()),
+                UniformValue::Mat4(_) => {
+                    ui.weak("mat4");
+                    false
+                }
+            };
+            if changed {
+                if let Err(error) =
+                    render_context.set_material_property(material_pipeline_id, &field.name, value)
+                {
+                    log::warn!("{error}");
+                }
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// Shows a drag value for each component of a vector, returning whether any changed.
+#[cfg(feature = "ui")]
+fn drag_components(ui: &mut crate::ui::egui::Ui, components: &mut [f32]) -> bool {
+    ui.horizontal(|ui| {
+        let mut changed = false;
+        for component in components {
+            changed |= ui
+                .add(crate::ui::egui::DragValue::new(component).speed(0.01))
+                .changed();
+        }
+        changed
+    })
+    .inner
+}
+
+/// Lays out frame stats as a grid of labels and values.
+#[cfg(feature = "ui")]
+pub(crate) fn stats_grid(ui: &mut crate::ui::egui::Ui, stats: &FrameStats) {
+    use crate::ui::egui;
+
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    egui::Grid::new("clockwork frame stats").show(ui, |ui| {
+        let mut row = |label: &str, value: String| {
+            ui.label(label);
+            ui.monospace(value);
+            ui.end_row();
+        };
+        row("fps", format!("{:.1}", stats.fps));
+        row("frame", format!("{:.2} ms", millis(stats.frame_time)));
+        row("p95", format!("{:.2} ms", millis(stats.frame_time_p95)));
+        row("p99", format!("{:.2} ms", millis(stats.frame_time_p99)));
+        row("max", format!("{:.2} ms", millis(stats.frame_time_max)));
+        let allocations = stats.allocations.total();
+        if allocations.count > 0 {
+            let allocs = |allocs: AllocStats| {
+                format!("{} ({:.1} KiB)", allocs.count, allocs.bytes as f64 / 1024.0)
+            };
+            row("allocs", allocs(allocations));
+            row("  events", allocs(stats.allocations.events));
+            row("  fixed update", allocs(stats.allocations.fixed_update));
+            row("  update", allocs(stats.allocations.update));
+            row("  render", allocs(stats.allocations.render));
+            row("  engine", allocs(stats.allocations.engine));
+        }
+        row("draw calls", stats.render.draw_calls.to_string());
+        row("indirect draws", stats.render.indirect_draws.to_string());
+        row(
+            "uploads",
+            format!(
+                "{:.1} KiB",
+                stats.render.buffer_upload_bytes as f64 / 1024.0
+            ),
+        );
+        row("textures", stats.render.texture_count.to_string());
+        row("meshes", stats.render.mesh_count.to_string());
+        row(
+            "bind groups",
+            stats.render.cached_bind_group_count.to_string(),
+        );
+        row(
+            "local buffer",
+            format!("{:.1} KiB", stats.render.local_buffer_bytes as f64 / 1024.0),
+        );
+        row(
+            "mesh buffers",
+            format!(
+                "{:.1} MiB",
+                stats.render.mesh_buffer_bytes as f64 / (1024.0 * 1024.0)
+            ),
+        );
+        row(
+            "in flight",
+            format!(
+                "{} / {}",
+                stats.render.latency.frames_in_flight, stats.render.latency.max_frames_in_flight
+            ),
+        );
+        if !stats.render.latency.input_latency.is_zero() {
+            row(
+                "input latency",
+                format!(
+                    "{:.1} / {:.1} ms",
+                    millis(stats.render.latency.input_latency),
+                    millis(stats.render.latency.input_latency_max)
+                ),
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_time_percentiles() {
+        let mut timer = FrameTimer::new();
+        assert_eq!(timer.stats(RenderStats::default()).fps, 0.0);
+
+        for millis in 1..=100 {
+            timer.record(Duration::from_millis(millis));
+        }
+        let stats = timer.stats(RenderStats::default());
+        assert_eq!(stats.frame_time_p50, Duration::from_millis(50));
+        assert_eq!(stats.frame_time_p99, Duration::from_millis(99));
+        assert_eq!(stats.frame_time_max, Duration::from_millis(100));
+        assert_eq!(stats.frame_time, Duration::from_micros(50_500));
+    }
+
+    #[test]
+    fn test_alloc_stats() {
+        let earlier = AllocStats {
+            count: 3,
+            bytes: 100,
+        };
+        let later = AllocStats {
+            count: 5,
+            bytes: 164,
+        };
+        assert_eq!(
+            later.since(earlier),
+            AllocStats {
+                count: 2,
+                bytes: 64
+            }
+        );
+
+        let start = AllocStats::current();
+        let allocation = vec![0u8; 256];
+        let allocated = AllocStats::current().since(start);
+        drop(allocation);
+        match cfg!(feature = "alloc-stats") {
+            true => assert!(allocated.count >= 1 && allocated.bytes >= 256),
+            false => assert_eq!(allocated, AllocStats::default()),
+        }
+    }
+
+    #[test]
+    fn test_frame_history_is_bounded() {
+        let mut timer = FrameTimer::new();
+        for _ in 0..FRAME_HISTORY {
+            timer.record(Duration::from_millis(100));
+        }
+        timer.record(Duration::from_millis(10));
+        assert_eq!(timer.frame_times.len(), FRAME_HISTORY);
+        assert_eq!(timer.frame_times.back(), Some(&Duration::from_millis(10)));
+    }
+}
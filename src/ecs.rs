@@ -0,0 +1,383 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+use anyhow::Result;
+
+/// Something in a [World], made up of the components inserted for it.
+///
+/// Like resources in a [crate::util::repository::Repository], entities carry a
+/// generation, so an entity that was despawned never refers to one spawned in its slot
+/// later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    pub index: u32,
+    pub generation: u32,
+}
+
+/// Stores entities and their components, such as for the objects of a level.
+///
+/// Components are any `'static` type, stored per type, so each entity has at most one
+/// component of each type.
+#[derive(Default)]
+pub struct World {
+    /// Generation of each slot, and whether an entity currently lives in it.
+    slots: Vec<(u32, bool)>,
+    /// Slots of despawned entities, reused before new slots are added.
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn ComponentStorage>>,
+}
+
+/// Components of a single type, indexed by entity slot.
+struct Storage<T>(Vec<Option<T>>);
+
+/// Operations on a [Storage] that don't need its component type.
+trait ComponentStorage {
+    fn remove_slot(&mut self, index: usize);
+    fn has_slot(&self, index: usize) -> bool;
+    fn type_name(&self) -> &'static str;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> ComponentStorage for Storage<T> {
+    fn remove_slot(&mut self, index: usize) {
+        if let Some(component) = self.0.get_mut(index) {
+            *component = None;
+        }
+    }
+
+    fn has_slot(&self, index: usize) -> bool {
+        self.0.get(index).is_some_and(Option::is_some)
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl World {
+    /// Creates a new empty [World].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an entity without components.
+    pub fn spawn(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.1 = true;
+                Entity {
+                    index,
+                    generation: slot.0,
+                }
+            }
+            None => {
+                self.slots.push((0, true));
+                Entity {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Removes an entity along with its components. Returns whether it was alive.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        let slot = &mut self.slots[entity.index as usize];
+        slot.0 = slot.0.wrapping_add(1);
+        slot.1 = false;
+        self.free.push(entity.index);
+        for storage in self.storages.values_mut() {
+            storage.remove_slot(entity.index as usize);
+        }
+        true
+    }
+
+    /// Checks if an entity exists.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.slots.get(entity.index as usize) == Some(&(entity.generation, true))
+    }
+
+    /// Gets the number of entities.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    /// Checks whether there are no entities.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets every entity.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, alive))| *alive)
+            .map(|(index, (generation, _))| Entity {
+                index: index as u32,
+                generation: *generation,
+            })
+    }
+
+    /// Gives an entity a component, replacing its component of the same type.
+    ///
+    /// Returns an error if the entity was despawned.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Result<()> {
+        anyhow::ensure!(self.is_alive(entity), "no entity {entity:?}");
+        let components = &mut self.storage_mut::<T>().0;
+        let index = entity.index as usize;
+        if index >= components.len() {
+            components.resize_with(index + 1, || None);
+        }
+        components[index] = Some(component);
+        Ok(())
+    }
+
+    /// Takes a component away from an entity, returning it if it had one.
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storage_mut::<T>()
+            .0
+            .get_mut(entity.index as usize)?
+            .take()
+    }
+
+    /// Gets a component of an entity.
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storage::<T>()?.0.get(entity.index as usize)?.as_ref()
+    }
+
+    /// Gets a component of an entity mutably.
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storage_mut::<T>()
+            .0
+            .get_mut(entity.index as usize)?
+            .as_mut()
+    }
+
+    /// Gets the type names of an entity's components, such as for inspecting it in a
+    /// debug UI.
+    pub fn component_names(&self, entity: Entity) -> Vec<&'static str> {
+        if !self.is_alive(entity) {
+            return Vec::new();
+        }
+        let mut names: Vec<&'static str> = self
+            .storages
+            .values()
+            .filter(|storage| storage.has_slot(entity.index as usize))
+            .map(|storage| storage.type_name())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Gets every entity with a component of type `A`, along with the component.
+    pub fn query<A: 'static>(&self) -> impl Iterator<Item = (Entity, &A)> {
+        let components = self.storage::<A>().map_or(&[][..], |storage| &storage.0);
+        components
+            .iter()
+            .enumerate()
+            .filter_map(|(index, component)| Some((self.entity_at(index), component.as_ref()?)))
+    }
+
+    /// Gets every entity with a component of type `A`, along with the component to
+    /// change.
+    pub fn query_mut<A: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut A)> {
+        let slots = &self.slots;
+        let components = self
+            .storages
+            .get_mut(&TypeId::of::<A>())
+            .map_or(&mut [][..], |storage| {
+                downcast_mut::<A>(storage).0.as_mut_slice()
+            });
+        components
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, component)| Some((entity_at(slots, index), component.as_mut()?)))
+    }
+
+    /// Gets every entity with components of both type `A` and `B`, along with the
+    /// components.
+    pub fn query2<A: 'static, B: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B)> {
+        let b = self.storage::<B>().map_or(&[][..], |storage| &storage.0);
+        self.query::<A>()
+            .filter_map(|(entity, a)| Some((entity, a, b.get(entity.index as usize)?.as_ref()?)))
+    }
+
+    /// Gets every entity with components of both type `A` and `B`, along with the
+    /// components to change, such as a position and a velocity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `A` and `B` are the same type, since a component can't be borrowed
+    /// mutably twice.
+    pub fn query2_mut<A: 'static, B: 'static>(
+        &mut self,
+    ) -> impl Iterator<Item = (Entity, &mut A, &mut B)> {
+        assert!(
+            TypeId::of::<A>() != TypeId::of::<B>(),
+            "query2_mut needs two different component types"
+        );
+        let slots = &self.slots;
+        let [a, b] = self
+            .storages
+            .get_disjoint_mut([&TypeId::of::<A>(), &TypeId::of::<B>()]);
+        let a = a.map_or(&mut [][..], |storage| {
+            downcast_mut::<A>(storage).0.as_mut_slice()
+        });
+        let b = b.map_or(&mut [][..], |storage| {
+            downcast_mut::<B>(storage).0.as_mut_slice()
+        });
+        a.iter_mut()
+            .zip(b.iter_mut())
+            .enumerate()
+            .filter_map(|(index, (a, b))| Some((entity_at(slots, index), a.as_mut()?, b.as_mut()?)))
+    }
+
+    /// Gets the storage of a component type, if one was ever inserted.
+    fn storage<T: 'static>(&self) -> Option<&Storage<T>> {
+        let storage = self.storages.get(&TypeId::of::<T>())?;
+        Some(
+            storage
+                .as_any()
+                .downcast_ref()
+                .expect("storage should match its type id"),
+        )
+    }
+
+    /// Gets the storage of a component type, creating it if needed.
+    fn storage_mut<T: 'static>(&mut self) -> &mut Storage<T> {
+        let storage = self
+            .storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Storage::<T>(Vec::new())));
+        downcast_mut(storage)
+    }
+
+    fn entity_at(&self, index: usize) -> Entity {
+        entity_at(&self.slots, index)
+    }
+}
+
+/// Gets the entity living in a slot. Components are removed on despawn, so any slot
+/// holding a component is alive.
+fn entity_at(slots: &[(u32, bool)], index: usize) -> Entity {
+    Entity {
+        index: index as u32,
+        generation: slots[index].0,
+    }
+}
+
+fn downcast_mut<T: 'static>(storage: &mut Box<dyn ComponentStorage>) -> &mut Storage<T> {
+    storage
+        .as_any_mut()
+        .downcast_mut()
+        .expect("storage should match its type id")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f32);
+
+    #[derive(Debug, PartialEq)]
+    struct Velocity(f32);
+
+    #[test]
+    fn test_despawned_entities_are_not_reused() {
+        let mut world = World::new();
+        let first = world.spawn();
+        world.insert(first, Position(1.0)).unwrap();
+        assert!(world.despawn(first));
+        assert!(!world.despawn(first));
+
+        let second = world.spawn();
+        assert_eq!(second.index, first.index);
+        assert_ne!(second, first);
+        assert!(world.get::<Position>(second).is_none());
+        assert!(world.get::<Position>(first).is_none());
+        assert!(world.insert(first, Position(2.0)).is_err());
+        assert_eq!(world.len(), 1);
+    }
+
+    #[test]
+    fn test_components() {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Position(1.0)).unwrap();
+        world.insert(entity, Position(2.0)).unwrap();
+        assert_eq!(world.get::<Position>(entity), Some(&Position(2.0)));
+        assert!(world.get::<Velocity>(entity).is_none());
+
+        world.insert(entity, Velocity(1.0)).unwrap();
+        assert_eq!(
+            world.component_names(entity),
+            vec![
+                std::any::type_name::<Position>(),
+                std::any::type_name::<Velocity>()
+            ]
+        );
+        world.remove::<Velocity>(entity);
+
+        world.get_mut::<Position>(entity).unwrap().0 = 3.0;
+        assert_eq!(world.remove::<Position>(entity), Some(Position(3.0)));
+        assert!(world.get::<Position>(entity).is_none());
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut world = World::new();
+        let moving = world.spawn();
+        world.insert(moving, Position(0.0)).unwrap();
+        world.insert(moving, Velocity(2.0)).unwrap();
+        let still = world.spawn();
+        world.insert(still, Position(5.0)).unwrap();
+        let ghost = world.spawn();
+        world.insert(ghost, Velocity(1.0)).unwrap();
+
+        for (_, position, velocity) in world.query2_mut::<Position, Velocity>() {
+            position.0 += velocity.0;
+        }
+        for (_, position) in world.query_mut::<Position>() {
+            position.0 *= 10.0;
+        }
+
+        let positions: Vec<(Entity, &Position)> = world.query::<Position>().collect();
+        assert_eq!(
+            positions,
+            vec![(moving, &Position(20.0)), (still, &Position(50.0))]
+        );
+        let both: Vec<Entity> = world
+            .query2::<Position, Velocity>()
+            .map(|(entity, _, _)| entity)
+            .collect();
+        assert_eq!(both, vec![moving]);
+        assert_eq!(world.query::<String>().count(), 0);
+    }
+}
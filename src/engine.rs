@@ -1,102 +1,936 @@
+use std::path::PathBuf;
+
+use instant::Instant;
+
 use crate::{
-    graphics::RenderContext,
+    config::{BackgroundBehavior, CursorGrab, EngineConfig, Fullscreen, WindowIcon},
+    config_file::ConfigFile,
+    diagnostics::{AllocStats, FrameAllocStats, FrameStats, FrameTimer},
+    error::ClockworkError,
+    graphics::{AdapterInfo, AdapterSelection, PresentMode, RenderContext},
     input::InputState,
-    input::{Keyboard, Mouse},
+    input::{
+        replay::{InputPlayer, InputRecording},
+        soak::InputFuzzer,
+        window_events, VirtualControls,
+    },
+    logging::{report_error, span},
+    timestep::{simulate, FixedTimestep, FrameLimiter, SimulationReport},
+    util::time::GameClock,
 };
+use winit::event_loop::ControlFlow;
 
 pub struct Engine {
     pub window: winit::window::Window,
     pub graphics_context: RenderContext,
     pub input_state: InputState,
+    /// On-screen touch controls, disabled by default.
+    pub virtual_controls: VirtualControls,
+    fixed_timestep: FixedTimestep,
+    /// See [Engine::time].
+    clock: GameClock,
+    /// See [Engine::set_max_fps].
+    frame_limiter: FrameLimiter,
+    frame_timer: FrameTimer,
+    /// Allocations made during the latest frame, see [FrameStats::allocations].
+    frame_allocs: FrameAllocStats,
+    /// Grab last set with [Engine::set_cursor_grab], restored when the window regains
+    /// focus.
+    cursor_grab: CursorGrab,
+    /// Whether the window has focus, as mouse motion is only counted while it does.
+    focused: bool,
+    /// Whether the window is hidden behind other windows.
+    occluded: bool,
+    /// Whether the window is in the background, see [Engine::is_in_background].
+    in_background: bool,
+    /// See [Engine::set_background_behavior].
+    background: BackgroundBehavior,
+    /// When the next frame may start while throttled in the background.
+    next_background_frame: Option<Instant>,
+    /// See [EngineConfig::leak_report].
+    leak_report: bool,
+    /// Whether input latency is measured, see [Engine::set_input_latency_probe].
+    input_latency_probe: bool,
+    /// Recording the input of each frame is added to, see
+    /// [Engine::start_input_recording].
+    input_recording: Option<InputRecording>,
+    /// Recording played in place of the window's input, see
+    /// [Engine::play_input_recording].
+    input_player: Option<InputPlayer>,
+    #[cfg(feature = "ui")]
+    ui_input: crate::ui::UiInput,
+    /// Whether frame stats are drawn over the debug UI.
+    #[cfg(feature = "ui")]
+    stats_overlay: bool,
+    /// Whether custom material uniforms can be edited in the debug UI.
+    #[cfg(feature = "ui")]
+    material_panel: bool,
+    /// Docked engine panels, see [Engine::set_debug_workspace].
+    #[cfg(feature = "ui")]
+    debug_workspace: Option<crate::ui::DebugWorkspace>,
+    /// Reloads [EngineConfig::config_file] as it changes.
+    #[cfg(debug_assertions)]
+    config_watcher: Option<crate::config_file::ConfigWatcher>,
+}
+
+impl Engine {
+    /// Sets the title of the window.
+    pub fn set_title(&self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    /// Sets the inner size of the window in pixels.
+    pub fn set_window_size(&self, size: glam::UVec2) {
+        self.window
+            .set_inner_size(winit::dpi::PhysicalSize::new(size.x, size.y));
+    }
+
+    /// Sets the smallest inner size the window can be resized to.
+    pub fn set_min_window_size(&self, min_size: Option<glam::UVec2>) {
+        self.window.set_min_inner_size(
+            min_size.map(|min_size| winit::dpi::PhysicalSize::new(min_size.x, min_size.y)),
+        );
+    }
+
+    /// Sets whether the window can be resized.
+    pub fn set_resizable(&self, resizable: bool) {
+        self.window.set_resizable(resizable);
+    }
+
+    /// Sets how the window occupies the screen, using the monitor the window is on.
+    pub fn set_fullscreen(&self, fullscreen: Fullscreen) {
+        self.window
+            .set_fullscreen(fullscreen.to_winit(self.window.current_monitor()));
+    }
+
+    /// Sets the icon of the window.
+    pub fn set_window_icon(&self, icon: Option<&WindowIcon>) {
+        self.window
+            .set_window_icon(icon.and_then(WindowIcon::to_winit));
+    }
+
+    /// Gets measurements of recent frames, such as frame times and draw calls, for
+    /// diagnosing slow frames.
+    pub fn stats(&self) -> FrameStats {
+        FrameStats {
+            allocations: self.frame_allocs,
+            ..self.frame_timer.stats(self.graphics_context.render_stats())
+        }
+    }
+
+    /// Sets whether the time from input being received to the first frame reflecting
+    /// it finishing on the gpu is measured, reported as
+    /// [crate::graphics::FrameLatencyStats::input_latency] in [Engine::stats] and the
+    /// stats overlay.
+    ///
+    /// Presentation isn't reported back by the platform, so the gpu finishing the frame
+    /// stands in for it, which leaves out the compositor and display.
+    pub fn set_input_latency_probe(&mut self, enabled: bool) {
+        self.input_latency_probe = enabled;
+    }
+
+    /// Sets whether [Engine::stats] are drawn in a corner of the debug UI.
+    #[cfg(feature = "ui")]
+    pub fn set_stats_overlay(&mut self, enabled: bool) {
+        self.stats_overlay = enabled;
+    }
+
+    /// Sets whether a window for tweaking the uniforms of custom materials live is
+    /// drawn in the debug UI, see [crate::diagnostics::material_panel].
+    #[cfg(feature = "ui")]
+    pub fn set_material_panel(&mut self, enabled: bool) {
+        self.material_panel = enabled;
+    }
+
+    /// Sets whether a [crate::ui::DebugWorkspace] of engine panels is docked around
+    /// the edges of the screen. Disabling it clears its console and inspector.
+    #[cfg(feature = "ui")]
+    pub fn set_debug_workspace(&mut self, enabled: bool) {
+        match enabled {
+            true => {
+                self.debug_workspace
+                    .get_or_insert_with(crate::ui::DebugWorkspace::new);
+            }
+            false => self.debug_workspace = None,
+        }
+    }
+
+    /// Gets the debug workspace if it's enabled, for logging to its console or
+    /// inspecting a world.
+    #[cfg(feature = "ui")]
+    pub fn debug_workspace_mut(&mut self) -> Option<&mut crate::ui::DebugWorkspace> {
+        self.debug_workspace.as_mut()
+    }
+
+    /// Gets the egui context of the debug UI, for drawing panels during
+    /// [Application::update]. They are drawn over everything else when the frame ends.
+    ///
+    /// Check [egui::Context::wants_pointer_input] and
+    /// [egui::Context::wants_keyboard_input] to ignore input the UI is using.
+    #[cfg(feature = "ui")]
+    pub fn ui_ctx(&self) -> &crate::ui::egui::Context {
+        self.graphics_context.ui_context()
+    }
+
+    /// Sets whether input methods (IME) can be used to type, such as for languages with
+    /// more characters than keys. Enable this while a text box is focused.
+    ///
+    /// Typed text is available from [InputState::typed_text] either way.
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        self.window.set_ime_allowed(allowed);
+    }
+
+    /// Sets where the input method's candidate window appears, in pixels from the top
+    /// left of the window, such as just below the focused text box.
+    pub fn set_ime_position(&self, position: glam::Vec2) {
+        self.window
+            .set_ime_position(winit::dpi::PhysicalPosition::new(position.x, position.y));
+    }
+
+    /// Sets how the cursor is kept within the window, such as locking it in place for
+    /// mouse look, with relative motion read from [InputState::mouse_motion].
+    pub fn set_cursor_grab(&mut self, grab: CursorGrab) -> anyhow::Result<()> {
+        self.cursor_grab = grab;
+        self.apply_cursor_grab()
+    }
+
+    /// Sets whether the cursor is visible while it's over the window.
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.window.set_cursor_visible(visible);
+    }
+
+    /// Moves the cursor to a position in pixels from the top left of the window.
+    pub fn set_cursor_position(&self, position: glam::Vec2) -> anyhow::Result<()> {
+        self.window
+            .set_cursor_position(winit::dpi::PhysicalPosition::new(position.x, position.y))?;
+        Ok(())
+    }
+
+    /// Gets the coordinate of the tile under the cursor as seen through a camera, or
+    /// `None` if the cursor is outside the window or not over the map.
+    pub fn cursor_tile(
+        &self,
+        camera: &crate::util::camera::Camera,
+        tilemap: &crate::util::tilemap::Tilemap,
+    ) -> Option<glam::UVec2> {
+        let position = self.input_state.mouse_position()?;
+        let size = self.window.inner_size();
+        tilemap.coordinate_under(
+            camera,
+            crate::util::coordinates::ScreenPosition(position),
+            glam::uvec2(size.width, size.height).as_vec2(),
+        )
+    }
+
+    fn apply_cursor_grab(&self) -> anyhow::Result<()> {
+        let result = self.window.set_cursor_grab(self.cursor_grab.to_winit());
+        match (result, self.cursor_grab) {
+            // Some platforms can only confine the cursor.
+            (Err(_), CursorGrab::Locked) => self
+                .window
+                .set_cursor_grab(winit::window::CursorGrabMode::Confined)?,
+            (result, _) => result?,
+        }
+        Ok(())
+    }
+
+    /// Sets whether presenting waits for the display's vertical sync.
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.graphics_context.set_vsync(vsync);
+    }
+
+    /// Sets how frames are presented, see [RenderContext::set_present_mode].
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) -> anyhow::Result<()> {
+        self.graphics_context.set_present_mode(present_mode)
+    }
+
+    /// Sets the most frames rendered per second, or `None` for no limit, such as to
+    /// keep the frame rate steady while vsync is off.
+    pub fn set_max_fps(&mut self, max_fps: Option<f64>) {
+        self.frame_limiter.set_max_fps(max_fps);
+    }
+
+    /// Sets the frames rendered per second, or `None` to render as fast as vsync
+    /// allows. Frames are paced by sleeping then spinning until each is due, so the
+    /// frame rate stays steady, such as for benchmarks or to keep laptops from
+    /// rendering thousands of frames per second with vsync off.
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.set_max_fps(target_fps.map(f64::from));
+    }
+
+    /// Gets the most frames rendered per second, or `None` if there is no limit.
+    pub fn max_fps(&self) -> Option<f64> {
+        self.frame_limiter.max_fps()
+    }
+
+    /// Sets what the engine does while the window is in the background.
+    pub fn set_background_behavior(&mut self, background: BackgroundBehavior) {
+        self.background = background;
+        self.next_background_frame = None;
+    }
+
+    /// Gets what the engine does while the window is in the background.
+    pub fn background_behavior(&self) -> BackgroundBehavior {
+        self.background
+    }
+
+    /// Checks if the window is in the background, having lost focus, been minimized, or
+    /// been hidden behind other windows.
+    pub fn is_in_background(&self) -> bool {
+        self.in_background
+    }
+
+    /// Updates whether the window is in the background, returning whether it changed.
+    fn update_background(&mut self) -> bool {
+        let in_background = !self.focused || self.occluded || self.graphics_context.is_minimized();
+        let changed = in_background != self.in_background;
+        if changed {
+            self.in_background = in_background;
+            self.next_background_frame = None;
+        }
+        changed
+    }
+
+    /// Gets how the event loop should wait instead of running a frame at `now`, or
+    /// `None` if the frame should run.
+    fn background_wait(&mut self, now: Instant) -> Option<ControlFlow> {
+        if !self.in_background {
+            return None;
+        }
+        match self.background {
+            BackgroundBehavior::Run => None,
+            BackgroundBehavior::Pause => Some(ControlFlow::Wait),
+            BackgroundBehavior::Throttle(max_fps) => {
+                match self.next_background_frame.filter(|next| *next > now) {
+                    Some(next) => Some(ControlFlow::WaitUntil(next)),
+                    None => {
+                        let frame_length =
+                            std::time::Duration::from_secs_f64(1.0 / max_fps.max(f64::EPSILON));
+                        self.next_background_frame = Some(now + frame_length);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets the most frames that can be submitted before the cpu waits for the gpu,
+    /// trading throughput for lower input latency.
+    pub fn set_max_frames_in_flight(&mut self, max_frames_in_flight: u32) {
+        self.graphics_context
+            .set_max_frames_in_flight(max_frames_in_flight);
+    }
+
+    /// Applies changes to the config file since it was last checked, returning whether
+    /// it could be read if it changed.
+    #[cfg(debug_assertions)]
+    fn reload_config_file(&mut self) -> Option<anyhow::Result<()>> {
+        let mut config_watcher = self.config_watcher.take()?;
+        let result = config_watcher.poll().map(|file| {
+            let file = file?;
+            file.apply_changes(&config_watcher.file, self);
+            config_watcher.file = file;
+            Ok(())
+        });
+        self.config_watcher = Some(config_watcher);
+        result
+    }
+
+    /// Signals the input of a window event, from the window or an input soak.
+    fn handle_input_event(&mut self, event: &winit::event::WindowEvent) {
+        let size = self.window.inner_size();
+        window_events::handle_window_event(
+            event,
+            glam::uvec2(size.width, size.height).as_vec2(),
+            &mut self.input_state,
+            &mut self.virtual_controls,
+        );
+    }
+
+    /// Starts recording the input of each frame, replacing any recording in progress.
+    pub fn start_input_recording(&mut self) {
+        self.input_recording = Some(InputRecording::new());
+    }
+
+    /// Stops recording input, returning the frames recorded since
+    /// [Engine::start_input_recording], or `None` if nothing was being recorded.
+    pub fn stop_input_recording(&mut self) -> Option<InputRecording> {
+        self.input_recording.take()
+    }
+
+    /// Plays recorded input in place of the window's, starting next frame. Each frame
+    /// runs with the delta it was recorded with, so fixed updates tick the same way.
+    pub fn play_input_recording(&mut self, recording: InputRecording) {
+        self.input_player = Some(InputPlayer::new(recording));
+    }
+
+    /// Whether recorded input is playing in place of the window's.
+    pub fn is_playing_input(&self) -> bool {
+        self.input_player.is_some()
+    }
+
+    /// Gets the input received from the window this frame in the order it happened,
+    /// see [InputState::events].
+    pub fn input_events(&self) -> &[crate::input::InputEvent] {
+        self.input_state.events()
+    }
+
+    /// Gets the game time, including the elapsed time, time scale and whether it's
+    /// paused.
+    pub fn time(&self) -> &GameClock {
+        &self.clock
+    }
+
+    /// Gets the game time to change its time scale or pause it.
+    pub fn time_mut(&mut self) -> &mut GameClock {
+        &mut self.clock
+    }
+
+    /// Sets how many times per second [Application::fixed_update] is called.
+    pub fn set_fixed_tick_rate(&mut self, tick_rate: f64) {
+        self.fixed_timestep.set_tick_rate(tick_rate);
+    }
+
+    /// Gets the time in seconds between calls to [Application::fixed_update].
+    pub fn fixed_delta(&self) -> f64 {
+        self.fixed_timestep.tick_length()
+    }
+
+    /// Runs [Application::fixed_update] back to back for a number of ticks without
+    /// rendering, such as to skip ahead in a balancing simulation, and reports how
+    /// fast it went.
+    ///
+    /// Call it from [Application::update] with the application itself. See
+    /// [crate::simulate] to run a simulation without an engine.
+    pub fn fast_forward<App: Application>(
+        &mut self,
+        app: &mut App,
+        ticks: u64,
+    ) -> SimulationReport {
+        let tick_rate = 1.0 / self.fixed_delta();
+        simulate(ticks, tick_rate, |delta| app.fixed_update(self, delta))
+    }
 }
 
 pub trait Application: 'static {
+    /// Called before the [Engine] is created to choose which adapter (GPU) to render
+    /// with, given every adapter that can render to the window.
+    ///
+    /// Returns `None` to render with [EngineConfig::adapter], which the engine config
+    /// file can set.
+    #[allow(unused_variables)]
+    fn select_adapter(adapters: &[AdapterInfo]) -> Option<AdapterSelection> {
+        None
+    }
+
     /// Called to create the application with the [Engine].
     fn init(engine: &mut Engine) -> Self;
 
-    /// Called right before a frame renders.
-    fn update(&mut self, engine: &mut Engine, delta: f64);
+    /// Called at a fixed rate, set by [EngineConfig::fixed_tick_rate], with the
+    /// fixed time step `delta` in seconds. Game logic that should be deterministic,
+    /// such as physics, belongs here.
+    ///
+    /// Runs zero or more times before each [Application::update], following game time,
+    /// so it runs less often in slow motion and not at all while paused, see
+    /// [Engine::time].
+    #[allow(unused_variables)]
+    fn fixed_update(&mut self, engine: &mut Engine, delta: f64) {}
+
+    /// Called right before a frame renders, with the seconds of game time since the
+    /// last frame, see [Engine::time].
+    ///
+    /// `alpha` is how far the current time is between the last fixed update and the
+    /// next one, from 0 to 1, so rendering can blend between the last two fixed states.
+    fn update(&mut self, engine: &mut Engine, delta: f64, alpha: f64);
 
     /// Called whenever the application window is resized.
     #[allow(unused_variables)]
     fn on_window_resize(&mut self, engine: &mut Engine, new_size: glam::UVec2) {}
+
+    /// Called when the application window is minimized, while minimized nothing
+    /// is rendered.
+    #[allow(unused_variables)]
+    fn on_minimized(&mut self, engine: &mut Engine) {}
+
+    /// Called when the application window is restored after being minimized.
+    #[allow(unused_variables)]
+    fn on_restored(&mut self, engine: &mut Engine) {}
+
+    /// Called when the application is suspended, such as when a mobile app goes to the
+    /// background. The surface is torn down and nothing is rendered until
+    /// [Application::on_resumed].
+    #[allow(unused_variables)]
+    fn on_suspended(&mut self, engine: &mut Engine) {}
+
+    /// Called when the application resumes after [Application::on_suspended], once the
+    /// surface has been recreated.
+    #[allow(unused_variables)]
+    fn on_resumed(&mut self, engine: &mut Engine) {}
+
+    /// Called when the application window gains or loses focus, such as to pause the
+    /// game while the player is in another window.
+    #[allow(unused_variables)]
+    fn on_focus_changed(&mut self, engine: &mut Engine, focused: bool) {}
+
+    /// Called when the application window goes into or out of the background, having
+    /// lost focus, been minimized, or been hidden behind other windows, such as to mute
+    /// audio. See [crate::EngineConfig::with_background] for pausing the engine.
+    #[allow(unused_variables)]
+    fn on_background_changed(&mut self, engine: &mut Engine, in_background: bool) {}
+
+    /// Called when the application window is moved, with the new position of its top
+    /// left corner in pixels.
+    #[allow(unused_variables)]
+    fn on_window_moved(&mut self, engine: &mut Engine, position: glam::IVec2) {}
+
+    /// Called when the window's scale factor (DPI) changes, such as when it's moved to
+    /// another monitor.
+    #[allow(unused_variables)]
+    fn on_scale_factor_changed(&mut self, engine: &mut Engine, scale_factor: f64) {}
+
+    /// Called when a file is dragged and dropped onto the application window.
+    #[allow(unused_variables)]
+    fn on_file_dropped(&mut self, engine: &mut Engine, path: PathBuf) {}
+
+    /// Called in debug builds after [EngineConfig::config_file] changes and its
+    /// settings are applied, or with the error if it couldn't be read.
+    #[allow(unused_variables)]
+    fn on_config_reloaded(&mut self, engine: &mut Engine, result: anyhow::Result<()>) {}
+
+    /// Called once the graphics device was lost, such as from a driver restart, and a
+    /// new one was opened with every resource uploaded to it again, see
+    /// [crate::graphics::RenderContext::is_device_lost]. Render targets come back
+    /// cleared, so anything drawn to them once, such as a captured environment map,
+    /// should be drawn again.
+    #[allow(unused_variables)]
+    fn on_device_restored(&mut self, engine: &mut Engine) {}
+
+    /// Called with each error the gpu reported during the frame, such as a resource
+    /// used in a way it wasn't created for, which is reported by default. The app keeps
+    /// running either way.
+    #[allow(unused_variables)]
+    fn on_gpu_error(&mut self, engine: &mut Engine, error: ClockworkError) {
+        report_error(error);
+    }
 }
 
 /// Instantiate an [Engine] that runs a Clockwork [Application].
-pub fn run<App: Application>() {
+///
+/// Only returns if the engine fails to start, such as when no GPU can render to the
+/// window. On the web it returns right away, and the engine starts once a GPU is
+/// found, logging to the browser console if it fails to.
+pub fn run<App: Application>() -> Result<(), ClockworkError> {
+    run_with_config::<App>(EngineConfig::default())
+}
+
+/// Instantiate an [Engine] with the given [EngineConfig] that runs a Clockwork
+/// [Application].
+///
+/// Only returns if the engine fails to start, such as when no GPU can render to the
+/// window. On the web it returns right away, and the engine starts once a GPU is
+/// found, logging to the browser console if it fails to.
+pub fn run_with_config<App: Application>(config: EngineConfig) -> Result<(), ClockworkError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return pollster::block_on(run_async::<App>(config));
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(error) = run_async::<App>(config).await {
+                report_error(format_args!("clockwork failed to start: {error}"));
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Starts the engine, waiting on the GPU asynchronously as browsers require, then runs
+/// the event loop.
+async fn run_async<App: Application>(mut config: EngineConfig) -> Result<(), ClockworkError> {
+    if let Some(log) = &config.log {
+        log.install().map_err(ClockworkError::Logging)?;
+    }
+
+    let config_file = match &config.config_file {
+        Some(path) => ConfigFile::load(path).map_err(ClockworkError::ConfigFile)?,
+        None => None,
+    };
+    if let Some(config_file) = &config_file {
+        config_file.apply(&mut config);
+    }
+
     let event_loop = winit::event_loop::EventLoop::new();
 
-    let window = winit::window::WindowBuilder::new()
-        .with_title("Clockwork Engine")
-        .build(&event_loop)
-        .unwrap();
+    let window = config
+        .window
+        .to_window_builder(&event_loop)
+        .build(&event_loop)?;
+    #[cfg(target_arch = "wasm32")]
+    crate::web::attach_canvas(&window, &config.window)?;
 
     let size = window.inner_size();
-    let graphics_context = RenderContext::new(&window, size.width, size.height);
+    let mut graphics_context = RenderContext::new_async(
+        &window,
+        size.width,
+        size.height,
+        config.vsync,
+        config.output_format,
+        |adapters| App::select_adapter(adapters).unwrap_or(config.adapter.clone()),
+    )
+    .await?;
+    graphics_context.set_max_frames_in_flight(config.max_frames_in_flight);
 
     let input_state = InputState::new();
 
+    #[cfg(feature = "ui")]
+    let ui_input = crate::ui::UiInput::new(window.scale_factor());
+
     let mut engine = Engine {
         input_state,
         window,
         graphics_context,
+        virtual_controls: Default::default(),
+        fixed_timestep: FixedTimestep::new(config.fixed_tick_rate),
+        clock: GameClock::new(),
+        frame_limiter: FrameLimiter::new(config.max_fps),
+        frame_timer: FrameTimer::new(),
+        frame_allocs: FrameAllocStats::default(),
+        cursor_grab: CursorGrab::None,
+        focused: true,
+        occluded: false,
+        in_background: false,
+        background: config.background,
+        next_background_frame: None,
+        leak_report: config.leak_report,
+        input_latency_probe: false,
+        input_recording: config
+            .input_recording
+            .as_ref()
+            .map(|_| InputRecording::new()),
+        input_player: config.input_playback.map(InputPlayer::new),
+        #[cfg(feature = "ui")]
+        ui_input,
+        #[cfg(feature = "ui")]
+        stats_overlay: false,
+        #[cfg(feature = "ui")]
+        material_panel: false,
+        #[cfg(feature = "ui")]
+        debug_workspace: None,
+        #[cfg(debug_assertions)]
+        config_watcher: config.config_file.map(|path| {
+            crate::config_file::ConfigWatcher::new(path, config_file.unwrap_or_default())
+        }),
     };
 
     let mut app = App::init(&mut engine);
+    let mut input_fuzzer = config.input_soak.map(InputFuzzer::new);
+    let input_recording_path = config.input_recording;
+    let exit_after_playback = engine.is_playing_input();
+    let mut last_update = Instant::now();
+    let mut last_frame_allocs = AllocStats::current();
+
+    run_event_loop(event_loop, move |event, _, control_flow| {
+        // Live input is ignored while a recording plays so it replays the same way.
+        #[cfg(feature = "ui")]
+        if let winit::event::Event::WindowEvent { event, .. } = &event {
+            if !engine.is_playing_input() {
+                engine.ui_input.handle_window_event(event);
+            }
+        }
+
+        if let winit::event::Event::WindowEvent { event, .. } = &event {
+            if !engine.is_playing_input() {
+                engine.handle_input_event(event);
+            }
+        }
 
-    event_loop.run(move |event, _, control_flow| match event {
-        winit::event::Event::WindowEvent { event, .. } => match event {
-            winit::event::WindowEvent::KeyboardInput {
-                input:
-                    winit::event::KeyboardInput {
-                        virtual_keycode: Some(keycode),
-                        state,
-                        ..
-                    },
-                is_synthetic: false,
+        match event {
+            winit::event::Event::WindowEvent { event, .. } => match event {
+                winit::event::WindowEvent::Focused(focused) => {
+                    engine.focused = focused;
+                    // Platforms release the grab when the window loses focus.
+                    if focused && engine.cursor_grab != CursorGrab::None {
+                        engine.apply_cursor_grab().ok();
+                    }
+                    app.on_focus_changed(&mut engine, focused);
+                }
+                winit::event::WindowEvent::Moved(position) => {
+                    app.on_window_moved(&mut engine, glam::ivec2(position.x, position.y));
+                }
+                winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    app.on_scale_factor_changed(&mut engine, scale_factor);
+                }
+                winit::event::WindowEvent::DroppedFile(path) => {
+                    app.on_file_dropped(&mut engine, path);
+                }
+                winit::event::WindowEvent::CloseRequested => control_flow.set_exit(),
+                // Some platforms invalidate the surface while the window is hidden.
+                winit::event::WindowEvent::Occluded(false) => {
+                    engine.occluded = false;
+                    if let Err(error) = engine.graphics_context.resume(&engine.window) {
+                        report_error(format_args!("failed to restore the surface: {error:#}"));
+                    }
+                }
+                winit::event::WindowEvent::Occluded(true) => engine.occluded = true,
+                winit::event::WindowEvent::Resized(winit::dpi::PhysicalSize { width, height }) => {
+                    let new_size = glam::UVec2 {
+                        x: width,
+                        y: height,
+                    };
+                    let was_minimized = engine.graphics_context.is_minimized();
+                    engine.graphics_context.resize_surface(new_size);
+
+                    match (was_minimized, engine.graphics_context.is_minimized()) {
+                        (false, true) => app.on_minimized(&mut engine),
+                        (true, false) => {
+                            app.on_restored(&mut engine);
+                            app.on_window_resize(&mut engine, new_size);
+                        }
+                        (false, false) => app.on_window_resize(&mut engine, new_size),
+                        (true, true) => (),
+                    }
+                }
+                _ => (),
+            },
+            winit::event::Event::DeviceEvent {
+                event: winit::event::DeviceEvent::MouseMotion { delta: (x, y) },
                 ..
-            } => {
-                let key: Keyboard = num::FromPrimitive::from_u32(keycode as u32).unwrap();
-                match state {
-                    winit::event::ElementState::Pressed => engine.input_state.signal_press_of(key),
-                    winit::event::ElementState::Released => {
-                        engine.input_state.signal_release_of(key)
+            } if engine.focused && !engine.is_playing_input() => {
+                engine
+                    .input_state
+                    .signal_event(crate::input::InputEventKind::MouseMotion(
+                        glam::dvec2(x, y).as_vec2(),
+                    ));
+            }
+            winit::event::Event::Suspended => {
+                engine.graphics_context.suspend();
+                app.on_suspended(&mut engine);
+            }
+            // Also sent once at startup, when there is nothing to resume.
+            winit::event::Event::Resumed if engine.graphics_context.is_suspended() => {
+                match engine.graphics_context.resume(&engine.window) {
+                    Ok(()) => app.on_resumed(&mut engine),
+                    Err(error) => {
+                        report_error(format_args!("failed to recreate the surface: {error:#}"));
+                        control_flow.set_exit();
                     }
                 }
             }
-            winit::event::WindowEvent::MouseInput { button, state, .. } => {
-                let button: Option<Mouse> = match button {
-                    winit::event::MouseButton::Left => Some(Mouse::Left),
-                    winit::event::MouseButton::Right => Some(Mouse::Right),
-                    winit::event::MouseButton::Middle => Some(Mouse::Middle),
-                    _ => None,
+            winit::event::Event::MainEventsCleared => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if engine.graphics_context.is_device_lost() {
+                    match engine.graphics_context.recover(&engine.window) {
+                        Ok(()) => app.on_device_restored(&mut engine),
+                        Err(source) => {
+                            report_error(ClockworkError::DeviceLost {
+                                adapter: engine.graphics_context.current_adapter().name,
+                                source: Box::new(source),
+                            });
+                            control_flow.set_exit_with_code(1);
+                            return;
+                        }
+                    }
+                }
+                if engine.update_background() {
+                    let in_background = engine.in_background;
+                    app.on_background_changed(&mut engine, in_background);
+                }
+                if let Some(wait) = engine.background_wait(Instant::now()) {
+                    // Paused time isn't simulated once the window returns.
+                    if wait == ControlFlow::Wait {
+                        last_update = Instant::now();
+                    }
+                    set_control_flow(control_flow, wait);
+                    return;
+                }
+                set_control_flow(control_flow, ControlFlow::Poll);
+
+                let now = Instant::now();
+                let wait = engine.frame_limiter.frame_start(now);
+                if !wait.is_zero() {
+                    // Browsers can't block, and pace frames to the display themselves.
+                    #[cfg(not(target_arch = "wasm32"))]
+                    crate::timestep::wait_until(now + wait);
+                }
+
+                let frame_allocs_start = AllocStats::current();
+                let mut frame_allocs = FrameAllocStats {
+                    events: frame_allocs_start.since(last_frame_allocs),
+                    ..Default::default()
                 };
 
-                if let Some(button) = button {
-                    match state {
-                        winit::event::ElementState::Pressed => {
-                            engine.input_state.signal_press_of(button)
-                        }
-                        winit::event::ElementState::Released => {
-                            engine.input_state.signal_release_of(button)
+                engine.graphics_context.poll_readbacks();
+
+                let now = Instant::now();
+                let mut delta = (now - last_update).as_secs_f64();
+                engine.frame_timer.record(now - last_update);
+                last_update = now;
+
+                #[cfg(feature = "ui")]
+                engine
+                    .graphics_context
+                    .begin_ui_frame(engine.ui_input.take(&engine.window));
+                #[cfg(feature = "ui")]
+                if engine.stats_overlay {
+                    crate::diagnostics::stats_overlay(engine.ui_ctx(), &engine.stats());
+                }
+                #[cfg(feature = "ui")]
+                if engine.material_panel {
+                    let ctx = engine.ui_ctx().clone();
+                    crate::diagnostics::material_panel(&ctx, &mut engine.graphics_context);
+                }
+
+                if let Some(input_fuzzer) = &mut input_fuzzer {
+                    let size = engine.window.inner_size();
+                    for event in
+                        input_fuzzer.frame_events(glam::uvec2(size.width, size.height).as_vec2())
+                    {
+                        #[cfg(feature = "ui")]
+                        engine.ui_input.handle_window_event(&event);
+                        engine.handle_input_event(&event);
+                    }
+                }
+
+                if let Some(input_player) = &mut engine.input_player {
+                    match input_player.play_frame(&mut engine.input_state) {
+                        Some(recorded_delta) => delta = recorded_delta,
+                        None => {
+                            engine.input_player = None;
+                            if exit_after_playback {
+                                control_flow.set_exit();
+                            }
                         }
                     }
                 }
+
+                #[cfg(debug_assertions)]
+                if let Some(result) = engine.reload_config_file() {
+                    app.on_config_reloaded(&mut engine, result);
+                }
+
+                if engine.input_latency_probe {
+                    if let Some(event) = engine.input_state.events().first() {
+                        engine.graphics_context.probe_input(event.timestamp);
+                    }
+                }
+
+                engine.clock.advance(delta);
+                engine.input_state.set_paused(engine.clock.is_paused());
+                let game_delta = engine.clock.delta();
+
+                let fixed_delta = engine.fixed_delta();
+                let phase_start = AllocStats::current();
+                for _ in 0..engine.fixed_timestep.advance(game_delta) {
+                    let _span = span("fixed update");
+                    app.fixed_update(&mut engine, fixed_delta);
+                }
+                let alpha = engine.fixed_timestep.alpha();
+                let update_start = AllocStats::current();
+                {
+                    let _span = span("update");
+                    app.update(&mut engine, game_delta, alpha);
+                }
+                frame_allocs.fixed_update = update_start.since(phase_start);
+                frame_allocs.update = AllocStats::current().since(update_start);
+                if let Some(input_recording) = &mut engine.input_recording {
+                    input_recording.record_frame(delta, &engine.input_state);
+                }
+                engine.input_state.clear_typed_text();
+                engine.input_state.clear_mouse_motion();
+                engine.input_state.begin_frame();
+
+                if let Some(input_fuzzer) = &mut input_fuzzer {
+                    if let Err(error) = input_fuzzer.check(
+                        &engine.input_state,
+                        &engine.virtual_controls,
+                        engine.graphics_context.render_stats(),
+                    ) {
+                        panic!("input soak failed: {error}");
+                    }
+                    if input_fuzzer.finished() {
+                        control_flow.set_exit();
+                    }
+                }
+
+                #[cfg(feature = "ui")]
+                if engine.debug_workspace.is_some() {
+                    let stats = engine.stats();
+                    if let Some(workspace) = &mut engine.debug_workspace {
+                        workspace.show(
+                            engine.graphics_context.ui_context(),
+                            &stats,
+                            &engine.graphics_context,
+                        );
+                    }
+                }
+
+                #[cfg(feature = "ui")]
+                if let Some(platform_output) = engine.graphics_context.finish_ui_frame() {
+                    engine
+                        .ui_input
+                        .apply_platform_output(&engine.window, platform_output);
+                }
+
+                for error in engine.graphics_context.take_errors() {
+                    app.on_gpu_error(&mut engine, error);
+                }
+
+                last_frame_allocs = AllocStats::current();
+                let mut app_allocs = frame_allocs.fixed_update;
+                app_allocs += frame_allocs.update;
+                frame_allocs.engine = last_frame_allocs
+                    .since(frame_allocs_start)
+                    .since(app_allocs);
+                frame_allocs.render = engine.graphics_context.take_render_allocs();
+                engine.frame_allocs = frame_allocs;
             }
-            winit::event::WindowEvent::CloseRequested => control_flow.set_exit(),
-            winit::event::WindowEvent::Resized(winit::dpi::PhysicalSize { width, height }) => {
-                let new_size = glam::UVec2 {
-                    x: width,
-                    y: height,
-                };
-                engine.graphics_context.resize_surface(new_size);
-                app.on_window_resize(&mut engine, new_size);
+            winit::event::Event::LoopDestroyed => {
+                if let (Some(path), Some(input_recording)) =
+                    (&input_recording_path, &engine.input_recording)
+                {
+                    if let Err(error) = input_recording.save(path) {
+                        report_error(format_args!("failed to save input recording: {error:#}"));
+                    }
+                }
+                if engine.leak_report {
+                    let report = engine.graphics_context.leak_report();
+                    let text = report.to_string();
+                    match report.has_outstanding_handles() {
+                        true => log::warn!("{}", text.trim_end()),
+                        false => log::debug!("{}", text.trim_end()),
+                    }
+                }
             }
             _ => (),
-        },
-        winit::event::Event::MainEventsCleared => {
-            app.update(&mut engine, 0.0);
         }
-        _ => (),
     });
+    Ok(())
+}
+
+/// Sets how the event loop waits, unless it's exiting.
+fn set_control_flow(control_flow: &mut ControlFlow, new_control_flow: ControlFlow) {
+    if !matches!(control_flow, ControlFlow::ExitWithCode(_)) {
+        *control_flow = new_control_flow;
+    }
+}
+
+/// Runs the winit event loop, which never returns natively. On the web it's handed to
+/// the browser and returns right away.
+fn run_event_loop<F>(event_loop: winit::event_loop::EventLoop<()>, event_handler: F)
+where
+    F: 'static
+        + FnMut(
+            winit::event::Event<'_, ()>,
+            &winit::event_loop::EventLoopWindowTarget<()>,
+            &mut winit::event_loop::ControlFlow,
+        ),
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    event_loop.run(event_handler);
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn(event_handler);
+    }
 }
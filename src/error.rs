@@ -0,0 +1,84 @@
+use crate::graphics::AdapterSelection;
+
+/// Errors starting or running the [crate::Engine], such as on a machine without a
+/// compatible GPU.
+#[derive(Debug, thiserror::Error)]
+pub enum ClockworkError {
+    /// The window couldn't be created.
+    #[error("failed to create the window: {0}")]
+    CreateWindow(#[from] winit::error::OsError),
+    /// The window can't be rendered to.
+    #[error("failed to create a surface to render to the window: {0}")]
+    CreateSurface(#[from] wgpu::CreateSurfaceError),
+    /// No adapter (GPU) can render to the window.
+    #[error(
+        "no graphics adapter for {selection:?} can render to the window (found {}), \
+         check that graphics drivers supporting Vulkan, Metal or DirectX 12 are installed",
+        match available.is_empty() {
+            true => "none".to_string(),
+            false => available.join(", "),
+        }
+    )]
+    NoCompatibleAdapter {
+        /// Adapter that was asked for, see [crate::EngineConfig::adapter].
+        selection: AdapterSelection,
+        /// Names of the adapters that can render to the window.
+        available: Vec<String>,
+    },
+    /// The adapter was found, but a device couldn't be opened on it.
+    #[error(
+        "failed to open a graphics device on {adapter}: {source}, try updating its \
+         drivers or selecting another adapter"
+    )]
+    RequestDevice {
+        /// Name of the adapter.
+        adapter: String,
+        #[source]
+        source: wgpu::RequestDeviceError,
+    },
+    /// The device was lost while running and a new one couldn't be opened, see
+    /// [crate::graphics::RenderContext::is_device_lost].
+    #[error(
+        "the graphics device on {adapter} was lost, such as from a driver restart, and \
+         couldn't be opened again: {source}"
+    )]
+    DeviceLost {
+        /// Name of the adapter.
+        adapter: String,
+        /// Why the new device couldn't be opened.
+        #[source]
+        source: Box<ClockworkError>,
+    },
+    /// The gpu reported an error while running, such as a resource used in a way it
+    /// wasn't created for, see [crate::graphics::RenderContext::take_errors].
+    #[error("the gpu reported an error: {0}")]
+    Gpu(String),
+    /// The adapter can render, but not to this window.
+    #[error("the graphics adapter {0} has no format to present to the window with")]
+    NoSurfaceFormat(String),
+    /// The window's canvas couldn't be found or added to the web page.
+    #[error("failed to attach the window to the page: {0}")]
+    Canvas(String),
+    /// The engine config file couldn't be read.
+    #[error("failed to load the engine config file: {0:#}")]
+    ConfigFile(anyhow::Error),
+    /// The logger set with [crate::EngineConfig::with_log] couldn't be installed.
+    #[error("failed to install the logger: {0:#}")]
+    Logging(anyhow::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_adapter_message() {
+        let error = ClockworkError::NoCompatibleAdapter {
+            selection: AdapterSelection::LowPower,
+            available: Vec::new(),
+        };
+        assert!(error
+            .to_string()
+            .starts_with("no graphics adapter for LowPower can render to the window (found none)"));
+    }
+}
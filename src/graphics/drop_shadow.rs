@@ -0,0 +1,242 @@
+use anyhow::Result;
+use glam::{vec4, Mat4, Vec2, Vec3, Vec4};
+
+use crate::util::repository::ResourceId;
+
+use super::{
+    CustomMaterial, Material, MaterialData, MaterialLayout, MaterialPipeline, RenderContext,
+    RenderOperation, TextureMaps, TextureParameters,
+};
+
+const SHADER_SOURCE: &str = include_str!("drop_shadow.wgsl");
+
+/// Shape of a [DropShadow].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DropShadowStyle {
+    /// Soft ellipse centered on the bottom edge of the sprite, with a size relative to
+    /// the sprite's.
+    Blob { size: Vec2 },
+    /// Blurred copy of the sprite's silhouette.
+    Silhouette,
+}
+
+/// Settings for cheap 2D shadows drawn beneath sprites.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DropShadow {
+    /// Shape of the shadow.
+    pub style: DropShadowStyle,
+    /// Color of the shadow, including its opacity.
+    pub color: Vec4,
+    /// Offset of the shadow from the sprite in world units.
+    pub offset: Vec2,
+    /// How soft the edge of the shadow is, from 0 (hard) to 1 (fully faded).
+    pub softness: f32,
+}
+
+/// Drop shadows registered with [RenderContext::create_drop_shadows].
+#[derive(Clone, Copy, Debug)]
+pub struct DropShadows {
+    pipeline_id: ResourceId<MaterialPipeline>,
+    settings: DropShadow,
+}
+
+/// Uniforms of the drop shadow shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DropShadowUniforms {
+    color: [f32; 4],
+    softness: f32,
+    mode: u32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for DropShadowUniforms {}
+unsafe impl bytemuck::Pod for DropShadowUniforms {}
+
+impl Default for DropShadow {
+    fn default() -> Self {
+        Self {
+            style: DropShadowStyle::Blob {
+                size: Vec2::new(0.8, 0.25),
+            },
+            color: vec4(0.0, 0.0, 0.0, 0.5),
+            offset: Vec2::ZERO,
+            softness: 0.5,
+        }
+    }
+}
+
+impl DropShadow {
+    /// Creates a [DropShadow] that copies the sprite's silhouette, offset by `offset`.
+    pub fn silhouette(offset: Vec2) -> Self {
+        Self {
+            style: DropShadowStyle::Silhouette,
+            offset,
+            softness: 0.1,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the color of the shadow.
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the offset of the shadow from the sprite in world units.
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets how soft the edge of the shadow is.
+    pub fn with_softness(mut self, softness: f32) -> Self {
+        self.softness = softness;
+        self
+    }
+
+    fn uniforms(&self) -> DropShadowUniforms {
+        DropShadowUniforms {
+            color: self.color.to_array(),
+            softness: self.softness.clamp(0.0, 1.0),
+            mode: match self.style {
+                DropShadowStyle::Blob { .. } => 0,
+                DropShadowStyle::Silhouette => 1,
+            },
+            _padding: [0; 2],
+        }
+    }
+
+    /// Transformation of the shadow of a sprite drawn with `transform`.
+    fn transform(&self, transform: Mat4) -> Mat4 {
+        let offset = Mat4::from_translation(self.offset.extend(0.0));
+        match self.style {
+            DropShadowStyle::Blob { size } => {
+                offset
+                    * transform
+                    * Mat4::from_translation(Vec3::new(0.0, -0.5, 0.0))
+                    * Mat4::from_scale(size.extend(1.0))
+            }
+            DropShadowStyle::Silhouette => offset * transform,
+        }
+    }
+}
+
+impl DropShadows {
+    /// Gets the current settings.
+    pub fn settings(&self) -> DropShadow {
+        self.settings
+    }
+
+    /// Creates the shadow operation for each operation, which should be rendered before
+    /// the operations themselves so the shadows end up beneath them.
+    pub fn shadow_operations(&self, operations: &[RenderOperation]) -> Vec<RenderOperation> {
+        operations
+            .iter()
+            .map(|operation| {
+                let texture_parameters = match operation.material {
+                    Material::BasicDiffuse(material) => material.texture_parameters,
+                    Material::Custom(material) => material.texture_parameters,
+                    Material::Reflective(material) => material.texture_parameters,
+                    Material::Mapped(material) => material.texture_parameters,
+                    Material::Pbr(material) => material
+                        .base_color_texture
+                        .map(|texture_id| TextureParameters::new(texture_id, None)),
+                }
+                .unwrap_or_default();
+
+                let texture_parameters = match self.settings.style {
+                    // The blob only needs uvs across the whole quad.
+                    DropShadowStyle::Blob { .. } => {
+                        TextureParameters::new(texture_parameters.texture_id, None)
+                    }
+                    DropShadowStyle::Silhouette => texture_parameters,
+                };
+
+                RenderOperation {
+                    transform: self.settings.transform(operation.transform),
+                    mesh_id: operation.mesh_id,
+                    material: Material::Custom(CustomMaterial {
+                        pipeline_id: self.pipeline_id,
+                        color: self.settings.color,
+                        texture_parameters: Some(texture_parameters),
+                        maps: TextureMaps::default(),
+                        data: MaterialData::default(),
+                    }),
+                    layers: operation.layers,
+                    transparent: operation.transparent,
+                    sort_layer: operation.sort_layer,
+                }
+            })
+            .collect()
+    }
+
+    /// Puts the shadow operations before the given operations.
+    pub fn with_shadows(&self, operations: &[RenderOperation]) -> Vec<RenderOperation> {
+        let mut with_shadows = self.shadow_operations(operations);
+        with_shadows.extend_from_slice(operations);
+        with_shadows
+    }
+}
+
+impl RenderContext {
+    /// Registers the material used to draw drop shadows with the given settings.
+    pub fn create_drop_shadows(&mut self, settings: DropShadow) -> Result<DropShadows> {
+        let pipeline_id = self.register_material(
+            SHADER_SOURCE,
+            MaterialLayout::with_uniforms(
+                std::mem::size_of::<DropShadowUniforms>() as wgpu::BufferAddress
+            )
+            .with_name("clockwork drop shadow"),
+        )?;
+        let mut drop_shadows = DropShadows {
+            pipeline_id,
+            settings,
+        };
+        self.update_drop_shadows(&mut drop_shadows, settings)?;
+        Ok(drop_shadows)
+    }
+
+    /// Changes the settings of drop shadows.
+    pub fn update_drop_shadows(
+        &mut self,
+        drop_shadows: &mut DropShadows,
+        settings: DropShadow,
+    ) -> Result<()> {
+        self.set_material_uniforms(
+            drop_shadows.pipeline_id,
+            bytemuck::bytes_of(&settings.uniforms()),
+        )?;
+        drop_shadows.settings = settings;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_transform() {
+        let shadow = DropShadow {
+            style: DropShadowStyle::Blob {
+                size: Vec2::new(0.5, 0.25),
+            },
+            offset: Vec2::new(1.0, 0.0),
+            ..Default::default()
+        };
+        let sprite = Mat4::from_scale(Vec3::new(2.0, 2.0, 1.0));
+
+        // The blob sits on the sprite's bottom edge, shifted by the offset.
+        let transform = shadow.transform(sprite);
+        let center = transform.transform_point3(Vec3::ZERO);
+        let right = transform.transform_point3(Vec3::new(0.5, 0.0, 0.0));
+        assert_eq!(center, Vec3::new(1.0, -1.0, 0.0));
+        assert_eq!(right - center, Vec3::new(0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_uniform_size() {
+        assert_eq!(std::mem::size_of::<DropShadowUniforms>(), 32);
+    }
+}
@@ -0,0 +1,87 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+struct Global {
+    mvp: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> global: Global;
+
+struct Local {
+    transform: mat4x4<f32>,
+    uv_window: vec4<f32>,
+}
+@group(0) @binding(1)
+var<uniform> local: Local;
+
+@group(1) @binding(0)
+var texture_sampler: sampler;
+@group(1) @binding(1)
+var texture: texture_2d<f32>;
+
+struct DropShadow {
+    color: vec4<f32>,
+    softness: f32,
+    // 0 for a blob, 1 for the sprite's silhouette.
+    mode: u32,
+}
+@group(2) @binding(0)
+var<uniform> shadow: DropShadow;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = global.mvp * local.transform * vec4<f32>(in.position, 1.0);
+    out.uv = local.uv_window.xy + (local.uv_window.zw * in.uv);
+    return out;
+}
+
+fn blob_coverage(uv: vec2<f32>) -> f32 {
+    let distance = length(uv * 2.0 - 1.0);
+    return 1.0 - smoothstep(1.0 - max(shadow.softness, 0.001), 1.0, distance);
+}
+
+fn silhouette_coverage(uv: vec2<f32>) -> f32 {
+    // Keep the taps inside the sprite's frame so neighbouring frames don't bleed in.
+    let window_min = local.uv_window.xy;
+    let window_max = local.uv_window.xy + local.uv_window.zw;
+    let radius = local.uv_window.zw * shadow.softness * 0.5;
+
+    var coverage = 0.0;
+    for (var x = -2; x <= 2; x++) {
+        for (var y = -2; y <= 2; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * 0.5 * radius;
+            let tap = uv + offset;
+            let inside = all(tap >= window_min) && all(tap <= window_max);
+            let alpha = textureSampleLevel(texture, texture_sampler, clamp(tap, window_min, window_max), 0.0).w;
+            coverage += select(0.0, alpha, inside);
+        }
+    }
+    return coverage / 25.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var coverage: f32;
+    if (shadow.mode == 0u) {
+        coverage = blob_coverage(in.uv);
+    } else {
+        coverage = silhouette_coverage(in.uv);
+    }
+
+    let alpha = shadow.color.w * coverage;
+    if (alpha < 0.001) {
+        discard;
+    }
+
+    // The pipeline blends with premultiplied alpha.
+    return vec4<f32>(shadow.color.xyz * alpha, alpha);
+}
@@ -1,4 +1,6 @@
-use wgpu::util::DeviceExt;
+use std::ops::Range;
+
+use crate::util::frustum::BoundingSphere;
 
 /// Foundational building block for a mesh.
 #[repr(C)]
@@ -15,6 +17,10 @@ pub struct Vertex {
 /// Data type to reference a particular [Vertex] in a list of vertices.
 pub type Index = u32;
 
+/// Color of a vertex in sRGB with alpha last, which is multiplied with the color the
+/// vertex is drawn with.
+pub type VertexColor = [u8; 4];
+
 /// Contains data used to construct a mesh.
 pub struct MeshData<'a> {
     /// What vertices make up the mesh.
@@ -23,9 +29,15 @@ pub struct MeshData<'a> {
     pub indices: &'a [Index],
 }
 
+/// Mesh loaded into the [super::RenderContext]'s shared vertex and index buffers.
 pub struct Mesh {
-    pub(crate) vertex_buffer: wgpu::Buffer,
-    pub(crate) index_buffer: wgpu::Buffer,
+    /// Range of the mesh's vertices, and their colors, in the shared buffers.
+    pub(crate) vertices: Range<u32>,
+    /// Range of the mesh's indices in the shared index buffer, which index from the
+    /// first of its vertices.
+    pub(crate) indices: Range<u32>,
+    /// Sphere around the mesh's vertices, for frustum culling.
+    pub(crate) bounds: BoundingSphere,
 }
 
 unsafe impl bytemuck::Zeroable for Vertex {}
@@ -45,23 +57,14 @@ pub(crate) const VERTEX_BUFFER_LAYOUT: wgpu::VertexBufferLayout = {
     }
 };
 
-impl Mesh {
-    pub(crate) fn load(device: &wgpu::Device, mesh_data: MeshData) -> Mesh {
-        Self {
-            vertex_buffer: device.create_buffer_init(
-                &(wgpu::util::BufferInitDescriptor {
-                    label: None,
-                    contents: bytemuck::cast_slice(mesh_data.vertices),
-                    usage: wgpu::BufferUsages::VERTEX,
-                }),
-            ),
-            index_buffer: device.create_buffer_init(
-                &(wgpu::util::BufferInitDescriptor {
-                    label: None,
-                    contents: bytemuck::cast_slice(mesh_data.indices),
-                    usage: wgpu::BufferUsages::INDEX,
-                }),
-            ),
-        }
+pub(crate) const COLOR_BUFFER_LAYOUT: wgpu::VertexBufferLayout = {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![
+        3 => Unorm8x4
+    ];
+
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<VertexColor>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &ATTRIBUTES,
     }
-}
+};
@@ -1,11 +1,37 @@
+pub(crate) mod drop_shadow;
 pub(crate) mod mesh;
+pub(crate) mod model;
 pub(crate) mod render_context;
+pub(crate) mod skeleton;
+pub(crate) mod sorting;
 pub(crate) mod texture;
+pub(crate) mod world_ui;
 
-pub use mesh::{Index, Mesh, MeshData, Vertex};
+pub use drop_shadow::{DropShadow, DropShadowStyle, DropShadows};
+pub use mesh::{Index, Mesh, MeshData, Vertex, VertexColor};
+pub use model::{Model, ModelData, ModelImage, ModelMaterial, Submesh, SubmeshData, SubmeshSkin};
 pub use render_context::{
-    BasicDiffuseMaterial, Material, RenderContext, RenderOperation, TextureParameters,
+    AdapterInfo, AdapterSelection, AutoExposure, BasicDiffuseMaterial, Billboard, Bloom,
+    BloomSettings, Brush, CustomMaterial, DebugDraw, DebugView, DynamicResolution,
+    DynamicResolutionSettings, EnvironmentMap, ExposureSettings, FrameLatencyStats,
+    GraphicsCapabilities, LeakReport, Light, Lighting, MappedMaterial, Material, MaterialData,
+    MaterialLayout, MaterialPipeline, MotionBlurSettings, OutputFormat, PbrMaterial,
+    PipelineWarmup, PixelPerfect, PostEffect, PostProcessStack, PresentMode, Readback,
+    ReflectiveMaterial, RenderContext, RenderLayers, RenderOperation, RenderPassDescriptor,
+    RenderStats, RenderTarget, StylisticEffect, StylisticEffects, TextureArray,
+    TextureArrayInstance, TextureMaps, TextureParameters, TextureReadback, TonemapOperator,
+    Tonemapping, UniformField, UniformType, UniformValue, VelocityBuffer, VelocityOperation,
+    WarmupPipeline, MAX_DOWNLEVEL_LIGHTS, MAX_LIGHTS, MAX_TILE_LIGHTS,
 };
+pub use skeleton::{
+    AnimationChannel, AnimationClip, AnimationProperty, Interpolation, Joint, JointTransform, Pose,
+    Skeleton, SkinWeights,
+};
+pub use sorting::{SortKey, SortingGroup, SpriteSorter};
+pub use texture::{SamplerSettings, TextureFilter, TextureLoadOptions, TextureWrap};
+pub use world_ui::{WorldQuad, WorldQuadSizing};
 
 /// Contains data for typical meshes.
 pub mod default_meshes;
+/// 2D particle emitters simulated on the cpu.
+pub mod particles;
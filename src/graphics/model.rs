@@ -0,0 +1,714 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Context, Result};
+use glam::{Mat4, Quat, UVec2, Vec2, Vec3, Vec4};
+
+use crate::util::repository::ResourceId;
+
+use super::{
+    skeleton::{
+        AnimationChannel, AnimationClip, AnimationProperty, Interpolation, Joint, JointTransform,
+        Skeleton, SkinWeights,
+    },
+    texture::Texture,
+    EnvironmentMap, Index, Mesh, PbrMaterial, RenderOperation, Vertex,
+};
+
+/// Model made up of meshes and textures loaded into a [super::RenderContext] with
+/// [super::RenderContext::load_model].
+#[derive(Clone, Debug, Default)]
+pub struct Model {
+    /// Parts of the model, each drawn with its own material.
+    pub submeshes: Vec<Submesh>,
+    /// Skeletons the skinned parts deform with, see [super::RenderContext::pose_model].
+    pub skeletons: Vec<Skeleton>,
+    /// Animations of the skeletons.
+    pub animations: Vec<AnimationClip>,
+}
+
+/// Part of a [Model] drawn with a single material.
+#[derive(Clone, Copy, Debug)]
+pub struct Submesh {
+    /// Mesh of the part.
+    pub mesh_id: ResourceId<Mesh>,
+    /// Transformation of the part relative to the model.
+    pub transform: Mat4,
+    /// Base color of the part's material.
+    pub color: Vec4,
+    /// Base color texture of the part's material.
+    pub texture_id: Option<ResourceId<Texture>>,
+    /// The part's full material, see [Model::pbr_render_operations].
+    pub material: PbrMaterial,
+    /// Index of the skeleton the part deforms with in [Model::skeletons], if it's
+    /// skinned.
+    pub skeleton: Option<usize>,
+}
+
+/// Model data imported from a file, before it is loaded onto the gpu.
+#[derive(Clone, Debug, Default)]
+pub struct ModelData {
+    /// Parts of the model.
+    pub submeshes: Vec<SubmeshData>,
+    /// Materials referred to by the parts.
+    pub materials: Vec<ModelMaterial>,
+    /// Images referred to by the materials.
+    pub images: Vec<ModelImage>,
+    /// Skeletons referred to by skinned parts.
+    pub skeletons: Vec<Skeleton>,
+    /// Animations of the skeletons.
+    pub animations: Vec<AnimationClip>,
+}
+
+/// Part of a [ModelData] drawn with a single material.
+#[derive(Clone, Debug, Default)]
+pub struct SubmeshData {
+    /// Name of the part in the file, if it has one.
+    pub name: Option<String>,
+    /// Vertices of the part.
+    pub vertices: Vec<Vertex>,
+    /// Order in which to traverse the vertices.
+    pub indices: Vec<Index>,
+    /// Transformation of the part relative to the model.
+    pub transform: Mat4,
+    /// Index of the part's material in [ModelData::materials].
+    pub material: Option<usize>,
+    /// How the part deforms with a skeleton, if it's skinned, in which case its
+    /// transformation is ignored.
+    pub skin: Option<SubmeshSkin>,
+}
+
+/// Skinning of a [SubmeshData].
+#[derive(Clone, Debug)]
+pub struct SubmeshSkin {
+    /// Index of the skeleton in [ModelData::skeletons].
+    pub skeleton: usize,
+    /// Joints each vertex follows, in the order of [SubmeshData::vertices].
+    pub weights: Vec<SkinWeights>,
+}
+
+/// Material of a [ModelData].
+#[derive(Clone, Debug)]
+pub struct ModelMaterial {
+    /// Name of the material in the file, if it has one.
+    pub name: Option<String>,
+    /// Base color, multiplied with the texture.
+    pub base_color: Vec4,
+    /// Index of the base color texture in [ModelData::images].
+    pub texture: Option<usize>,
+    /// How metallic the material is, multiplied with the metallic-roughness texture.
+    pub metallic: f32,
+    /// How rough the material is, multiplied with the metallic-roughness texture.
+    pub roughness: f32,
+    /// Index of the metallic-roughness texture in [ModelData::images].
+    pub metallic_roughness_texture: Option<usize>,
+    /// Index of the normal map in [ModelData::images].
+    pub normal_texture: Option<usize>,
+    /// How strongly the normal map bends the surface.
+    pub normal_scale: f32,
+    /// Index of the occlusion texture in [ModelData::images].
+    pub occlusion_texture: Option<usize>,
+    /// How much the occlusion texture darkens ambient light.
+    pub occlusion_strength: f32,
+    /// Light given off, multiplied with the emissive texture.
+    pub emissive: Vec3,
+    /// Index of the emissive texture in [ModelData::images].
+    pub emissive_texture: Option<usize>,
+}
+
+/// Image of a [ModelData], decoded to RGBA8.
+#[derive(Clone, Debug)]
+pub struct ModelImage {
+    /// Size of the image in pixels.
+    pub size: UVec2,
+    /// Pixels of the image.
+    pub rgba: Vec<u8>,
+}
+
+impl Default for ModelMaterial {
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_color: Vec4::ONE,
+            texture: None,
+            // Formats without metallic-roughness materials are treated as matte.
+            metallic: 0.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: Vec3::ZERO,
+            emissive_texture: None,
+        }
+    }
+}
+
+impl Model {
+    /// Creates a [RenderOperation] for each part of the model.
+    pub fn render_operations(&self, transform: Mat4) -> Vec<RenderOperation> {
+        self.submeshes
+            .iter()
+            .map(|submesh| {
+                let transform = transform * submesh.transform;
+                match submesh.texture_id {
+                    Some(texture_id) => RenderOperation::textured_mesh(
+                        transform,
+                        submesh.mesh_id,
+                        texture_id,
+                        None,
+                        submesh.color,
+                    ),
+                    None => {
+                        RenderOperation::colored_mesh(transform, submesh.mesh_id, submesh.color)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Creates a [RenderOperation] for each part of the model with its full
+    /// [PbrMaterial], lit by an environment map if one is given.
+    pub fn pbr_render_operations(
+        &self,
+        transform: Mat4,
+        environment_map_id: Option<ResourceId<EnvironmentMap>>,
+    ) -> Vec<RenderOperation> {
+        self.submeshes
+            .iter()
+            .map(|submesh| {
+                RenderOperation::pbr_mesh(
+                    transform * submesh.transform,
+                    submesh.mesh_id,
+                    PbrMaterial {
+                        environment_map_id,
+                        ..submesh.material
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Gets the first animation with a name.
+    pub fn animation(&self, name: &str) -> Option<&AnimationClip> {
+        self.animations
+            .iter()
+            .find(|animation| animation.name.as_deref() == Some(name))
+    }
+}
+
+impl ModelData {
+    /// Imports a model from an OBJ file, along with its materials and textures.
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let (models, materials) = tobj::load_obj(path, &obj_load_options())
+            .with_context(|| format!("failed to load {}", path.display()))?;
+        let materials =
+            materials.with_context(|| format!("failed to load materials of {}", path.display()))?;
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut data = Self::from_obj_meshes(models);
+
+        for material in materials {
+            let texture = match &material.diffuse_texture {
+                Some(texture) => {
+                    let texture_path = directory.join(texture);
+                    let image = image::open(&texture_path)
+                        .with_context(|| format!("failed to load {}", texture_path.display()))?
+                        .to_rgba8();
+                    data.images.push(ModelImage {
+                        size: UVec2::new(image.width(), image.height()),
+                        rgba: image.into_raw(),
+                    });
+                    Some(data.images.len() - 1)
+                }
+                None => None,
+            };
+
+            let diffuse = material.diffuse.unwrap_or([1.0; 3]);
+            data.materials.push(ModelMaterial {
+                name: Some(material.name),
+                base_color: Vec3::from(diffuse).extend(material.dissolve.unwrap_or(1.0)),
+                texture,
+                ..Default::default()
+            });
+        }
+
+        Ok(data)
+    }
+
+    /// Imports a model from the contents of an OBJ file, ignoring its materials.
+    pub fn from_obj_bytes(bytes: &[u8]) -> Result<Self> {
+        let (models, _) = tobj::load_obj_buf(&mut &bytes[..], &obj_load_options(), |_| {
+            Err(tobj::LoadError::OpenFileFailed)
+        })?;
+
+        let mut data = Self::from_obj_meshes(models);
+        for submesh in &mut data.submeshes {
+            submesh.material = None;
+        }
+        Ok(data)
+    }
+
+    /// Imports a model from a glTF file, along with any buffers and images it refers to.
+    pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let (document, buffers, images) =
+            gltf::import(path).with_context(|| format!("failed to load {}", path.display()))?;
+        Self::from_gltf(&document, &buffers, &images)
+    }
+
+    /// Imports a model from the contents of a glTF or glb file. Buffers and images must
+    /// be embedded.
+    pub fn from_gltf_bytes(bytes: &[u8]) -> Result<Self> {
+        let (document, buffers, images) = gltf::import_slice(bytes)?;
+        Self::from_gltf(&document, &buffers, &images)
+    }
+
+    fn from_obj_meshes(models: Vec<tobj::Model>) -> Self {
+        let submeshes = models
+            .into_iter()
+            .map(|model| {
+                let mesh = model.mesh;
+                let vertices = (0..mesh.positions.len() / 3)
+                    .map(|index| Vertex {
+                        position: Vec3::from_slice(&mesh.positions[index * 3..]),
+                        normal: mesh
+                            .normals
+                            .get(index * 3..index * 3 + 3)
+                            .map(Vec3::from_slice)
+                            .unwrap_or_default(),
+                        // OBJ texture coordinates start from the bottom left.
+                        texture_coordinates: mesh
+                            .texcoords
+                            .get(index * 2..index * 2 + 2)
+                            .map(|uv| Vec2::new(uv[0], 1.0 - uv[1]))
+                            .unwrap_or_default(),
+                    })
+                    .collect();
+
+                SubmeshData {
+                    name: Some(model.name),
+                    vertices,
+                    indices: mesh.indices,
+                    transform: Mat4::IDENTITY,
+                    material: mesh.material_id,
+                    skin: None,
+                }
+            })
+            .collect();
+
+        Self {
+            submeshes,
+            ..Default::default()
+        }
+    }
+
+    fn from_gltf(
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        images: &[gltf::image::Data],
+    ) -> Result<Self> {
+        let mut data = Self {
+            images: images
+                .iter()
+                .map(gltf_image_to_rgba)
+                .collect::<Result<_>>()?,
+            materials: document
+                .materials()
+                .map(|material| {
+                    let pbr = material.pbr_metallic_roughness();
+                    let normal = material.normal_texture();
+                    let occlusion = material.occlusion_texture();
+                    ModelMaterial {
+                        name: material.name().map(str::to_string),
+                        base_color: Vec4::from(pbr.base_color_factor()),
+                        texture: pbr
+                            .base_color_texture()
+                            .map(|info| info.texture().source().index()),
+                        metallic: pbr.metallic_factor(),
+                        roughness: pbr.roughness_factor(),
+                        metallic_roughness_texture: pbr
+                            .metallic_roughness_texture()
+                            .map(|info| info.texture().source().index()),
+                        normal_texture: normal
+                            .as_ref()
+                            .map(|normal| normal.texture().source().index()),
+                        normal_scale: normal.as_ref().map_or(1.0, |normal| normal.scale()),
+                        occlusion_texture: occlusion
+                            .as_ref()
+                            .map(|occlusion| occlusion.texture().source().index()),
+                        occlusion_strength: occlusion
+                            .as_ref()
+                            .map_or(1.0, |occlusion| occlusion.strength()),
+                        emissive: Vec3::from(material.emissive_factor()),
+                        emissive_texture: material
+                            .emissive_texture()
+                            .map(|info| info.texture().source().index()),
+                    }
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .context("glTF file has no scenes")?;
+
+        // Transformation of every node in the scene, which skeletons are placed by.
+        let mut node_transforms = vec![Mat4::IDENTITY; document.nodes().len()];
+        let mut nodes: Vec<(gltf::Node, Mat4)> =
+            scene.nodes().map(|node| (node, Mat4::IDENTITY)).collect();
+        while let Some((node, parent_transform)) = nodes.pop() {
+            let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+            node_transforms[node.index()] = transform;
+
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    // Points, lines and strips have no equivalent in the engine's meshes.
+                    if primitive.mode() != gltf::mesh::Mode::Triangles {
+                        continue;
+                    }
+
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let positions: Vec<Vec3> = reader
+                        .read_positions()
+                        .context("glTF primitive has no positions")?
+                        .map(Vec3::from)
+                        .collect();
+                    let mut normals = reader.read_normals().into_iter().flatten();
+                    let mut texture_coordinates = reader
+                        .read_tex_coords(0)
+                        .map(|coordinates| coordinates.into_f32())
+                        .into_iter()
+                        .flatten();
+
+                    let vertices = positions
+                        .iter()
+                        .map(|&position| Vertex {
+                            position,
+                            normal: normals.next().map(Vec3::from).unwrap_or_default(),
+                            texture_coordinates: texture_coordinates
+                                .next()
+                                .map(Vec2::from)
+                                .unwrap_or_default(),
+                        })
+                        .collect();
+                    let indices = match reader.read_indices() {
+                        Some(indices) => indices.into_u32().collect(),
+                        None => (0..positions.len() as Index).collect(),
+                    };
+                    let skin = match (node.skin(), reader.read_joints(0), reader.read_weights(0)) {
+                        (Some(skin), Some(joints), Some(weights)) => Some(SubmeshSkin {
+                            skeleton: skin.index(),
+                            weights: joints
+                                .into_u16()
+                                .zip(weights.into_f32())
+                                .map(|(joints, weights)| skin_weights(joints, weights))
+                                .collect(),
+                        }),
+                        _ => None,
+                    };
+
+                    data.submeshes.push(SubmeshData {
+                        name: mesh.name().map(str::to_string),
+                        vertices,
+                        indices,
+                        // Skinned vertices are placed by their joints alone.
+                        transform: match skin {
+                            Some(_) => Mat4::IDENTITY,
+                            None => transform,
+                        },
+                        material: primitive.material().index(),
+                        skin,
+                    });
+                }
+            }
+
+            nodes.extend(node.children().map(|child| (child, transform)));
+        }
+
+        let mut parents = vec![None; document.nodes().len()];
+        for node in document.nodes() {
+            for child in node.children() {
+                parents[child.index()] = Some(node.index());
+            }
+        }
+        // Index of each skeleton's joints by their node.
+        let joint_indices: Vec<HashMap<usize, usize>> = document
+            .skins()
+            .map(|skin| {
+                skin.joints()
+                    .enumerate()
+                    .map(|(index, node)| (node.index(), index))
+                    .collect()
+            })
+            .collect();
+
+        for (skin, joint_indices) in document.skins().zip(joint_indices.iter()) {
+            let inverse_binds: Vec<Mat4> = skin
+                .reader(|buffer| Some(&buffers[buffer.index()]))
+                .read_inverse_bind_matrices()
+                .map(|matrices| {
+                    matrices
+                        .map(|matrix| Mat4::from_cols_array_2d(&matrix))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let mut root = Mat4::IDENTITY;
+            let joints = skin
+                .joints()
+                .enumerate()
+                .map(|(index, node)| {
+                    let parent_node = parents[node.index()];
+                    let parent = parent_node.and_then(|parent| joint_indices.get(&parent).copied());
+                    if let (None, Some(parent_node)) = (parent, parent_node) {
+                        root = node_transforms[parent_node];
+                    }
+                    let (translation, rotation, scale) = node.transform().decomposed();
+                    Joint {
+                        name: node.name().map(str::to_string),
+                        parent,
+                        inverse_bind: inverse_binds.get(index).copied().unwrap_or(Mat4::IDENTITY),
+                        rest: JointTransform {
+                            translation: Vec3::from(translation),
+                            rotation: Quat::from_array(rotation),
+                            scale: Vec3::from(scale),
+                        },
+                    }
+                })
+                .collect();
+            data.skeletons.push(Skeleton { joints, root });
+        }
+
+        for animation in document.animations() {
+            // Animations can move the joints of several skeletons, which each get a clip.
+            for (skeleton, joint_indices) in joint_indices.iter().enumerate() {
+                let channels: Vec<AnimationChannel> = animation
+                    .channels()
+                    .filter_map(|channel| {
+                        let joint = *joint_indices.get(&channel.target().node().index())?;
+                        gltf_channel(channel, joint, buffers)
+                    })
+                    .collect();
+                if channels.is_empty() {
+                    continue;
+                }
+                data.animations.push(AnimationClip {
+                    name: animation.name().map(str::to_string),
+                    skeleton,
+                    duration: channels
+                        .iter()
+                        .filter_map(|channel| channel.times.last().copied())
+                        .fold(0.0, f32::max),
+                    channels,
+                });
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+/// Gets skin weights that add up to 1, where vertices without any weight follow their
+/// first joint.
+fn skin_weights(joints: [u16; 4], weights: [f32; 4]) -> SkinWeights {
+    let total: f32 = weights.iter().sum();
+    SkinWeights {
+        joints: joints.map(u32::from),
+        weights: match total > 0.0 {
+            true => weights.map(|weight| weight / total),
+            false => [1.0, 0.0, 0.0, 0.0],
+        },
+    }
+}
+
+/// Reads the keyframes of a glTF animation channel moving a joint, or `None` for
+/// morph target weights, which aren't supported.
+fn gltf_channel(
+    channel: gltf::animation::Channel,
+    joint: usize,
+    buffers: &[gltf::buffer::Data],
+) -> Option<AnimationChannel> {
+    use gltf::animation::util::ReadOutputs;
+
+    let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+    let times = reader.read_inputs()?.collect();
+    let (property, values) = match reader.read_outputs()? {
+        ReadOutputs::Translations(translations) => (
+            AnimationProperty::Translation,
+            translations
+                .map(|value| Vec3::from(value).extend(0.0))
+                .collect(),
+        ),
+        ReadOutputs::Rotations(rotations) => (
+            AnimationProperty::Rotation,
+            rotations.into_f32().map(Vec4::from).collect(),
+        ),
+        ReadOutputs::Scales(scales) => (
+            AnimationProperty::Scale,
+            scales.map(|value| Vec3::from(value).extend(0.0)).collect(),
+        ),
+        ReadOutputs::MorphTargetWeights(_) => return None,
+    };
+    let interpolation = match channel.sampler().interpolation() {
+        gltf::animation::Interpolation::Step => Interpolation::Step,
+        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+    };
+    Some(AnimationChannel {
+        joint,
+        property,
+        interpolation,
+        times,
+        values,
+    })
+}
+
+fn obj_load_options() -> tobj::LoadOptions {
+    tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    }
+}
+
+/// Converts a decoded glTF image to RGBA8.
+fn gltf_image_to_rgba(image: &gltf::image::Data) -> Result<ModelImage> {
+    use gltf::image::Format;
+
+    let (channels, channel_size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let to_u8 = |bytes: &[u8]| -> u8 {
+        match channel_size {
+            1 => bytes[0],
+            2 => (u16::from_ne_bytes([bytes[0], bytes[1]]) >> 8) as u8,
+            _ => {
+                let value = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+        }
+    };
+
+    let rgba = image
+        .pixels
+        .chunks_exact(channels * channel_size)
+        .flat_map(|pixel| {
+            let channel = |index: usize| {
+                pixel
+                    .get(index * channel_size..(index + 1) * channel_size)
+                    .map(to_u8)
+            };
+            match channels {
+                // Single channel images are grayscale.
+                1 | 2 => {
+                    let value = channel(0).unwrap_or(0);
+                    [value, value, value, channel(1).unwrap_or(u8::MAX)]
+                }
+                _ => [
+                    channel(0).unwrap_or(0),
+                    channel(1).unwrap_or(0),
+                    channel(2).unwrap_or(0),
+                    channel(3).unwrap_or(u8::MAX),
+                ],
+            }
+        })
+        .collect::<Vec<u8>>();
+
+    anyhow::ensure!(
+        rgba.len() == (image.width * image.height * 4) as usize,
+        "glTF image has {} bytes of pixels but is {}x{}",
+        image.pixels.len(),
+        image.width,
+        image.height
+    );
+
+    Ok(ModelImage {
+        size: UVec2::new(image.width, image.height),
+        rgba,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_obj_quad() {
+        let data = ModelData::from_obj_bytes(include_bytes!("test_files/quad.obj")).unwrap();
+
+        assert_eq!(data.submeshes.len(), 1);
+        let submesh = &data.submeshes[0];
+        assert_eq!(submesh.name.as_deref(), Some("quad"));
+        assert_eq!(submesh.vertices.len(), 4);
+        assert_eq!(submesh.indices.len(), 6);
+        assert_eq!(submesh.vertices[0].normal, Vec3::Z);
+        assert_eq!(submesh.vertices[0].texture_coordinates, Vec2::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn test_gltf_triangle() {
+        let data = ModelData::from_gltf_bytes(include_bytes!("test_files/triangle.gltf")).unwrap();
+
+        assert_eq!(data.submeshes.len(), 1);
+        let submesh = &data.submeshes[0];
+        assert_eq!(submesh.vertices.len(), 3);
+        assert_eq!(submesh.indices, vec![0, 1, 2]);
+        assert_eq!(submesh.transform, Mat4::from_translation(Vec3::X));
+        assert_eq!(submesh.material, Some(0));
+        assert_eq!(data.materials[0].base_color, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(data.materials[0].metallic, 0.25);
+        assert_eq!(data.materials[0].roughness, 0.5);
+        assert_eq!(data.materials[0].emissive, Vec3::new(0.0, 0.5, 0.0));
+        assert_eq!(data.materials[0].normal_texture, None);
+    }
+
+    #[test]
+    fn test_gltf_skinned() {
+        let data = ModelData::from_gltf_bytes(include_bytes!("test_files/skinned.gltf")).unwrap();
+
+        let submesh = &data.submeshes[0];
+        assert_eq!(submesh.transform, Mat4::IDENTITY);
+        let skin = submesh.skin.as_ref().unwrap();
+        assert_eq!(skin.skeleton, 0);
+        assert_eq!(skin.weights[1].joints, [0, 1, 0, 0]);
+        assert_eq!(skin.weights[1].weights, [0.5, 0.5, 0.0, 0.0]);
+
+        let skeleton = &data.skeletons[0];
+        assert_eq!(skeleton.joints[0].name.as_deref(), Some("tip"));
+        assert_eq!(skeleton.joints[0].parent, Some(1));
+        assert_eq!(skeleton.joints[1].parent, None);
+        assert_eq!(
+            skeleton.root,
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0))
+        );
+        for matrix in skeleton.joint_matrices(&skeleton.rest_pose()) {
+            assert!(matrix.abs_diff_eq(Mat4::IDENTITY, 1e-6));
+        }
+
+        // The channel moving the armature, which isn't a joint, is left out.
+        let clip = &data.animations[0];
+        assert_eq!(clip.name.as_deref(), Some("wave"));
+        assert_eq!(clip.channels.len(), 2);
+        assert_eq!(clip.duration, 1.0);
+        let mut pose = skeleton.rest_pose();
+        clip.sample(0.75, &mut pose);
+        assert_eq!(pose.joints[0].translation, Vec3::new(0.0, 3.0, 0.0));
+        assert!(pose.joints[1].rotation.abs_diff_eq(
+            Quat::from_rotation_z(0.75 * std::f32::consts::FRAC_PI_2),
+            1e-5
+        ));
+    }
+}
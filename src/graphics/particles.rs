@@ -0,0 +1,335 @@
+use anyhow::Result;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::util::repository::{ResourceId, StrongResourceId};
+
+use super::{
+    texture::Texture, Index, Mesh, MeshData, RenderContext, RenderOperation, Vertex, VertexColor,
+};
+
+/// How a [ParticleEmitter] spawns and animates its particles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticleSettings {
+    /// Particles spawned per second while emitting.
+    pub spawn_rate: f32,
+    /// Shortest and longest a particle lives for, in seconds.
+    pub lifetime: (f32, f32),
+    /// Corners of the range each particle's starting velocity is picked from, in world
+    /// units per second.
+    pub velocity: (Vec2, Vec2),
+    /// Acceleration applied to every particle, in world units per second squared.
+    pub gravity: Vec2,
+    /// Color of a particle when it spawns, including its opacity.
+    pub start_color: Vec4,
+    /// Color a particle fades to by the end of its life.
+    pub end_color: Vec4,
+    /// Size of a particle in world units when it spawns.
+    pub start_scale: f32,
+    /// Size a particle shrinks or grows to by the end of its life.
+    pub end_scale: f32,
+    /// Most particles alive at once, past which no more spawn.
+    pub max_particles: usize,
+}
+
+/// Spawns 2d particles and simulates them on the cpu, drawing every particle as a
+/// textured quad in a single draw call.
+///
+/// Particles are laid out on the xy plane, at the depth of the emitter.
+pub struct ParticleEmitter {
+    pub settings: ParticleSettings,
+    /// Where particles spawn. The z coordinate is the depth they're drawn at.
+    pub position: Vec3,
+    /// Whether particles spawn over time at [ParticleSettings::spawn_rate].
+    pub emitting: bool,
+    particles: Vec<Particle>,
+    /// Fraction of a particle owed from previous updates.
+    spawn_debt: f32,
+    /// State of the xorshift generator, which is never zero.
+    random_state: u64,
+    /// Mesh the particles are written to, created on first draw.
+    mesh: Option<StrongResourceId<Mesh>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+}
+
+impl Default for ParticleSettings {
+    fn default() -> Self {
+        Self {
+            spawn_rate: 20.0,
+            lifetime: (1.0, 1.0),
+            velocity: (Vec2::new(-1.0, 1.0), Vec2::new(1.0, 2.0)),
+            gravity: Vec2::ZERO,
+            start_color: Vec4::ONE,
+            end_color: Vec4::new(1.0, 1.0, 1.0, 0.0),
+            start_scale: 0.25,
+            end_scale: 0.25,
+            max_particles: 1000,
+        }
+    }
+}
+
+impl ParticleSettings {
+    /// Sets how many particles spawn per second.
+    pub fn with_spawn_rate(mut self, spawn_rate: f32) -> Self {
+        self.spawn_rate = spawn_rate;
+        self
+    }
+
+    /// Sets the shortest and longest a particle lives for, in seconds.
+    pub fn with_lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = (min, max);
+        self
+    }
+
+    /// Sets the range each particle's starting velocity is picked from.
+    pub fn with_velocity(mut self, min: Vec2, max: Vec2) -> Self {
+        self.velocity = (min, max);
+        self
+    }
+
+    /// Sets the acceleration applied to every particle.
+    pub fn with_gravity(mut self, gravity: Vec2) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    /// Sets the color particles spawn with and fade to over their life.
+    pub fn with_colors(mut self, start: Vec4, end: Vec4) -> Self {
+        self.start_color = start;
+        self.end_color = end;
+        self
+    }
+
+    /// Sets the size particles spawn with and change to over their life.
+    pub fn with_scales(mut self, start: f32, end: f32) -> Self {
+        self.start_scale = start;
+        self.end_scale = end;
+        self
+    }
+
+    /// Sets the most particles alive at once.
+    pub fn with_max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+}
+
+impl ParticleEmitter {
+    /// Creates a [ParticleEmitter] that starts emitting, where the same seed always
+    /// spawns the same particles.
+    pub fn new(settings: ParticleSettings, position: Vec3, seed: u64) -> Self {
+        Self {
+            settings,
+            position,
+            emitting: true,
+            particles: Vec::new(),
+            spawn_debt: 0.0,
+            random_state: seed.max(1),
+            mesh: None,
+        }
+    }
+
+    /// Gets how many particles are alive.
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Spawns a number of particles at once, such as for an explosion, up to
+    /// [ParticleSettings::max_particles].
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    /// Removes every particle.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.spawn_debt = 0.0;
+    }
+
+    /// Advances the simulation by `delta` seconds, moving and aging particles, removing
+    /// those past their lifetime, and spawning new ones while emitting.
+    pub fn update(&mut self, delta: f32) {
+        let gravity = self.settings.gravity;
+        self.particles.retain_mut(|particle| {
+            particle.age += delta;
+            particle.velocity += gravity * delta;
+            particle.position += particle.velocity * delta;
+            particle.age < particle.lifetime
+        });
+
+        if self.emitting {
+            self.spawn_debt += self.settings.spawn_rate * delta;
+            while self.spawn_debt >= 1.0 {
+                self.spawn_debt -= 1.0;
+                self.spawn();
+            }
+        }
+    }
+
+    /// Gets the operation that draws every particle with a texture, such as one frame of
+    /// a sprite through `uv_window`, writing the particles to the emitter's mesh.
+    ///
+    /// Returns `None` while there are no particles.
+    pub fn render_operation(
+        &mut self,
+        context: &mut RenderContext,
+        texture_id: ResourceId<Texture>,
+        uv_window: Option<Vec4>,
+    ) -> Result<Option<RenderOperation>> {
+        if self.particles.is_empty() {
+            return Ok(None);
+        }
+
+        let (vertices, colors, indices) = self.quads();
+        let mesh_data = MeshData {
+            vertices: &vertices,
+            indices: &indices,
+        };
+        let mesh_id = match &self.mesh {
+            Some(mesh) => {
+                context.update_mesh_with_colors(mesh.id(), mesh_data, &colors)?;
+                mesh.id()
+            }
+            None => {
+                let mesh_id = context.load_mesh_with_colors(mesh_data, &colors)?;
+                self.mesh = Some(context.make_mesh_strong(mesh_id));
+                mesh_id
+            }
+        };
+
+        Ok(Some(
+            RenderOperation::textured_mesh(
+                Mat4::IDENTITY,
+                mesh_id,
+                texture_id,
+                uv_window,
+                Vec4::ONE,
+            )
+            .with_transparent(true),
+        ))
+    }
+
+    fn spawn(&mut self) {
+        if self.particles.len() >= self.settings.max_particles {
+            return;
+        }
+        let (min_lifetime, max_lifetime) = self.settings.lifetime;
+        let (min_velocity, max_velocity) = self.settings.velocity;
+        let lifetime = lerp(min_lifetime, max_lifetime, self.random());
+        let velocity = Vec2::new(
+            lerp(min_velocity.x, max_velocity.x, self.random()),
+            lerp(min_velocity.y, max_velocity.y, self.random()),
+        );
+        self.particles.push(Particle {
+            position: self.position.truncate(),
+            velocity,
+            age: 0.0,
+            lifetime,
+        });
+    }
+
+    /// Gets a random number from 0 to 1.
+    fn random(&mut self) -> f32 {
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.random_state = x;
+        (x >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Builds a quad for each particle, sized and colored by how far through its life
+    /// it is.
+    fn quads(&self) -> (Vec<Vertex>, Vec<VertexColor>, Vec<Index>) {
+        const CORNERS: [(Vec2, Vec2); 4] = [
+            (Vec2::new(-0.5, -0.5), Vec2::new(0.0, 1.0)),
+            (Vec2::new(0.5, -0.5), Vec2::new(1.0, 1.0)),
+            (Vec2::new(-0.5, 0.5), Vec2::new(0.0, 0.0)),
+            (Vec2::new(0.5, 0.5), Vec2::new(1.0, 0.0)),
+        ];
+
+        let settings = &self.settings;
+        let mut vertices = Vec::with_capacity(self.particles.len() * 4);
+        let mut colors = Vec::with_capacity(self.particles.len() * 4);
+        let mut indices = Vec::with_capacity(self.particles.len() * 6);
+        for particle in self.particles.iter() {
+            let t = match particle.lifetime > 0.0 {
+                true => (particle.age / particle.lifetime).clamp(0.0, 1.0),
+                false => 1.0,
+            };
+            let scale = lerp(settings.start_scale, settings.end_scale, t);
+            let color = settings.start_color.lerp(settings.end_color, t);
+            let color = (color.clamp(Vec4::ZERO, Vec4::ONE) * 255.0).round();
+
+            let first = vertices.len() as Index;
+            for (corner, texture_coordinates) in CORNERS {
+                vertices.push(Vertex {
+                    position: (particle.position + corner * scale).extend(self.position.z),
+                    normal: Vec3::Z,
+                    texture_coordinates,
+                });
+                colors.push([color.x as u8, color.y as u8, color.z as u8, color.w as u8]);
+            }
+            indices.extend([first, first + 1, first + 3, first, first + 3, first + 2]);
+        }
+        (vertices, colors, indices)
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_and_expire() {
+        let settings = ParticleSettings::default()
+            .with_spawn_rate(10.0)
+            .with_lifetime(0.5, 0.5)
+            .with_velocity(Vec2::X, Vec2::X)
+            .with_gravity(Vec2::new(0.0, -10.0))
+            .with_max_particles(8);
+        let mut emitter = ParticleEmitter::new(settings, Vec3::new(0.0, 0.0, 2.0), 7);
+
+        emitter.update(0.25);
+        assert_eq!(emitter.particle_count(), 2);
+        emitter.update(0.25);
+        assert_eq!(emitter.particle_count(), 5);
+
+        emitter.emitting = false;
+        emitter.burst(20);
+        assert_eq!(emitter.particle_count(), 8);
+        emitter.update(0.6);
+        assert_eq!(emitter.particle_count(), 0);
+    }
+
+    #[test]
+    fn test_quads_follow_lifetime() {
+        let settings = ParticleSettings::default()
+            .with_lifetime(1.0, 1.0)
+            .with_velocity(Vec2::ZERO, Vec2::ZERO)
+            .with_colors(Vec4::ONE, Vec4::ZERO)
+            .with_scales(2.0, 0.0);
+        let mut emitter = ParticleEmitter::new(settings, Vec3::new(1.0, 1.0, 3.0), 1);
+        emitter.emitting = false;
+        emitter.burst(1);
+        emitter.update(0.5);
+
+        let (vertices, colors, indices) = emitter.quads();
+        assert_eq!((vertices.len(), colors.len(), indices.len()), (4, 4, 6));
+        assert_eq!(vertices[0].position, Vec3::new(0.5, 0.5, 3.0));
+        assert_eq!(vertices[3].position, Vec3::new(1.5, 1.5, 3.0));
+        assert_eq!(colors[0], [128; 4]);
+    }
+}
@@ -0,0 +1,32 @@
+pub use wgpu::AdapterInfo;
+
+/// Which graphics adapter (GPU) to render with.
+///
+/// This is serializable so a choice made in a settings menu can be saved and used
+/// at the next startup, such as with the `adapter` setting of the engine config file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdapterSelection {
+    /// Prefer a discrete or external GPU.
+    #[default]
+    HighPerformance,
+    /// Prefer an integrated GPU.
+    LowPower,
+    /// Use the adapter with this name, as reported by [AdapterInfo::name].
+    ///
+    /// Falls back to [AdapterSelection::HighPerformance] if no such adapter exists,
+    /// for example when an external GPU has been unplugged.
+    Named(String),
+}
+
+impl AdapterSelection {
+    /// Gets the power preference to request an adapter with, if not selecting by name.
+    pub(crate) fn power_preference(&self) -> wgpu::PowerPreference {
+        match self {
+            AdapterSelection::LowPower => wgpu::PowerPreference::LowPower,
+            AdapterSelection::HighPerformance | AdapterSelection::Named(_) => {
+                wgpu::PowerPreference::HighPerformance
+            }
+        }
+    }
+}
@@ -0,0 +1,23 @@
+
+// Uniforms of every operation in a multi-draw, indexed by the draw's first instance.
+@group(2) @binding(0)
+var<storage, read> batched_locals: array<Local>;
+
+@vertex
+fn vs_batched(
+    in: VertexInput,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let operation = batched_locals[instance_index];
+    var out = transform_vertex(in, operation);
+    // The color is the same across the operation, so it's applied per vertex.
+    out.color *= vec4<f32>(operation.color.rgb * operation.color.a, operation.color.a);
+    return out;
+}
+
+@fragment
+fn fs_batched(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    return shade_default(in, vec4<f32>(1.0));
+}
@@ -0,0 +1,358 @@
+use anyhow::Result;
+use glam::UVec2;
+
+use crate::{graphics::texture::Texture, util::repository::ResourceId};
+
+use super::{
+    post_process::{self, FullscreenPass, PostProcessor, INTERMEDIATE_FORMAT},
+    RenderContext, RenderTarget,
+};
+
+const SHADER_SOURCE: &str = include_str!("bloom.wgsl");
+
+/// Settings of a [Bloom] effect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomSettings {
+    /// Brightness above which colors start to glow.
+    pub threshold: f32,
+    /// Range below the threshold over which the glow eases in, so it doesn't start
+    /// abruptly.
+    pub knee: f32,
+    /// Strength of the glow added to the scene.
+    pub intensity: f32,
+    /// How far the glow spreads, from 0 for a tight glow to 1 for a wide haze.
+    pub scatter: f32,
+    /// Number of times the image is halved in size, where more passes spread the glow
+    /// further.
+    pub passes: u32,
+}
+
+/// Makes bright parts of the scene glow, see [RenderContext::apply_bloom].
+pub struct Bloom {
+    /// Settings of the effect, which can be changed at any time.
+    pub settings: BloomSettings,
+    /// Chain of textures, each half the size of the one before.
+    mips: Vec<Texture>,
+    /// Size of the source the chain was created for.
+    source_size: UVec2,
+    /// Generation of the device the textures were created on, so they are created
+    /// again after [RenderContext::recover].
+    device_generation: u32,
+}
+
+/// Pipelines for each step of bloom.
+pub(crate) struct BloomPipelines {
+    prefilter: wgpu::RenderPipeline,
+    downsample: wgpu::RenderPipeline,
+    upsample: wgpu::RenderPipeline,
+    combine: wgpu::RenderPipeline,
+    /// Combine pipeline drawing to an intermediate texture, for the middle of a
+    /// [super::PostProcessStack].
+    combine_intermediate: wgpu::RenderPipeline,
+}
+
+/// Uniforms of the bloom shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BloomUniforms {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Zeroable for BloomUniforms {}
+unsafe impl bytemuck::Pod for BloomUniforms {}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            knee: 0.4,
+            intensity: 0.8,
+            scatter: 0.7,
+            passes: 5,
+        }
+    }
+}
+
+impl BloomSettings {
+    /// Sets the brightness above which colors glow.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the strength of the glow.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Sets how far the glow spreads.
+    pub fn with_scatter(mut self, scatter: f32) -> Self {
+        self.scatter = scatter;
+        self
+    }
+
+    /// Sets the number of downsample passes.
+    pub fn with_passes(mut self, passes: u32) -> Self {
+        self.passes = passes;
+        self
+    }
+}
+
+impl Bloom {
+    /// Creates a new [Bloom]. Its textures are created the first time it is applied.
+    pub fn new(settings: BloomSettings) -> Self {
+        Self {
+            settings,
+            mips: Vec::new(),
+            source_size: UVec2::ZERO,
+            device_generation: 0,
+        }
+    }
+
+    /// Creates the chain of textures for a source, unless it's the size of the last
+    /// and on the same device.
+    ///
+    /// Returns an error if the source is too small to halve.
+    pub(crate) fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        device_generation: u32,
+        source_size: UVec2,
+    ) -> Result<()> {
+        let sizes = mip_sizes(source_size, self.settings.passes);
+        if self.source_size != source_size
+            || self.mips.len() != sizes.len()
+            || self.device_generation != device_generation
+        {
+            self.mips = sizes
+                .iter()
+                .map(|&size| post_process::create_intermediate_texture(device, size))
+                .collect();
+            self.source_size = source_size;
+            self.device_generation = device_generation;
+        }
+        anyhow::ensure!(
+            !self.mips.is_empty(),
+            "a {source_size} source is too small for bloom"
+        );
+        Ok(())
+    }
+
+    /// Encodes the passes drawing `source` to `target` with bright parts glowing, once
+    /// [Bloom::prepare]d for the source. `output` targets are in the surface's format
+    /// rather than the intermediate one.
+    pub(crate) fn encode(
+        &self,
+        device: &wgpu::Device,
+        post_processor: &PostProcessor,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+        output: bool,
+    ) {
+        let pipelines = post_processor
+            .bloom
+            .as_ref()
+            .expect("bloom pipelines created");
+        let settings = self.settings;
+        let uniforms = BloomUniforms {
+            threshold: settings.threshold,
+            knee: settings.knee.max(0.0),
+            intensity: settings.intensity,
+            _padding: 0.0,
+        };
+        let uniforms = bytemuck::bytes_of(&uniforms);
+
+        // Threshold while halving, then keep halving.
+        post_processor.encode(
+            device,
+            encoder,
+            FullscreenPass {
+                label: "clockwork bloom prefilter pass",
+                pipeline: &pipelines.prefilter,
+                source,
+                secondary: None,
+                uniforms,
+                target: &self.mips[0].view,
+                blend_constant: None,
+            },
+        );
+        for pair in self.mips.windows(2) {
+            post_processor.encode(
+                device,
+                encoder,
+                FullscreenPass {
+                    label: "clockwork bloom downsample pass",
+                    pipeline: &pipelines.downsample,
+                    source: &pair[0].view,
+                    secondary: None,
+                    uniforms,
+                    target: &pair[1].view,
+                    blend_constant: None,
+                },
+            );
+        }
+
+        // Work back up, spreading each level into the one above.
+        for pair in self.mips.windows(2).rev() {
+            post_processor.encode(
+                device,
+                encoder,
+                FullscreenPass {
+                    label: "clockwork bloom upsample pass",
+                    pipeline: &pipelines.upsample,
+                    source: &pair[1].view,
+                    secondary: None,
+                    uniforms,
+                    target: &pair[0].view,
+                    blend_constant: Some(settings.scatter.clamp(0.0, 1.0) as f64),
+                },
+            );
+        }
+
+        post_processor.encode(
+            device,
+            encoder,
+            FullscreenPass {
+                label: "clockwork bloom combine pass",
+                pipeline: match output {
+                    true => &pipelines.combine,
+                    false => &pipelines.combine_intermediate,
+                },
+                source,
+                secondary: Some(&self.mips[0].view),
+                uniforms,
+                target,
+                blend_constant: None,
+            },
+        );
+    }
+}
+
+/// Gets the size of each texture in the downsample chain, stopping early once the
+/// image can't get any smaller.
+fn mip_sizes(source_size: UVec2, passes: u32) -> Vec<UVec2> {
+    (1..=passes.clamp(1, 16))
+        .map(|level| source_size >> level)
+        .take_while(|size| size.cmpge(UVec2::ONE).all())
+        .collect()
+}
+
+impl BloomPipelines {
+    pub(crate) fn new(device: &wgpu::Device, post_processor: &PostProcessor) -> Self {
+        let shader = post_process::create_shader(device, "clockwork bloom shader", SHADER_SOURCE);
+        let pipeline = |entry_point, format, blend| {
+            post_processor.create_pipeline(
+                device,
+                "clockwork bloom pipeline",
+                &shader,
+                entry_point,
+                format,
+                blend,
+            )
+        };
+
+        // Each upsample is mixed into the level above by the scatter.
+        let scatter_blend = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::OneMinusConstant,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        Self {
+            prefilter: pipeline("fs_prefilter", INTERMEDIATE_FORMAT, None),
+            downsample: pipeline("fs_downsample", INTERMEDIATE_FORMAT, None),
+            upsample: pipeline(
+                "fs_upsample",
+                INTERMEDIATE_FORMAT,
+                Some(wgpu::BlendState {
+                    color: scatter_blend,
+                    alpha: scatter_blend,
+                }),
+            ),
+            combine: pipeline("fs_combine", post_processor.color_format, None),
+            combine_intermediate: pipeline("fs_combine", INTERMEDIATE_FORMAT, None),
+        }
+    }
+}
+
+impl RenderContext {
+    /// Draws `source` to `target` with bright parts glowing.
+    ///
+    /// Render the scene to a texture from [RenderContext::create_render_target], then
+    /// apply bloom to draw it to the surface. Like render passes, nothing is drawn to the
+    /// surface outside of a frame.
+    pub fn apply_bloom(
+        &mut self,
+        bloom: &mut Bloom,
+        source: ResourceId<Texture>,
+        target: RenderTarget,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            target != RenderTarget::Texture(source),
+            "bloom can't draw to its own source {source:?}"
+        );
+        let source_size = self
+            .textures
+            .get(source)
+            .ok_or_else(|| anyhow::anyhow!("no texture {source:?}"))?
+            .size;
+
+        bloom.prepare(&self.device, self.device_generation, source_size)?;
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(device, color_format));
+        if post_processor.bloom.is_none() {
+            post_processor.bloom = Some(BloomPipelines::new(device, post_processor));
+        }
+
+        let Some(target_view) = self.post_process_target_view(target)? else {
+            return Ok(());
+        };
+        let post_processor = self.post_processor.as_ref().expect("created above");
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork bloom encoder"),
+            }),
+        );
+        bloom.encode(
+            &self.device,
+            post_processor,
+            &mut encoder,
+            &self.textures[source].view,
+            target_view,
+            true,
+        );
+
+        self.submit(encoder);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mip_sizes() {
+        assert_eq!(
+            mip_sizes(UVec2::new(100, 40), 3),
+            vec![UVec2::new(50, 20), UVec2::new(25, 10), UVec2::new(12, 5)]
+        );
+        // Stops before either side reaches zero.
+        assert_eq!(mip_sizes(UVec2::new(8, 4), 10).len(), 2);
+    }
+
+    #[test]
+    fn test_shader_validates() {
+        post_process::validate_shader(SHADER_SOURCE);
+    }
+}
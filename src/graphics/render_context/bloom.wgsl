@@ -0,0 +1,83 @@
+struct BloomUniforms {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+@group(0) @binding(3)
+var<uniform> bloom: BloomUniforms;
+
+fn texel_size(texture: texture_2d<f32>) -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(texture));
+}
+
+fn tap(texture: texture_2d<f32>, uv: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+    return textureSample(texture, source_sampler, uv + offset * texel_size(texture)).rgb;
+}
+
+// Downsamples with 13 taps weighted so bright pixels don't flicker as they move.
+fn downsample(texture: texture_2d<f32>, uv: vec2<f32>) -> vec3<f32> {
+    let a = tap(texture, uv, vec2<f32>(-2.0, 2.0));
+    let b = tap(texture, uv, vec2<f32>(0.0, 2.0));
+    let c = tap(texture, uv, vec2<f32>(2.0, 2.0));
+    let d = tap(texture, uv, vec2<f32>(-2.0, 0.0));
+    let e = tap(texture, uv, vec2<f32>(0.0, 0.0));
+    let f = tap(texture, uv, vec2<f32>(2.0, 0.0));
+    let g = tap(texture, uv, vec2<f32>(-2.0, -2.0));
+    let h = tap(texture, uv, vec2<f32>(0.0, -2.0));
+    let i = tap(texture, uv, vec2<f32>(2.0, -2.0));
+    let j = tap(texture, uv, vec2<f32>(-1.0, 1.0));
+    let k = tap(texture, uv, vec2<f32>(1.0, 1.0));
+    let l = tap(texture, uv, vec2<f32>(-1.0, -1.0));
+    let m = tap(texture, uv, vec2<f32>(1.0, -1.0));
+
+    return e * 0.125
+        + (a + c + g + i) * 0.03125
+        + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
+
+// Upsamples with a 3x3 tent filter.
+fn upsample(texture: texture_2d<f32>, uv: vec2<f32>) -> vec3<f32> {
+    var color = tap(texture, uv, vec2<f32>(0.0, 0.0)) * 4.0;
+    color += (tap(texture, uv, vec2<f32>(-1.0, 0.0))
+        + tap(texture, uv, vec2<f32>(1.0, 0.0))
+        + tap(texture, uv, vec2<f32>(0.0, -1.0))
+        + tap(texture, uv, vec2<f32>(0.0, 1.0))) * 2.0;
+    color += tap(texture, uv, vec2<f32>(-1.0, -1.0))
+        + tap(texture, uv, vec2<f32>(1.0, -1.0))
+        + tap(texture, uv, vec2<f32>(-1.0, 1.0))
+        + tap(texture, uv, vec2<f32>(1.0, 1.0));
+    return color / 16.0;
+}
+
+// Keeps only the parts of a color brighter than the threshold, easing in over the knee.
+fn threshold(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - bloom.threshold + bloom.knee, 0.0, 2.0 * bloom.knee);
+    soft = soft * soft / (4.0 * bloom.knee + 0.00001);
+    let contribution = max(soft, brightness - bloom.threshold) / max(brightness, 0.00001);
+    return color * contribution;
+}
+
+@fragment
+fn fs_prefilter(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(threshold(downsample(source_texture, in.uv)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(source_texture, in.uv), 1.0);
+}
+
+@fragment
+fn fs_upsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(upsample(source_texture, in.uv), 1.0);
+}
+
+@fragment
+fn fs_combine(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let scene = textureSample(source_texture, source_sampler, in.uv);
+    let glow = upsample(secondary_texture, in.uv) * bloom.intensity;
+    return vec4<f32>(scene.rgb + glow, scene.a);
+}
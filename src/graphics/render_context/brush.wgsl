@@ -0,0 +1,61 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+struct Global {
+    mvp: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> global: Global;
+
+struct Local {
+    transform: mat4x4<f32>,
+    uv_window: vec4<f32>,
+}
+@group(0) @binding(1)
+var<uniform> local: Local;
+
+@group(1) @binding(0)
+var texture_sampler: sampler;
+@group(1) @binding(1)
+var texture: texture_2d<f32>;
+
+struct Brush {
+    color: vec4<f32>,
+    hardness: f32,
+    // 1 to fade the edges out in a circle, 0 to use the stamp texture as is.
+    circular: u32,
+}
+@group(2) @binding(0)
+var<uniform> brush: Brush;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = global.mvp * local.transform * vec4<f32>(in.position, 1.0);
+    out.uv = local.uv_window.xy + (local.uv_window.zw * in.uv);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = brush.color * textureSample(texture, texture_sampler, in.uv);
+    if (brush.circular == 1u) {
+        let distance = length(in.uv * 2.0 - 1.0);
+        color.w *= 1.0 - smoothstep(min(brush.hardness, 0.999), 1.0, distance);
+    }
+
+    if (color.w < 0.001) {
+        discard;
+    }
+
+    // The pipeline blends with premultiplied alpha.
+    return vec4<f32>(color.xyz * color.w, color.w);
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use super::{AdapterInfo, RenderContext};
+
+/// Sample counts a texture format can be multisampled with, from fewest to most.
+const SAMPLE_COUNTS: [u32; 5] = [1, 2, 4, 8, 16];
+
+/// What the adapter (GPU) being rendered with is and supports, see
+/// [RenderContext::adapter_info].
+///
+/// Applications can pick quality settings from it, and its [fmt::Display] output is
+/// meant to be included in bug reports.
+#[derive(Clone, Debug)]
+pub struct GraphicsCapabilities {
+    /// Name, vendor, driver and backend of the adapter.
+    pub info: AdapterInfo,
+    /// Limits of the device the engine opened, which are what rendering can rely on.
+    pub limits: wgpu::Limits,
+    /// Optional features the adapter supports.
+    pub features: wgpu::Features,
+    /// Format the surface is rendered in.
+    pub color_format: wgpu::TextureFormat,
+    /// Sample counts [GraphicsCapabilities::color_format] can be multisampled with.
+    pub msaa_sample_counts: Vec<u32>,
+    /// Formats the surface supports, see [crate::graphics::OutputFormat].
+    pub surface_formats: Vec<wgpu::TextureFormat>,
+}
+
+impl GraphicsCapabilities {
+    /// Checks if the surface's format can be multisampled with `sample_count`
+    /// samples.
+    pub fn supports_msaa(&self, sample_count: u32) -> bool {
+        self.msaa_sample_counts.contains(&sample_count)
+    }
+
+    /// Gets the most samples the surface's format can be multisampled with.
+    pub fn max_msaa_sample_count(&self) -> u32 {
+        self.msaa_sample_counts.iter().copied().max().unwrap_or(1)
+    }
+}
+
+/// Gets the sample counts a format with the given features can be multisampled with.
+fn sample_counts(flags: wgpu::TextureFormatFeatureFlags) -> Vec<u32> {
+    SAMPLE_COUNTS
+        .into_iter()
+        .filter(|&count| flags.sample_count_supported(count))
+        .collect()
+}
+
+impl RenderContext {
+    /// Gets what the adapter (GPU) being rendered with is and supports, including its
+    /// backend, name, limits, features and multisampling support.
+    pub fn adapter_info(&self) -> GraphicsCapabilities {
+        let color_format = self.color_format();
+        GraphicsCapabilities {
+            info: self.adapter.get_info(),
+            limits: self.device.limits(),
+            features: self.adapter.features(),
+            color_format,
+            msaa_sample_counts: sample_counts(self.texture_format_features(color_format).flags),
+            surface_formats: self
+                .surface
+                .as_ref()
+                .map(|surface| surface.get_capabilities(&self.adapter).formats)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Gets how the adapter supports a texture format, such as whether it can be
+    /// rendered to, filtered or multisampled.
+    pub fn texture_format_features(
+        &self,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::TextureFormatFeatures {
+        self.adapter.get_texture_format_features(format)
+    }
+}
+
+impl fmt::Display for GraphicsCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "clockwork graphics capabilities")?;
+        writeln!(
+            f,
+            "  adapter: {} ({:?}, {:?})",
+            self.info.name, self.info.device_type, self.info.backend
+        )?;
+        writeln!(
+            f,
+            "  driver: {} {}",
+            self.info.driver, self.info.driver_info
+        )?;
+        writeln!(
+            f,
+            "  vendor: {:#06x}, device: {:#06x}",
+            self.info.vendor, self.info.device
+        )?;
+        writeln!(f, "  color format: {:?}", self.color_format)?;
+        writeln!(f, "  msaa sample counts: {:?}", self.msaa_sample_counts)?;
+        writeln!(f, "  surface formats: {:?}", self.surface_formats)?;
+        writeln!(
+            f,
+            "  max texture size: {}, max texture array layers: {}",
+            self.limits.max_texture_dimension_2d, self.limits.max_texture_array_layers
+        )?;
+        writeln!(f, "  features: {:?}", self.features)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msaa_support() {
+        let flags = wgpu::TextureFormatFeatureFlags::MULTISAMPLE_X2
+            | wgpu::TextureFormatFeatureFlags::MULTISAMPLE_X4;
+        let capabilities = GraphicsCapabilities {
+            info: AdapterInfo {
+                name: "Test GPU".to_string(),
+                vendor: 0x10de,
+                device: 0x2204,
+                device_type: wgpu::DeviceType::DiscreteGpu,
+                driver: "test".to_string(),
+                driver_info: "1.0".to_string(),
+                backend: wgpu::Backend::Vulkan,
+            },
+            limits: wgpu::Limits::default(),
+            features: wgpu::Features::empty(),
+            color_format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            msaa_sample_counts: sample_counts(flags),
+            surface_formats: vec![wgpu::TextureFormat::Bgra8UnormSrgb],
+        };
+        assert_eq!(capabilities.msaa_sample_counts, vec![1, 2, 4]);
+        assert!(capabilities.supports_msaa(4) && !capabilities.supports_msaa(8));
+        assert_eq!(capabilities.max_msaa_sample_count(), 4);
+
+        let report = capabilities.to_string();
+        assert!(report.contains("adapter: Test GPU (DiscreteGpu, Vulkan)"));
+        assert!(report.contains("vendor: 0x10de, device: 0x2204"));
+    }
+}
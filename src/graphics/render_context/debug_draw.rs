@@ -0,0 +1,365 @@
+use std::f32::consts::TAU;
+
+use anyhow::Result;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::util::{
+    collision::Aabb,
+    shadow_frustum::{frustum_corners, FRUSTUM_EDGES},
+};
+
+use super::{RenderContext, RenderTarget};
+
+const SHADER_SOURCE: &str = include_str!("debug_draw.wgsl");
+
+/// Number of segments circles are drawn with.
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Lines queued to be drawn on top of the scene, see [RenderContext::debug_draw].
+#[derive(Clone, Debug, Default)]
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for DebugVertex {}
+unsafe impl bytemuck::Pod for DebugVertex {}
+
+/// Resources for drawing debug lines, created the first time they are drawn.
+pub(crate) struct DebugDrawer {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// Vertex buffer, which grows to fit the most lines drawn at once.
+    vertex_buffer: wgpu::Buffer,
+}
+
+impl DebugDraw {
+    /// Queues a line.
+    pub fn draw_line(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+        let color = color.to_array();
+        self.vertices.extend([
+            DebugVertex {
+                position: start.to_array(),
+                color,
+            },
+            DebugVertex {
+                position: end.to_array(),
+                color,
+            },
+        ]);
+    }
+
+    /// Queues the outline of a 2D [Aabb], such as a collider, at z = 0.
+    pub fn draw_aabb(&mut self, aabb: &Aabb, color: Vec4) {
+        let corners = [
+            aabb.min,
+            Vec2::new(aabb.max.x, aabb.min.y),
+            aabb.max,
+            Vec2::new(aabb.min.x, aabb.max.y),
+        ];
+        for (index, corner) in corners.iter().enumerate() {
+            let next = corners[(index + 1) % corners.len()];
+            self.draw_line(corner.extend(0.0), next.extend(0.0), color);
+        }
+    }
+
+    /// Queues the edges of a 3D box.
+    pub fn draw_box(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        // Same corner order as [frustum_corners], so the frustum edges apply.
+        let corners: Vec<Vec3> = (0..8)
+            .map(|index| {
+                Vec3::new(
+                    if index & 1 == 0 { min.x } else { max.x },
+                    if index & 2 == 0 { min.y } else { max.y },
+                    if index & 4 == 0 { min.z } else { max.z },
+                )
+            })
+            .collect();
+        for (start, end) in FRUSTUM_EDGES {
+            self.draw_line(corners[start], corners[end], color);
+        }
+    }
+
+    /// Queues the edges of the frustum described by a view projection matrix, such as
+    /// another camera's.
+    pub fn draw_frustum(&mut self, view_projection: Mat4, color: Vec4) {
+        let corners = frustum_corners(view_projection);
+        for (start, end) in FRUSTUM_EDGES {
+            self.draw_line(corners[start], corners[end], color);
+        }
+    }
+
+    /// Queues a circle facing along `normal`, where a normal of [Vec3::Z] gives a circle
+    /// in the xy plane for 2D.
+    pub fn draw_circle(&mut self, center: Vec3, radius: f32, normal: Vec3, color: Vec4) {
+        let (tangent, bitangent) = normal.normalize_or_zero().any_orthonormal_pair();
+        let point = |segment: usize| {
+            let angle = segment as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+            center + (tangent * angle.cos() + bitangent * angle.sin()) * radius
+        };
+        for segment in 0..CIRCLE_SEGMENTS {
+            self.draw_line(point(segment), point(segment + 1), color);
+        }
+    }
+
+    /// Queues a square grid of `cells` by `cells` cells centered on `center`, spanning
+    /// the plane of `axes`, such as `(Vec3::X, Vec3::Y)` for 2D or `(Vec3::X, Vec3::Z)`
+    /// for a floor.
+    pub fn draw_grid(
+        &mut self,
+        center: Vec3,
+        axes: (Vec3, Vec3),
+        cell_size: f32,
+        cells: u32,
+        color: Vec4,
+    ) {
+        let (u, v) = (axes.0 * cell_size, axes.1 * cell_size);
+        let half = cells as f32 / 2.0;
+        for line in 0..=cells {
+            let offset = line as f32 - half;
+            self.draw_line(
+                center + u * offset - v * half,
+                center + u * offset + v * half,
+                color,
+            );
+            self.draw_line(
+                center + v * offset - u * half,
+                center + v * offset + u * half,
+                color,
+            );
+        }
+    }
+
+    /// Gets the number of lines queued.
+    pub fn line_count(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    /// Removes every queued line.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+impl DebugDrawer {
+    pub(crate) fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("clockwork debug draw shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER_SOURCE.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork debug draw bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            }),
+        );
+        let uniform_buffer = device.create_buffer_init(
+            &(wgpu::util::BufferInitDescriptor {
+                label: Some("clockwork debug draw uniform buffer"),
+                contents: bytemuck::bytes_of(&Mat4::IDENTITY.to_cols_array_2d()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }),
+        );
+        let bind_group = device.create_bind_group(
+            &(wgpu::BindGroupDescriptor {
+                label: Some("clockwork debug draw bind group"),
+                layout: &bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+            }),
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some("clockwork debug draw pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            }),
+        );
+        let pipeline = device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some("clockwork debug draw pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                // Lines are drawn over everything so they're never hidden.
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }),
+        );
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            vertex_buffer: create_vertex_buffer(device, 0),
+        }
+    }
+}
+
+/// Creates a vertex buffer with room for at least `vertices` vertices.
+fn create_vertex_buffer(device: &wgpu::Device, vertices: usize) -> wgpu::Buffer {
+    device.create_buffer(
+        &(wgpu::BufferDescriptor {
+            label: Some("clockwork debug draw vertex buffer"),
+            size: (vertices.max(256).next_power_of_two() * std::mem::size_of::<DebugVertex>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }),
+    )
+}
+
+impl RenderContext {
+    /// Gets the queue of debug lines, which are drawn by
+    /// [RenderContext::render_debug_draw]. Lines can be queued at any point in a frame.
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
+
+    /// Draws the queued debug lines on top of everything in `target`, then clears the
+    /// queue. Call this after the scene's render passes.
+    ///
+    /// Like render passes, nothing is drawn to the surface outside of a frame, though
+    /// the queue is still cleared.
+    pub fn render_debug_draw(&mut self, view_projection: Mat4, target: RenderTarget) -> Result<()> {
+        let vertices = std::mem::take(&mut self.debug_draw.vertices);
+        let target_view = match target {
+            RenderTarget::Surface => match &self.frame {
+                Some(frame) => &frame.view,
+                None => return Ok(()),
+            },
+            RenderTarget::Texture(texture_id) => {
+                &self
+                    .textures
+                    .get(texture_id)
+                    .ok_or_else(|| anyhow::anyhow!("no texture {texture_id:?}"))?
+                    .view
+            }
+        };
+        if vertices.is_empty() {
+            return Ok(());
+        }
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let drawer = self
+            .debug_drawer
+            .get_or_insert_with(|| DebugDrawer::new(device, color_format));
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        if drawer.vertex_buffer.size() < vertex_bytes.len() as wgpu::BufferAddress {
+            drawer.vertex_buffer = create_vertex_buffer(device, vertices.len());
+        }
+        self.queue
+            .write_buffer(&drawer.vertex_buffer, 0, vertex_bytes);
+        self.queue.write_buffer(
+            &drawer.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&view_projection.to_cols_array_2d()),
+        );
+        self.counters
+            .upload(vertex_bytes.len() + std::mem::size_of::<Mat4>());
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork debug draw encoder"),
+            }),
+        );
+        {
+            let mut render_pass = encoder.begin_render_pass(
+                &(wgpu::RenderPassDescriptor {
+                    label: Some("clockwork debug draw pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: target_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                }),
+            );
+            render_pass.set_pipeline(&drawer.pipeline);
+            render_pass.set_bind_group(0, &drawer.bind_group, &[]);
+            render_pass
+                .set_vertex_buffer(0, drawer.vertex_buffer.slice(..vertex_bytes.len() as u64));
+            render_pass.draw(0..vertices.len() as u32, 0..1);
+            self.counters.draw(1);
+        }
+
+        self.submit(encoder);
+
+        // Keep the allocation for next frame's lines.
+        self.debug_draw.vertices = vertices;
+        self.debug_draw.vertices.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shapes_queue_lines() {
+        let mut debug_draw = DebugDraw::default();
+        debug_draw.draw_aabb(&Aabb::new(Vec2::ZERO, Vec2::ONE), Vec4::ONE);
+        assert_eq!(debug_draw.line_count(), 4);
+        debug_draw.draw_box(Vec3::ZERO, Vec3::ONE, Vec4::ONE);
+        assert_eq!(debug_draw.line_count(), 16);
+        debug_draw.draw_grid(Vec3::ZERO, (Vec3::X, Vec3::Y), 1.0, 4, Vec4::ONE);
+        assert_eq!(debug_draw.line_count(), 26);
+
+        debug_draw.clear();
+        debug_draw.draw_circle(Vec3::ONE, 2.0, Vec3::Z, Vec4::ONE);
+        assert!(debug_draw
+            .vertices
+            .iter()
+            .all(
+                |vertex| ((Vec3::from(vertex.position) - Vec3::ONE).length() - 2.0).abs() < 1e-5
+                    && (vertex.position[2] - 1.0).abs() < 1e-5
+            ));
+    }
+}
@@ -0,0 +1,81 @@
+use super::RenderContext;
+
+/// What operations show in place of their shading, to diagnose rendering issues, see
+/// [RenderContext::set_debug_view].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugView {
+    /// Depth as grayscale, brighter the closer to the camera.
+    Depth,
+    /// How many fragments land on each pixel, built up in orange as operations are
+    /// blended over each other, to find where hidden geometry is drawn.
+    Overdraw,
+    /// World space normals, with each axis mapped from -1..1 to a color channel.
+    Normals,
+    /// A checkerboard over each uv tile, tinted by the uv, to find stretched, flipped
+    /// or mismatched texture coordinates.
+    UvChecker,
+    /// Mip level the texture is sampled at, from green for the full texture to red for
+    /// the sixth level and beyond, to find textures larger than they're drawn.
+    MipLevel,
+}
+
+impl DebugView {
+    /// Every debug view, such as to cycle through them with a key.
+    pub const ALL: [DebugView; 5] = [
+        DebugView::Depth,
+        DebugView::Overdraw,
+        DebugView::Normals,
+        DebugView::UvChecker,
+        DebugView::MipLevel,
+    ];
+
+    /// Gets the value the shaders switch on, where 0 is no debug view.
+    pub(crate) fn to_raw(debug_view: Option<DebugView>) -> u32 {
+        match debug_view {
+            None => 0,
+            Some(DebugView::Depth) => 1,
+            Some(DebugView::Overdraw) => 2,
+            Some(DebugView::Normals) => 3,
+            Some(DebugView::UvChecker) => 4,
+            Some(DebugView::MipLevel) => 5,
+        }
+    }
+}
+
+impl RenderContext {
+    /// Sets what operations show in place of their shading for render passes made after
+    /// this, or `None` to shade them normally, which is the default.
+    ///
+    /// Debug views apply to the built in materials. Operations with a
+    /// [super::CustomMaterial] keep their own shading, but are blended like everything
+    /// else while [DebugView::Overdraw] is shown.
+    pub fn set_debug_view(&mut self, debug_view: Option<DebugView>) {
+        self.debug_view = debug_view;
+    }
+
+    /// Gets the debug view shown, see [RenderContext::set_debug_view].
+    pub fn debug_view(&self) -> Option<DebugView> {
+        self.debug_view
+    }
+
+    /// Whether every operation is drawn blended so overdraw builds up.
+    pub(crate) fn shows_overdraw(&self) -> bool {
+        self.debug_view == Some(DebugView::Overdraw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_debug_views() {
+        assert_eq!(DebugView::to_raw(None), 0);
+        let raw: Vec<u32> = DebugView::ALL
+            .iter()
+            .map(|debug_view| DebugView::to_raw(Some(*debug_view)))
+            .collect();
+        // Matches the cases of apply_debug_view in shader.wgsl.
+        assert_eq!(raw, vec![1, 2, 3, 4, 5]);
+    }
+}
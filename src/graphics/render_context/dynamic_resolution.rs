@@ -0,0 +1,300 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use glam::{UVec2, Vec2};
+
+use crate::{graphics::texture::Texture, util::repository::ResourceId};
+
+use super::{
+    post_process::{self, FullscreenPass, PostProcessor},
+    RenderContext, RenderTarget,
+};
+
+const SHADER_SOURCE: &str = include_str!("dynamic_resolution.wgsl");
+
+/// Fraction of the target frame time the gpu has to be under before the scale grows,
+/// so it doesn't bounce around the target.
+const GROW_HEADROOM: f32 = 0.85;
+
+/// How much of the way to the scale that would hit the target frame time is moved each
+/// frame.
+const ADJUST_RATE: f32 = 0.25;
+
+/// Settings of [DynamicResolution].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DynamicResolutionSettings {
+    /// Time the gpu should take per frame, such as 16ms to hold 60 frames per second.
+    pub target_frame_time: Duration,
+    /// Smallest fraction of the window's resolution the scene is rendered at.
+    pub min_scale: f32,
+    /// Largest fraction of the window's resolution the scene is rendered at.
+    pub max_scale: f32,
+}
+
+impl Default for DynamicResolutionSettings {
+    fn default() -> Self {
+        Self {
+            target_frame_time: Duration::from_micros(16_667),
+            min_scale: 0.5,
+            max_scale: 1.0,
+        }
+    }
+}
+
+impl DynamicResolutionSettings {
+    /// Creates [DynamicResolutionSettings] aiming for a gpu frame time.
+    pub fn new(target_frame_time: Duration) -> Self {
+        Self {
+            target_frame_time,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the smallest fraction of the window's resolution rendered at.
+    pub fn with_min_scale(mut self, min_scale: f32) -> Self {
+        self.min_scale = min_scale;
+        self
+    }
+
+    /// Sets the largest fraction of the window's resolution rendered at.
+    pub fn with_max_scale(mut self, max_scale: f32) -> Self {
+        self.max_scale = max_scale;
+        self
+    }
+}
+
+/// Scene resolution that follows the load on the gpu, rendering fewer pixels when
+/// frames take longer than [DynamicResolutionSettings::target_frame_time].
+///
+/// Each frame, after [RenderContext::begin_frame]:
+/// 1. [RenderContext::update_dynamic_resolution] picks the scale for the frame.
+/// 2. Scene passes draw into it with
+///    [super::RenderPassDescriptor::with_dynamic_resolution].
+/// 3. [RenderContext::upscale_dynamic_resolution] stretches the scene over the window.
+/// 4. UI passes draw to the surface at the window's resolution.
+///
+/// The texture is sized for [DynamicResolutionSettings::max_scale] and only part of it
+/// is drawn to, so changing the scale never reallocates it mid-game.
+pub struct DynamicResolution {
+    settings: DynamicResolutionSettings,
+    scale: f32,
+    /// Texture the scene is drawn to, along with the size of the surface it was
+    /// created for.
+    texture: Option<(ResourceId<Texture>, UVec2)>,
+    /// Size of the region of the texture drawn to this frame.
+    render_size: UVec2,
+}
+
+/// Pipeline that stretches the rendered region over the target.
+pub(crate) struct UpscalePipeline {
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UpscaleUniforms {
+    uv_scale: [f32; 2],
+    uv_max: [f32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for UpscaleUniforms {}
+unsafe impl bytemuck::Pod for UpscaleUniforms {}
+
+impl DynamicResolution {
+    /// Creates a [DynamicResolution] starting at the largest scale.
+    pub fn new(settings: DynamicResolutionSettings) -> Self {
+        Self {
+            scale: settings.max_scale,
+            settings,
+            texture: None,
+            render_size: UVec2::ZERO,
+        }
+    }
+
+    /// Gets the fraction of the window's resolution the scene is rendered at this
+    /// frame.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Gets the size in pixels the scene is rendered at this frame.
+    pub fn render_size(&self) -> UVec2 {
+        self.render_size
+    }
+
+    /// Gets the texture scene passes draw to, or `None` before the first
+    /// [RenderContext::update_dynamic_resolution].
+    pub fn texture_id(&self) -> Option<ResourceId<Texture>> {
+        self.texture.map(|(texture_id, _)| texture_id)
+    }
+
+    /// Sets the settings, keeping the current scale within the new limits.
+    pub fn set_settings(&mut self, settings: DynamicResolutionSettings) {
+        self.settings = settings;
+        self.scale = self.scale.clamp(settings.min_scale, settings.max_scale);
+    }
+}
+
+/// Gets the scale for the next frame given how long the gpu took at the current one.
+fn next_scale(settings: &DynamicResolutionSettings, scale: f32, gpu_time: Duration) -> f32 {
+    let target = settings.target_frame_time.as_secs_f32();
+    if gpu_time.is_zero() || target <= 0.0 {
+        return scale;
+    }
+
+    let load = gpu_time.as_secs_f32() / target;
+    // The pixels drawn go with the square of the scale.
+    let ideal = scale / load.sqrt();
+    let scale = match (GROW_HEADROOM..=1.0).contains(&load) {
+        true => scale,
+        false => scale + (ideal - scale) * ADJUST_RATE,
+    };
+    scale.clamp(settings.min_scale, settings.max_scale)
+}
+
+impl UpscalePipeline {
+    pub(crate) fn new(device: &wgpu::Device, post_processor: &PostProcessor) -> Self {
+        let shader = post_process::create_shader(device, "clockwork upscale shader", SHADER_SOURCE);
+        Self {
+            render_pipeline: post_processor.create_pipeline(
+                device,
+                "clockwork upscale pipeline",
+                &shader,
+                "fs_main",
+                post_processor.color_format,
+                None,
+            ),
+        }
+    }
+}
+
+impl RenderContext {
+    /// Picks the scale of a [DynamicResolution] for the current frame from how long
+    /// the gpu took with the most recently finished one, see
+    /// [super::FrameLatencyStats::gpu_latency], and recreates its texture if the
+    /// window was resized.
+    pub fn update_dynamic_resolution(&mut self, dynamic_resolution: &mut DynamicResolution) {
+        let surface_size = UVec2::new(self.surface_config.width, self.surface_config.height);
+        let settings = dynamic_resolution.settings;
+        if dynamic_resolution.texture.is_some() {
+            dynamic_resolution.scale = next_scale(
+                &settings,
+                dynamic_resolution.scale,
+                self.frame_latency_stats().gpu_latency,
+            );
+        }
+
+        let texture_size = (surface_size.as_vec2() * settings.max_scale.max(settings.min_scale))
+            .ceil()
+            .as_uvec2()
+            .max(UVec2::ONE);
+        let texture_id = match dynamic_resolution.texture {
+            Some((texture_id, size)) if size == surface_size => texture_id,
+            texture => {
+                if let Some((texture_id, _)) = texture {
+                    self.remove_texture(texture_id);
+                }
+                let texture_id = self.create_render_target(texture_size);
+                dynamic_resolution.texture = Some((texture_id, surface_size));
+                texture_id
+            }
+        };
+
+        dynamic_resolution.render_size = (surface_size.as_vec2() * dynamic_resolution.scale)
+            .round()
+            .as_uvec2()
+            .clamp(UVec2::ONE, self.textures[texture_id].size);
+    }
+
+    /// Draws the scene rendered into a [DynamicResolution] this frame over `target`,
+    /// stretched to fill it.
+    ///
+    /// Like render passes, nothing is drawn to the surface outside of a frame.
+    pub fn upscale_dynamic_resolution(
+        &mut self,
+        dynamic_resolution: &DynamicResolution,
+        target: RenderTarget,
+    ) -> Result<()> {
+        let Some(source) = dynamic_resolution.texture_id() else {
+            anyhow::bail!("dynamic resolution was never updated");
+        };
+        anyhow::ensure!(
+            target != RenderTarget::Texture(source),
+            "dynamic resolution can't upscale to its own texture {source:?}"
+        );
+        anyhow::ensure!(self.textures.get(source).is_some(), "no texture {source:?}");
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(device, color_format));
+        if post_processor.upscale.is_none() {
+            post_processor.upscale = Some(UpscalePipeline::new(device, post_processor));
+        }
+
+        let Some(target_view) = self.post_process_target_view(target)? else {
+            return Ok(());
+        };
+        let post_processor = self.post_processor.as_ref().expect("created above");
+        let pipeline = post_processor.upscale.as_ref().expect("created above");
+
+        let source_size = self.textures[source].size.as_vec2();
+        let render_size = dynamic_resolution.render_size.as_vec2();
+        let uniforms = UpscaleUniforms {
+            uv_scale: (render_size / source_size).to_array(),
+            uv_max: ((render_size - Vec2::splat(0.5)) / source_size).to_array(),
+        };
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork upscale encoder"),
+            }),
+        );
+        post_processor.encode(
+            &self.device,
+            &mut encoder,
+            FullscreenPass {
+                label: "clockwork upscale pass",
+                pipeline: &pipeline.render_pipeline,
+                source: &self.textures[source].view,
+                secondary: None,
+                uniforms: bytemuck::bytes_of(&uniforms),
+                target: target_view,
+                blend_constant: None,
+            },
+        );
+
+        self.submit(encoder);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_scale() {
+        let settings = DynamicResolutionSettings::new(Duration::from_millis(16));
+        let frame = |millis| Duration::from_millis(millis);
+
+        // Over budget, so fewer pixels are drawn, but not below the minimum.
+        let scale = next_scale(&settings, 1.0, frame(32));
+        assert!(scale < 1.0 && scale > 0.5);
+        assert_eq!(next_scale(&settings, 0.5, frame(100)), 0.5);
+
+        // Just under budget holds steady, well under grows back.
+        assert_eq!(next_scale(&settings, 0.7, frame(15)), 0.7);
+        assert!(next_scale(&settings, 0.7, frame(8)) > 0.7);
+        assert_eq!(next_scale(&settings, 1.0, frame(1)), 1.0);
+
+        // Nothing measured yet.
+        assert_eq!(next_scale(&settings, 0.8, Duration::ZERO), 0.8);
+    }
+
+    #[test]
+    fn test_shader_validates() {
+        post_process::validate_shader(SHADER_SOURCE);
+    }
+}
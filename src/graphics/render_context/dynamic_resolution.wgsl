@@ -0,0 +1,15 @@
+struct UpscaleUniforms {
+    // Fraction of the source covered by the rendered region.
+    uv_scale: vec2<f32>,
+    // Furthest uv sampled, half a texel inside the region so filtering doesn't pick up
+    // the unused part of the source.
+    uv_max: vec2<f32>,
+}
+@group(0) @binding(3)
+var<uniform> upscale: UpscaleUniforms;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let uv = min(in.uv * upscale.uv_scale, upscale.uv_max);
+    return textureSample(source_texture, source_sampler, uv);
+}
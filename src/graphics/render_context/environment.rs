@@ -0,0 +1,551 @@
+use std::{collections::HashMap, f32::consts::FRAC_PI_2};
+
+use anyhow::Result;
+use glam::{vec3, Mat4, UVec2, Vec3, Vec4};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::util::repository::Repository;
+use crate::{
+    graphics::texture::{self, Texture},
+    util::repository::ResourceId,
+};
+
+use super::{
+    create_render_pipeline, create_render_pipeline_layout,
+    light_culling::LightBuffers,
+    post_process::{self, FullscreenPass, PostProcessor},
+    RenderContext, RenderOperation, RenderPassDescriptor, RenderTarget,
+};
+
+/// Source of the shader reflective materials are drawn with.
+const SHADER_SOURCE: &str = concat!(
+    include_str!("shader.wgsl"),
+    include_str!("environment.wgsl")
+);
+
+/// Source of the post process shader that generates mip levels of environment maps.
+const MIPS_SHADER_SOURCE: &str = include_str!("environment_mips.wgsl");
+
+/// Closest distance to the capture point that is drawn into an environment map.
+const CAPTURE_NEAR: f32 = 0.05;
+/// Furthest distance from the capture point that is drawn into an environment map.
+const CAPTURE_FAR: f32 = 1000.0;
+
+/// Direction and up vector of each cube face, in the order of the texture's layers.
+const CUBE_FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::NEG_Z),
+    (Vec3::NEG_Y, Vec3::Z),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y),
+];
+
+/// Surroundings seen from a point, stored as the six faces of a cube, which reflective
+/// materials mirror.
+///
+/// Maps have a full chain of increasingly blurry mip levels, which rough materials
+/// reflect instead of the sharp faces.
+pub struct EnvironmentMap {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    size: u32,
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+    /// Pixels of each face of maps loaded from images, kept to upload them again
+    /// after the device is lost. Captured maps have none and come back blank.
+    #[cfg(not(target_arch = "wasm32"))]
+    faces: Option<Vec<Vec<u8>>>,
+}
+
+/// Resources for drawing reflective materials, created along with the first
+/// environment map.
+pub(crate) struct EnvironmentRenderer {
+    pub(crate) bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pub(crate) render_pipeline: wgpu::RenderPipeline,
+    pub(crate) transparent_render_pipeline: wgpu::RenderPipeline,
+    /// Texture each face is rendered to before being copied into the map, reused
+    /// between captures of the same size.
+    capture_target: Option<(u32, ResourceId<Texture>)>,
+    /// Pipelines generating mip levels for each format of map, created as needed.
+    mip_pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl EnvironmentMap {
+    /// Gets the width and height of each face in pixels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub(crate) fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        renderer: &EnvironmentRenderer,
+        size: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let mip_level_count = texture::mip_level_count(UVec2::splat(size));
+        let texture = device.create_texture(
+            &(wgpu::TextureDescriptor {
+                label: Some("clockwork environment map"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }),
+        );
+        let view = texture.create_view(
+            &(wgpu::TextureViewDescriptor {
+                label: Some("clockwork environment map view"),
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            }),
+        );
+        let bind_group = device.create_bind_group(
+            &(wgpu::BindGroupDescriptor {
+                label: Some("clockwork environment map bind group"),
+                layout: &renderer.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Sampler(&renderer.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                ],
+            }),
+        );
+
+        Self {
+            texture,
+            bind_group,
+            size,
+            format,
+            mip_level_count,
+            #[cfg(not(target_arch = "wasm32"))]
+            faces: None,
+        }
+    }
+
+    /// Uploads the first mip level of each face.
+    fn write_faces(&mut self, queue: &wgpu::Queue, faces: Vec<Vec<u8>>) {
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                face,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.size * 4),
+                    rows_per_image: Some(self.size),
+                },
+                wgpu::Extent3d {
+                    width: self.size,
+                    height: self.size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.faces = Some(faces);
+        }
+    }
+
+    /// Creates a view of a single face at a single mip level.
+    fn face_view(&self, layer: u32, mip_level: u32) -> wgpu::TextureView {
+        self.texture.create_view(
+            &(wgpu::TextureViewDescriptor {
+                label: Some("clockwork environment map face view"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_mip_level: mip_level,
+                mip_level_count: Some(1),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            }),
+        )
+    }
+}
+
+impl EnvironmentRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        light_buffers: LightBuffers,
+        buffers_bind_group_layout: &wgpu::BindGroupLayout,
+        textures_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork environment map bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            }),
+        );
+        let sampler = device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                label: Some("clockwork environment map sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+        );
+
+        let (render_pipeline, transparent_render_pipeline) = create_render_pipeline(
+            device,
+            color_format,
+            "clockwork reflective pipeline",
+            &create_render_pipeline_layout(
+                device,
+                "clockwork reflective pipeline layout",
+                &[
+                    buffers_bind_group_layout,
+                    textures_bind_group_layout,
+                    &bind_group_layout,
+                ],
+            ),
+            wgpu::ShaderSource::Wgsl(light_buffers.shader_source(SHADER_SOURCE).into()),
+            "fs_reflective",
+        );
+
+        Self {
+            bind_group_layout,
+            sampler,
+            render_pipeline,
+            transparent_render_pipeline,
+            capture_target: None,
+            mip_pipelines: HashMap::new(),
+        }
+    }
+}
+
+/// Gets the view projection of each cube face as seen from `position`, in the order of
+/// the texture's layers.
+///
+/// Cube maps are looked up as if seen from inside the cube, so the projections are
+/// mirrored horizontally compared to a regular camera.
+fn face_view_projections(position: Vec3) -> [Mat4; 6] {
+    let projection = Mat4::from_scale(vec3(-1.0, 1.0, 1.0))
+        * Mat4::perspective_rh(FRAC_PI_2, 1.0, CAPTURE_NEAR, CAPTURE_FAR);
+    CUBE_FACES
+        .map(|(direction, up)| projection * Mat4::look_at_rh(position, position + direction, up))
+}
+
+impl RenderContext {
+    /// Loads an environment map from the images of its six faces, in the order +X, -X,
+    /// +Y, -Y, +Z, -Z, and returns a [ResourceId<EnvironmentMap>] that refers to it.
+    ///
+    /// Faces must be square and the same size.
+    pub fn load_environment_map(
+        &mut self,
+        faces: [&[u8]; 6],
+    ) -> Result<ResourceId<EnvironmentMap>> {
+        let faces = faces
+            .iter()
+            .map(|bytes| Ok(image::load_from_memory(bytes)?.to_rgba8()))
+            .collect::<Result<Vec<_>>>()?;
+        let size = faces[0].width();
+        anyhow::ensure!(
+            faces
+                .iter()
+                .all(|face| face.width() == size && face.height() == size),
+            "environment map faces must be square and the same size"
+        );
+
+        let mut environment_map =
+            self.new_environment_map(size, wgpu::TextureFormat::Rgba8UnormSrgb);
+        let faces: Vec<Vec<u8>> = faces.into_iter().map(|face| face.into_raw()).collect();
+        self.counters
+            .upload(faces.iter().map(Vec::len).sum::<usize>());
+        environment_map.write_faces(&self.queue, faces);
+        let environment_map_id = self.environment_maps.add(environment_map, None);
+        self.generate_environment_mips(environment_map_id);
+        Ok(environment_map_id)
+    }
+
+    /// Creates a blank environment map with faces of the given size, to be drawn with
+    /// [RenderContext::capture_environment_map].
+    pub fn create_environment_map(&mut self, size: u32) -> ResourceId<EnvironmentMap> {
+        let environment_map = self.new_environment_map(size.max(1), self.color_format());
+        self.environment_maps.add(environment_map, None)
+    }
+
+    /// Draws operations as seen from `position` into every face of an environment map,
+    /// such as a probe in the middle of a room that the shiny objects in it reflect.
+    ///
+    /// Captures are as expensive as six render passes, so static scenes are best
+    /// captured once, such as when a level loads.
+    ///
+    /// Returns an error if the map was loaded from images rather than created with
+    /// [RenderContext::create_environment_map].
+    pub fn capture_environment_map(
+        &mut self,
+        environment_map_id: ResourceId<EnvironmentMap>,
+        position: Vec3,
+        operations: &[RenderOperation],
+        clear_color: Vec4,
+    ) -> Result<()> {
+        let environment_map = self
+            .environment_maps
+            .get(environment_map_id)
+            .ok_or_else(|| anyhow::anyhow!("no environment map {environment_map_id:?}"))?;
+        anyhow::ensure!(
+            environment_map.format == self.color_format(),
+            "environment map {environment_map_id:?} was loaded from images and can't be captured"
+        );
+        let size = environment_map.size;
+        let target = self.environment_capture_target(size);
+
+        for (layer, view_projection) in face_view_projections(position).into_iter().enumerate() {
+            self.render_pass(
+                &RenderPassDescriptor::new(view_projection)
+                    .with_target(RenderTarget::Texture(target))
+                    .with_clear_color(Some(clear_color))
+                    .with_label("clockwork environment capture pass"),
+                operations,
+            );
+
+            let mut command_encoder = self.device.create_command_encoder(
+                &(wgpu::CommandEncoderDescriptor {
+                    label: Some("clockwork environment capture copy"),
+                }),
+            );
+            command_encoder.copy_texture_to_texture(
+                self.textures[target].texture.as_image_copy(),
+                wgpu::ImageCopyTexture {
+                    texture: &self.environment_maps[environment_map_id].texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+            );
+            self.submit(command_encoder);
+        }
+        self.generate_environment_mips(environment_map_id);
+        Ok(())
+    }
+
+    /// Fills every mip level of an environment map after the first by downsampling
+    /// the one before it.
+    fn generate_environment_mips(&mut self, environment_map_id: ResourceId<EnvironmentMap>) {
+        let color_format = self.color_format();
+        let environment_map = &self.environment_maps[environment_map_id];
+        let environment = self
+            .environment
+            .as_mut()
+            .expect("created along with the environment map");
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(&self.device, color_format));
+        let device = &self.device;
+        let pipeline = environment
+            .mip_pipelines
+            .entry(environment_map.format)
+            .or_insert_with(|| {
+                let shader = post_process::create_shader(
+                    device,
+                    "clockwork environment mip shader",
+                    MIPS_SHADER_SOURCE,
+                );
+                post_processor.create_pipeline(
+                    device,
+                    "clockwork environment mip pipeline",
+                    &shader,
+                    "fs_downsample",
+                    environment_map.format,
+                    None,
+                )
+            });
+
+        let mut command_encoder = device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork environment mips"),
+            }),
+        );
+        for layer in 0..6 {
+            for mip_level in 1..environment_map.mip_level_count {
+                post_processor.encode(
+                    device,
+                    &mut command_encoder,
+                    FullscreenPass {
+                        label: "clockwork environment mip pass",
+                        pipeline,
+                        source: &environment_map.face_view(layer, mip_level - 1),
+                        secondary: None,
+                        uniforms: &[],
+                        target: &environment_map.face_view(layer, mip_level),
+                        blend_constant: None,
+                    },
+                );
+            }
+        }
+        self.submit(command_encoder);
+    }
+
+    /// Creates an environment map, along with the resources for reflective materials
+    /// if this is the first one.
+    fn new_environment_map(&mut self, size: u32, format: wgpu::TextureFormat) -> EnvironmentMap {
+        let color_format = self.color_format();
+        let light_buffers = self.light_buffers();
+        let renderer = self.environment.get_or_insert_with(|| {
+            EnvironmentRenderer::new(
+                &self.device,
+                color_format,
+                light_buffers,
+                &self.buffers_bind_group_layout,
+                &self.textures_bind_group_layout,
+            )
+        });
+        EnvironmentMap::new(&self.device, renderer, size, format)
+    }
+
+    /// Creates environment maps from a context whose device was lost again under the
+    /// same ids, see [RenderContext::recover].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn restore_environment_maps(
+        &mut self,
+        mut environment_maps: Repository<EnvironmentMap>,
+        environment: Option<EnvironmentRenderer>,
+    ) {
+        for (_, environment_map) in environment_maps.iter_mut() {
+            let mut restored =
+                self.new_environment_map(environment_map.size, environment_map.format);
+            if let Some(faces) = environment_map.faces.take() {
+                restored.write_faces(&self.queue, faces);
+            }
+            *environment_map = restored;
+        }
+        self.environment_maps = environment_maps;
+        // The capture target is a render target, restored along with the textures.
+        if let (Some(environment), Some(old)) = (&mut self.environment, environment) {
+            environment.capture_target = old.capture_target;
+        }
+
+        let loaded: Vec<_> = self
+            .environment_maps
+            .iter()
+            .filter(|(_, environment_map)| environment_map.faces.is_some())
+            .map(|(environment_map_id, _)| environment_map_id)
+            .collect();
+        for environment_map_id in loaded {
+            self.generate_environment_mips(environment_map_id);
+        }
+    }
+
+    /// Gets a render target to capture faces of the given size into.
+    fn environment_capture_target(&mut self, size: u32) -> ResourceId<Texture> {
+        let previous = self
+            .environment
+            .as_ref()
+            .expect("created along with the environment map")
+            .capture_target;
+        match previous {
+            Some((previous_size, target)) if previous_size == size => target,
+            _ => {
+                if let Some((_, target)) = previous {
+                    self.textures.remove(target);
+                    self.render_target_depth_textures.remove(&target);
+                }
+                let target = self.create_render_target(UVec2::splat(size));
+                if let Some(environment) = &mut self.environment {
+                    environment.capture_target = Some((size, target));
+                }
+                target
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_face_view_projections_look_along_faces() {
+        let position = vec3(1.0, 2.0, 3.0);
+        for ((direction, up), view_projection) in
+            CUBE_FACES.iter().zip(face_view_projections(position))
+        {
+            let center = view_projection.project_point3(position + *direction);
+            assert!(center.x.abs() < 1e-5 && center.y.abs() < 1e-5);
+
+            // Up is at the top of each face, as cube map lookups expect.
+            let top = view_projection.project_point3(position + *direction + *up * 0.5);
+            assert!(top.y > 0.0 && top.x.abs() < 1e-5);
+        }
+
+        // Faces are mirrored, so +X of the -Z face is on the left.
+        let right =
+            face_view_projections(position)[5].project_point3(position + vec3(0.5, 0.0, -1.0));
+        assert!(right.x < 0.0);
+    }
+
+    #[test]
+    fn test_mips_shader_validates() {
+        post_process::validate_shader(MIPS_SHADER_SOURCE);
+    }
+
+    #[test]
+    fn test_shader_validates() {
+        for light_buffers in [LightBuffers::Tiled, LightBuffers::Uniform] {
+            let source = light_buffers.shader_source(SHADER_SOURCE);
+            let module = naga::front::wgsl::parse_str(&source).unwrap();
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .unwrap();
+        }
+    }
+}
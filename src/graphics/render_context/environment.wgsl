@@ -0,0 +1,36 @@
+
+@group(2) @binding(0)
+var environment_sampler: sampler;
+@group(2) @binding(1)
+var environment: texture_cube<f32>;
+
+// Schlick's approximation of how much light a surface reflects, where `reflectivity`
+// is how much it reflects when looked at head on.
+fn fresnel(reflectivity: f32, cos_theta: f32) -> f32 {
+    return reflectivity + (1.0 - reflectivity) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Fragment shader for reflective materials, which mirror the environment map more
+// the more glancing the view is.
+@fragment
+fn fs_reflective(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    // Both textures are sampled before discarding, while control flow is uniform.
+    let sample = textureSample(texture, texture_sampler, in.uv) * in.color * operation_color();
+    let normal = normalize(in.normal);
+    let to_camera = normalize(global.camera.xyz - in.world_position * global.camera.w);
+    let reflected = textureSample(environment, environment_sampler, reflect(-to_camera, normal));
+    if (sample.w < 0.001) {
+        discard;
+    }
+
+    var color = sample.rgb;
+    if (lighting.lit != 0u) {
+        color = apply_lighting(color, in.world_position, normal, in.clip_position.xy);
+    }
+
+    let amount = fresnel(local.parameters.x, max(dot(normal, to_camera), 0.0));
+    let shaded = vec4<f32>(mix(color, reflected.rgb * sample.w, amount), sample.w);
+    return apply_debug_view(light_heatmap(shaded, in.clip_position.xy), in);
+}
@@ -0,0 +1,7 @@
+
+// Downsamples a face of an environment map into its next mip level. Sampling between
+// texels with a linear sampler averages each 2x2 block.
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(source_texture, source_sampler, in.uv);
+}
@@ -0,0 +1,51 @@
+//! Caches of bind groups and per-operation buffers only grow while rendering, so after
+//! a spike of operations or textures the extra gpu memory would stay around for the
+//! rest of the session. Entries remember the [RenderContext::frame_index] they were
+//! last used, and [RenderContext::end_frame] drops those unused for
+//! [MAX_UNUSED_FRAMES].
+
+use super::RenderContext;
+
+/// Frames a cached bind group or buffer can go unused before it's dropped.
+pub(crate) const MAX_UNUSED_FRAMES: u64 = 240;
+
+/// Gets whether an entry last used on one frame should be dropped on another.
+pub(crate) fn is_stale(last_used: u64, frame_index: u64) -> bool {
+    frame_index.saturating_sub(last_used) > MAX_UNUSED_FRAMES
+}
+
+impl RenderContext {
+    /// Drops the cached bind groups that haven't been used for [MAX_UNUSED_FRAMES], and
+    /// the local buffer if it has been more than twice as large as needed for as long.
+    pub(crate) fn evict_stale_caches(&mut self) {
+        let frame_index = self.frame_index;
+        self.textures_bind_groups
+            .retain(|_, (.., last_used)| !is_stale(*last_used, frame_index));
+        if let Some(pbr) = &mut self.pbr {
+            pbr.evict_stale_bind_groups(frame_index);
+        }
+
+        // The local buffer is created again at the size the next render pass needs.
+        if self
+            .local_uniforms
+            .as_ref()
+            .is_some_and(|local_uniforms| local_uniforms.is_stale(frame_index))
+        {
+            self.local_uniforms = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_stale() {
+        assert!(!is_stale(0, 0));
+        assert!(!is_stale(10, MAX_UNUSED_FRAMES + 10));
+        assert!(is_stale(10, MAX_UNUSED_FRAMES + 11));
+        // Used after the frame being checked, such as before the index is advanced.
+        assert!(!is_stale(5, 4));
+    }
+}
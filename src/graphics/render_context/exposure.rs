@@ -0,0 +1,323 @@
+use anyhow::Result;
+use glam::UVec2;
+
+use crate::{graphics::texture::Texture, util::repository::ResourceId};
+
+use super::{
+    post_process::{self, FullscreenPass, PostProcessor, INTERMEDIATE_FORMAT},
+    RenderContext, RenderTarget,
+};
+
+const SHADER_SOURCE: &str = include_str!("exposure.wgsl");
+
+/// Settings of an [AutoExposure] effect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExposureSettings {
+    /// Brightness the scene's average luminance is scaled towards, where 0.18 is middle
+    /// grey.
+    pub key: f32,
+    /// Smallest the exposure can get, limiting how much bright scenes are darkened.
+    pub min_exposure: f32,
+    /// Largest the exposure can get, limiting how much dark scenes are brightened.
+    pub max_exposure: f32,
+    /// How quickly exposure adapts when the scene gets brighter, per second.
+    pub speed_up: f32,
+    /// How quickly exposure adapts when the scene gets darker, per second.
+    pub speed_down: f32,
+}
+
+/// Adjusts the exposure of the scene over time based on its average brightness, like
+/// eyes adapting when moving between dark and bright areas, see
+/// [RenderContext::apply_auto_exposure].
+pub struct AutoExposure {
+    /// Settings of the effect, which can be changed at any time.
+    pub settings: ExposureSettings,
+    /// Chain of log luminance textures, halving in size down to a single pixel.
+    mips: Vec<Texture>,
+    /// Single pixel textures holding the adapted luminance, alternating each frame.
+    adapted: Vec<Texture>,
+    /// Index of the adapted texture written last.
+    current: usize,
+    /// Whether the next application should skip adapting, such as after a cut.
+    reset: bool,
+    /// Generation of the device the textures were created on, so they are created
+    /// again after [RenderContext::recover].
+    device_generation: u32,
+}
+
+/// Pipelines for each step of auto exposure.
+pub(crate) struct ExposurePipelines {
+    log_luminance: wgpu::RenderPipeline,
+    downsample: wgpu::RenderPipeline,
+    adapt: wgpu::RenderPipeline,
+    apply: wgpu::RenderPipeline,
+}
+
+/// Uniforms of the exposure shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ExposureUniforms {
+    key: f32,
+    min_exposure: f32,
+    max_exposure: f32,
+    delta_time: f32,
+    speed_up: f32,
+    speed_down: f32,
+    reset: u32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Zeroable for ExposureUniforms {}
+unsafe impl bytemuck::Pod for ExposureUniforms {}
+
+impl Default for ExposureSettings {
+    fn default() -> Self {
+        Self {
+            key: 0.18,
+            min_exposure: 0.25,
+            max_exposure: 4.0,
+            speed_up: 3.0,
+            speed_down: 1.0,
+        }
+    }
+}
+
+impl ExposureSettings {
+    /// Sets the brightness the scene is scaled towards.
+    pub fn with_key(mut self, key: f32) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Sets the range the exposure is clamped to.
+    pub fn with_range(mut self, min_exposure: f32, max_exposure: f32) -> Self {
+        self.min_exposure = min_exposure;
+        self.max_exposure = max_exposure;
+        self
+    }
+
+    /// Sets how quickly exposure adapts to brighter and darker scenes.
+    pub fn with_speed(mut self, speed_up: f32, speed_down: f32) -> Self {
+        self.speed_up = speed_up;
+        self.speed_down = speed_down;
+        self
+    }
+}
+
+impl AutoExposure {
+    /// Creates a new [AutoExposure]. Its textures are created the first time it is
+    /// applied.
+    pub fn new(settings: ExposureSettings) -> Self {
+        Self {
+            settings,
+            mips: Vec::new(),
+            adapted: Vec::new(),
+            current: 0,
+            reset: true,
+            device_generation: 0,
+        }
+    }
+
+    /// Makes the next application jump straight to the scene's exposure instead of
+    /// adapting, such as after a camera cut.
+    pub fn reset(&mut self) {
+        self.reset = true;
+    }
+}
+
+/// Gets the size of each texture in the luminance chain, halving down to a single pixel.
+fn luminance_mip_sizes(source_size: UVec2) -> Vec<UVec2> {
+    let mut sizes = vec![(source_size / 2).max(UVec2::ONE)];
+    while let Some(&size) = sizes.last().filter(|size| **size != UVec2::ONE) {
+        sizes.push((size / 2).max(UVec2::ONE));
+    }
+    sizes
+}
+
+impl ExposurePipelines {
+    pub(crate) fn new(device: &wgpu::Device, post_processor: &PostProcessor) -> Self {
+        let shader =
+            post_process::create_shader(device, "clockwork exposure shader", SHADER_SOURCE);
+        let pipeline = |entry_point, format| {
+            post_processor.create_pipeline(
+                device,
+                "clockwork exposure pipeline",
+                &shader,
+                entry_point,
+                format,
+                None,
+            )
+        };
+
+        Self {
+            log_luminance: pipeline("fs_log_luminance", INTERMEDIATE_FORMAT),
+            downsample: pipeline("fs_downsample", INTERMEDIATE_FORMAT),
+            adapt: pipeline("fs_adapt", INTERMEDIATE_FORMAT),
+            apply: pipeline("fs_apply", post_processor.color_format),
+        }
+    }
+}
+
+impl RenderContext {
+    /// Draws `source` to `target` with its exposure adapted towards the scene's
+    /// average brightness, advancing the adaptation by `delta` seconds.
+    ///
+    /// Like [RenderContext::apply_bloom], render the scene to a texture first. The
+    /// luminance never leaves the gpu, so this doesn't stall the frame.
+    pub fn apply_auto_exposure(
+        &mut self,
+        auto_exposure: &mut AutoExposure,
+        delta: f32,
+        source: ResourceId<Texture>,
+        target: RenderTarget,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            target != RenderTarget::Texture(source),
+            "auto exposure can't draw to its own source {source:?}"
+        );
+        let source_size = self
+            .textures
+            .get(source)
+            .ok_or_else(|| anyhow::anyhow!("no texture {source:?}"))?
+            .size;
+
+        if auto_exposure.device_generation != self.device_generation {
+            auto_exposure.mips.clear();
+            auto_exposure.adapted.clear();
+            auto_exposure.device_generation = self.device_generation;
+        }
+        let sizes = luminance_mip_sizes(source_size);
+        if auto_exposure.mips.first().map(|mip| mip.size) != sizes.first().copied() {
+            auto_exposure.mips = sizes
+                .iter()
+                .map(|&size| post_process::create_intermediate_texture(&self.device, size))
+                .collect();
+        }
+        if auto_exposure.adapted.is_empty() {
+            auto_exposure.adapted = (0..2)
+                .map(|_| post_process::create_intermediate_texture(&self.device, UVec2::ONE))
+                .collect();
+            auto_exposure.reset = true;
+        }
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(device, color_format));
+        if post_processor.exposure.is_none() {
+            post_processor.exposure = Some(ExposurePipelines::new(device, post_processor));
+        }
+
+        let Some(target_view) = self.post_process_target_view(target)? else {
+            return Ok(());
+        };
+        let post_processor = self.post_processor.as_ref().expect("created above");
+        let pipelines = post_processor.exposure.as_ref().expect("created above");
+        let source_view = &self.textures[source].view;
+
+        let settings = auto_exposure.settings;
+        let uniforms = ExposureUniforms {
+            key: settings.key,
+            min_exposure: settings.min_exposure.min(settings.max_exposure),
+            max_exposure: settings.max_exposure,
+            delta_time: delta.max(0.0),
+            speed_up: settings.speed_up.max(0.0),
+            speed_down: settings.speed_down.max(0.0),
+            reset: auto_exposure.reset as u32,
+            _padding: 0.0,
+        };
+        let uniforms = bytemuck::bytes_of(&uniforms);
+        auto_exposure.reset = false;
+
+        let previous = &auto_exposure.adapted[auto_exposure.current];
+        auto_exposure.current = 1 - auto_exposure.current;
+        let adapted = &auto_exposure.adapted[auto_exposure.current];
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork exposure encoder"),
+            }),
+        );
+        let pass = |encoder: &mut wgpu::CommandEncoder,
+                    label: &'static str,
+                    pipeline: &wgpu::RenderPipeline,
+                    source: &wgpu::TextureView,
+                    secondary: Option<&wgpu::TextureView>,
+                    target: &wgpu::TextureView| {
+            post_processor.encode(
+                &self.device,
+                encoder,
+                FullscreenPass {
+                    label,
+                    pipeline,
+                    source,
+                    secondary,
+                    uniforms,
+                    target,
+                    blend_constant: None,
+                },
+            );
+        };
+
+        // Average the log luminance down to a single pixel.
+        pass(
+            &mut encoder,
+            "clockwork exposure luminance pass",
+            &pipelines.log_luminance,
+            source_view,
+            None,
+            &auto_exposure.mips[0].view,
+        );
+        for pair in auto_exposure.mips.windows(2) {
+            pass(
+                &mut encoder,
+                "clockwork exposure downsample pass",
+                &pipelines.downsample,
+                &pair[0].view,
+                None,
+                &pair[1].view,
+            );
+        }
+
+        let average = &auto_exposure.mips[auto_exposure.mips.len() - 1];
+        pass(
+            &mut encoder,
+            "clockwork exposure adapt pass",
+            &pipelines.adapt,
+            &average.view,
+            Some(&previous.view),
+            &adapted.view,
+        );
+        pass(
+            &mut encoder,
+            "clockwork exposure apply pass",
+            &pipelines.apply,
+            source_view,
+            Some(&adapted.view),
+            target_view,
+        );
+
+        self.submit(encoder);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luminance_mip_sizes() {
+        let sizes = luminance_mip_sizes(UVec2::new(100, 20));
+        assert_eq!(sizes.first(), Some(&UVec2::new(50, 10)));
+        assert_eq!(sizes.last(), Some(&UVec2::ONE));
+        assert_eq!(sizes.len(), 6);
+        assert_eq!(luminance_mip_sizes(UVec2::ONE), vec![UVec2::ONE]);
+    }
+
+    #[test]
+    fn test_shader_validates() {
+        post_process::validate_shader(SHADER_SOURCE);
+    }
+}
@@ -0,0 +1,56 @@
+struct ExposureUniforms {
+    key: f32,
+    min_exposure: f32,
+    max_exposure: f32,
+    delta_time: f32,
+    speed_up: f32,
+    speed_down: f32,
+    // Whether to jump straight to the current luminance, such as on the first frame.
+    reset: u32,
+    _padding: f32,
+}
+@group(0) @binding(3)
+var<uniform> exposure: ExposureUniforms;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Averages in log space, so a few very bright pixels don't dominate.
+@fragment
+fn fs_log_luminance(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, in.uv).rgb;
+    return vec4<f32>(log(max(luminance(color), 0.0001)), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(source_texture, source_sampler, in.uv);
+}
+
+// Eases the adapted luminance in the secondary texture towards the scene's average.
+@fragment
+fn fs_adapt(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let average = exp(textureSample(source_texture, source_sampler, vec2<f32>(0.5)).r);
+    let previous = textureSample(secondary_texture, source_sampler, vec2<f32>(0.5)).r;
+    if exposure.reset != 0u {
+        return vec4<f32>(average, 0.0, 0.0, 1.0);
+    }
+
+    // Eyes adjust to brightness faster than to darkness.
+    let speed = select(exposure.speed_down, exposure.speed_up, average > previous);
+    let adapted = mix(previous, average, 1.0 - exp(-exposure.delta_time * speed));
+    return vec4<f32>(adapted, 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_apply(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, in.uv);
+    let adapted = textureSample(secondary_texture, source_sampler, vec2<f32>(0.5)).r;
+    let scale = clamp(
+        exposure.key / max(adapted, 0.0001),
+        exposure.min_exposure,
+        exposure.max_exposure,
+    );
+    return vec4<f32>(color.rgb * scale, color.a);
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use instant::Instant;
+
+use super::RenderContext;
+
+/// Default for [RenderContext::set_max_frames_in_flight].
+pub(crate) const DEFAULT_MAX_FRAMES_IN_FLIGHT: u32 = 2;
+
+/// Number of recent frames input latency is measured over.
+const INPUT_LATENCY_HISTORY: usize = 120;
+
+/// Measurements of how far the cpu runs ahead of the gpu, for checking the effect of
+/// [RenderContext::set_max_frames_in_flight].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameLatencyStats {
+    /// Most frames that may be submitted but not yet finished by the gpu.
+    pub max_frames_in_flight: u32,
+    /// Frames submitted but not known to be finished at the start of the latest frame.
+    pub frames_in_flight: u32,
+    /// How long the latest frame waited for the gpu to catch up before starting.
+    pub wait_time: Duration,
+    /// How long the gpu took to finish the most recently completed frame after it was
+    /// submitted.
+    pub gpu_latency: Duration,
+    /// Average time from input being received to the gpu finishing the first frame
+    /// that reflects it, over recent frames with input. Zero unless the probe is
+    /// enabled with [crate::Engine::set_input_latency_probe].
+    pub input_latency: Duration,
+    /// Longest recent input latency, see [FrameLatencyStats::input_latency].
+    pub input_latency_max: Duration,
+}
+
+/// Frame that was submitted and may still be running on the gpu.
+struct InFlightFrame {
+    submission: wgpu::SubmissionIndex,
+    submitted_at: Instant,
+    finished_at: Arc<Mutex<Option<Instant>>>,
+    /// When the earliest input the frame reflects was received, if it's being probed.
+    input_at: Option<Instant>,
+}
+
+/// Limits how many frames can be in flight at once.
+pub(crate) struct FramePacer {
+    max_frames_in_flight: u32,
+    frames: VecDeque<InFlightFrame>,
+    /// Latest submission of the current frame.
+    last_submission: Option<wgpu::SubmissionIndex>,
+    /// Earliest probed input not yet reflected by a submitted frame.
+    input_at: Option<Instant>,
+    input_latencies: VecDeque<Duration>,
+    stats: FrameLatencyStats,
+}
+
+impl FramePacer {
+    /// Creates a new [FramePacer].
+    pub(crate) fn new(max_frames_in_flight: u32) -> Self {
+        let max_frames_in_flight = max_frames_in_flight.max(1);
+        Self {
+            max_frames_in_flight,
+            frames: VecDeque::new(),
+            last_submission: None,
+            input_at: None,
+            input_latencies: VecDeque::new(),
+            stats: FrameLatencyStats {
+                max_frames_in_flight,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Records a submission made during the current frame.
+    pub(crate) fn submitted(&mut self, submission: wgpu::SubmissionIndex) {
+        self.last_submission = Some(submission);
+    }
+
+    /// Removes the frame at the front of the queue, recording how long it took.
+    fn retire_front(&mut self) {
+        if let Some(frame) = self.frames.pop_front() {
+            let finished_at = frame
+                .finished_at
+                .lock()
+                .unwrap()
+                .unwrap_or_else(Instant::now);
+            self.stats.gpu_latency = finished_at.saturating_duration_since(frame.submitted_at);
+            if let Some(input_at) = frame.input_at {
+                self.record_input_latency(finished_at.saturating_duration_since(input_at));
+            }
+        }
+    }
+
+    /// Records the latency of a frame's input, updating the average and worst.
+    fn record_input_latency(&mut self, latency: Duration) {
+        if self.input_latencies.len() == INPUT_LATENCY_HISTORY {
+            self.input_latencies.pop_front();
+        }
+        self.input_latencies.push_back(latency);
+        self.stats.input_latency =
+            self.input_latencies.iter().sum::<Duration>() / self.input_latencies.len() as u32;
+        self.stats.input_latency_max = self
+            .input_latencies
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or_default();
+    }
+
+    fn front_finished(&self) -> bool {
+        self.frames
+            .front()
+            .is_some_and(|frame| frame.finished_at.lock().unwrap().is_some())
+    }
+}
+
+impl RenderContext {
+    /// Sets the most frames that can be submitted before the cpu waits for the gpu to
+    /// finish the oldest one.
+    ///
+    /// Lower values reduce the latency between input and what's on screen at the cost
+    /// of less overlap between the cpu and gpu, with 1 giving the lowest latency.
+    /// Values below 1 are treated as 1.
+    pub fn set_max_frames_in_flight(&mut self, max_frames_in_flight: u32) {
+        let max_frames_in_flight = max_frames_in_flight.max(1);
+        self.frame_pacer.max_frames_in_flight = max_frames_in_flight;
+        self.frame_pacer.stats.max_frames_in_flight = max_frames_in_flight;
+    }
+
+    /// Gets the most frames that can be submitted before the cpu waits for the gpu.
+    pub fn max_frames_in_flight(&self) -> u32 {
+        self.frame_pacer.max_frames_in_flight
+    }
+
+    /// Gets measurements of frame latency as of the latest frame.
+    pub fn frame_latency_stats(&self) -> FrameLatencyStats {
+        self.frame_pacer.stats
+    }
+
+    /// Probes input received at `timestamp`, measuring how long it takes to reach the
+    /// screen through the next frame submitted.
+    pub(crate) fn probe_input(&mut self, timestamp: Instant) {
+        self.frame_pacer.input_at.get_or_insert(timestamp);
+    }
+
+    /// Blocks until fewer than the maximum number of frames are in flight.
+    pub(crate) fn wait_for_frames_in_flight(&mut self) {
+        self.device.poll(wgpu::Maintain::Poll);
+        while self.frame_pacer.front_finished() {
+            self.frame_pacer.retire_front();
+        }
+        self.frame_pacer.stats.frames_in_flight = self.frame_pacer.frames.len() as u32;
+
+        let start = Instant::now();
+        while self.frame_pacer.frames.len() as u32 >= self.frame_pacer.max_frames_in_flight {
+            let submission = self.frame_pacer.frames[0].submission.clone();
+            self.device
+                .poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+            self.frame_pacer.retire_front();
+        }
+        self.frame_pacer.stats.wait_time = start.elapsed();
+    }
+
+    /// Marks the end of the current frame's submissions so later frames can wait on it.
+    pub(crate) fn track_frame_in_flight(&mut self) {
+        let Some(submission) = self.frame_pacer.last_submission.take() else {
+            return;
+        };
+
+        let finished_at = Arc::new(Mutex::new(None));
+        let callback_finished_at = finished_at.clone();
+        self.queue.on_submitted_work_done(move || {
+            *callback_finished_at.lock().unwrap() = Some(Instant::now());
+        });
+
+        self.frame_pacer.frames.push_back(InFlightFrame {
+            submission,
+            submitted_at: Instant::now(),
+            finished_at,
+            input_at: self.frame_pacer.input_at.take(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_latency_average_and_worst() {
+        let mut frame_pacer = FramePacer::new(2);
+        for millis in [10, 30, 20] {
+            frame_pacer.record_input_latency(Duration::from_millis(millis));
+        }
+        assert_eq!(frame_pacer.stats.input_latency, Duration::from_millis(20));
+        assert_eq!(
+            frame_pacer.stats.input_latency_max,
+            Duration::from_millis(30)
+        );
+
+        for _ in 0..INPUT_LATENCY_HISTORY {
+            frame_pacer.record_input_latency(Duration::from_millis(5));
+        }
+        assert_eq!(
+            frame_pacer.stats.input_latency_max,
+            Duration::from_millis(5)
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Thin layer over the device and queue operations of the renderer's buffer
+//! bookkeeping, such as the [super::mesh_pool::MeshPool], so it can be unit tested
+//! against a [MockGpu] without a gpu.
+
+use std::{
+    panic::AssertUnwindSafe,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Copy of a range of bytes between two buffers.
+pub(crate) struct BufferCopy<'a, Buffer> {
+    pub(crate) source: &'a Buffer,
+    pub(crate) source_offset: u64,
+    pub(crate) destination: &'a Buffer,
+    pub(crate) destination_offset: u64,
+    pub(crate) size: u64,
+}
+
+/// Buffer operations of a device and its queue.
+pub(crate) trait Gpu {
+    type Buffer;
+
+    /// Creates a zeroed buffer of a size in bytes.
+    fn create_buffer(&self, label: &str, size: u64, usage: wgpu::BufferUsages) -> Self::Buffer;
+
+    /// Writes bytes into a buffer at an offset, which lands before the next
+    /// submission.
+    fn write_buffer(&self, buffer: &Self::Buffer, offset: u64, data: &[u8]);
+
+    /// Copies ranges between buffers in a single submission.
+    fn copy_buffers(&self, label: &str, copies: &[BufferCopy<Self::Buffer>]);
+}
+
+/// [Gpu] of a wgpu device and queue.
+pub(crate) struct WgpuGpu<'a> {
+    pub(crate) device: &'a wgpu::Device,
+    pub(crate) queue: &'a wgpu::Queue,
+    /// Set if a submission finds the device lost, see [submit].
+    pub(crate) device_lost: &'a AtomicBool,
+}
+
+impl<'a> WgpuGpu<'a> {
+    pub(crate) fn new(
+        device: &'a wgpu::Device,
+        queue: &'a wgpu::Queue,
+        device_lost: &'a AtomicBool,
+    ) -> Self {
+        Self {
+            device,
+            queue,
+            device_lost,
+        }
+    }
+}
+
+/// Submits a command buffer, returning [None] and setting `device_lost` if the device
+/// can't take it.
+///
+/// wgpu 0.17 has no device lost callback, and instead panics when a submission fails,
+/// which is how a lost device shows up. The panic is caught so the renderer can move
+/// to a new device rather than bringing the app down.
+pub(crate) fn submit(
+    queue: &wgpu::Queue,
+    command_buffer: wgpu::CommandBuffer,
+    device_lost: &AtomicBool,
+) -> Option<wgpu::SubmissionIndex> {
+    catch_device_loss(device_lost, || {
+        queue.submit(std::iter::once(command_buffer))
+    })
+}
+
+/// Runs an operation wgpu panics in when the device is lost, see [submit].
+pub(crate) fn catch_device_loss<T>(
+    device_lost: &AtomicBool,
+    operation: impl FnOnce() -> T,
+) -> Option<T> {
+    if device_lost.load(Ordering::Relaxed) {
+        return None;
+    }
+    match std::panic::catch_unwind(AssertUnwindSafe(operation)) {
+        Ok(result) => Some(result),
+        Err(_) => {
+            log::error!("the graphics device was lost");
+            device_lost.store(true, Ordering::Relaxed);
+            None
+        }
+    }
+}
+
+impl Gpu for WgpuGpu<'_> {
+    type Buffer = wgpu::Buffer;
+
+    fn create_buffer(&self, label: &str, size: u64, usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        self.device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            }),
+        )
+    }
+
+    fn write_buffer(&self, buffer: &wgpu::Buffer, offset: u64, data: &[u8]) {
+        self.queue.write_buffer(buffer, offset, data);
+    }
+
+    fn copy_buffers(&self, label: &str, copies: &[BufferCopy<wgpu::Buffer>]) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&(wgpu::CommandEncoderDescriptor { label: Some(label) }));
+        for copy in copies {
+            encoder.copy_buffer_to_buffer(
+                copy.source,
+                copy.source_offset,
+                copy.destination,
+                copy.destination_offset,
+                copy.size,
+            );
+        }
+        submit(self.queue, encoder.finish(), self.device_lost);
+    }
+}
+
+/// Buffer of a [MockGpu], as an index into its buffers.
+#[cfg(test)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct MockBuffer(usize);
+
+/// [Gpu] keeping buffers in memory, which checks operations the way wgpu validates
+/// them.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct MockGpu {
+    buffers: std::cell::RefCell<Vec<(wgpu::BufferUsages, Vec<u8>)>>,
+    /// Number of times [Gpu::copy_buffers] submitted.
+    pub(crate) submissions: std::cell::Cell<usize>,
+}
+
+#[cfg(test)]
+impl MockGpu {
+    /// Gets the bytes of a buffer.
+    pub(crate) fn contents(&self, buffer: &MockBuffer) -> Vec<u8> {
+        self.buffers.borrow()[buffer.0].1.clone()
+    }
+}
+
+#[cfg(test)]
+impl Gpu for MockGpu {
+    type Buffer = MockBuffer;
+
+    fn create_buffer(&self, _label: &str, size: u64, usage: wgpu::BufferUsages) -> MockBuffer {
+        let mut buffers = self.buffers.borrow_mut();
+        buffers.push((usage, vec![0; size as usize]));
+        MockBuffer(buffers.len() - 1)
+    }
+
+    fn write_buffer(&self, buffer: &MockBuffer, offset: u64, data: &[u8]) {
+        assert_eq!(offset % wgpu::COPY_BUFFER_ALIGNMENT, 0);
+        assert_eq!(data.len() as u64 % wgpu::COPY_BUFFER_ALIGNMENT, 0);
+        let (usage, contents) = &mut self.buffers.borrow_mut()[buffer.0];
+        assert!(usage.contains(wgpu::BufferUsages::COPY_DST));
+        contents[offset as usize..offset as usize + data.len()].copy_from_slice(data);
+    }
+
+    fn copy_buffers(&self, _label: &str, copies: &[BufferCopy<MockBuffer>]) {
+        let mut buffers = self.buffers.borrow_mut();
+        for copy in copies {
+            assert_eq!(copy.size % wgpu::COPY_BUFFER_ALIGNMENT, 0);
+            let (usage, source) = &buffers[copy.source.0];
+            assert!(usage.contains(wgpu::BufferUsages::COPY_SRC));
+            let bytes = source[copy.source_offset as usize..][..copy.size as usize].to_vec();
+
+            let (usage, destination) = &mut buffers[copy.destination.0];
+            assert!(usage.contains(wgpu::BufferUsages::COPY_DST));
+            destination[copy.destination_offset as usize..][..bytes.len()].copy_from_slice(&bytes);
+        }
+        self.submissions.set(self.submissions.get() + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catch_device_loss() {
+        let device_lost = AtomicBool::new(false);
+        assert_eq!(catch_device_loss(&device_lost, || 1), Some(1));
+        assert!(!device_lost.load(Ordering::Relaxed));
+
+        assert_eq!(
+            catch_device_loss(&device_lost, || -> u32 { panic!("lost") }),
+            None
+        );
+        assert!(device_lost.load(Ordering::Relaxed));
+        // Nothing more is sent to a lost device.
+        assert_eq!(catch_device_loss(&device_lost, || 1), None);
+    }
+}
@@ -0,0 +1,282 @@
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+
+use super::{
+    create_render_pipeline_layout, create_render_pipeline_with_vertex_entry,
+    light_culling::LightBuffers,
+    render_operation::{RawRenderOperation, Shading},
+    LocalBuffer, RenderContext,
+};
+
+/// Source of the shader batches are drawn with.
+const SHADER_SOURCE: &str = concat!(include_str!("shader.wgsl"), include_str!("batched.wgsl"));
+
+/// Features needed to draw batches with multi-draw indirect.
+pub(crate) const INDIRECT_FEATURES: wgpu::Features =
+    wgpu::Features::MULTI_DRAW_INDIRECT.union(wgpu::Features::INDIRECT_FIRST_INSTANCE);
+
+/// Fewest consecutive operations drawn with one multi-draw, as a single draw gains
+/// nothing from it.
+const MIN_BATCH_LEN: usize = 2;
+
+/// Arguments of one indexed draw in an indirect buffer, as wgpu expects them.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct DrawIndexedIndirect {
+    pub(crate) index_count: u32,
+    pub(crate) instance_count: u32,
+    pub(crate) first_index: u32,
+    pub(crate) base_vertex: i32,
+    /// Index of the draw's uniforms in the batched locals.
+    pub(crate) first_instance: u32,
+}
+
+unsafe impl Zeroable for DrawIndexedIndirect {}
+unsafe impl Pod for DrawIndexedIndirect {}
+
+/// Gets the runs of consecutive operations that can be drawn with a single
+/// multi-draw, which are opaque operations with the default material sharing textures.
+///
+/// Every mesh is in the same buffers, so the draws only differ in their ranges and
+/// uniforms, which the batched shader reads from a storage buffer.
+pub(crate) fn find_batches(operations: &[RawRenderOperation]) -> Vec<Range<usize>> {
+    let batchable = |operation: &RawRenderOperation| {
+        matches!(operation.shading, Shading::Default) && !operation.transparent
+    };
+
+    let mut batches = Vec::new();
+    let mut start = 0;
+    while start < operations.len() {
+        let first = &operations[start];
+        let len = match batchable(first) {
+            true => operations[start..]
+                .iter()
+                .take_while(|operation| {
+                    batchable(operation) && operation.texture_group_ids == first.texture_group_ids
+                })
+                .count(),
+            false => 1,
+        };
+        if len >= MIN_BATCH_LEN {
+            batches.push(start..start + len);
+        }
+        start += len;
+    }
+    batches
+}
+
+/// Resources for drawing batches of operations with multi-draw indirect, created the
+/// first time a batch is drawn on adapters that support it.
+pub(crate) struct IndirectDrawer {
+    locals_bind_group_layout: wgpu::BindGroupLayout,
+    pub(crate) render_pipeline: wgpu::RenderPipeline,
+    /// Uniforms of every batched operation in a pass, along with their bind group.
+    locals: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+    /// Draw arguments of every batched operation in a pass.
+    arguments: Option<wgpu::Buffer>,
+}
+
+impl IndirectDrawer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        buffers_bind_group_layout: &wgpu::BindGroupLayout,
+        textures_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let locals_bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork batched locals bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            }),
+        );
+
+        // Batches are only ever opaque.
+        let (render_pipeline, _) = create_render_pipeline_with_vertex_entry(
+            device,
+            color_format,
+            "clockwork batched pipeline",
+            &create_render_pipeline_layout(
+                device,
+                "clockwork batched pipeline layout",
+                &[
+                    buffers_bind_group_layout,
+                    textures_bind_group_layout,
+                    &locals_bind_group_layout,
+                ],
+            ),
+            // Downlevel adapters, which have uniform light buffers, never draw batches.
+            wgpu::ShaderSource::Wgsl(LightBuffers::Tiled.shader_source(SHADER_SOURCE).into()),
+            "vs_batched",
+            "fs_batched",
+        );
+
+        Self {
+            locals_bind_group_layout,
+            render_pipeline,
+            locals: None,
+            arguments: None,
+        }
+    }
+
+    /// Writes the uniforms and draw arguments of a pass's batched operations, growing
+    /// the buffers if they're too small.
+    pub(crate) fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        locals: &[LocalBuffer],
+        arguments: &[DrawIndexedIndirect],
+    ) {
+        let locals_bytes: &[u8] = bytemuck::cast_slice(locals);
+        if self
+            .locals
+            .as_ref()
+            .is_none_or(|(buffer, _)| buffer.size() < locals_bytes.len() as u64)
+        {
+            let buffer = create_buffer(
+                device,
+                "clockwork batched locals buffer",
+                locals_bytes.len(),
+                wgpu::BufferUsages::STORAGE,
+            );
+            let bind_group = device.create_bind_group(
+                &(wgpu::BindGroupDescriptor {
+                    label: Some("clockwork batched locals bind group"),
+                    layout: &self.locals_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                }),
+            );
+            self.locals = Some((buffer, bind_group));
+        }
+
+        let arguments_bytes: &[u8] = bytemuck::cast_slice(arguments);
+        if self
+            .arguments
+            .as_ref()
+            .is_none_or(|buffer| buffer.size() < arguments_bytes.len() as u64)
+        {
+            self.arguments = Some(create_buffer(
+                device,
+                "clockwork indirect arguments buffer",
+                arguments_bytes.len(),
+                wgpu::BufferUsages::INDIRECT,
+            ));
+        }
+
+        let (locals_buffer, _) = self.locals.as_ref().expect("created above");
+        queue.write_buffer(locals_buffer, 0, locals_bytes);
+        queue.write_buffer(self.arguments_buffer(), 0, arguments_bytes);
+    }
+
+    /// Gets the bind group of the uniforms last written with [IndirectDrawer::write].
+    pub(crate) fn locals_bind_group(&self) -> &wgpu::BindGroup {
+        &self.locals.as_ref().expect("written before drawing").1
+    }
+
+    /// Gets the draw arguments last written with [IndirectDrawer::write].
+    pub(crate) fn arguments_buffer(&self) -> &wgpu::Buffer {
+        self.arguments.as_ref().expect("written before drawing")
+    }
+}
+
+impl RenderContext {
+    /// Sets whether batches of opaque operations with the default material are drawn
+    /// with multi-draw indirect, which is on by default where the adapter supports it.
+    ///
+    /// Turning it off draws them one at a time, such as to compare the draw calls in
+    /// [RenderContext::render_stats].
+    pub fn set_multi_draw_indirect(&mut self, enabled: bool) {
+        self.multi_draw_indirect = enabled;
+    }
+
+    /// Checks if batches are drawn with multi-draw indirect, which needs it enabled and
+    /// supported by the adapter, and the adapter not to be downlevel, see
+    /// [RenderContext::is_downlevel].
+    pub fn multi_draw_indirect(&self) -> bool {
+        self.multi_draw_indirect
+            && !self.downlevel
+            && self.device.features().contains(INDIRECT_FEATURES)
+    }
+}
+
+/// Creates a buffer with room for `len` bytes, rounded up to the next power of two so
+/// growing passes don't recreate it every frame.
+fn create_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    len: usize,
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    device.create_buffer(
+        &(wgpu::BufferDescriptor {
+            label: Some(label),
+            size: len.next_power_of_two() as u64,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec4};
+
+    use super::*;
+    use crate::{graphics::RenderOperation, util::repository::ResourceId};
+
+    #[test]
+    fn test_shader_validates() {
+        let source = LightBuffers::Tiled.shader_source(SHADER_SOURCE);
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_find_batches() {
+        let colored =
+            || RenderOperation::colored_mesh(Mat4::IDENTITY, ResourceId::new(0), Vec4::ONE);
+        let textured = || {
+            RenderOperation::textured_mesh(
+                Mat4::IDENTITY,
+                ResourceId::new(0),
+                ResourceId::new(1),
+                None,
+                Vec4::ONE,
+            )
+        };
+        let operations: Vec<RawRenderOperation> = [
+            colored(),
+            colored(),
+            colored(),
+            textured(),
+            textured().with_transparent(true),
+            textured(),
+            textured(),
+            colored(),
+        ]
+        .into_iter()
+        .map(RawRenderOperation::from)
+        .collect();
+
+        // The lone and transparent operations are drawn as usual.
+        assert_eq!(find_batches(&operations), vec![0..3, 5..7]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+
+use crate::{
+    graphics::{mesh::Mesh, texture::Texture},
+    util::repository::OutstandingHandle,
+};
+
+use super::RenderContext;
+
+/// Resources still allocated by a [RenderContext], see [RenderContext::leak_report].
+///
+/// The engine logs one when the application exits if
+/// [crate::EngineConfig::leak_report] is set, so resources that pile up over a long
+/// session stand out.
+pub struct LeakReport {
+    /// Meshes loaded.
+    pub meshes: usize,
+    /// Textures loaded, including render targets.
+    pub textures: usize,
+    /// Pipelines registered for custom materials.
+    pub material_pipelines: usize,
+    /// Environment maps loaded.
+    pub environment_maps: usize,
+    /// Texture bind groups cached, including those of PBR materials.
+    pub cached_bind_groups: usize,
+    /// Samplers cached.
+    pub samplers: usize,
+    /// Depth textures of render targets.
+    pub render_target_depth_textures: usize,
+    /// Readbacks still waiting on the gpu.
+    pub pending_readbacks: usize,
+    /// Size of the buffers every mesh's vertices and indices are allocated from.
+    pub mesh_buffer_bytes: u64,
+    /// Reference counted mesh handles still alive.
+    pub strong_meshes: Vec<OutstandingHandle<Mesh>>,
+    /// Reference counted texture handles still alive.
+    pub strong_textures: Vec<OutstandingHandle<Texture>>,
+}
+
+impl RenderContext {
+    /// Gets the resources the context still holds, along with the reference counted
+    /// handles still alive and where they were made.
+    pub fn leak_report(&self) -> LeakReport {
+        LeakReport {
+            meshes: self.meshes.len(),
+            textures: self.textures.len(),
+            material_pipelines: self.material_pipelines.len(),
+            environment_maps: self.environment_maps.len(),
+            cached_bind_groups: self.textures_bind_groups.len()
+                + self.pbr.as_ref().map_or(0, |pbr| pbr.bind_group_count()),
+            samplers: self.samplers.len(),
+            render_target_depth_textures: self.render_target_depth_textures.len(),
+            pending_readbacks: self.pending_readbacks.len(),
+            mesh_buffer_bytes: self.mesh_pool.size_bytes(),
+            strong_meshes: self.strong_meshes.outstanding(),
+            strong_textures: self.strong_textures.outstanding(),
+        }
+    }
+}
+
+impl LeakReport {
+    /// Checks if any reference counted handles are still alive, which likely means
+    /// something holding them was leaked.
+    pub fn has_outstanding_handles(&self) -> bool {
+        !self.strong_meshes.is_empty() || !self.strong_textures.is_empty()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "clockwork leak report")?;
+        writeln!(
+            f,
+            "  meshes: {} ({} bytes of mesh buffers)",
+            self.meshes, self.mesh_buffer_bytes
+        )?;
+        writeln!(f, "  textures: {}", self.textures)?;
+        writeln!(f, "  material pipelines: {}", self.material_pipelines)?;
+        writeln!(f, "  environment maps: {}", self.environment_maps)?;
+        writeln!(f, "  cached bind groups: {}", self.cached_bind_groups)?;
+        writeln!(f, "  samplers: {}", self.samplers)?;
+        writeln!(
+            f,
+            "  render target depth textures: {}",
+            self.render_target_depth_textures
+        )?;
+        writeln!(f, "  pending readbacks: {}", self.pending_readbacks)?;
+        write_outstanding(f, "mesh", &self.strong_meshes)?;
+        write_outstanding(f, "texture", &self.strong_textures)
+    }
+}
+
+/// Writes the handles still alive for a kind of resource.
+fn write_outstanding<T>(
+    f: &mut fmt::Formatter<'_>,
+    kind: &str,
+    handles: &[OutstandingHandle<T>],
+) -> fmt::Result {
+    writeln!(f, "  outstanding {kind} handles: {}", handles.len())?;
+    for handle in handles {
+        writeln!(
+            f,
+            "    {kind} {} held by {} handle(s)",
+            handle.id.index, handle.count
+        )?;
+        if let Some(backtrace) = &handle.backtrace {
+            for line in backtrace.lines() {
+                writeln!(f, "      {line}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::repository::ResourceId;
+
+    #[test]
+    fn test_leak_report_display() {
+        let report = LeakReport {
+            meshes: 2,
+            textures: 1,
+            material_pipelines: 0,
+            environment_maps: 0,
+            cached_bind_groups: 3,
+            samplers: 1,
+            render_target_depth_textures: 0,
+            pending_readbacks: 0,
+            mesh_buffer_bytes: 1024,
+            strong_meshes: vec![OutstandingHandle {
+                id: ResourceId::new(4),
+                count: 2,
+                backtrace: Some("at game::spawn\nat main".to_string()),
+            }],
+            strong_textures: Vec::new(),
+        };
+        let text = report.to_string();
+        assert!(text.contains("meshes: 2 (1024 bytes of mesh buffers)"));
+        assert!(text.contains("outstanding mesh handles: 1\n    mesh 4 held by 2 handle(s)"));
+        assert!(text.contains("      at game::spawn\n      at main\n"));
+        assert!(text.contains("outstanding texture handles: 0"));
+        assert!(report.has_outstanding_handles());
+    }
+}
@@ -0,0 +1,259 @@
+//! Tiled culling of the lights applied by the default and PBR shaders.
+//!
+//! The screen is split into a fixed grid of [LIGHT_TILES], and each render pass lists
+//! the lights that can reach each tile, so fragments only loop over the lights near
+//! them instead of every light in the scene.
+
+use bytemuck::bytes_of;
+use glam::{uvec2, vec2, Mat4, UVec2, Vec2, Vec3};
+
+use super::{
+    lighting::{Light, LightingBuffer, MAX_DOWNLEVEL_LIGHTS, MAX_LIGHTS},
+    DebugView, RenderContext,
+};
+
+/// Declarations of the light buffers when they are storage buffers.
+const TILED_SOURCE: &str = include_str!("lights_tiled.wgsl");
+
+/// Declarations of the light buffers when they are uniform buffers.
+const UNIFORM_SOURCE: &str = include_str!("lights_uniform.wgsl");
+
+/// Columns and rows of tiles the screen is split into for culling lights.
+pub(crate) const LIGHT_TILES: UVec2 = uvec2(16, 9);
+
+const TILE_COUNT: usize = (LIGHT_TILES.x * LIGHT_TILES.y) as usize;
+
+/// Most lights applied to a single tile of the screen. Lights past this are ignored
+/// within that tile.
+pub const MAX_TILE_LIGHTS: usize = 64;
+
+/// Length in `u32`s of the light tiles buffer bound at `@group(0) @binding(4)`, which
+/// holds the offset and count of each tile's light indices followed by the indices.
+pub(crate) const LIGHT_TILES_LEN: usize = TILE_COUNT * (2 + MAX_TILE_LIGHTS);
+
+/// How lights are handed to shaders, which depends on whether the adapter has storage
+/// buffers, see [RenderContext::is_downlevel].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LightBuffers {
+    /// Every light in a storage buffer, culled into tiles listed in a second one.
+    Tiled,
+    /// The first [MAX_DOWNLEVEL_LIGHTS] lights in a uniform buffer, followed by a
+    /// second one with their count, each applied to the whole screen.
+    Uniform,
+}
+
+impl LightBuffers {
+    pub(crate) fn new(downlevel: bool) -> Self {
+        match downlevel {
+            true => Self::Uniform,
+            false => Self::Tiled,
+        }
+    }
+
+    /// Adds the declarations of the light buffers to a shader built on the default
+    /// shader.
+    pub(crate) fn shader_source(self, source: &str) -> String {
+        let lights = match self {
+            Self::Tiled => TILED_SOURCE,
+            Self::Uniform => UNIFORM_SOURCE,
+        };
+        format!("{source}{lights}")
+    }
+
+    /// Gets the type of the light buffers' bindings.
+    pub(crate) fn binding_type(self) -> wgpu::BufferBindingType {
+        match self {
+            Self::Tiled => wgpu::BufferBindingType::Storage { read_only: true },
+            Self::Uniform => wgpu::BufferBindingType::Uniform,
+        }
+    }
+
+    /// Gets how the light buffers are used.
+    pub(crate) fn usage(self) -> wgpu::BufferUsages {
+        let usage = match self {
+            Self::Tiled => wgpu::BufferUsages::STORAGE,
+            Self::Uniform => wgpu::BufferUsages::UNIFORM,
+        };
+        usage | wgpu::BufferUsages::COPY_DST
+    }
+
+    /// Gets the most lights applied at once.
+    pub(crate) fn max_lights(self) -> usize {
+        match self {
+            Self::Tiled => MAX_LIGHTS,
+            Self::Uniform => MAX_DOWNLEVEL_LIGHTS,
+        }
+    }
+
+    /// Gets the size of the buffer bound at `@group(0) @binding(4)`.
+    pub(crate) fn tiles_size(self) -> u64 {
+        let len = match self {
+            Self::Tiled => LIGHT_TILES_LEN,
+            // The count padded to a vec4.
+            Self::Uniform => 4,
+        };
+        (len * std::mem::size_of::<u32>()) as u64
+    }
+}
+
+/// Gets the tiles a light can reach as a start and exclusive end, or `None` if it
+/// can't reach the screen at all.
+fn tile_range(light: &Light, view_projection: Mat4) -> Option<(UVec2, UVec2)> {
+    let Light::Point {
+        position, range, ..
+    } = *light
+    else {
+        return Some((UVec2::ZERO, LIGHT_TILES));
+    };
+
+    let corners = (0..8).map(|corner| {
+        let sign = |bit: i32| match corner & bit {
+            0 => -1.0,
+            _ => 1.0,
+        };
+        view_projection * (position + Vec3::new(sign(1), sign(2), sign(4)) * range).extend(1.0)
+    });
+    let mut min = Vec2::INFINITY;
+    let mut max = Vec2::NEG_INFINITY;
+    let (mut before_near, mut beyond_far) = (true, true);
+    for clip in corners {
+        before_near &= clip.z < 0.0;
+        beyond_far &= clip.z > clip.w;
+        if clip.w <= f32::EPSILON {
+            // Bounds crossing the camera's plane can cover any part of the screen.
+            min = Vec2::NEG_ONE;
+            max = Vec2::ONE;
+            continue;
+        }
+        let ndc = clip.truncate().truncate() / clip.w;
+        min = min.min(ndc);
+        max = max.max(ndc);
+    }
+    if before_near || beyond_far {
+        return None;
+    }
+
+    // Tiles go from the top left like the screen, so y is flipped.
+    let to_tiles = |ndc: Vec2| {
+        (vec2(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * LIGHT_TILES.as_vec2())
+            .clamp(Vec2::ZERO, LIGHT_TILES.as_vec2())
+    };
+    let (a, b) = (to_tiles(min), to_tiles(max));
+    let start = a.min(b).floor().as_uvec2();
+    let end = a.max(b).ceil().as_uvec2();
+    (start.x < end.x && start.y < end.y).then_some((start, end))
+}
+
+/// Lists the lights reaching each tile, packed as the light tiles buffer expects.
+pub(crate) fn cull_lights(lights: &[Light], view_projection: Mat4) -> Vec<u32> {
+    let mut tiles = vec![Vec::new(); TILE_COUNT];
+    for (index, light) in lights.iter().take(MAX_LIGHTS).enumerate() {
+        let Some((start, end)) = tile_range(light, view_projection) else {
+            continue;
+        };
+        for y in start.y..end.y {
+            for x in start.x..end.x {
+                let tile: &mut Vec<u32> = &mut tiles[(y * LIGHT_TILES.x + x) as usize];
+                if tile.len() < MAX_TILE_LIGHTS {
+                    tile.push(index as u32);
+                }
+            }
+        }
+    }
+
+    let mut packed = Vec::with_capacity(TILE_COUNT * 2 + tiles.iter().map(Vec::len).sum::<usize>());
+    let mut offset = (TILE_COUNT * 2) as u32;
+    for tile in tiles.iter() {
+        packed.extend([offset, tile.len() as u32]);
+        offset += tile.len() as u32;
+    }
+    packed.extend(tiles.into_iter().flatten());
+    packed
+}
+
+impl RenderContext {
+    /// Culls the lights from [RenderContext::set_lighting] against a render pass's
+    /// view, and uploads them for the pass to use.
+    pub(crate) fn cull_lights_for_pass(&mut self, view_projection: Mat4, target_size: UVec2) {
+        // Written even when unlit, as it also holds the debug view.
+        let mut lighting_buffer = LightingBuffer::new(self.lighting.as_ref());
+        lighting_buffer.target_size = target_size.as_vec2().to_array();
+        lighting_buffer.heatmap = self.light_heatmap as u32;
+        lighting_buffer.debug_view = DebugView::to_raw(self.debug_view);
+        self.queue
+            .write_buffer(&self.lighting_buffer, 0, bytes_of(&lighting_buffer));
+        self.counters.upload(std::mem::size_of::<LightingBuffer>());
+
+        let Some(lighting) = self.lighting.as_ref() else {
+            return;
+        };
+        let light_tiles = match self.light_buffers() {
+            LightBuffers::Tiled => cull_lights(&lighting.lights, view_projection),
+            LightBuffers::Uniform => {
+                vec![
+                    lighting.lights.len().min(MAX_DOWNLEVEL_LIGHTS) as u32,
+                    0,
+                    0,
+                    0,
+                ]
+            }
+        };
+        self.queue.write_buffer(
+            &self.light_tiles_buffer,
+            0,
+            bytemuck::cast_slice(&light_tiles),
+        );
+        self.counters
+            .upload(std::mem::size_of_val(light_tiles.as_slice()));
+    }
+
+    /// Sets whether lit operations are tinted by how many lights reach their part of
+    /// the screen, from blue for none to red for 16 or more, to find where culling
+    /// isn't keeping light counts down.
+    pub fn set_light_heatmap(&mut self, light_heatmap: bool) {
+        self.light_heatmap = light_heatmap;
+    }
+
+    /// Gets whether the light heatmap is shown, see [RenderContext::set_light_heatmap].
+    pub fn light_heatmap(&self) -> bool {
+        self.light_heatmap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cull_lights() {
+        let view_projection = Mat4::orthographic_rh(-8.0, 8.0, -4.5, 4.5, 0.0, 10.0)
+            * Mat4::look_at_rh(Vec3::Z, Vec3::ZERO, Vec3::Y);
+        let lights = [
+            Light::directional(Vec3::NEG_Z, Vec3::ONE, 1.0),
+            // Covers the top left tile only.
+            Light::point(Vec3::new(-7.5, 4.0, 0.0), Vec3::ONE, 1.0, 0.4),
+            // Off to the side of the screen.
+            Light::point(Vec3::new(20.0, 0.0, 0.0), Vec3::ONE, 1.0, 1.0),
+        ];
+        let packed = cull_lights(&lights, view_projection);
+        let tile = |index: usize| {
+            let (offset, count) = (packed[index * 2] as usize, packed[index * 2 + 1] as usize);
+            &packed[offset..offset + count]
+        };
+        assert_eq!(tile(0), [0, 1]);
+        assert_eq!(tile(1), [0]);
+        assert_eq!(tile(TILE_COUNT - 1), [0]);
+        assert_eq!(packed.len(), TILE_COUNT * 3 + 1);
+    }
+
+    #[test]
+    fn test_uniform_lights_source() {
+        assert!(UNIFORM_SOURCE.contains(&format!(
+            "const MAX_DOWNLEVEL_LIGHTS = {MAX_DOWNLEVEL_LIGHTS}u;"
+        )));
+        assert_eq!(
+            LightBuffers::Uniform.tiles_size(),
+            std::mem::size_of::<[u32; 4]>() as u64
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3, Vec4};
+
+use super::RenderContext;
+
+/// Most lights the default shader applies at once. Lights past this are ignored.
+///
+/// Only the lights reaching each part of the screen are applied there, up to
+/// [super::MAX_TILE_LIGHTS], so scenes can have many small point lights.
+pub const MAX_LIGHTS: usize = 1024;
+
+/// Most lights applied at once on adapters without storage buffers, such as with
+/// WebGL, where every light is applied to the whole screen, see
+/// [RenderContext::is_downlevel].
+pub const MAX_DOWNLEVEL_LIGHTS: usize = 64;
+
+/// Light source used by the default shader.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    /// Light coming from infinitely far away in a single direction, like the sun.
+    Directional {
+        /// Direction the light travels in.
+        direction: Vec3,
+        /// Color of the light.
+        color: Vec3,
+        /// Brightness the color is multiplied by.
+        intensity: f32,
+    },
+    /// Light shining outward from a position, like a lamp.
+    Point {
+        /// World position of the light.
+        position: Vec3,
+        /// Color of the light.
+        color: Vec3,
+        /// Brightness the color is multiplied by.
+        intensity: f32,
+        /// Distance at which the light fades out completely.
+        range: f32,
+    },
+}
+
+/// Lights applied to operations drawn with the default shader, see
+/// [RenderContext::set_lighting].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lighting {
+    /// Light applied evenly to everything, so faces turned away from every light
+    /// aren't black.
+    pub ambient: Vec3,
+    /// Lights in the scene, of which only the first [MAX_LIGHTS] are used, or
+    /// [MAX_DOWNLEVEL_LIGHTS] on downlevel adapters. Point lights are only applied to the
+    /// parts of the screen their range reaches.
+    pub lights: Vec<Light>,
+    /// Strength of specular highlights, where 0 disables them.
+    pub specular: f32,
+    /// How tight specular highlights are, where higher values give smaller, sharper
+    /// highlights.
+    pub shininess: f32,
+}
+
+/// Light as stored in the lights buffer bound at `@group(0) @binding(3)`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RawLight {
+    /// Direction for directional lights with w = 0, or position for point lights with
+    /// w = 1.
+    position: [f32; 4],
+    /// Color times intensity, and the range of point lights in w.
+    color: [f32; 4],
+}
+
+/// Uniform buffer of lighting settings bound at `@group(0) @binding(2)`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LightingBuffer {
+    ambient: [f32; 4],
+    /// Whether lighting is applied at all, so unlit scenes such as 2D sprites are drawn
+    /// with their texture's colors.
+    lit: u32,
+    specular: f32,
+    shininess: f32,
+    /// Whether to show the light heatmap, see [RenderContext::set_light_heatmap].
+    pub(crate) heatmap: u32,
+    /// Size of the render pass's target, to find the light tile of a fragment.
+    pub(crate) target_size: [f32; 2],
+    /// What operations show instead of their shading, see
+    /// [RenderContext::set_debug_view].
+    pub(crate) debug_view: u32,
+    _padding: u32,
+}
+
+unsafe impl Zeroable for RawLight {}
+unsafe impl Pod for RawLight {}
+
+unsafe impl Zeroable for LightingBuffer {}
+unsafe impl Pod for LightingBuffer {}
+
+impl Light {
+    /// Creates a new directional [Light].
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Self::Directional {
+            direction,
+            color,
+            intensity,
+        }
+    }
+
+    /// Creates a new point [Light].
+    pub fn point(position: Vec3, color: Vec3, intensity: f32, range: f32) -> Self {
+        Self::Point {
+            position,
+            color,
+            intensity,
+            range,
+        }
+    }
+
+    fn to_raw(self) -> RawLight {
+        match self {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => RawLight {
+                position: direction.normalize_or_zero().extend(0.0).to_array(),
+                color: (color * intensity).extend(0.0).to_array(),
+            },
+            Light::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => RawLight {
+                position: position.extend(1.0).to_array(),
+                color: (color * intensity)
+                    .extend(range.max(f32::EPSILON))
+                    .to_array(),
+            },
+        }
+    }
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            ambient: Vec3::splat(0.1),
+            lights: Vec::new(),
+            specular: 0.5,
+            shininess: 32.0,
+        }
+    }
+}
+
+impl Lighting {
+    /// Creates a new [Lighting] with the given ambient light and no other lights.
+    pub fn new(ambient: Vec3) -> Self {
+        Self {
+            ambient,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a light.
+    pub fn with_light(mut self, light: Light) -> Self {
+        self.lights.push(light);
+        self
+    }
+
+    /// Sets the strength and shininess of specular highlights.
+    pub fn with_specular(mut self, specular: f32, shininess: f32) -> Self {
+        self.specular = specular;
+        self.shininess = shininess;
+        self
+    }
+}
+
+impl LightingBuffer {
+    /// Packs lighting into the layout the default shader expects, or `None` for unlit.
+    pub(crate) fn new(lighting: Option<&Lighting>) -> Self {
+        let Some(lighting) = lighting else {
+            return Self::zeroed();
+        };
+
+        Self {
+            ambient: lighting.ambient.extend(0.0).to_array(),
+            lit: 1,
+            specular: lighting.specular,
+            shininess: lighting.shininess.max(1.0),
+            ..Self::zeroed()
+        }
+    }
+}
+
+/// Packs the first `max_lights` lights into the layout the lights buffer expects.
+pub(crate) fn raw_lights(lighting: &Lighting, max_lights: usize) -> Vec<RawLight> {
+    lighting
+        .lights
+        .iter()
+        .take(max_lights)
+        .map(|light| light.to_raw())
+        .collect()
+}
+
+/// Gets the camera of a view projection matrix as a homogeneous point, which is its
+/// position with w = 1 for perspective projections, or the direction it looks from
+/// with w = 0 for orthographic projections.
+pub(crate) fn camera_position(view_projection: Mat4) -> Vec4 {
+    // Every view ray passes through the point that projects to infinity along z.
+    let camera = view_projection.inverse() * Vec4::Z;
+    if camera.w.abs() > f32::EPSILON {
+        (camera.truncate() / camera.w).extend(1.0)
+    } else {
+        (-camera.truncate().normalize_or_zero()).extend(0.0)
+    }
+}
+
+impl RenderContext {
+    /// Sets the lighting applied by the default shader to render passes made after this,
+    /// or `None` to draw unlit, which is the default.
+    pub fn set_lighting(&mut self, lighting: Option<&Lighting>) {
+        self.lighting = lighting.cloned();
+        let Some(lighting) = lighting else {
+            self.queue.write_buffer(
+                &self.lighting_buffer,
+                0,
+                bytemuck::bytes_of(&LightingBuffer::new(None)),
+            );
+            self.counters.upload(std::mem::size_of::<LightingBuffer>());
+            return;
+        };
+
+        // The rest of the lighting buffer is written by each render pass, along with
+        // the lights reaching each tile.
+        let lights = raw_lights(lighting, self.light_buffers().max_lights());
+        self.queue
+            .write_buffer(&self.lights_buffer, 0, bytemuck::cast_slice(&lights));
+        self.counters
+            .upload(std::mem::size_of_val(lights.as_slice()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera_position() {
+        let eye = Vec3::new(3.0, 2.0, 5.0);
+        let perspective =
+            Mat4::perspective_rh(1.0, 1.5, 0.1, 100.0) * Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y);
+        assert!(camera_position(perspective).abs_diff_eq(eye.extend(1.0), 1e-3));
+
+        let orthographic = Mat4::orthographic_rh(-1.0, 1.0, -1.0, 1.0, 0.1, 10.0)
+            * Mat4::look_at_rh(Vec3::Z, Vec3::ZERO, Vec3::Y);
+        assert!(camera_position(orthographic).abs_diff_eq(Vec4::Z, 1e-5));
+    }
+
+    #[test]
+    fn test_lighting_buffer() {
+        assert_eq!(LightingBuffer::new(None).lit, 0);
+
+        let mut lighting = Lighting::new(Vec3::ZERO);
+        lighting.lights = vec![Light::point(Vec3::ONE, Vec3::ONE, 2.0, 5.0); MAX_LIGHTS + 1];
+        let buffer = LightingBuffer::new(Some(&lighting));
+        assert_eq!(buffer.lit, 1);
+        let lights = raw_lights(&lighting, MAX_LIGHTS);
+        assert_eq!(lights.len(), MAX_LIGHTS);
+        assert_eq!(
+            raw_lights(&lighting, MAX_DOWNLEVEL_LIGHTS).len(),
+            MAX_DOWNLEVEL_LIGHTS
+        );
+        assert_eq!(lights[0].color, [2.0, 2.0, 2.0, 5.0]);
+    }
+}
@@ -0,0 +1,17 @@
+// Lights in storage buffers, culled into tiles of the screen.
+
+@group(0) @binding(3)
+var<storage, read> lights: array<Light>;
+// The offset and count of each tile's light indices, followed by the indices.
+@group(0) @binding(4)
+var<storage, read> light_tiles: array<u32>;
+
+// Gets the number of lights reaching a tile.
+fn tile_light_count(tile: u32) -> u32 {
+    return light_tiles[tile * 2u + 1u];
+}
+
+// Gets one of the lights reaching a tile.
+fn tile_light(tile: u32, index: u32) -> Light {
+    return lights[light_tiles[light_tiles[tile * 2u] + index]];
+}
@@ -0,0 +1,20 @@
+// Lights in a uniform buffer, for adapters without storage buffers such as with
+// WebGL. Lights aren't culled, so every tile is reached by every light.
+
+const MAX_DOWNLEVEL_LIGHTS = 64u;
+
+@group(0) @binding(3)
+var<uniform> lights: array<Light, MAX_DOWNLEVEL_LIGHTS>;
+// The number of lights in x.
+@group(0) @binding(4)
+var<uniform> light_count: vec4<u32>;
+
+// Gets the number of lights reaching a tile.
+fn tile_light_count(tile: u32) -> u32 {
+    return min(light_count.x, MAX_DOWNLEVEL_LIGHTS);
+}
+
+// Gets one of the lights reaching a tile.
+fn tile_light(tile: u32, index: u32) -> Light {
+    return lights[index];
+}
@@ -0,0 +1,168 @@
+//! The uniforms of every operation in a render pass are written into one buffer with
+//! a single upload, and each draw binds it at its own dynamic offset, instead of each
+//! operation having its own buffer and bind group.
+
+use super::{eviction, render_operation::RawRenderOperation, LocalBuffer, RenderContext};
+
+/// Buffer of per-operation uniforms, along with the buffers bind group that binds it.
+pub(crate) struct LocalUniforms {
+    buffer: wgpu::Buffer,
+    /// Distance between operations' uniforms, see [local_stride].
+    stride: usize,
+    /// Number of operations the buffer fits.
+    capacity: usize,
+    pub(crate) bind_group: wgpu::BindGroup,
+    /// [RenderContext::frame_index] a render pass last needed more than half the
+    /// capacity, so the buffer can shrink again after a spike of operations.
+    last_needed: u64,
+}
+
+/// Gets the distance between operations' uniforms in the buffer, which must be a
+/// multiple of the device's uniform offset alignment.
+pub(crate) fn local_stride(device: &wgpu::Device) -> usize {
+    wgpu::util::align_to(
+        std::mem::size_of::<LocalBuffer>() as u32,
+        device.limits().min_uniform_buffer_offset_alignment,
+    ) as usize
+}
+
+/// Packs the uniforms of operations one stride apart.
+fn pack_locals(operations: &[RawRenderOperation], stride: usize) -> Vec<u8> {
+    let mut bytes = vec![0; operations.len() * stride];
+    for (chunk, operation) in bytes.chunks_exact_mut(stride).zip(operations) {
+        chunk[..std::mem::size_of::<LocalBuffer>()]
+            .copy_from_slice(bytemuck::bytes_of(&LocalBuffer::new(operation)));
+    }
+    bytes
+}
+
+impl LocalUniforms {
+    /// Gets the dynamic offset of the uniforms of the operation at an index.
+    pub(crate) fn offset(&self, index: usize) -> u32 {
+        (index * self.stride) as u32
+    }
+
+    /// Gets the size of the buffer in bytes.
+    pub(crate) fn size_bytes(&self) -> u64 {
+        self.buffer.size()
+    }
+
+    /// Gets whether the buffer has been larger than needed for long enough to be
+    /// dropped, see [eviction].
+    pub(crate) fn is_stale(&self, frame_index: u64) -> bool {
+        eviction::is_stale(self.last_needed, frame_index)
+    }
+}
+
+impl RenderContext {
+    /// Writes the uniforms of a render pass's operations, growing the buffer to fit
+    /// them.
+    pub(crate) fn write_local_uniforms(&mut self, operations: &[RawRenderOperation]) {
+        if operations.is_empty() {
+            return;
+        }
+
+        let stride = local_stride(&self.device);
+        let capacity = self
+            .local_uniforms
+            .as_ref()
+            .map_or(0, |local_uniforms| local_uniforms.capacity);
+        if capacity < operations.len() {
+            self.local_uniforms =
+                Some(self.create_local_uniforms(operations.len().next_power_of_two(), stride));
+        }
+        let frame_index = self.frame_index;
+        let local_uniforms = self.local_uniforms.as_mut().expect("created above");
+        if operations.len() * 2 > local_uniforms.capacity {
+            local_uniforms.last_needed = frame_index;
+        }
+
+        let bytes = pack_locals(operations, stride);
+        self.queue.write_buffer(&local_uniforms.buffer, 0, &bytes);
+        self.counters.upload(bytes.len());
+    }
+
+    fn create_local_uniforms(&self, capacity: usize, stride: usize) -> LocalUniforms {
+        let buffer = self.device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some("clockwork local buffer"),
+                size: (capacity * stride) as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        );
+        let bind_group = self.device.create_bind_group(
+            &(wgpu::BindGroupDescriptor {
+                label: Some("clockwork buffers bind group"),
+                layout: &self.buffers_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.global_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &buffer,
+                            offset: 0,
+                            size: wgpu::BufferSize::new(std::mem::size_of::<LocalBuffer>() as u64),
+                        }),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.lighting_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: self.lights_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: self.light_tiles_buffer.as_entire_binding(),
+                    },
+                ],
+            }),
+        );
+
+        LocalUniforms {
+            buffer,
+            stride,
+            capacity,
+            bind_group,
+            last_needed: self.frame_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3, Vec4};
+
+    use super::*;
+    use crate::{graphics::RenderOperation, util::repository::ResourceId};
+
+    #[test]
+    fn test_pack_locals() {
+        let operation = |x: f32| {
+            RawRenderOperation::from(RenderOperation::colored_mesh(
+                Mat4::from_translation(Vec3::X * x),
+                ResourceId::new(0),
+                Vec4::ONE,
+            ))
+        };
+        let operations = [operation(1.0), operation(2.0)];
+        let bytes = pack_locals(&operations, 256);
+        assert_eq!(bytes.len(), 512);
+
+        // The translation is the first three floats of the fourth column.
+        let translation_x = |offset: usize| {
+            let start = offset + 12 * std::mem::size_of::<f32>();
+            f32::from_ne_bytes(bytes[start..start + 4].try_into().unwrap())
+        };
+        assert_eq!(translation_x(0), 1.0);
+        assert_eq!(translation_x(256), 2.0);
+        assert!(bytes[std::mem::size_of::<LocalBuffer>()..256]
+            .iter()
+            .all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,34 @@
+
+@group(1) @binding(2)
+var normal_map: texture_2d<f32>;
+@group(1) @binding(3)
+var emissive_map: texture_2d<f32>;
+@group(1) @binding(4)
+var mask_map: texture_2d<f32>;
+
+// Like fs_main, with the normal bent by the normal map, the mask's red channel
+// multiplied into the alpha and light from the emissive map added after lighting.
+@fragment
+fn fs_mapped(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    // Everything needing uniform control flow happens before discarding.
+    let diffuse = textureSample(texture, texture_sampler, in.uv) * in.color * operation_color();
+    let mapped_normal = textureSample(normal_map, texture_sampler, in.uv).xyz * 2.0 - 1.0;
+    let emissive = textureSample(emissive_map, texture_sampler, in.uv).rgb * local.emissive.rgb;
+    let mask = textureSample(mask_map, texture_sampler, in.uv).r;
+    let normal = perturb_normal(normalize(in.normal), in.world_position, in.uv, mapped_normal);
+
+    let sample = diffuse * mask;
+    if (sample.w < 0.001) {
+        discard;
+    }
+
+    if (lighting.lit == 0u) {
+        return apply_debug_view(vec4<f32>(sample.rgb + emissive * sample.w, sample.w), in);
+    }
+
+    let color = apply_lighting(sample.rgb, in.world_position, normal, in.clip_position.xy);
+    let shaded = vec4<f32>(color + emissive * sample.w, sample.w);
+    return apply_debug_view(light_heatmap(shaded, in.clip_position.xy), in);
+}
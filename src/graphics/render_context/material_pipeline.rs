@@ -0,0 +1,74 @@
+use super::UniformField;
+
+/// Describes the resources a custom material's shader uses besides the engine's own.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MaterialLayout {
+    /// Size in bytes of the material's uniform buffer, which is bound at
+    /// `@group(2) @binding(0)`. Zero means the material has no uniforms.
+    pub uniform_size: u64,
+    /// Name the material's pipeline is labeled with in graphics debuggers.
+    pub name: Option<&'static str>,
+}
+
+/// Render pipeline built from a user supplied shader.
+pub struct MaterialPipeline {
+    /// Render pipeline, and the same without depth writes for transparent operations.
+    /// Missing until compiled by [super::RenderContext::warm_up_materials], in the
+    /// meantime the material is drawn with the default pipeline.
+    pub(crate) pipelines: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+
+    /// Size of the uniform buffer, rounded up to satisfy uniform alignment.
+    pub(crate) uniform_size: u64,
+
+    /// Uniform buffer and its bind group, if the material has uniforms.
+    pub(crate) uniforms: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+
+    /// Copy of the uniform buffer's contents, so fields can be read back by name.
+    pub(crate) uniform_data: Vec<u8>,
+
+    /// Fields of the uniforms, reflected from the shader.
+    pub(crate) uniform_fields: Vec<UniformField>,
+
+    /// See [MaterialLayout::name].
+    pub(crate) name: Option<&'static str>,
+
+    /// Source of the material's shader, kept to compile it again after the device is
+    /// lost.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) shader_source: String,
+}
+
+impl MaterialLayout {
+    /// Creates a [MaterialLayout] with a uniform buffer of the given size.
+    pub fn with_uniforms(uniform_size: u64) -> Self {
+        Self {
+            uniform_size,
+            name: None,
+        }
+    }
+
+    /// Sets the name the material's pipeline is labeled with in graphics debuggers.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+}
+
+/// Creates the bind group layout for a material's uniforms.
+pub(crate) fn create_material_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &(wgpu::BindGroupLayoutDescriptor {
+            label: Some("clockwork material bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        }),
+    )
+}
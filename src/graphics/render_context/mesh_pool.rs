@@ -0,0 +1,700 @@
+use std::ops::Range;
+
+use bytemuck::Zeroable;
+
+use super::gpu::{BufferCopy, Gpu};
+use crate::{
+    graphics::{Index, Mesh, MeshData, Vertex, VertexColor},
+    util::{
+        frustum::BoundingSphere,
+        repository::{Repository, ResourceId},
+    },
+};
+
+/// Vertices the pool has room for before it first grows.
+const INITIAL_VERTEX_CAPACITY: u32 = 1 << 14;
+
+/// Indices the pool has room for before it first grows.
+const INITIAL_INDEX_CAPACITY: u32 = 1 << 16;
+
+/// Ranges of a buffer handed out first fit from a sorted list of free ranges.
+#[derive(Clone, Debug)]
+pub(crate) struct RangeAllocator {
+    capacity: u32,
+    /// Free ranges in order, with touching ranges merged.
+    free: Vec<Range<u32>>,
+}
+
+impl RangeAllocator {
+    pub(crate) fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            free: (capacity > 0).then_some(0..capacity).into_iter().collect(),
+        }
+    }
+
+    /// Allocates the first free range long enough, if there is one.
+    pub(crate) fn allocate(&mut self, len: u32) -> Option<Range<u32>> {
+        if len == 0 {
+            return Some(0..0);
+        }
+        let index = self
+            .free
+            .iter()
+            .position(|free| free.end - free.start >= len)?;
+        let start = self.free[index].start;
+        self.free[index].start += len;
+        if self.free[index].is_empty() {
+            self.free.remove(index);
+        }
+        Some(start..start + len)
+    }
+
+    /// Frees an allocated range, merging it with the free ranges it touches.
+    pub(crate) fn free(&mut self, mut range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+        let index = self.free.partition_point(|free| free.start < range.start);
+        if self
+            .free
+            .get(index)
+            .is_some_and(|next| next.start == range.end)
+        {
+            range.end = self.free.remove(index).end;
+        }
+        match index.checked_sub(1) {
+            Some(previous) if self.free[previous].end == range.start => {
+                self.free[previous].end = range.end;
+            }
+            _ => self.free.insert(index, range),
+        }
+    }
+
+    /// Gets the total length of the free ranges.
+    pub(crate) fn free_len(&self) -> u32 {
+        self.free.iter().map(|free| free.end - free.start).sum()
+    }
+
+    /// Gets the free length between allocations, which only compaction can reclaim
+    /// for allocations longer than the gaps.
+    pub(crate) fn fragmented_len(&self) -> u32 {
+        let trailing = self
+            .free
+            .last()
+            .filter(|free| free.end == self.capacity)
+            .map_or(0, |free| free.end - free.start);
+        self.free_len() - trailing
+    }
+
+    /// Gets the capacity to repack into for an allocation of `len`, doubling until
+    /// everything fits.
+    fn capacity_for(&self, len: u32) -> u32 {
+        let needed = self.capacity - self.free_len() + len;
+        let mut capacity = self.capacity.max(1);
+        while capacity < needed {
+            capacity *= 2;
+        }
+        capacity
+    }
+}
+
+/// Vertices, colors and indices of every [Mesh], suballocated from a few large buffers
+/// rather than a few buffers per mesh.
+///
+/// The buffers are bound once per pass, with each mesh drawn from its own ranges. When
+/// an allocation doesn't fit, or enough space is lost to gaps between meshes, every
+/// mesh is copied into new buffers packed together, see [MeshPool::repack].
+///
+/// What's written to the buffers is also kept in memory, so the pool can be written
+/// again to a new device after the old one is lost, see [MeshPool::restore].
+pub(crate) struct MeshPool<Buffer = wgpu::Buffer> {
+    pub(crate) vertex_buffer: Buffer,
+    /// Color of each vertex, at the same index as the vertex.
+    pub(crate) color_buffer: Buffer,
+    pub(crate) index_buffer: Buffer,
+    vertices: RangeAllocator,
+    indices: RangeAllocator,
+    /// Contents of the vertex buffer, with meshes in their bind pose rather than as
+    /// deformed by skinning.
+    vertex_data: Vec<Vertex>,
+    color_data: Vec<VertexColor>,
+    index_data: Vec<Index>,
+}
+
+impl<Buffer> MeshPool<Buffer> {
+    pub(crate) fn new(gpu: &impl Gpu<Buffer = Buffer>) -> Self {
+        Self::with_capacity(gpu, INITIAL_VERTEX_CAPACITY, INITIAL_INDEX_CAPACITY)
+    }
+
+    fn with_capacity(
+        gpu: &impl Gpu<Buffer = Buffer>,
+        vertex_capacity: u32,
+        index_capacity: u32,
+    ) -> Self {
+        let buffer = |label, size: usize, usage| {
+            gpu.create_buffer(
+                label,
+                size as wgpu::BufferAddress,
+                usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            )
+        };
+        Self {
+            vertex_buffer: buffer(
+                "clockwork mesh pool vertex buffer",
+                vertex_capacity as usize * std::mem::size_of::<Vertex>(),
+                // Skinned meshes are deformed in place by a compute shader.
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            ),
+            color_buffer: buffer(
+                "clockwork mesh pool color buffer",
+                vertex_capacity as usize * std::mem::size_of::<VertexColor>(),
+                wgpu::BufferUsages::VERTEX,
+            ),
+            index_buffer: buffer(
+                "clockwork mesh pool index buffer",
+                index_capacity as usize * std::mem::size_of::<Index>(),
+                wgpu::BufferUsages::INDEX,
+            ),
+            vertices: RangeAllocator::new(vertex_capacity),
+            indices: RangeAllocator::new(index_capacity),
+            vertex_data: vec![Vertex::zeroed(); vertex_capacity as usize],
+            color_data: vec![[0; 4]; vertex_capacity as usize],
+            index_data: vec![0; index_capacity as usize],
+        }
+    }
+
+    /// Creates the pool's buffers again with the same contents, such as on a new
+    /// device after the old one was lost, so every mesh keeps its ranges.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn restore<NewBuffer>(
+        &self,
+        gpu: &impl Gpu<Buffer = NewBuffer>,
+    ) -> MeshPool<NewBuffer> {
+        let (vertex_capacity, index_capacity) = self.capacities();
+        let mut restored = MeshPool::with_capacity(gpu, vertex_capacity, index_capacity);
+        restored.vertices = self.vertices.clone();
+        restored.indices = self.indices.clone();
+        restored.vertex_data.clone_from(&self.vertex_data);
+        restored.color_data.clone_from(&self.color_data);
+        restored.index_data.clone_from(&self.index_data);
+        for (buffer, bytes) in [
+            (
+                &restored.vertex_buffer,
+                bytemuck::cast_slice(&restored.vertex_data),
+            ),
+            (
+                &restored.color_buffer,
+                bytemuck::cast_slice(&restored.color_data),
+            ),
+            (
+                &restored.index_buffer,
+                bytemuck::cast_slice(&restored.index_data),
+            ),
+        ] {
+            if !bytes.is_empty() {
+                gpu.write_buffer(buffer, 0, bytes);
+            }
+        }
+        restored
+    }
+
+    /// Gets the vertices of a mesh as they were written, before any skinning.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn mesh_vertices(&self, mesh: &Mesh) -> &[Vertex] {
+        &self.vertex_data[mesh.vertices.start as usize..mesh.vertices.end as usize]
+    }
+
+    /// Allocates and writes a mesh, with every vertex white if `colors` is [None].
+    ///
+    /// `meshes` are the meshes already in the pool, which are moved if it has to be
+    /// repacked to make room.
+    pub(crate) fn load(
+        &mut self,
+        gpu: &impl Gpu<Buffer = Buffer>,
+        meshes: &mut Repository<Mesh>,
+        mesh_data: MeshData,
+        colors: Option<&[VertexColor]>,
+    ) -> Mesh {
+        let (vertex_count, index_count) = (
+            mesh_data.vertices.len() as u32,
+            mesh_data.indices.len() as u32,
+        );
+        let mut mesh = match self.allocate(vertex_count, index_count) {
+            Some(mesh) => mesh,
+            None => {
+                self.repack(
+                    gpu,
+                    meshes,
+                    self.vertices.capacity_for(vertex_count),
+                    self.indices.capacity_for(index_count),
+                );
+                self.allocate(vertex_count, index_count)
+                    .expect("repacked with enough room")
+            }
+        };
+        self.write(gpu, &mut mesh, mesh_data, colors);
+        mesh
+    }
+
+    /// Rewrites a mesh in the pool with new data, with every vertex white if `colors` is
+    /// [None].
+    ///
+    /// The mesh keeps its ranges if the new data fits in them, with any space left over
+    /// freed, and otherwise moves to new ones. Returns [None] if there's no such mesh.
+    pub(crate) fn update(
+        &mut self,
+        gpu: &impl Gpu<Buffer = Buffer>,
+        meshes: &mut Repository<Mesh>,
+        mesh_id: ResourceId<Mesh>,
+        mesh_data: MeshData,
+        colors: Option<&[VertexColor]>,
+    ) -> Option<()> {
+        let (vertex_count, index_count) = (
+            mesh_data.vertices.len() as u32,
+            mesh_data.indices.len() as u32,
+        );
+        let mesh = meshes.get_mut(mesh_id)?;
+        let fits =
+            vertex_count <= mesh.vertices.len() as u32 && index_count <= mesh.indices.len() as u32;
+        match fits {
+            true => {
+                let vertex_end = mesh.vertices.start + vertex_count;
+                let index_end = mesh.indices.start + index_count;
+                self.vertices.free(vertex_end..mesh.vertices.end);
+                self.indices.free(index_end..mesh.indices.end);
+                mesh.vertices.end = vertex_end;
+                mesh.indices.end = index_end;
+
+                let mut mesh = std::mem::replace(mesh, Self::empty_mesh());
+                self.write(gpu, &mut mesh, mesh_data, colors);
+                meshes[mesh_id] = mesh;
+            }
+            false => {
+                // Emptied first so a repack to make room doesn't copy the old data.
+                let old = std::mem::replace(mesh, Self::empty_mesh());
+                self.free(&old);
+                meshes[mesh_id] = self.load(gpu, meshes, mesh_data, colors);
+            }
+        }
+        Some(())
+    }
+
+    /// Mesh without any vertices or indices.
+    fn empty_mesh() -> Mesh {
+        Mesh {
+            vertices: 0..0,
+            indices: 0..0,
+            bounds: BoundingSphere::default(),
+        }
+    }
+
+    /// Writes mesh data into the ranges of a mesh, which must fit it exactly.
+    fn write(
+        &mut self,
+        gpu: &impl Gpu<Buffer = Buffer>,
+        mesh: &mut Mesh,
+        mesh_data: MeshData,
+        colors: Option<&[VertexColor]>,
+    ) {
+        mesh.bounds =
+            BoundingSphere::from_points(mesh_data.vertices.iter().map(|vertex| vertex.position));
+
+        let white;
+        let colors = match colors {
+            Some(colors) => colors,
+            None => {
+                white = vec![[u8::MAX; 4]; mesh_data.vertices.len()];
+                &white
+            }
+        };
+        let write = |buffer, start: u32, element_size: usize, bytes: &[u8]| {
+            if !bytes.is_empty() {
+                gpu.write_buffer(buffer, (start as usize * element_size) as u64, bytes);
+            }
+        };
+        write(
+            &self.vertex_buffer,
+            mesh.vertices.start,
+            std::mem::size_of::<Vertex>(),
+            bytemuck::cast_slice(mesh_data.vertices),
+        );
+        write(
+            &self.color_buffer,
+            mesh.vertices.start,
+            std::mem::size_of::<VertexColor>(),
+            bytemuck::cast_slice(colors),
+        );
+        write(
+            &self.index_buffer,
+            mesh.indices.start,
+            std::mem::size_of::<Index>(),
+            bytemuck::cast_slice(mesh_data.indices),
+        );
+
+        let (vertices, indices) = (mesh.vertices.start as usize, mesh.indices.start as usize);
+        self.vertex_data[vertices..][..mesh_data.vertices.len()]
+            .copy_from_slice(mesh_data.vertices);
+        self.color_data[vertices..][..colors.len()].copy_from_slice(colors);
+        self.index_data[indices..][..mesh_data.indices.len()].copy_from_slice(mesh_data.indices);
+    }
+
+    /// Frees the ranges of a mesh, such as when it's destroyed.
+    pub(crate) fn free(&mut self, mesh: &Mesh) {
+        self.vertices.free(mesh.vertices.clone());
+        self.indices.free(mesh.indices.clone());
+    }
+
+    /// Checks whether over a quarter of the vertex or index buffer is lost to gaps
+    /// between meshes, which [MeshPool::repack] reclaims.
+    pub(crate) fn needs_compaction(&self) -> bool {
+        self.vertices.fragmented_len() > self.vertices.capacity / 4
+            || self.indices.fragmented_len() > self.indices.capacity / 4
+    }
+
+    /// Copies every mesh into new buffers with the given capacities, packed together
+    /// with the free space at the end.
+    ///
+    /// Frames in flight keep drawing from the old buffers, which wgpu keeps alive until
+    /// they're done.
+    pub(crate) fn repack(
+        &mut self,
+        gpu: &impl Gpu<Buffer = Buffer>,
+        meshes: &mut Repository<Mesh>,
+        vertex_capacity: u32,
+        index_capacity: u32,
+    ) {
+        let mut repacked = Self::with_capacity(gpu, vertex_capacity, index_capacity);
+        // Each mesh's old ranges, along with where they start in the new buffers.
+        let moves: Vec<(Mesh, u32, u32)> = meshes
+            .iter_mut()
+            .map(|(_, mesh)| {
+                let mut packed = repacked
+                    .allocate(mesh.vertices.len() as u32, mesh.indices.len() as u32)
+                    .expect("repacked buffers should fit every mesh");
+                packed.bounds = mesh.bounds;
+                let (vertex_start, index_start) = (packed.vertices.start, packed.indices.start);
+                (std::mem::replace(mesh, packed), vertex_start, index_start)
+            })
+            .collect();
+
+        let mut copies = Vec::new();
+        for (mesh, vertex_start, index_start) in moves.iter() {
+            let vertex_count = mesh.vertices.len() as u32;
+            let (vertices, indices) = (
+                mesh.vertices.start as usize..mesh.vertices.end as usize,
+                mesh.indices.start as usize..mesh.indices.end as usize,
+            );
+            repacked.vertex_data[*vertex_start as usize..][..vertices.len()]
+                .copy_from_slice(&self.vertex_data[vertices.clone()]);
+            repacked.color_data[*vertex_start as usize..][..vertices.len()]
+                .copy_from_slice(&self.color_data[vertices]);
+            repacked.index_data[*index_start as usize..][..indices.len()]
+                .copy_from_slice(&self.index_data[indices]);
+            copies.extend(copy_range(
+                &self.vertex_buffer,
+                &repacked.vertex_buffer,
+                mesh.vertices.start,
+                *vertex_start,
+                vertex_count,
+                std::mem::size_of::<Vertex>(),
+            ));
+            copies.extend(copy_range(
+                &self.color_buffer,
+                &repacked.color_buffer,
+                mesh.vertices.start,
+                *vertex_start,
+                vertex_count,
+                std::mem::size_of::<VertexColor>(),
+            ));
+            copies.extend(copy_range(
+                &self.index_buffer,
+                &repacked.index_buffer,
+                mesh.indices.start,
+                *index_start,
+                mesh.indices.len() as u32,
+                std::mem::size_of::<Index>(),
+            ));
+        }
+
+        gpu.copy_buffers("clockwork mesh pool repack encoder", &copies);
+        *self = repacked;
+    }
+
+    /// Allocates the ranges of a mesh, or [None] if either doesn't fit.
+    fn allocate(&mut self, vertex_count: u32, index_count: u32) -> Option<Mesh> {
+        let vertices = self.vertices.allocate(vertex_count)?;
+        match self.indices.allocate(index_count) {
+            Some(indices) => Some(Mesh {
+                vertices,
+                indices,
+                bounds: BoundingSphere::default(),
+            }),
+            None => {
+                self.vertices.free(vertices);
+                None
+            }
+        }
+    }
+
+    /// Gets how many vertices and indices the pool has room for.
+    pub(crate) fn capacities(&self) -> (u32, u32) {
+        (self.vertices.capacity, self.indices.capacity)
+    }
+
+    /// Gets the size of the pool's buffers in bytes.
+    pub(crate) fn size_bytes(&self) -> u64 {
+        let vertex_size = std::mem::size_of::<Vertex>() + std::mem::size_of::<VertexColor>();
+        (self.vertices.capacity as usize * vertex_size
+            + self.indices.capacity as usize * std::mem::size_of::<Index>()) as u64
+    }
+}
+
+/// Gets the copy of `len` elements of `element_size` bytes between buffers, if there
+/// are any.
+fn copy_range<'a, Buffer>(
+    source: &'a Buffer,
+    destination: &'a Buffer,
+    from: u32,
+    to: u32,
+    len: u32,
+    element_size: usize,
+) -> Option<BufferCopy<'a, Buffer>> {
+    (len > 0).then(|| BufferCopy {
+        source,
+        source_offset: (from as usize * element_size) as u64,
+        destination,
+        destination_offset: (to as usize * element_size) as u64,
+        size: (len as usize * element_size) as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, Vec3};
+
+    use super::*;
+    use crate::graphics::render_context::gpu::{MockBuffer, MockGpu};
+
+    fn vertices(x: f32, count: usize) -> Vec<Vertex> {
+        (0..count)
+            .map(|index| Vertex {
+                position: Vec3::new(x, index as f32, 0.0),
+                normal: Vec3::Z,
+                texture_coordinates: Vec2::ZERO,
+            })
+            .collect()
+    }
+
+    /// Gets the bytes of a mesh's vertices and indices in a pool.
+    fn read_mesh(gpu: &MockGpu, pool: &MeshPool<MockBuffer>, mesh: &Mesh) -> (Vec<u8>, Vec<u8>) {
+        let vertex_size = std::mem::size_of::<Vertex>();
+        let index_size = std::mem::size_of::<Index>();
+        (
+            gpu.contents(&pool.vertex_buffer)[mesh.vertices.start as usize * vertex_size
+                ..mesh.vertices.end as usize * vertex_size]
+                .to_vec(),
+            gpu.contents(&pool.index_buffer)
+                [mesh.indices.start as usize * index_size..mesh.indices.end as usize * index_size]
+                .to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_range_allocator() {
+        let mut allocator = RangeAllocator::new(10);
+        let a = allocator.allocate(3).unwrap();
+        let b = allocator.allocate(3).unwrap();
+        let c = allocator.allocate(3).unwrap();
+        assert_eq!((a.clone(), b.clone(), c.clone()), (0..3, 3..6, 6..9));
+        assert_eq!(allocator.allocate(2), None);
+        assert_eq!(allocator.allocate(0), Some(0..0));
+
+        allocator.free(a);
+        allocator.free(c);
+        assert_eq!(allocator.free_len(), 7);
+        // Only the gap at the front is fragmented, the rest is free at the end.
+        assert_eq!(allocator.fragmented_len(), 3);
+        assert_eq!(allocator.allocate(4), Some(6..10));
+
+        allocator.free(6..10);
+        allocator.free(b);
+        assert_eq!(allocator.free_len(), 10);
+        assert_eq!(allocator.fragmented_len(), 0);
+    }
+
+    #[test]
+    fn test_capacity_for() {
+        let mut allocator = RangeAllocator::new(8);
+        allocator.allocate(6).unwrap();
+        allocator.free(0..2);
+        // Fragmented space is reclaimed before growing.
+        assert_eq!(allocator.capacity_for(4), 8);
+        assert_eq!(allocator.capacity_for(5), 16);
+        assert_eq!(RangeAllocator::new(0).capacity_for(3), 4);
+    }
+
+    #[test]
+    fn test_repack_moves_meshes() {
+        let gpu = MockGpu::default();
+        let mut pool = MeshPool::with_capacity(&gpu, 8, 8);
+        let mut meshes = Repository::new();
+        let (a_vertices, b_vertices) = (vertices(1.0, 3), vertices(2.0, 3));
+        let load = |pool: &mut MeshPool<MockBuffer>, meshes: &mut Repository<Mesh>, vertices| {
+            let mesh_data = MeshData {
+                vertices,
+                indices: &[0, 1, 2],
+            };
+            let mesh = pool.load(&gpu, meshes, mesh_data, None);
+            meshes.add(mesh, None)
+        };
+        let a = load(&mut pool, &mut meshes, &a_vertices);
+        let b = load(&mut pool, &mut meshes, &b_vertices);
+        assert_eq!(meshes[b].vertices, 3..6);
+        let b_bounds = meshes[b].bounds;
+        assert_eq!(b_bounds.center, Vec3::new(2.0, 1.0, 0.0));
+        let b_bytes = read_mesh(&gpu, &pool, &meshes[b]);
+        assert_eq!(b_bytes.0, bytemuck::cast_slice::<Vertex, u8>(&b_vertices));
+
+        let a = meshes.remove(a).unwrap();
+        pool.free(&a);
+        assert!(pool.needs_compaction());
+        pool.repack(&gpu, &mut meshes, 8, 8);
+        assert_eq!(gpu.submissions.get(), 1);
+        assert_eq!(meshes[b].vertices, 0..3);
+        assert_eq!(read_mesh(&gpu, &pool, &meshes[b]), b_bytes);
+        assert_eq!(meshes[b].bounds, b_bounds);
+        assert!(!pool.needs_compaction());
+    }
+
+    #[test]
+    fn test_restore_after_repack() {
+        let gpu = MockGpu::default();
+        let mut pool = MeshPool::with_capacity(&gpu, 4, 4);
+        let mut meshes = Repository::new();
+        let (a_vertices, b_vertices) = (vertices(1.0, 3), vertices(2.0, 3));
+        for vertices in [&a_vertices, &b_vertices] {
+            let mesh_data = MeshData {
+                vertices,
+                indices: &[0, 1, 2],
+            };
+            let mesh = pool.load(&gpu, &mut meshes, mesh_data, None);
+            meshes.add(mesh, None);
+        }
+        // The second mesh only fit after the pool grew.
+        assert_eq!(pool.capacities(), (8, 8));
+        let bytes: Vec<_> = meshes
+            .iter()
+            .map(|(_, mesh)| read_mesh(&gpu, &pool, mesh))
+            .collect();
+
+        let new_gpu = MockGpu::default();
+        let restored = pool.restore(&new_gpu);
+        assert_eq!(restored.capacities(), pool.capacities());
+        for ((_, mesh), bytes) in meshes.iter().zip(bytes) {
+            assert_eq!(read_mesh(&new_gpu, &restored, mesh), bytes);
+        }
+        let (_, b) = meshes.iter().nth(1).unwrap();
+        assert_eq!(
+            bytemuck::cast_slice::<Vertex, u8>(restored.mesh_vertices(b)),
+            bytemuck::cast_slice::<Vertex, u8>(&b_vertices)
+        );
+    }
+
+    #[test]
+    fn test_update_in_place_and_moved() {
+        let gpu = MockGpu::default();
+        let mut pool = MeshPool::with_capacity(&gpu, 8, 8);
+        let mut meshes = Repository::new();
+        let first = vertices(1.0, 4);
+        let mesh = pool.load(
+            &gpu,
+            &mut meshes,
+            MeshData {
+                vertices: &first,
+                indices: &[0, 1, 2, 3],
+            },
+            None,
+        );
+        let mesh_id = meshes.add(mesh, None);
+
+        // Smaller data is written in place, freeing what's left over.
+        let smaller = vertices(2.0, 3);
+        pool.update(
+            &gpu,
+            &mut meshes,
+            mesh_id,
+            MeshData {
+                vertices: &smaller,
+                indices: &[0, 1, 2],
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(meshes[mesh_id].vertices, 0..3);
+        assert_eq!(meshes[mesh_id].bounds.center, Vec3::new(2.0, 1.0, 0.0));
+        assert_eq!(pool.vertices.free_len(), 5);
+        assert_eq!(
+            read_mesh(&gpu, &pool, &meshes[mesh_id]).0,
+            bytemuck::cast_slice::<Vertex, u8>(&smaller)
+        );
+
+        // Larger data moves, growing the pool if it has to.
+        let larger = vertices(3.0, 10);
+        let indices: Vec<Index> = (0..10).collect();
+        pool.update(
+            &gpu,
+            &mut meshes,
+            mesh_id,
+            MeshData {
+                vertices: &larger,
+                indices: &indices,
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(meshes[mesh_id].vertices.len(), 10);
+        assert_eq!(pool.vertices.free_len(), pool.capacities().0 - 10);
+        assert_eq!(
+            read_mesh(&gpu, &pool, &meshes[mesh_id]).0,
+            bytemuck::cast_slice::<Vertex, u8>(&larger)
+        );
+    }
+
+    #[test]
+    fn test_load_grows_pool() {
+        let gpu = MockGpu::default();
+        let mut pool = MeshPool::with_capacity(&gpu, 4, 4);
+        let mut meshes = Repository::new();
+        let small = vertices(1.0, 3);
+        let mesh = pool.load(
+            &gpu,
+            &mut meshes,
+            MeshData {
+                vertices: &small,
+                indices: &[0, 1, 2],
+            },
+            None,
+        );
+        let small_id = meshes.add(mesh, None);
+        let small_bytes = read_mesh(&gpu, &pool, &meshes[small_id]);
+
+        let large = vertices(2.0, 6);
+        let mesh = pool.load(
+            &gpu,
+            &mut meshes,
+            MeshData {
+                vertices: &large,
+                indices: &[0, 1, 2, 3, 4, 5],
+            },
+            None,
+        );
+        assert_eq!(pool.capacities(), (16, 16));
+        assert_eq!(mesh.vertices, 3..9);
+        assert_eq!(read_mesh(&gpu, &pool, &meshes[small_id]), small_bytes);
+        // Vertices without colors are white.
+        assert!(gpu.contents(&pool.color_buffer)[..9 * 4]
+            .iter()
+            .all(|byte| *byte == u8::MAX));
+    }
+}
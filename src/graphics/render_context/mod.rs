@@ -1,46 +1,218 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use anyhow::Result;
 use bytemuck::{bytes_of, Pod, Zeroable};
-use glam::UVec2;
+use glam::{Mat4, UVec2};
+#[cfg(not(target_arch = "wasm32"))]
 use pollster::block_on;
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use wgpu::util::DeviceExt;
 
 use crate::{
-    graphics::{mesh::VERTEX_BUFFER_LAYOUT, Mesh, MeshData},
-    util::repository::{Repository, ResourceId},
+    diagnostics::AllocStats,
+    error::ClockworkError,
+    graphics::{
+        mesh::{COLOR_BUFFER_LAYOUT, VERTEX_BUFFER_LAYOUT},
+        Mesh, MeshData, Model, ModelData, Submesh, VertexColor,
+    },
+    util::{
+        frustum::Frustum,
+        import_cache::ImportCache,
+        repository::{Repository, ResourceId, StrongResourceId, StrongResourceIds},
+    },
 };
 
-use super::texture::Texture;
-
+use super::texture::{self, SamplerSettings, Texture, TextureLoadOptions};
+
+mod adapter_selection;
+mod bloom;
+mod capabilities;
+mod debug_draw;
+mod debug_view;
+mod dynamic_resolution;
+mod environment;
+mod eviction;
+mod exposure;
+mod frame_pacing;
+mod gpu;
+mod indirect;
+mod leak_report;
+mod light_culling;
+mod lighting;
+mod local_uniforms;
+mod material_pipeline;
+mod mesh_pool;
+mod motion_blur;
+mod output_format;
+mod paint;
+mod pbr;
+mod pixel_perfect;
+mod post_process;
+mod post_process_stack;
+mod readback;
+#[cfg(not(target_arch = "wasm32"))]
+mod recovery;
 mod render_operation;
+mod render_pass;
+mod skinning;
+mod stats;
+mod stylistic;
+mod texture_array;
+mod texture_slots;
+#[cfg(feature = "ui")]
+mod ui_renderer;
+mod uniform_reflection;
+mod warmup;
+pub use adapter_selection::{AdapterInfo, AdapterSelection};
+pub use bloom::{Bloom, BloomSettings};
+pub use capabilities::GraphicsCapabilities;
+pub use debug_draw::DebugDraw;
+pub use debug_view::DebugView;
+pub use dynamic_resolution::{DynamicResolution, DynamicResolutionSettings};
+pub use environment::EnvironmentMap;
+pub use exposure::{AutoExposure, ExposureSettings};
+pub use frame_pacing::FrameLatencyStats;
+pub use leak_report::LeakReport;
+pub use light_culling::MAX_TILE_LIGHTS;
+pub use lighting::{Light, Lighting, MAX_DOWNLEVEL_LIGHTS, MAX_LIGHTS};
+pub use material_pipeline::{MaterialLayout, MaterialPipeline};
+pub use motion_blur::{MotionBlurSettings, VelocityBuffer, VelocityOperation};
+pub use output_format::OutputFormat;
+pub use paint::Brush;
+pub use pixel_perfect::PixelPerfect;
+pub use post_process_stack::{PostEffect, PostProcessStack, TonemapOperator, Tonemapping};
+pub use readback::{Readback, TextureReadback};
 pub use render_operation::*;
+pub use render_pass::*;
+pub use stats::RenderStats;
+pub use stylistic::{StylisticEffect, StylisticEffects};
+pub use texture_array::{TextureArray, TextureArrayInstance};
+use texture_slots::{FallbackTextures, TextureSlots, NORMAL_SLOT, TEXTURE_SLOTS};
+pub use uniform_reflection::{UniformField, UniformType, UniformValue};
+pub use warmup::{PipelineWarmup, WarmupPipeline};
+pub use wgpu::PresentMode;
+
+/// Backends adapters are searched on. Browsers render with WebGPU where it's
+/// available, falling back to WebGL.
+#[cfg(not(target_arch = "wasm32"))]
+const BACKENDS: wgpu::Backends = wgpu::Backends::PRIMARY;
+#[cfg(target_arch = "wasm32")]
+const BACKENDS: wgpu::Backends = wgpu::Backends::BROWSER_WEBGPU.union(wgpu::Backends::GL);
+
+/// Fewest storage buffers per shader stage needed outside the downlevel path, which
+/// is what compute skinning binds.
+const MIN_STORAGE_BUFFERS: u32 = 4;
+
+/// Checks if an adapter lacks the compute shaders or storage buffers that tiled light
+/// culling, compute skinning and multi-draw indirect need, such as with WebGL.
+fn is_downlevel(adapter: &wgpu::Adapter) -> bool {
+    let compute = adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+    !compute || adapter.limits().max_storage_buffers_per_shader_stage < MIN_STORAGE_BUFFERS
+}
+
+/// Limits requested from the device, which browsers and downlevel adapters cap below
+/// native GPUs.
+fn device_limits(downlevel: bool) -> wgpu::Limits {
+    match downlevel {
+        true => wgpu::Limits::downlevel_webgl2_defaults(),
+        false if cfg!(target_arch = "wasm32") => wgpu::Limits::downlevel_defaults(),
+        false => wgpu::Limits::default(),
+    }
+}
 
-/// TextureId for a blank white texture.
-const DEFAULT_TEXTURE_ID: ResourceId<Texture> = ResourceId::new(0);
+/// Shared ownership of gpu resources, which are only thread safe off the web.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type Shared<T> = Arc<T>;
+#[cfg(target_arch = "wasm32")]
+pub(crate) type Shared<T> = std::rc::Rc<T>;
+
+/// Source of the default shader, along with the fragment shader of mapped materials.
+const SHADER_SOURCE: &str = concat!(include_str!("shader.wgsl"), include_str!("mapped.wgsl"));
 
 /// Context for rendering visual elements.
 pub struct RenderContext {
-    pub(crate) device: wgpu::Device,
+    pub(crate) instance: wgpu::Instance,
+    pub(crate) adapter: wgpu::Adapter,
+    pub(crate) device: Shared<wgpu::Device>,
     pub(crate) queue: wgpu::Queue,
-    pub(crate) surface: wgpu::Surface,
+    /// Set once the device is lost, see [RenderContext::is_device_lost].
+    device_lost: AtomicBool,
+    /// Number of times the device was opened again after being lost, which effects
+    /// holding their own textures compare against, see [RenderContext::recover].
+    device_generation: u32,
+    /// Errors the gpu reported since they were last taken, see
+    /// [RenderContext::take_errors].
+    errors: Arc<Mutex<Vec<ClockworkError>>>,
+    /// Whether the adapter lacks compute shaders or storage buffers, see
+    /// [RenderContext::is_downlevel].
+    downlevel: bool,
+    /// Surface rendered to, or `None` while suspended, see [RenderContext::suspend].
+    pub(crate) surface: Option<wgpu::Surface>,
     pub(crate) surface_config: wgpu::SurfaceConfiguration,
 
     // -- BUFFERS --
     /// Bind group layout for the global and local buffers.
     buffers_bind_group_layout: wgpu::BindGroupLayout,
 
-    /// Contains all local buffers and associated bind groups.
-    bind_groups_and_buffers: Vec<(wgpu::BindGroup, wgpu::Buffer)>,
+    /// Buffer of the uniforms of each operation in a render pass, and the bind group
+    /// binding it along with the other buffers, see [local_uniforms].
+    local_uniforms: Option<local_uniforms::LocalUniforms>,
 
     /// Global buffer.
     global_buffer: wgpu::Buffer,
+
+    /// Lights used by the default shader, see [RenderContext::set_lighting].
+    lighting_buffer: wgpu::Buffer,
+
+    /// Buffer of every light in [RenderContext::lighting].
+    lights_buffer: wgpu::Buffer,
+
+    /// Buffer of the lights reaching each tile of the screen, see [light_culling], or
+    /// of how many lights there are on downlevel adapters.
+    light_tiles_buffer: wgpu::Buffer,
+
+    /// Lighting from [RenderContext::set_lighting], culled again for each render pass.
+    lighting: Option<Lighting>,
+
+    /// See [RenderContext::set_light_heatmap].
+    light_heatmap: bool,
+
+    /// See [RenderContext::set_debug_view].
+    debug_view: Option<DebugView>,
     // -------------
 
     // -- MESHES --
     /// Mesh resources.
     meshes: Repository<Mesh>,
+
+    /// Shared buffers the meshes' vertices and indices are allocated from.
+    mesh_pool: mesh_pool::MeshPool,
+
+    /// Reference counted handles to meshes, see [RenderContext::make_mesh_strong].
+    strong_meshes: StrongResourceIds<Mesh>,
+
+    /// Names of meshes, marked where they're drawn for graphics debuggers, see
+    /// [RenderContext::load_mesh_named].
+    mesh_labels: HashMap<ResourceId<Mesh>, String>,
+
+    /// Meshes by the names they were loaded with, see [RenderContext::mesh_id].
+    mesh_names: HashMap<String, ResourceId<Mesh>>,
+
+    /// Buffers of the meshes loaded with [RenderContext::load_skinned_mesh].
+    skinned_meshes: HashMap<ResourceId<Mesh>, skinning::SkinnedMesh>,
+
+    /// Pipeline for [RenderContext::skin_mesh], created along with the first skinned
+    /// mesh.
+    skinner: Option<skinning::Skinner>,
     // ------------
 
     // -- TEXTURES --
@@ -49,334 +221,1914 @@ pub struct RenderContext {
 
     /// Bind groups for textures.
     // todo: use a faster hashmap!
-    textures_bind_groups: HashMap<[ResourceId<Texture>; 1], ([usize; 1], wgpu::BindGroup)>,
+    /// Along with the generations of the textures they were created with and the
+    /// [RenderContext::frame_index] they were last used.
+    textures_bind_groups: HashMap<TextureSlots, ([usize; TEXTURE_SLOTS], wgpu::BindGroup, u64)>,
+
+    /// Textures bound in place of missing texture slots.
+    fallback_textures: FallbackTextures,
 
     /// Texture resources.
     textures: Repository<Texture>,
 
-    /// Sampler to use with the textures.
-    sampler: wgpu::Sampler,
+    /// Reference counted handles to textures, see [RenderContext::make_texture_strong].
+    strong_textures: StrongResourceIds<Texture>,
+
+    /// Textures by the names they were loaded with, see [RenderContext::texture_id].
+    texture_names: HashMap<String, ResourceId<Texture>>,
+
+    /// Samplers textures are drawn with, created as they are needed.
+    samplers: HashMap<SamplerSettings, wgpu::Sampler>,
+
+    /// Texture arrays loaded with [RenderContext::load_texture_array].
+    texture_arrays: Repository<texture_array::TextureArray>,
+
+    /// Resources for [RenderContext::render_texture_array], created the first time it
+    /// is used.
+    texture_array_drawer: Option<texture_array::TextureArrayDrawer>,
 
     /// Depth texture.
     depth_texture: Texture,
+
+    /// Depth textures for textures created as render targets.
+    render_target_depth_textures: HashMap<ResourceId<Texture>, Texture>,
     // --------------
+    /// Surface texture being rendered to between [RenderContext::begin_frame] and
+    /// [RenderContext::end_frame].
+    frame: Option<Frame>,
+
+    /// Whether the surface is zero-sized, in which case nothing is rendered to it.
+    minimized: bool,
+
+    /// Debug groups render passes are wrapped in, see [RenderContext::push_debug_group].
+    debug_groups: Vec<String>,
+
+    /// Readbacks waiting on the gpu, see [RenderContext::poll_readbacks].
+    pending_readbacks: Vec<readback::PendingReadback>,
+
+    /// Frames in flight on the gpu, see [RenderContext::set_max_frames_in_flight].
+    frame_pacer: frame_pacing::FramePacer,
+
+    /// Work counted during the current frame, see [RenderContext::render_stats].
+    counters: stats::RenderCounters,
+
+    /// Draw calls, uploaded bytes and indirect draws of the latest finished frame.
+    frame_counts: (u32, u64, u32),
+
+    /// Allocations made by render passes since they were last taken, see
+    /// [crate::diagnostics::FrameAllocStats::render].
+    render_allocs: AllocStats,
+
+    /// Number of frames ended so far, which caches track their last use with.
+    frame_index: u64,
 
     // -- RENDER PIPELINES --
     /// Main render pipeline for now.
     pub(crate) render_pipeline: wgpu::RenderPipeline,
+
+    /// Main render pipeline without depth writes, for transparent operations.
+    transparent_render_pipeline: wgpu::RenderPipeline,
+
+    /// Pipelines for [Material::Mapped], opaque and transparent.
+    mapped_render_pipelines: (wgpu::RenderPipeline, wgpu::RenderPipeline),
+
+    /// Whether batches are drawn with multi-draw indirect where supported, see
+    /// [RenderContext::set_multi_draw_indirect].
+    multi_draw_indirect: bool,
+
+    /// Resources for multi-draw indirect, created the first time a batch is drawn.
+    indirect_drawer: Option<indirect::IndirectDrawer>,
+
+    /// Whether operations outside the view are skipped, see
+    /// [RenderContext::set_frustum_culling].
+    frustum_culling: bool,
+
+    /// Bind group layout for custom material uniforms.
+    material_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// Pipelines registered for custom materials.
+    material_pipelines: Repository<MaterialPipeline>,
+
+    /// Resources for [RenderContext::paint], created the first time it is used.
+    painter: Option<paint::Painter>,
+
+    /// Environment maps for reflective materials.
+    environment_maps: Repository<EnvironmentMap>,
+
+    /// Resources for reflective materials, created along with the first environment map.
+    environment: Option<environment::EnvironmentRenderer>,
+
+    /// Resources for PBR materials, created the first time one is drawn.
+    pbr: Option<pbr::PbrRenderer>,
+
+    /// Resources for post process effects, created the first time one is used.
+    post_processor: Option<post_process::PostProcessor>,
+
+    /// Lines queued by [RenderContext::debug_draw].
+    debug_draw: DebugDraw,
+
+    /// Resources for [RenderContext::render_debug_draw], created the first time it is used.
+    debug_drawer: Option<debug_draw::DebugDrawer>,
+
+    /// Pipelines compiling in the background, see [RenderContext::warm_up_pipelines].
+    pending_warmups: Vec<std::sync::mpsc::Receiver<warmup::WarmedPipelines>>,
+
+    /// Debug UI drawn over the surface at the end of each frame.
+    #[cfg(feature = "ui")]
+    ui: ui_renderer::UiLayer,
     // ----------------------
 }
 
 impl RenderContext {
-    /// Creates a new [GraphicsContext].
-    pub(crate) fn new<Window: HasRawWindowHandle + HasRawDisplayHandle>(
-        window: &Window,
-        width: u32,
-        height: u32,
-    ) -> Self {
-        block_on(Self::new_async(window, width, height))
-    }
-
     /// Creates a new [GraphicsContext] asynchronously.
+    ///
+    /// `select_adapter` is given every adapter that can render to the window and
+    /// decides which one to use.
+    ///
+    /// Returns an error if no adapter can render to the window, or if the selected one
+    /// fails to open a device.
     pub(crate) async fn new_async<Window: HasRawWindowHandle + HasRawDisplayHandle>(
         window: &Window,
         width: u32,
         height: u32,
-    ) -> Self {
+        vsync: bool,
+        output_format: OutputFormat,
+        select_adapter: impl FnOnce(&[AdapterInfo]) -> AdapterSelection,
+    ) -> Result<Self, ClockworkError> {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
+            backends: BACKENDS,
             dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
         });
 
-        let surface = (unsafe { instance.create_surface(window) }).unwrap();
+        let surface = unsafe { instance.create_surface(window) }?;
 
-        let adapter = instance
-            .request_adapter(
-                &(wgpu::RequestAdapterOptionsBase {
-                    power_preference: wgpu::PowerPreference::HighPerformance,
-                    force_fallback_adapter: false,
-                    compatible_surface: Some(&surface),
-                }),
-            )
-            .await
-            .unwrap();
+        let mut adapters: Vec<wgpu::Adapter> = instance
+            .enumerate_adapters(BACKENDS)
+            .filter(|adapter| adapter.is_surface_supported(&surface))
+            .collect();
+        let adapter_infos: Vec<AdapterInfo> =
+            adapters.iter().map(|adapter| adapter.get_info()).collect();
+        let selection = select_adapter(&adapter_infos);
+
+        let named_adapter = match &selection {
+            AdapterSelection::Named(name) => adapter_infos
+                .iter()
+                .position(|info| &info.name == name)
+                .map(|index| adapters.swap_remove(index)),
+            _ => None,
+        };
+        let adapter = match named_adapter {
+            Some(adapter) => adapter,
+            None => instance
+                .request_adapter(
+                    &(wgpu::RequestAdapterOptionsBase {
+                        power_preference: selection.power_preference(),
+                        force_fallback_adapter: false,
+                        compatible_surface: Some(&surface),
+                    }),
+                )
+                .await
+                .ok_or_else(|| ClockworkError::NoCompatibleAdapter {
+                    selection: selection.clone(),
+                    available: adapter_infos.iter().map(|info| info.name.clone()).collect(),
+                })?,
+        };
+        let adapter_info = adapter.get_info();
+        log::info!(
+            "rendering with {} ({:?})",
+            adapter_info.name,
+            adapter_info.backend
+        );
+        let adapter_name = adapter_info.name;
+        let downlevel = is_downlevel(&adapter);
+        if downlevel {
+            log::info!(
+                "{adapter_name} has no compute shaders or storage buffers, so lights aren't \
+                 culled into tiles, skinning runs on the cpu and multi-draw indirect is off"
+            );
+        }
+        let light_buffers = light_culling::LightBuffers::new(downlevel);
 
         let (device, queue) = adapter
             .request_device(
                 &(wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::POLYGON_MODE_LINE,
-                    limits: Default::default(),
+                    label: Some("clockwork device"),
+                    features: adapter.features()
+                        & (wgpu::Features::POLYGON_MODE_LINE | indirect::INDIRECT_FEATURES),
+                    limits: device_limits(downlevel).using_resolution(adapter.limits()),
                 }),
                 None,
             )
             .await
-            .unwrap();
-
+            .map_err(|source| ClockworkError::RequestDevice {
+                adapter: adapter_name.clone(),
+                source,
+            })?;
+
+        // wgpu panics on every error by default, but they are collected for the
+        // engine to report instead, so a mistake in one draw doesn't end the app.
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let uncaptured_errors = Arc::clone(&errors);
+        device.on_uncaptured_error(Box::new(move |error| {
+            uncaptured_errors
+                .lock()
+                .unwrap()
+                .push(ClockworkError::Gpu(error.to_string()));
+        }));
+        let device_lost = AtomicBool::new(false);
+
+        // A zero-sized surface can't be configured, so hold off until the window
+        // has a size.
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_capabilities(&adapter).formats[0],
-            width,
-            height,
-            present_mode: wgpu::PresentMode::AutoVsync,
+            format: output_format::select_surface_format(
+                &surface.get_capabilities(&adapter).formats,
+                output_format,
+            )
+            .ok_or(ClockworkError::NoSurfaceFormat(adapter_name))?,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: present_mode_for(vsync),
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
             view_formats: vec![],
         };
-        surface.configure(&device, &surface_config);
+        if width != 0 && height != 0 {
+            surface.configure(&device, &surface_config);
+        }
 
         // -- BUFFERS --
-        let buffers_bind_group_layout = create_buffers_bind_group_layout(&device);
-        let bind_groups_and_buffers = Vec::new();
+        let buffers_bind_group_layout = create_buffers_bind_group_layout(&device, light_buffers);
         let global_buffer = device.create_buffer_init(
             &(wgpu::util::BufferInitDescriptor {
-                label: None,
+                label: Some("clockwork global buffer"),
                 contents: bytes_of(&GlobalBuffer::zeroed()),
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             }),
         );
+        let lighting_buffer = device.create_buffer_init(
+            &(wgpu::util::BufferInitDescriptor {
+                label: Some("clockwork lighting buffer"),
+                contents: bytes_of(&lighting::LightingBuffer::new(None)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }),
+        );
+        let lights_buffer = device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some("clockwork lights buffer"),
+                size: (light_buffers.max_lights() * std::mem::size_of::<lighting::RawLight>())
+                    as u64,
+                usage: light_buffers.usage(),
+                mapped_at_creation: false,
+            }),
+        );
+        let light_tiles_buffer = device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some("clockwork light tiles buffer"),
+                size: light_buffers.tiles_size(),
+                usage: light_buffers.usage(),
+                mapped_at_creation: false,
+            }),
+        );
 
         // -- MESHES --
         let meshes = Repository::new();
+        let mesh_pool = mesh_pool::MeshPool::new(&gpu::WgpuGpu::new(&device, &queue, &device_lost));
 
         // -- TEXTURES --
         let textures_bind_group_layout = create_textures_bind_group_layout(&device);
         let textures_bind_groups = HashMap::new();
+        let fallback_textures = FallbackTextures::new(&device, &queue);
         let textures = Repository::new();
-        let sampler = device.create_sampler(
-            &(wgpu::SamplerDescriptor {
-                label: None,
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Nearest,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                lod_min_clamp: 0.0,
-                lod_max_clamp: 0.0,
-                compare: None,
-                anisotropy_clamp: 1,
-                border_color: None,
-            }),
-        );
+        let samplers = HashMap::new();
         let depth_texture = Texture::create_depth_texture(
             &device,
             UVec2 {
-                x: width,
-                y: height,
+                x: width.max(1),
+                y: height.max(1),
             },
         );
 
         // -- RENDER PIPELINES --
-        let render_pipeline = create_render_pipeline(
+        let default_pipeline_layout = create_render_pipeline_layout(
             &device,
-            &create_render_pipeline_layout(
-                &device,
-                &buffers_bind_group_layout,
-                &textures_bind_group_layout,
-            ),
-            wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+            "clockwork default pipeline layout",
+            &[&buffers_bind_group_layout, &textures_bind_group_layout],
         );
-
-        Self {
-            device,
+        let shader_source = light_buffers.shader_source(SHADER_SOURCE);
+        let (render_pipeline, transparent_render_pipeline) = create_render_pipeline(
+            &device,
+            surface_config.format,
+            "clockwork default pipeline",
+            &default_pipeline_layout,
+            wgpu::ShaderSource::Wgsl(shader_source.as_str().into()),
+            "fs_main",
+        );
+        let mapped_render_pipelines = create_render_pipeline(
+            &device,
+            surface_config.format,
+            "clockwork mapped pipeline",
+            &default_pipeline_layout,
+            wgpu::ShaderSource::Wgsl(shader_source.into()),
+            "fs_mapped",
+        );
+        let material_bind_group_layout =
+            material_pipeline::create_material_bind_group_layout(&device);
+        let material_pipelines = Repository::new();
+
+        Ok(Self {
+            instance,
+            adapter,
+            device: Shared::new(device),
             queue,
-            surface,
+            device_lost,
+            device_generation: 0,
+            errors,
+            downlevel,
+            surface: Some(surface),
             surface_config,
 
             buffers_bind_group_layout,
-            bind_groups_and_buffers,
+            local_uniforms: None,
             global_buffer,
+            lighting_buffer,
+            lights_buffer,
+            light_tiles_buffer,
+            lighting: None,
+            light_heatmap: false,
+            debug_view: None,
 
             meshes,
+            mesh_pool,
+            strong_meshes: StrongResourceIds::new(),
+            mesh_labels: HashMap::new(),
+            mesh_names: HashMap::new(),
+            skinned_meshes: HashMap::new(),
+            skinner: None,
 
             textures_bind_group_layout,
             textures_bind_groups,
+            fallback_textures,
             textures,
-            sampler,
+            strong_textures: StrongResourceIds::new(),
+            texture_names: HashMap::new(),
+            samplers,
+            texture_arrays: Repository::new(),
+            texture_array_drawer: None,
             depth_texture,
+            render_target_depth_textures: HashMap::new(),
+
+            frame: None,
+            minimized: width == 0 || height == 0,
+            debug_groups: Vec::new(),
+            pending_readbacks: Vec::new(),
+            frame_pacer: frame_pacing::FramePacer::new(frame_pacing::DEFAULT_MAX_FRAMES_IN_FLIGHT),
+            counters: Default::default(),
+            frame_counts: (0, 0, 0),
+            render_allocs: AllocStats::default(),
+            frame_index: 0,
 
             render_pipeline,
-        }
+            transparent_render_pipeline,
+            mapped_render_pipelines,
+            multi_draw_indirect: true,
+            indirect_drawer: None,
+            frustum_culling: true,
+            material_bind_group_layout,
+            material_pipelines,
+            painter: None,
+            environment_maps: Repository::new(),
+            environment: None,
+            pbr: None,
+            debug_draw: DebugDraw::default(),
+            debug_drawer: None,
+            pending_warmups: Vec::new(),
+            #[cfg(feature = "ui")]
+            ui: ui_renderer::UiLayer::new(),
+            post_processor: None,
+        })
+    }
+
+    /// Lists every adapter (GPU) that can render to the window.
+    ///
+    /// Changing adapters requires restarting, so a choice from this list is meant to
+    /// be saved as an [AdapterSelection], such as in the engine config file, and used
+    /// as [crate::EngineConfig::adapter] next time.
+    pub fn enumerate_adapters(&self) -> Vec<AdapterInfo> {
+        self.instance
+            .enumerate_adapters(BACKENDS)
+            .filter(|adapter| {
+                self.surface
+                    .as_ref()
+                    .is_none_or(|surface| adapter.is_surface_supported(surface))
+            })
+            .map(|adapter| adapter.get_info())
+            .collect()
+    }
+
+    /// Gets information about the adapter (GPU) being rendered with.
+    pub fn current_adapter(&self) -> AdapterInfo {
+        self.adapter.get_info()
     }
 
     /// Loads a mesh and returns a [ResourceId<Mesh>] that refers to it.
     pub fn load_mesh(&mut self, mesh_data: MeshData) -> ResourceId<Mesh> {
-        let mesh = Mesh::load(&self.device, mesh_data);
+        self.counters.upload(
+            std::mem::size_of_val(mesh_data.vertices) + std::mem::size_of_val(mesh_data.indices),
+        );
+        let mesh = self.mesh_pool.load(
+            &gpu::WgpuGpu::new(&self.device, &self.queue, &self.device_lost),
+            &mut self.meshes,
+            mesh_data,
+            None,
+        );
         self.meshes.add(mesh, None)
     }
 
+    /// Loads a mesh like [RenderContext::load_mesh] under a name it can be found by with
+    /// [RenderContext::mesh_id], which also marks where it's drawn in graphics
+    /// debuggers such as RenderDoc.
+    ///
+    /// Loading another mesh with the same name takes the name over.
+    pub fn load_mesh_named(&mut self, name: &str, mesh_data: MeshData) -> ResourceId<Mesh> {
+        let mesh_id = self.load_mesh(mesh_data);
+        self.mesh_labels.insert(mesh_id, name.to_string());
+        self.mesh_names.insert(name.to_string(), mesh_id);
+        mesh_id
+    }
+
+    /// Finds a mesh by the name it was loaded with, see
+    /// [RenderContext::load_mesh_named].
+    pub fn mesh_id(&self, name: &str) -> Option<ResourceId<Mesh>> {
+        self.mesh_names.get(name).copied()
+    }
+
+    /// Replaces the vertices and indices of a loaded mesh, such as for particle trails
+    /// or deformed terrain that change every frame, without loading a new mesh.
+    ///
+    /// The mesh's space in the shared buffers is reused when the new data fits in it,
+    /// and otherwise the mesh moves to a larger space. Every vertex becomes white, as
+    /// with [RenderContext::load_mesh].
+    ///
+    /// Returns an error if the mesh doesn't exist.
+    pub fn update_mesh(&mut self, mesh_id: ResourceId<Mesh>, mesh_data: MeshData) -> Result<()> {
+        self.counters.upload(
+            std::mem::size_of_val(mesh_data.vertices) + std::mem::size_of_val(mesh_data.indices),
+        );
+        self.mesh_pool
+            .update(
+                &gpu::WgpuGpu::new(&self.device, &self.queue, &self.device_lost),
+                &mut self.meshes,
+                mesh_id,
+                mesh_data,
+                None,
+            )
+            .ok_or_else(|| anyhow::anyhow!("no mesh {mesh_id:?}"))
+    }
+
+    /// Replaces the vertices, indices and vertex colors of a loaded mesh, see
+    /// [RenderContext::update_mesh] and [RenderContext::load_mesh_with_colors].
+    ///
+    /// Returns an error if the mesh doesn't exist or there isn't exactly one color per
+    /// vertex.
+    pub fn update_mesh_with_colors(
+        &mut self,
+        mesh_id: ResourceId<Mesh>,
+        mesh_data: MeshData,
+        colors: &[VertexColor],
+    ) -> Result<()> {
+        anyhow::ensure!(
+            colors.len() == mesh_data.vertices.len(),
+            "expected {} vertex colors, got {}",
+            mesh_data.vertices.len(),
+            colors.len()
+        );
+        self.counters.upload(
+            std::mem::size_of_val(mesh_data.vertices)
+                + std::mem::size_of_val(mesh_data.indices)
+                + std::mem::size_of_val(colors),
+        );
+        self.mesh_pool
+            .update(
+                &gpu::WgpuGpu::new(&self.device, &self.queue, &self.device_lost),
+                &mut self.meshes,
+                mesh_id,
+                mesh_data,
+                Some(colors),
+            )
+            .ok_or_else(|| anyhow::anyhow!("no mesh {mesh_id:?}"))
+    }
+
+    /// Loads a mesh with a color for each vertex, such as for gradients or tinting
+    /// baked into geometry, and returns a [ResourceId<Mesh>] that refers to it.
+    ///
+    /// Colors are blended between vertices in linear space, and multiply the texture
+    /// and color the mesh is drawn with.
+    ///
+    /// Returns an error if there isn't exactly one color per vertex.
+    pub fn load_mesh_with_colors(
+        &mut self,
+        mesh_data: MeshData,
+        colors: &[VertexColor],
+    ) -> Result<ResourceId<Mesh>> {
+        anyhow::ensure!(
+            colors.len() == mesh_data.vertices.len(),
+            "expected {} vertex colors, got {}",
+            mesh_data.vertices.len(),
+            colors.len()
+        );
+        self.counters.upload(
+            std::mem::size_of_val(mesh_data.vertices)
+                + std::mem::size_of_val(mesh_data.indices)
+                + std::mem::size_of_val(colors),
+        );
+        let mesh = self.mesh_pool.load(
+            &gpu::WgpuGpu::new(&self.device, &self.queue, &self.device_lost),
+            &mut self.meshes,
+            mesh_data,
+            Some(colors),
+        );
+        Ok(self.meshes.add(mesh, None))
+    }
+
     /// Loads a texture and returns a [TextureId] that refers to it.
+    ///
+    /// The texture is sampled with [SamplerSettings::nearest], see
+    /// [RenderContext::load_texture_with_sampler] to choose otherwise.
     pub fn load_texture(&mut self, bytes: &[u8]) -> Result<ResourceId<Texture>> {
-        Ok(self
+        self.load_texture_with_sampler(bytes, SamplerSettings::default())
+    }
+
+    /// Loads a texture that is sampled as described by `sampler`, and returns a
+    /// [TextureId] that refers to it.
+    pub fn load_texture_with_sampler(
+        &mut self,
+        bytes: &[u8],
+        sampler: SamplerSettings,
+    ) -> Result<ResourceId<Texture>> {
+        self.load_texture_with_options(bytes, sampler, TextureLoadOptions::default())
+    }
+
+    /// Loads a texture like [RenderContext::load_texture_with_sampler], with its pixels
+    /// prepared as described by `load_options`.
+    pub fn load_texture_with_options(
+        &mut self,
+        bytes: &[u8],
+        sampler: SamplerSettings,
+        load_options: TextureLoadOptions,
+    ) -> Result<ResourceId<Texture>> {
+        self.load_texture_labeled(bytes, sampler, load_options, Texture::DEFAULT_LABEL)
+    }
+
+    /// Loads a texture like [RenderContext::load_texture] under a name it can be found
+    /// by with [RenderContext::texture_id], such as `"player"` or its path, which
+    /// graphics debuggers such as RenderDoc also show.
+    ///
+    /// Loading another texture with the same name takes the name over.
+    pub fn load_texture_named(&mut self, name: &str, bytes: &[u8]) -> Result<ResourceId<Texture>> {
+        self.load_texture_named_with_sampler(name, bytes, SamplerSettings::default())
+    }
+
+    /// Loads a texture like [RenderContext::load_texture_with_sampler], labeled with a
+    /// name, see [RenderContext::load_texture_named].
+    pub fn load_texture_named_with_sampler(
+        &mut self,
+        name: &str,
+        bytes: &[u8],
+        sampler: SamplerSettings,
+    ) -> Result<ResourceId<Texture>> {
+        let texture_id =
+            self.load_texture_labeled(bytes, sampler, TextureLoadOptions::default(), name)?;
+        self.texture_names.insert(name.to_string(), texture_id);
+        Ok(texture_id)
+    }
+
+    /// Finds a texture by the name it was loaded with, see
+    /// [RenderContext::load_texture_named].
+    pub fn texture_id(&self, name: &str) -> Option<ResourceId<Texture>> {
+        self.texture_names.get(name).copied()
+    }
+
+    fn load_texture_labeled(
+        &mut self,
+        bytes: &[u8],
+        sampler: SamplerSettings,
+        load_options: TextureLoadOptions,
+        label: &str,
+    ) -> Result<ResourceId<Texture>> {
+        Ok(self.textures.add(
+            Texture::load(
+                &self.device,
+                &self.queue,
+                bytes,
+                sampler,
+                load_options,
+                label,
+            )?,
+            None,
+        ))
+    }
+
+    /// Loads a texture like [RenderContext::load_texture_with_options], reusing the
+    /// decoded and mipmapped pixels of an earlier load from `cache`.
+    pub fn load_texture_cached(
+        &mut self,
+        cache: &ImportCache,
+        bytes: &[u8],
+        sampler: SamplerSettings,
+        load_options: TextureLoadOptions,
+    ) -> Result<ResourceId<Texture>> {
+        let (size, mip_levels) = cache.import_texture(bytes, sampler, load_options)?;
+        Ok(self.textures.add(
+            Texture {
+                sampler,
+                load_options,
+                ..Texture::from_mip_levels(
+                    &self.device,
+                    &self.queue,
+                    size,
+                    mip_levels,
+                    wgpu::TextureFormat::Rgba8UnormSrgb,
+                    Texture::DEFAULT_LABEL,
+                )
+            },
+            None,
+        ))
+    }
+
+    /// Loads a texture holding data rather than colors, such as the normal,
+    /// metallic-roughness and occlusion maps of a [PbrMaterial], and returns a
+    /// [TextureId] that refers to it.
+    ///
+    /// Data textures are sampled as is, without sRGB decoding or premultiplying alpha.
+    pub fn load_data_texture(
+        &mut self,
+        bytes: &[u8],
+        sampler: SamplerSettings,
+    ) -> Result<ResourceId<Texture>> {
+        let image = image::load_from_memory(bytes)?.to_rgba8();
+        Ok(self.textures.add(
+            Texture::from_data_rgba(
+                &self.device,
+                &self.queue,
+                UVec2::new(image.width(), image.height()),
+                &image,
+                sampler,
+                "clockwork data texture",
+            ),
+            None,
+        ))
+    }
+
+    /// Gets a reference counted handle to a mesh. Once every handle to it is dropped,
+    /// the mesh is destroyed at the end of the frame.
+    ///
+    /// Plain [ResourceId]s to the mesh don't keep it alive, so they shouldn't outlive
+    /// its handles.
+    pub fn make_mesh_strong(&mut self, mesh_id: ResourceId<Mesh>) -> StrongResourceId<Mesh> {
+        self.strong_meshes.make_strong(mesh_id)
+    }
+
+    /// Gets a reference counted handle to a texture. Once every handle to it is
+    /// dropped, the texture is destroyed at the end of the frame along with the bind
+    /// groups it was drawn with.
+    ///
+    /// Plain [ResourceId]s to the texture don't keep it alive, so they shouldn't outlive
+    /// its handles.
+    pub fn make_texture_strong(
+        &mut self,
+        texture_id: ResourceId<Texture>,
+    ) -> StrongResourceId<Texture> {
+        self.strong_textures.make_strong(texture_id)
+    }
+
+    /// Destroys the meshes and textures whose strong handles were all dropped.
+    ///
+    /// Buffers and textures the gpu is still using are kept alive by wgpu until it's
+    /// done with them, so this is safe while frames are in flight.
+    fn destroy_dropped_resources(&mut self) {
+        for mesh_id in self.strong_meshes.take_dropped() {
+            if let Some(mesh) = self.meshes.remove(mesh_id) {
+                self.mesh_pool.free(&mesh);
+            }
+            self.mesh_labels.remove(&mesh_id);
+            self.mesh_names.retain(|_, named_id| *named_id != mesh_id);
+            self.skinned_meshes.remove(&mesh_id);
+        }
+        if self.mesh_pool.needs_compaction() {
+            let (vertex_capacity, index_capacity) = self.mesh_pool.capacities();
+            self.mesh_pool.repack(
+                &gpu::WgpuGpu::new(&self.device, &self.queue, &self.device_lost),
+                &mut self.meshes,
+                vertex_capacity,
+                index_capacity,
+            );
+        }
+        for texture_id in self.strong_textures.take_dropped() {
+            self.remove_texture(texture_id);
+        }
+    }
+
+    /// Removes a texture along with its depth texture and the bind groups using it.
+    fn remove_texture(&mut self, texture_id: ResourceId<Texture>) {
+        self.textures.remove(texture_id);
+        self.texture_names
+            .retain(|_, named_id| *named_id != texture_id);
+        self.render_target_depth_textures.remove(&texture_id);
+        self.textures_bind_groups
+            .retain(|texture_ids, _| !texture_ids.contains(&Some(texture_id)));
+        if let Some(pbr) = &mut self.pbr {
+            pbr.forget_texture(texture_id);
+        }
+    }
+
+    /// Iterates over every loaded texture and its size in pixels, including render
+    /// targets.
+    pub fn texture_sizes(&self) -> impl Iterator<Item = (ResourceId<Texture>, UVec2)> + '_ {
+        self.textures
+            .iter()
+            .map(|(texture_id, texture)| (texture_id, texture.size))
+    }
+
+    /// Iterates over every loaded mesh.
+    pub fn mesh_ids(&self) -> impl Iterator<Item = ResourceId<Mesh>> + '_ {
+        self.meshes.iter().map(|(mesh_id, _)| mesh_id)
+    }
+
+    /// Changes how a texture is sampled.
+    ///
+    /// Mipmaps are only generated when a texture is loaded, so enabling them here has
+    /// no effect on textures loaded without them.
+    pub fn set_texture_sampler(
+        &mut self,
+        texture_id: ResourceId<Texture>,
+        sampler: SamplerSettings,
+    ) -> Result<()> {
+        let texture = self
+            .textures
+            .get_mut(texture_id)
+            .ok_or_else(|| anyhow::anyhow!("no texture {texture_id:?}"))?;
+        texture.sampler = sampler;
+        self.textures_bind_groups
+            .retain(|texture_ids, _| !texture_ids.contains(&Some(texture_id)));
+        Ok(())
+    }
+
+    /// Loads a texture from raw RGBA8 pixels and returns a [TextureId] that refers to it.
+    pub fn load_texture_rgba(&mut self, size: UVec2, rgba: &[u8]) -> Result<ResourceId<Texture>> {
+        anyhow::ensure!(
+            rgba.len() == (size.x * size.y * 4) as usize,
+            "expected {} bytes for a {}x{} texture, got {}",
+            size.x * size.y * 4,
+            size.x,
+            size.y,
+            rgba.len()
+        );
+        Ok(self.textures.add(
+            Texture::from_rgba(
+                &self.device,
+                &self.queue,
+                size,
+                rgba,
+                SamplerSettings::default(),
+                TextureLoadOptions::default(),
+                Texture::DEFAULT_LABEL,
+            ),
+            None,
+        ))
+    }
+
+    /// Overwrites a region of a texture with raw RGBA8 pixels, where `offset` is the
+    /// region's top left corner in pixels.
+    pub fn update_texture_region(
+        &mut self,
+        texture_id: ResourceId<Texture>,
+        offset: UVec2,
+        size: UVec2,
+        rgba: &[u8],
+    ) -> Result<()> {
+        let texture = self
             .textures
-            .add(Texture::load(&self.device, &self.queue, bytes)?, None))
+            .get_mut(texture_id)
+            .ok_or_else(|| anyhow::anyhow!("no texture {texture_id:?}"))?;
+        anyhow::ensure!(
+            (offset + size).cmple(texture.size).all(),
+            "region at {offset} of size {size} is outside of the {} texture",
+            texture.size
+        );
+        anyhow::ensure!(
+            rgba.len() == (size.x * size.y * 4) as usize,
+            "expected {} bytes for a {}x{} region, got {}",
+            size.x * size.y * 4,
+            size.x,
+            size.y,
+            rgba.len()
+        );
+
+        let mut pixels: Vec<u8> = match texture.format {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => rgba
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+                .collect(),
+            _ => rgba.to_vec(),
+        };
+        // Keep regions of loaded textures consistent with how they were loaded.
+        if texture.format == wgpu::TextureFormat::Rgba8UnormSrgb
+            && texture.load_options.premultiply_alpha
+        {
+            texture::premultiply_alpha(&mut pixels);
+        }
+        // Later mip levels are left as they were, as they are on the gpu.
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(base) = texture
+            .contents
+            .as_mut()
+            .and_then(|levels| levels.first_mut())
+        {
+            texture::copy_region(base, texture.size.x, offset, size, &pixels);
+        }
+
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: offset.x,
+                    y: offset.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(size.x * 4),
+                rows_per_image: Some(size.y),
+            },
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+        Ok(())
+    }
+
+    /// Loads the meshes and textures of imported model data and returns a [Model]
+    /// that refers to them.
+    ///
+    /// Skinned parts are loaded with [RenderContext::load_skinned_mesh], and are posed
+    /// with [RenderContext::pose_model].
+    ///
+    /// Returns an error if a skinned part refers to joints its skeleton doesn't have.
+    pub fn load_model(&mut self, model_data: &ModelData) -> Result<Model> {
+        // Metallic-roughness, normal and occlusion maps hold data rather than colors, so
+        // their images are loaded as data textures instead, or as well if they are also
+        // used for colors.
+        let is_data = |index: usize| {
+            model_data.materials.iter().any(|material| {
+                [
+                    material.metallic_roughness_texture,
+                    material.normal_texture,
+                    material.occlusion_texture,
+                ]
+                .contains(&Some(index))
+            })
+        };
+        let is_color = |index: usize| {
+            !is_data(index)
+                || model_data.materials.iter().any(|material| {
+                    [material.texture, material.emissive_texture].contains(&Some(index))
+                })
+        };
+        let texture_ids = model_data
+            .images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                is_color(index)
+                    .then(|| self.load_texture_rgba(image.size, &image.rgba))
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let data_texture_ids: Vec<Option<ResourceId<Texture>>> = model_data
+            .images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                is_data(index).then(|| {
+                    self.textures.add(
+                        Texture::from_data_rgba(
+                            &self.device,
+                            &self.queue,
+                            image.size,
+                            &image.rgba,
+                            SamplerSettings::default(),
+                            "clockwork model data texture",
+                        ),
+                        None,
+                    )
+                })
+            })
+            .collect();
+
+        let submeshes = model_data
+            .submeshes
+            .iter()
+            .map(|submesh| {
+                let material = submesh
+                    .material
+                    .and_then(|index| model_data.materials.get(index))
+                    .cloned()
+                    .unwrap_or_default();
+                let texture =
+                    |index: Option<usize>| index.and_then(|index| *texture_ids.get(index)?);
+                let data_texture =
+                    |index: Option<usize>| index.and_then(|index| *data_texture_ids.get(index)?);
+
+                let mesh_data = MeshData {
+                    vertices: &submesh.vertices,
+                    indices: &submesh.indices,
+                };
+                let mesh_id = match &submesh.skin {
+                    Some(skin) => {
+                        let joint_count = model_data
+                            .skeletons
+                            .get(skin.skeleton)
+                            .map_or(0, |skeleton| skeleton.joints.len());
+                        self.load_skinned_mesh(mesh_data, &skin.weights, joint_count)?
+                    }
+                    None => self.load_mesh(mesh_data),
+                };
+                if let Some(name) = &submesh.name {
+                    self.mesh_labels.insert(mesh_id, name.clone());
+                }
+
+                Ok(Submesh {
+                    mesh_id,
+                    transform: submesh.transform,
+                    color: material.base_color,
+                    texture_id: texture(material.texture),
+                    material: PbrMaterial {
+                        base_color: material.base_color,
+                        base_color_texture: texture(material.texture),
+                        metallic: material.metallic,
+                        roughness: material.roughness,
+                        metallic_roughness_texture: data_texture(
+                            material.metallic_roughness_texture,
+                        ),
+                        normal_texture: data_texture(material.normal_texture),
+                        normal_scale: material.normal_scale,
+                        occlusion_texture: data_texture(material.occlusion_texture),
+                        occlusion_strength: material.occlusion_strength,
+                        emissive: material.emissive,
+                        emissive_texture: texture(material.emissive_texture),
+                        environment_map_id: None,
+                    },
+                    skeleton: submesh.skin.as_ref().map(|skin| skin.skeleton),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Model {
+            submeshes,
+            skeletons: model_data.skeletons.clone(),
+            animations: model_data.animations.clone(),
+        })
+    }
+
+    /// Registers a custom material from WGSL source and returns a
+    /// [ResourceId<MaterialPipeline>] to use with [Material::Custom].
+    ///
+    /// The shader must provide `vs_main` and `fs_main` entry points, and has access to
+    /// the same resources as the default shader:
+    /// - Vertex inputs `position` (location 0), `normal` (location 1), `uv` (location 2)
+    ///   and `color` (location 3), which is in sRGB as given to
+    ///   [RenderContext::load_mesh_with_colors].
+    /// - `@group(0) @binding(0)` the global uniforms (`mvp: mat4x4<f32>`,
+    ///   `camera: vec4<f32>`).
+    /// - `@group(0) @binding(1)` the per-operation uniforms (`transform: mat4x4<f32>`,
+    ///   `uv_window: vec4<f32>`, `normal_transform: mat4x4<f32>`,
+    ///   `parameters: vec4<f32>`, `color: vec4<f32>`, `emissive: vec4<f32>`,
+    ///   `data: array<vec4<f32>, 4>`), where `data` is the operation's
+    ///   [CustomMaterial::data].
+    /// - `@group(0) @binding(2)` the lighting settings from [RenderContext::set_lighting],
+    ///   then bindings 3 and 4 the storage buffers of lights and of the lights reaching
+    ///   each tile of the screen, laid out as in the default shader. On downlevel
+    ///   adapters, see [RenderContext::is_downlevel], these are instead uniform buffers
+    ///   of [MAX_DOWNLEVEL_LIGHTS] lights and of their count.
+    /// - `@group(1) @binding(0)` a sampler, then bindings 1 to 4 the texture, normal
+    ///   map, emissive map and mask of [CustomMaterial::maps]. Missing textures are
+    ///   white, and missing normal maps point straight out of the surface.
+    /// - `@group(2) @binding(0)` the material's own uniforms, if
+    ///   [MaterialLayout::uniform_size] is not zero.
+    ///
+    /// Returns an error if the shader fails to compile or doesn't match the layout.
+    pub fn register_material(
+        &mut self,
+        shader_source: &str,
+        layout: MaterialLayout,
+    ) -> Result<ResourceId<MaterialPipeline>> {
+        let name = layout.name.unwrap_or("clockwork material");
+        let pipeline_layout = self.create_material_pipeline_layout(name, layout);
+
+        // Browsers report errors asynchronously, so they can't be waited on here and
+        // are logged to the console instead.
+        #[cfg(not(target_arch = "wasm32"))]
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipelines = create_render_pipeline(
+            &self.device,
+            self.color_format(),
+            &format!("{name} pipeline"),
+            &pipeline_layout,
+            wgpu::ShaderSource::Wgsl(shader_source.into()),
+            "fs_main",
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(error) = block_on(self.device.pop_error_scope()) {
+            anyhow::bail!("failed to create material pipeline: {error}");
+        }
+
+        Ok(self.add_material(shader_source, layout, Some(pipelines)))
+    }
+
+    /// Creates the layout of a custom material's pipelines.
+    pub(crate) fn create_material_pipeline_layout(
+        &self,
+        name: &str,
+        layout: MaterialLayout,
+    ) -> wgpu::PipelineLayout {
+        let bind_group_layouts: &[&wgpu::BindGroupLayout] = match layout.uniform_size {
+            0 => &[
+                &self.buffers_bind_group_layout,
+                &self.textures_bind_group_layout,
+            ],
+            _ => &[
+                &self.buffers_bind_group_layout,
+                &self.textures_bind_group_layout,
+                &self.material_bind_group_layout,
+            ],
+        };
+        create_render_pipeline_layout(
+            &self.device,
+            &format!("{name} pipeline layout"),
+            bind_group_layouts,
+        )
+    }
+
+    /// Adds a custom material along with its uniforms, with its pipelines if they
+    /// have been compiled.
+    pub(crate) fn add_material(
+        &mut self,
+        shader_source: &str,
+        layout: MaterialLayout,
+        pipelines: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+    ) -> ResourceId<MaterialPipeline> {
+        let material_pipeline = self.create_material(shader_source, layout, pipelines);
+        self.material_pipelines.add(material_pipeline, None)
+    }
+
+    /// Creates a custom material's uniforms, with its pipelines if they have been
+    /// compiled.
+    fn create_material(
+        &self,
+        shader_source: &str,
+        layout: MaterialLayout,
+        pipelines: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+    ) -> MaterialPipeline {
+        let uniform_size = wgpu::util::align_to(layout.uniform_size, 16);
+        let name = layout.name.unwrap_or("clockwork material");
+        let uniforms = (uniform_size > 0).then(|| {
+            let buffer = self.device.create_buffer(
+                &(wgpu::BufferDescriptor {
+                    label: Some(&format!("{name} uniform buffer")),
+                    size: uniform_size,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+            );
+            let bind_group = self.device.create_bind_group(
+                &(wgpu::BindGroupDescriptor {
+                    label: Some(&format!("{name} uniforms bind group")),
+                    layout: &self.material_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                }),
+            );
+            (buffer, bind_group)
+        });
+
+        let uniform_fields = match uniforms {
+            Some(_) => uniform_reflection::reflect_uniform_fields(shader_source, 2, 0),
+            None => Vec::new(),
+        };
+
+        MaterialPipeline {
+            pipelines,
+            uniform_size,
+            uniforms,
+            uniform_data: vec![0; uniform_size as usize],
+            uniform_fields,
+            name: layout.name,
+            #[cfg(not(target_arch = "wasm32"))]
+            shader_source: shader_source.to_string(),
+        }
+    }
+
+    /// Compiles a custom material from a context whose device was lost again, along
+    /// with its uniforms as they were, see [RenderContext::recover].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restore_material(&self, material_pipeline: &MaterialPipeline) -> MaterialPipeline {
+        let layout = MaterialLayout {
+            uniform_size: material_pipeline.uniform_size,
+            name: material_pipeline.name,
+        };
+        let name = layout.name.unwrap_or("clockwork material");
+        let pipelines = create_render_pipeline(
+            &self.device,
+            self.color_format(),
+            &format!("{name} pipeline"),
+            &self.create_material_pipeline_layout(name, layout),
+            wgpu::ShaderSource::Wgsl(material_pipeline.shader_source.as_str().into()),
+            "fs_main",
+        );
+        let mut restored =
+            self.create_material(&material_pipeline.shader_source, layout, Some(pipelines));
+        if let Some((buffer, _)) = &restored.uniforms {
+            self.queue
+                .write_buffer(buffer, 0, &material_pipeline.uniform_data);
+        }
+        restored
+            .uniform_data
+            .clone_from(&material_pipeline.uniform_data);
+        restored
+    }
+
+    /// Writes the uniforms of a custom material.
+    ///
+    /// `bytes` is written to the start of the material's uniform buffer, and must
+    /// fit within it and be a multiple of 4 bytes long.
+    pub fn set_material_uniforms(
+        &mut self,
+        material_pipeline_id: ResourceId<MaterialPipeline>,
+        bytes: &[u8],
+    ) -> Result<()> {
+        self.write_material_uniforms(material_pipeline_id, 0, bytes)
+    }
+
+    /// Iterates over every custom material and the name its layout was given, if any.
+    pub fn material_names(
+        &self,
+    ) -> impl Iterator<Item = (ResourceId<MaterialPipeline>, Option<&'static str>)> + '_ {
+        self.material_pipelines
+            .iter()
+            .map(|(material_pipeline_id, material_pipeline)| {
+                (material_pipeline_id, material_pipeline.name)
+            })
+    }
+
+    /// Gets the fields of a custom material's uniforms, as declared in its shader.
+    ///
+    /// This allows tools such as debug panels to tweak materials without knowing
+    /// their layout ahead of time.
+    pub fn material_uniform_fields(
+        &self,
+        material_pipeline_id: ResourceId<MaterialPipeline>,
+    ) -> &[UniformField] {
+        self.material_pipelines
+            .get(material_pipeline_id)
+            .map(|material_pipeline| material_pipeline.uniform_fields.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Gets the current value of a field in a custom material's uniforms by name.
+    pub fn material_property(
+        &self,
+        material_pipeline_id: ResourceId<MaterialPipeline>,
+        name: &str,
+    ) -> Option<UniformValue> {
+        let material_pipeline = self.material_pipelines.get(material_pipeline_id)?;
+        let field = material_pipeline
+            .uniform_fields
+            .iter()
+            .find(|field| field.name == name)?;
+        UniformValue::from_bytes(
+            field.ty,
+            &material_pipeline.uniform_data[field.offset as usize..],
+        )
+    }
+
+    /// Sets a field in a custom material's uniforms by name.
+    ///
+    /// Returns an error if there is no such field or the value is the wrong type.
+    pub fn set_material_property(
+        &mut self,
+        material_pipeline_id: ResourceId<MaterialPipeline>,
+        name: &str,
+        value: UniformValue,
+    ) -> Result<()> {
+        let field = self
+            .material_uniform_fields(material_pipeline_id)
+            .iter()
+            .find(|field| field.name == name)
+            .ok_or_else(|| anyhow::anyhow!("material has no uniform named '{name}'"))?;
+        anyhow::ensure!(
+            field.ty == value.ty(),
+            "uniform '{name}' is {:?}, not {:?}",
+            field.ty,
+            value.ty()
+        );
+
+        let offset = field.offset as u64;
+        self.write_material_uniforms(material_pipeline_id, offset, &value.to_bytes())
     }
 
-    /// Performs a render pass.
+    /// Writes bytes into a custom material's uniforms at an offset.
+    fn write_material_uniforms(
+        &mut self,
+        material_pipeline_id: ResourceId<MaterialPipeline>,
+        offset: u64,
+        bytes: &[u8],
+    ) -> Result<()> {
+        let material_pipeline = self
+            .material_pipelines
+            .get_mut(material_pipeline_id)
+            .ok_or_else(|| anyhow::anyhow!("no material pipeline {material_pipeline_id:?}"))?;
+        let Some((buffer, _)) = &material_pipeline.uniforms else {
+            anyhow::bail!("material pipeline has no uniforms");
+        };
+        anyhow::ensure!(
+            offset + bytes.len() as u64 <= material_pipeline.uniform_size,
+            "{} bytes of uniforms at offset {offset} don't fit in {} bytes",
+            bytes.len(),
+            material_pipeline.uniform_size
+        );
+        anyhow::ensure!(
+            (bytes.len() as u64).is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            "uniforms must be a multiple of {} bytes long",
+            wgpu::COPY_BUFFER_ALIGNMENT
+        );
+
+        self.queue.write_buffer(buffer, offset, bytes);
+        self.counters.upload(bytes.len());
+        material_pipeline.uniform_data[offset as usize..offset as usize + bytes.len()]
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Creates a texture that can be rendered to with [RenderTarget::Texture], and
+    /// sampled like any other texture afterwards.
+    pub fn create_render_target(&mut self, size: UVec2) -> ResourceId<Texture> {
+        let texture = Texture::create_render_target(&self.device, size, self.color_format());
+        let texture_id = self.textures.add(texture, None);
+        self.render_target_depth_textures.insert(
+            texture_id,
+            Texture::create_depth_texture(&self.device, size),
+        );
+        texture_id
+    }
+
+    /// Performs a render pass that clears and draws to the surface, then presents it.
+    ///
+    /// This is shorthand for a frame with a single render pass, see
+    /// [RenderContext::begin_frame] for rendering multiple passes and the errors it
+    /// returns.
     pub fn perform_render_pass(
         &mut self,
         model_view_projection: [[f32; 4]; 4],
         operations: &[RenderOperation],
+    ) -> Result<()> {
+        self.begin_frame()?;
+        self.render_pass(
+            &RenderPassDescriptor::new(Mat4::from_cols_array_2d(&model_view_projection)),
+            operations,
+        );
+        self.end_frame();
+        Ok(())
+    }
+
+    /// Begins a frame by acquiring the next surface texture.
+    ///
+    /// Any number of [RenderContext::render_pass] calls can follow, and
+    /// [RenderContext::end_frame] presents the result.
+    ///
+    /// Returns false without starting a frame while the window is minimized, the
+    /// application is suspended or the device is lost, or when
+    /// the surface has no texture to give this frame, such as mid-resize. Returns an
+    /// error if the gpu is out of memory.
+    pub fn begin_frame(&mut self) -> Result<bool> {
+        if self.minimized || self.surface.is_none() || self.is_device_lost() {
+            return Ok(false);
+        }
+
+        self.wait_for_frames_in_flight();
+        self.receive_warmed_pipelines();
+        let Some(surface_texture) = self.acquire_surface_texture()? else {
+            return Ok(false);
+        };
+        let view = surface_texture
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        self.frame = Some(Frame {
+            surface_texture,
+            view,
+        });
+        Ok(true)
+    }
+
+    /// Gets the next surface texture, reconfiguring the surface once if it no longer
+    /// matches the window.
+    fn acquire_surface_texture(&mut self) -> Result<Option<wgpu::SurfaceTexture>> {
+        let Some(surface) = &self.surface else {
+            return Ok(None);
+        };
+        let mut reconfigured = false;
+        loop {
+            let error =
+                match gpu::catch_device_loss(&self.device_lost, || surface.get_current_texture()) {
+                    Some(Ok(surface_texture)) => return Ok(Some(surface_texture)),
+                    Some(Err(error)) => error,
+                    None => return Ok(None),
+                };
+            match (surface_recovery(&error), reconfigured) {
+                (SurfaceRecovery::Reconfigure, false) => {
+                    surface.configure(&self.device, &self.surface_config);
+                    reconfigured = true;
+                }
+                (SurfaceRecovery::Reconfigure | SurfaceRecovery::SkipFrame, _) => return Ok(None),
+                (SurfaceRecovery::Fail, _) => {
+                    anyhow::bail!("failed to get the next surface texture: {error}")
+                }
+            }
+        }
+    }
+
+    /// Ends the frame and presents the surface.
+    pub fn end_frame(&mut self) {
+        #[cfg(feature = "ui")]
+        self.render_ui();
+
+        if let Some(frame) = self.frame.take() {
+            frame.surface_texture.present();
+        }
+        self.finish_frame_counts();
+        self.track_frame_in_flight();
+        self.destroy_dropped_resources();
+        self.evict_stale_caches();
+        self.frame_index += 1;
+    }
+
+    /// Renders operations within the current frame.
+    ///
+    /// Each pass is submitted on its own, so passes can use different view
+    /// projections and targets, and later passes see the results of earlier ones.
+    ///
+    /// Passes targeting the surface do nothing if no frame was begun, such as while
+    /// the window is minimized.
+    pub fn render_pass(
+        &mut self,
+        descriptor: &RenderPassDescriptor,
+        operations: &[RenderOperation],
     ) {
-        let operations: Vec<RawRenderOperation> = operations
+        if descriptor.target == RenderTarget::Surface && self.frame.is_none() {
+            return;
+        }
+        let _span = crate::logging::span("render pass");
+        let allocs = AllocStats::current();
+
+        let frustum = self
+            .frustum_culling
+            .then(|| Frustum::from_view_projection(descriptor.view_projection));
+        let mut operations: Vec<RawRenderOperation> = operations
             .iter()
+            .filter(|operation| operation.layers.intersects(descriptor.layers))
+            .filter(|operation| {
+                frustum.is_none_or(|frustum| {
+                    let bounds = self.meshes[operation.mesh_id].bounds;
+                    frustum.intersects_sphere(&bounds.transformed(operation.transform))
+                })
+            })
             .map(|operation| RawRenderOperation::from(*operation))
             .collect();
+        render_operation::order_for_drawing(&mut operations, descriptor.view_projection);
 
-        // Step 1: Create necessary local buffers.
-        let difference = operations
-            .len()
-            .checked_sub(self.bind_groups_and_buffers.len())
-            .filter(|&difference| difference > 0);
-
-        if let Some(difference) = difference {
-            self.bind_groups_and_buffers
-                .extend((0..difference).map(|_| {
-                    let local_buffer = self.device.create_buffer_init(
-                        &(wgpu::util::BufferInitDescriptor {
-                            label: None,
-                            contents: bytes_of(&LocalBuffer::zeroed()),
-                            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                        }),
-                    );
-
-                    let bind_group = self.device.create_bind_group(
-                        &(wgpu::BindGroupDescriptor {
-                            label: None,
-                            layout: &self.buffers_bind_group_layout,
-                            entries: &[
-                                wgpu::BindGroupEntry {
-                                    binding: 0,
-                                    resource: self.global_buffer.as_entire_binding(),
-                                },
-                                wgpu::BindGroupEntry {
-                                    binding: 1,
-                                    resource: local_buffer.as_entire_binding(),
-                                },
-                            ],
-                        }),
-                    );
-
-                    (bind_group, local_buffer)
-                }));
-        }
+        // Step 1: Write every operation's uniforms into the local buffer.
+        self.write_local_uniforms(&operations);
 
         // Step 2: Copy over the global buffer data.
         let global_buffer = GlobalBuffer {
-            mvp: model_view_projection,
+            mvp: descriptor.view_projection.to_cols_array_2d(),
+            camera: lighting::camera_position(descriptor.view_projection).to_array(),
         };
         self.queue
             .write_buffer(&self.global_buffer, 0, bytes_of(&global_buffer));
+        self.counters.upload(std::mem::size_of::<GlobalBuffer>());
+        let target_size = match descriptor.target {
+            RenderTarget::Surface => {
+                UVec2::new(self.surface_config.width, self.surface_config.height)
+            }
+            RenderTarget::Texture(texture_id) => self.textures[texture_id].size,
+        };
+        let target_size = match descriptor.viewport {
+            Some(viewport) => viewport.clamp(UVec2::ONE, target_size),
+            None => target_size,
+        };
+        self.cull_lights_for_pass(descriptor.view_projection, target_size);
 
         // Step 3: Ensure all texture bind groups are created and valid.
         for operation in operations.iter() {
-            self.ensure_textures_bind_group_valid(operation.texture_group_ids);
+            match operation.shading {
+                Shading::Pbr { textures, .. } => self.ensure_pbr_bind_group_valid(textures),
+                _ => self.ensure_textures_bind_group_valid(operation.texture_group_ids),
+            }
+        }
+
+        // Write the uniforms and draw arguments of batches drawn with multi-draw indirect.
+        // Batches have no blended pipeline to show overdraw with.
+        let batches = match self.multi_draw_indirect() && !self.shows_overdraw() {
+            true => indirect::find_batches(&operations),
+            false => Vec::new(),
+        };
+        if !batches.is_empty() {
+            let batched = || batches.iter().flat_map(|batch| &operations[batch.clone()]);
+            let locals: Vec<LocalBuffer> = batched().map(LocalBuffer::new).collect();
+            let arguments: Vec<indirect::DrawIndexedIndirect> = batched()
+                .enumerate()
+                .map(|(index, operation)| {
+                    let mesh = &self.meshes[operation.mesh_id];
+                    indirect::DrawIndexedIndirect {
+                        index_count: mesh.indices.len() as u32,
+                        instance_count: 1,
+                        first_index: mesh.indices.start,
+                        base_vertex: mesh.vertices.start as i32,
+                        first_instance: index as u32,
+                    }
+                })
+                .collect();
+            let color_format = self.color_format();
+            let indirect_drawer = self.indirect_drawer.get_or_insert_with(|| {
+                indirect::IndirectDrawer::new(
+                    &self.device,
+                    color_format,
+                    &self.buffers_bind_group_layout,
+                    &self.textures_bind_group_layout,
+                )
+            });
+            indirect_drawer.write(&self.device, &self.queue, &locals, &arguments);
+            self.counters.upload(
+                std::mem::size_of_val(locals.as_slice())
+                    + std::mem::size_of_val(arguments.as_slice()),
+            );
         }
 
         // Step 3: Start the render pass.
-        let surface_texture = self.surface.get_current_texture().unwrap();
-        let view = surface_texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let (view, depth_view) = match descriptor.target {
+            RenderTarget::Surface => (
+                &self.frame.as_ref().expect("checked above").view,
+                &self.depth_texture.view,
+            ),
+            RenderTarget::Texture(texture_id) => (
+                &self.textures[texture_id].view,
+                &self.render_target_depth_textures[&texture_id].view,
+            ),
+        };
+
+        let mut command_encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some(descriptor.label),
+            }),
+        );
 
-        let mut command_encoder = self
-            .device
-            .create_command_encoder(&(wgpu::CommandEncoderDescriptor { label: None }));
+        // Wrap the pass in the user's debug scopes so captures are navigable.
+        for debug_group in self.debug_groups.iter() {
+            command_encoder.push_debug_group(debug_group);
+        }
 
         {
             let mut render_pass = command_encoder.begin_render_pass(
                 &(wgpu::RenderPassDescriptor {
-                    label: None,
+                    label: Some(descriptor.label),
                     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
+                        view,
                         resolve_target: None,
                         ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
+                            load: match descriptor.clear_color {
+                                Some(color) => wgpu::LoadOp::Clear(wgpu::Color {
+                                    r: color.x as f64,
+                                    g: color.y as f64,
+                                    b: color.z as f64,
+                                    a: color.w as f64,
+                                }),
+                                None => wgpu::LoadOp::Load,
+                            },
                             store: true,
                         },
                     })],
                     depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &self.depth_texture.view,
+                        view: depth_view,
                         depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0),
+                            load: match descriptor.clear_depth {
+                                true => wgpu::LoadOp::Clear(1.0),
+                                false => wgpu::LoadOp::Load,
+                            },
                             store: true,
                         }),
                         stencil_ops: None,
                     }),
                 }),
             );
+            if descriptor.viewport.is_some() {
+                let size = target_size.as_vec2();
+                render_pass.set_viewport(0.0, 0.0, size.x, size.y, 0.0, 1.0);
+            }
+            // Every mesh is drawn from the same buffers.
+            render_pass.set_vertex_buffer(0, self.mesh_pool.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.mesh_pool.color_buffer.slice(..));
+            render_pass.set_index_buffer(
+                self.mesh_pool.index_buffer.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
 
-            render_pass.set_pipeline(&self.render_pipeline);
-
-            // Step 4: Copy data from local buffers and render.
+            // Step 4: Render.
+            let mut current_pipeline = None;
+            let mut batches = batches.iter().peekable();
+            let mut first_instance = 0;
+            let mut batch_end = 0;
             for (index, operation) in operations.iter().copied().enumerate() {
-                let (buffers_bind_group, buffer) = self
-                    .bind_groups_and_buffers
-                    .get(index)
-                    .expect("should have been sized");
-
-                // Write data into the local buffer.
-                let local_buffer = LocalBuffer {
-                    transform: operation.transform.to_cols_array_2d(),
-                    uv_window: operation.uv_windows[0].to_array(),
-                };
-                self.queue.write_buffer(buffer, 0, bytes_of(&local_buffer));
+                // The rest of a batch was drawn along with its first operation.
+                if index < batch_end {
+                    continue;
+                }
+                let batch = batches.next_if(|batch| batch.start == index);
+
+                // Switch pipelines only when the material or transparency changes.
+                let pipeline = (
+                    std::mem::discriminant(&operation.shading),
+                    operation.shading.pipeline_id(),
+                    operation.transparent,
+                    batch.is_some(),
+                );
+                if index == 0 || current_pipeline != Some(pipeline) {
+                    if index != 0 {
+                        render_pass.pop_debug_group();
+                    }
+                    current_pipeline = Some(pipeline);
+                    let (render_pipeline, transparent_render_pipeline) = match operation.shading {
+                        _ if batch.is_some() => {
+                            render_pass.push_debug_group("batched pipeline");
+                            let indirect_drawer =
+                                self.indirect_drawer.as_ref().expect("created above");
+                            render_pass.set_bind_group(2, indirect_drawer.locals_bind_group(), &[]);
+                            (
+                                &indirect_drawer.render_pipeline,
+                                &indirect_drawer.render_pipeline,
+                            )
+                        }
+                        Shading::Default => {
+                            render_pass.push_debug_group("default pipeline");
+                            (&self.render_pipeline, &self.transparent_render_pipeline)
+                        }
+                        Shading::Mapped { .. } => {
+                            render_pass.push_debug_group("mapped pipeline");
+                            let (render_pipeline, transparent_render_pipeline) =
+                                &self.mapped_render_pipelines;
+                            (render_pipeline, transparent_render_pipeline)
+                        }
+                        Shading::Custom(pipeline_id) => {
+                            let material_pipeline = &self.material_pipelines[pipeline_id];
+                            render_pass.push_debug_group(&match material_pipeline.name {
+                                Some(name) => format!("material pipeline {name}"),
+                                None => format!("material pipeline {}", pipeline_id.index),
+                            });
+                            match &material_pipeline.pipelines {
+                                Some((render_pipeline, transparent_render_pipeline)) => {
+                                    if let Some((_, bind_group)) = &material_pipeline.uniforms {
+                                        render_pass.set_bind_group(2, bind_group, &[]);
+                                    }
+                                    (render_pipeline, transparent_render_pipeline)
+                                }
+                                // Still compiling in a warmup.
+                                None => (&self.render_pipeline, &self.transparent_render_pipeline),
+                            }
+                        }
+                        Shading::Reflective(_) => {
+                            render_pass.push_debug_group("reflective pipeline");
+                            let environment = self
+                                .environment
+                                .as_ref()
+                                .expect("created along with the environment map");
+                            (
+                                &environment.render_pipeline,
+                                &environment.transparent_render_pipeline,
+                            )
+                        }
+                        Shading::Pbr { .. } => {
+                            render_pass.push_debug_group("pbr pipeline");
+                            let pbr = self.pbr.as_ref().expect("created above");
+                            (&pbr.render_pipeline, &pbr.transparent_render_pipeline)
+                        }
+                    };
+                    render_pass.set_pipeline(
+                        match operation.transparent || self.shows_overdraw() {
+                            true => transparent_render_pipeline,
+                            false => render_pipeline,
+                        },
+                    );
+                }
 
-                // Set the local buffers' bind group.
-                render_pass.set_bind_group(0, buffers_bind_group, &[]);
+                let local_uniforms = self.local_uniforms.as_ref().expect("written above");
+                let buffers_bind_group = &local_uniforms.bind_group;
+                let local_offset = [local_uniforms.offset(index)];
+
+                if let Some(batch) = batch {
+                    // The batch's uniforms were written above, so the offset only
+                    // needs to be in bounds.
+                    render_pass.set_bind_group(0, buffers_bind_group, &local_offset);
+                    render_pass.set_bind_group(
+                        1,
+                        self.get_textures_bind_group(operation.texture_group_ids),
+                        &[],
+                    );
+                    let indirect_drawer = self.indirect_drawer.as_ref().expect("created above");
+                    let stride = std::mem::size_of::<indirect::DrawIndexedIndirect>() as u64;
+                    render_pass.multi_draw_indexed_indirect(
+                        indirect_drawer.arguments_buffer(),
+                        first_instance as u64 * stride,
+                        batch.len() as u32,
+                    );
+                    first_instance += batch.len();
+                    batch_end = batch.end;
+                    self.counters.draw(1);
+                    self.counters.indirect(batch.len());
+                    continue;
+                }
 
-                // Set the bind group for the group of textures.
-                let textures_bind_group =
-                    self.get_textures_bind_group(operation.texture_group_ids[0]);
-                render_pass.set_bind_group(1, textures_bind_group, &[]);
+                // Bind the buffers at this operation's uniforms.
+                render_pass.set_bind_group(0, buffers_bind_group, &local_offset);
+
+                // Set the bind groups for the group of textures and the environment.
+                match operation.shading {
+                    Shading::Pbr {
+                        textures,
+                        environment_map_id,
+                        ..
+                    } => {
+                        let pbr = self.pbr.as_ref().expect("created above");
+                        render_pass.set_bind_group(1, pbr.bind_group(textures), &[]);
+                        let environment_map = match environment_map_id {
+                            Some(environment_map_id) => &self.environment_maps[environment_map_id],
+                            None => &pbr.default_environment,
+                        };
+                        render_pass.set_bind_group(2, environment_map.bind_group(), &[]);
+                    }
+                    _ => {
+                        let textures_bind_group =
+                            self.get_textures_bind_group(operation.texture_group_ids);
+                        render_pass.set_bind_group(1, textures_bind_group, &[]);
+                    }
+                }
+                if let Shading::Reflective(environment_map_id) = operation.shading {
+                    render_pass.set_bind_group(
+                        2,
+                        self.environment_maps[environment_map_id].bind_group(),
+                        &[],
+                    );
+                }
 
+                if let Some(label) = self.mesh_labels.get(&operation.mesh_id) {
+                    render_pass.insert_debug_marker(label);
+                }
                 let mesh = &self.meshes[operation.mesh_id];
+                render_pass.draw_indexed(mesh.indices.clone(), mesh.vertices.start as i32, 0..1);
+                self.counters.draw(1);
+            }
 
-                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-                render_pass
-                    .set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(
-                    0..(mesh.index_buffer.size() as u32) / (std::mem::size_of::<u32>() as u32),
-                    0,
-                    0..1,
-                );
+            if !operations.is_empty() {
+                render_pass.pop_debug_group();
             }
         }
 
+        for _ in self.debug_groups.iter() {
+            command_encoder.pop_debug_group();
+        }
+
         // Step 5: Submit the pass.
-        self.queue.submit(std::iter::once(command_encoder.finish()));
+        self.submit(command_encoder);
+        self.render_allocs += AllocStats::current().since(allocs);
+    }
+
+    /// Sets whether presenting waits for the display's vertical sync.
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.surface_config.present_mode = present_mode_for(vsync);
+        self.configure_surface();
+    }
+
+    /// Checks if presenting waits for the display's vertical sync.
+    pub fn vsync(&self) -> bool {
+        matches!(
+            self.surface_config.present_mode,
+            PresentMode::AutoVsync | PresentMode::Fifo | PresentMode::FifoRelaxed
+        )
+    }
+
+    /// Sets how frames are presented, such as [PresentMode::Immediate] for
+    /// benchmarking or [PresentMode::Mailbox] for low latency without tearing.
+    ///
+    /// Returns an error if the surface doesn't support the mode, see
+    /// [RenderContext::supported_present_modes]. The `Auto` modes are always
+    /// supported.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<()> {
+        let supported = self.supported_present_modes();
+        anyhow::ensure!(
+            present_mode_supported(present_mode, &supported),
+            "present mode {present_mode:?} isn't supported by the surface, which supports \
+             {supported:?}"
+        );
+        self.surface_config.present_mode = present_mode;
+        self.configure_surface();
+        Ok(())
+    }
+
+    /// Gets how frames are presented.
+    pub fn present_mode(&self) -> PresentMode {
+        self.surface_config.present_mode
+    }
+
+    /// Gets the present modes the surface supports besides the `Auto` ones.
+    pub fn supported_present_modes(&self) -> Vec<PresentMode> {
+        self.surface
+            .as_ref()
+            .map(|surface| surface.get_capabilities(&self.adapter).present_modes)
+            .unwrap_or_default()
+    }
+
+    /// Sets whether render passes skip operations whose mesh is entirely outside the
+    /// view, which is on by default.
+    ///
+    /// Meshes are culled by the sphere around their vertices, so turn this off for
+    /// materials that move vertices further than that, or to benchmark drawing
+    /// everything.
+    pub fn set_frustum_culling(&mut self, enabled: bool) {
+        self.frustum_culling = enabled;
+    }
+
+    /// Checks if operations outside the view are skipped, see
+    /// [RenderContext::set_frustum_culling].
+    pub fn frustum_culling(&self) -> bool {
+        self.frustum_culling
+    }
+
+    /// Opens a debug group that following render passes are nested within when
+    /// viewed in graphics debuggers such as RenderDoc.
+    ///
+    /// Groups can be nested, and each must be closed with
+    /// [RenderContext::pop_debug_group].
+    pub fn push_debug_group<S: Into<String>>(&mut self, label: S) {
+        self.debug_groups.push(label.into());
+    }
+
+    /// Closes the most recently opened debug group.
+    pub fn pop_debug_group(&mut self) {
+        self.debug_groups.pop();
+    }
+
+    /// Checks if the device was lost, such as when the graphics driver restarts or an
+    /// external GPU is unplugged. Nothing is rendered once it is, until the engine
+    /// opens a new device and uploads every resource to it again, or exits with
+    /// [crate::ClockworkError::DeviceLost] if it can't.
+    ///
+    /// Browsers abort on the panic a lost device causes, so it isn't recovered from
+    /// on the web.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
 
-        // Present (TEMP)
-        surface_texture.present();
+    /// Takes the errors the gpu reported since this was last called, such as a
+    /// resource used in a way it wasn't created for. The engine passes them to
+    /// [crate::Application::on_gpu_error] every frame.
+    pub fn take_errors(&mut self) -> Vec<ClockworkError> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+
+    /// Submits commands recorded during the frame, see [gpu::submit].
+    fn submit(&mut self, encoder: wgpu::CommandEncoder) {
+        if let Some(submission) = gpu::submit(&self.queue, encoder.finish(), &self.device_lost) {
+            self.frame_pacer.submitted(submission);
+        }
+    }
+
+    /// Checks if the adapter lacks compute shaders or storage buffers, such as with
+    /// WebGL. Lights are then applied to the whole screen rather than culled into
+    /// tiles, up to [MAX_DOWNLEVEL_LIGHTS], skinned meshes are deformed on the cpu,
+    /// and multi-draw indirect is off.
+    pub fn is_downlevel(&self) -> bool {
+        self.downlevel
+    }
+
+    /// Gets how lights are handed to shaders.
+    pub(crate) fn light_buffers(&self) -> light_culling::LightBuffers {
+        light_culling::LightBuffers::new(self.downlevel)
+    }
+
+    /// Checks if the surface is zero-sized because the window is minimized.
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
     }
 
     /// Resizes the surface that is rendered to.
+    ///
+    /// A zero-sized surface can't be configured, so it is left as is and rendering
+    /// is skipped until it is resized again.
     pub(crate) fn resize_surface(&mut self, new_size: UVec2) {
+        self.minimized = new_size.x == 0 || new_size.y == 0;
+        if self.minimized {
+            return;
+        }
+
         self.surface_config.width = new_size.x;
         self.surface_config.height = new_size.y;
-        self.surface.configure(&self.device, &self.surface_config);
+        self.configure_surface();
 
         self.depth_texture = Texture::create_depth_texture(&self.device, new_size);
     }
 
+    /// Configures the surface with [RenderContext::surface_config], unless it's
+    /// zero-sized or suspended.
+    fn configure_surface(&self) {
+        if let (false, Some(surface)) = (self.minimized, &self.surface) {
+            surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
+    /// Checks if the surface was torn down because the application is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.surface.is_none()
+    }
+
+    /// Tears down the surface when the application is suspended, such as when an
+    /// Android app goes to the background and its window is destroyed. Nothing is
+    /// rendered until [RenderContext::resume].
+    pub(crate) fn suspend(&mut self) {
+        self.frame = None;
+        self.surface = None;
+    }
+
+    /// Recreates the surface for the window after [RenderContext::suspend], or
+    /// configures the existing one again, such as when the window stops being
+    /// occluded.
+    ///
+    /// Returns an error if the surface can't be created, or the adapter can't render
+    /// to it.
+    pub(crate) fn resume<Window: HasRawWindowHandle + HasRawDisplayHandle>(
+        &mut self,
+        window: &Window,
+    ) -> Result<()> {
+        if self.surface.is_none() {
+            let surface = unsafe { self.instance.create_surface(window) }?;
+            anyhow::ensure!(
+                self.adapter.is_surface_supported(&surface),
+                "adapter {} can't render to the recreated window",
+                self.adapter.get_info().name
+            );
+            self.surface = Some(surface);
+        }
+        self.configure_surface();
+        Ok(())
+    }
+
     /// Ensures the bind group for the group of textures is created and valid.
-    fn ensure_textures_bind_group_valid(&mut self, texture_ids: [ResourceId<Texture>; 1]) {
+    fn ensure_textures_bind_group_valid(&mut self, texture_ids: TextureSlots) {
         let key = texture_ids;
 
-        let actual_generations =
-            texture_ids.map(|texture_id| self.textures.get_generation(texture_id));
+        let actual_generations = texture_ids.map(|texture_id| {
+            texture_id.map_or(0, |texture_id| self.textures.get_generation(texture_id))
+        });
+
+        // The first texture decides how the group is sampled.
+        let sampler_settings = texture_ids
+            .iter()
+            .flatten()
+            .next()
+            .map_or(SamplerSettings::default(), |texture_id| {
+                self.textures[*texture_id].sampler
+            });
+        let device = &self.device;
+        self.samplers
+            .entry(sampler_settings)
+            .or_insert_with(|| sampler_settings.create_sampler(device));
+
         let generate_bind_group_entry = || {
             let entries: Vec<wgpu::BindGroupEntry> = std::iter::once(wgpu::BindGroupEntry {
                 binding: 0,
-                resource: wgpu::BindingResource::Sampler(&self.sampler),
+                resource: wgpu::BindingResource::Sampler(&self.samplers[&sampler_settings]),
             })
             .chain(texture_ids.iter().enumerate().map(|(binding, texture_id)| {
-                let texture = &self.textures[*texture_id];
+                let texture = match texture_id {
+                    Some(texture_id) => &self.textures[*texture_id],
+                    None => self.fallback_textures.get(binding == NORMAL_SLOT),
+                };
                 wgpu::BindGroupEntry {
                     binding: binding as u32 + 1,
                     resource: wgpu::BindingResource::TextureView(&texture.view),
@@ -386,40 +2138,44 @@ impl RenderContext {
 
             let bind_group = self.device.create_bind_group(
                 &(wgpu::BindGroupDescriptor {
-                    label: None,
+                    label: Some("clockwork textures bind group"),
                     layout: &self.textures_bind_group_layout,
                     entries: entries.as_slice(),
                 }),
             );
 
-            dbg!((&key, &bind_group));
-            (actual_generations, bind_group)
+            log::trace!("created textures bind group for {key:?}");
+            (actual_generations, bind_group, self.frame_index)
         };
 
-        let generations_and_bind_group = self.textures_bind_groups.get(&key);
-        if let Some((generations, ..)) = generations_and_bind_group {
-            if *generations != actual_generations {
-                self.textures_bind_groups
-                    .insert(key, generate_bind_group_entry());
+        let generations_and_bind_group = self.textures_bind_groups.get_mut(&key);
+        if let Some((generations, _, last_used)) = generations_and_bind_group {
+            if *generations == actual_generations {
+                *last_used = self.frame_index;
+                return;
             }
-        } else {
-            self.textures_bind_groups
-                .insert(key, generate_bind_group_entry());
         }
+        let entry = generate_bind_group_entry();
+        self.textures_bind_groups.insert(key, entry);
     }
 
     /// Gets the appropriate bind group for the following textures.
-    fn get_textures_bind_group(&self, diffuse_texture_id: ResourceId<Texture>) -> &wgpu::BindGroup {
-        let key = [diffuse_texture_id];
-
-        &self.textures_bind_groups[&key].1
+    fn get_textures_bind_group(&self, texture_ids: TextureSlots) -> &wgpu::BindGroup {
+        &self.textures_bind_groups[&texture_ids].1
     }
 }
 
+/// Surface texture acquired for the current frame.
+struct Frame {
+    surface_texture: wgpu::SurfaceTexture,
+    view: wgpu::TextureView,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 struct GlobalBuffer {
     mvp: [[f32; 4]; 4],
+    camera: [f32; 4],
 }
 
 #[repr(C)]
@@ -427,19 +2183,92 @@ struct GlobalBuffer {
 struct LocalBuffer {
     transform: [[f32; 4]; 4],
     uv_window: [f32; 4],
+    normal_transform: [[f32; 4]; 4],
+    parameters: [f32; 4],
+    color: [f32; 4],
+    emissive: [f32; 4],
+    data: [u8; MATERIAL_DATA_SIZE],
 }
 
 unsafe impl Zeroable for GlobalBuffer {}
 unsafe impl Pod for GlobalBuffer {}
 
+impl LocalBuffer {
+    fn new(operation: &RawRenderOperation) -> Self {
+        Self {
+            transform: operation.transform.to_cols_array_2d(),
+            uv_window: operation.uv_windows[0].to_array(),
+            normal_transform: normal_transform(operation.transform).to_cols_array_2d(),
+            parameters: operation.parameters.to_array(),
+            color: operation.colors[0].to_array(),
+            emissive: match operation.shading {
+                Shading::Pbr { emissive, .. } | Shading::Mapped { emissive } => {
+                    emissive.extend(0.0).to_array()
+                }
+                _ => [0.0; 4],
+            },
+            data: operation.data.0,
+        }
+    }
+}
+
 unsafe impl Zeroable for LocalBuffer {}
 unsafe impl Pod for LocalBuffer {}
 
+/// Gets the matrix that transforms normals, which keeps them perpendicular to
+/// surfaces under non-uniform scaling.
+fn normal_transform(transform: Mat4) -> Mat4 {
+    if transform.determinant() == 0.0 {
+        return transform;
+    }
+    transform.inverse().transpose()
+}
+
+/// How to recover when the surface can't give a texture to draw to.
+#[derive(Debug, PartialEq, Eq)]
+enum SurfaceRecovery {
+    /// The surface no longer matches the window, so configure it again and retry.
+    Reconfigure,
+    /// Nothing is wrong for long, so try again next frame.
+    SkipFrame,
+    /// Rendering can't go on.
+    Fail,
+}
+
+/// Gets how to recover from an error getting the surface texture.
+fn surface_recovery(error: &wgpu::SurfaceError) -> SurfaceRecovery {
+    match error {
+        wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost => SurfaceRecovery::Reconfigure,
+        wgpu::SurfaceError::Timeout => SurfaceRecovery::SkipFrame,
+        wgpu::SurfaceError::OutOfMemory => SurfaceRecovery::Fail,
+    }
+}
+
+/// Gets the present mode to use depending on vsync.
+fn present_mode_for(vsync: bool) -> wgpu::PresentMode {
+    match vsync {
+        true => wgpu::PresentMode::AutoVsync,
+        false => wgpu::PresentMode::AutoNoVsync,
+    }
+}
+
+/// Checks if a present mode can be used with a surface supporting `supported`. The
+/// `Auto` modes fall back to what the surface has.
+fn present_mode_supported(present_mode: PresentMode, supported: &[PresentMode]) -> bool {
+    matches!(
+        present_mode,
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync
+    ) || supported.contains(&present_mode)
+}
+
 /// Creates the bind group layout for the buffers.
-fn create_buffers_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+fn create_buffers_bind_group_layout(
+    device: &wgpu::Device,
+    light_buffers: light_culling::LightBuffers,
+) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(
         &(wgpu::BindGroupLayoutDescriptor {
-            label: None,
+            label: Some("clockwork buffers bind group layout"),
             entries: &[
                 // globals
                 wgpu::BindGroupLayoutEntry {
@@ -456,6 +2285,19 @@ fn create_buffers_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLay
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<LocalBuffer>() as u64
+                        ),
+                    },
+                    count: None,
+                },
+                // lighting
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -463,32 +2305,25 @@ fn create_buffers_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLay
                     },
                     count: None,
                 },
-            ],
-        }),
-    )
-}
-
-/// Creates the bind group layout for the textures.
-fn create_textures_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
-    device.create_bind_group_layout(
-        &(wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                // sampler
+                // lights
                 wgpu::BindGroupLayoutEntry {
-                    binding: 0,
+                    binding: 3,
                     visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    ty: wgpu::BindingType::Buffer {
+                        ty: light_buffers.binding_type(),
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
                     count: None,
                 },
-                // texture
+                // light tiles
                 wgpu::BindGroupLayoutEntry {
-                    binding: 1,
+                    binding: 4,
                     visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: light_buffers.binding_type(),
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
                     },
                     count: None,
                 },
@@ -497,72 +2332,214 @@ fn create_textures_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLa
     )
 }
 
+/// Creates the bind group layout for the textures.
+fn create_textures_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    // sampler
+    let entries: Vec<wgpu::BindGroupLayoutEntry> = std::iter::once(wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    })
+    // texture slots
+    .chain(
+        (1..=TEXTURE_SLOTS as u32).map(|binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }),
+    )
+    .collect();
+
+    device.create_bind_group_layout(
+        &(wgpu::BindGroupLayoutDescriptor {
+            label: Some("clockwork textures bind group layout"),
+            entries: &entries,
+        }),
+    )
+}
+
 /// Create the layout for the render pipeline.
 fn create_render_pipeline_layout(
     device: &wgpu::Device,
-    buffers_bind_group_layout: &wgpu::BindGroupLayout,
-    textures_bind_group_layout: &wgpu::BindGroupLayout,
+    label: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
 ) -> wgpu::PipelineLayout {
     device.create_pipeline_layout(
         &(wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[buffers_bind_group_layout, textures_bind_group_layout],
+            label: Some(label),
+            bind_group_layouts,
             push_constant_ranges: &[],
         }),
     )
 }
 
-/// Creates the render pipeline.
+/// Creates the render pipelines for opaque and transparent operations, which only
+/// differ in whether they write depth.
 fn create_render_pipeline(
     device: &wgpu::Device,
+    color_format: wgpu::TextureFormat,
+    label: &str,
     render_pipeline_layout: &wgpu::PipelineLayout,
     shader_source: wgpu::ShaderSource,
-) -> wgpu::RenderPipeline {
+    fragment_entry_point: &str,
+) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    create_render_pipeline_with_vertex_entry(
+        device,
+        color_format,
+        label,
+        render_pipeline_layout,
+        shader_source,
+        "vs_main",
+        fragment_entry_point,
+    )
+}
+
+/// Like [create_render_pipeline], with a vertex entry point other than `vs_main`.
+fn create_render_pipeline_with_vertex_entry(
+    device: &wgpu::Device,
+    color_format: wgpu::TextureFormat,
+    label: &str,
+    render_pipeline_layout: &wgpu::PipelineLayout,
+    shader_source: wgpu::ShaderSource,
+    vertex_entry_point: &str,
+    fragment_entry_point: &str,
+) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: None,
+        label: Some(label),
         source: shader_source,
     });
 
-    device.create_render_pipeline(
-        &(wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[VERTEX_BUFFER_LAYOUT],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None, //Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                stencil: Default::default(),
-                bias: Default::default(),
+    let create = |depth_write_enabled| {
+        device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: vertex_entry_point,
+                    buffers: &[VERTEX_BUFFER_LAYOUT, COLOR_BUFFER_LAYOUT],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: fragment_entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None, //Some(wgpu::Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
             }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        }),
-    )
+        )
+    };
+    (create(true), create(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shader_validates() {
+        for light_buffers in [
+            light_culling::LightBuffers::Tiled,
+            light_culling::LightBuffers::Uniform,
+        ] {
+            let source = light_buffers.shader_source(SHADER_SOURCE);
+            let module = naga::front::wgsl::parse_str(&source).unwrap();
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_local_buffer_fits_offset_alignment() {
+        // Operations' uniforms stay one default offset alignment apart, including the
+        // data of custom materials.
+        assert_eq!(
+            std::mem::size_of::<LocalBuffer>() as u32,
+            wgpu::Limits::default().min_uniform_buffer_offset_alignment
+        );
+    }
+
+    #[test]
+    fn test_normal_transform_keeps_normals_perpendicular() {
+        let transform = Mat4::from_rotation_z(0.5) * Mat4::from_scale(glam::vec3(4.0, 1.0, 0.5));
+        // A slanted surface, and its normal before transforming.
+        let tangent = glam::vec3(1.0, 1.0, 0.0);
+        let normal = glam::vec3(1.0, -1.0, 0.0);
+
+        let tangent = transform.transform_vector3(tangent);
+        let normal = normal_transform(transform).transform_vector3(normal);
+        assert!(tangent.dot(normal).abs() < 1e-5);
+        assert!(
+            transform
+                .transform_vector3(glam::vec3(1.0, -1.0, 0.0))
+                .dot(tangent)
+                .abs()
+                > 1.0
+        );
+
+        let flattened = Mat4::from_scale(glam::vec3(1.0, 0.0, 1.0));
+        assert_eq!(normal_transform(flattened), flattened);
+    }
+
+    #[test]
+    fn test_present_mode_supported() {
+        let supported = [PresentMode::Fifo, PresentMode::Immediate];
+        assert!(present_mode_supported(PresentMode::Immediate, &supported));
+        assert!(!present_mode_supported(PresentMode::Mailbox, &supported));
+        assert!(present_mode_supported(PresentMode::AutoNoVsync, &[]));
+    }
+
+    #[test]
+    fn test_surface_recovery() {
+        assert_eq!(
+            surface_recovery(&wgpu::SurfaceError::Outdated),
+            SurfaceRecovery::Reconfigure
+        );
+        assert_eq!(
+            surface_recovery(&wgpu::SurfaceError::Lost),
+            SurfaceRecovery::Reconfigure
+        );
+        assert_eq!(
+            surface_recovery(&wgpu::SurfaceError::Timeout),
+            SurfaceRecovery::SkipFrame
+        );
+        assert_eq!(
+            surface_recovery(&wgpu::SurfaceError::OutOfMemory),
+            SurfaceRecovery::Fail
+        );
+    }
 }
@@ -0,0 +1,521 @@
+use anyhow::Result;
+use glam::{Mat4, UVec2};
+
+use crate::{
+    graphics::{mesh::VERTEX_BUFFER_LAYOUT, texture::Texture},
+    util::repository::ResourceId,
+};
+
+use super::{
+    post_process::{self, FullscreenPass, PostProcessor, INTERMEDIATE_FORMAT},
+    RenderContext, RenderOperation, RenderTarget,
+};
+
+const VELOCITY_SHADER_SOURCE: &str = include_str!("velocity.wgsl");
+const SHADER_SOURCE: &str = include_str!("motion_blur.wgsl");
+
+/// Settings of motion blur, see [RenderContext::apply_motion_blur].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionBlurSettings {
+    /// Fraction of the frame the virtual shutter stays open for, where 0 disables the
+    /// blur and 1 blurs across the whole movement since the last frame.
+    pub shutter: f32,
+    /// Number of samples taken along each pixel's movement. More samples give a
+    /// smoother blur at a higher cost.
+    pub samples: u32,
+}
+
+/// Operation drawn into a [VelocityBuffer], along with where it was last frame.
+#[derive(Clone, Copy)]
+pub struct VelocityOperation {
+    /// Operation as it is this frame.
+    pub operation: RenderOperation,
+    /// Transform of the operation last frame.
+    pub previous_transform: Mat4,
+    /// Whether the operation blurs. Operations that opt out still hide what's behind
+    /// them, but stay sharp.
+    pub blurred: bool,
+}
+
+/// How far everything on screen moved since the last frame, written by
+/// [RenderContext::render_velocity] for effects such as motion blur.
+pub struct VelocityBuffer {
+    targets: Option<VelocityTargets>,
+    buffers: Option<VelocityBuffers>,
+    view_projection: Option<Mat4>,
+    previous_view_projection: Mat4,
+    /// Generation of the device the textures were created on, so they are created
+    /// again after [RenderContext::recover].
+    device_generation: u32,
+}
+
+/// Textures velocity is rendered to.
+struct VelocityTargets {
+    velocity: Texture,
+    depth: Texture,
+}
+
+/// Uniform buffers of a [VelocityBuffer].
+struct VelocityBuffers {
+    global: wgpu::Buffer,
+    objects: wgpu::Buffer,
+    /// Number of objects the object buffer fits.
+    capacity: usize,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Pipeline that renders operations into a [VelocityBuffer].
+pub(crate) struct VelocityPipeline {
+    bind_group_layout: wgpu::BindGroupLayout,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+/// Pipeline that blurs along the velocity.
+pub(crate) struct MotionBlurPipeline {
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct VelocityGlobal {
+    view_projection: [[f32; 4]; 4],
+    previous_view_projection: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct VelocityObject {
+    transform: [[f32; 4]; 4],
+    previous_transform: [[f32; 4]; 4],
+    blurred: f32,
+    _padding: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MotionBlurUniforms {
+    inverse_view_projection: [[f32; 4]; 4],
+    previous_view_projection: [[f32; 4]; 4],
+    shutter: f32,
+    samples: u32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for VelocityGlobal {}
+unsafe impl bytemuck::Pod for VelocityGlobal {}
+
+unsafe impl bytemuck::Zeroable for VelocityObject {}
+unsafe impl bytemuck::Pod for VelocityObject {}
+
+unsafe impl bytemuck::Zeroable for MotionBlurUniforms {}
+unsafe impl bytemuck::Pod for MotionBlurUniforms {}
+
+impl Default for MotionBlurSettings {
+    fn default() -> Self {
+        Self {
+            shutter: 0.5,
+            samples: 8,
+        }
+    }
+}
+
+impl MotionBlurSettings {
+    /// Creates new [MotionBlurSettings].
+    pub fn new(shutter: f32, samples: u32) -> Self {
+        Self { shutter, samples }
+    }
+}
+
+impl VelocityOperation {
+    /// Creates a new [VelocityOperation] for an operation that moved from
+    /// `previous_transform`.
+    pub fn new(operation: RenderOperation, previous_transform: Mat4) -> Self {
+        Self {
+            operation,
+            previous_transform,
+            blurred: true,
+        }
+    }
+
+    /// Creates a new [VelocityOperation] for an operation that didn't move, so it only
+    /// blurs with the camera.
+    pub fn still(operation: RenderOperation) -> Self {
+        Self::new(operation, operation.transform)
+    }
+
+    /// Sets whether the operation blurs.
+    pub fn with_blur(mut self, blurred: bool) -> Self {
+        self.blurred = blurred;
+        self
+    }
+}
+
+impl Default for VelocityBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VelocityBuffer {
+    /// Creates a new [VelocityBuffer]. Its textures are created the first time it is
+    /// rendered to.
+    pub fn new() -> Self {
+        Self {
+            targets: None,
+            buffers: None,
+            view_projection: None,
+            previous_view_projection: Mat4::IDENTITY,
+            device_generation: 0,
+        }
+    }
+
+    /// Gets the size of the buffer in pixels, or `None` if nothing was rendered to it.
+    pub fn size(&self) -> Option<UVec2> {
+        self.targets.as_ref().map(|targets| targets.velocity.size)
+    }
+}
+
+/// Gets the distance between objects in the object buffer, which must be a multiple of
+/// the device's uniform offset alignment.
+fn object_stride(device: &wgpu::Device) -> usize {
+    wgpu::util::align_to(
+        std::mem::size_of::<VelocityObject>() as u32,
+        device.limits().min_uniform_buffer_offset_alignment,
+    ) as usize
+}
+
+impl MotionBlurPipeline {
+    pub(crate) fn new(device: &wgpu::Device, post_processor: &PostProcessor) -> Self {
+        let shader =
+            post_process::create_shader(device, "clockwork motion blur shader", SHADER_SOURCE);
+        Self {
+            render_pipeline: post_processor.create_pipeline(
+                device,
+                "clockwork motion blur pipeline",
+                &shader,
+                "fs_main",
+                post_processor.color_format,
+                None,
+            ),
+        }
+    }
+}
+
+impl VelocityPipeline {
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        let uniform_entry = |binding, has_dynamic_offset, size: usize| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset,
+                min_binding_size: wgpu::BufferSize::new(size as u64),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork velocity bind group layout"),
+                entries: &[
+                    uniform_entry(0, false, std::mem::size_of::<VelocityGlobal>()),
+                    uniform_entry(1, true, std::mem::size_of::<VelocityObject>()),
+                ],
+            }),
+        );
+        let layout = device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some("clockwork velocity pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            }),
+        );
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("clockwork velocity shader"),
+            source: wgpu::ShaderSource::Wgsl(VELOCITY_SHADER_SOURCE.into()),
+        });
+
+        let render_pipeline = device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some("clockwork velocity pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[VERTEX_BUFFER_LAYOUT],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: INTERMEDIATE_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }),
+        );
+
+        Self {
+            bind_group_layout,
+            render_pipeline,
+        }
+    }
+}
+
+impl RenderContext {
+    /// Renders how far each operation moved since the last frame into a
+    /// [VelocityBuffer] of the given size, which should match the scene's.
+    ///
+    /// Call this once per frame with the same operations and view projection the scene
+    /// was drawn with, and the camera's movement is tracked automatically.
+    pub fn render_velocity(
+        &mut self,
+        velocity: &mut VelocityBuffer,
+        size: UVec2,
+        view_projection: Mat4,
+        operations: &[VelocityOperation],
+    ) {
+        let size = size.max(UVec2::ONE);
+        velocity.previous_view_projection = velocity.view_projection.unwrap_or(view_projection);
+        velocity.view_projection = Some(view_projection);
+
+        if velocity.device_generation != self.device_generation {
+            velocity.targets = None;
+            velocity.buffers = None;
+            velocity.device_generation = self.device_generation;
+        }
+        if velocity.size() != Some(size) {
+            velocity.targets = Some(VelocityTargets {
+                velocity: post_process::create_intermediate_texture(&self.device, size),
+                depth: Texture::create_depth_texture(&self.device, size),
+            });
+        }
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(device, color_format));
+        let pipeline = post_processor
+            .velocity
+            .get_or_insert_with(|| VelocityPipeline::new(device));
+
+        // Grow the object buffer to fit every operation.
+        let stride = object_stride(device);
+        let capacity = velocity
+            .buffers
+            .as_ref()
+            .map_or(0, |buffers| buffers.capacity);
+        if capacity < operations.len().max(1) {
+            let capacity = operations.len().max(1).next_power_of_two();
+            let global = device.create_buffer(
+                &(wgpu::BufferDescriptor {
+                    label: Some("clockwork velocity global buffer"),
+                    size: std::mem::size_of::<VelocityGlobal>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+            );
+            let objects = device.create_buffer(
+                &(wgpu::BufferDescriptor {
+                    label: Some("clockwork velocity object buffer"),
+                    size: (capacity * stride) as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+            );
+            let bind_group = device.create_bind_group(
+                &(wgpu::BindGroupDescriptor {
+                    label: Some("clockwork velocity bind group"),
+                    layout: &pipeline.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: global.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &objects,
+                                offset: 0,
+                                size: wgpu::BufferSize::new(
+                                    std::mem::size_of::<VelocityObject>() as u64
+                                ),
+                            }),
+                        },
+                    ],
+                }),
+            );
+            velocity.buffers = Some(VelocityBuffers {
+                global,
+                objects,
+                capacity,
+                bind_group,
+            });
+        }
+        let buffers = velocity.buffers.as_ref().expect("created above");
+        let targets = velocity.targets.as_ref().expect("created above");
+
+        let global = VelocityGlobal {
+            view_projection: view_projection.to_cols_array_2d(),
+            previous_view_projection: velocity.previous_view_projection.to_cols_array_2d(),
+        };
+        self.queue
+            .write_buffer(&buffers.global, 0, bytemuck::bytes_of(&global));
+        self.counters.upload(std::mem::size_of::<VelocityGlobal>());
+
+        let mut objects = vec![0; operations.len() * stride];
+        for (chunk, operation) in objects.chunks_exact_mut(stride).zip(operations) {
+            let object = VelocityObject {
+                transform: operation.operation.transform.to_cols_array_2d(),
+                previous_transform: operation.previous_transform.to_cols_array_2d(),
+                blurred: if operation.blurred { 1.0 } else { 0.0 },
+                _padding: [0.0; 3],
+            };
+            chunk[..std::mem::size_of::<VelocityObject>()]
+                .copy_from_slice(bytemuck::bytes_of(&object));
+        }
+        if !objects.is_empty() {
+            self.queue.write_buffer(&buffers.objects, 0, &objects);
+            self.counters.upload(objects.len());
+        }
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork velocity encoder"),
+            }),
+        );
+        {
+            let mut render_pass = encoder.begin_render_pass(
+                &(wgpu::RenderPassDescriptor {
+                    label: Some("clockwork velocity pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &targets.velocity.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &targets.depth.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: true,
+                        }),
+                        stencil_ops: None,
+                    }),
+                }),
+            );
+            render_pass.set_pipeline(&pipeline.render_pipeline);
+            render_pass.set_vertex_buffer(0, self.mesh_pool.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(
+                self.mesh_pool.index_buffer.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+
+            for (index, operation) in operations.iter().enumerate() {
+                let Some(mesh) = self.meshes.get(operation.operation.mesh_id) else {
+                    continue;
+                };
+                render_pass.set_bind_group(0, &buffers.bind_group, &[(index * stride) as u32]);
+                render_pass.draw_indexed(mesh.indices.clone(), mesh.vertices.start as i32, 0..1);
+                self.counters.draw(1);
+            }
+        }
+
+        self.submit(encoder);
+    }
+
+    /// Draws `source` to `target`, blurred along the movement in a [VelocityBuffer]
+    /// rendered this frame with [RenderContext::render_velocity].
+    ///
+    /// Like render passes, nothing is drawn to the surface outside of a frame.
+    pub fn apply_motion_blur(
+        &mut self,
+        settings: MotionBlurSettings,
+        velocity: &VelocityBuffer,
+        source: ResourceId<Texture>,
+        target: RenderTarget,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            target != RenderTarget::Texture(source),
+            "motion blur can't draw to its own source {source:?}"
+        );
+        anyhow::ensure!(self.textures.get(source).is_some(), "no texture {source:?}");
+        let (Some(targets), Some(view_projection)) =
+            (velocity.targets.as_ref(), velocity.view_projection)
+        else {
+            anyhow::bail!("velocity buffer was never rendered to");
+        };
+        anyhow::ensure!(
+            velocity.device_generation == self.device_generation,
+            "velocity buffer wasn't rendered to since the device was lost"
+        );
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(device, color_format));
+        if post_processor.motion_blur.is_none() {
+            post_processor.motion_blur = Some(MotionBlurPipeline::new(device, post_processor));
+        }
+
+        let Some(target_view) = self.post_process_target_view(target)? else {
+            return Ok(());
+        };
+        let post_processor = self.post_processor.as_ref().expect("created above");
+        let pipeline = post_processor.motion_blur.as_ref().expect("created above");
+
+        let uniforms = MotionBlurUniforms {
+            inverse_view_projection: view_projection.inverse().to_cols_array_2d(),
+            previous_view_projection: velocity.previous_view_projection.to_cols_array_2d(),
+            shutter: settings.shutter.max(0.0),
+            samples: settings.samples.clamp(1, 64),
+            _padding: [0; 2],
+        };
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork motion blur encoder"),
+            }),
+        );
+        post_processor.encode(
+            &self.device,
+            &mut encoder,
+            FullscreenPass {
+                label: "clockwork motion blur pass",
+                pipeline: &pipeline.render_pipeline,
+                source: &self.textures[source].view,
+                secondary: Some(&targets.velocity.view),
+                uniforms: bytemuck::bytes_of(&uniforms),
+                target: target_view,
+                blend_constant: None,
+            },
+        );
+
+        self.submit(encoder);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shader_validates() {
+        post_process::validate_shader(SHADER_SOURCE);
+    }
+}
@@ -0,0 +1,39 @@
+struct MotionBlurUniforms {
+    inverse_view_projection: mat4x4<f32>,
+    previous_view_projection: mat4x4<f32>,
+    shutter: f32,
+    samples: u32,
+}
+@group(0) @binding(3)
+var<uniform> motion_blur: MotionBlurUniforms;
+
+// Velocity of the far plane caused only by the camera moving, for the background
+// where nothing was drawn to the velocity buffer.
+fn camera_velocity(uv: vec2<f32>) -> vec2<f32> {
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let world = motion_blur.inverse_view_projection * vec4<f32>(ndc, 1.0, 1.0);
+    let previous = motion_blur.previous_view_projection * world;
+    if (previous.w <= 0.0) {
+        return vec2<f32>(0.0);
+    }
+    return (ndc - previous.xy / previous.w) * vec2<f32>(0.5, -0.5);
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let velocity_sample = textureSample(secondary_texture, source_sampler, in.uv);
+    var velocity = velocity_sample.xy;
+    if (velocity_sample.a == 0.0) {
+        velocity = camera_velocity(in.uv);
+    }
+    velocity *= motion_blur.shutter;
+
+    // Average samples along the path the pixel moved, centered on the pixel.
+    let count = max(motion_blur.samples, 1u);
+    var color = vec4<f32>(0.0);
+    for (var index = 0u; index < count; index++) {
+        let offset = (f32(index) + 0.5) / f32(count) - 0.5;
+        color += textureSampleLevel(source_texture, source_sampler, in.uv - velocity * offset, 0.0);
+    }
+    return color / f32(count);
+}
@@ -0,0 +1,97 @@
+//! Choice of the surface's format, which every pipeline drawing to the surface or a
+//! render target is built against.
+
+use super::RenderContext;
+
+/// Color encoding of the frames presented to the window, see
+/// [crate::EngineConfig::output_format].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// 8 bit color encoded to sRGB as it's written, so colors are given in linear space.
+    #[default]
+    Srgb,
+    /// 8 bit color presented as written, for games doing their own encoding.
+    Linear,
+    /// 16 bit float color that can go past 1 on HDR displays.
+    Hdr,
+}
+
+/// Picks the surface format for an [OutputFormat] out of the formats the surface
+/// supports, falling back to an sRGB format and then to the surface's preferred one.
+pub(crate) fn select_surface_format(
+    formats: &[wgpu::TextureFormat],
+    output_format: OutputFormat,
+) -> Option<wgpu::TextureFormat> {
+    let find = |matches: fn(wgpu::TextureFormat) -> bool| {
+        formats.iter().copied().find(|format| matches(*format))
+    };
+    let preferred = match output_format {
+        OutputFormat::Srgb => None,
+        OutputFormat::Linear => find(|format| {
+            matches!(
+                format,
+                wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Rgba8Unorm
+            )
+        }),
+        OutputFormat::Hdr => find(|format| format == wgpu::TextureFormat::Rgba16Float),
+    };
+    preferred
+        .or_else(|| find(|format| format.is_srgb()))
+        .or_else(|| formats.first().copied())
+}
+
+impl RenderContext {
+    /// Gets the format of the surface, which render targets share so the same
+    /// pipelines can draw to either.
+    pub fn color_format(&self) -> wgpu::TextureFormat {
+        self.surface_config.format
+    }
+
+    /// Gets the [OutputFormat] the surface ended up with, which may differ from the
+    /// requested one when the surface doesn't support it.
+    pub fn output_format(&self) -> OutputFormat {
+        match self.surface_config.format {
+            wgpu::TextureFormat::Rgba16Float => OutputFormat::Hdr,
+            format if format.is_srgb() => OutputFormat::Srgb,
+            _ => OutputFormat::Linear,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::TextureFormat;
+
+    #[test]
+    fn test_select_surface_format() {
+        let formats = [
+            TextureFormat::Bgra8Unorm,
+            TextureFormat::Bgra8UnormSrgb,
+            TextureFormat::Rgba16Float,
+        ];
+        let select = |output_format| select_surface_format(&formats, output_format);
+        assert_eq!(
+            select(OutputFormat::Srgb),
+            Some(TextureFormat::Bgra8UnormSrgb)
+        );
+        assert_eq!(
+            select(OutputFormat::Linear),
+            Some(TextureFormat::Bgra8Unorm)
+        );
+        assert_eq!(select(OutputFormat::Hdr), Some(TextureFormat::Rgba16Float));
+
+        // Unsupported formats fall back to sRGB, then whatever the surface prefers.
+        let formats = [TextureFormat::Rgba8Unorm, TextureFormat::Rgba8UnormSrgb];
+        assert_eq!(
+            select_surface_format(&formats, OutputFormat::Hdr),
+            Some(TextureFormat::Rgba8UnormSrgb)
+        );
+        assert_eq!(
+            select_surface_format(&[TextureFormat::Rgb10a2Unorm], OutputFormat::Srgb),
+            Some(TextureFormat::Rgb10a2Unorm)
+        );
+        assert_eq!(select_surface_format(&[], OutputFormat::Srgb), None);
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::Result;
+use glam::{vec4, Mat4, UVec2, Vec2, Vec4};
+
+use crate::{
+    graphics::{default_meshes::QUAD_MESH_DATA, texture::Texture, Mesh},
+    util::repository::ResourceId,
+};
+
+use super::{
+    CustomMaterial, Material, MaterialData, MaterialLayout, MaterialPipeline, RenderContext,
+    RenderLayers, RenderOperation, RenderPassDescriptor, RenderTarget, TextureMaps,
+    TextureParameters,
+};
+
+const SHADER_SOURCE: &str = include_str!("brush.wgsl");
+
+/// Describes how [RenderContext::paint] marks a texture.
+#[derive(Clone, Copy, Debug)]
+pub struct Brush {
+    /// Color to paint with, including its opacity.
+    pub color: Vec4,
+    /// Size of the brush in uv units, so `1.0` covers the whole texture.
+    pub size: Vec2,
+    /// Rotation of the brush in radians.
+    pub rotation: f32,
+    /// How far from the center of a circular brush its edge starts fading, from 0 to 1.
+    pub hardness: f32,
+    /// Texture stamped by the brush, multiplied by its color. Without one the brush
+    /// paints a circle.
+    pub stamp: Option<ResourceId<Texture>>,
+}
+
+/// Resources used for painting, created the first time something is painted.
+pub(crate) struct Painter {
+    pipeline_id: ResourceId<MaterialPipeline>,
+    quad_mesh_id: ResourceId<Mesh>,
+    white_texture_id: ResourceId<Texture>,
+}
+
+/// Uniforms of the brush shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BrushUniforms {
+    color: [f32; 4],
+    hardness: f32,
+    circular: u32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for BrushUniforms {}
+unsafe impl bytemuck::Pod for BrushUniforms {}
+
+impl Brush {
+    /// Creates a round [Brush] with the given diameter in uv units.
+    pub fn circle(diameter: f32, color: Vec4) -> Self {
+        Self {
+            color,
+            size: Vec2::splat(diameter),
+            rotation: 0.0,
+            hardness: 0.5,
+            stamp: None,
+        }
+    }
+
+    /// Creates a [Brush] that stamps a texture with the given size in uv units.
+    pub fn stamp(texture_id: ResourceId<Texture>, size: Vec2) -> Self {
+        Self {
+            color: Vec4::ONE,
+            size,
+            rotation: 0.0,
+            hardness: 1.0,
+            stamp: Some(texture_id),
+        }
+    }
+
+    /// Sets the rotation of the brush in radians.
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Sets how far from the center the edge of a circular brush starts fading.
+    pub fn with_hardness(mut self, hardness: f32) -> Self {
+        self.hardness = hardness;
+        self
+    }
+
+    /// Transformation of a quad painted at `uv` in the uv space of [uv_view_projection].
+    fn transform(&self, uv: Vec2) -> Mat4 {
+        // The quad is y-up while uvs are y-down, so flip it to keep stamps upright.
+        Mat4::from_translation(uv.extend(0.0))
+            * Mat4::from_rotation_z(self.rotation)
+            * Mat4::from_scale(self.size.extend(1.0) * glam::vec3(1.0, -1.0, 1.0))
+    }
+}
+
+/// View projection that maps uv coordinates onto a render target.
+fn uv_view_projection() -> Mat4 {
+    Mat4::orthographic_rh(0.0, 1.0, 1.0, 0.0, -1.0, 1.0)
+}
+
+impl RenderContext {
+    /// Loads a texture that can be painted on with [RenderContext::paint], such as a
+    /// terrain splat map.
+    pub fn load_paintable_texture(&mut self, bytes: &[u8]) -> Result<ResourceId<Texture>> {
+        let image = image::load_from_memory(bytes)?.to_rgba8();
+        let size = UVec2::new(image.width(), image.height());
+        let texture_id = self.create_render_target(size);
+        self.update_texture_region(texture_id, UVec2::ZERO, size, &image)?;
+        Ok(texture_id)
+    }
+
+    /// Paints a brush mark centered at `uv` into a texture. The mark stays until it is
+    /// painted over, so this is suitable for scorch marks, footprints and the like.
+    ///
+    /// The texture must come from [RenderContext::create_render_target] or
+    /// [RenderContext::load_paintable_texture].
+    pub fn paint(
+        &mut self,
+        texture_id: ResourceId<Texture>,
+        uv: Vec2,
+        brush: &Brush,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            self.render_target_depth_textures.contains_key(&texture_id),
+            "texture {texture_id:?} is not a render target"
+        );
+        anyhow::ensure!(
+            brush.stamp != Some(texture_id),
+            "a texture can't be stamped onto itself"
+        );
+
+        let painter = match &self.painter {
+            Some(painter) => painter,
+            None => {
+                let painter = Painter {
+                    pipeline_id: self.register_material(
+                        SHADER_SOURCE,
+                        MaterialLayout::with_uniforms(
+                            std::mem::size_of::<BrushUniforms>() as wgpu::BufferAddress
+                        )
+                        .with_name("clockwork paint"),
+                    )?,
+                    quad_mesh_id: self.load_mesh(QUAD_MESH_DATA),
+                    white_texture_id: self.load_texture_rgba(UVec2::ONE, &[u8::MAX; 4])?,
+                };
+                self.painter.insert(painter)
+            }
+        };
+        let (pipeline_id, quad_mesh_id, white_texture_id) = (
+            painter.pipeline_id,
+            painter.quad_mesh_id,
+            painter.white_texture_id,
+        );
+
+        let uniforms = BrushUniforms {
+            color: brush.color.to_array(),
+            hardness: brush.hardness.clamp(0.0, 1.0),
+            circular: brush.stamp.is_none() as u32,
+            _padding: [0; 2],
+        };
+        self.set_material_uniforms(pipeline_id, bytemuck::bytes_of(&uniforms))?;
+
+        let operation = RenderOperation {
+            transform: brush.transform(uv),
+            mesh_id: quad_mesh_id,
+            material: Material::Custom(CustomMaterial {
+                pipeline_id,
+                color: brush.color,
+                texture_parameters: Some(TextureParameters::new(
+                    brush.stamp.unwrap_or(white_texture_id),
+                    Some(vec4(0.0, 0.0, 1.0, 1.0)),
+                )),
+                maps: TextureMaps::default(),
+                data: MaterialData::default(),
+            }),
+            layers: RenderLayers::DEFAULT,
+            transparent: false,
+            sort_layer: 0,
+        };
+        self.render_pass(
+            &RenderPassDescriptor::new(uv_view_projection())
+                .with_clear_color(None)
+                .with_target(RenderTarget::Texture(texture_id))
+                .with_label("clockwork paint pass"),
+            &[operation],
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_brush_lands_on_uv() {
+        let brush = Brush::circle(0.5, Vec4::ONE);
+        let transform = uv_view_projection() * brush.transform(Vec2::new(0.25, 0.25));
+
+        // The brush's top left corner is the texture's top left corner in clip space.
+        let top_left = transform.project_point3(Vec3::new(-0.5, 0.5, 0.0));
+        assert!(top_left.truncate().abs_diff_eq(glam::vec2(-1.0, 1.0), 1e-6));
+        let bottom_right = transform.project_point3(Vec3::new(0.5, -0.5, 0.0));
+        assert!(bottom_right
+            .truncate()
+            .abs_diff_eq(glam::vec2(0.0, 0.0), 1e-6));
+    }
+}
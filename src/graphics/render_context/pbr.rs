@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use crate::{
+    graphics::texture::{SamplerSettings, Texture},
+    util::repository::ResourceId,
+};
+
+use super::{
+    create_render_pipeline, create_render_pipeline_layout,
+    environment::{EnvironmentMap, EnvironmentRenderer},
+    eviction,
+    light_culling::LightBuffers,
+    render_operation::PbrTextures,
+    RenderContext,
+};
+
+/// Source of the shader PBR materials are drawn with.
+const SHADER_SOURCE: &str = concat!(
+    include_str!("shader.wgsl"),
+    include_str!("environment.wgsl"),
+    include_str!("pbr.wgsl")
+);
+
+/// Index of the normal map in [PbrTextures].
+const NORMAL_TEXTURE_INDEX: usize = 2;
+
+/// Resources for drawing [super::PbrMaterial]s, created the first time one is drawn.
+pub(crate) struct PbrRenderer {
+    textures_bind_group_layout: wgpu::BindGroupLayout,
+    pub(crate) render_pipeline: wgpu::RenderPipeline,
+    pub(crate) transparent_render_pipeline: wgpu::RenderPipeline,
+    /// Environment of materials without one, which reflects nothing.
+    pub(crate) default_environment: EnvironmentMap,
+    /// Bind groups for each set of textures, along with the generations of the textures
+    /// they were created with and the frame they were last used.
+    bind_groups: HashMap<PbrTextures, (PbrGenerations, wgpu::BindGroup, u64)>,
+}
+
+type PbrGenerations = [usize; 5];
+
+impl PbrRenderer {
+    fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        light_buffers: LightBuffers,
+        buffers_bind_group_layout: &wgpu::BindGroupLayout,
+        environment: &EnvironmentRenderer,
+    ) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let textures_bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork pbr textures bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    texture_entry(1),
+                    texture_entry(2),
+                    texture_entry(3),
+                    texture_entry(4),
+                    texture_entry(5),
+                ],
+            }),
+        );
+
+        let (render_pipeline, transparent_render_pipeline) = create_render_pipeline(
+            device,
+            color_format,
+            "clockwork pbr pipeline",
+            &create_render_pipeline_layout(
+                device,
+                "clockwork pbr pipeline layout",
+                &[
+                    buffers_bind_group_layout,
+                    &textures_bind_group_layout,
+                    &environment.bind_group_layout,
+                ],
+            ),
+            wgpu::ShaderSource::Wgsl(light_buffers.shader_source(SHADER_SOURCE).into()),
+            "fs_pbr",
+        );
+
+        Self {
+            textures_bind_group_layout,
+            render_pipeline,
+            transparent_render_pipeline,
+            default_environment: EnvironmentMap::new(
+                device,
+                environment,
+                1,
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+            ),
+            bind_groups: HashMap::new(),
+        }
+    }
+
+    /// Gets the bind group for a set of textures.
+    pub(crate) fn bind_group(&self, textures: PbrTextures) -> &wgpu::BindGroup {
+        &self.bind_groups[&textures].1
+    }
+
+    /// Drops the bind groups using a texture, such as when it's destroyed.
+    pub(crate) fn forget_texture(&mut self, texture_id: ResourceId<Texture>) {
+        self.bind_groups
+            .retain(|textures, _| !textures.contains(&Some(texture_id)));
+    }
+
+    /// Drops the bind groups that haven't been used for a while, see [eviction].
+    pub(crate) fn evict_stale_bind_groups(&mut self, frame_index: u64) {
+        self.bind_groups
+            .retain(|_, (.., last_used)| !eviction::is_stale(*last_used, frame_index));
+    }
+
+    /// Gets the number of bind groups cached.
+    pub(crate) fn bind_group_count(&self) -> usize {
+        self.bind_groups.len()
+    }
+}
+
+impl RenderContext {
+    /// Ensures the resources for PBR materials are created, along with a valid bind
+    /// group for the set of textures.
+    pub(crate) fn ensure_pbr_bind_group_valid(&mut self, textures: PbrTextures) {
+        let color_format = self.color_format();
+        let light_buffers = self.light_buffers();
+        let environment = self.environment.get_or_insert_with(|| {
+            EnvironmentRenderer::new(
+                &self.device,
+                color_format,
+                light_buffers,
+                &self.buffers_bind_group_layout,
+                &self.textures_bind_group_layout,
+            )
+        });
+        let pbr = self.pbr.get_or_insert_with(|| {
+            PbrRenderer::new(
+                &self.device,
+                color_format,
+                light_buffers,
+                &self.buffers_bind_group_layout,
+                environment,
+            )
+        });
+
+        let generations = textures.map(|texture_id| {
+            texture_id.map_or(0, |texture_id| self.textures.get_generation(texture_id))
+        });
+        if let Some((bind_group_generations, _, last_used)) = pbr.bind_groups.get_mut(&textures) {
+            if *bind_group_generations == generations {
+                *last_used = self.frame_index;
+                return;
+            }
+        }
+
+        // The first texture decides how the group is sampled.
+        let sampler_settings = textures
+            .iter()
+            .flatten()
+            .next()
+            .map_or(SamplerSettings::default(), |texture_id| {
+                self.textures[*texture_id].sampler
+            });
+        let device = &self.device;
+        let sampler = self
+            .samplers
+            .entry(sampler_settings)
+            .or_insert_with(|| sampler_settings.create_sampler(device));
+
+        let views: Vec<&wgpu::TextureView> = textures
+            .iter()
+            .enumerate()
+            .map(|(index, texture_id)| match texture_id {
+                Some(texture_id) => &self.textures[*texture_id].view,
+                None => {
+                    &self
+                        .fallback_textures
+                        .get(index == NORMAL_TEXTURE_INDEX)
+                        .view
+                }
+            })
+            .collect();
+        let entries: Vec<wgpu::BindGroupEntry> =
+            std::iter::once(wgpu::BindingResource::Sampler(sampler))
+                .chain(views.into_iter().map(wgpu::BindingResource::TextureView))
+                .enumerate()
+                .map(|(binding, resource)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource,
+                })
+                .collect();
+        let bind_group = device.create_bind_group(
+            &(wgpu::BindGroupDescriptor {
+                label: Some("clockwork pbr textures bind group"),
+                layout: &pbr.textures_bind_group_layout,
+                entries: &entries,
+            }),
+        );
+        pbr.bind_groups
+            .insert(textures, (generations, bind_group, self.frame_index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shader_validates() {
+        for light_buffers in [LightBuffers::Tiled, LightBuffers::Uniform] {
+            let source = light_buffers.shader_source(SHADER_SOURCE);
+            let module = naga::front::wgsl::parse_str(&source).unwrap();
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .unwrap();
+        }
+    }
+}
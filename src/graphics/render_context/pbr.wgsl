@@ -0,0 +1,123 @@
+
+@group(1) @binding(2)
+var metallic_roughness_texture: texture_2d<f32>;
+@group(1) @binding(3)
+var normal_texture: texture_2d<f32>;
+@group(1) @binding(4)
+var occlusion_texture: texture_2d<f32>;
+@group(1) @binding(5)
+var emissive_texture: texture_2d<f32>;
+
+const PI: f32 = 3.14159265;
+
+// GGX distribution of microfacet normals.
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha_squared = alpha * alpha;
+    let denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    return alpha_squared / (PI * denominator * denominator);
+}
+
+// Height correlated Smith visibility, which includes the BRDF's denominator.
+fn visibility_smith(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    let alpha_squared = alpha * alpha;
+    let view = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha_squared) + alpha_squared);
+    let light = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha_squared) + alpha_squared);
+    return 0.5 / max(view + light, 1e-5);
+}
+
+fn fresnel_schlick(f0: vec3<f32>, cos_theta: f32) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Analytic fit of the split sum approximation's BRDF term, in place of a lookup table.
+fn environment_brdf(f0: vec3<f32>, roughness: f32, n_dot_v: f32) -> vec3<f32> {
+    let c0 = vec4<f32>(-1.0, -0.0275, -0.572, 0.022);
+    let c1 = vec4<f32>(1.0, 0.0425, 1.04, -0.04);
+    let r = roughness * c0 + c1;
+    let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+    let scale_bias = vec2<f32>(-1.04, 1.04) * a004 + r.zw;
+    return f0 * scale_bias.x + scale_bias.y;
+}
+
+// Fragment shader for PBR materials, with metallic, roughness, normal scale and
+// occlusion strength in `local.parameters`.
+@fragment
+fn fs_pbr(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    // Everything needing uniform control flow happens before discarding. Textures are
+    // premultiplied, so the color is too.
+    let base_sample = textureSample(texture, texture_sampler, in.uv) * in.color;
+    let alpha = base_sample.a * local.color.a;
+    let albedo = base_sample.rgb * local.color.rgb * local.color.a;
+    let metallic_roughness = textureSample(metallic_roughness_texture, texture_sampler, in.uv);
+    let mapped_normal = textureSample(normal_texture, texture_sampler, in.uv).xyz * 2.0 - 1.0;
+    let occlusion_sample = textureSample(occlusion_texture, texture_sampler, in.uv).r;
+    let emissive = textureSample(emissive_texture, texture_sampler, in.uv).rgb * local.emissive.rgb;
+
+    let geometric_normal = normalize(in.normal);
+    let normal = perturb_normal(
+        geometric_normal,
+        in.world_position,
+        in.uv,
+        normalize(vec3<f32>(mapped_normal.xy * local.parameters.z, mapped_normal.z)),
+    );
+    let to_camera = normalize(global.camera.xyz - in.world_position * global.camera.w);
+    let reflected = reflect(-to_camera, normal);
+    let roughness = clamp(metallic_roughness.g * local.parameters.y, 0.04, 1.0);
+    let max_mip_level = f32(textureNumLevels(environment) - 1u);
+    let prefiltered = textureSampleLevel(
+        environment,
+        environment_sampler,
+        reflected,
+        roughness * max_mip_level,
+    ).rgb;
+    let irradiance = textureSampleLevel(
+        environment,
+        environment_sampler,
+        normal,
+        max_mip_level,
+    ).rgb;
+    if (alpha < 0.001) {
+        discard;
+    }
+
+    if (lighting.lit == 0u) {
+        return apply_debug_view(vec4<f32>(albedo + emissive * alpha, alpha), in);
+    }
+
+    let metallic = clamp(metallic_roughness.b * local.parameters.x, 0.0, 1.0);
+    let occlusion = mix(1.0, occlusion_sample, local.parameters.w);
+    let roughness_squared = roughness * roughness;
+    let diffuse_color = albedo * (1.0 - metallic);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let n_dot_v = max(dot(normal, to_camera), 0.0001);
+
+    var color = vec3<f32>(0.0);
+    let tile = light_tile(in.clip_position.xy);
+    let count = tile_light_count(tile);
+    for (var index = 0u; index < count; index++) {
+        let light = tile_light(tile, index);
+        let incidence = light_incidence(light, in.world_position);
+        let to_light = incidence.xyz;
+        let n_dot_l = max(dot(normal, to_light), 0.0);
+        if (n_dot_l <= 0.0) {
+            continue;
+        }
+
+        let halfway = normalize(to_light + to_camera);
+        let fresnel = fresnel_schlick(f0, max(dot(halfway, to_camera), 0.0));
+        let specular = fresnel
+            * distribution_ggx(max(dot(normal, halfway), 0.0), roughness_squared)
+            * visibility_smith(n_dot_v, n_dot_l, roughness_squared);
+        let diffuse = (1.0 - fresnel) * diffuse_color;
+        // Lights use the default shader's units, where a light shining straight at a
+        // white surface lights it fully, so the BRDF is scaled by pi.
+        color += (diffuse + specular * PI) * light.color.rgb * incidence.w * n_dot_l;
+    }
+
+    let ambient = (lighting.ambient.rgb + irradiance) * diffuse_color
+        + prefiltered * environment_brdf(f0, roughness, n_dot_v);
+    color += ambient * occlusion + emissive * alpha;
+    return apply_debug_view(light_heatmap(vec4<f32>(color, alpha), in.clip_position.xy), in);
+}
@@ -0,0 +1,258 @@
+use anyhow::Result;
+use glam::{IVec2, UVec2, Vec2, Vec4};
+
+use crate::{graphics::texture::Texture, input::InputState, util::repository::ResourceId};
+
+use super::{
+    post_process::{self, FullscreenPass, PostProcessor},
+    RenderContext, RenderTarget,
+};
+
+const SHADER_SOURCE: &str = include_str!("pixel_perfect.wgsl");
+
+/// Low resolution the scene is rendered at and then scaled up to the window by a whole
+/// number, so every pixel of the scene is the same size on screen, such as for retro
+/// games.
+///
+/// The scaled scene is centered, with bars filling the rest of the window.
+///
+/// Each frame, after [RenderContext::begin_frame]:
+/// 1. [RenderContext::update_pixel_perfect] fits the scene to the window.
+/// 2. Scene passes draw into it with
+///    [super::RenderPassDescriptor::with_pixel_perfect].
+/// 3. [RenderContext::present_pixel_perfect] scales the scene up to the window.
+///
+/// Use [PixelPerfect::virtual_mouse_position] to find the cursor in the scene.
+pub struct PixelPerfect {
+    resolution: UVec2,
+    /// Color of the bars around the scene.
+    pub bar_color: Vec4,
+    texture: Option<ResourceId<Texture>>,
+    /// Window pixels per scene pixel.
+    scale: u32,
+    /// Top left of the scaled scene in the window, in pixels.
+    offset: IVec2,
+}
+
+/// Pipeline that scales the scene up to the target.
+pub(crate) struct PixelPerfectPipeline {
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PixelPerfectUniforms {
+    offset: [f32; 2],
+    scale: f32,
+    _padding: f32,
+    bar_color: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for PixelPerfectUniforms {}
+unsafe impl bytemuck::Pod for PixelPerfectUniforms {}
+
+impl PixelPerfect {
+    /// Creates a [PixelPerfect] rendering at a resolution, such as 320x180, with black
+    /// bars.
+    pub fn new(resolution: UVec2) -> Self {
+        Self {
+            resolution: resolution.max(UVec2::ONE),
+            bar_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            texture: None,
+            scale: 1,
+            offset: IVec2::ZERO,
+        }
+    }
+
+    /// Sets the color of the bars around the scene.
+    pub fn with_bar_color(mut self, bar_color: Vec4) -> Self {
+        self.bar_color = bar_color;
+        self
+    }
+
+    /// Gets the resolution the scene is rendered at.
+    pub fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
+
+    /// Sets the resolution the scene is rendered at, which takes effect on the next
+    /// [RenderContext::update_pixel_perfect].
+    pub fn set_resolution(&mut self, resolution: UVec2) {
+        self.resolution = resolution.max(UVec2::ONE);
+    }
+
+    /// Gets the number of window pixels across each pixel of the scene.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Gets the top left and size of the scaled scene in the window, in pixels.
+    pub fn viewport(&self) -> (IVec2, UVec2) {
+        (self.offset, self.resolution * self.scale)
+    }
+
+    /// Gets the texture scene passes draw to, or `None` before the first
+    /// [RenderContext::update_pixel_perfect].
+    pub fn texture_id(&self) -> Option<ResourceId<Texture>> {
+        self.texture
+    }
+
+    /// Converts a position in pixels from the top left of the window to pixels from the
+    /// top left of the scene, or `None` if it's over the bars.
+    pub fn window_to_virtual(&self, position: Vec2) -> Option<Vec2> {
+        let position = (position - self.offset.as_vec2()) / self.scale as f32;
+        (position.cmpge(Vec2::ZERO).all() && position.cmplt(self.resolution.as_vec2()).all())
+            .then_some(position)
+    }
+
+    /// Gets the position of the cursor in pixels from the top left of the scene, or
+    /// `None` if it's not over the scene.
+    pub fn virtual_mouse_position(&self, input_state: &InputState) -> Option<Vec2> {
+        self.window_to_virtual(input_state.mouse_position()?)
+    }
+}
+
+/// Gets the largest whole number scale a resolution fits in a window at, and the top
+/// left that centers it. Windows smaller than the resolution show its middle.
+fn fit(resolution: UVec2, window_size: UVec2) -> (u32, IVec2) {
+    let scale = (window_size / resolution).min_element().max(1);
+    let offset = (window_size.as_ivec2() - (resolution * scale).as_ivec2()) / 2;
+    (scale, offset)
+}
+
+impl PixelPerfectPipeline {
+    pub(crate) fn new(device: &wgpu::Device, post_processor: &PostProcessor) -> Self {
+        let shader =
+            post_process::create_shader(device, "clockwork pixel perfect shader", SHADER_SOURCE);
+        Self {
+            render_pipeline: post_processor.create_pipeline(
+                device,
+                "clockwork pixel perfect pipeline",
+                &shader,
+                "fs_main",
+                post_processor.color_format,
+                None,
+            ),
+        }
+    }
+}
+
+impl RenderContext {
+    /// Fits a [PixelPerfect] to the window, and recreates its texture if its resolution
+    /// changed.
+    pub fn update_pixel_perfect(&mut self, pixel_perfect: &mut PixelPerfect) {
+        let surface_size = UVec2::new(self.surface_config.width, self.surface_config.height);
+        (pixel_perfect.scale, pixel_perfect.offset) = fit(pixel_perfect.resolution, surface_size);
+
+        let current = pixel_perfect
+            .texture
+            .filter(|&texture_id| self.textures[texture_id].size == pixel_perfect.resolution);
+        if current.is_none() {
+            if let Some(texture_id) = pixel_perfect.texture {
+                self.remove_texture(texture_id);
+            }
+            pixel_perfect.texture = Some(self.create_render_target(pixel_perfect.resolution));
+        }
+    }
+
+    /// Draws the scene rendered into a [PixelPerfect] this frame over `target`, scaled
+    /// up to fit the window with bars around it.
+    ///
+    /// Like render passes, nothing is drawn to the surface outside of a frame.
+    pub fn present_pixel_perfect(
+        &mut self,
+        pixel_perfect: &PixelPerfect,
+        target: RenderTarget,
+    ) -> Result<()> {
+        let Some(source) = pixel_perfect.texture_id() else {
+            anyhow::bail!("pixel perfect rendering was never updated");
+        };
+        anyhow::ensure!(
+            target != RenderTarget::Texture(source),
+            "pixel perfect rendering can't present to its own texture {source:?}"
+        );
+        anyhow::ensure!(self.textures.get(source).is_some(), "no texture {source:?}");
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(device, color_format));
+        if post_processor.pixel_perfect.is_none() {
+            post_processor.pixel_perfect = Some(PixelPerfectPipeline::new(device, post_processor));
+        }
+
+        let Some(target_view) = self.post_process_target_view(target)? else {
+            return Ok(());
+        };
+        let post_processor = self.post_processor.as_ref().expect("created above");
+        let pipeline = post_processor
+            .pixel_perfect
+            .as_ref()
+            .expect("created above");
+
+        let uniforms = PixelPerfectUniforms {
+            offset: pixel_perfect.offset.as_vec2().to_array(),
+            scale: pixel_perfect.scale as f32,
+            _padding: 0.0,
+            bar_color: pixel_perfect.bar_color.to_array(),
+        };
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork pixel perfect encoder"),
+            }),
+        );
+        post_processor.encode(
+            &self.device,
+            &mut encoder,
+            FullscreenPass {
+                label: "clockwork pixel perfect pass",
+                pipeline: &pipeline.render_pipeline,
+                source: &self.textures[source].view,
+                secondary: None,
+                uniforms: bytemuck::bytes_of(&uniforms),
+                target: target_view,
+                blend_constant: None,
+            },
+        );
+
+        self.submit(encoder);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_and_mouse() {
+        let resolution = UVec2::new(320, 180);
+        assert_eq!(fit(resolution, UVec2::new(1920, 1080)), (6, IVec2::ZERO));
+        // Letterboxed above and below, with the leftover split evenly.
+        assert_eq!(
+            fit(resolution, UVec2::new(1280, 1000)),
+            (4, IVec2::new(0, 140))
+        );
+        // Too small to fit shows the middle at the original size.
+        assert_eq!(
+            fit(resolution, UVec2::new(300, 180)),
+            (1, IVec2::new(-10, 0))
+        );
+
+        let mut pixel_perfect = PixelPerfect::new(resolution);
+        (pixel_perfect.scale, pixel_perfect.offset) = fit(resolution, UVec2::new(1280, 1000));
+        assert_eq!(
+            pixel_perfect.window_to_virtual(Vec2::new(6.0, 150.0)),
+            Some(Vec2::new(1.5, 2.5))
+        );
+        assert_eq!(pixel_perfect.window_to_virtual(Vec2::new(6.0, 100.0)), None);
+        assert_eq!(pixel_perfect.window_to_virtual(Vec2::new(6.0, 860.0)), None);
+    }
+
+    #[test]
+    fn test_shader_validates() {
+        post_process::validate_shader(SHADER_SOURCE);
+    }
+}
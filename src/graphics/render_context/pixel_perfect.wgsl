@@ -0,0 +1,23 @@
+struct PixelPerfectUniforms {
+    // Top left of the scaled image in the target, in pixels, which is negative when the
+    // target is smaller than the image.
+    offset: vec2<f32>,
+    // Whole number of target pixels per pixel of the image.
+    scale: f32,
+    _padding: f32,
+    bar_color: vec4<f32>,
+}
+@group(0) @binding(3)
+var<uniform> pixel_perfect: PixelPerfectUniforms;
+
+// Loads the pixel of the image each target pixel falls in, so pixels stay square
+// and sharp, and fills the rest of the target with the bar color.
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let position = floor((in.clip_position.xy - pixel_perfect.offset) / pixel_perfect.scale);
+    let size = vec2<f32>(textureDimensions(source_texture));
+    if any(position < vec2<f32>(0.0)) || any(position >= size) {
+        return pixel_perfect.bar_color;
+    }
+    return textureLoad(source_texture, vec2<i32>(position), 0);
+}
@@ -0,0 +1,335 @@
+use glam::UVec2;
+use wgpu::util::DeviceExt;
+
+use crate::graphics::texture::{SamplerSettings, Texture, TextureFilter};
+
+use super::{
+    bloom, dynamic_resolution, exposure, motion_blur, pixel_perfect, post_process_stack,
+    stats::RenderCounters, stylistic, RenderContext, RenderTarget, Shared,
+};
+
+/// Start of every post process shader, providing `vs_main`, the source texture at
+/// `@group(0) @binding(1)` and a secondary texture at `@group(0) @binding(2)`.
+const PRELUDE: &str = include_str!("post_process.wgsl");
+
+/// Format of the textures effects render to between passes, which has the range
+/// and precision to accumulate light.
+pub(crate) const INTERMEDIATE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Resources shared by post process effects, created the first time one is used.
+pub(crate) struct PostProcessor {
+    /// Shared with post processors compiling pipelines for a warmup, so their
+    /// pipelines can be merged into this one.
+    layout: Shared<PostProcessLayout>,
+    /// Format of the surface and render targets effects write their output to.
+    pub(crate) color_format: wgpu::TextureFormat,
+    /// Work done by passes, added to the context's counts when the frame ends.
+    pub(crate) counters: RenderCounters,
+    /// Pipelines for [RenderContext::apply_bloom].
+    pub(crate) bloom: Option<bloom::BloomPipelines>,
+    /// Pipelines for [RenderContext::apply_auto_exposure].
+    pub(crate) exposure: Option<exposure::ExposurePipelines>,
+    /// Pipeline for [RenderContext::render_velocity].
+    pub(crate) velocity: Option<motion_blur::VelocityPipeline>,
+    /// Pipeline for [RenderContext::apply_motion_blur].
+    pub(crate) motion_blur: Option<motion_blur::MotionBlurPipeline>,
+    /// Pipelines for [RenderContext::apply_stylistic_effects].
+    pub(crate) stylistic: Option<stylistic::StylisticPipelines>,
+    /// Pipelines for [RenderContext::apply_post_process_stack].
+    pub(crate) stack: Option<post_process_stack::StackPipelines>,
+    /// Pipeline for [RenderContext::upscale_dynamic_resolution].
+    pub(crate) upscale: Option<dynamic_resolution::UpscalePipeline>,
+    /// Pipeline for [RenderContext::present_pixel_perfect].
+    pub(crate) pixel_perfect: Option<pixel_perfect::PixelPerfectPipeline>,
+}
+
+/// Layouts every post process pipeline and bind group is made with.
+struct PostProcessLayout {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    sampler: wgpu::Sampler,
+}
+
+/// A single fullscreen draw of a post process effect.
+pub(crate) struct FullscreenPass<'a> {
+    pub(crate) label: &'static str,
+    pub(crate) pipeline: &'a wgpu::RenderPipeline,
+    pub(crate) source: &'a wgpu::TextureView,
+    /// Second texture the effect reads, such as the scene when combining.
+    pub(crate) secondary: Option<&'a wgpu::TextureView>,
+    /// Uniforms bound at `@group(0) @binding(3)`.
+    pub(crate) uniforms: &'a [u8],
+    pub(crate) target: &'a wgpu::TextureView,
+    /// Constant used by pipelines that blend with [wgpu::BlendFactor::Constant]. Passes
+    /// with one blend over the target's contents, while others replace them.
+    pub(crate) blend_constant: Option<f64>,
+}
+
+impl PostProcessor {
+    /// Creates a new [PostProcessor].
+    pub(crate) fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork post process bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    texture_entry(1),
+                    texture_entry(2),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            }),
+        );
+        let pipeline_layout = device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some("clockwork post process pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            }),
+        );
+        let sampler = SamplerSettings {
+            filter: TextureFilter::Linear,
+            ..Default::default()
+        }
+        .create_sampler(device);
+
+        Self::with_layout(
+            Shared::new(PostProcessLayout {
+                bind_group_layout,
+                pipeline_layout,
+                sampler,
+            }),
+            color_format,
+        )
+    }
+
+    /// Creates a [PostProcessor] without pipelines on the given layout.
+    fn with_layout(layout: Shared<PostProcessLayout>, color_format: wgpu::TextureFormat) -> Self {
+        Self {
+            layout,
+            color_format,
+            counters: RenderCounters::default(),
+            bloom: None,
+            exposure: None,
+            velocity: None,
+            motion_blur: None,
+            stylistic: None,
+            stack: None,
+            upscale: None,
+            pixel_perfect: None,
+        }
+    }
+
+    /// Creates an empty [PostProcessor] on the same layout, whose pipelines can be
+    /// merged back into this one with [PostProcessor::merge].
+    pub(crate) fn sharing_layout(&self) -> Self {
+        Self::with_layout(Shared::clone(&self.layout), self.color_format)
+    }
+
+    /// Takes the pipelines of a [PostProcessor] made with
+    /// [PostProcessor::sharing_layout] that this one doesn't have yet.
+    pub(crate) fn merge(&mut self, other: Self) {
+        self.bloom = self.bloom.take().or(other.bloom);
+        self.exposure = self.exposure.take().or(other.exposure);
+        self.velocity = self.velocity.take().or(other.velocity);
+        self.motion_blur = self.motion_blur.take().or(other.motion_blur);
+        self.stylistic = self.stylistic.take().or(other.stylistic);
+        self.stack = self.stack.take().or(other.stack);
+        self.upscale = self.upscale.take().or(other.upscale);
+        self.pixel_perfect = self.pixel_perfect.take().or(other.pixel_perfect);
+    }
+
+    /// Creates a pipeline that runs the fragment `entry_point` of an effect's shader
+    /// over the whole target.
+    pub(crate) fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        shader: &wgpu::ShaderModule,
+        entry_point: &str,
+        format: wgpu::TextureFormat,
+        blend: Option<wgpu::BlendState>,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&self.layout.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }),
+        )
+    }
+
+    /// Encodes a fullscreen pass.
+    pub(crate) fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pass: FullscreenPass,
+    ) {
+        // Uniform buffers can't be empty.
+        let mut uniforms = pass.uniforms.to_vec();
+        uniforms.resize(wgpu::util::align_to(uniforms.len().max(16), 16), 0);
+        let uniform_buffer = device.create_buffer_init(
+            &(wgpu::util::BufferInitDescriptor {
+                label: Some("clockwork post process uniform buffer"),
+                contents: &uniforms,
+                usage: wgpu::BufferUsages::UNIFORM,
+            }),
+        );
+
+        let bind_group = device.create_bind_group(
+            &(wgpu::BindGroupDescriptor {
+                label: Some("clockwork post process bind group"),
+                layout: &self.layout.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Sampler(&self.layout.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(pass.source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(
+                            pass.secondary.unwrap_or(pass.source),
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            }),
+        );
+
+        let mut render_pass = encoder.begin_render_pass(
+            &(wgpu::RenderPassDescriptor {
+                label: Some(pass.label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: pass.target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: match pass.blend_constant {
+                            Some(_) => wgpu::LoadOp::Load,
+                            None => wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        },
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            }),
+        );
+        render_pass.set_pipeline(pass.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        if let Some(blend_constant) = pass.blend_constant {
+            render_pass.set_blend_constant(wgpu::Color {
+                r: blend_constant,
+                g: blend_constant,
+                b: blend_constant,
+                a: blend_constant,
+            });
+        }
+        render_pass.draw(0..3, 0..1);
+        self.counters.draw(1);
+        self.counters.upload(uniforms.len());
+    }
+}
+
+/// Creates the shader module of an effect, prepending the shared prelude.
+pub(crate) fn create_shader(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(format!("{PRELUDE}{source}").into()),
+    })
+}
+
+/// Creates a texture for effects to render to between passes.
+pub(crate) fn create_intermediate_texture(device: &wgpu::Device, size: UVec2) -> Texture {
+    Texture::create_render_target(device, size.max(UVec2::ONE), INTERMEDIATE_FORMAT)
+}
+
+impl RenderContext {
+    /// Gets the view to draw the result of an effect to, or `None` when drawing to the
+    /// surface outside of a frame.
+    pub(crate) fn post_process_target_view(
+        &self,
+        target: RenderTarget,
+    ) -> anyhow::Result<Option<&wgpu::TextureView>> {
+        match target {
+            RenderTarget::Surface => Ok(self.frame.as_ref().map(|frame| &frame.view)),
+            RenderTarget::Texture(texture_id) => self
+                .textures
+                .get(texture_id)
+                .map(|texture| Some(&texture.view))
+                .ok_or_else(|| anyhow::anyhow!("no texture {texture_id:?}")),
+        }
+    }
+}
+
+/// Checks that an effect's shader is valid once the prelude is prepended.
+#[cfg(test)]
+pub(crate) fn validate_shader(source: &str) {
+    let module = naga::front::wgsl::parse_str(&format!("{PRELUDE}{source}")).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prelude_validates() {
+        validate_shader("");
+    }
+}
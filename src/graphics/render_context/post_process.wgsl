@@ -0,0 +1,24 @@
+// Shared by every post process shader, which is appended after this.
+
+struct FullscreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var source_sampler: sampler;
+@group(0) @binding(1)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var secondary_texture: texture_2d<f32>;
+
+// Draws a single triangle covering the whole target.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    var out: FullscreenOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
@@ -0,0 +1,441 @@
+use anyhow::Result;
+use bytemuck::Zeroable;
+use glam::Vec2;
+
+use crate::{graphics::texture::Texture, util::repository::ResourceId};
+
+use super::{
+    bloom::{Bloom, BloomPipelines, BloomSettings},
+    post_process::{self, FullscreenPass, PostProcessor, INTERMEDIATE_FORMAT},
+    stylistic::{self, StylisticEffect, StylisticPipelines},
+    RenderContext, RenderTarget,
+};
+
+const SHADER_SOURCE: &str = include_str!("post_process_stack.wgsl");
+
+/// Curve a [Tonemapping] maps colors into the displayable range with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TonemapOperator {
+    /// Clamps colors, so anything brighter than white is cut off.
+    Clamp,
+    /// Compresses bright colors smoothly, never quite reaching white.
+    Reinhard,
+    /// Filmic curve with a gentle toe and shoulder.
+    #[default]
+    Aces,
+}
+
+/// Settings of a tonemapping [PostEffect].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tonemapping {
+    pub operator: TonemapOperator,
+    /// Multiplier applied to colors before they are mapped.
+    pub exposure: f32,
+    /// Gamma adjustment applied after mapping, where values above 1 brighten the
+    /// midtones. The surface already encodes colors for display, so 1 leaves them
+    /// unchanged.
+    pub gamma: f32,
+}
+
+/// Effect in a [PostProcessStack].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PostEffect {
+    /// Makes bright parts of the image glow, see [RenderContext::apply_bloom].
+    Bloom(BloomSettings),
+    /// Maps colors into the displayable range, best placed after effects that
+    /// brighten the image.
+    Tonemap(Tonemapping),
+    /// Gives the image a particular look, see [RenderContext::apply_stylistic_effects].
+    Stylistic(StylisticEffect),
+    /// Offsets the image, such as to shake the screen on impacts.
+    ScreenShake {
+        /// Offset as a fraction of the image, with x to the right and y up.
+        offset: Vec2,
+    },
+}
+
+/// Effects applied to a rendered scene one after another, in order, see
+/// [RenderContext::apply_post_process_stack].
+///
+/// Effects pass their results to each other in a high precision format, so effects
+/// brightening the scene past white, such as bloom, keep their range until it's
+/// tonemapped.
+pub struct PostProcessStack {
+    /// Effects to apply, in the order they are applied, which can be changed at any
+    /// time to enable, disable or reorder them.
+    pub effects: Vec<PostEffect>,
+    /// Textures effects draw to before the last one draws to the target.
+    intermediates: Vec<Texture>,
+    /// Chain of textures shared by the bloom effects, as many as the first one's
+    /// [BloomSettings::passes].
+    bloom: Bloom,
+    /// Counts up every time the stack is applied, so grain moves.
+    frame: u32,
+    /// Generation of the device the textures were created on, so they are created
+    /// again after [RenderContext::recover].
+    device_generation: u32,
+}
+
+/// Pipelines for the effects only found in a [PostProcessStack], as intermediate and
+/// output pairs.
+pub(crate) struct StackPipelines {
+    tonemap: (wgpu::RenderPipeline, wgpu::RenderPipeline),
+    screen_shake: (wgpu::RenderPipeline, wgpu::RenderPipeline),
+}
+
+/// Uniforms of the post process stack shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct StackUniforms {
+    exposure: f32,
+    gamma: f32,
+    curve: u32,
+    _padding: f32,
+    offset: [f32; 2],
+    _padding_offset: [f32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for StackUniforms {}
+unsafe impl bytemuck::Pod for StackUniforms {}
+
+impl Default for Tonemapping {
+    fn default() -> Self {
+        Self {
+            operator: TonemapOperator::default(),
+            exposure: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl Tonemapping {
+    /// Creates a new [Tonemapping] with the given curve.
+    pub fn new(operator: TonemapOperator) -> Self {
+        Self {
+            operator,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the multiplier applied to colors before they are mapped.
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Sets the gamma adjustment applied after mapping.
+    pub fn with_gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+}
+
+impl Default for PostProcessStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PostProcessStack {
+    /// Creates a new [PostProcessStack] with no effects.
+    pub fn new() -> Self {
+        Self {
+            effects: Vec::new(),
+            intermediates: Vec::new(),
+            bloom: Bloom::new(BloomSettings::default()),
+            frame: 0,
+            device_generation: 0,
+        }
+    }
+
+    /// Adds an effect, applied after the ones before it.
+    pub fn with_effect(mut self, effect: PostEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Adds bloom, applied after the effects before it.
+    pub fn with_bloom(self, settings: BloomSettings) -> Self {
+        self.with_effect(PostEffect::Bloom(settings))
+    }
+
+    /// Adds tonemapping, applied after the effects before it.
+    pub fn with_tonemapping(self, tonemapping: Tonemapping) -> Self {
+        self.with_effect(PostEffect::Tonemap(tonemapping))
+    }
+
+    /// Adds a vignette, applied after the effects before it.
+    pub fn with_vignette(self, intensity: f32, smoothness: f32) -> Self {
+        self.with_effect(PostEffect::Stylistic(StylisticEffect::vignette(
+            intensity, smoothness,
+        )))
+    }
+
+    /// Adds screen shake, applied after the effects before it, see
+    /// [PostProcessStack::set_screen_shake].
+    pub fn with_screen_shake(self) -> Self {
+        self.with_effect(PostEffect::ScreenShake { offset: Vec2::ZERO })
+    }
+
+    /// Sets the offset of every screen shake effect, such as each frame from a shaking
+    /// camera.
+    pub fn set_screen_shake(&mut self, offset: Vec2) {
+        for effect in self.effects.iter_mut() {
+            if let PostEffect::ScreenShake { offset: shake } = effect {
+                *shake = offset;
+            }
+        }
+    }
+}
+
+impl StackPipelines {
+    pub(crate) fn new(device: &wgpu::Device, post_processor: &PostProcessor) -> Self {
+        let shader = post_process::create_shader(
+            device,
+            "clockwork post process stack shader",
+            SHADER_SOURCE,
+        );
+        let pipelines = |entry_point| {
+            let pipeline = |format| {
+                post_processor.create_pipeline(
+                    device,
+                    "clockwork post process stack pipeline",
+                    &shader,
+                    entry_point,
+                    format,
+                    None,
+                )
+            };
+            (
+                pipeline(INTERMEDIATE_FORMAT),
+                pipeline(post_processor.color_format),
+            )
+        };
+
+        Self {
+            tonemap: pipelines("fs_tonemap"),
+            screen_shake: pipelines("fs_screen_shake"),
+        }
+    }
+}
+
+/// Gets the uniforms of an effect in the stack's own shader.
+fn stack_uniforms(effect: &PostEffect) -> StackUniforms {
+    let mut uniforms = StackUniforms::zeroed();
+    match *effect {
+        PostEffect::Tonemap(tonemapping) => {
+            uniforms.exposure = tonemapping.exposure;
+            uniforms.gamma = tonemapping.gamma;
+            uniforms.curve = match tonemapping.operator {
+                TonemapOperator::Clamp => 0,
+                TonemapOperator::Reinhard => 1,
+                TonemapOperator::Aces => 2,
+            };
+        }
+        // Uv coordinates go down the image.
+        PostEffect::ScreenShake { offset } => uniforms.offset = [offset.x, -offset.y],
+        PostEffect::Bloom(_) | PostEffect::Stylistic(_) => {}
+    }
+    uniforms
+}
+
+impl RenderContext {
+    /// Draws `source` to `target` with each effect of the stack applied in order.
+    ///
+    /// Like [RenderContext::apply_bloom], render the scene to a texture first. With no
+    /// effects the source is drawn unchanged.
+    ///
+    /// Returns an error if the target is the source, or if the source is too small for
+    /// bloom.
+    pub fn apply_post_process_stack(
+        &mut self,
+        stack: &mut PostProcessStack,
+        source: ResourceId<Texture>,
+        target: RenderTarget,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            target != RenderTarget::Texture(source),
+            "a post process stack can't draw to its own source {source:?}"
+        );
+        let source_size = self
+            .textures
+            .get(source)
+            .ok_or_else(|| anyhow::anyhow!("no texture {source:?}"))?
+            .size;
+
+        let intermediates = stack.effects.len().saturating_sub(1).min(2);
+        if stack.device_generation != self.device_generation {
+            stack.intermediates.clear();
+            stack.device_generation = self.device_generation;
+        }
+        if stack.intermediates.len() != intermediates
+            || stack
+                .intermediates
+                .first()
+                .is_some_and(|texture| texture.size != source_size)
+        {
+            stack.intermediates = (0..intermediates)
+                .map(|_| post_process::create_intermediate_texture(&self.device, source_size))
+                .collect();
+        }
+        if let Some(settings) = stack.effects.iter().find_map(|effect| match effect {
+            PostEffect::Bloom(settings) => Some(*settings),
+            _ => None,
+        }) {
+            stack.bloom.settings.passes = settings.passes;
+            stack
+                .bloom
+                .prepare(&self.device, self.device_generation, source_size)?;
+        }
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(device, color_format));
+        if post_processor.bloom.is_none() {
+            post_processor.bloom = Some(BloomPipelines::new(device, post_processor));
+        }
+        if post_processor.stylistic.is_none() {
+            post_processor.stylistic = Some(StylisticPipelines::new(device, post_processor));
+        }
+        if post_processor.stack.is_none() {
+            post_processor.stack = Some(StackPipelines::new(device, post_processor));
+        }
+
+        let Some(target_view) = self.post_process_target_view(target)? else {
+            return Ok(());
+        };
+        let post_processor = self.post_processor.as_ref().expect("created above");
+        let stylistic_pipelines = post_processor.stylistic.as_ref().expect("created above");
+        let stack_pipelines = post_processor.stack.as_ref().expect("created above");
+
+        stack.frame = stack.frame.wrapping_add(1);
+        let seed = (stack.frame % 1024) as f32;
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork post process stack encoder"),
+            }),
+        );
+
+        let mut pass_source = &self.textures[source].view;
+        if stack.effects.is_empty() {
+            post_processor.encode(
+                &self.device,
+                &mut encoder,
+                FullscreenPass {
+                    label: "clockwork post process stack pass",
+                    pipeline: &stylistic_pipelines.output[stylistic::COPY_INDEX],
+                    source: pass_source,
+                    secondary: None,
+                    uniforms: &[],
+                    target: target_view,
+                    blend_constant: None,
+                },
+            );
+        }
+        for (effect, pass_target) in stack
+            .effects
+            .iter()
+            .zip(stylistic::pass_targets(stack.effects.len()))
+        {
+            let output = pass_target.is_none();
+            let view = match pass_target {
+                Some(intermediate) => &stack.intermediates[intermediate].view,
+                None => target_view,
+            };
+            let pair = |(intermediate, output_pipeline)| match output {
+                true => output_pipeline,
+                false => intermediate,
+            };
+            let (pipeline, uniforms) = match effect {
+                PostEffect::Bloom(settings) => {
+                    stack.bloom.settings = *settings;
+                    stack.bloom.encode(
+                        &self.device,
+                        post_processor,
+                        &mut encoder,
+                        pass_source,
+                        view,
+                        output,
+                    );
+                    pass_source = view;
+                    continue;
+                }
+                PostEffect::Stylistic(stylistic_effect) => {
+                    let index = stylistic_effect.index();
+                    (
+                        pair((
+                            &stylistic_pipelines.intermediate[index],
+                            &stylistic_pipelines.output[index],
+                        )),
+                        bytemuck::bytes_of(&stylistic_effect.uniforms(seed)).to_vec(),
+                    )
+                }
+                PostEffect::Tonemap(_) => (
+                    pair((&stack_pipelines.tonemap.0, &stack_pipelines.tonemap.1)),
+                    bytemuck::bytes_of(&stack_uniforms(effect)).to_vec(),
+                ),
+                PostEffect::ScreenShake { .. } => (
+                    pair((
+                        &stack_pipelines.screen_shake.0,
+                        &stack_pipelines.screen_shake.1,
+                    )),
+                    bytemuck::bytes_of(&stack_uniforms(effect)).to_vec(),
+                ),
+            };
+            post_processor.encode(
+                &self.device,
+                &mut encoder,
+                FullscreenPass {
+                    label: "clockwork post process stack pass",
+                    pipeline,
+                    source: pass_source,
+                    secondary: None,
+                    uniforms: &uniforms,
+                    target: view,
+                    blend_constant: None,
+                },
+            );
+            pass_source = view;
+        }
+
+        self.submit(encoder);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_order_and_shake() {
+        let mut stack = PostProcessStack::new()
+            .with_bloom(BloomSettings::default())
+            .with_screen_shake()
+            .with_tonemapping(Tonemapping::new(TonemapOperator::Reinhard))
+            .with_vignette(0.5, 0.5);
+        stack.set_screen_shake(Vec2::new(0.1, 0.2));
+
+        assert!(matches!(stack.effects[0], PostEffect::Bloom(_)));
+        assert_eq!(
+            stack.effects[1],
+            PostEffect::ScreenShake {
+                offset: Vec2::new(0.1, 0.2)
+            }
+        );
+        assert_eq!(stack_uniforms(&stack.effects[1]).offset, [0.1, -0.2]);
+        assert_eq!(stack_uniforms(&stack.effects[2]).curve, 1);
+        assert!(matches!(
+            stack.effects[3],
+            PostEffect::Stylistic(StylisticEffect::Vignette { .. })
+        ));
+    }
+
+    #[test]
+    fn test_shader_validates() {
+        post_process::validate_shader(SHADER_SOURCE);
+    }
+}
@@ -0,0 +1,45 @@
+struct StackUniforms {
+    exposure: f32,
+    gamma: f32,
+    // 0 for clamping, 1 for Reinhard and 2 for ACES.
+    curve: u32,
+    _padding: f32,
+    // Screen shake offset in uv space.
+    offset: vec2<f32>,
+    _padding_offset: vec2<f32>,
+}
+@group(0) @binding(3)
+var<uniform> stack: StackUniforms;
+
+// Fit of the ACES filmic curve by Krzysztof Narkowicz.
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let mapped = (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+    return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Maps colors into the displayable range, then applies the gamma adjustment.
+@fragment
+fn fs_tonemap(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, in.uv);
+    let exposed = max(color.rgb * stack.exposure, vec3<f32>(0.0));
+    var mapped: vec3<f32>;
+    switch stack.curve {
+        case 1u: {
+            mapped = exposed / (exposed + 1.0);
+        }
+        case 2u: {
+            mapped = aces(exposed);
+        }
+        default: {
+            mapped = min(exposed, vec3<f32>(1.0));
+        }
+    }
+    return vec4<f32>(pow(mapped, vec3<f32>(1.0 / max(stack.gamma, 0.0001))), color.a);
+}
+
+// Offsets the image, stretching its edges into the gap left behind.
+@fragment
+fn fs_screen_shake(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let uv = clamp(in.uv - stack.offset, vec2<f32>(0.0), vec2<f32>(1.0));
+    return textureSample(source_texture, source_sampler, uv);
+}
@@ -0,0 +1,345 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use anyhow::Result;
+use glam::UVec2;
+
+use crate::{
+    graphics::texture::{self, Texture},
+    util::repository::ResourceId,
+};
+
+use super::{gpu, RenderContext};
+
+/// Pixels read back from a texture with [RenderContext::read_texture].
+#[derive(Clone, Debug)]
+pub struct TextureReadback {
+    /// Size of the texture in pixels.
+    pub size: UVec2,
+    /// Pixels of the texture as RGBA8, row by row from the top left. Colors of HDR
+    /// render targets are clamped and sRGB encoded, like those of the other formats.
+    pub rgba: Vec<u8>,
+}
+
+/// How texels read back from a render target are converted to RGBA8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TexelConversion {
+    Rgba8,
+    Bgra8,
+    /// Linear 16 bit float color, as with [super::OutputFormat::Hdr].
+    Rgba16Float,
+}
+
+/// Handle to data being read back from the gpu, which arrives a frame or more later.
+///
+/// Either check on it each frame with [Readback::try_take], or `.await` it.
+pub struct Readback<T> {
+    shared: Arc<Mutex<ReadbackState<T>>>,
+}
+
+struct ReadbackState<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Completes a [Readback] with the raw bytes read from the gpu.
+type FinishReadback = Box<dyn FnOnce(Result<Vec<u8>>) + Send>;
+
+/// Readback waiting on its staging buffer to be mapped.
+pub(crate) struct PendingReadback {
+    buffer: wgpu::Buffer,
+    mapped: Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+    finish: FinishReadback,
+}
+
+impl<T> Readback<T> {
+    /// Takes the result if it has arrived. Returns `None` while it's still pending and
+    /// after it has been taken.
+    pub fn try_take(&self) -> Option<Result<T>> {
+        self.shared.lock().unwrap().result.take()
+    }
+
+    /// Checks if the result has arrived and not been taken yet.
+    pub fn is_ready(&self) -> bool {
+        self.shared.lock().unwrap().result.is_some()
+    }
+}
+
+impl<T> Future for Readback<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Creates a readback along with the function that completes it.
+fn readback<T: Send + 'static>(
+    convert: impl FnOnce(Vec<u8>) -> T + Send + 'static,
+) -> (Readback<T>, FinishReadback) {
+    let shared = Arc::new(Mutex::new(ReadbackState {
+        result: None,
+        waker: None,
+    }));
+
+    let finish_shared = shared.clone();
+    let finish = Box::new(move |bytes: Result<Vec<u8>>| {
+        let mut state = finish_shared.lock().unwrap();
+        state.result = Some(bytes.map(convert));
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+
+    (Readback { shared }, finish)
+}
+
+impl TexelConversion {
+    /// Returns an error for formats that can't be converted to RGBA8.
+    fn new(format: wgpu::TextureFormat) -> Result<Self> {
+        match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {
+                Ok(Self::Rgba8)
+            }
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                Ok(Self::Bgra8)
+            }
+            wgpu::TextureFormat::Rgba16Float => Ok(Self::Rgba16Float),
+            format => anyhow::bail!("textures in {format:?} can't be read back"),
+        }
+    }
+
+    fn convert(self, mut texels: Vec<u8>) -> Vec<u8> {
+        match self {
+            Self::Rgba8 => texels,
+            Self::Bgra8 => {
+                texels
+                    .chunks_exact_mut(4)
+                    .for_each(|pixel| pixel.swap(0, 2));
+                texels
+            }
+            Self::Rgba16Float => texels
+                .chunks_exact(8)
+                .flat_map(|texel| {
+                    let channel = |index: usize| {
+                        f16_to_f32(u16::from_le_bytes([texel[index * 2], texel[index * 2 + 1]]))
+                            .clamp(0.0, 1.0)
+                    };
+                    let encode =
+                        |linear: f32| (texture::linear_to_srgb(linear) * 255.0).round() as u8;
+                    [
+                        encode(channel(0)),
+                        encode(channel(1)),
+                        encode(channel(2)),
+                        (channel(3) * 255.0).round() as u8,
+                    ]
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Converts a half precision float from its bits.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+/// Removes the padding wgpu requires at the end of each row of a texture copy.
+fn unpad_rows(bytes: &[u8], unpadded_bytes_per_row: usize, padded_bytes_per_row: usize) -> Vec<u8> {
+    bytes
+        .chunks(padded_bytes_per_row)
+        .flat_map(|row| &row[..unpadded_bytes_per_row])
+        .copied()
+        .collect()
+}
+
+impl PendingReadback {
+    /// Fails the readback, as its buffer belongs to a device that was lost.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn abandon(self) {
+        (self.finish)(Err(anyhow::anyhow!(
+            "the graphics device was lost before the readback finished"
+        )));
+    }
+}
+
+impl RenderContext {
+    /// Starts reading back the pixels of a texture without blocking, such as for
+    /// screenshots or picking.
+    ///
+    /// The texture must be a render target from [RenderContext::create_render_target].
+    ///
+    /// Returns an error if it isn't, or if its format can't be converted to RGBA8.
+    pub fn read_texture(
+        &mut self,
+        texture_id: ResourceId<Texture>,
+    ) -> Result<Readback<TextureReadback>> {
+        let texture = self
+            .textures
+            .get(texture_id)
+            .ok_or_else(|| anyhow::anyhow!("no texture {texture_id:?}"))?;
+        anyhow::ensure!(
+            self.render_target_depth_textures.contains_key(&texture_id),
+            "texture {texture_id:?} is not a render target"
+        );
+
+        let size = texture.size;
+        let conversion = TexelConversion::new(texture.format)?;
+        let texel_size = texture.format.block_size(None).ok_or_else(|| {
+            anyhow::anyhow!("textures in {:?} can't be read back", texture.format)
+        })?;
+        let unpadded_bytes_per_row = size.x * texel_size;
+        let padded_bytes_per_row =
+            wgpu::util::align_to(unpadded_bytes_per_row, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = self.create_staging_buffer((padded_bytes_per_row * size.y) as u64);
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork readback encoder"),
+            }),
+        );
+        encoder.copy_texture_to_buffer(
+            texture.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(size.y),
+                },
+            },
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+        gpu::submit(&self.queue, encoder.finish(), &self.device_lost);
+
+        let (readback, finish) = readback(move |bytes| {
+            let texels = unpad_rows(
+                &bytes,
+                unpadded_bytes_per_row as usize,
+                padded_bytes_per_row as usize,
+            );
+            TextureReadback {
+                size,
+                rgba: conversion.convert(texels),
+            }
+        });
+        self.map_staging_buffer(buffer, finish);
+        Ok(readback)
+    }
+
+    /// Completes any readbacks whose data has arrived, without waiting on the gpu.
+    ///
+    /// The engine calls this once per update.
+    pub fn poll_readbacks(&mut self) {
+        if self.pending_readbacks.is_empty() {
+            return;
+        }
+        self.device.poll(wgpu::Maintain::Poll);
+
+        let pending = std::mem::take(&mut self.pending_readbacks);
+        for readback in pending {
+            let mapped = readback.mapped.lock().unwrap().take();
+            match mapped {
+                None => self.pending_readbacks.push(readback),
+                Some(Ok(())) => {
+                    let bytes = readback.buffer.slice(..).get_mapped_range().to_vec();
+                    readback.buffer.unmap();
+                    (readback.finish)(Ok(bytes));
+                }
+                Some(Err(error)) => (readback.finish)(Err(error.into())),
+            }
+        }
+    }
+
+    fn create_staging_buffer(&self, size: wgpu::BufferAddress) -> wgpu::Buffer {
+        self.device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some("clockwork readback buffer"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+        )
+    }
+
+    fn map_staging_buffer(&mut self, buffer: wgpu::Buffer, finish: FinishReadback) {
+        let mapped = Arc::new(Mutex::new(None));
+        let callback_mapped = mapped.clone();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                *callback_mapped.lock().unwrap() = Some(result);
+            });
+
+        self.pending_readbacks.push(PendingReadback {
+            buffer,
+            mapped,
+            finish,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpad_rows() {
+        let bytes = [1, 2, 0, 0, 3, 4, 0, 0];
+        assert_eq!(unpad_rows(&bytes, 2, 4), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_hdr_readback_converts_to_rgba8() {
+        // A 1x2 Rgba16Float target holds 8 byte texels, in rows padded to 256 bytes.
+        let format = wgpu::TextureFormat::Rgba16Float;
+        let texel_size = format.block_size(None).unwrap() as usize;
+        assert_eq!(texel_size, 8);
+
+        // Opaque white brighter than the display shows, and half transparent black.
+        let texels: [[u16; 4]; 2] = [[0x4000, 0x3c00, 0x3c00, 0x3c00], [0, 0, 0, 0x3800]];
+        let mut bytes = vec![0; 2 * 256];
+        for (row, texel) in texels.iter().enumerate() {
+            bytes[row * 256..row * 256 + texel_size].copy_from_slice(bytemuck::cast_slice(texel));
+        }
+
+        let texels = unpad_rows(&bytes, texel_size, 256);
+        let rgba = TexelConversion::new(format).unwrap().convert(texels);
+        assert_eq!(rgba, vec![255, 255, 255, 255, 0, 0, 0, 128]);
+
+        assert!(TexelConversion::new(wgpu::TextureFormat::Rgba32Float).is_err());
+    }
+
+    #[test]
+    fn test_readback_completes() {
+        let (readback, finish) = readback(|bytes| bytes.len());
+        assert!(readback.try_take().is_none());
+
+        finish(Ok(vec![0; 3]));
+        assert!(readback.is_ready());
+        assert_eq!(pollster::block_on(readback).unwrap(), 3);
+    }
+}
@@ -0,0 +1,121 @@
+//! Recovery from a lost device, such as after the graphics driver restarts, by opening
+//! a new device and uploading every resource to it again from the copies kept on the
+//! cpu.
+
+use glam::UVec2;
+use pollster::block_on;
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+
+use crate::error::ClockworkError;
+
+use super::{gpu, AdapterSelection, RenderContext, Texture};
+
+impl RenderContext {
+    /// Opens a new device on the same adapter once [RenderContext::is_device_lost],
+    /// and uploads every resource to it again under the same ids, so the ids the
+    /// application holds keep working.
+    ///
+    /// Meshes, textures, texture arrays, environment maps loaded from images,
+    /// materials and lighting come back as they were. Render targets and captured
+    /// environment maps come back cleared, and readbacks in flight fail. Pipelines
+    /// created on demand or still warming up are compiled again when next used, and
+    /// effects holding their own textures, such as [super::Bloom], create them again
+    /// the next time they are applied.
+    ///
+    /// Returns an error if no device can be opened, such as when the adapter was
+    /// unplugged, in which case the context stays lost.
+    pub(crate) fn recover<Window: HasRawWindowHandle + HasRawDisplayHandle>(
+        &mut self,
+        window: &Window,
+    ) -> Result<(), ClockworkError> {
+        let adapter = self.adapter.get_info().name;
+        let size = match self.minimized {
+            true => UVec2::ZERO,
+            false => UVec2::new(self.surface_config.width, self.surface_config.height),
+        };
+        // A window only has one surface at a time.
+        self.frame = None;
+        let suspended = self.surface.take().is_none();
+
+        let mut context = block_on(Self::new_async(
+            window,
+            size.x,
+            size.y,
+            self.vsync(),
+            self.output_format(),
+            |_| AdapterSelection::Named(adapter.clone()),
+        ))?;
+        if suspended {
+            context.suspend();
+        }
+        context.surface_config.present_mode = self.surface_config.present_mode;
+        context.configure_surface();
+        context.device_generation = self.device_generation + 1;
+        context.set_max_frames_in_flight(self.max_frames_in_flight());
+        context.errors.lock().unwrap().extend(self.take_errors());
+        let mut old = std::mem::replace(self, context);
+
+        // -- MESHES --
+        self.mesh_pool = old.mesh_pool.restore(&gpu::WgpuGpu::new(
+            &self.device,
+            &self.queue,
+            &self.device_lost,
+        ));
+        self.meshes = old.meshes;
+        self.strong_meshes = old.strong_meshes;
+        self.mesh_labels = old.mesh_labels;
+        self.mesh_names = old.mesh_names;
+        self.restore_skinned_meshes(old.skinned_meshes);
+
+        // -- TEXTURES --
+        for (_, texture) in old.textures.iter_mut() {
+            *texture = texture.restore(&self.device, &self.queue);
+        }
+        self.textures = old.textures;
+        self.strong_textures = old.strong_textures;
+        self.texture_names = old.texture_names;
+        self.render_target_depth_textures = old
+            .render_target_depth_textures
+            .into_keys()
+            .filter_map(|texture_id| {
+                let size = self.textures.get(texture_id)?.size;
+                Some((
+                    texture_id,
+                    Texture::create_depth_texture(&self.device, size),
+                ))
+            })
+            .collect();
+        for (_, texture_array) in old.texture_arrays.iter_mut() {
+            *texture_array = texture_array.restore(&self.device, &self.queue);
+        }
+        self.texture_arrays = old.texture_arrays;
+
+        // -- RENDER PIPELINES --
+        for (_, material_pipeline) in old.material_pipelines.iter_mut() {
+            *material_pipeline = self.restore_material(material_pipeline);
+        }
+        self.material_pipelines = old.material_pipelines;
+        self.restore_environment_maps(old.environment_maps, old.environment);
+        self.painter = old.painter;
+
+        self.set_lighting(old.lighting.as_ref());
+        self.light_heatmap = old.light_heatmap;
+        self.debug_view = old.debug_view;
+        self.multi_draw_indirect = old.multi_draw_indirect;
+        self.frustum_culling = old.frustum_culling;
+        self.debug_draw = old.debug_draw;
+        self.debug_groups = old.debug_groups;
+        self.counters = old.counters;
+        self.frame_counts = old.frame_counts;
+        self.render_allocs = old.render_allocs;
+        self.frame_index = old.frame_index;
+        for readback in old.pending_readbacks {
+            readback.abandon();
+        }
+        #[cfg(feature = "ui")]
+        self.restore_ui(old.ui);
+
+        log::info!("recovered from losing the graphics device on {adapter}");
+        Ok(())
+    }
+}
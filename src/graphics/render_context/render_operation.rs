@@ -1,10 +1,16 @@
-use glam::{vec4, Mat4, Vec4};
+use anyhow::Result;
+use glam::{vec4, Mat3, Mat4, Quat, Vec3, Vec4};
 
 use crate::{
     graphics::{texture::Texture, Mesh},
-    util::repository::ResourceId,
+    util::{
+        repository::ResourceId,
+        sprite::{Sprite, SpritePlacement},
+    },
 };
 
+use super::{texture_slots::TextureSlots, EnvironmentMap, MaterialPipeline};
+
 /// Structure to represent a rendering operation that can be executed by a [Context].
 #[derive(Clone, Copy)]
 pub struct RenderOperation {
@@ -16,12 +22,46 @@ pub struct RenderOperation {
 
     /// Material to use with the mesh.
     pub material: Material,
+
+    /// Layers the operation is on, so passes can skip it with
+    /// [super::RenderPassDescriptor::with_layers].
+    pub layers: RenderLayers,
+
+    /// Whether the operation is see-through. Transparent operations are drawn after
+    /// opaque ones, from back to front, without writing depth so they don't hide what's
+    /// behind them.
+    pub transparent: bool,
+
+    /// Layer the operation is drawn on. Every sort layer is drawn over all lower ones,
+    /// while operations on the same sort layer keep the order they were given in, so 2D
+    /// scenes can order backgrounds, entities and UI without relying on depth.
+    pub sort_layer: i32,
+}
+
+/// Set of up to 32 layers, used to choose which [RenderOperation]s a pass draws, such
+/// as keeping UI out of a minimap camera.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderLayers(pub u32);
+
+/// How an operation is turned to face the camera, such as for sprites placed in a 3D
+/// world, see [RenderOperation::with_billboard].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Billboard {
+    /// Faces the camera fully, such as for particles and floating icons.
+    Spherical,
+    /// Only turns around the world's up axis so it stays upright, such as for
+    /// characters and trees in 2.5D games.
+    Cylindrical,
 }
 
 /// Types of materials that can be used.
 #[derive(Clone, Copy)]
 pub enum Material {
     BasicDiffuse(BasicDiffuseMaterial),
+    Custom(CustomMaterial),
+    Reflective(ReflectiveMaterial),
+    Pbr(PbrMaterial),
+    Mapped(MappedMaterial),
 }
 
 /// Material to apply a texture multiplied by a solid color to a mesh.
@@ -33,6 +73,145 @@ pub struct BasicDiffuseMaterial {
     pub texture_parameters: Option<TextureParameters>,
 }
 
+/// Material rendered by a shader registered with [super::RenderContext::register_material].
+#[derive(Clone, Copy)]
+pub struct CustomMaterial {
+    /// Pipeline to render with.
+    pub pipeline_id: ResourceId<MaterialPipeline>,
+    /// Color passed to the shader.
+    pub color: Vec4,
+    /// Texture passed to the shader.
+    pub texture_parameters: Option<TextureParameters>,
+    /// Additional textures passed to the shader, such as for effects that sample a
+    /// mask.
+    pub maps: TextureMaps,
+    /// Values passed to the shader for just this operation, such as how dissolved it
+    /// is or the color it's flashing.
+    pub data: MaterialData,
+}
+
+/// Size in bytes of a [MaterialData].
+pub const MATERIAL_DATA_SIZE: usize = 64;
+
+/// Small block of bytes passed to a [CustomMaterial]'s shader for a single operation,
+/// read as `data: array<vec4<f32>, 4>` at the end of the per-operation uniforms.
+///
+/// Unlike [super::RenderContext::set_material_uniforms], which is shared by every
+/// operation using the material, each operation has its own data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MaterialData(pub(crate) [u8; MATERIAL_DATA_SIZE]);
+
+/// Material that mirrors an [EnvironmentMap] over a texture multiplied by a color, such
+/// as for metal or glossy paint.
+#[derive(Clone, Copy)]
+pub struct ReflectiveMaterial {
+    /// Color to apply.
+    pub color: Vec4,
+    /// Texture to apply.
+    pub texture_parameters: Option<TextureParameters>,
+    /// Surroundings to reflect.
+    pub environment_map_id: ResourceId<EnvironmentMap>,
+    /// How much of the environment is reflected when looking straight at the surface,
+    /// from 0 to 1. More is reflected at glancing angles.
+    pub reflectivity: f32,
+}
+
+/// Material like [BasicDiffuseMaterial] with normal, emissive and mask textures, lit
+/// when lighting is enabled with [super::RenderContext::set_lighting].
+#[derive(Clone, Copy)]
+pub struct MappedMaterial {
+    /// Color to apply.
+    pub color: Vec4,
+    /// Texture to apply.
+    pub texture_parameters: Option<TextureParameters>,
+    /// Normal, emissive and mask textures, sampled with the same uv window as the
+    /// texture.
+    pub maps: TextureMaps,
+    /// Light given off by the surface, multiplied with the emissive map.
+    pub emissive: Vec3,
+}
+
+impl MaterialData {
+    /// Creates [MaterialData] starting with `bytes`, with the rest zeroed.
+    ///
+    /// Returns an error if there are more than [MATERIAL_DATA_SIZE] bytes.
+    pub fn new(bytes: &[u8]) -> Result<Self> {
+        anyhow::ensure!(
+            bytes.len() <= MATERIAL_DATA_SIZE,
+            "material data is {} bytes, but can be at most {MATERIAL_DATA_SIZE}",
+            bytes.len()
+        );
+        let mut data = [0; MATERIAL_DATA_SIZE];
+        data[..bytes.len()].copy_from_slice(bytes);
+        Ok(Self(data))
+    }
+
+    /// Creates [MaterialData] from up to four vectors, such as a dissolve amount and a
+    /// flash color, with the rest zeroed.
+    pub fn from_vec4s(values: &[Vec4]) -> Self {
+        let mut data = [0; MATERIAL_DATA_SIZE];
+        for (chunk, value) in data.chunks_exact_mut(16).zip(values) {
+            chunk.copy_from_slice(bytemuck::cast_slice(&value.to_array()));
+        }
+        Self(data)
+    }
+}
+
+impl Default for MaterialData {
+    fn default() -> Self {
+        Self([0; MATERIAL_DATA_SIZE])
+    }
+}
+
+/// Textures used alongside a material's main texture. Color textures are loaded as
+/// usual, while normal maps and masks hold data and are loaded with
+/// [super::RenderContext::load_data_texture].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextureMaps {
+    /// Tangent space normal map, with +Y pointing up the texture.
+    pub normal_texture: Option<ResourceId<Texture>>,
+    /// Texture multiplied with the light the surface gives off.
+    pub emissive_texture: Option<ResourceId<Texture>>,
+    /// Texture with how opaque the surface is in its red channel, such as for cutouts
+    /// and dissolve effects.
+    pub mask_texture: Option<ResourceId<Texture>>,
+}
+
+/// Physically based material following glTF's metallic-roughness model, lit by the
+/// scene's lights and, if it has one, an [EnvironmentMap].
+///
+/// Each factor is multiplied with its texture, and missing textures leave their factor
+/// as is. Color textures are loaded as usual, while metallic-roughness, normal and
+/// occlusion maps hold data and are loaded with
+/// [super::RenderContext::load_data_texture].
+#[derive(Clone, Copy, Debug)]
+pub struct PbrMaterial {
+    /// Color of the surface, with alpha for transparent materials.
+    pub base_color: Vec4,
+    /// Texture multiplied with the base color.
+    pub base_color_texture: Option<ResourceId<Texture>>,
+    /// How metallic the surface is, from 0 for dielectrics to 1 for metals.
+    pub metallic: f32,
+    /// How rough the surface is, from 0 for a mirror finish to 1 for a matte one.
+    pub roughness: f32,
+    /// Texture with roughness in its green channel and metallic in its blue channel.
+    pub metallic_roughness_texture: Option<ResourceId<Texture>>,
+    /// Tangent space normal map, with +Y pointing up the texture.
+    pub normal_texture: Option<ResourceId<Texture>>,
+    /// How strongly the normal map bends the surface.
+    pub normal_scale: f32,
+    /// Texture with how much ambient light reaches the surface in its red channel.
+    pub occlusion_texture: Option<ResourceId<Texture>>,
+    /// How much the occlusion texture darkens ambient light, from 0 to 1.
+    pub occlusion_strength: f32,
+    /// Light given off by the surface.
+    pub emissive: Vec3,
+    /// Texture multiplied with the emitted light.
+    pub emissive_texture: Option<ResourceId<Texture>>,
+    /// Surroundings reflected by the surface and lighting it.
+    pub environment_map_id: Option<ResourceId<EnvironmentMap>>,
+}
+
 /// Parameters to use when applying a texture.
 #[derive(Clone, Copy)]
 pub struct TextureParameters {
@@ -43,6 +222,81 @@ pub struct TextureParameters {
     pub uv_window: Vec4,
 }
 
+impl Default for PbrMaterial {
+    /// Defaults match glTF's, a white fully metallic and fully rough surface.
+    fn default() -> Self {
+        Self {
+            base_color: Vec4::ONE,
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: Vec3::ZERO,
+            emissive_texture: None,
+            environment_map_id: None,
+        }
+    }
+}
+
+impl RenderLayers {
+    /// Every layer.
+    pub const ALL: Self = Self(u32::MAX);
+    /// No layers.
+    pub const NONE: Self = Self(0);
+    /// Layer operations are on unless set otherwise.
+    pub const DEFAULT: Self = Self::layer(0);
+
+    /// Creates a [RenderLayers] with only the given layer, from 0 to 31.
+    pub const fn layer(layer: u32) -> Self {
+        Self(1 << layer)
+    }
+
+    /// Adds the given layer.
+    pub const fn with(self, layer: u32) -> Self {
+        Self(self.0 | Self::layer(layer).0)
+    }
+
+    /// Removes the given layer.
+    pub const fn without(self, layer: u32) -> Self {
+        Self(self.0 & !Self::layer(layer).0)
+    }
+
+    /// Checks if the given layer is included.
+    pub const fn contains(self, layer: u32) -> bool {
+        self.intersects(Self::layer(layer))
+    }
+
+    /// Checks if any layer is in both sets.
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Billboard {
+    /// Gets the rotation turning something facing +z toward a camera with the given view
+    /// matrix, parallel to the view so billboards next to each other line up.
+    pub fn rotation(self, view: Mat4) -> Quat {
+        match self {
+            Billboard::Spherical => Quat::from_mat3(&Mat3::from_mat4(view)).inverse(),
+            Billboard::Cylindrical => {
+                // Direction from the scene back toward the camera.
+                let back = view.row(2).truncate();
+                Quat::from_rotation_y(back.x.atan2(back.z))
+            }
+        }
+    }
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
 impl RenderOperation {
     /// Creates a [RenderOperation] to render a mesh with a solid color.
     pub fn colored_mesh(
@@ -57,6 +311,9 @@ impl RenderOperation {
                 color,
                 texture_parameters: None,
             }),
+            layers: RenderLayers::DEFAULT,
+            transparent: false,
+            sort_layer: 0,
         }
     }
 
@@ -73,13 +330,81 @@ impl RenderOperation {
             mesh_id,
             material: Material::BasicDiffuse(BasicDiffuseMaterial {
                 color,
-                texture_parameters: Some(TextureParameters {
-                    texture_id,
-                    uv_window: uv_window.unwrap_or_default(),
-                }),
+                texture_parameters: Some(TextureParameters::new(texture_id, uv_window)),
             }),
+            layers: RenderLayers::DEFAULT,
+            transparent: false,
+            sort_layer: 0,
+        }
+    }
+
+    /// Creates a [RenderOperation] to render a frame of a sprite with a unit quad such as
+    /// [crate::graphics::default_meshes::QUAD_MESH_DATA], flipped, rotated and scaled
+    /// as placed.
+    ///
+    /// Trimmed and rotated frames land where they would in the untrimmed sprite.
+    pub fn sprite(
+        quad_mesh_id: ResourceId<Mesh>,
+        sprite: &Sprite,
+        frame: usize,
+        placement: SpritePlacement,
+    ) -> RenderOperation {
+        let frame = sprite
+            .get_frame(frame)
+            .flipped(placement.flip_x, placement.flip_y);
+        RenderOperation::textured_mesh(
+            placement.transform(sprite.sprite_dims) * frame.quad_transform(),
+            quad_mesh_id,
+            sprite.texture,
+            Some(frame.uv_window),
+            placement.color,
+        )
+    }
+
+    /// Creates a [RenderOperation] to render a mesh with a [PbrMaterial].
+    pub fn pbr_mesh(
+        transform: Mat4,
+        mesh_id: ResourceId<Mesh>,
+        material: PbrMaterial,
+    ) -> RenderOperation {
+        RenderOperation {
+            transform,
+            mesh_id,
+            material: Material::Pbr(material),
+            layers: RenderLayers::DEFAULT,
+            transparent: false,
+            sort_layer: 0,
         }
     }
+
+    /// Turns the operation to face a camera with the given view matrix, rotating it
+    /// around its position. Sprites can instead be turned around their pivot with
+    /// [SpritePlacement::with_billboard].
+    pub fn with_billboard(mut self, billboard: Billboard, view: Mat4) -> Self {
+        let position = self.transform.w_axis.truncate();
+        self.transform = Mat4::from_rotation_translation(billboard.rotation(view), position)
+            * Mat4::from_translation(-position)
+            * self.transform;
+        self
+    }
+
+    /// Sets the layers the operation is on.
+    pub fn with_layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Sets whether the operation is see-through.
+    pub fn with_transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    /// Sets the layer the operation is drawn on.
+    pub fn with_sort_layer(mut self, sort_layer: i32) -> Self {
+        self.sort_layer = sort_layer;
+        self
+    }
 }
 
 impl Default for TextureParameters {
@@ -106,33 +431,343 @@ impl TextureParameters {
 pub(crate) struct RawRenderOperation {
     pub transform: Mat4,
     pub mesh_id: ResourceId<Mesh>,
-    pub texture_group_ids: [ResourceId<Texture>; 1],
+    pub shading: Shading,
+    pub texture_group_ids: TextureSlots,
     pub uv_windows: [Vec4; 1],
     pub colors: [Vec4; 1],
+    /// Material specific values passed to the shader.
+    pub parameters: Vec4,
+    /// Values passed to a custom material's shader.
+    pub data: MaterialData,
+    pub transparent: bool,
+    pub sort_layer: i32,
+}
+
+/// How a [RawRenderOperation] is shaded, which decides its pipeline and bind groups.
+#[derive(Clone, Copy)]
+pub(crate) enum Shading {
+    Default,
+    Mapped {
+        emissive: Vec3,
+    },
+    Custom(ResourceId<MaterialPipeline>),
+    Reflective(ResourceId<EnvironmentMap>),
+    Pbr {
+        /// Base color, metallic-roughness, normal, occlusion and emissive textures.
+        textures: PbrTextures,
+        emissive: Vec3,
+        environment_map_id: Option<ResourceId<EnvironmentMap>>,
+    },
+}
+
+/// Textures of a [PbrMaterial], in the order they are bound.
+pub(crate) type PbrTextures = [Option<ResourceId<Texture>>; 5];
+
+impl Shading {
+    /// Gets the custom material pipeline, if there is one.
+    pub fn pipeline_id(&self) -> Option<ResourceId<MaterialPipeline>> {
+        match self {
+            Shading::Custom(pipeline_id) => Some(*pipeline_id),
+            _ => None,
+        }
+    }
 }
 
 impl From<RenderOperation> for RawRenderOperation {
     fn from(value: RenderOperation) -> Self {
-        let (texture_group_ids, uv_windows, colors) = match value.material {
+        let no_maps = TextureMaps::default();
+        let (shading, color, texture_parameters, maps, parameters) = match value.material {
             Material::BasicDiffuse(BasicDiffuseMaterial {
                 color,
                 texture_parameters,
-            }) => {
-                let TextureParameters {
-                    texture_id,
-                    uv_window,
-                } = texture_parameters.unwrap_or_default();
+            }) => (
+                Shading::Default,
+                color,
+                texture_parameters,
+                no_maps,
+                Vec4::ZERO,
+            ),
+            Material::Custom(CustomMaterial {
+                pipeline_id,
+                color,
+                texture_parameters,
+                maps,
+                ..
+            }) => (
+                Shading::Custom(pipeline_id),
+                color,
+                texture_parameters,
+                maps,
+                Vec4::ZERO,
+            ),
+            Material::Mapped(MappedMaterial {
+                color,
+                texture_parameters,
+                maps,
+                emissive,
+            }) => (
+                Shading::Mapped { emissive },
+                color,
+                texture_parameters,
+                maps,
+                Vec4::ZERO,
+            ),
+            Material::Reflective(ReflectiveMaterial {
+                color,
+                texture_parameters,
+                environment_map_id,
+                reflectivity,
+            }) => (
+                Shading::Reflective(environment_map_id),
+                color,
+                texture_parameters,
+                no_maps,
+                vec4(reflectivity, 0.0, 0.0, 0.0),
+            ),
+            Material::Pbr(material) => (
+                Shading::Pbr {
+                    textures: [
+                        material.base_color_texture,
+                        material.metallic_roughness_texture,
+                        material.normal_texture,
+                        material.occlusion_texture,
+                        material.emissive_texture,
+                    ],
+                    emissive: material.emissive,
+                    environment_map_id: material.environment_map_id,
+                },
+                material.base_color,
+                None,
+                no_maps,
+                vec4(
+                    material.metallic,
+                    material.roughness,
+                    material.normal_scale,
+                    material.occlusion_strength,
+                ),
+            ),
+        };
 
-                ([texture_id], [uv_window], [color])
-            }
+        let uv_window = texture_parameters.unwrap_or_default().uv_window;
+        let texture_group_ids = [
+            texture_parameters.map(|texture_parameters| texture_parameters.texture_id),
+            maps.normal_texture,
+            maps.emissive_texture,
+            maps.mask_texture,
+        ];
+        let (uv_windows, colors) = ([uv_window], [color]);
+        let data = match value.material {
+            Material::Custom(material) => material.data,
+            _ => MaterialData::default(),
         };
 
         RawRenderOperation {
             transform: value.transform,
             mesh_id: value.mesh_id,
+            shading,
             texture_group_ids,
             uv_windows,
             colors,
+            parameters,
+            data,
+            transparent: value.transparent,
+            sort_layer: value.sort_layer,
         }
     }
 }
+
+/// Gets the depth of a transformation's origin as seen through a view projection,
+/// increasing away from the camera for both perspective and orthographic projections.
+fn view_depth(view_projection: Mat4, transform: Mat4) -> f32 {
+    let clip = view_projection * transform.w_axis;
+    match clip.w.abs() > f32::EPSILON {
+        true => clip.z / clip.w,
+        false => clip.z,
+    }
+}
+
+/// Orders operations for drawing by sort layer. Within each sort layer, opaque
+/// operations come first from front to back so hidden pixels fail the depth test early,
+/// then transparent operations from back to front so they blend over what's behind them.
+///
+/// Sorting is stable, so operations at equal depth keep the order they were given in.
+pub(crate) fn order_for_drawing(operations: &mut [RawRenderOperation], view_projection: Mat4) {
+    operations.sort_by(|a, b| {
+        a.sort_layer
+            .cmp(&b.sort_layer)
+            .then(a.transparent.cmp(&b.transparent))
+            .then_with(|| {
+                let depth_a = view_depth(view_projection, a.transform);
+                let depth_b = view_depth(view_projection, b.transform);
+                match a.transparent {
+                    true => depth_b.total_cmp(&depth_a),
+                    false => depth_a.total_cmp(&depth_b),
+                }
+            })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_render_layers() {
+        let layers = RenderLayers::layer(3).with(5);
+        assert!(layers.contains(3) && layers.contains(5));
+        assert!(!layers.contains(0));
+        assert!(!layers.without(3).contains(3));
+        assert!(layers.intersects(RenderLayers::ALL));
+        assert!(!layers.intersects(RenderLayers::DEFAULT));
+        assert!(!RenderLayers::NONE.intersects(RenderLayers::ALL));
+    }
+
+    #[test]
+    fn test_material_data() {
+        let data = MaterialData::new(&[1, 2, 3]).unwrap();
+        assert_eq!(data.0[..4], [1, 2, 3, 0]);
+        assert!(MaterialData::new(&[0; MATERIAL_DATA_SIZE + 1]).is_err());
+
+        let data = MaterialData::from_vec4s(&[Vec4::ONE, Vec4::new(0.0, 0.5, 0.0, 0.0)]);
+        let floats: &[f32] = bytemuck::cast_slice(&data.0);
+        assert_eq!(floats[..8], [1.0, 1.0, 1.0, 1.0, 0.0, 0.5, 0.0, 0.0]);
+        assert!(floats[8..].iter().all(|&value| value == 0.0));
+    }
+
+    #[test]
+    fn test_billboard() {
+        // A camera to the right of the origin, looking at it from above.
+        let camera = Mat4::look_at_rh(Vec3::new(10.0, 5.0, 0.0), Vec3::ZERO, Vec3::Y);
+        let to_camera = Vec3::new(10.0, 5.0, 0.0).normalize();
+
+        let normal = Billboard::Spherical.rotation(camera) * Vec3::Z;
+        assert!(normal.abs_diff_eq(to_camera, 1e-5));
+        let normal = Billboard::Cylindrical.rotation(camera) * Vec3::Z;
+        assert!(normal.abs_diff_eq(Vec3::X, 1e-5));
+
+        let operation = RenderOperation::colored_mesh(
+            Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0)),
+            ResourceId::new(0),
+            Vec4::ONE,
+        )
+        .with_billboard(Billboard::Cylindrical, camera);
+        assert!(operation
+            .transform
+            .transform_point3(Vec3::ZERO)
+            .abs_diff_eq(Vec3::new(1.0, 2.0, 3.0), 1e-5));
+        assert!(operation
+            .transform
+            .transform_vector3(Vec3::Z)
+            .abs_diff_eq(Vec3::X, 1e-5));
+    }
+
+    #[test]
+    fn test_texture_slots() {
+        let texture = |index| Some(ResourceId::new(index));
+        let operation = RawRenderOperation::from(RenderOperation {
+            material: Material::Mapped(MappedMaterial {
+                color: Vec4::ONE,
+                texture_parameters: Some(TextureParameters::new(ResourceId::new(1), None)),
+                maps: TextureMaps {
+                    normal_texture: texture(2),
+                    emissive_texture: None,
+                    mask_texture: texture(3),
+                },
+                emissive: Vec3::ONE,
+            }),
+            ..RenderOperation::colored_mesh(Mat4::IDENTITY, ResourceId::new(0), Vec4::ONE)
+        });
+        assert_eq!(
+            operation.texture_group_ids,
+            [texture(1), texture(2), None, texture(3)]
+        );
+
+        let untextured = RawRenderOperation::from(RenderOperation::colored_mesh(
+            Mat4::IDENTITY,
+            ResourceId::new(0),
+            Vec4::ONE,
+        ));
+        assert_eq!(untextured.texture_group_ids, [None; 4]);
+    }
+
+    #[test]
+    fn test_order_for_drawing_by_depth() {
+        // Looking down -z, so more negative z is further away.
+        let view_projection = Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0);
+        let operation = |z: f32, transparent: bool| {
+            RawRenderOperation::from(
+                RenderOperation::colored_mesh(
+                    Mat4::from_translation(Vec3::new(0.0, 0.0, z)),
+                    ResourceId::new(0),
+                    Vec4::ONE,
+                )
+                .with_transparent(transparent),
+            )
+        };
+        let mut operations = vec![
+            operation(-2.0, true),
+            operation(-5.0, false),
+            operation(-8.0, true),
+            operation(-1.0, false),
+            operation(-2.0, true),
+        ];
+        operations[4].mesh_id = ResourceId::new(1);
+
+        order_for_drawing(&mut operations, view_projection);
+        let order: Vec<(f32, bool)> = operations
+            .iter()
+            .map(|operation| (operation.transform.w_axis.z, operation.transparent))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (-1.0, false),
+                (-5.0, false),
+                (-8.0, true),
+                (-2.0, true),
+                (-2.0, true),
+            ]
+        );
+        // Equal depths keep their order.
+        assert_eq!(operations[4].mesh_id, ResourceId::new(1));
+
+        // Orthographic projections sort the same way.
+        let orthographic = Mat4::orthographic_rh(-1.0, 1.0, -1.0, 1.0, 0.1, 100.0);
+        order_for_drawing(&mut operations, orthographic);
+        assert_eq!(operations[0].transform.w_axis.z, -1.0);
+        assert_eq!(operations[2].transform.w_axis.z, -8.0);
+    }
+
+    #[test]
+    fn test_order_for_drawing_by_sort_layer() {
+        let operation = |z: f32, sort_layer: i32| {
+            RawRenderOperation::from(
+                RenderOperation::colored_mesh(
+                    Mat4::from_translation(Vec3::new(0.0, 0.0, z)),
+                    ResourceId::new(0),
+                    Vec4::ONE,
+                )
+                .with_sort_layer(sort_layer),
+            )
+        };
+        let mut operations = vec![
+            operation(0.0, 2),
+            operation(-1.0, 0),
+            operation(0.0, -1),
+            operation(-1.0, 2),
+            operation(0.0, 0),
+        ];
+
+        order_for_drawing(&mut operations, Mat4::IDENTITY);
+        let order: Vec<(i32, f32)> = operations
+            .iter()
+            .map(|operation| (operation.sort_layer, operation.transform.w_axis.z))
+            .collect();
+        assert_eq!(
+            order,
+            vec![(-1, 0.0), (0, -1.0), (0, 0.0), (2, -1.0), (2, 0.0)]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use glam::{vec4, Mat4, UVec2, Vec4};
+
+use crate::{
+    graphics::texture::Texture,
+    util::{camera::Camera, repository::ResourceId},
+};
+
+use super::{DynamicResolution, PixelPerfect, RenderLayers};
+
+/// Where a render pass draws to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderTarget {
+    /// The window's surface for the current frame.
+    Surface,
+    /// A texture created with [super::RenderContext::create_render_target].
+    Texture(ResourceId<Texture>),
+}
+
+/// Describes how a single render pass within a frame draws.
+#[derive(Clone, Copy, Debug)]
+pub struct RenderPassDescriptor {
+    /// View projection matrix used for every operation in the pass.
+    pub view_projection: Mat4,
+    /// Color to clear the target with, or `None` to draw over what is already there.
+    pub clear_color: Option<Vec4>,
+    /// Whether to clear the depth buffer before drawing.
+    pub clear_depth: bool,
+    /// Where to draw.
+    pub target: RenderTarget,
+    /// Name of the pass shown in graphics debuggers such as RenderDoc.
+    pub label: &'static str,
+    /// Layers drawn by the pass, where operations on none of them are skipped.
+    pub layers: RenderLayers,
+    /// Size of the region at the top left of the target to draw to, or `None` to draw
+    /// to all of it.
+    pub viewport: Option<UVec2>,
+}
+
+impl RenderPassDescriptor {
+    /// Default color render passes are cleared with.
+    pub const DEFAULT_CLEAR_COLOR: Vec4 = vec4(0.1, 0.2, 0.3, 1.0);
+
+    /// Creates a [RenderPassDescriptor] that clears and draws to the surface.
+    pub fn new(view_projection: Mat4) -> Self {
+        Self {
+            view_projection,
+            clear_color: Some(Self::DEFAULT_CLEAR_COLOR),
+            clear_depth: true,
+            target: RenderTarget::Surface,
+            label: "clockwork render pass",
+            layers: RenderLayers::ALL,
+            viewport: None,
+        }
+    }
+
+    /// Creates a [RenderPassDescriptor] that clears and draws to the surface from a
+    /// [Camera], drawing only the camera's layers.
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self::new(camera.get_view_projection_matrix()).with_layers(camera.layers)
+    }
+
+    /// Creates a [RenderPassDescriptor] that draws over the surface without clearing,
+    /// such as for a UI overlay.
+    pub fn overlay(view_projection: Mat4) -> Self {
+        Self {
+            view_projection,
+            clear_color: None,
+            clear_depth: true,
+            target: RenderTarget::Surface,
+            label: "clockwork overlay pass",
+            layers: RenderLayers::ALL,
+            viewport: None,
+        }
+    }
+
+    /// Creates a [RenderPassDescriptor] for [crate::graphics::WorldQuad]s, drawn over the
+    /// scene from a [Camera] after it's been rendered.
+    ///
+    /// The depth buffer is cleared so quads show through walls. Use
+    /// `.with_clear_depth(false)` to have geometry hide them instead.
+    pub fn world_ui(camera: &Camera) -> Self {
+        Self {
+            label: "clockwork world ui pass",
+            ..Self::overlay(camera.get_view_projection_matrix()).with_layers(camera.layers)
+        }
+    }
+
+    /// Sets the color to clear with, or `None` to keep the target's contents.
+    pub fn with_clear_color(mut self, clear_color: Option<Vec4>) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    /// Sets whether the depth buffer is cleared.
+    pub fn with_clear_depth(mut self, clear_depth: bool) -> Self {
+        self.clear_depth = clear_depth;
+        self
+    }
+
+    /// Sets where to draw.
+    pub fn with_target(mut self, target: RenderTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Sets the layers drawn by the pass.
+    pub fn with_layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Sets the region at the top left of the target to draw to.
+    pub fn with_viewport(mut self, viewport: Option<UVec2>) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    /// Sets the pass to draw to the part of a [DynamicResolution]'s texture used this
+    /// frame, if it's been updated.
+    pub fn with_dynamic_resolution(mut self, dynamic_resolution: &DynamicResolution) -> Self {
+        if let Some(texture_id) = dynamic_resolution.texture_id() {
+            self.target = RenderTarget::Texture(texture_id);
+            self.viewport = Some(dynamic_resolution.render_size());
+        }
+        self
+    }
+
+    /// Sets the pass to draw to a [PixelPerfect]'s texture, if it's been updated.
+    pub fn with_pixel_perfect(mut self, pixel_perfect: &PixelPerfect) -> Self {
+        if let Some(texture_id) = pixel_perfect.texture_id() {
+            self.target = RenderTarget::Texture(texture_id);
+            self.viewport = None;
+        }
+        self
+    }
+
+    /// Sets the name of the pass shown in graphics debuggers.
+    pub fn with_label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+}
@@ -2,15 +2,23 @@ struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
+    // sRGB, white for meshes without vertex colors.
+    @location(3) color: vec4<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    // Linear and premultiplied, so it blends correctly between vertices.
+    @location(3) color: vec4<f32>,
 };
 
 struct Global {
     mvp: mat4x4<f32>,
+    // Camera position with w = 1, or the direction it looks from with w = 0.
+    camera: vec4<f32>,
 }
 @group(0) @binding(0)
 var<uniform> global: Global;
@@ -18,25 +26,233 @@ var<uniform> global: Global;
 struct Local {
     transform: mat4x4<f32>,
     uv_window: vec4<f32>,
+    normal_transform: mat4x4<f32>,
+    // Material specific values, such as the reflectivity of reflective materials.
+    parameters: vec4<f32>,
+    color: vec4<f32>,
+    // Light given off by emissive materials.
+    emissive: vec4<f32>,
+    // Per-operation values of custom materials.
+    data: array<vec4<f32>, 4>,
 }
 @group(0) @binding(1)
 var<uniform> local: Local;
 
+struct Light {
+    // Direction with w = 0, or position with w = 1.
+    position: vec4<f32>,
+    // Color times intensity, with the range of point lights in w.
+    color: vec4<f32>,
+}
+
+struct Lighting {
+    ambient: vec4<f32>,
+    lit: u32,
+    specular: f32,
+    shininess: f32,
+    heatmap: u32,
+    target_size: vec2<f32>,
+    debug_view: u32,
+}
+@group(0) @binding(2)
+var<uniform> lighting: Lighting;
+// Bindings 3 and 4 hold the lights, and are declared along with `tile_light_count`
+// and `tile_light` by lights_tiled.wgsl or lights_uniform.wgsl depending on the
+// adapter.
+
+const LIGHT_TILES = vec2<u32>(16u, 9u);
+
 @group(1) @binding(0)
 var texture_sampler: sampler;
 @group(1) @binding(1)
 var texture: texture_2d<f32>;
 
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// Gets the color of the operation premultiplied by its alpha, like textures are.
+fn operation_color() -> vec4<f32> {
+    return vec4<f32>(local.color.rgb * local.color.a, local.color.a);
+}
+
+// Transforms a vertex by the uniforms of the operation drawing it.
+fn transform_vertex(in: VertexInput, operation: Local) -> VertexOutput {
+    var out: VertexOutput;
+    let vertex_transform = operation.transform * vec4<f32>(in.position, 1.0);
+    out.clip_position = global.mvp * vertex_transform;
+    out.uv = operation.uv_window.xy + (operation.uv_window.zw * in.uv);
+    out.world_position = vertex_transform.xyz;
+    out.normal = (operation.normal_transform * vec4<f32>(in.normal, 0.0)).xyz;
+    out.color = vec4<f32>(srgb_to_linear(in.color.rgb) * in.color.a, in.color.a);
+    return out;
+}
+
 @vertex
 fn vs_main(
     in: VertexInput,
     @builtin(vertex_index) in_vertex_index: u32,
 ) -> VertexOutput {
-    var out: VertexOutput;
-    let vertex_transform = local.transform * vec4<f32>(in.position, 1.0);
-    out.clip_position = global.mvp * vertex_transform;
-    out.uv = local.uv_window.xy + (local.uv_window.zw * in.uv);
-    return out;
+    return transform_vertex(in, local);
+}
+
+// Bends the normal by a tangent space normal map. Meshes have no tangents, so the
+// tangent frame is built from how the position and uv change across the screen.
+fn perturb_normal(
+    normal: vec3<f32>,
+    world_position: vec3<f32>,
+    uv: vec2<f32>,
+    mapped: vec3<f32>,
+) -> vec3<f32> {
+    let dp1 = dpdx(world_position);
+    let dp2 = dpdy(world_position);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+
+    let dp2_perpendicular = cross(dp2, normal);
+    let dp1_perpendicular = cross(normal, dp1);
+    let tangent = dp2_perpendicular * duv1.x + dp1_perpendicular * duv2.x;
+    // Normal maps point +Y up the texture, which is towards decreasing v.
+    let bitangent = -(dp2_perpendicular * duv1.y + dp1_perpendicular * duv2.y);
+
+    let length_squared = max(dot(tangent, tangent), dot(bitangent, bitangent));
+    if (length_squared < 1e-12) {
+        return normal;
+    }
+    let scale = inverseSqrt(length_squared);
+    return normalize(mat3x3<f32>(tangent * scale, bitangent * scale, normal) * mapped);
+}
+
+// Gets the direction from a point to a light, with how much of the light reaches the
+// point in w.
+fn light_incidence(light: Light, world_position: vec3<f32>) -> vec4<f32> {
+    if (light.position.w == 0.0) {
+        return vec4<f32>(-light.position.xyz, 1.0);
+    }
+
+    let offset = light.position.xyz - world_position;
+    let distance = length(offset);
+    let falloff = clamp(1.0 - pow(distance / light.color.w, 2.0), 0.0, 1.0);
+    return vec4<f32>(offset / max(distance, 0.0001), falloff * falloff);
+}
+
+// Gets the index of the light tile a fragment is in.
+fn light_tile(frag_position: vec2<f32>) -> u32 {
+    let tile = vec2<u32>(frag_position / lighting.target_size * vec2<f32>(LIGHT_TILES));
+    let clamped = min(tile, LIGHT_TILES - 1u);
+    return clamped.y * LIGHT_TILES.x + clamped.x;
+}
+
+// Tints a shaded color from blue to red by how many lights reach its tile, when the
+// light heatmap is shown.
+fn light_heatmap(color: vec4<f32>, frag_position: vec2<f32>) -> vec4<f32> {
+    if (lighting.heatmap == 0u) {
+        return color;
+    }
+    let heat = min(f32(tile_light_count(light_tile(frag_position))) / 16.0, 1.0);
+    let tint = clamp(vec3<f32>(heat * 2.0 - 1.0, 1.0 - abs(heat * 2.0 - 1.0), 1.0 - heat * 2.0), vec3<f32>(0.0), vec3<f32>(1.0));
+    return vec4<f32>(mix(color.rgb, tint * color.a, 0.75), color.a);
+}
+
+// Colors a mip level from green for the full texture, through blue and purple, to red
+// for the sixth level and beyond.
+fn mip_level_color(level: f32) -> vec3<f32> {
+    let t = clamp(level / 5.0, 0.0, 1.0);
+    return vec3<f32>(t, 1.0 - t, 1.0 - abs(t * 2.0 - 1.0));
+}
+
+// Replaces a shaded, premultiplied color with what the debug view chosen with
+// RenderContext::set_debug_view shows, if any.
+fn apply_debug_view(color: vec4<f32>, in: VertexOutput) -> vec4<f32> {
+    switch lighting.debug_view {
+        // Depth, brighter the closer the fragment is.
+        case 1u: {
+            var closeness = 1.0 - in.clip_position.z;
+            if (global.camera.w != 0.0) {
+                closeness = 1.0 / (1.0 + distance(global.camera.xyz, in.world_position) * 0.1);
+            }
+            return vec4<f32>(vec3<f32>(closeness), 1.0);
+        }
+        // Overdraw, blended over each other so the color builds up where many
+        // fragments land on the same pixel.
+        case 2u: {
+            return vec4<f32>(vec3<f32>(1.0, 0.35, 0.1) * 0.15, 0.15);
+        }
+        // Normals, mapped from -1..1 to colors.
+        case 3u: {
+            return vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 1.0);
+        }
+        // Checkerboard of 8 by 8 cells per uv tile, tinted by the uv so flipped and
+        // rotated uvs stand out.
+        case 4u: {
+            let cell = vec2<i32>(floor(in.uv * 8.0));
+            let shade = select(0.35, 1.0, ((cell.x + cell.y) & 1) == 0);
+            return vec4<f32>(shade * vec3<f32>(fract(in.uv), 1.0), 1.0);
+        }
+        // Mip level the texture is sampled at.
+        case 5u: {
+            let texel = in.uv * vec2<f32>(textureDimensions(texture));
+            let footprint = max(length(dpdx(texel)), length(dpdy(texel)));
+            return vec4<f32>(mip_level_color(log2(max(footprint, 1.0))), 1.0);
+        }
+        default: {
+            return color;
+        }
+    }
+}
+
+// Lambert diffuse plus Blinn-Phong specular for every light reaching the fragment.
+fn apply_lighting(
+    albedo: vec3<f32>,
+    world_position: vec3<f32>,
+    normal: vec3<f32>,
+    frag_position: vec2<f32>,
+) -> vec3<f32> {
+    let to_camera = normalize(global.camera.xyz - world_position * global.camera.w);
+    var diffuse = lighting.ambient.rgb;
+    var specular = vec3<f32>(0.0);
+
+    let tile = light_tile(frag_position);
+    let count = tile_light_count(tile);
+    for (var index = 0u; index < count; index++) {
+        let light = tile_light(tile, index);
+        let incidence = light_incidence(light, world_position);
+        let to_light = incidence.xyz;
+
+        let radiance = light.color.rgb * incidence.w;
+        let lambert = max(dot(normal, to_light), 0.0);
+        diffuse += radiance * lambert;
+
+        if (lambert > 0.0) {
+            let halfway = normalize(to_light + to_camera);
+            let highlight = pow(max(dot(normal, halfway), 0.0), lighting.shininess);
+            specular += radiance * highlight * lighting.specular;
+        }
+    }
+
+    return albedo * diffuse + specular;
+}
+
+// Shades a fragment of the default material, tinted by a premultiplied color.
+fn shade_default(in: VertexOutput, tint: vec4<f32>) -> vec4<f32> {
+    let sample = textureSample(texture, texture_sampler, in.uv) * in.color * tint;
+    if (sample.w < 0.001) {
+        discard;
+    }
+
+    if (lighting.lit == 0u) {
+        return apply_debug_view(sample, in);
+    }
+
+    let color = apply_lighting(
+        sample.rgb,
+        in.world_position,
+        normalize(in.normal),
+        in.clip_position.xy,
+    );
+    return apply_debug_view(light_heatmap(vec4<f32>(color, sample.w), in.clip_position.xy), in);
 }
 
 // Fragment shader
@@ -44,10 +260,5 @@ fn vs_main(
 fn fs_main(
     in: VertexOutput,    
 ) -> @location(0) vec4<f32> {
-    let sample = textureSample(texture, texture_sampler, in.uv);
-    if (sample.w < 0.001) {
-        discard;
-    }
-    
-    return sample;
-}
\ No newline at end of file
+    return shade_default(in, operation_color());
+}
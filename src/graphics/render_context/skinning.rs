@@ -0,0 +1,464 @@
+//! Deforms skinned meshes by their joints in a compute pass, writing the deformed
+//! vertices over the mesh's own in the mesh pool, so skinned meshes are drawn by every
+//! pipeline like any other. Downlevel adapters without compute shaders deform them on
+//! the cpu instead.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    graphics::{
+        skeleton::{self, Pose, SkinWeights},
+        Mesh, MeshData, Model, Vertex,
+    },
+    util::{frustum::BoundingSphere, repository::ResourceId},
+};
+
+use super::RenderContext;
+
+const SHADER_SOURCE: &str = include_str!("skinning.wgsl");
+
+/// Vertices deformed by each workgroup of the compute shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Pipeline deforming skinned meshes.
+pub(crate) struct Skinner {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+/// Mesh loaded with [RenderContext::load_skinned_mesh].
+pub(crate) struct SkinnedMesh {
+    deformer: Deformer,
+    /// Weights of each vertex, kept to create the deformer again after the device is
+    /// lost.
+    weights: Vec<SkinWeights>,
+    joint_count: usize,
+    vertex_count: u32,
+    /// Sphere around the vertices in the bind pose.
+    bind_bounds: BoundingSphere,
+}
+
+/// How a skinned mesh is deformed, see [RenderContext::is_downlevel].
+enum Deformer {
+    /// In a compute pass by the skinning shader.
+    Gpu(Box<SkinningBuffers>),
+    /// On the cpu, from the bind pose kept in memory.
+    Cpu { bind_vertices: Vec<Vertex> },
+}
+
+/// Buffers read by the skinning shader for a mesh.
+struct SkinningBuffers {
+    /// Vertices in the bind pose, which every pose is deformed from.
+    bind_vertices: wgpu::Buffer,
+    weights: wgpu::Buffer,
+    joint_matrices: wgpu::Buffer,
+    params: wgpu::Buffer,
+}
+
+/// Uniforms of the skinning shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SkinningParams {
+    first_vertex: u32,
+    vertex_count: u32,
+    _padding: [u32; 2],
+}
+
+unsafe impl Zeroable for SkinningParams {}
+unsafe impl Pod for SkinningParams {}
+
+impl Skinner {
+    fn new(device: &wgpu::Device) -> Self {
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("clockwork skinning bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, true),
+                storage(4, false),
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("clockwork skinning pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("clockwork skinning shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER_SOURCE.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("clockwork skinning pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+}
+
+impl RenderContext {
+    /// Loads a mesh that deforms with the joints of a skeleton and returns a
+    /// [ResourceId<Mesh>] that refers to it, see [RenderContext::skin_mesh].
+    ///
+    /// The mesh is drawn like any other, in its bind pose until it's first skinned.
+    ///
+    /// Returns an error if there isn't exactly one [SkinWeights] per vertex, or if they
+    /// refer to joints past `joint_count`.
+    pub fn load_skinned_mesh(
+        &mut self,
+        mesh_data: MeshData,
+        weights: &[SkinWeights],
+        joint_count: usize,
+    ) -> Result<ResourceId<Mesh>> {
+        anyhow::ensure!(
+            weights.len() == mesh_data.vertices.len(),
+            "expected {} skin weights, got {}",
+            mesh_data.vertices.len(),
+            weights.len()
+        );
+        anyhow::ensure!(
+            weights
+                .iter()
+                .flat_map(|weights| weights.joints)
+                .all(|joint| (joint as usize) < joint_count),
+            "skin weights refer to joints past the skeleton's {joint_count}"
+        );
+
+        let mesh_id = self.load_mesh(MeshData {
+            vertices: mesh_data.vertices,
+            indices: mesh_data.indices,
+        });
+        self.counters.upload(std::mem::size_of_val(weights));
+
+        let skinned_mesh = SkinnedMesh {
+            deformer: self.create_deformer(mesh_data.vertices, weights, joint_count),
+            weights: weights.to_vec(),
+            joint_count,
+            vertex_count: mesh_data.vertices.len() as u32,
+            bind_bounds: self.meshes[mesh_id].bounds,
+        };
+        self.skinned_meshes.insert(mesh_id, skinned_mesh);
+        Ok(mesh_id)
+    }
+
+    /// Creates what deforms a skinned mesh, along with the skinning pipeline if this is
+    /// the first one deformed on the gpu.
+    fn create_deformer(
+        &mut self,
+        bind_vertices: &[Vertex],
+        weights: &[SkinWeights],
+        joint_count: usize,
+    ) -> Deformer {
+        match self.downlevel {
+            true => Deformer::Cpu {
+                bind_vertices: bind_vertices.to_vec(),
+            },
+            false => {
+                let device = &self.device;
+                self.skinner.get_or_insert_with(|| Skinner::new(device));
+                let storage = |label, contents: &[u8]| {
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some(label),
+                        contents,
+                        usage: wgpu::BufferUsages::STORAGE,
+                    })
+                };
+                Deformer::Gpu(Box::new(SkinningBuffers {
+                    bind_vertices: storage(
+                        "clockwork skinning bind vertices buffer",
+                        bytemuck::cast_slice(bind_vertices),
+                    ),
+                    weights: storage(
+                        "clockwork skinning weights buffer",
+                        bytemuck::cast_slice(weights),
+                    ),
+                    joint_matrices: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("clockwork skinning joint matrices buffer"),
+                        size: (joint_count.max(1) * std::mem::size_of::<Mat4>())
+                            as wgpu::BufferAddress,
+                        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    params: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("clockwork skinning params buffer"),
+                        size: std::mem::size_of::<SkinningParams>() as wgpu::BufferAddress,
+                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                }))
+            }
+        }
+    }
+
+    /// Creates the deformers of skinned meshes from a context whose device was lost
+    /// again, from the bind poses in the mesh pool, see [RenderContext::recover].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn restore_skinned_meshes(
+        &mut self,
+        skinned_meshes: HashMap<ResourceId<Mesh>, SkinnedMesh>,
+    ) {
+        for (mesh_id, skinned_mesh) in skinned_meshes {
+            let Some(mesh) = self.meshes.get(mesh_id) else {
+                continue;
+            };
+            let bind_vertices = self.mesh_pool.mesh_vertices(mesh).to_vec();
+            let deformer = self.create_deformer(
+                &bind_vertices,
+                &skinned_mesh.weights,
+                skinned_mesh.joint_count,
+            );
+            self.skinned_meshes.insert(
+                mesh_id,
+                SkinnedMesh {
+                    deformer,
+                    ..skinned_mesh
+                },
+            );
+        }
+    }
+
+    /// Deforms a mesh loaded with [RenderContext::load_skinned_mesh] by a matrix for
+    /// each joint, such as from [crate::graphics::Skeleton::joint_matrices], on the
+    /// gpu before the next render pass draws it, or right away on the cpu on downlevel
+    /// adapters, see [RenderContext::is_downlevel].
+    ///
+    /// Returns an error if the mesh isn't skinned, there isn't exactly one matrix per
+    /// joint, or its vertices were replaced with [RenderContext::update_mesh].
+    pub fn skin_mesh(&mut self, mesh_id: ResourceId<Mesh>, joint_matrices: &[Mat4]) -> Result<()> {
+        let skinned_mesh = self
+            .skinned_meshes
+            .get(&mesh_id)
+            .ok_or_else(|| anyhow::anyhow!("mesh {mesh_id:?} isn't skinned"))?;
+        anyhow::ensure!(
+            joint_matrices.len() == skinned_mesh.joint_count,
+            "expected {} joint matrices, got {}",
+            skinned_mesh.joint_count,
+            joint_matrices.len()
+        );
+        let mesh = self
+            .meshes
+            .get_mut(mesh_id)
+            .ok_or_else(|| anyhow::anyhow!("no mesh {mesh_id:?}"))?;
+        anyhow::ensure!(
+            mesh.vertices.len() as u32 == skinned_mesh.vertex_count,
+            "skinned mesh {mesh_id:?} has {} vertices instead of {}",
+            mesh.vertices.len(),
+            skinned_mesh.vertex_count
+        );
+        mesh.bounds = skeleton::skinned_bounds(skinned_mesh.bind_bounds, joint_matrices);
+        if joint_matrices.is_empty() || skinned_mesh.vertex_count == 0 {
+            return Ok(());
+        }
+
+        let buffers = match &skinned_mesh.deformer {
+            Deformer::Gpu(buffers) => buffers,
+            Deformer::Cpu { bind_vertices } => {
+                let vertices = skin_vertices(bind_vertices, &skinned_mesh.weights, joint_matrices);
+                self.queue.write_buffer(
+                    &self.mesh_pool.vertex_buffer,
+                    mesh.vertices.start as u64 * std::mem::size_of::<Vertex>() as u64,
+                    bytemuck::cast_slice(&vertices),
+                );
+                self.counters
+                    .upload(std::mem::size_of_val(vertices.as_slice()));
+                return Ok(());
+            }
+        };
+
+        let params = SkinningParams {
+            first_vertex: mesh.vertices.start,
+            vertex_count: skinned_mesh.vertex_count,
+            _padding: [0; 2],
+        };
+        self.queue
+            .write_buffer(&buffers.params, 0, bytemuck::bytes_of(&params));
+        self.queue.write_buffer(
+            &buffers.joint_matrices,
+            0,
+            bytemuck::cast_slice(
+                &joint_matrices
+                    .iter()
+                    .map(Mat4::to_cols_array)
+                    .collect::<Vec<_>>(),
+            ),
+        );
+        self.counters
+            .upload(std::mem::size_of_val(joint_matrices) + std::mem::size_of_val(&params));
+
+        // Bound again each time, as the mesh pool's vertex buffer is replaced when it
+        // grows or is repacked.
+        let skinner = self
+            .skinner
+            .as_ref()
+            .expect("created with the first skinned mesh");
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("clockwork skinning bind group"),
+            layout: &skinner.bind_group_layout,
+            entries: &[
+                buffers.params.as_entire_binding(),
+                buffers.bind_vertices.as_entire_binding(),
+                buffers.weights.as_entire_binding(),
+                buffers.joint_matrices.as_entire_binding(),
+                self.mesh_pool.vertex_buffer.as_entire_binding(),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(binding, resource)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource,
+            })
+            .collect::<Vec<_>>(),
+        });
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork skinning encoder"),
+            }),
+        );
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("clockwork skinning pass"),
+            });
+            compute_pass.set_pipeline(&skinner.pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                skinned_mesh.vertex_count.div_ceil(WORKGROUP_SIZE),
+                1,
+                1,
+            );
+        }
+        self.submit(encoder);
+        Ok(())
+    }
+
+    /// Deforms the parts of a model skinned to one of its skeletons into a pose, see
+    /// [RenderContext::skin_mesh].
+    ///
+    /// The pose belongs to the loaded meshes, so models animated independently, such as
+    /// several characters sharing a file, should each be loaded with
+    /// [RenderContext::load_model].
+    ///
+    /// Returns an error if the model has no such skeleton.
+    pub fn pose_model(&mut self, model: &Model, skeleton: usize, pose: &Pose) -> Result<()> {
+        let joint_matrices = model
+            .skeletons
+            .get(skeleton)
+            .ok_or_else(|| anyhow::anyhow!("model has no skeleton {skeleton}"))?
+            .joint_matrices(pose);
+        for submesh in model.submeshes.iter() {
+            if submesh.skeleton == Some(skeleton) {
+                self.skin_mesh(submesh.mesh_id, &joint_matrices)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deforms vertices by the joints they're weighted to, the same way the skinning
+/// shader does.
+fn skin_vertices(
+    bind_vertices: &[Vertex],
+    weights: &[SkinWeights],
+    joint_matrices: &[Mat4],
+) -> Vec<Vertex> {
+    bind_vertices
+        .iter()
+        .zip(weights)
+        .map(|(vertex, skin)| {
+            let mut position = Vec4::ZERO;
+            let mut normal = Vec4::ZERO;
+            for (joint, weight) in skin.joints.into_iter().zip(skin.weights) {
+                let joint = joint_matrices[joint as usize];
+                position += joint * vertex.position.extend(1.0) * weight;
+                normal += joint * vertex.normal.extend(0.0) * weight;
+            }
+            Vertex {
+                position: position.truncate(),
+                normal: normal.truncate().normalize_or_zero(),
+                texture_coordinates: vertex.texture_coordinates,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shader_validates() {
+        let module = naga::front::wgsl::parse_str(SHADER_SOURCE).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_skin_vertices() {
+        let vertex = Vertex {
+            position: glam::vec3(1.0, 0.0, 0.0),
+            normal: glam::Vec3::X,
+            texture_coordinates: glam::vec2(0.5, 0.5),
+        };
+        let halfway = SkinWeights {
+            joints: [0, 1, 0, 0],
+            weights: [0.5, 0.5, 0.0, 0.0],
+        };
+        let joint_matrices = [
+            Mat4::IDENTITY,
+            Mat4::from_translation(glam::vec3(0.0, 2.0, 0.0))
+                * Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        ];
+        let skinned = skin_vertices(&[vertex], &[halfway], &joint_matrices);
+        assert!(skinned[0]
+            .position
+            .abs_diff_eq(glam::vec3(0.5, 1.5, 0.0), 1e-5));
+        assert!(skinned[0]
+            .normal
+            .abs_diff_eq(glam::vec3(1.0, 1.0, 0.0).normalize(), 1e-5));
+        assert_eq!(skinned[0].texture_coordinates, vertex.texture_coordinates);
+    }
+
+    #[test]
+    fn test_vertex_layout_matches_shader() {
+        // The shader reads and writes vertices as 8 floats.
+        assert_eq!(
+            std::mem::size_of::<crate::graphics::Vertex>(),
+            8 * std::mem::size_of::<f32>()
+        );
+        assert_eq!(std::mem::size_of::<SkinWeights>(), 32);
+    }
+}
@@ -0,0 +1,63 @@
+// Deforms the vertices of a skinned mesh by its joints, writing them over the mesh's
+// vertices in the mesh pool.
+
+struct SkinningParams {
+    // Index of the mesh's first vertex in the mesh pool.
+    first_vertex: u32,
+    vertex_count: u32,
+    _padding: vec2<u32>,
+}
+
+struct SkinWeights {
+    joints: vec4<u32>,
+    weights: vec4<f32>,
+}
+
+// Vertices are read and written as floats, since vec3s would be padded to 16 bytes.
+const VERTEX_FLOATS: u32 = 8u;
+
+@group(0) @binding(0)
+var<uniform> params: SkinningParams;
+@group(0) @binding(1)
+var<storage, read> bind_vertices: array<f32>;
+@group(0) @binding(2)
+var<storage, read> skin_weights: array<SkinWeights>;
+@group(0) @binding(3)
+var<storage, read> joint_matrices: array<mat4x4<f32>>;
+@group(0) @binding(4)
+var<storage, read_write> vertices: array<f32>;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.vertex_count {
+        return;
+    }
+
+    let source = index * VERTEX_FLOATS;
+    let position = vec4<f32>(bind_vertices[source], bind_vertices[source + 1u], bind_vertices[source + 2u], 1.0);
+    let normal = vec4<f32>(bind_vertices[source + 3u], bind_vertices[source + 4u], bind_vertices[source + 5u], 0.0);
+
+    // Normals are transformed by the joints as well, which is exact as long as they
+    // aren't scaled unevenly.
+    let skin = skin_weights[index];
+    var skinned_position = vec4<f32>(0.0);
+    var skinned_normal = vec4<f32>(0.0);
+    for (var i = 0u; i < 4u; i++) {
+        let joint = joint_matrices[skin.joints[i]];
+        skinned_position += joint * position * skin.weights[i];
+        skinned_normal += joint * normal * skin.weights[i];
+    }
+    let normal_length = length(skinned_normal.xyz);
+    let skinned = select(skinned_normal.xyz, skinned_normal.xyz / normal_length, normal_length > 0.0);
+
+    let destination = (params.first_vertex + index) * VERTEX_FLOATS;
+    vertices[destination] = skinned_position.x;
+    vertices[destination + 1u] = skinned_position.y;
+    vertices[destination + 2u] = skinned_position.z;
+    vertices[destination + 3u] = skinned.x;
+    vertices[destination + 4u] = skinned.y;
+    vertices[destination + 5u] = skinned.z;
+    vertices[destination + 6u] = bind_vertices[source + 6u];
+    vertices[destination + 7u] = bind_vertices[source + 7u];
+}
@@ -0,0 +1,125 @@
+use std::cell::Cell;
+
+use crate::diagnostics::AllocStats;
+
+use super::{FrameLatencyStats, RenderContext};
+
+/// Work the [RenderContext] did during the latest frame, see
+/// [RenderContext::render_stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    /// Draw calls made, including post process passes.
+    pub draw_calls: u32,
+    /// Draws made through multi-draw indirect, each call of which counts once in
+    /// [RenderStats::draw_calls], see [RenderContext::set_multi_draw_indirect].
+    pub indirect_draws: u32,
+    /// Bytes written to gpu buffers, such as per-operation uniforms and new meshes.
+    pub buffer_upload_bytes: u64,
+    /// Textures currently loaded, including render targets.
+    pub texture_count: usize,
+    /// Meshes currently loaded.
+    pub mesh_count: usize,
+    /// Texture bind groups currently cached, including those of PBR materials.
+    pub cached_bind_group_count: usize,
+    /// Size of the buffer per-operation uniforms are written to.
+    pub local_buffer_bytes: u64,
+    /// Size of the buffers every mesh's vertices and indices are allocated from.
+    pub mesh_buffer_bytes: u64,
+    /// How far the cpu is running ahead of the gpu.
+    pub latency: FrameLatencyStats,
+}
+
+/// Counts work as it happens during a frame.
+///
+/// Counts are kept in cells so they can be added to while other parts of the context
+/// are borrowed, such as in the middle of a render pass.
+#[derive(Debug, Default)]
+pub(crate) struct RenderCounters {
+    draw_calls: Cell<u32>,
+    upload_bytes: Cell<u64>,
+    indirect_draws: Cell<u32>,
+}
+
+impl RenderCounters {
+    /// Counts draw calls.
+    pub(crate) fn draw(&self, calls: usize) {
+        self.draw_calls.set(self.draw_calls.get() + calls as u32);
+    }
+
+    /// Counts draws made within a multi-draw indirect call.
+    pub(crate) fn indirect(&self, draws: usize) {
+        self.indirect_draws
+            .set(self.indirect_draws.get() + draws as u32);
+    }
+
+    /// Counts bytes written to a gpu buffer.
+    pub(crate) fn upload(&self, bytes: usize) {
+        self.upload_bytes
+            .set(self.upload_bytes.get() + bytes as u64);
+    }
+
+    /// Takes the counts of draw calls, uploaded bytes and indirect draws, resetting
+    /// them for the next frame.
+    pub(crate) fn take(&self) -> (u32, u64, u32) {
+        (
+            self.draw_calls.take(),
+            self.upload_bytes.take(),
+            self.indirect_draws.take(),
+        )
+    }
+}
+
+impl RenderContext {
+    /// Gets the work done during the latest frame, which ends with
+    /// [RenderContext::end_frame].
+    pub fn render_stats(&self) -> RenderStats {
+        let (draw_calls, buffer_upload_bytes, indirect_draws) = self.frame_counts;
+        RenderStats {
+            draw_calls,
+            indirect_draws,
+            buffer_upload_bytes,
+            texture_count: self.textures.len(),
+            mesh_count: self.meshes.len(),
+            cached_bind_group_count: self.textures_bind_groups.len()
+                + self.pbr.as_ref().map_or(0, |pbr| pbr.bind_group_count()),
+            local_buffer_bytes: self
+                .local_uniforms
+                .as_ref()
+                .map_or(0, |local_uniforms| local_uniforms.size_bytes()),
+            mesh_buffer_bytes: self.mesh_pool.size_bytes(),
+            latency: self.frame_latency_stats(),
+        }
+    }
+
+    /// Records the counts of the frame that just ended.
+    pub(crate) fn finish_frame_counts(&mut self) {
+        let (mut draw_calls, mut upload_bytes, indirect_draws) = self.counters.take();
+        if let Some(post_processor) = &self.post_processor {
+            let (post_draw_calls, post_upload_bytes, _) = post_processor.counters.take();
+            draw_calls += post_draw_calls;
+            upload_bytes += post_upload_bytes;
+        }
+        self.frame_counts = (draw_calls, upload_bytes, indirect_draws);
+    }
+
+    /// Takes the allocations render passes made since this was last called.
+    pub(crate) fn take_render_allocs(&mut self) -> AllocStats {
+        std::mem::take(&mut self.render_allocs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_reset_when_taken() {
+        let counters = RenderCounters::default();
+        counters.draw(3);
+        counters.upload(64);
+        counters.upload(16);
+        counters.indirect(5);
+        assert_eq!(counters.take(), (3, 80, 5));
+        assert_eq!(counters.take(), (0, 0, 0));
+    }
+}
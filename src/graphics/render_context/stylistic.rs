@@ -0,0 +1,296 @@
+use anyhow::Result;
+use bytemuck::Zeroable;
+
+use crate::{graphics::texture::Texture, util::repository::ResourceId};
+
+use super::{
+    post_process::{self, FullscreenPass, PostProcessor, INTERMEDIATE_FORMAT},
+    RenderContext, RenderTarget,
+};
+
+const SHADER_SOURCE: &str = include_str!("stylistic.wgsl");
+
+/// Cheap effect that gives the scene a particular look, see
+/// [RenderContext::apply_stylistic_effects].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StylisticEffect {
+    /// Splits colors apart towards the edges of the screen like a cheap lens.
+    ChromaticAberration {
+        /// How far colors split at the edges, as a fraction of the screen.
+        intensity: f32,
+    },
+    /// Noise that changes every frame like film.
+    FilmGrain {
+        /// Strength of the noise, where around 0.1 is subtle.
+        intensity: f32,
+    },
+    /// Darkens the corners of the screen.
+    Vignette {
+        /// How dark the corners get, from 0 for no change to 1 for black.
+        intensity: f32,
+        /// How gradually the darkening fades in from the center, from 0 to 1.
+        smoothness: f32,
+    },
+}
+
+/// Effects applied one after another in order, so their ordering can be controlled.
+pub struct StylisticEffects {
+    /// Effects to apply, in the order they are applied.
+    pub effects: Vec<StylisticEffect>,
+    /// Textures effects draw to before the last one draws to the target.
+    intermediates: Vec<Texture>,
+    /// Counts up every time the effects are applied, so grain moves.
+    frame: u32,
+    /// Generation of the device the textures were created on, so they are created
+    /// again after [RenderContext::recover].
+    device_generation: u32,
+}
+
+/// Pipelines for each effect, indexed by [StylisticEffect::index].
+pub(crate) struct StylisticPipelines {
+    /// Pipelines drawing to intermediate textures.
+    pub(crate) intermediate: Vec<wgpu::RenderPipeline>,
+    /// Pipelines drawing to the target.
+    pub(crate) output: Vec<wgpu::RenderPipeline>,
+}
+
+/// Uniforms of the stylistic shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct StylisticUniforms {
+    intensity: f32,
+    smoothness: f32,
+    seed: f32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Zeroable for StylisticUniforms {}
+unsafe impl bytemuck::Pod for StylisticUniforms {}
+
+/// Fragment entry points, with copying last for when there are no effects.
+const ENTRY_POINTS: [&str; 4] = [
+    "fs_chromatic_aberration",
+    "fs_film_grain",
+    "fs_vignette",
+    "fs_copy",
+];
+pub(crate) const COPY_INDEX: usize = 3;
+
+impl StylisticEffect {
+    /// Creates a new chromatic aberration [StylisticEffect].
+    pub fn chromatic_aberration(intensity: f32) -> Self {
+        Self::ChromaticAberration { intensity }
+    }
+
+    /// Creates a new film grain [StylisticEffect].
+    pub fn film_grain(intensity: f32) -> Self {
+        Self::FilmGrain { intensity }
+    }
+
+    /// Creates a new vignette [StylisticEffect].
+    pub fn vignette(intensity: f32, smoothness: f32) -> Self {
+        Self::Vignette {
+            intensity,
+            smoothness,
+        }
+    }
+
+    pub(crate) fn index(&self) -> usize {
+        match self {
+            StylisticEffect::ChromaticAberration { .. } => 0,
+            StylisticEffect::FilmGrain { .. } => 1,
+            StylisticEffect::Vignette { .. } => 2,
+        }
+    }
+
+    pub(crate) fn uniforms(&self, seed: f32) -> StylisticUniforms {
+        let (intensity, smoothness) = match *self {
+            StylisticEffect::ChromaticAberration { intensity } => (intensity, 0.0),
+            StylisticEffect::FilmGrain { intensity } => (intensity, 0.0),
+            StylisticEffect::Vignette {
+                intensity,
+                smoothness,
+            } => (intensity, smoothness),
+        };
+        StylisticUniforms {
+            intensity,
+            smoothness,
+            seed,
+            _padding: 0.0,
+        }
+    }
+}
+
+impl Default for StylisticEffects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StylisticEffects {
+    /// Creates a new [StylisticEffects] with no effects.
+    pub fn new() -> Self {
+        Self {
+            effects: Vec::new(),
+            intermediates: Vec::new(),
+            frame: 0,
+            device_generation: 0,
+        }
+    }
+
+    /// Adds an effect, applied after the ones before it.
+    pub fn with_effect(mut self, effect: StylisticEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+}
+
+/// Gets which intermediate texture each pass draws to, or `None` for the target. Passes
+/// alternate between two textures so each can read the one before.
+pub(crate) fn pass_targets(passes: usize) -> Vec<Option<usize>> {
+    (0..passes)
+        .map(|pass| (pass + 1 < passes).then_some(pass % 2))
+        .collect()
+}
+
+impl StylisticPipelines {
+    pub(crate) fn new(device: &wgpu::Device, post_processor: &PostProcessor) -> Self {
+        let shader =
+            post_process::create_shader(device, "clockwork stylistic shader", SHADER_SOURCE);
+        let pipelines = |format| {
+            ENTRY_POINTS
+                .iter()
+                .map(|entry_point| {
+                    post_processor.create_pipeline(
+                        device,
+                        "clockwork stylistic pipeline",
+                        &shader,
+                        entry_point,
+                        format,
+                        None,
+                    )
+                })
+                .collect()
+        };
+
+        Self {
+            intermediate: pipelines(INTERMEDIATE_FORMAT),
+            output: pipelines(post_processor.color_format),
+        }
+    }
+}
+
+impl RenderContext {
+    /// Draws `source` to `target` with each of the effects applied in order.
+    ///
+    /// Like [RenderContext::apply_bloom], render the scene to a texture first. With no
+    /// effects the source is drawn unchanged.
+    pub fn apply_stylistic_effects(
+        &mut self,
+        effects: &mut StylisticEffects,
+        source: ResourceId<Texture>,
+        target: RenderTarget,
+    ) -> Result<()> {
+        anyhow::ensure!(
+            target != RenderTarget::Texture(source),
+            "stylistic effects can't draw to their own source {source:?}"
+        );
+        let source_size = self
+            .textures
+            .get(source)
+            .ok_or_else(|| anyhow::anyhow!("no texture {source:?}"))?
+            .size;
+
+        let intermediates = effects.effects.len().saturating_sub(1).min(2);
+        if effects.device_generation != self.device_generation {
+            effects.intermediates.clear();
+            effects.device_generation = self.device_generation;
+        }
+        if effects.intermediates.len() != intermediates
+            || effects
+                .intermediates
+                .first()
+                .is_some_and(|texture| texture.size != source_size)
+        {
+            effects.intermediates = (0..intermediates)
+                .map(|_| post_process::create_intermediate_texture(&self.device, source_size))
+                .collect();
+        }
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let post_processor = self
+            .post_processor
+            .get_or_insert_with(|| PostProcessor::new(device, color_format));
+        if post_processor.stylistic.is_none() {
+            post_processor.stylistic = Some(StylisticPipelines::new(device, post_processor));
+        }
+
+        let Some(target_view) = self.post_process_target_view(target)? else {
+            return Ok(());
+        };
+        let post_processor = self.post_processor.as_ref().expect("created above");
+        let pipelines = post_processor.stylistic.as_ref().expect("created above");
+
+        effects.frame = effects.frame.wrapping_add(1);
+        let seed = (effects.frame % 1024) as f32;
+        let passes = match effects.effects.is_empty() {
+            true => vec![(COPY_INDEX, StylisticUniforms::zeroed())],
+            false => effects
+                .effects
+                .iter()
+                .map(|effect| (effect.index(), effect.uniforms(seed)))
+                .collect(),
+        };
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork stylistic encoder"),
+            }),
+        );
+
+        let mut pass_source = &self.textures[source].view;
+        for ((index, uniforms), pass_target) in passes.iter().zip(pass_targets(passes.len())) {
+            let (pipeline, view) = match pass_target {
+                Some(intermediate) => (
+                    &pipelines.intermediate[*index],
+                    &effects.intermediates[intermediate].view,
+                ),
+                None => (&pipelines.output[*index], target_view),
+            };
+            post_processor.encode(
+                &self.device,
+                &mut encoder,
+                FullscreenPass {
+                    label: "clockwork stylistic pass",
+                    pipeline,
+                    source: pass_source,
+                    secondary: None,
+                    uniforms: bytemuck::bytes_of(uniforms),
+                    target: view,
+                    blend_constant: None,
+                },
+            );
+            pass_source = view;
+        }
+
+        self.submit(encoder);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pass_targets() {
+        assert_eq!(pass_targets(1), vec![None]);
+        assert_eq!(pass_targets(4), vec![Some(0), Some(1), Some(0), None]);
+    }
+
+    #[test]
+    fn test_shader_validates() {
+        post_process::validate_shader(SHADER_SOURCE);
+    }
+}
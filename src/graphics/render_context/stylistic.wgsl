@@ -0,0 +1,52 @@
+struct StylisticUniforms {
+    intensity: f32,
+    // Vignette smoothness.
+    smoothness: f32,
+    // Changes every frame so grain moves.
+    seed: f32,
+    _padding: f32,
+}
+@group(0) @binding(3)
+var<uniform> effect: StylisticUniforms;
+
+@fragment
+fn fs_copy(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(source_texture, source_sampler, in.uv);
+}
+
+// Splits the red and blue channels apart towards the edges, like a cheap lens.
+@fragment
+fn fs_chromatic_aberration(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let offset = (in.uv - 0.5) * effect.intensity;
+    let center = textureSample(source_texture, source_sampler, in.uv);
+    let red = textureSample(source_texture, source_sampler, in.uv + offset).r;
+    let blue = textureSample(source_texture, source_sampler, in.uv - offset).b;
+    return vec4<f32>(red, center.g, blue, center.a);
+}
+
+fn hash(point: vec2<f32>) -> f32 {
+    let p = fract(point * vec2<f32>(443.897, 441.423));
+    let q = p + dot(p, p.yx + 19.19);
+    return fract((q.x + q.y) * q.x);
+}
+
+// Adds noise that changes every frame, strongest in the midtones like real film.
+@fragment
+fn fs_film_grain(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, in.uv);
+    let pixel = floor(in.clip_position.xy);
+    let noise = hash(pixel + effect.seed * vec2<f32>(17.0, 59.0)) - 0.5;
+    let luminance = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    let response = 1.0 - abs(clamp(luminance, 0.0, 1.0) * 2.0 - 1.0) * 0.5;
+    return vec4<f32>(max(color.rgb + noise * effect.intensity * response, vec3<f32>(0.0)), color.a);
+}
+
+// Darkens the corners.
+@fragment
+fn fs_vignette(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, in.uv);
+    let distance = length((in.uv - 0.5) * 2.0) / sqrt(2.0);
+    let radius = 1.0 - clamp(effect.smoothness, 0.0001, 1.0);
+    let darkening = smoothstep(radius, 1.0, distance) * clamp(effect.intensity, 0.0, 1.0);
+    return vec4<f32>(color.rgb * (1.0 - darkening), color.a);
+}
@@ -0,0 +1,525 @@
+use anyhow::Result;
+use glam::{Mat4, UVec2, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    graphics::texture::{self, SamplerSettings, TextureLoadOptions},
+    util::repository::ResourceId,
+};
+
+use super::{RenderContext, RenderTarget};
+
+const SHADER_SOURCE: &str = include_str!("texture_array.wgsl");
+
+/// Stack of same sized images sampled by layer index, such as the kinds of tile in a
+/// tilemap, so quads showing any of them are drawn together with
+/// [RenderContext::render_texture_array] instead of needing an atlas.
+pub struct TextureArray {
+    #[allow(unused)]
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    size: UVec2,
+    layer_count: u32,
+    sampler: SamplerSettings,
+    /// Pixels of each mip level of each layer, kept to upload them again after the
+    /// device is lost.
+    #[cfg(not(target_arch = "wasm32"))]
+    layer_mips: Vec<Vec<Vec<u8>>>,
+}
+
+/// Quad showing one layer of a [TextureArray].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureArrayInstance {
+    /// Transformation of a unit quad centered on the origin, like
+    /// [crate::graphics::default_meshes::QUAD_MESH_DATA].
+    pub transform: Mat4,
+    /// Layer of the array to show.
+    pub layer: u32,
+    /// Color the layer is multiplied by.
+    pub color: Vec4,
+}
+
+/// Resources for [RenderContext::render_texture_array], created the first time it is
+/// used.
+pub(crate) struct TextureArrayDrawer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+}
+
+/// Layout of a [TextureArrayInstance] in the instance buffer.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InstanceRaw {
+    transform: [[f32; 4]; 4],
+    color: [f32; 4],
+    layer: u32,
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for InstanceRaw {}
+unsafe impl bytemuck::Pod for InstanceRaw {}
+
+impl TextureArray {
+    /// Gets the size of each layer in pixels.
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// Gets the number of layers.
+    pub fn layer_count(&self) -> u32 {
+        self.layer_count
+    }
+
+    /// Creates a texture array from the pixels of each layer, prepared by
+    /// [texture::prepare_mip_levels].
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: UVec2,
+        layer_mips: Vec<Vec<Vec<u8>>>,
+        sampler: SamplerSettings,
+    ) -> Self {
+        let texture = device.create_texture(
+            &(wgpu::TextureDescriptor {
+                label: Some("clockwork texture array"),
+                size: wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: layer_mips.len() as u32,
+                },
+                mip_level_count: layer_mips[0].len() as u32,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }),
+        );
+        for (layer, mip_levels) in layer_mips.iter().enumerate() {
+            for (mip_level, rgba) in mip_levels.iter().enumerate() {
+                let level_size = (size >> mip_level as u32).max(UVec2::ONE);
+                texture::write_mip_level(
+                    queue,
+                    &texture,
+                    mip_level as u32,
+                    layer as u32,
+                    level_size,
+                    rgba,
+                );
+            }
+        }
+        let view = texture.create_view(
+            &(wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            }),
+        );
+
+        Self {
+            texture,
+            view,
+            size,
+            layer_count: layer_mips.len() as u32,
+            sampler,
+            #[cfg(not(target_arch = "wasm32"))]
+            layer_mips,
+        }
+    }
+
+    /// Creates the texture array again on a new device, after the one it was created
+    /// on was lost.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn restore(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::new(
+            device,
+            queue,
+            self.size,
+            self.layer_mips.clone(),
+            self.sampler,
+        )
+    }
+}
+
+impl TextureArrayInstance {
+    /// Creates a [TextureArrayInstance] showing a layer as is.
+    pub fn new(transform: Mat4, layer: u32) -> Self {
+        Self {
+            transform,
+            layer,
+            color: Vec4::ONE,
+        }
+    }
+
+    /// Sets the color the layer is multiplied by.
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl From<&TextureArrayInstance> for InstanceRaw {
+    fn from(instance: &TextureArrayInstance) -> Self {
+        Self {
+            transform: instance.transform.to_cols_array_2d(),
+            color: instance.color.to_array(),
+            layer: instance.layer,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// Gets the size shared by every layer of an array.
+fn layer_size(sizes: &[UVec2]) -> Result<UVec2> {
+    let Some(&size) = sizes.first() else {
+        anyhow::bail!("a texture array needs at least one layer");
+    };
+    if let Some((layer, other)) = sizes.iter().enumerate().find(|(_, &other)| other != size) {
+        anyhow::bail!(
+            "layer {layer} of the texture array is {}x{}, but layer 0 is {}x{}",
+            other.x,
+            other.y,
+            size.x,
+            size.y
+        );
+    }
+    Ok(size)
+}
+
+impl TextureArrayDrawer {
+    fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("clockwork texture array shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER_SOURCE.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork texture array bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            }),
+        );
+        let uniform_buffer = device.create_buffer_init(
+            &(wgpu::util::BufferInitDescriptor {
+                label: Some("clockwork texture array uniform buffer"),
+                contents: bytemuck::bytes_of(&Mat4::IDENTITY.to_cols_array_2d()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }),
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some("clockwork texture array pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            }),
+        );
+        let pipeline = device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some("clockwork texture array pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            0 => Float32x4,
+                            1 => Float32x4,
+                            2 => Float32x4,
+                            3 => Float32x4,
+                            4 => Float32x4,
+                            5 => Uint32,
+                        ],
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // Drawn in the order given over what's already in the target, like a
+                // 2D background or tilemap layer.
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }),
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            instance_buffer: create_instance_buffer(device, 0),
+        }
+    }
+}
+
+/// Creates an instance buffer with room for at least `instances` instances.
+fn create_instance_buffer(device: &wgpu::Device, instances: usize) -> wgpu::Buffer {
+    device.create_buffer(
+        &(wgpu::BufferDescriptor {
+            label: Some("clockwork texture array instance buffer"),
+            size: (instances.max(256).next_power_of_two() * std::mem::size_of::<InstanceRaw>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }),
+    )
+}
+
+impl RenderContext {
+    /// Loads a [TextureArray] with a layer for each image, and returns a
+    /// [ResourceId<TextureArray>] that refers to it.
+    ///
+    /// Returns an error if an image fails to decode, the images aren't all the same
+    /// size, or there are more than the adapter supports.
+    pub fn load_texture_array(&mut self, layers: &[&[u8]]) -> Result<ResourceId<TextureArray>> {
+        self.load_texture_array_with_sampler(layers, SamplerSettings::default())
+    }
+
+    /// Loads a [TextureArray] that is sampled as described by `sampler`, see
+    /// [RenderContext::load_texture_array].
+    pub fn load_texture_array_with_sampler(
+        &mut self,
+        layers: &[&[u8]],
+        sampler: SamplerSettings,
+    ) -> Result<ResourceId<TextureArray>> {
+        self.load_texture_array_with_options(layers, sampler, TextureLoadOptions::default())
+    }
+
+    /// Loads a [TextureArray] like [RenderContext::load_texture_array_with_sampler],
+    /// with its pixels prepared as described by `load_options`.
+    pub fn load_texture_array_with_options(
+        &mut self,
+        layers: &[&[u8]],
+        sampler: SamplerSettings,
+        load_options: TextureLoadOptions,
+    ) -> Result<ResourceId<TextureArray>> {
+        let images = layers
+            .iter()
+            .map(|bytes| Ok(image::load_from_memory(bytes)?.to_rgba8()))
+            .collect::<Result<Vec<_>>>()?;
+        let sizes: Vec<UVec2> = images
+            .iter()
+            .map(|image| UVec2::new(image.width(), image.height()))
+            .collect();
+        let size = layer_size(&sizes)?;
+        let max_layers = self.device.limits().max_texture_array_layers;
+        anyhow::ensure!(
+            images.len() as u32 <= max_layers,
+            "texture array has {} layers, but the adapter supports at most {max_layers}",
+            images.len()
+        );
+
+        let layer_mips: Vec<Vec<Vec<u8>>> = images
+            .iter()
+            .map(|image| texture::prepare_mip_levels(size, image, sampler, load_options))
+            .collect();
+        self.counters
+            .upload(layer_mips.iter().flatten().map(Vec::len).sum::<usize>());
+
+        Ok(self.texture_arrays.add(
+            TextureArray::new(&self.device, &self.queue, size, layer_mips, sampler),
+            None,
+        ))
+    }
+
+    /// Gets a loaded [TextureArray].
+    pub fn texture_array(
+        &self,
+        texture_array_id: ResourceId<TextureArray>,
+    ) -> Option<&TextureArray> {
+        self.texture_arrays.get(texture_array_id)
+    }
+
+    /// Destroys a [TextureArray].
+    pub fn remove_texture_array(&mut self, texture_array_id: ResourceId<TextureArray>) {
+        self.texture_arrays.remove(texture_array_id);
+    }
+
+    /// Draws quads showing layers of a [TextureArray] over `target` in a single draw
+    /// call, in the order given and without depth testing, such as for a tilemap or
+    /// terrain with many kinds of tile.
+    ///
+    /// Like render passes, nothing is drawn to the surface outside of a frame.
+    ///
+    /// Returns an error if an instance shows a layer past the end of the array.
+    pub fn render_texture_array(
+        &mut self,
+        view_projection: Mat4,
+        texture_array_id: ResourceId<TextureArray>,
+        instances: &[TextureArrayInstance],
+        target: RenderTarget,
+    ) -> Result<()> {
+        let texture_array = self
+            .texture_arrays
+            .get(texture_array_id)
+            .ok_or_else(|| anyhow::anyhow!("no texture array {texture_array_id:?}"))?;
+        if let Some(instance) = instances
+            .iter()
+            .find(|instance| instance.layer >= texture_array.layer_count)
+        {
+            anyhow::bail!(
+                "texture array {texture_array_id:?} has {} layers, but layer {} was drawn",
+                texture_array.layer_count,
+                instance.layer
+            );
+        }
+        let target_view = match target {
+            RenderTarget::Surface => match &self.frame {
+                Some(frame) => &frame.view,
+                None => return Ok(()),
+            },
+            RenderTarget::Texture(texture_id) => {
+                &self
+                    .textures
+                    .get(texture_id)
+                    .ok_or_else(|| anyhow::anyhow!("no texture {texture_id:?}"))?
+                    .view
+            }
+        };
+        if instances.is_empty() {
+            return Ok(());
+        }
+
+        let color_format = self.color_format();
+        let device = &self.device;
+        let drawer = self
+            .texture_array_drawer
+            .get_or_insert_with(|| TextureArrayDrawer::new(device, color_format));
+        let raw_instances: Vec<InstanceRaw> = instances.iter().map(InstanceRaw::from).collect();
+        let instance_bytes: &[u8] = bytemuck::cast_slice(&raw_instances);
+        if drawer.instance_buffer.size() < instance_bytes.len() as wgpu::BufferAddress {
+            drawer.instance_buffer = create_instance_buffer(device, instances.len());
+        }
+        self.queue
+            .write_buffer(&drawer.instance_buffer, 0, instance_bytes);
+        self.queue.write_buffer(
+            &drawer.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&view_projection.to_cols_array_2d()),
+        );
+        self.counters
+            .upload(instance_bytes.len() + std::mem::size_of::<Mat4>());
+
+        let sampler_settings = texture_array.sampler;
+        let sampler = self
+            .samplers
+            .entry(sampler_settings)
+            .or_insert_with(|| sampler_settings.create_sampler(device));
+        let bind_group = device.create_bind_group(
+            &(wgpu::BindGroupDescriptor {
+                label: Some("clockwork texture array bind group"),
+                layout: &drawer.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: drawer.uniform_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&texture_array.view),
+                    },
+                ],
+            }),
+        );
+
+        let mut encoder = device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork texture array encoder"),
+            }),
+        );
+        {
+            let mut render_pass = encoder.begin_render_pass(
+                &(wgpu::RenderPassDescriptor {
+                    label: Some("clockwork texture array pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: target_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                }),
+            );
+            render_pass.set_pipeline(&drawer.pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.set_vertex_buffer(
+                0,
+                drawer.instance_buffer.slice(..instance_bytes.len() as u64),
+            );
+            render_pass.draw(0..6, 0..instances.len() as u32);
+            self.counters.draw(1);
+        }
+
+        self.submit(encoder);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_size() {
+        let size = UVec2::new(16, 16);
+        assert_eq!(layer_size(&[size, size]).unwrap(), size);
+        assert!(layer_size(&[]).is_err());
+        assert!(layer_size(&[size, UVec2::new(16, 8)]).is_err());
+    }
+
+    #[test]
+    fn test_shader_validates() {
+        let module = naga::front::wgsl::parse_str(SHADER_SOURCE).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+        // Instances are laid out as the shader's vertex inputs expect.
+        assert_eq!(std::mem::size_of::<InstanceRaw>(), 96);
+    }
+}
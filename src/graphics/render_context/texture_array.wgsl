@@ -0,0 +1,56 @@
+@group(0) @binding(0)
+var<uniform> view_projection: mat4x4<f32>;
+@group(0) @binding(1)
+var array_sampler: sampler;
+@group(0) @binding(2)
+var array_texture: texture_2d_array<f32>;
+
+struct InstanceInput {
+    @location(0) transform_0: vec4<f32>,
+    @location(1) transform_1: vec4<f32>,
+    @location(2) transform_2: vec4<f32>,
+    @location(3) transform_3: vec4<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) layer: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) layer: u32,
+};
+
+// Two triangles covering a unit quad, with its bottom left at the origin.
+var<private> CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 0.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(0.0, 1.0),
+);
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, instance: InstanceInput) -> VertexOutput {
+    let transform = mat4x4<f32>(
+        instance.transform_0,
+        instance.transform_1,
+        instance.transform_2,
+        instance.transform_3,
+    );
+    let corner = CORNERS[vertex_index];
+
+    var out: VertexOutput;
+    // Centered on the origin like the default quad mesh, with v going down the texture.
+    out.clip_position = view_projection * transform * vec4<f32>(corner - 0.5, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    out.color = vec4<f32>(instance.color.rgb * instance.color.a, instance.color.a);
+    out.layer = instance.layer;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(array_texture, array_sampler, in.uv, in.layer) * in.color;
+}
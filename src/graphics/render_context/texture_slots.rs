@@ -0,0 +1,51 @@
+use glam::UVec2;
+
+use crate::{
+    graphics::texture::{SamplerSettings, Texture},
+    util::repository::ResourceId,
+};
+
+/// Number of textures bound for default, reflective, mapped and custom materials.
+pub(crate) const TEXTURE_SLOTS: usize = 4;
+
+/// Index of the normal map in [TextureSlots].
+pub(crate) const NORMAL_SLOT: usize = 1;
+
+/// Diffuse texture, normal map, emissive map and mask of an operation, in the order they
+/// are bound after the sampler.
+pub(crate) type TextureSlots = [Option<ResourceId<Texture>>; TEXTURE_SLOTS];
+
+/// Textures bound in place of missing ones, which leave the values they multiply as
+/// they are.
+pub(crate) struct FallbackTextures {
+    pub(crate) white: Texture,
+    /// Normal map pointing straight out of the surface.
+    pub(crate) flat_normal: Texture,
+}
+
+impl FallbackTextures {
+    pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let pixel = |rgba: [u8; 4]| {
+            Texture::from_data_rgba(
+                device,
+                queue,
+                UVec2::ONE,
+                &rgba,
+                SamplerSettings::default(),
+                "clockwork fallback texture",
+            )
+        };
+        Self {
+            white: pixel([u8::MAX; 4]),
+            flat_normal: pixel([128, 128, u8::MAX, u8::MAX]),
+        }
+    }
+
+    /// Gets the texture to bind for a missing texture, given whether it's a normal map.
+    pub(crate) fn get(&self, normal_map: bool) -> &Texture {
+        match normal_map {
+            true => &self.flat_normal,
+            false => &self.white,
+        }
+    }
+}
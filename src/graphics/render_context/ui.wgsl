@@ -0,0 +1,51 @@
+struct UiUniforms {
+    // Size of the screen in points.
+    screen_size: vec2<f32>,
+    _padding: vec2<f32>,
+}
+@group(0) @binding(0)
+var<uniform> ui: UiUniforms;
+
+@group(1) @binding(0)
+var ui_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var ui_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    // Premultiplied srgb.
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    let lower = srgb / 12.92;
+    let higher = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(higher, lower, srgb < vec3<f32>(0.04045));
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        2.0 * in.position.x / ui.screen_size.x - 1.0,
+        1.0 - 2.0 * in.position.y / ui.screen_size.y,
+        0.0,
+        1.0,
+    );
+    out.uv = in.uv;
+    // The surface is srgb, so blend in linear space.
+    out.color = vec4<f32>(linear_from_srgb(in.color.rgb), in.color.a);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color * textureSample(ui_texture, ui_sampler, in.uv);
+}
@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::graphics::texture::{SamplerSettings, TextureFilter};
+
+use super::RenderContext;
+
+const SHADER_SOURCE: &str = include_str!("ui.wgsl");
+
+/// Debug UI drawn over the end of every frame, see [RenderContext::ui_context].
+pub(crate) struct UiLayer {
+    context: egui::Context,
+    /// Whether an egui frame was begun and hasn't been drawn yet.
+    frame_running: bool,
+    /// What egui asked of the window during the latest frame.
+    platform_output: Option<egui::PlatformOutput>,
+    /// Resources for drawing, created the first time the UI is drawn.
+    renderer: Option<UiRenderer>,
+    /// Every update of each texture since it was last replaced whole, kept to upload
+    /// them again after the device is lost, as egui only sends them once.
+    #[cfg(not(target_arch = "wasm32"))]
+    texture_deltas: HashMap<egui::TextureId, Vec<egui::epaint::ImageDelta>>,
+}
+
+/// Resources for drawing egui's output.
+struct UiRenderer {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    linear_sampler: wgpu::Sampler,
+    nearest_sampler: wgpu::Sampler,
+    /// Textures egui has uploaded, such as its font atlas.
+    textures: HashMap<egui::TextureId, (wgpu::Texture, wgpu::BindGroup)>,
+}
+
+/// Uniforms of the ui shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UiUniforms {
+    screen_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+unsafe impl bytemuck::Zeroable for UiUniforms {}
+unsafe impl bytemuck::Pod for UiUniforms {}
+
+impl UiLayer {
+    /// Creates a new [UiLayer].
+    pub(crate) fn new() -> Self {
+        Self {
+            context: egui::Context::default(),
+            frame_running: false,
+            platform_output: None,
+            renderer: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            texture_deltas: HashMap::new(),
+        }
+    }
+}
+
+impl UiRenderer {
+    fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("clockwork ui shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER_SOURCE.into()),
+        });
+
+        let uniform_bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork ui uniform bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            }),
+        );
+        let texture_bind_group_layout = device.create_bind_group_layout(
+            &(wgpu::BindGroupLayoutDescriptor {
+                label: Some("clockwork ui texture bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            }),
+        );
+
+        let uniform_buffer = device.create_buffer(
+            &(wgpu::BufferDescriptor {
+                label: Some("clockwork ui uniform buffer"),
+                size: std::mem::size_of::<UiUniforms>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        );
+        let uniform_bind_group = device.create_bind_group(
+            &(wgpu::BindGroupDescriptor {
+                label: Some("clockwork ui uniform bind group"),
+                layout: &uniform_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+            }),
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(
+            &(wgpu::PipelineLayoutDescriptor {
+                label: Some("clockwork ui pipeline layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            }),
+        );
+        let pipeline = device.create_render_pipeline(
+            &(wgpu::RenderPipelineDescriptor {
+                label: Some("clockwork ui pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<egui::epaint::Vertex>()
+                            as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![
+                            0 => Float32x2,
+                            1 => Float32x2,
+                            2 => Unorm8x4,
+                        ],
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }),
+        );
+
+        let sampler = |filter| {
+            SamplerSettings {
+                filter,
+                ..Default::default()
+            }
+            .create_sampler(device)
+        };
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+            texture_bind_group_layout,
+            linear_sampler: sampler(TextureFilter::Linear),
+            nearest_sampler: sampler(TextureFilter::Nearest),
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Creates or updates a texture egui uses.
+    fn set_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        id: egui::TextureId,
+        delta: &egui::epaint::ImageDelta,
+    ) {
+        let (size, pixels): ([usize; 2], Vec<egui::Color32>) = match &delta.image {
+            egui::ImageData::Color(image) => (image.size, image.pixels.clone()),
+            egui::ImageData::Font(image) => (image.size, image.srgba_pixels(None).collect()),
+        };
+        let extent = wgpu::Extent3d {
+            width: size[0] as u32,
+            height: size[1] as u32,
+            depth_or_array_layers: 1,
+        };
+
+        let origin = match delta.pos {
+            Some([x, y]) => wgpu::Origin3d {
+                x: x as u32,
+                y: y as u32,
+                z: 0,
+            },
+            None => {
+                // The whole texture is replaced, which may change its size.
+                let texture = device.create_texture(
+                    &(wgpu::TextureDescriptor {
+                        label: Some("clockwork ui texture"),
+                        size: extent,
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                        view_formats: &[],
+                    }),
+                );
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let sampler = match delta.options.magnification {
+                    egui::TextureFilter::Linear => &self.linear_sampler,
+                    egui::TextureFilter::Nearest => &self.nearest_sampler,
+                };
+                let bind_group = device.create_bind_group(
+                    &(wgpu::BindGroupDescriptor {
+                        label: Some("clockwork ui texture bind group"),
+                        layout: &self.texture_bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(&view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(sampler),
+                            },
+                        ],
+                    }),
+                );
+                self.textures.insert(id, (texture, bind_group));
+                wgpu::Origin3d::ZERO
+            }
+        };
+
+        let Some((texture, _)) = self.textures.get(&id) else {
+            return;
+        };
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&pixels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * extent.width),
+                rows_per_image: Some(extent.height),
+            },
+            extent,
+        );
+    }
+}
+
+impl RenderContext {
+    /// Gets the egui context of the debug UI, which is drawn over the surface when the
+    /// frame ends.
+    pub(crate) fn ui_context(&self) -> &egui::Context {
+        &self.ui.context
+    }
+
+    /// Begins a UI frame with the input gathered since the last one.
+    pub(crate) fn begin_ui_frame(&mut self, raw_input: egui::RawInput) {
+        self.ui.context.begin_frame(raw_input);
+        self.ui.frame_running = true;
+    }
+
+    /// Ends the UI frame if it wasn't drawn, such as while minimized, and takes what
+    /// egui asked of the window.
+    pub(crate) fn finish_ui_frame(&mut self) -> Option<egui::PlatformOutput> {
+        if self.ui.frame_running {
+            self.ui.frame_running = false;
+            let output = self.ui.context.end_frame();
+            // Textures still need updating so later frames have them.
+            self.update_ui_textures(&output.textures_delta);
+            self.free_ui_textures(&output.textures_delta);
+            self.ui.platform_output = Some(output.platform_output);
+        }
+        self.ui.platform_output.take()
+    }
+
+    /// Ends the UI frame and draws it over the surface.
+    pub(crate) fn render_ui(&mut self) {
+        if !self.ui.frame_running {
+            return;
+        }
+        self.ui.frame_running = false;
+        let output = self.ui.context.end_frame();
+        self.ui.platform_output = Some(output.platform_output);
+        self.update_ui_textures(&output.textures_delta);
+
+        let primitives = self.ui.context.tessellate(output.shapes);
+        let Some(frame) = &self.frame else {
+            return;
+        };
+        let renderer = self
+            .ui
+            .renderer
+            .as_ref()
+            .expect("created by texture update");
+
+        let pixels_per_point = self.ui.context.pixels_per_point();
+        let target_size = glam::uvec2(self.surface_config.width, self.surface_config.height);
+        self.queue.write_buffer(
+            &renderer.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&UiUniforms {
+                screen_size: (target_size.as_vec2() / pixels_per_point).to_array(),
+                _padding: [0.0; 2],
+            }),
+        );
+
+        // Every mesh shares one vertex and index buffer.
+        let mut vertices: Vec<egui::epaint::Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut draws = Vec::new();
+        for primitive in &primitives {
+            let egui::epaint::Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            let min = (glam::vec2(primitive.clip_rect.min.x, primitive.clip_rect.min.y)
+                * pixels_per_point)
+                .round()
+                .as_uvec2()
+                .min(target_size);
+            let max = (glam::vec2(primitive.clip_rect.max.x, primitive.clip_rect.max.y)
+                * pixels_per_point)
+                .round()
+                .as_uvec2()
+                .clamp(min, target_size);
+            if min.cmpeq(max).any() || mesh.indices.is_empty() {
+                continue;
+            }
+
+            let base_vertex = vertices.len() as i32;
+            let first_index = indices.len() as u32;
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+            draws.push((
+                mesh.texture_id,
+                min,
+                max - min,
+                first_index..indices.len() as u32,
+                base_vertex,
+            ));
+        }
+
+        let mut encoder = self.device.create_command_encoder(
+            &(wgpu::CommandEncoderDescriptor {
+                label: Some("clockwork ui encoder"),
+            }),
+        );
+        if !draws.is_empty() {
+            self.counters.upload(
+                std::mem::size_of_val(vertices.as_slice())
+                    + std::mem::size_of_val(indices.as_slice()),
+            );
+            let vertex_buffer = self.device.create_buffer_init(
+                &(wgpu::util::BufferInitDescriptor {
+                    label: Some("clockwork ui vertex buffer"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+            );
+            let index_buffer = self.device.create_buffer_init(
+                &(wgpu::util::BufferInitDescriptor {
+                    label: Some("clockwork ui index buffer"),
+                    contents: bytemuck::cast_slice(&indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                }),
+            );
+
+            let mut render_pass = encoder.begin_render_pass(
+                &(wgpu::RenderPassDescriptor {
+                    label: Some("clockwork ui pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &frame.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                }),
+            );
+            render_pass.set_pipeline(&renderer.pipeline);
+            render_pass.set_bind_group(0, &renderer.uniform_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            for (texture_id, position, size, indices, base_vertex) in draws {
+                let Some((_, bind_group)) = renderer.textures.get(&texture_id) else {
+                    continue;
+                };
+                render_pass.set_scissor_rect(position.x, position.y, size.x, size.y);
+                render_pass.set_bind_group(1, bind_group, &[]);
+                render_pass.draw_indexed(indices, base_vertex, 0..1);
+                self.counters.draw(1);
+            }
+        }
+
+        self.submit(encoder);
+
+        self.free_ui_textures(&output.textures_delta);
+    }
+
+    fn update_ui_textures(&mut self, textures_delta: &egui::TexturesDelta) {
+        let color_format = self.color_format();
+        let device = &self.device;
+        let renderer = self
+            .ui
+            .renderer
+            .get_or_insert_with(|| UiRenderer::new(device, color_format));
+        for (id, delta) in &textures_delta.set {
+            renderer.set_texture(device, &self.queue, *id, delta);
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let deltas = self.ui.texture_deltas.entry(*id).or_default();
+                if delta.pos.is_none() {
+                    deltas.clear();
+                }
+                deltas.push(delta.clone());
+            }
+        }
+    }
+
+    fn free_ui_textures(&mut self, textures_delta: &egui::TexturesDelta) {
+        #[cfg(not(target_arch = "wasm32"))]
+        for id in &textures_delta.free {
+            self.ui.texture_deltas.remove(id);
+        }
+        if let Some(renderer) = &mut self.ui.renderer {
+            for id in &textures_delta.free {
+                renderer.textures.remove(id);
+            }
+        }
+    }
+
+    /// Takes over the debug UI of a context whose device was lost, uploading its
+    /// textures again, see [RenderContext::recover].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn restore_ui(&mut self, mut ui: UiLayer) {
+        ui.renderer = None;
+        self.ui = ui;
+        let textures_delta = egui::TexturesDelta {
+            set: self
+                .ui
+                .texture_deltas
+                .iter()
+                .flat_map(|(id, deltas)| deltas.iter().map(|delta| (*id, delta.clone())))
+                .collect(),
+            free: Vec::new(),
+        };
+        // Each list starts by replacing the texture whole, so replaying it records
+        // the same list again.
+        self.update_ui_textures(&textures_delta);
+    }
+}
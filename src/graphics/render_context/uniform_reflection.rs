@@ -0,0 +1,231 @@
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+/// Type of a field within a material's uniforms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UniformType {
+    F32,
+    I32,
+    U32,
+    Vec2,
+    Vec3,
+    Vec4,
+    Mat4,
+    /// A type that can't be edited by name, such as an array or nested struct.
+    Other,
+}
+
+/// Field within a material's uniforms, found by reflecting the shader.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UniformField {
+    /// Name of the field in the shader.
+    pub name: String,
+    /// Type of the field.
+    pub ty: UniformType,
+    /// Offset of the field in bytes from the start of the uniforms.
+    pub offset: u32,
+}
+
+/// Value of a field within a material's uniforms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UniformValue {
+    F32(f32),
+    I32(i32),
+    U32(u32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat4(Mat4),
+}
+
+impl UniformField {
+    /// Checks if the field looks like a color, going by its type and name, so tools
+    /// can show a color picker rather than sliders.
+    pub fn is_color(&self) -> bool {
+        let name = self.name.to_lowercase();
+        matches!(self.ty, UniformType::Vec3 | UniformType::Vec4)
+            && (name.contains("color") || name.contains("colour") || name.contains("tint"))
+    }
+}
+
+impl UniformValue {
+    /// Gets the [UniformType] of this value.
+    pub fn ty(&self) -> UniformType {
+        match self {
+            UniformValue::F32(_) => UniformType::F32,
+            UniformValue::I32(_) => UniformType::I32,
+            UniformValue::U32(_) => UniformType::U32,
+            UniformValue::Vec2(_) => UniformType::Vec2,
+            UniformValue::Vec3(_) => UniformType::Vec3,
+            UniformValue::Vec4(_) => UniformType::Vec4,
+            UniformValue::Mat4(_) => UniformType::Mat4,
+        }
+    }
+
+    /// Converts the value to the bytes the shader expects.
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        match self {
+            UniformValue::F32(value) => bytemuck::bytes_of(&value).to_vec(),
+            UniformValue::I32(value) => bytemuck::bytes_of(&value).to_vec(),
+            UniformValue::U32(value) => bytemuck::bytes_of(&value).to_vec(),
+            UniformValue::Vec2(value) => bytemuck::cast_slice(&value.to_array()).to_vec(),
+            UniformValue::Vec3(value) => bytemuck::cast_slice(&value.to_array()).to_vec(),
+            UniformValue::Vec4(value) => bytemuck::cast_slice(&value.to_array()).to_vec(),
+            UniformValue::Mat4(value) => bytemuck::cast_slice(&value.to_cols_array()).to_vec(),
+        }
+    }
+
+    /// Reads a value of the given type from the bytes the shader sees.
+    pub(crate) fn from_bytes(ty: UniformType, bytes: &[u8]) -> Option<Self> {
+        let floats = |count: usize| -> Option<Vec<f32>> {
+            let bytes = bytes.get(..count * 4)?;
+            Some(
+                bytes
+                    .chunks_exact(4)
+                    .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect(),
+            )
+        };
+        let word = || -> Option<[u8; 4]> { bytes.get(..4)?.try_into().ok() };
+
+        Some(match ty {
+            UniformType::F32 => UniformValue::F32(f32::from_le_bytes(word()?)),
+            UniformType::I32 => UniformValue::I32(i32::from_le_bytes(word()?)),
+            UniformType::U32 => UniformValue::U32(u32::from_le_bytes(word()?)),
+            UniformType::Vec2 => UniformValue::Vec2(Vec2::from_slice(&floats(2)?)),
+            UniformType::Vec3 => UniformValue::Vec3(Vec3::from_slice(&floats(3)?)),
+            UniformType::Vec4 => UniformValue::Vec4(Vec4::from_slice(&floats(4)?)),
+            UniformType::Mat4 => UniformValue::Mat4(Mat4::from_cols_slice(&floats(16)?)),
+            UniformType::Other => return None,
+        })
+    }
+}
+
+/// Finds the fields of the uniform struct bound at `@group(group) @binding(binding)`
+/// in WGSL source.
+///
+/// Returns an empty list if the shader doesn't parse or has no such uniform.
+pub(crate) fn reflect_uniform_fields(
+    shader_source: &str,
+    group: u32,
+    binding: u32,
+) -> Vec<UniformField> {
+    let Ok(module) = naga::front::wgsl::parse_str(shader_source) else {
+        return Vec::new();
+    };
+
+    let uniform = module.global_variables.iter().find(|(_, variable)| {
+        variable.space == naga::AddressSpace::Uniform
+            && variable
+                .binding
+                .as_ref()
+                .is_some_and(|resource| resource.group == group && resource.binding == binding)
+    });
+    let Some((_, uniform)) = uniform else {
+        return Vec::new();
+    };
+
+    let naga::TypeInner::Struct { members, .. } = &module.types[uniform.ty].inner else {
+        return Vec::new();
+    };
+
+    members
+        .iter()
+        .map(|member| UniformField {
+            name: member.name.clone().unwrap_or_default(),
+            ty: uniform_type(&module.types[member.ty].inner),
+            offset: member.offset,
+        })
+        .collect()
+}
+
+/// Maps a naga type onto a [UniformType].
+fn uniform_type(inner: &naga::TypeInner) -> UniformType {
+    use naga::{ScalarKind, TypeInner, VectorSize};
+
+    match *inner {
+        TypeInner::Scalar {
+            kind: ScalarKind::Float,
+            width: 4,
+        } => UniformType::F32,
+        TypeInner::Scalar {
+            kind: ScalarKind::Sint,
+            width: 4,
+        } => UniformType::I32,
+        TypeInner::Scalar {
+            kind: ScalarKind::Uint,
+            width: 4,
+        } => UniformType::U32,
+        TypeInner::Vector {
+            size,
+            kind: ScalarKind::Float,
+            width: 4,
+        } => match size {
+            VectorSize::Bi => UniformType::Vec2,
+            VectorSize::Tri => UniformType::Vec3,
+            VectorSize::Quad => UniformType::Vec4,
+        },
+        TypeInner::Matrix {
+            columns: VectorSize::Quad,
+            rows: VectorSize::Quad,
+            width: 4,
+        } => UniformType::Mat4,
+        _ => UniformType::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = r#"
+        struct Dissolve {
+            amount: f32,
+            seed: u32,
+            edge_color: vec4<f32>,
+            warp: mat4x4<f32>,
+        }
+        @group(2) @binding(0)
+        var<uniform> dissolve: Dissolve;
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return dissolve.edge_color * dissolve.amount;
+        }
+    "#;
+
+    #[test]
+    fn test_reflect_fields() {
+        let fields = reflect_uniform_fields(SHADER, 2, 0);
+        let summary: Vec<(&str, UniformType, u32)> = fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.ty, field.offset))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("amount", UniformType::F32, 0),
+                ("seed", UniformType::U32, 4),
+                ("edge_color", UniformType::Vec4, 16),
+                ("warp", UniformType::Mat4, 32),
+            ]
+        );
+        assert!(fields[2].is_color());
+        assert!(!fields[0].is_color());
+    }
+
+    #[test]
+    fn test_reflect_missing_binding() {
+        assert!(reflect_uniform_fields(SHADER, 2, 1).is_empty());
+        assert!(reflect_uniform_fields("not wgsl", 2, 0).is_empty());
+    }
+
+    #[test]
+    fn test_value_round_trip() {
+        let value = UniformValue::Vec3(Vec3::new(1.0, -2.0, 0.5));
+        assert_eq!(
+            UniformValue::from_bytes(UniformType::Vec3, &value.to_bytes()),
+            Some(value)
+        );
+    }
+}
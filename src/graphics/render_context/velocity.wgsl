@@ -0,0 +1,47 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) current: vec4<f32>,
+    @location(1) previous: vec4<f32>,
+};
+
+struct VelocityGlobal {
+    view_projection: mat4x4<f32>,
+    previous_view_projection: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> global: VelocityGlobal;
+
+struct VelocityObject {
+    transform: mat4x4<f32>,
+    previous_transform: mat4x4<f32>,
+    // 1 for objects that blur, 0 for ones that opted out.
+    blurred: f32,
+}
+@group(0) @binding(1)
+var<uniform> object: VelocityObject;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let position = vec4<f32>(in.position, 1.0);
+    out.current = global.view_projection * object.transform * position;
+    out.previous = global.previous_view_projection * object.previous_transform * position;
+    out.clip_position = out.current;
+    return out;
+}
+
+// Writes how far the surface moved on screen since the last frame in uv units, with
+// alpha marking that something was drawn.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let current = in.current.xy / in.current.w;
+    let previous = in.previous.xy / in.previous.w;
+    let velocity = (current - previous) * vec2<f32>(0.5, -0.5) * object.blurred;
+    return vec4<f32>(velocity, 0.0, 1.0);
+}
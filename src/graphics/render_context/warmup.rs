@@ -0,0 +1,397 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc, Arc,
+};
+
+use anyhow::{Context, Result};
+
+use crate::util::repository::ResourceId;
+
+use super::{
+    bloom::BloomPipelines,
+    create_render_pipeline,
+    debug_draw::DebugDrawer,
+    exposure::ExposurePipelines,
+    material_pipeline::{MaterialLayout, MaterialPipeline},
+    motion_blur::{MotionBlurPipeline, VelocityPipeline},
+    post_process::PostProcessor,
+    stylistic::StylisticPipelines,
+    RenderContext, Shared,
+};
+
+/// Pipelines that are otherwise compiled the first time they are used, which can
+/// hitch that frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WarmupPipeline {
+    /// Pipelines of [RenderContext::apply_bloom].
+    Bloom,
+    /// Pipelines of [RenderContext::apply_auto_exposure].
+    AutoExposure,
+    /// Pipelines of [RenderContext::render_velocity] and
+    /// [RenderContext::apply_motion_blur].
+    MotionBlur,
+    /// Pipelines of [RenderContext::apply_stylistic_effects].
+    StylisticEffects,
+    /// Pipeline of [RenderContext::render_debug_draw].
+    DebugDraw,
+}
+
+/// Progress of pipelines compiling in the background, see
+/// [RenderContext::warm_up_pipelines].
+#[derive(Clone, Debug)]
+pub struct PipelineWarmup {
+    total: usize,
+    compiled: Arc<AtomicUsize>,
+}
+
+/// Pipelines for a warmup to compile on a background thread.
+struct WarmupJob {
+    pipelines: Vec<WarmupPipeline>,
+    /// Post processor sharing the context's layout, which post process pipelines are
+    /// added to.
+    post_processor: Option<PostProcessor>,
+    materials: Vec<MaterialJob>,
+}
+
+/// Custom material for a warmup to compile the pipelines of.
+struct MaterialJob {
+    id: ResourceId<MaterialPipeline>,
+    name: String,
+    shader_source: String,
+    pipeline_layout: wgpu::PipelineLayout,
+}
+
+/// Pipelines compiled by a warmup, waiting to be handed to the context.
+pub(crate) struct WarmedPipelines {
+    post_processor: Option<PostProcessor>,
+    debug_drawer: Option<DebugDrawer>,
+    materials: Vec<(
+        ResourceId<MaterialPipeline>,
+        (wgpu::RenderPipeline, wgpu::RenderPipeline),
+    )>,
+}
+
+impl WarmupPipeline {
+    /// Every pipeline that can be warmed up.
+    pub const ALL: [Self; 5] = [
+        Self::Bloom,
+        Self::AutoExposure,
+        Self::MotionBlur,
+        Self::StylisticEffects,
+        Self::DebugDraw,
+    ];
+}
+
+impl PipelineWarmup {
+    /// Gets the number of pipelines compiled so far.
+    pub fn compiled(&self) -> usize {
+        self.compiled.load(Ordering::Acquire)
+    }
+
+    /// Gets the number of pipelines being compiled.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Gets how much of the warmup is done, from 0 to 1, such as for a loading bar.
+    pub fn progress(&self) -> f32 {
+        match self.total {
+            0 => 1.0,
+            total => self.compiled() as f32 / total as f32,
+        }
+    }
+
+    /// Checks if every pipeline has been compiled. They are used from the next frame
+    /// on.
+    pub fn is_finished(&self) -> bool {
+        self.compiled() >= self.total
+    }
+}
+
+impl WarmedPipelines {
+    /// Compiles the pipelines of a job, counting each one as it finishes.
+    fn compile(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        job: WarmupJob,
+        compiled: &AtomicUsize,
+    ) -> Self {
+        let mut warmed = Self {
+            post_processor: job.post_processor,
+            debug_drawer: None,
+            materials: Vec::with_capacity(job.materials.len()),
+        };
+        for pipeline in job.pipelines {
+            if pipeline == WarmupPipeline::DebugDraw {
+                warmed.debug_drawer = Some(DebugDrawer::new(device, color_format));
+                compiled.fetch_add(1, Ordering::Release);
+                continue;
+            }
+            let post_processor = warmed
+                .post_processor
+                .as_mut()
+                .expect("given for post process pipelines");
+            match pipeline {
+                WarmupPipeline::Bloom => {
+                    post_processor.bloom = Some(BloomPipelines::new(device, post_processor));
+                }
+                WarmupPipeline::AutoExposure => {
+                    post_processor.exposure = Some(ExposurePipelines::new(device, post_processor));
+                }
+                WarmupPipeline::MotionBlur => {
+                    post_processor.velocity = Some(VelocityPipeline::new(device));
+                    post_processor.motion_blur =
+                        Some(MotionBlurPipeline::new(device, post_processor));
+                }
+                WarmupPipeline::StylisticEffects => {
+                    post_processor.stylistic =
+                        Some(StylisticPipelines::new(device, post_processor));
+                }
+                WarmupPipeline::DebugDraw => unreachable!("compiled above"),
+            }
+            compiled.fetch_add(1, Ordering::Release);
+        }
+        for material in job.materials {
+            let pipelines = create_render_pipeline(
+                device,
+                color_format,
+                &format!("{} pipeline", material.name),
+                &material.pipeline_layout,
+                wgpu::ShaderSource::Wgsl(material.shader_source.into()),
+                "fs_main",
+            );
+            warmed.materials.push((material.id, pipelines));
+            compiled.fetch_add(1, Ordering::Release);
+        }
+        warmed
+    }
+}
+
+/// Checks that a custom material's shader parses and validates, which would
+/// otherwise only be found out once its pipelines are compiled.
+fn validate_shader(shader_source: &str) -> Result<()> {
+    let module = naga::front::wgsl::parse_str(shader_source)
+        .map_err(|error| anyhow::anyhow!(error.emit_to_string(shader_source)))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)?;
+    Ok(())
+}
+
+/// Removes repeated pipelines, keeping the first of each.
+fn unique_pipelines(pipelines: &[WarmupPipeline]) -> Vec<WarmupPipeline> {
+    let mut unique = Vec::with_capacity(pipelines.len());
+    for pipeline in pipelines {
+        if !unique.contains(pipeline) {
+            unique.push(*pipeline);
+        }
+    }
+    unique
+}
+
+impl RenderContext {
+    /// Starts compiling pipelines on a background thread, so they are ready before
+    /// their first use instead of hitching that frame, such as during a loading
+    /// screen. Pipelines that already exist are skipped.
+    ///
+    /// Compiled pipelines are used from the first frame begun after they finish, and
+    /// the returned [PipelineWarmup] reports the progress.
+    pub fn warm_up_pipelines(&mut self, pipelines: &[WarmupPipeline]) -> Result<PipelineWarmup> {
+        let pipelines: Vec<WarmupPipeline> = unique_pipelines(pipelines)
+            .into_iter()
+            .filter(|pipeline| !self.has_pipeline(*pipeline))
+            .collect();
+        // Post process pipelines are made on the layout of the context's post
+        // processor, so they can join it even if effects were used in the meantime.
+        let color_format = self.color_format();
+        let post_processor = match pipelines
+            .iter()
+            .any(|pipeline| *pipeline != WarmupPipeline::DebugDraw)
+        {
+            true => {
+                let device = &self.device;
+                let post_processor = self
+                    .post_processor
+                    .get_or_insert_with(|| PostProcessor::new(device, color_format));
+                Some(post_processor.sharing_layout())
+            }
+            false => None,
+        };
+        self.start_warmup(WarmupJob {
+            pipelines,
+            post_processor,
+            materials: Vec::new(),
+        })
+    }
+
+    /// Registers custom materials like [RenderContext::register_material], but
+    /// compiles their pipelines on a background thread, such as for every permutation
+    /// of a material during a loading screen.
+    ///
+    /// The materials can be used and their uniforms set right away, and are drawn with
+    /// the default pipeline until the returned [PipelineWarmup] finishes. Returns an
+    /// error if a shader fails to compile, in which case none of the materials are
+    /// registered. Other errors, such as a shader not matching its layout, are
+    /// reported as uncaptured gpu errors once compiled.
+    pub fn warm_up_materials(
+        &mut self,
+        materials: &[(&str, MaterialLayout)],
+    ) -> Result<(Vec<ResourceId<MaterialPipeline>>, PipelineWarmup)> {
+        for (shader_source, layout) in materials {
+            let name = layout.name.unwrap_or("clockwork material");
+            validate_shader(shader_source)
+                .with_context(|| format!("failed to create material pipeline {name}"))?;
+        }
+
+        let jobs = materials
+            .iter()
+            .map(|(shader_source, layout)| {
+                let name = layout.name.unwrap_or("clockwork material");
+                MaterialJob {
+                    id: self.add_material(shader_source, *layout, None),
+                    name: name.to_string(),
+                    shader_source: shader_source.to_string(),
+                    pipeline_layout: self.create_material_pipeline_layout(name, *layout),
+                }
+            })
+            .collect::<Vec<_>>();
+        let ids = jobs.iter().map(|job| job.id).collect();
+        let warmup = self.start_warmup(WarmupJob {
+            pipelines: Vec::new(),
+            post_processor: None,
+            materials: jobs,
+        })?;
+        Ok((ids, warmup))
+    }
+
+    /// Starts compiling a job's pipelines, on a background thread where there is one.
+    fn start_warmup(&mut self, job: WarmupJob) -> Result<PipelineWarmup> {
+        let warmup = PipelineWarmup {
+            total: job.pipelines.len() + job.materials.len(),
+            compiled: Arc::new(AtomicUsize::new(0)),
+        };
+        if warmup.total == 0 {
+            return Ok(warmup);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let device = Shared::clone(&self.device);
+        let color_format = self.color_format();
+        let compiled = Arc::clone(&warmup.compiled);
+        let compile = move || {
+            let warmed = WarmedPipelines::compile(&device, color_format, job, &compiled);
+            // The context may have been dropped in the meantime.
+            let _ = sender.send(warmed);
+        };
+
+        // Browsers have no threads to compile on, so the pipelines are compiled now.
+        #[cfg(target_arch = "wasm32")]
+        compile();
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::Builder::new()
+            .name("clockwork pipeline warmup".to_string())
+            .spawn(compile)
+            .context("failed to start pipeline warmup thread")?;
+        self.pending_warmups.push(receiver);
+        Ok(warmup)
+    }
+
+    /// Checks if the given pipeline has already been created.
+    fn has_pipeline(&self, pipeline: WarmupPipeline) -> bool {
+        let post_processor = self.post_processor.as_ref();
+        match pipeline {
+            WarmupPipeline::Bloom => post_processor.is_some_and(|post| post.bloom.is_some()),
+            WarmupPipeline::AutoExposure => {
+                post_processor.is_some_and(|post| post.exposure.is_some())
+            }
+            WarmupPipeline::MotionBlur => post_processor
+                .is_some_and(|post| post.velocity.is_some() && post.motion_blur.is_some()),
+            WarmupPipeline::StylisticEffects => {
+                post_processor.is_some_and(|post| post.stylistic.is_some())
+            }
+            WarmupPipeline::DebugDraw => self.debug_drawer.is_some(),
+        }
+    }
+
+    /// Hands over pipelines from finished warmups.
+    pub(crate) fn receive_warmed_pipelines(&mut self) {
+        let mut pending = std::mem::take(&mut self.pending_warmups);
+        pending.retain(|receiver| match receiver.try_recv() {
+            Ok(warmed) => {
+                // Effects used in the meantime compiled their own pipelines, which are
+                // kept over the warmed ones.
+                if let (Some(post_processor), Some(warmed)) =
+                    (&mut self.post_processor, warmed.post_processor)
+                {
+                    post_processor.merge(warmed);
+                }
+                // One created on demand in the meantime works the same.
+                if self.debug_drawer.is_none() {
+                    self.debug_drawer = warmed.debug_drawer;
+                }
+                for (id, pipelines) in warmed.materials {
+                    if let Some(material_pipeline) = self.material_pipelines.get_mut(id) {
+                        material_pipeline.pipelines = Some(pipelines);
+                    }
+                }
+                false
+            }
+            Err(mpsc::TryRecvError::Empty) => true,
+            Err(mpsc::TryRecvError::Disconnected) => false,
+        });
+        self.pending_warmups = pending;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_progress() {
+        let warmup = PipelineWarmup {
+            total: 4,
+            compiled: Arc::new(AtomicUsize::new(0)),
+        };
+        assert_eq!(warmup.progress(), 0.0);
+        warmup.compiled.fetch_add(1, Ordering::Release);
+        assert_eq!(warmup.progress(), 0.25);
+        assert!(!warmup.is_finished());
+        warmup.compiled.store(4, Ordering::Release);
+        assert!(warmup.is_finished());
+
+        let empty = PipelineWarmup {
+            total: 0,
+            compiled: Arc::new(AtomicUsize::new(0)),
+        };
+        assert!(empty.is_finished());
+        assert_eq!(empty.progress(), 1.0);
+    }
+
+    #[test]
+    fn test_validate_shader() {
+        assert!(validate_shader(
+            "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4(1.0); }"
+        )
+        .is_ok());
+        assert!(
+            validate_shader("@fragment fn fs_main() -> @location(0) vec4<f32> { return 1; }")
+                .is_err()
+        );
+        assert!(validate_shader("fn fs_main( {").is_err());
+    }
+
+    #[test]
+    fn test_unique_pipelines() {
+        assert_eq!(
+            unique_pipelines(&[
+                WarmupPipeline::DebugDraw,
+                WarmupPipeline::Bloom,
+                WarmupPipeline::DebugDraw,
+            ]),
+            vec![WarmupPipeline::DebugDraw, WarmupPipeline::Bloom]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+use crate::util::frustum::BoundingSphere;
+
+/// Joints a vertex of a skinned mesh follows and how much it follows each, see
+/// [crate::graphics::RenderContext::load_skinned_mesh].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SkinWeights {
+    /// Indices of the joints in the mesh's [Skeleton].
+    pub joints: [u32; 4],
+    /// Weight of each joint, which should add up to 1.
+    pub weights: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for SkinWeights {}
+unsafe impl bytemuck::Pod for SkinWeights {}
+
+/// Translation, rotation and scale of a joint relative to its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+/// Joint, or bone, of a [Skeleton].
+#[derive(Clone, Debug)]
+pub struct Joint {
+    /// Name of the joint in the file, if it has one.
+    pub name: Option<String>,
+    /// Index of the joint's parent in [Skeleton::joints], or `None` for a root joint.
+    pub parent: Option<usize>,
+    /// Transformation from the model into the joint's space in the bind pose, which
+    /// the mesh was modelled in.
+    pub inverse_bind: Mat4,
+    /// Transformation of the joint when it isn't animated.
+    pub rest: JointTransform,
+}
+
+/// Hierarchy of joints that skinned meshes deform with.
+#[derive(Clone, Debug, Default)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    /// Transformation of the root joints' parent relative to the model.
+    pub root: Mat4,
+}
+
+/// Transformation of each joint of a [Skeleton], such as sampled from an
+/// [AnimationClip].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pose {
+    /// Transformation of each joint, in the order of [Skeleton::joints].
+    pub joints: Vec<JointTransform>,
+}
+
+/// Keyframed animation of the joints of a [Skeleton].
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    /// Name of the animation in the file, if it has one.
+    pub name: Option<String>,
+    /// Index of the skeleton the clip animates in [crate::graphics::ModelData::skeletons].
+    pub skeleton: usize,
+    /// Time of the last keyframe, in seconds.
+    pub duration: f32,
+    pub channels: Vec<AnimationChannel>,
+}
+
+/// Keyframes of one property of one joint in an [AnimationClip].
+#[derive(Clone, Debug)]
+pub struct AnimationChannel {
+    /// Index of the joint in [Skeleton::joints].
+    pub joint: usize,
+    pub property: AnimationProperty,
+    pub interpolation: Interpolation,
+    /// Time of each keyframe in seconds, in increasing order.
+    pub times: Vec<f32>,
+    /// Value of each keyframe, with rotations as quaternions in `xyzw` order and the
+    /// `w` of translations and scales unused.
+    ///
+    /// [Interpolation::CubicSpline] keyframes have three values each: the in tangent,
+    /// the value and the out tangent.
+    pub values: Vec<Vec4>,
+}
+
+/// Property of a joint animated by an [AnimationChannel].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationProperty {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+/// How an [AnimationChannel] changes between keyframes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Holds each keyframe until the next.
+    Step,
+    /// Blends linearly between keyframes, along the shortest arc for rotations.
+    Linear,
+    /// Follows a curve through the keyframes shaped by their tangents.
+    CubicSpline,
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl JointTransform {
+    /// Gets the transformation as a matrix.
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    /// Blends between two transformations, such as to cross fade animations.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+impl Skeleton {
+    /// Gets the pose with every joint at rest.
+    pub fn rest_pose(&self) -> Pose {
+        Pose {
+            joints: self.joints.iter().map(|joint| joint.rest).collect(),
+        }
+    }
+
+    /// Gets the matrix skinned vertices are transformed by for each joint in a pose,
+    /// which takes them from the bind pose into the pose. Joints missing from the pose
+    /// are at rest.
+    pub fn joint_matrices(&self, pose: &Pose) -> Vec<Mat4> {
+        let mut globals = vec![None; self.joints.len()];
+        (0..self.joints.len())
+            .map(|index| self.global(index, pose, &mut globals) * self.joints[index].inverse_bind)
+            .collect()
+    }
+
+    /// Gets the transformation of a joint relative to the model, caching it along with
+    /// its ancestors', as joints can come before their parents.
+    fn global(&self, index: usize, pose: &Pose, globals: &mut [Option<Mat4>]) -> Mat4 {
+        if let Some(global) = globals[index] {
+            return global;
+        }
+        let joint = &self.joints[index];
+        let parent = match joint.parent {
+            Some(parent) => self.global(parent, pose, globals),
+            None => self.root,
+        };
+        let global = parent * pose.joints.get(index).unwrap_or(&joint.rest).matrix();
+        globals[index] = Some(global);
+        global
+    }
+}
+
+impl Pose {
+    /// Blends between two poses of the same skeleton, such as to cross fade
+    /// animations.
+    pub fn lerp(&self, other: &Pose, t: f32) -> Pose {
+        Pose {
+            joints: self
+                .joints
+                .iter()
+                .zip(other.joints.iter())
+                .map(|(a, b)| a.lerp(b, t))
+                .collect(),
+        }
+    }
+}
+
+impl AnimationClip {
+    /// Sets the joints the clip animates in a pose to their transformation at a time in
+    /// seconds, leaving the others as they are.
+    ///
+    /// Times outside the clip hold its first or last keyframes, so wrap the time with
+    /// [f32::rem_euclid] and [AnimationClip::duration] to loop.
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        for channel in self.channels.iter() {
+            let (Some(joint), Some(value)) =
+                (pose.joints.get_mut(channel.joint), channel.sample(time))
+            else {
+                continue;
+            };
+            match channel.property {
+                AnimationProperty::Translation => joint.translation = value.truncate(),
+                AnimationProperty::Rotation => joint.rotation = Quat::from_vec4(value).normalize(),
+                AnimationProperty::Scale => joint.scale = value.truncate(),
+            }
+        }
+    }
+}
+
+impl AnimationChannel {
+    /// Gets the value of the channel at a time in seconds, or `None` if it has no
+    /// keyframes.
+    pub fn sample(&self, time: f32) -> Option<Vec4> {
+        let stride = match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+        let value = |key: usize| self.values.get(key * stride + stride / 2).copied();
+
+        let next = self.times.partition_point(|&key_time| key_time <= time);
+        if next == 0 {
+            return value(0);
+        }
+        if next == self.times.len() {
+            return value(next - 1);
+        }
+        let previous = next - 1;
+        let span = self.times[next] - self.times[previous];
+        let t = (time - self.times[previous]) / span;
+        let (a, b) = (value(previous)?, value(next)?);
+
+        let value = match self.interpolation {
+            Interpolation::Step => a,
+            Interpolation::Linear => match self.property {
+                AnimationProperty::Rotation => {
+                    Quat::from_vec4(a).slerp(Quat::from_vec4(b), t).into()
+                }
+                _ => a.lerp(b, t),
+            },
+            Interpolation::CubicSpline => {
+                let out_tangent = *self.values.get(previous * 3 + 2)? * span;
+                let in_tangent = *self.values.get(next * 3)? * span;
+                let (t2, t3) = (t * t, t * t * t);
+                let value = a * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + out_tangent * (t3 - 2.0 * t2 + t)
+                    + b * (-2.0 * t3 + 3.0 * t2)
+                    + in_tangent * (t3 - t2);
+                match self.property {
+                    AnimationProperty::Rotation => value.normalize_or_zero(),
+                    _ => value,
+                }
+            }
+        };
+        Some(value)
+    }
+}
+
+/// Gets a sphere around a skinned mesh in a pose from a sphere around it in the bind
+/// pose, as every skinned vertex lies between its joints' transformations of it.
+pub(crate) fn skinned_bounds(
+    bind_bounds: BoundingSphere,
+    joint_matrices: &[Mat4],
+) -> BoundingSphere {
+    let corners = joint_matrices.iter().flat_map(|&matrix| {
+        let sphere = bind_bounds.transformed(matrix);
+        [
+            sphere.center - Vec3::splat(sphere.radius),
+            sphere.center + Vec3::splat(sphere.radius),
+        ]
+    });
+    match joint_matrices.is_empty() {
+        true => bind_bounds,
+        false => BoundingSphere::from_points(corners),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets a root joint one unit up with a child another unit up, where the child
+    /// comes first so its parent is resolved out of order.
+    fn two_joints() -> Skeleton {
+        let joint = |parent, bind_position: Vec3| Joint {
+            name: None,
+            parent,
+            inverse_bind: Mat4::from_translation(-bind_position),
+            rest: JointTransform {
+                translation: Vec3::Y,
+                ..Default::default()
+            },
+        };
+        Skeleton {
+            joints: vec![joint(Some(1), Vec3::Y * 2.0), joint(None, Vec3::Y)],
+            root: Mat4::IDENTITY,
+        }
+    }
+
+    #[test]
+    fn test_joint_matrices() {
+        let skeleton = two_joints();
+        let mut pose = skeleton.rest_pose();
+        for matrix in skeleton.joint_matrices(&pose) {
+            assert!(matrix.abs_diff_eq(Mat4::IDENTITY, 1e-6));
+        }
+
+        // Turning the root turns the child around it too.
+        pose.joints[1].rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        let matrices = skeleton.joint_matrices(&pose);
+        let tip = matrices[0].transform_point3(Vec3::new(0.0, 2.0, 0.0));
+        assert!(tip.abs_diff_eq(Vec3::new(-1.0, 1.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn test_sample_interpolation() {
+        let mut channel = AnimationChannel {
+            joint: 0,
+            property: AnimationProperty::Translation,
+            interpolation: Interpolation::Linear,
+            times: vec![1.0, 3.0],
+            values: vec![Vec4::ZERO, Vec4::new(4.0, 0.0, 0.0, 0.0)],
+        };
+        assert_eq!(channel.sample(0.0), Some(Vec4::ZERO));
+        assert_eq!(channel.sample(2.0), Some(Vec4::new(2.0, 0.0, 0.0, 0.0)));
+        assert_eq!(channel.sample(5.0), Some(Vec4::new(4.0, 0.0, 0.0, 0.0)));
+
+        channel.interpolation = Interpolation::Step;
+        assert_eq!(channel.sample(2.9), Some(Vec4::ZERO));
+
+        // Flat tangents ease in and out, passing the midpoint halfway.
+        channel.interpolation = Interpolation::CubicSpline;
+        channel.values = vec![
+            Vec4::ZERO,
+            Vec4::ZERO,
+            Vec4::ZERO,
+            Vec4::ZERO,
+            Vec4::new(4.0, 0.0, 0.0, 0.0),
+            Vec4::ZERO,
+        ];
+        assert_eq!(channel.sample(2.0), Some(Vec4::new(2.0, 0.0, 0.0, 0.0)));
+        assert!(channel.sample(1.5).unwrap().x < 1.0);
+
+        let clip = AnimationClip {
+            name: None,
+            skeleton: 0,
+            duration: 3.0,
+            channels: vec![channel],
+        };
+        let mut pose = two_joints().rest_pose();
+        clip.sample(3.0, &mut pose);
+        assert_eq!(pose.joints[0].translation, Vec3::new(4.0, 0.0, 0.0));
+        assert_eq!(pose.joints[1].translation, Vec3::Y);
+    }
+}
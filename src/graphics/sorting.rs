@@ -0,0 +1,194 @@
+use std::cmp::Ordering;
+
+use glam::Mat4;
+
+use super::RenderOperation;
+
+/// Key 2D render operations are sorted by, drawn from lowest to highest.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SortKey {
+    /// Layer to draw on. Every layer is drawn over all lower layers.
+    pub layer: i32,
+    /// Order within the layer.
+    pub order: f32,
+}
+
+/// Something that can be sorted by a [SpriteSorter].
+#[derive(Clone)]
+enum Sortable {
+    Operation(SortKey, RenderOperation),
+    Group(SortingGroup),
+}
+
+/// Operations that sort together as a single unit against everything outside the group,
+/// such as the sprites making up a character.
+///
+/// Children are sorted among themselves relative to the group, and are transformed by
+/// the group's transform.
+#[derive(Clone)]
+pub struct SortingGroup {
+    /// Where the group as a whole sorts.
+    pub key: SortKey,
+    /// Transformation applied to every child.
+    pub transform: Mat4,
+    children: Vec<Sortable>,
+}
+
+/// Collects 2D render operations and sorting groups, then orders them for drawing.
+///
+/// Sorting is stable, so operations with equal keys keep the order they were added in.
+/// Since the depth test still applies, operations being sorted should share a depth.
+#[derive(Clone, Default)]
+pub struct SpriteSorter {
+    items: Vec<Sortable>,
+}
+
+impl SortKey {
+    /// Creates a new [SortKey].
+    pub fn new(layer: i32, order: f32) -> Self {
+        Self { layer, order }
+    }
+
+    /// Creates a [SortKey] where things lower on screen are drawn over things higher up,
+    /// as is typical for top down games.
+    pub fn y_sorted(layer: i32, y: f32) -> Self {
+        Self { layer, order: -y }
+    }
+
+    fn cmp(&self, other: &SortKey) -> Ordering {
+        self.layer
+            .cmp(&other.layer)
+            .then(self.order.total_cmp(&other.order))
+    }
+}
+
+impl Sortable {
+    fn key(&self) -> &SortKey {
+        match self {
+            Sortable::Operation(key, _) => key,
+            Sortable::Group(group) => &group.key,
+        }
+    }
+}
+
+impl SortingGroup {
+    /// Creates a new empty [SortingGroup].
+    pub fn new(key: SortKey, transform: Mat4) -> Self {
+        Self {
+            key,
+            transform,
+            children: Vec::new(),
+        }
+    }
+
+    /// Adds an operation to the group, sorted by `key` relative to the group's other
+    /// children.
+    pub fn push(&mut self, key: SortKey, operation: RenderOperation) {
+        self.children.push(Sortable::Operation(key, operation));
+    }
+
+    /// Adds a nested group.
+    pub fn push_group(&mut self, group: SortingGroup) {
+        self.children.push(Sortable::Group(group));
+    }
+}
+
+impl SpriteSorter {
+    /// Creates a new empty [SpriteSorter].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an operation.
+    pub fn push(&mut self, key: SortKey, operation: RenderOperation) {
+        self.items.push(Sortable::Operation(key, operation));
+    }
+
+    /// Adds a group that sorts as a single unit.
+    pub fn push_group(&mut self, group: SortingGroup) {
+        self.items.push(Sortable::Group(group));
+    }
+
+    /// Removes everything added so the sorter can be reused for the next frame.
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Gets every operation in the order it should be drawn.
+    pub fn sorted(&self) -> Vec<RenderOperation> {
+        let mut operations = Vec::new();
+        flatten(&self.items, Mat4::IDENTITY, &mut operations);
+        operations
+    }
+}
+
+fn flatten(items: &[Sortable], transform: Mat4, operations: &mut Vec<RenderOperation>) {
+    let mut items: Vec<&Sortable> = items.iter().collect();
+    items.sort_by(|a, b| a.key().cmp(b.key()));
+
+    for item in items {
+        match item {
+            Sortable::Operation(_, operation) => operations.push(RenderOperation {
+                transform: transform * operation.transform,
+                ..*operation
+            }),
+            Sortable::Group(group) => {
+                flatten(&group.children, transform * group.transform, operations)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec3, Vec4};
+
+    use crate::util::repository::ResourceId;
+
+    use super::*;
+
+    /// Creates an operation identified by its x translation.
+    fn operation(id: f32) -> RenderOperation {
+        RenderOperation::colored_mesh(
+            Mat4::from_translation(Vec3::new(id, 0.0, 0.0)),
+            ResourceId::new(0),
+            Vec4::ONE,
+        )
+    }
+
+    fn ids(operations: &[RenderOperation]) -> Vec<f32> {
+        operations
+            .iter()
+            .map(|operation| operation.transform.w_axis.x)
+            .collect()
+    }
+
+    #[test]
+    fn test_groups_sort_together() {
+        let mut character = SortingGroup::new(SortKey::new(0, 5.0), Mat4::IDENTITY);
+        character.push(SortKey::new(0, 100.0), operation(2.0));
+        character.push(SortKey::new(0, -100.0), operation(1.0));
+
+        let mut sorter = SpriteSorter::new();
+        sorter.push(SortKey::new(0, 10.0), operation(4.0));
+        sorter.push_group(character);
+        sorter.push(SortKey::new(0, 0.0), operation(0.0));
+        sorter.push(SortKey::new(-1, 50.0), operation(-1.0));
+
+        // The group's children stay together despite their own keys.
+        assert_eq!(ids(&sorter.sorted()), vec![-1.0, 0.0, 1.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn test_group_transform() {
+        let mut group = SortingGroup::new(
+            SortKey::default(),
+            Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0)),
+        );
+        group.push(SortKey::default(), operation(1.0));
+
+        let mut sorter = SpriteSorter::new();
+        sorter.push_group(group);
+        assert_eq!(ids(&sorter.sorted()), vec![11.0]);
+    }
+}
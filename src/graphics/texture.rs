@@ -1,49 +1,388 @@
 use glam::UVec2;
-use wgpu::util::DeviceExt;
+use image::{imageops::FilterType, RgbaImage};
 
 pub struct Texture {
     #[allow(unused)]
     pub(crate) texture: wgpu::Texture,
     pub(crate) view: wgpu::TextureView,
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) size: UVec2,
+    /// How the texture is sampled when drawn.
+    pub(crate) sampler: SamplerSettings,
+    /// How the texture's pixels were prepared when it was loaded.
+    pub(crate) load_options: TextureLoadOptions,
+    /// Pixels of each mip level as uploaded, kept to upload them again after the
+    /// device is lost. Render targets have none and come back cleared.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) contents: Option<Vec<Vec<u8>>>,
+    /// Name shown by graphics debuggers.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) label: String,
+}
+
+/// How texels are blended when a texture is drawn larger or smaller than its size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    /// Uses the closest texel, keeping pixel art crisp.
+    #[default]
+    Nearest,
+    /// Blends between neighbouring texels, for smooth textures.
+    Linear,
+}
+
+/// What happens when a texture is sampled outside of the 0 to 1 uv range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    /// Stretches the texels on the edge.
+    #[default]
+    Clamp,
+    /// Tiles the texture.
+    Repeat,
+    /// Tiles the texture, flipping every other tile.
+    MirrorRepeat,
+}
+
+/// Describes how a texture is sampled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SamplerSettings {
+    /// Filter used when the texture is magnified or minified.
+    pub filter: TextureFilter,
+    /// Wrapping along the u axis.
+    pub wrap_u: TextureWrap,
+    /// Wrapping along the v axis.
+    pub wrap_v: TextureWrap,
+    /// Whether to generate mipmaps when loading the texture, so it doesn't shimmer when
+    /// drawn small or far away.
+    pub mipmaps: bool,
+}
+
+/// Describes how a texture's pixels are prepared when it is loaded, which unlike its
+/// [SamplerSettings] can't be changed afterwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureLoadOptions {
+    /// Whether to multiply colors by their alpha when loading the texture, which is the
+    /// default. Textures are blended as premultiplied, so images with straight alpha get
+    /// dark fringes around their edges without this.
+    pub premultiply_alpha: bool,
+}
+
+impl Default for TextureLoadOptions {
+    fn default() -> Self {
+        Self {
+            premultiply_alpha: true,
+        }
+    }
+}
+
+impl TextureLoadOptions {
+    /// Creates [TextureLoadOptions] for data such as normal maps, whose pixels are
+    /// loaded as is.
+    pub(crate) fn data() -> Self {
+        Self {
+            premultiply_alpha: false,
+        }
+    }
+
+    /// Sets whether colors are multiplied by their alpha when loading, where `false`
+    /// suits images that are already premultiplied.
+    pub fn with_premultiplied_alpha(mut self, premultiply_alpha: bool) -> Self {
+        self.premultiply_alpha = premultiply_alpha;
+        self
+    }
+}
+
+impl SamplerSettings {
+    /// Creates [SamplerSettings] suited for pixel art, which is the default.
+    pub fn nearest() -> Self {
+        Self::default()
+    }
+
+    /// Creates [SamplerSettings] with smooth filtering and mipmaps, suited for 3D models.
+    pub fn linear() -> Self {
+        Self {
+            filter: TextureFilter::Linear,
+            mipmaps: true,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the wrapping along both axes.
+    pub fn with_wrap(mut self, wrap: TextureWrap) -> Self {
+        self.wrap_u = wrap;
+        self.wrap_v = wrap;
+        self
+    }
+
+    /// Sets whether mipmaps are generated.
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub(crate) fn create_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        let filter = match self.filter {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        };
+        let address_mode = |wrap| match wrap {
+            TextureWrap::Clamp => wgpu::AddressMode::ClampToEdge,
+            TextureWrap::Repeat => wgpu::AddressMode::Repeat,
+            TextureWrap::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        };
+
+        device.create_sampler(
+            &(wgpu::SamplerDescriptor {
+                label: Some("clockwork sampler"),
+                address_mode_u: address_mode(self.wrap_u),
+                address_mode_v: address_mode(self.wrap_v),
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: filter,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: if self.mipmaps { 32.0 } else { 0.0 },
+                compare: None,
+                anisotropy_clamp: 1,
+                border_color: None,
+            }),
+        )
+    }
+}
+
+/// Converts an sRGB encoded channel to linear.
+fn srgb_to_linear(channel: f32) -> f32 {
+    match channel <= 0.04045 {
+        true => channel / 12.92,
+        false => ((channel + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+/// Converts a linear channel to sRGB encoded.
+pub(crate) fn linear_to_srgb(channel: f32) -> f32 {
+    match channel <= 0.0031308 {
+        true => channel * 12.92,
+        false => 1.055 * channel.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/// Multiplies the colors of sRGB encoded RGBA8 pixels by their alpha.
+///
+/// The multiplication is done in linear space, since that's where the gpu blends
+/// after decoding the texture.
+pub(crate) fn premultiply_alpha(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+        if alpha == u8::MAX {
+            continue;
+        }
+        let alpha = alpha as f32 / 255.0;
+        for channel in &mut pixel[..3] {
+            let linear = srgb_to_linear(*channel as f32 / 255.0) * alpha;
+            *channel = (linear_to_srgb(linear) * 255.0).round() as u8;
+        }
+    }
+}
+
+/// Gets the number of mip levels in a full mip chain for a texture of the given size.
+pub(crate) fn mip_level_count(size: UVec2) -> u32 {
+    u32::BITS - size.max_element().max(1).leading_zeros()
 }
 
 impl Texture {
+    /// Label of loaded textures without a name, which graphics debuggers show.
+    pub(crate) const DEFAULT_LABEL: &'static str = "clockwork texture";
+
     pub(crate) fn load(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8],
+        sampler: SamplerSettings,
+        load_options: TextureLoadOptions,
+        label: &str,
     ) -> anyhow::Result<Texture> {
-        let image = image::load_from_memory(bytes)?;
-        let bytes = image.to_rgba8();
-
-        let texture = device.create_texture_with_data(
+        let image = image::load_from_memory(bytes)?.to_rgba8();
+        Ok(Self::from_rgba(
+            device,
             queue,
+            UVec2::new(image.width(), image.height()),
+            &image,
+            sampler,
+            load_options,
+            label,
+        ))
+    }
+
+    pub(crate) fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: UVec2,
+        rgba: &[u8],
+        sampler: SamplerSettings,
+        load_options: TextureLoadOptions,
+        label: &str,
+    ) -> Texture {
+        let mip_levels = prepare_mip_levels(size, rgba, sampler, load_options);
+        Texture {
+            sampler,
+            load_options,
+            ..Self::from_mip_levels(
+                device,
+                queue,
+                size,
+                mip_levels,
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                label,
+            )
+        }
+    }
+
+    /// Creates a texture holding data rather than colors, such as a normal map, which
+    /// is sampled without sRGB decoding or premultiplying.
+    pub(crate) fn from_data_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: UVec2,
+        rgba: &[u8],
+        sampler: SamplerSettings,
+        label: &str,
+    ) -> Texture {
+        let load_options = TextureLoadOptions::data();
+        let mip_levels = prepare_mip_levels(size, rgba, sampler, load_options);
+        Texture {
+            sampler,
+            load_options,
+            ..Self::from_mip_levels(
+                device,
+                queue,
+                size,
+                mip_levels,
+                wgpu::TextureFormat::Rgba8Unorm,
+                label,
+            )
+        }
+    }
+
+    /// Creates a texture from pixels prepared by [prepare_mip_levels]. It has the
+    /// default sampler and load options, which callers replace with the ones the pixels
+    /// were prepared with.
+    pub(crate) fn from_mip_levels(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: UVec2,
+        mip_levels: Vec<Vec<u8>>,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Texture {
+        let texture = device.create_texture(
             &(wgpu::TextureDescriptor {
-                label: None,
+                label: Some(label),
                 size: wgpu::Extent3d {
-                    width: image.width(),
-                    height: image.height(),
+                    width: size.x,
+                    height: size.y,
                     depth_or_array_layers: 1,
                 },
-                mip_level_count: 1,
+                mip_level_count: mip_levels.len() as u32,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             }),
-            &bytes,
         );
 
+        for (mip_level, rgba) in mip_levels.iter().enumerate() {
+            let level_size = (size >> mip_level as u32).max(UVec2::ONE);
+            write_mip_level(queue, &texture, mip_level as u32, 0, level_size, rgba);
+        }
+
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        Ok(Texture { texture, view })
+        Texture {
+            texture,
+            view,
+            format,
+            size,
+            sampler: SamplerSettings::default(),
+            load_options: TextureLoadOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            contents: Some(mip_levels),
+            #[cfg(not(target_arch = "wasm32"))]
+            label: label.to_string(),
+        }
+    }
+
+    /// Creates the texture again on a new device, after the one it was created on
+    /// was lost.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn restore(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
+        match &self.contents {
+            Some(mip_levels) => Texture {
+                sampler: self.sampler,
+                load_options: self.load_options,
+                ..Self::from_mip_levels(
+                    device,
+                    queue,
+                    self.size,
+                    mip_levels.clone(),
+                    self.format,
+                    &self.label,
+                )
+            },
+            None if self.format == wgpu::TextureFormat::Depth32Float => {
+                Self::create_depth_texture(device, self.size)
+            }
+            None => Texture {
+                sampler: self.sampler,
+                load_options: self.load_options,
+                ..Self::create_render_target(device, self.size, self.format)
+            },
+        }
+    }
+
+    pub(crate) fn create_render_target(
+        device: &wgpu::Device,
+        size: UVec2,
+        format: wgpu::TextureFormat,
+    ) -> Texture {
+        let texture = device.create_texture(
+            &(wgpu::TextureDescriptor {
+                label: Some("clockwork render target"),
+                size: wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }),
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Texture {
+            texture,
+            view,
+            format,
+            size,
+            sampler: SamplerSettings::default(),
+            load_options: TextureLoadOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            contents: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            label: "clockwork render target".to_string(),
+        }
     }
 
     pub(crate) fn create_depth_texture(device: &wgpu::Device, size: UVec2) -> Texture {
         let texture = device.create_texture(
             &(wgpu::TextureDescriptor {
-                label: None,
+                label: Some("clockwork depth texture"),
                 size: wgpu::Extent3d {
                     width: size.x,
                     height: size.y,
@@ -60,6 +399,182 @@ impl Texture {
         );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        Texture { texture, view }
+        Texture {
+            texture,
+            view,
+            format: wgpu::TextureFormat::Depth32Float,
+            size,
+            sampler: SamplerSettings::default(),
+            load_options: TextureLoadOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            contents: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            label: "clockwork depth texture".to_string(),
+        }
+    }
+}
+
+/// Gets the pixels of each mip level of a texture, premultiplied as `load_options`
+/// and downsampled as `sampler` asks for.
+pub(crate) fn prepare_mip_levels(
+    size: UVec2,
+    rgba: &[u8],
+    sampler: SamplerSettings,
+    load_options: TextureLoadOptions,
+) -> Vec<Vec<u8>> {
+    let mut base = rgba.to_vec();
+    if load_options.premultiply_alpha {
+        premultiply_alpha(&mut base);
+    }
+    if !sampler.mipmaps {
+        return vec![base];
+    }
+
+    // Each level is downsampled from the previous one.
+    let mut level = RgbaImage::from_raw(size.x, size.y, base.clone())
+        .expect("texture size should match its pixels");
+    let mut mip_levels = vec![base];
+    for mip_level in 1..mip_level_count(size) {
+        let level_size = (size >> mip_level).max(UVec2::ONE);
+        level = image::imageops::resize(&level, level_size.x, level_size.y, FilterType::Triangle);
+        mip_levels.push(level.to_vec());
+    }
+    mip_levels
+}
+
+/// Copies the RGBA8 pixels of a region into an image `width` pixels wide, where
+/// `offset` is the region's top left corner.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn copy_region(image: &mut [u8], width: u32, offset: UVec2, size: UVec2, region: &[u8]) {
+    let row_length = size.x as usize * 4;
+    for row in 0..size.y as usize {
+        let start = ((offset.y as usize + row) * width as usize + offset.x as usize) * 4;
+        image[start..start + row_length]
+            .copy_from_slice(&region[row * row_length..(row + 1) * row_length]);
+    }
+}
+
+/// Uploads the pixels of a single mip level of one layer.
+pub(crate) fn write_mip_level(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    mip_level: u32,
+    layer: u32,
+    size: UVec2,
+    rgba: &[u8],
+) {
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: 0,
+                z: layer,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(size.x * 4),
+            rows_per_image: Some(size.y),
+        },
+        wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mip_level_count() {
+        assert_eq!(mip_level_count(UVec2::new(1, 1)), 1);
+        assert_eq!(mip_level_count(UVec2::new(256, 256)), 9);
+        assert_eq!(mip_level_count(UVec2::new(300, 17)), 9);
+    }
+
+    #[test]
+    fn test_prepare_mip_levels() {
+        let size = UVec2::new(4, 2);
+        let rgba = [255; 32];
+        let levels = prepare_mip_levels(
+            size,
+            &rgba,
+            SamplerSettings::linear(),
+            TextureLoadOptions::default(),
+        );
+        let lengths: Vec<usize> = levels.iter().map(Vec::len).collect();
+        assert_eq!(lengths, vec![32, 8, 4]);
+        assert_eq!(
+            prepare_mip_levels(
+                size,
+                &rgba,
+                SamplerSettings::nearest(),
+                TextureLoadOptions::default(),
+            )
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_prepare_mip_levels_load_options() {
+        let size = UVec2::new(1, 1);
+        let rgba = [200, 100, 50, 128];
+        let prepare = |load_options| {
+            prepare_mip_levels(size, &rgba, SamplerSettings::nearest(), load_options)
+        };
+        assert_ne!(prepare(TextureLoadOptions::default())[0], rgba);
+        assert_eq!(
+            prepare(TextureLoadOptions::default().with_premultiplied_alpha(false))[0],
+            rgba
+        );
+    }
+
+    #[test]
+    fn test_copy_region() {
+        // A 2x2 region in the bottom right of a 3x3 image.
+        let mut image = [0; 36];
+        copy_region(&mut image, 3, UVec2::new(1, 1), UVec2::new(2, 2), &[1; 16]);
+        let changed: Vec<bool> = image.chunks_exact(4).map(|pixel| pixel[0] == 1).collect();
+        assert_eq!(
+            changed,
+            [false, false, false, false, true, true, false, true, true]
+        );
+    }
+
+    #[test]
+    fn test_premultiplied_edges_composite_correctly() {
+        // Opaque red, a half transparent red edge, and a transparent pixel whose color
+        // should never show.
+        let straight = [255, 0, 0, 255, 255, 0, 0, 128, 255, 255, 255, 0];
+        let mut premultiplied = straight;
+        premultiply_alpha(&mut premultiplied);
+        assert_eq!(premultiplied[..4], straight[..4]);
+        assert_eq!(premultiplied[8..], [0, 0, 0, 0]);
+
+        // Compositing over a background the way the gpu does, in linear space with
+        // premultiplied blending, matches blending the straight colors by alpha.
+        let background = [0.0, 0.5, 1.0];
+        for (straight, premultiplied) in straight.chunks_exact(4).zip(premultiplied.chunks_exact(4))
+        {
+            let alpha = straight[3] as f32 / 255.0;
+            for channel in 0..3 {
+                let color = srgb_to_linear(straight[channel] as f32 / 255.0);
+                let expected = color * alpha + background[channel] * (1.0 - alpha);
+                let composited = srgb_to_linear(premultiplied[channel] as f32 / 255.0)
+                    + background[channel] * (1.0 - alpha);
+                assert!(
+                    (composited - expected).abs() < 0.01,
+                    "channel {channel} composited to {composited} instead of {expected}"
+                );
+            }
+        }
     }
 }
@@ -0,0 +1,231 @@
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::util::{camera::Camera, coordinates, repository::ResourceId};
+
+use super::{
+    mesh::Mesh, texture::Texture, BasicDiffuseMaterial, Material, RenderLayers, RenderOperation,
+    TextureParameters,
+};
+
+/// How far the fill of a bar is drawn in front of its background, in world units, so
+/// it sorts after the background.
+const BAR_FILL_BIAS: f32 = 0.001;
+
+/// How a [WorldQuad] is sized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorldQuadSizing {
+    /// Size and offset are in world units, so the quad shrinks with distance like
+    /// everything else.
+    World,
+    /// Size and offset are in pixels, so the quad stays the same size on screen at
+    /// any distance.
+    Screen,
+}
+
+/// Quad anchored to a point in the world that always faces the camera, such as a
+/// health bar or name plate over an entity.
+///
+/// Quads are turned into operations with [WorldQuad::to_render_operation], and drawn
+/// in a late pass such as [super::RenderPassDescriptor::world_ui] so they show over
+/// the scene.
+#[derive(Clone, Copy)]
+pub struct WorldQuad {
+    /// Point in the world the quad is attached to.
+    pub anchor: Vec3,
+    /// Offset of the quad's center from the anchor along the camera's right and up.
+    pub offset: Vec2,
+    /// Width and height of the quad.
+    pub size: Vec2,
+    /// Units of the size and offset.
+    pub sizing: WorldQuadSizing,
+    /// Color of the quad, multiplied with the texture if there is one.
+    pub color: Vec4,
+    /// Texture to draw on the quad.
+    pub texture_parameters: Option<TextureParameters>,
+    /// Layers the quad is on.
+    pub layers: RenderLayers,
+    /// Distance in world units the quad is moved towards the camera, to order quads
+    /// sharing an anchor.
+    pub bias: f32,
+}
+
+impl WorldQuad {
+    /// Creates a white [WorldQuad] centered on an anchor.
+    pub fn new(anchor: Vec3, size: Vec2, sizing: WorldQuadSizing) -> Self {
+        Self {
+            anchor,
+            offset: Vec2::ZERO,
+            size,
+            sizing,
+            color: Vec4::ONE,
+            texture_parameters: None,
+            layers: RenderLayers::DEFAULT,
+            bias: 0.0,
+        }
+    }
+
+    /// Sets the offset of the quad from its anchor.
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the color of the quad.
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the texture drawn on the quad.
+    pub fn with_texture(
+        mut self,
+        texture_id: ResourceId<Texture>,
+        uv_window: Option<Vec4>,
+    ) -> Self {
+        self.texture_parameters = Some(TextureParameters::new(texture_id, uv_window));
+        self
+    }
+
+    /// Sets the layers the quad is on.
+    pub fn with_layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Sets how far the quad is moved towards the camera.
+    pub fn with_bias(mut self, bias: f32) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    /// Splits the quad into itself as a background and a fill drawn over it, filled
+    /// from the left by `fraction` from 0 to 1, such as for a health bar.
+    pub fn bar(self, fraction: f32, fill_color: Vec4) -> [WorldQuad; 2] {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let fill = WorldQuad {
+            offset: self.offset - Vec2::X * self.size.x * (1.0 - fraction) * 0.5,
+            size: Vec2::new(self.size.x * fraction, self.size.y),
+            color: fill_color,
+            bias: self.bias + BAR_FILL_BIAS,
+            ..self
+        };
+        [self, fill]
+    }
+
+    /// Gets the transform of a unit quad facing the camera to draw the quad with, or
+    /// `None` if its anchor is behind the camera.
+    pub fn transform(&self, camera: &Camera, screen_size: Vec2) -> Option<Mat4> {
+        let view_projection = camera.get_view_projection_matrix();
+        let (_, rotation, _) = camera.affine.to_scale_rotation_translation();
+        let anchor_on_screen = camera.world_to_screen(self.anchor, screen_size)?;
+
+        let scale = match self.sizing {
+            WorldQuadSizing::World => 1.0,
+            WorldQuadSizing::Screen => {
+                // World units per pixel at the anchor's distance.
+                let up = coordinates::world_to_screen(
+                    view_projection,
+                    self.anchor + rotation * Vec3::Y,
+                    screen_size,
+                )?;
+                let pixels = up.0.distance(anchor_on_screen.0);
+                if pixels <= f32::EPSILON {
+                    return None;
+                }
+                1.0 / pixels
+            }
+        };
+
+        let translation = self.anchor + rotation * (self.offset * scale).extend(self.bias);
+        Some(Mat4::from_scale_rotation_translation(
+            (self.size * scale).extend(1.0),
+            rotation,
+            translation,
+        ))
+    }
+
+    /// Creates the transparent operation drawing the quad with
+    /// [super::default_meshes::QUAD_MESH_DATA], or `None` if it's behind the camera.
+    ///
+    /// The quad uses the basic diffuse material, so it's lit by
+    /// [super::RenderContext::set_lighting] like anything else.
+    pub fn to_render_operation(
+        &self,
+        quad_mesh_id: ResourceId<Mesh>,
+        camera: &Camera,
+        screen_size: Vec2,
+    ) -> Option<RenderOperation> {
+        Some(RenderOperation {
+            transform: self.transform(camera, screen_size)?,
+            mesh_id: quad_mesh_id,
+            material: Material::BasicDiffuse(BasicDiffuseMaterial {
+                color: self.color,
+                texture_parameters: self.texture_parameters,
+            }),
+            layers: self.layers,
+            transparent: true,
+            sort_layer: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec2, Affine3A};
+
+    use super::*;
+    use crate::util::camera::Projection;
+
+    const SCREEN: Vec2 = vec2(800.0, 600.0);
+
+    fn camera() -> Camera {
+        Camera::new(
+            Affine3A::IDENTITY,
+            Projection::Perspective {
+                aspect: SCREEN.x / SCREEN.y,
+                fov: 1.0,
+                znear: 0.1,
+                zfar: 100.0,
+            },
+        )
+    }
+
+    #[test]
+    fn test_screen_sized_quad() {
+        let camera = camera();
+        let width_on_screen = |distance: f32| {
+            let quad = WorldQuad::new(
+                Vec3::new(1.0, 0.0, -distance),
+                vec2(50.0, 10.0),
+                WorldQuadSizing::Screen,
+            );
+            let transform = quad.transform(&camera, SCREEN).unwrap();
+            let corner = |x: f32| {
+                camera
+                    .world_to_screen(transform.transform_point3(Vec3::X * x), SCREEN)
+                    .unwrap()
+                    .0
+            };
+            corner(0.5).x - corner(-0.5).x
+        };
+        assert!((width_on_screen(5.0) - 50.0).abs() < 0.1);
+        assert!((width_on_screen(40.0) - 50.0).abs() < 0.1);
+
+        let behind = WorldQuad::new(Vec3::Z, Vec2::ONE, WorldQuadSizing::World);
+        assert!(behind.transform(&camera, SCREEN).is_none());
+    }
+
+    #[test]
+    fn test_bar() {
+        let [background, fill] = WorldQuad::new(Vec3::ZERO, vec2(2.0, 0.5), WorldQuadSizing::World)
+            .with_offset(Vec2::Y)
+            .bar(0.25, Vec4::X);
+        assert_eq!(background.size, vec2(2.0, 0.5));
+        assert_eq!(fill.size, vec2(0.5, 0.5));
+        // Left edges line up.
+        assert_eq!(fill.offset.x - fill.size.x * 0.5, -1.0);
+        assert_eq!(fill.offset.y, 1.0);
+        assert!(fill.bias > background.bias);
+        assert_eq!(fill.color, Vec4::X);
+    }
+}
@@ -0,0 +1,311 @@
+use num::FromPrimitive;
+
+use crate::{
+    graphics::texture::Texture,
+    util::{
+        repository::ResourceId,
+        sprite::load_aseprite_sprites,
+        texture_atlas::{SpriteId, TextureAtlas},
+    },
+};
+
+use super::{inputs::MAX_KEY, Input, InputState, Keyboard, Mouse, Scancode};
+
+/// Kind of device button prompts are shown for, as each labels its buttons
+/// differently.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PromptDevice {
+    #[default]
+    KeyboardMouse,
+    Xbox,
+    PlayStation,
+    Switch,
+}
+
+/// Button of a gamepad by where it is on the pad, as the labels on it change between
+/// devices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    /// Bottom face button, A on Xbox, Cross on PlayStation and B on Switch.
+    South,
+    /// Right face button.
+    East,
+    /// Left face button.
+    West,
+    /// Top face button.
+    North,
+    LeftShoulder,
+    RightShoulder,
+    LeftTrigger,
+    RightTrigger,
+    /// Left of the middle buttons, such as View or Share.
+    Select,
+    /// Right of the middle buttons, such as Menu or Options.
+    Start,
+    /// Pressing down on the left stick.
+    LeftStick,
+    /// Pressing down on the right stick.
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Inputs bound to an action on each kind of device, such as E and the south button
+/// for "interact", to show the prompt for whichever device is in use.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PromptBinding {
+    /// Key or mouse button bound to the action.
+    pub keyboard_mouse: Option<Input>,
+    /// Gamepad button bound to the action.
+    pub gamepad: Option<GamepadButton>,
+}
+
+/// How to show an input in a prompt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Glyph {
+    /// Name of the glyph's sprite in a [PromptAtlas], such as `xbox_a` or `key_e`.
+    pub name: String,
+    /// Text to show when there's no sprite, such as `A` or `Cross`.
+    pub label: String,
+}
+
+/// Glyph sprites loaded into a [TextureAtlas], with one aseprite tag per [Glyph::name].
+#[derive(Clone, Debug)]
+pub struct PromptAtlas {
+    image: String,
+}
+
+impl PromptBinding {
+    /// Creates a [PromptBinding] with nothing bound.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the key or mouse button bound to the action.
+    pub fn with_keyboard_mouse<I: Into<Input>>(mut self, input: I) -> Self {
+        self.keyboard_mouse = Some(input.into());
+        self
+    }
+
+    /// Sets the gamepad button bound to the action.
+    pub fn with_gamepad(mut self, button: GamepadButton) -> Self {
+        self.gamepad = Some(button);
+        self
+    }
+
+    /// Gets the glyph to show for the action on a device, or `None` if nothing is bound
+    /// on it.
+    ///
+    /// Scancodes show the key at their position in the keyboard layout in use, so a
+    /// prompt for the key above S reads Z on AZERTY once it's been pressed, see
+    /// [InputState::key_of]. Until then they show the key on a US QWERTY keyboard.
+    pub fn glyph(&self, device: PromptDevice, input_state: &InputState) -> Option<Glyph> {
+        match device {
+            PromptDevice::KeyboardMouse => input_glyph(self.keyboard_mouse?, input_state),
+            device => Some(gamepad_glyph(device, self.gamepad?)),
+        }
+    }
+}
+
+/// Gets the glyph of a gamepad button as labelled on a device, where the keyboard and
+/// mouse fall back to Xbox labels.
+pub fn gamepad_glyph(device: PromptDevice, button: GamepadButton) -> Glyph {
+    use GamepadButton::*;
+
+    let (prefix, label) = match device {
+        PromptDevice::PlayStation => (
+            "playstation",
+            match button {
+                South => "Cross",
+                East => "Circle",
+                West => "Square",
+                North => "Triangle",
+                LeftShoulder => "L1",
+                RightShoulder => "R1",
+                LeftTrigger => "L2",
+                RightTrigger => "R2",
+                Select => "Share",
+                Start => "Options",
+                LeftStick => "L3",
+                RightStick => "R3",
+                _ => dpad_label(button),
+            },
+        ),
+        // Nintendo swaps A and B, and X and Y, around from Xbox.
+        PromptDevice::Switch => (
+            "switch",
+            match button {
+                South => "B",
+                East => "A",
+                West => "Y",
+                North => "X",
+                LeftShoulder => "L",
+                RightShoulder => "R",
+                LeftTrigger => "ZL",
+                RightTrigger => "ZR",
+                Select => "Minus",
+                Start => "Plus",
+                LeftStick => "LS",
+                RightStick => "RS",
+                _ => dpad_label(button),
+            },
+        ),
+        PromptDevice::Xbox | PromptDevice::KeyboardMouse => (
+            "xbox",
+            match button {
+                South => "A",
+                East => "B",
+                West => "X",
+                North => "Y",
+                LeftShoulder => "LB",
+                RightShoulder => "RB",
+                LeftTrigger => "LT",
+                RightTrigger => "RT",
+                Select => "View",
+                Start => "Menu",
+                LeftStick => "LS",
+                RightStick => "RS",
+                _ => dpad_label(button),
+            },
+        ),
+    };
+    Glyph::new(prefix, label)
+}
+
+/// Gets the glyph of a key or mouse button, or `None` for a scancode with no known key.
+pub fn input_glyph(input: Input, input_state: &InputState) -> Option<Glyph> {
+    match input {
+        Input::Keyboard(key) => Some(key_glyph(key)),
+        Input::Scancode(scancode) => Some(key_glyph(
+            input_state
+                .key_of(scancode)
+                .or_else(|| qwerty_key(scancode))?,
+        )),
+        Input::Mouse(button) => Some(Glyph::new(
+            "mouse",
+            match button {
+                Mouse::Left => "Left",
+                Mouse::Right => "Right",
+                Mouse::Middle => "Middle",
+            },
+        )),
+    }
+}
+
+/// Gets the key at a scancode on a US QWERTY keyboard, see [Scancode::qwerty].
+fn qwerty_key(scancode: Scancode) -> Option<Keyboard> {
+    (0..=MAX_KEY)
+        .filter_map(Keyboard::from_usize)
+        .find(|key| Scancode::qwerty(*key) == Some(scancode))
+}
+
+fn key_glyph(key: Keyboard) -> Glyph {
+    let name = format!("{key:?}");
+    // Digits are named Key0 to Key9.
+    let label = match name.strip_prefix("Key") {
+        Some(digit) if digit.len() == 1 => digit,
+        _ => &name,
+    };
+    Glyph::new("key", label)
+}
+
+fn dpad_label(button: GamepadButton) -> &'static str {
+    match button {
+        GamepadButton::DPadUp => "D-Pad Up",
+        GamepadButton::DPadDown => "D-Pad Down",
+        GamepadButton::DPadLeft => "D-Pad Left",
+        _ => "D-Pad Right",
+    }
+}
+
+impl Glyph {
+    fn new(prefix: &str, label: &str) -> Self {
+        let name = label
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        Self {
+            name: format!("{prefix}_{name}"),
+            label: label.to_string(),
+        }
+    }
+}
+
+impl PromptAtlas {
+    /// Loads glyph sprites from an aseprite generated json document into an atlas,
+    /// where each glyph is a tag named after its [Glyph::name], such as `xbox_a`.
+    pub fn load(
+        atlas: &mut TextureAtlas,
+        aseprite_json: &str,
+        texture: ResourceId<Texture>,
+    ) -> anyhow::Result<Self> {
+        let loaded = load_aseprite_sprites(aseprite_json, texture)?;
+        for (tag, sprite) in loaded.sprites {
+            atlas.add_sprite(sprite, &loaded.image, tag.as_deref());
+        }
+        Ok(Self {
+            image: loaded.image,
+        })
+    }
+
+    /// Gets the sprite of a glyph, or `None` if the atlas doesn't have one, in which
+    /// case show its [Glyph::label] instead.
+    pub fn sprite_id(&self, atlas: &TextureAtlas, glyph: &Glyph) -> Option<SpriteId> {
+        atlas.get_sprite_id(&self.image, Some(&glyph.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyphs_per_device() {
+        let input_state = InputState::new();
+        let interact = PromptBinding::new()
+            .with_keyboard_mouse(Keyboard::E)
+            .with_gamepad(GamepadButton::South);
+        let glyph = |device| interact.glyph(device, &input_state).unwrap();
+
+        assert_eq!(glyph(PromptDevice::KeyboardMouse).name, "key_e");
+        assert_eq!(glyph(PromptDevice::Xbox).label, "A");
+        assert_eq!(glyph(PromptDevice::PlayStation).name, "playstation_cross");
+        assert_eq!(glyph(PromptDevice::Switch).label, "B");
+        assert_eq!(
+            gamepad_glyph(PromptDevice::Xbox, GamepadButton::DPadUp).name,
+            "xbox_dpadup"
+        );
+
+        assert!(PromptBinding::new()
+            .with_keyboard_mouse(Mouse::Left)
+            .glyph(PromptDevice::Switch, &input_state)
+            .is_none());
+    }
+
+    #[test]
+    fn test_key_glyphs_follow_layout() {
+        let mut input_state = InputState::new();
+        assert_eq!(key_glyph(Keyboard::Key1).label, "1");
+
+        let scancode = Scancode::qwerty(Keyboard::W).unwrap();
+        let binding = PromptBinding::new().with_keyboard_mouse(scancode);
+        assert_eq!(
+            binding
+                .glyph(PromptDevice::KeyboardMouse, &input_state)
+                .unwrap()
+                .label,
+            "W"
+        );
+        input_state.signal_scancode_key(scancode, Keyboard::Z);
+        assert_eq!(
+            binding
+                .glyph(PromptDevice::KeyboardMouse, &input_state)
+                .unwrap()
+                .label,
+            "Z"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use glam::Vec2;
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+
+use super::{Keyboard, Mouse, Scancode};
+
+/// Input received from the window during a frame, in the order it happened, see
+/// [super::InputState::events].
+#[derive(Clone, Debug)]
+pub struct InputEvent {
+    /// When the engine received the input.
+    pub timestamp: Instant,
+    /// What happened.
+    pub kind: InputEventKind,
+}
+
+/// Kind of an [InputEvent].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum InputEventKind {
+    /// A key was pressed or released, including repeats while it's held.
+    Key {
+        /// Physical position of the key.
+        scancode: Scancode,
+        /// Key in the keyboard layout, if there is one.
+        key: Option<Keyboard>,
+        pressed: bool,
+    },
+    /// A mouse button was pressed or released.
+    MouseButton { button: Mouse, pressed: bool },
+    /// The cursor moved to a position in pixels from the top left of the window, or
+    /// `None` if it left the window.
+    CursorMoved(Option<Vec2>),
+    /// The mouse moved by some amount, in unaccelerated device units with y pointing
+    /// down.
+    MouseMotion(Vec2),
+    /// The mouse wheel or touchpad scrolled.
+    Wheel(ScrollDelta),
+    /// Text was typed, either a single character or text committed by an input method.
+    Text(String),
+}
+
+/// Amount scrolled by an [InputEventKind::Wheel].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScrollDelta {
+    /// Lines, as most mouse wheels scroll by.
+    Lines(Vec2),
+    /// Pixels, as touchpads scroll by.
+    Pixels(Vec2),
+}
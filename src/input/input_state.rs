@@ -1,13 +1,42 @@
-use std::time::{ Instant, Duration };
-use super::{ inputs::{ INPUTS, MAX_KEY }, Keyboard, Input, Mouse };
+use std::{ collections::HashMap, time::Duration };
+
+use instant::Instant;
+use glam::Vec2;
+use super::{ inputs::{ INPUTS, MAX_KEY }, InputEvent, InputEventKind, Keyboard, Input, Mouse, Scancode };
+
+/// State of a single [Input].
+#[derive(Clone, Copy, Default)]
+struct InputRecord {
+    pressed: bool,
+    press_timestamp: Option<Instant>,
+    release_timestamp: Option<Instant>,
+    /// Frame the input was last pressed in, see [InputState::begin_frame].
+    press_frame: Option<u64>,
+    release_frame: Option<u64>,
+}
 
 /// Manages storing the current state of all the applications possible inputs.
 pub struct InputState {
-    // important to note that a state is only valid if it has a
-    // non-None timestamp.
-    states: [bool; INPUTS],
-    press_timestamps: [Option<Instant>; INPUTS],
-    releaste_timestamps: [Option<Instant>; INPUTS],
+    records: [InputRecord; INPUTS],
+    /// State of each scancode seen so far, as there are too many possible scancodes to
+    /// store up front.
+    scancodes: HashMap<u32, InputRecord>,
+    /// Number of frames begun so far.
+    frame: u64,
+    /// Key each scancode last produced in the keyboard layout, see [InputState::key_of].
+    scancode_keys: HashMap<u32, Keyboard>,
+    /// Characters typed this frame, including control characters like backspace.
+    typed_text: String,
+    /// Text being composed with an input method, and the selected byte range within it.
+    ime_preedit: Option<(String, Option<(usize, usize)>)>,
+    /// Position of the cursor in pixels from the top left of the window, if it's over it.
+    mouse_position: Option<Vec2>,
+    /// Relative mouse motion this frame.
+    mouse_motion: Vec2,
+    /// Input received from the window this frame, in order.
+    events: Vec<InputEvent>,
+    /// When input timing was paused, see [InputState::set_paused].
+    paused_at: Option<Instant>,
 }
 
 impl From<Keyboard> for Input {
@@ -22,12 +51,25 @@ impl From<Mouse> for Input {
     }
 }
 
+impl From<Scancode> for Input {
+    fn from(val: Scancode) -> Self {
+        Input::Scancode(val)
+    }
+}
+
 impl Default for InputState {
     fn default() -> Self {
         Self {
-            states: [false; INPUTS],
-            press_timestamps: [None; INPUTS],
-            releaste_timestamps: [None; INPUTS],
+            records: [InputRecord::default(); INPUTS],
+            scancodes: HashMap::new(),
+            frame: 0,
+            scancode_keys: HashMap::new(),
+            typed_text: String::new(),
+            ime_preedit: None,
+            mouse_position: None,
+            mouse_motion: Vec2::ZERO,
+            events: Vec::new(),
+            paused_at: None,
         }
     }
 }
@@ -40,12 +82,12 @@ impl InputState {
 
     /// Checks if an [Input] is currently pressed.
     pub fn check_pressed<I: Into<Input>>(&self, input: I) -> bool {
-        self.states[Self::get_state_index(input.into())]
+        self.state(input.into()).pressed
     }
 
     /// Checks if an [Input] is currently released.
     pub fn check_released<I: Into<Input>>(&self, input: I) -> bool {
-        !self.states[Self::get_state_index(input.into())]
+        !self.state(input.into()).pressed
     }
 
     /// Checks if an [Input] was pressed within a [Duration].
@@ -64,44 +106,258 @@ impl InputState {
         self.check_within_duration(input.into(), duration, false)
     }
 
+    /// Checks if an [Input] was pressed since the last frame, which is true for exactly
+    /// one update after the press, and for the fixed updates before it.
+    ///
+    /// Unlike [InputState::check_pressed_within] this doesn't depend on frame times, and
+    /// still catches an input pressed and released within the same frame.
+    pub fn just_pressed<I: Into<Input>>(&self, input: I) -> bool {
+        self.state(input.into()).press_frame == Some(self.frame)
+    }
+
+    /// Checks if an [Input] was released since the last frame, which is true for exactly
+    /// one update after the release, and for the fixed updates before it.
+    pub fn just_released<I: Into<Input>>(&self, input: I) -> bool {
+        self.state(input.into()).release_frame == Some(self.frame)
+    }
+
+    /// Begins a new frame of input, so inputs pressed or released before it are no longer
+    /// [InputState::just_pressed] or [InputState::just_released], and the frame's
+    /// [InputState::events] are cleared. The engine calls this after each update.
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+        self.events.clear();
+    }
+
+    /// Pauses or resumes the time used by [InputState::check_pressed_within] and
+    /// [InputState::check_released_within], so inputs don't age while the game is
+    /// paused. Inputs signaled while paused count as happening when it resumes.
+    ///
+    /// The engine calls this to follow [crate::util::time::GameClock::is_paused].
+    pub fn set_paused(&mut self, paused: bool) {
+        match (paused, self.paused_at) {
+            (true, None) => self.paused_at = Some(Instant::now()),
+            (false, Some(paused_at)) => {
+                let paused_for = Instant::now().saturating_duration_since(paused_at);
+                for record in self.records.iter_mut().chain(self.scancodes.values_mut()) {
+                    let timestamps = [&mut record.press_timestamp, &mut record.release_timestamp];
+                    for timestamp in timestamps.into_iter().flatten() {
+                        *timestamp += paused_for;
+                    }
+                }
+                self.paused_at = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Gets the input received from the window this frame in the order it happened,
+    /// such as to record it for a replay or feed it to a UI framework.
+    ///
+    /// Unlike the polled state, this keeps every press of an input tapped several times
+    /// within a frame.
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    /// Signals to the [InputState] that an [InputEvent] was received now, updating the
+    /// polled state to match and adding it to [InputState::events].
+    pub fn signal_event(&mut self, kind: InputEventKind) {
+        match &kind {
+            InputEventKind::Key { scancode, key, pressed } => {
+                if let Some(key) = key {
+                    self.signal_scancode_key(*scancode, *key);
+                }
+                let inputs = [Some(Input::Scancode(*scancode)), key.map(Input::Keyboard)];
+                for input in inputs.into_iter().flatten() {
+                    match pressed {
+                        true => self.signal_press_of(input),
+                        false => self.signal_release_of(input),
+                    }
+                }
+            }
+            InputEventKind::MouseButton { button, pressed } => match pressed {
+                true => self.signal_press_of(*button),
+                false => self.signal_release_of(*button),
+            },
+            InputEventKind::CursorMoved(position) => self.signal_mouse_position(*position),
+            InputEventKind::MouseMotion(motion) => self.signal_mouse_motion(*motion),
+            InputEventKind::Wheel(_) => (),
+            InputEventKind::Text(text) => self.typed_text.push_str(text),
+        }
+        self.events.push(InputEvent { timestamp: Instant::now(), kind });
+    }
+
     /// Signals to the [InputState] that a specific [input] was pressed.
     ///
     /// Will ignore if the [input] is already pressed.
     pub fn signal_press_of<I: Into<Input>>(&mut self, input: I) {
-        let index = Self::get_state_index(input.into());
-        if !self.states[index] {
-            self.states[index] = true;
-            self.press_timestamps[index] = Some(Instant::now());
+        let frame = self.frame;
+        let now = self.now();
+        let record = self.state_mut(input.into());
+        if !record.pressed {
+            record.pressed = true;
+            record.press_timestamp = Some(now);
+            record.press_frame = Some(frame);
         }
     }
 
     /// Signals to the [InputState] that a specific [input] was released.
     pub fn signal_release_of<I: Into<Input>>(&mut self, input: I) {
-        let index = Self::get_state_index(input.into());
-        if self.states[index] {
-            self.states[index] = false;
-            self.releaste_timestamps[index] = Some(Instant::now());
+        let frame = self.frame;
+        let now = self.now();
+        let record = self.state_mut(input.into());
+        if record.pressed {
+            record.pressed = false;
+            record.release_timestamp = Some(now);
+            record.release_frame = Some(frame);
         }
     }
 
+    /// Gets the key a [Scancode] last produced in the player's keyboard layout, such
+    /// as to show `Z` in the controls for [Scancode::qwerty] `W` on an AZERTY keyboard.
+    ///
+    /// Returns `None` until the key has been pressed once.
+    pub fn key_of(&self, scancode: Scancode) -> Option<Keyboard> {
+        self.scancode_keys.get(&scancode.0).copied()
+    }
+
+    /// Signals to the [InputState] the key a [Scancode] produces in the keyboard layout.
+    pub fn signal_scancode_key(&mut self, scancode: Scancode, key: Keyboard) {
+        self.scancode_keys.insert(scancode.0, key);
+    }
+
+    /// Gets the text typed this frame, including text committed by an input method.
+    ///
+    /// Control characters are included as typed, such as `'\u{8}'` for backspace and
+    /// `'\r'` for enter, see [InputState::apply_typed_text] to edit a string with them.
+    pub fn typed_text(&self) -> &str {
+        &self.typed_text
+    }
+
+    /// Gets the text currently being composed with an input method (IME), along with the
+    /// selected byte range within it, so it can be shown before it's committed.
+    pub fn ime_preedit(&self) -> Option<(&str, Option<(usize, usize)>)> {
+        self.ime_preedit
+            .as_ref()
+            .map(|(text, selection)| (text.as_str(), *selection))
+    }
+
+    /// Edits a single line of text with what was typed this frame, as a text box would.
+    /// Printable characters are appended and backspace removes the last character.
+    ///
+    /// Returns whether enter was typed, such as to submit a chat message.
+    pub fn apply_typed_text(&self, text: &mut String) -> bool {
+        let mut entered = false;
+        for character in self.typed_text.chars() {
+            match character {
+                '\u{8}' => {
+                    text.pop();
+                }
+                '\r' | '\n' => entered = true,
+                character if !character.is_control() => text.push(character),
+                _ => (),
+            }
+        }
+        entered
+    }
+
+    /// Signals to the [InputState] that a character was typed.
+    pub fn signal_typed_character(&mut self, character: char) {
+        self.typed_text.push(character);
+    }
+
+    /// Signals to the [InputState] that an input method committed text.
+    pub fn signal_ime_commit(&mut self, text: &str) {
+        self.typed_text.push_str(text);
+        self.ime_preedit = None;
+    }
+
+    /// Signals to the [InputState] the text being composed with an input method, or
+    /// `None` once composition ends.
+    pub fn signal_ime_preedit(&mut self, preedit: Option<(String, Option<(usize, usize)>)>) {
+        self.ime_preedit = preedit.filter(|(text, _)| !text.is_empty());
+    }
+
+    /// Clears the text typed this frame. The engine calls this after each update.
+    pub fn clear_typed_text(&mut self) {
+        self.typed_text.clear();
+    }
+
+    /// Gets the position of the cursor in pixels from the top left of the window, or
+    /// `None` if it's outside the window.
+    pub fn mouse_position(&self) -> Option<Vec2> {
+        self.mouse_position
+    }
+
+    /// Gets how far the mouse moved this frame, in unaccelerated device units with y
+    /// pointing down.
+    ///
+    /// Unlike [InputState::mouse_position] this keeps accumulating while the cursor is
+    /// grabbed with [crate::Engine::set_cursor_grab], such as for mouse look.
+    pub fn mouse_motion(&self) -> Vec2 {
+        self.mouse_motion
+    }
+
+    /// Signals to the [InputState] that the cursor moved, or `None` if it left the window.
+    pub fn signal_mouse_position(&mut self, position: Option<Vec2>) {
+        self.mouse_position = position;
+    }
+
+    /// Signals to the [InputState] that the mouse moved by some amount.
+    pub fn signal_mouse_motion(&mut self, motion: Vec2) {
+        self.mouse_motion += motion;
+    }
+
+    /// Clears the mouse motion of this frame. The engine calls this after each update.
+    pub fn clear_mouse_motion(&mut self) {
+        self.mouse_motion = Vec2::ZERO;
+    }
+
+    /// Gets the number of scancodes whose state or key is stored, which grows with each
+    /// new scancode seen.
+    pub(crate) fn scancode_count(&self) -> usize {
+        self.scancodes.len().max(self.scancode_keys.len())
+    }
+
     fn get_state_index(input: Input) -> usize {
         match input {
             Input::Keyboard(key) => key as usize,
             Input::Mouse(input) => (input as usize) + 1 + MAX_KEY,
+            Input::Scancode(_) => unreachable!("scancodes are stored separately"),
+        }
+    }
+
+    fn state(&self, input: Input) -> InputRecord {
+        match input {
+            Input::Scancode(scancode) => self.scancodes.get(&scancode.0).copied().unwrap_or_default(),
+            input => self.records[Self::get_state_index(input)],
         }
     }
 
+    fn state_mut(&mut self, input: Input) -> &mut InputRecord {
+        match input {
+            Input::Scancode(scancode) => self.scancodes.entry(scancode.0).or_default(),
+            input => &mut self.records[Self::get_state_index(input)],
+        }
+    }
+
+    /// Gets the current time, which stands still while paused.
+    fn now(&self) -> Instant {
+        self.paused_at.unwrap_or_else(Instant::now)
+    }
+
     fn check_within_duration(&self, input: Input, duration: Duration, is_pressed: bool) -> bool {
-        let index = Self::get_state_index(input);
+        let record = self.state(input);
 
-        let timestamps = match is_pressed {
-            true => &self.press_timestamps,
-            false => &self.releaste_timestamps,
+        let timestamp = match is_pressed {
+            true => record.press_timestamp,
+            false => record.release_timestamp,
         };
 
         // Check if the input timestamp is within 'duration' from now.
-        if let Some(timestamp) = timestamps[index] {
-            let input_duration = Instant::now() - timestamp;
+        if let Some(timestamp) = timestamp {
+            let input_duration = self.now().saturating_duration_since(timestamp);
             input_duration <= duration
         } else {
             false
@@ -138,6 +394,114 @@ mod tests {
         assert!(input_state.check_released(Keyboard::A));
     }
 
+    #[test]
+    fn test_last_inputs() {
+        let mut input_state = InputState::new();
+        input_state.signal_press_of(Keyboard::Cut);
+        input_state.signal_press_of(Mouse::Middle);
+        assert!(input_state.check_pressed(Keyboard::Cut));
+        assert!(input_state.check_pressed(Mouse::Middle));
+    }
+
+    #[test]
+    fn test_scancodes() {
+        let mut input_state = InputState::new();
+        let w = Scancode::qwerty(Keyboard::W).unwrap();
+        assert!(input_state.check_released(w));
+        assert!(input_state.key_of(w).is_none());
+
+        // The W position types Z on an AZERTY keyboard.
+        input_state.signal_press_of(w);
+        input_state.signal_press_of(Keyboard::Z);
+        input_state.signal_scancode_key(w, Keyboard::Z);
+        assert!(input_state.check_pressed(w));
+        assert!(input_state.check_pressed_within(w, Duration::from_secs(1)));
+        assert!(input_state.check_released(Keyboard::W));
+        assert!(matches!(input_state.key_of(w), Some(Keyboard::Z)));
+
+        input_state.signal_release_of(w);
+        assert!(input_state.check_released(w));
+        assert!(input_state.check_released_within(w, Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_just_pressed_and_released() {
+        let mut input_state = InputState::new();
+        input_state.signal_press_of(Keyboard::Space);
+        assert!(input_state.just_pressed(Keyboard::Space));
+        assert!(!input_state.just_released(Keyboard::Space));
+
+        // Held down, so only the first frame counts.
+        input_state.begin_frame();
+        input_state.signal_press_of(Keyboard::Space);
+        assert!(input_state.check_pressed(Keyboard::Space));
+        assert!(!input_state.just_pressed(Keyboard::Space));
+
+        input_state.begin_frame();
+        input_state.signal_release_of(Keyboard::Space);
+        assert!(input_state.just_released(Keyboard::Space));
+        input_state.begin_frame();
+        assert!(!input_state.just_released(Keyboard::Space));
+
+        // A tap within a single frame is both.
+        let w = Scancode::qwerty(Keyboard::W).unwrap();
+        input_state.signal_press_of(w);
+        input_state.signal_release_of(w);
+        assert!(input_state.just_pressed(w));
+        assert!(input_state.just_released(w));
+        assert!(input_state.check_released(w));
+    }
+
+    #[test]
+    fn test_events() {
+        let mut input_state = InputState::new();
+        input_state.signal_event(InputEventKind::Text("a".to_string()));
+        input_state.signal_event(InputEventKind::MouseMotion(Vec2::X));
+        input_state.signal_event(InputEventKind::Key {
+            scancode: Scancode(17),
+            key: Some(Keyboard::W),
+            pressed: true,
+        });
+        assert_eq!(input_state.typed_text(), "a");
+        assert_eq!(input_state.mouse_motion(), Vec2::X);
+        assert!(input_state.check_pressed(Keyboard::W) && input_state.check_pressed(Scancode(17)));
+
+        let events = input_state.events();
+        assert!(matches!(&events[0].kind, InputEventKind::Text(text) if text == "a"));
+        assert!(matches!(events[1].kind, InputEventKind::MouseMotion(motion) if motion == Vec2::X));
+        assert!(events[0].timestamp <= events[1].timestamp);
+
+        input_state.begin_frame();
+        assert!(input_state.events().is_empty());
+    }
+
+    #[test]
+    fn test_apply_typed_text() {
+        let mut input_state = InputState::new();
+        "hey\u{8}llo".chars().for_each(|character| input_state.signal_typed_character(character));
+        input_state.signal_ime_commit("世界");
+
+        let mut text = String::from(">");
+        assert!(!input_state.apply_typed_text(&mut text));
+        assert_eq!(text, ">hello世界");
+
+        input_state.clear_typed_text();
+        input_state.signal_typed_character('\r');
+        assert!(input_state.apply_typed_text(&mut text));
+        assert_eq!(input_state.typed_text(), "\r");
+    }
+
+    #[test]
+    fn test_mouse_motion_accumulates() {
+        let mut input_state = InputState::new();
+        input_state.signal_mouse_motion(Vec2::new(3.0, -1.0));
+        input_state.signal_mouse_motion(Vec2::new(2.0, 4.0));
+        assert_eq!(input_state.mouse_motion(), Vec2::new(5.0, 3.0));
+
+        input_state.clear_mouse_motion();
+        assert_eq!(input_state.mouse_motion(), Vec2::ZERO);
+    }
+
     #[test]
     fn test_check_when_pressed_within() {
         let mut input_state = InputState::new();
@@ -158,4 +522,17 @@ mod tests {
         sleep(Duration::from_millis(50));
         assert!(!input_state.check_released_within(Mouse::Left, Duration::from_millis(75)));
     }
+
+    #[test]
+    fn test_check_pressed_within_paused() {
+        let mut input_state = InputState::new();
+        input_state.signal_press_of(Mouse::Left);
+        input_state.set_paused(true);
+        sleep(Duration::from_millis(100));
+        assert!(input_state.check_pressed_within(Mouse::Left, Duration::from_millis(75)));
+
+        // Time the game was paused for doesn't count once it resumes.
+        input_state.set_paused(false);
+        assert!(input_state.check_pressed_within(Mouse::Left, Duration::from_millis(75)));
+    }
 }
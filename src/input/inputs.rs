@@ -1,16 +1,27 @@
-pub const INPUTS: usize = MAX_MOUSE + MAX_KEY;
+/// Number of inputs, where the `MAX_` constants are the last index of each kind.
+pub const INPUTS: usize = (MAX_MOUSE + 1) + (MAX_KEY + 1);
 
 // Note, very importantly with this current implementation, I'm sad to say this
 // will only work for 584942417355.072 years.
 
 /// Represents a type of input that can be checked.
+#[derive(Clone, Copy, Debug)]
 pub enum Input {
     Keyboard(Keyboard),
     Mouse(Mouse),
+    Scancode(Scancode),
 }
 
+/// Physical key by its position on the keyboard, whatever layout is in use, as the
+/// platform's raw scancode.
+///
+/// Checking [Scancode::qwerty] keys rather than [Keyboard] keys keeps controls such as
+/// WASD in the same place on AZERTY and other layouts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Scancode(pub u32);
+
 /// Possible mouse button inputs.
-#[derive(Clone, Copy, num_derive::FromPrimitive)]
+#[derive(Clone, Copy, Debug, num_derive::FromPrimitive, serde::Serialize, serde::Deserialize)]
 pub enum Mouse {
     Left,
     Right,
@@ -21,7 +32,7 @@ pub enum Mouse {
 pub const MAX_MOUSE: usize = Mouse::Middle as usize;
 
 /// Possible keyboard button inputs.
-#[derive(Clone, Copy, num_derive::FromPrimitive)]
+#[derive(Clone, Copy, Debug, num_derive::FromPrimitive, serde::Serialize, serde::Deserialize)]
 pub enum Keyboard {
     /// The '1' key over the letters.
     Key1,
@@ -221,3 +232,69 @@ pub enum Keyboard {
 }
 
 pub const MAX_KEY: usize = Keyboard::Cut as usize;
+
+/// Keys with a known position, along with their scancode on other platforms and on macOS.
+const QWERTY_SCANCODES: [(Keyboard, u32, u32); 44] = [
+    (Keyboard::Key1, 0x02, 0x12),
+    (Keyboard::Key2, 0x03, 0x13),
+    (Keyboard::Key3, 0x04, 0x14),
+    (Keyboard::Key4, 0x05, 0x15),
+    (Keyboard::Key5, 0x06, 0x17),
+    (Keyboard::Key6, 0x07, 0x16),
+    (Keyboard::Key7, 0x08, 0x1A),
+    (Keyboard::Key8, 0x09, 0x1C),
+    (Keyboard::Key9, 0x0A, 0x19),
+    (Keyboard::Key0, 0x0B, 0x1D),
+    (Keyboard::Q, 0x10, 0x0C),
+    (Keyboard::W, 0x11, 0x0D),
+    (Keyboard::E, 0x12, 0x0E),
+    (Keyboard::R, 0x13, 0x0F),
+    (Keyboard::T, 0x14, 0x11),
+    (Keyboard::Y, 0x15, 0x10),
+    (Keyboard::U, 0x16, 0x20),
+    (Keyboard::I, 0x17, 0x22),
+    (Keyboard::O, 0x18, 0x1F),
+    (Keyboard::P, 0x19, 0x23),
+    (Keyboard::A, 0x1E, 0x00),
+    (Keyboard::S, 0x1F, 0x01),
+    (Keyboard::D, 0x20, 0x02),
+    (Keyboard::F, 0x21, 0x03),
+    (Keyboard::G, 0x22, 0x05),
+    (Keyboard::H, 0x23, 0x04),
+    (Keyboard::J, 0x24, 0x26),
+    (Keyboard::K, 0x25, 0x28),
+    (Keyboard::L, 0x26, 0x25),
+    (Keyboard::Z, 0x2C, 0x06),
+    (Keyboard::X, 0x2D, 0x07),
+    (Keyboard::C, 0x2E, 0x08),
+    (Keyboard::V, 0x2F, 0x09),
+    (Keyboard::B, 0x30, 0x0B),
+    (Keyboard::N, 0x31, 0x2D),
+    (Keyboard::M, 0x32, 0x2E),
+    (Keyboard::Escape, 0x01, 0x35),
+    (Keyboard::Backspace, 0x0E, 0x33),
+    (Keyboard::Tab, 0x0F, 0x30),
+    (Keyboard::Return, 0x1C, 0x24),
+    (Keyboard::LControl, 0x1D, 0x3B),
+    (Keyboard::LShift, 0x2A, 0x38),
+    (Keyboard::LAlt, 0x38, 0x3A),
+    (Keyboard::Space, 0x39, 0x31),
+];
+
+impl Scancode {
+    /// Gets the scancode of the key where the given key is on a US QWERTY keyboard,
+    /// such as `Scancode::qwerty(Keyboard::W)` for the key above S on any layout.
+    ///
+    /// Only letters, digits, and a few common keys are known.
+    pub fn qwerty(key: Keyboard) -> Option<Scancode> {
+        QWERTY_SCANCODES
+            .iter()
+            .find(|(known, _, _)| *known as usize == key as usize)
+            .map(
+                |(_, scancode, macos_scancode)| match cfg!(target_os = "macos") {
+                    true => Scancode(*macos_scancode),
+                    false => Scancode(*scancode),
+                },
+            )
+    }
+}
@@ -1,5 +1,18 @@
+pub(crate) mod glyphs;
+pub(crate) mod input_event;
 pub(crate) mod inputs;
 pub(crate) mod input_state;
+pub(crate) mod virtual_controls;
+pub mod replay;
+pub(crate) mod soak;
+pub(crate) mod window_events;
 
-pub use inputs::{ Input, Keyboard, Mouse };
+pub use glyphs::{ gamepad_glyph, input_glyph, GamepadButton, Glyph, PromptAtlas, PromptBinding, PromptDevice };
+pub use input_event::{ InputEvent, InputEventKind, ScrollDelta };
+pub use inputs::{ Input, Keyboard, Mouse, Scancode };
 pub use input_state::InputState;
+pub use soak::InputSoak;
+pub use virtual_controls::{
+    Anchor, ButtonId, JoystickId, TouchPhase, VirtualButton, VirtualControls, VirtualJoystick,
+    VirtualLayout,
+};
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::{InputEventKind, InputState};
+
+/// Input of a single frame of an [InputRecording].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Seconds since the previous frame, which playback runs the frame with so fixed
+    /// updates tick the same way.
+    pub delta: f64,
+    /// Input received during the frame, in order.
+    pub events: Vec<InputEventKind>,
+}
+
+/// Input recorded frame by frame, such as with [crate::Engine::start_input_recording],
+/// which can be saved and played back through the engine in place of live input for
+/// automated gameplay tests and demos.
+///
+/// Playing a recording from startup reproduces the run it was recorded in, as long as
+/// the application only depends on its input and the deltas it's given.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl InputRecording {
+    /// Creates an empty [InputRecording].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the input of a frame that ran for `delta` seconds, from the
+    /// [InputState::events] it received.
+    pub fn record_frame(&mut self, delta: f64, input_state: &InputState) {
+        self.frames.push(RecordedFrame {
+            delta,
+            events: input_state
+                .events()
+                .iter()
+                .map(|event| event.kind.clone())
+                .collect(),
+        });
+    }
+
+    /// Serializes the recording to JSON.
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Deserializes a recording from JSON.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Writes the recording to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json()?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Reads a recording from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("invalid recording {}", path.display()))
+    }
+}
+
+/// Feeds an [InputRecording] to an [InputState] frame by frame.
+pub(crate) struct InputPlayer {
+    recording: InputRecording,
+    frame: usize,
+}
+
+impl InputPlayer {
+    /// Creates an [InputPlayer] starting at the first frame.
+    pub(crate) fn new(recording: InputRecording) -> Self {
+        Self {
+            recording,
+            frame: 0,
+        }
+    }
+
+    /// Signals the input of the next frame, returning the delta to run it with, or
+    /// `None` once every frame has played.
+    pub(crate) fn play_frame(&mut self, input_state: &mut InputState) -> Option<f64> {
+        let frame = self.recording.frames.get(self.frame)?;
+        self.frame += 1;
+        for event in frame.events.iter() {
+            input_state.signal_event(event.clone());
+        }
+        Some(frame.delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::input::{Keyboard, Mouse, Scancode};
+
+    #[test]
+    fn test_record_and_play() {
+        let mut live = InputState::new();
+        let mut recording = InputRecording::new();
+        live.signal_event(InputEventKind::Key {
+            scancode: Scancode(30),
+            key: Some(Keyboard::A),
+            pressed: true,
+        });
+        live.signal_event(InputEventKind::CursorMoved(Some(Vec2::new(4.0, 2.0))));
+        recording.record_frame(0.016, &live);
+        live.begin_frame();
+        live.signal_event(InputEventKind::MouseButton {
+            button: Mouse::Left,
+            pressed: true,
+        });
+        recording.record_frame(0.02, &live);
+
+        let recording = InputRecording::from_json(&recording.to_json().unwrap()).unwrap();
+        let mut replayed = InputState::new();
+        let mut player = InputPlayer::new(recording);
+        assert_eq!(player.play_frame(&mut replayed), Some(0.016));
+        assert!(replayed.just_pressed(Keyboard::A));
+        assert_eq!(replayed.mouse_position(), Some(Vec2::new(4.0, 2.0)));
+        replayed.begin_frame();
+
+        assert_eq!(player.play_frame(&mut replayed), Some(0.02));
+        assert!(replayed.just_pressed(Mouse::Left));
+        assert!(!replayed.just_pressed(Keyboard::A));
+        assert!(replayed.check_pressed(Scancode(30)));
+        assert_eq!(player.play_frame(&mut replayed), None);
+    }
+}
@@ -0,0 +1,323 @@
+use glam::Vec2;
+use winit::event::{ElementState, VirtualKeyCode, WindowEvent};
+
+use super::{InputState, VirtualControls};
+use crate::graphics::RenderStats;
+
+/// Touches are given ids below this, like fingers on a screen.
+const MAX_TOUCHES: u64 = 5;
+
+/// Keys are given scancodes below this.
+const SCANCODES: u64 = 256;
+
+/// Most bind groups the renderer may cache beyond those of the first frame, as input
+/// alone shouldn't make it draw new combinations of textures.
+const MAX_BIND_GROUP_GROWTH: usize = 64;
+
+/// Most bytes the renderer's local buffer may grow by beyond the first frame.
+const MAX_LOCAL_BUFFER_GROWTH: u64 = 4 * 1024 * 1024;
+
+/// Characters typed by a soak, including control characters and ones outside ASCII.
+const CHARACTERS: [char; 8] = ['a', 'Z', ' ', '\u{8}', '\r', '\u{7f}', 'é', '🦀'];
+
+/// Text composed with an input method during a soak.
+const IME_TEXT: [&str; 4] = ["", "k", "にほん", "a🦀b"];
+
+/// Soak test that feeds seeded random input to the [crate::Application] for a number
+/// of frames, then exits, see [crate::EngineConfig::with_input_soak].
+///
+/// The same seed always produces the same input, so a failing soak can be replayed.
+/// The soak panics if input handling panics, or if input state or the renderer's
+/// caches grow without bound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputSoak {
+    /// Seed of the random input.
+    pub seed: u64,
+    /// Frames to run before exiting.
+    pub frames: u64,
+    /// Most window events fed each frame.
+    pub max_events_per_frame: u32,
+}
+
+impl InputSoak {
+    /// Creates an [InputSoak] feeding up to 8 events a frame.
+    pub fn new(seed: u64, frames: u64) -> Self {
+        Self {
+            seed,
+            frames,
+            max_events_per_frame: 8,
+        }
+    }
+
+    /// Sets the most window events fed each frame.
+    pub fn with_max_events_per_frame(mut self, max_events_per_frame: u32) -> Self {
+        self.max_events_per_frame = max_events_per_frame;
+        self
+    }
+}
+
+/// Generates the random window events of an [InputSoak].
+pub(crate) struct InputFuzzer {
+    soak: InputSoak,
+    /// State of the xorshift generator, which is never zero.
+    state: u64,
+    frame: u64,
+    /// Render stats of the first checked frame, which the renderer's caches are
+    /// measured against.
+    first_render_stats: Option<RenderStats>,
+}
+
+impl InputFuzzer {
+    /// Creates a new [InputFuzzer].
+    pub(crate) fn new(soak: InputSoak) -> Self {
+        Self {
+            soak,
+            state: soak.seed.max(1),
+            frame: 0,
+            first_render_stats: None,
+        }
+    }
+
+    /// Checks whether every frame of the soak has run.
+    pub(crate) fn finished(&self) -> bool {
+        self.frame >= self.soak.frames
+    }
+
+    /// Generates the events of the next frame, for a window of the given size.
+    pub(crate) fn frame_events(&mut self, window_size: Vec2) -> Vec<WindowEvent<'static>> {
+        self.frame += 1;
+        let count = self.below(self.soak.max_events_per_frame as u64 + 1);
+        (0..count).map(|_| self.event(window_size)).collect()
+    }
+
+    /// Checks that input state and the renderer's caches stayed bounded after a frame,
+    /// once the frame's typed text and events are cleared.
+    pub(crate) fn check(
+        &mut self,
+        input_state: &InputState,
+        virtual_controls: &VirtualControls,
+        render_stats: RenderStats,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            input_state.typed_text().is_empty(),
+            "typed text wasn't cleared after frame {}",
+            self.frame
+        );
+        anyhow::ensure!(
+            input_state.events().is_empty(),
+            "{} input events weren't cleared after frame {}",
+            input_state.events().len(),
+            self.frame
+        );
+        anyhow::ensure!(
+            input_state.scancode_count() <= SCANCODES as usize,
+            "state is kept for {} scancodes after frame {}, but only {SCANCODES} were used",
+            input_state.scancode_count(),
+            self.frame
+        );
+        anyhow::ensure!(
+            virtual_controls.touch_count() <= MAX_TOUCHES as usize,
+            "{} touches are held after frame {}, but only {MAX_TOUCHES} fingers are down",
+            virtual_controls.touch_count(),
+            self.frame
+        );
+
+        let first = *self.first_render_stats.get_or_insert(render_stats);
+        anyhow::ensure!(
+            render_stats.cached_bind_group_count
+                <= first.cached_bind_group_count + MAX_BIND_GROUP_GROWTH,
+            "{} bind groups are cached after frame {}, up from {} on the first frame",
+            render_stats.cached_bind_group_count,
+            self.frame,
+            first.cached_bind_group_count
+        );
+        anyhow::ensure!(
+            render_stats.local_buffer_bytes <= first.local_buffer_bytes + MAX_LOCAL_BUFFER_GROWTH,
+            "the local buffer is {} bytes after frame {}, up from {} on the first frame",
+            render_stats.local_buffer_bytes,
+            self.frame,
+            first.local_buffer_bytes
+        );
+        Ok(())
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Gets a random number from zero up to but not including `bound`.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn state(&mut self) -> ElementState {
+        match self.below(2) {
+            0 => ElementState::Pressed,
+            _ => ElementState::Released,
+        }
+    }
+
+    #[allow(deprecated)]
+    fn event(&mut self, window_size: Vec2) -> WindowEvent<'static> {
+        // SAFETY: The id is only compared, never passed to winit.
+        let device_id = unsafe { winit::event::DeviceId::dummy() };
+        match self.below(7) {
+            0 => WindowEvent::KeyboardInput {
+                device_id,
+                input: winit::event::KeyboardInput {
+                    scancode: self.below(SCANCODES) as u32,
+                    state: self.state(),
+                    virtual_keycode: Some(self.key()),
+                    modifiers: Default::default(),
+                },
+                is_synthetic: self.below(8) == 0,
+            },
+            1 => WindowEvent::MouseInput {
+                device_id,
+                state: self.state(),
+                button: match self.below(4) {
+                    0 => winit::event::MouseButton::Left,
+                    1 => winit::event::MouseButton::Right,
+                    2 => winit::event::MouseButton::Middle,
+                    _ => winit::event::MouseButton::Other(self.below(16) as u16),
+                },
+                modifiers: Default::default(),
+            },
+            2 => WindowEvent::ReceivedCharacter(
+                CHARACTERS[self.below(CHARACTERS.len() as u64) as usize],
+            ),
+            3 => {
+                let text = IME_TEXT[self.below(IME_TEXT.len() as u64) as usize].to_string();
+                WindowEvent::Ime(match self.below(4) {
+                    0 => winit::event::Ime::Enabled,
+                    1 => {
+                        let selection = (!text.is_empty()).then_some((0, text.len()));
+                        winit::event::Ime::Preedit(text, selection)
+                    }
+                    2 => winit::event::Ime::Commit(text),
+                    _ => winit::event::Ime::Disabled,
+                })
+            }
+            4 => WindowEvent::CursorMoved {
+                device_id,
+                position: self.position(window_size),
+                modifiers: Default::default(),
+            },
+            5 => WindowEvent::Focused(self.below(4) != 0),
+            _ => WindowEvent::Touch(winit::event::Touch {
+                device_id,
+                phase: match self.below(4) {
+                    0 => winit::event::TouchPhase::Started,
+                    1 | 2 => winit::event::TouchPhase::Moved,
+                    _ => winit::event::TouchPhase::Ended,
+                },
+                location: self.position(window_size),
+                force: None,
+                id: self.below(MAX_TOUCHES),
+            }),
+        }
+    }
+
+    fn key(&mut self) -> VirtualKeyCode {
+        let index = self.below(VirtualKeyCode::Cut as u64 + 1) as u32;
+        // SAFETY: Key codes are a `u32` enum without gaps, ending with `Cut`.
+        unsafe { std::mem::transmute::<u32, VirtualKeyCode>(index) }
+    }
+
+    /// Gets a random position in pixels, sometimes just outside the window.
+    fn position(&mut self, window_size: Vec2) -> winit::dpi::PhysicalPosition<f64> {
+        let mut coordinate = |size: f32| (self.below(1200) as f64 / 1000.0 - 0.1) * size as f64;
+        winit::dpi::PhysicalPosition::new(coordinate(window_size.x), coordinate(window_size.y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use super::*;
+    use crate::input::{
+        window_events::handle_window_event, Anchor, Keyboard, VirtualButton, VirtualJoystick,
+        VirtualLayout,
+    };
+
+    const SCREEN: Vec2 = vec2(800.0, 600.0);
+
+    #[test]
+    fn test_soak() {
+        let mut input_state = InputState::new();
+        let mut virtual_controls = VirtualControls::new();
+        virtual_controls.enabled = true;
+        virtual_controls.add_joystick(VirtualJoystick::new(VirtualLayout::new(
+            Anchor::BottomLeft,
+            vec2(150.0, 150.0),
+            150.0,
+        )));
+        virtual_controls.add_button(VirtualButton::new(
+            VirtualLayout::new(Anchor::BottomRight, vec2(150.0, 150.0), 150.0),
+            Keyboard::Space,
+        ));
+
+        let mut fuzzer = InputFuzzer::new(InputSoak::new(42, 5000));
+        while !fuzzer.finished() {
+            for event in fuzzer.frame_events(SCREEN) {
+                handle_window_event(&event, SCREEN, &mut input_state, &mut virtual_controls);
+            }
+            input_state.clear_typed_text();
+            input_state.begin_frame();
+            fuzzer
+                .check(&input_state, &virtual_controls, RenderStats::default())
+                .unwrap();
+        }
+
+        handle_window_event(
+            &WindowEvent::Focused(false),
+            SCREEN,
+            &mut input_state,
+            &mut virtual_controls,
+        );
+        assert_eq!(virtual_controls.touch_count(), 0);
+    }
+
+    #[test]
+    fn test_soak_catches_render_cache_growth() {
+        let input_state = InputState::new();
+        let virtual_controls = VirtualControls::new();
+        let mut fuzzer = InputFuzzer::new(InputSoak::new(42, 5000));
+        let mut render_stats = RenderStats {
+            cached_bind_group_count: 10,
+            local_buffer_bytes: 1024,
+            ..Default::default()
+        };
+
+        let check = |fuzzer: &mut InputFuzzer, render_stats| {
+            fuzzer.frame_events(SCREEN);
+            fuzzer.check(&input_state, &virtual_controls, render_stats)
+        };
+        check(&mut fuzzer, render_stats).unwrap();
+        render_stats.cached_bind_group_count += MAX_BIND_GROUP_GROWTH;
+        check(&mut fuzzer, render_stats).unwrap();
+        render_stats.cached_bind_group_count += 1;
+        assert!(check(&mut fuzzer, render_stats).is_err());
+
+        render_stats.cached_bind_group_count = 10;
+        render_stats.local_buffer_bytes += MAX_LOCAL_BUFFER_GROWTH + 1;
+        assert!(check(&mut fuzzer, render_stats).is_err());
+    }
+
+    #[test]
+    fn test_seed_replays() {
+        let events = |seed| {
+            let mut fuzzer = InputFuzzer::new(InputSoak::new(seed, 10));
+            (0..10)
+                .flat_map(|_| fuzzer.frame_events(SCREEN))
+                .map(|event| format!("{event:?}"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(events(7), events(7));
+        assert_ne!(events(7), events(8));
+    }
+}
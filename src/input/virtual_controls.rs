@@ -0,0 +1,570 @@
+use std::collections::HashMap;
+
+use glam::{vec2, vec3, vec4, Mat4, Vec2};
+
+use super::{Input, InputState};
+use crate::{
+    graphics::{texture::Texture, Mesh, RenderOperation},
+    util::{coordinates::ScreenPosition, repository::ResourceId},
+};
+
+/// Corner of the screen a virtual control is positioned relative to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    BottomLeft,
+    BottomRight,
+    TopLeft,
+    TopRight,
+}
+
+/// Where and how big a virtual control is on the screen.
+#[derive(Clone, Copy, Debug)]
+pub struct VirtualLayout {
+    /// Corner of the screen to position relative to.
+    pub anchor: Anchor,
+    /// Offset in pixels from the anchor towards the center of the screen.
+    pub offset: Vec2,
+    /// Radius of the control in pixels.
+    pub radius: f32,
+}
+
+/// On-screen analog stick driven by touch.
+#[derive(Clone, Copy, Debug)]
+pub struct VirtualJoystick {
+    /// Layout of the joystick base.
+    pub layout: VirtualLayout,
+    /// Portion of the radius (from 0 - 1) that is ignored around the center.
+    pub dead_zone: f32,
+    /// Inputs signaled when the stick is pushed past `direction_threshold`,
+    /// in the order left, right, up, down.
+    pub directions: Option<[Input; 4]>,
+    /// How far (from 0 - 1) the stick must be pushed to signal a direction.
+    pub direction_threshold: f32,
+    /// Opacity when not being touched.
+    pub opacity: f32,
+    /// Opacity while being touched.
+    pub pressed_opacity: f32,
+    axis: Vec2,
+    /// Which of `directions` the joystick itself is holding, so inputs pressed by
+    /// other devices aren't released when the stick moves.
+    pressed_directions: [bool; 4],
+}
+
+/// On-screen button driven by touch.
+#[derive(Clone, Copy, Debug)]
+pub struct VirtualButton {
+    /// Layout of the button.
+    pub layout: VirtualLayout,
+    /// Input signaled when the button is pressed.
+    pub input: Input,
+    /// Opacity when not being touched.
+    pub opacity: f32,
+    /// Opacity while being touched.
+    pub pressed_opacity: f32,
+    pressed: bool,
+}
+
+/// Phase of a touch, mirrors winit's touch phases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchPhase {
+    Started,
+    Moved,
+    Ended,
+    Cancelled,
+}
+
+/// Set of virtual joysticks and buttons that feed an [InputState] like any other device.
+#[derive(Default)]
+pub struct VirtualControls {
+    /// Whether the controls react to touches and should be rendered.
+    pub enabled: bool,
+    joysticks: Vec<VirtualJoystick>,
+    buttons: Vec<VirtualButton>,
+    /// Which control each active touch is holding.
+    touches: HashMap<u64, Grab>,
+}
+
+/// Identifies a control grabbed by a touch.
+#[derive(Clone, Copy, Debug)]
+enum Grab {
+    Joystick(usize),
+    Button(usize),
+}
+
+/// Index of a joystick within [VirtualControls].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JoystickId(usize);
+
+/// Index of a button within [VirtualControls].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ButtonId(usize);
+
+impl VirtualLayout {
+    /// Creates a new [VirtualLayout].
+    pub fn new(anchor: Anchor, offset: Vec2, radius: f32) -> Self {
+        Self {
+            anchor,
+            offset,
+            radius,
+        }
+    }
+
+    /// Gets the center of the control in pixels, with the origin at the bottom left
+    /// of the screen.
+    pub fn center(&self, screen_size: Vec2) -> Vec2 {
+        match self.anchor {
+            Anchor::BottomLeft => self.offset,
+            Anchor::BottomRight => vec2(screen_size.x - self.offset.x, self.offset.y),
+            Anchor::TopLeft => vec2(self.offset.x, screen_size.y - self.offset.y),
+            Anchor::TopRight => screen_size - self.offset,
+        }
+    }
+
+    fn contains(&self, screen_size: Vec2, point: Vec2) -> bool {
+        self.center(screen_size).distance(point) <= self.radius
+    }
+}
+
+impl VirtualJoystick {
+    /// Creates a new [VirtualJoystick] with no directional inputs.
+    pub fn new(layout: VirtualLayout) -> Self {
+        Self {
+            layout,
+            dead_zone: 0.15,
+            directions: None,
+            direction_threshold: 0.5,
+            opacity: 0.4,
+            pressed_opacity: 0.7,
+            axis: Vec2::ZERO,
+            pressed_directions: [false; 4],
+        }
+    }
+
+    /// Sets the inputs signaled when the stick is pushed left, right, up, or down.
+    pub fn with_directions(mut self, left: Input, right: Input, up: Input, down: Input) -> Self {
+        self.directions = Some([left, right, up, down]);
+        self
+    }
+
+    /// Gets the current position of the stick, where each axis is from -1 to 1 and
+    /// up is positive y.
+    pub fn axis(&self) -> Vec2 {
+        self.axis
+    }
+
+    fn set_axis(&mut self, axis: Vec2, input_state: &mut InputState) {
+        let length = axis.length();
+        self.axis = if length <= self.dead_zone {
+            Vec2::ZERO
+        } else {
+            // Rescale so the edge of the dead zone maps to 0.
+            let scaled = (length.min(1.0) - self.dead_zone) / (1.0 - self.dead_zone);
+            axis / length * scaled
+        };
+
+        if let Some([left, right, up, down]) = self.directions {
+            let threshold = self.direction_threshold;
+            let directions = [
+                (left, self.axis.x < -threshold),
+                (right, self.axis.x > threshold),
+                (up, self.axis.y > threshold),
+                (down, self.axis.y < -threshold),
+            ];
+            for ((input, active), pressed) in
+                directions.into_iter().zip(&mut self.pressed_directions)
+            {
+                match (active, *pressed) {
+                    (true, false) => input_state.signal_press_of(input),
+                    (false, true) => input_state.signal_release_of(input),
+                    _ => {}
+                }
+                *pressed = active;
+            }
+        }
+    }
+}
+
+impl VirtualButton {
+    /// Creates a new [VirtualButton] that signals the given [Input].
+    pub fn new<I: Into<Input>>(layout: VirtualLayout, input: I) -> Self {
+        Self {
+            layout,
+            input: input.into(),
+            opacity: 0.4,
+            pressed_opacity: 0.7,
+            pressed: false,
+        }
+    }
+
+    /// Checks if the button is currently held by a touch.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+impl VirtualControls {
+    /// Creates a new enabled [VirtualControls] with no controls.
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a [VirtualJoystick] and returns its [JoystickId].
+    pub fn add_joystick(&mut self, joystick: VirtualJoystick) -> JoystickId {
+        self.joysticks.push(joystick);
+        JoystickId(self.joysticks.len() - 1)
+    }
+
+    /// Adds a [VirtualButton] and returns its [ButtonId].
+    pub fn add_button(&mut self, button: VirtualButton) -> ButtonId {
+        self.buttons.push(button);
+        ButtonId(self.buttons.len() - 1)
+    }
+
+    /// Gets a [VirtualJoystick] given its [JoystickId].
+    pub fn joystick(&self, id: JoystickId) -> &VirtualJoystick {
+        &self.joysticks[id.0]
+    }
+
+    /// Gets a [VirtualJoystick] mutably given its [JoystickId], for changing its layout.
+    pub fn joystick_mut(&mut self, id: JoystickId) -> &mut VirtualJoystick {
+        &mut self.joysticks[id.0]
+    }
+
+    /// Gets a [VirtualButton] given its [ButtonId].
+    pub fn button(&self, id: ButtonId) -> &VirtualButton {
+        &self.buttons[id.0]
+    }
+
+    /// Gets a [VirtualButton] mutably given its [ButtonId], for changing its layout.
+    pub fn button_mut(&mut self, id: ButtonId) -> &mut VirtualButton {
+        &mut self.buttons[id.0]
+    }
+
+    /// Signals a touch to the controls, which in turn signal any bound inputs to
+    /// the [InputState].
+    ///
+    /// `position` is in pixels with the origin at the top left of the screen, as
+    /// reported by the window.
+    pub fn handle_touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        position: Vec2,
+        screen_size: Vec2,
+        input_state: &mut InputState,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let point = ScreenPosition(position).to_bottom_left(screen_size);
+
+        match phase {
+            TouchPhase::Started => {
+                // A touch that never ended shouldn't hold its control forever.
+                if let Some(grab) = self.touches.remove(&id) {
+                    self.release_grab(grab, input_state);
+                }
+
+                let grab = self
+                    .joysticks
+                    .iter()
+                    .position(|joystick| joystick.layout.contains(screen_size, point))
+                    .map(Grab::Joystick)
+                    .or_else(|| {
+                        self.buttons
+                            .iter()
+                            .position(|button| button.layout.contains(screen_size, point))
+                            .map(Grab::Button)
+                    });
+
+                if let Some(grab) = grab {
+                    self.touches.insert(id, grab);
+                    self.update_grab(grab, point, screen_size, input_state);
+                }
+            }
+            TouchPhase::Moved => {
+                if let Some(grab) = self.touches.get(&id).copied() {
+                    self.update_grab(grab, point, screen_size, input_state);
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                if let Some(grab) = self.touches.remove(&id) {
+                    self.release_grab(grab, input_state);
+                }
+            }
+        }
+    }
+
+    /// Gets the number of touches holding a control.
+    pub(crate) fn touch_count(&self) -> usize {
+        self.touches.len()
+    }
+
+    /// Releases every held control, for example when the window loses focus.
+    pub fn release_all(&mut self, input_state: &mut InputState) {
+        for (_, grab) in std::mem::take(&mut self.touches) {
+            self.release_grab(grab, input_state);
+        }
+    }
+
+    /// Creates [RenderOperation]s that draw the controls with a quad mesh.
+    ///
+    /// The operations are positioned in pixels with the origin at the bottom left
+    /// of the screen, so they are meant to be rendered with a projection such as
+    /// `Mat4::orthographic_rh(0.0, width, 0.0, height, -1.0, 1.0)`.
+    pub fn render_operations(
+        &self,
+        screen_size: Vec2,
+        quad_mesh_id: ResourceId<Mesh>,
+        texture_id: Option<ResourceId<Texture>>,
+    ) -> Vec<RenderOperation> {
+        if !self.enabled {
+            return Vec::new();
+        }
+
+        let circle = |center: Vec2, radius: f32, opacity: f32| {
+            let transform = Mat4::from_scale_rotation_translation(
+                vec3(radius * 2.0, radius * 2.0, 1.0),
+                Default::default(),
+                center.extend(0.0),
+            );
+            // Colors are premultiplied.
+            let color = vec4(opacity, opacity, opacity, opacity);
+            match texture_id {
+                Some(texture_id) => {
+                    RenderOperation::textured_mesh(transform, quad_mesh_id, texture_id, None, color)
+                }
+                None => RenderOperation::colored_mesh(transform, quad_mesh_id, color),
+            }
+        };
+
+        let held = |grab_matches: &dyn Fn(&Grab) -> bool| self.touches.values().any(grab_matches);
+
+        let joysticks = self
+            .joysticks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, joystick)| {
+                let center = joystick.layout.center(screen_size);
+                let radius = joystick.layout.radius;
+                let opacity = match held(&|grab| matches!(grab, Grab::Joystick(i) if *i == index)) {
+                    true => joystick.pressed_opacity,
+                    false => joystick.opacity,
+                };
+
+                [
+                    circle(center, radius, opacity),
+                    circle(center + joystick.axis * radius * 0.5, radius * 0.5, opacity),
+                ]
+            });
+
+        let buttons = self.buttons.iter().map(|button| {
+            let opacity = match button.pressed {
+                true => button.pressed_opacity,
+                false => button.opacity,
+            };
+            circle(
+                button.layout.center(screen_size),
+                button.layout.radius,
+                opacity,
+            )
+        });
+
+        joysticks.chain(buttons).collect()
+    }
+
+    fn update_grab(
+        &mut self,
+        grab: Grab,
+        point: Vec2,
+        screen_size: Vec2,
+        input_state: &mut InputState,
+    ) {
+        match grab {
+            Grab::Joystick(index) => {
+                let joystick = &mut self.joysticks[index];
+                let center = joystick.layout.center(screen_size);
+                let axis = (point - center) / joystick.layout.radius;
+                joystick.set_axis(axis, input_state);
+            }
+            Grab::Button(index) => {
+                let button = &mut self.buttons[index];
+                if !button.pressed {
+                    button.pressed = true;
+                    input_state.signal_press_of(button.input);
+                }
+            }
+        }
+    }
+
+    fn release_grab(&mut self, grab: Grab, input_state: &mut InputState) {
+        match grab {
+            Grab::Joystick(index) => self.joysticks[index].set_axis(Vec2::ZERO, input_state),
+            Grab::Button(index) => {
+                let button = &mut self.buttons[index];
+                button.pressed = false;
+                input_state.signal_release_of(button.input);
+            }
+        }
+    }
+}
+
+impl From<winit::event::TouchPhase> for TouchPhase {
+    fn from(value: winit::event::TouchPhase) -> Self {
+        match value {
+            winit::event::TouchPhase::Started => TouchPhase::Started,
+            winit::event::TouchPhase::Moved => TouchPhase::Moved,
+            winit::event::TouchPhase::Ended => TouchPhase::Ended,
+            winit::event::TouchPhase::Cancelled => TouchPhase::Cancelled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Keyboard;
+
+    const SCREEN: Vec2 = Vec2::new(800.0, 600.0);
+
+    fn controls() -> (VirtualControls, JoystickId, ButtonId) {
+        let mut controls = VirtualControls::new();
+        let joystick = controls.add_joystick(
+            VirtualJoystick::new(VirtualLayout::new(
+                Anchor::BottomLeft,
+                vec2(100.0, 100.0),
+                50.0,
+            ))
+            .with_directions(
+                Keyboard::A.into(),
+                Keyboard::D.into(),
+                Keyboard::W.into(),
+                Keyboard::S.into(),
+            ),
+        );
+        let button = controls.add_button(VirtualButton::new(
+            VirtualLayout::new(Anchor::BottomRight, vec2(100.0, 100.0), 40.0),
+            Keyboard::Space,
+        ));
+        (controls, joystick, button)
+    }
+
+    #[test]
+    fn test_button_press_and_release() {
+        let (mut controls, _, button) = controls();
+        let mut input_state = InputState::new();
+
+        // (700, 500) from the top left is (700, 100) from the bottom left.
+        controls.handle_touch(
+            0,
+            TouchPhase::Started,
+            vec2(700.0, 500.0),
+            SCREEN,
+            &mut input_state,
+        );
+        assert!(controls.button(button).is_pressed());
+        assert!(input_state.check_pressed(Keyboard::Space));
+
+        controls.handle_touch(
+            0,
+            TouchPhase::Ended,
+            vec2(700.0, 500.0),
+            SCREEN,
+            &mut input_state,
+        );
+        assert!(!controls.button(button).is_pressed());
+        assert!(input_state.check_released(Keyboard::Space));
+    }
+
+    #[test]
+    fn test_touch_outside_ignored() {
+        let (mut controls, joystick, button) = controls();
+        let mut input_state = InputState::new();
+
+        controls.handle_touch(
+            0,
+            TouchPhase::Started,
+            vec2(400.0, 300.0),
+            SCREEN,
+            &mut input_state,
+        );
+        assert!(!controls.button(button).is_pressed());
+        assert!(controls.joystick(joystick).axis() == Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_joystick_directions() {
+        let (mut controls, joystick, _) = controls();
+        let mut input_state = InputState::new();
+
+        controls.handle_touch(
+            3,
+            TouchPhase::Started,
+            vec2(100.0, 500.0),
+            SCREEN,
+            &mut input_state,
+        );
+        assert!(controls.joystick(joystick).axis() == Vec2::ZERO);
+
+        // Drag upwards past the edge of the stick, which is clamped.
+        controls.handle_touch(
+            3,
+            TouchPhase::Moved,
+            vec2(100.0, 400.0),
+            SCREEN,
+            &mut input_state,
+        );
+        assert!((controls.joystick(joystick).axis() - vec2(0.0, 1.0)).length() < 0.001);
+        assert!(input_state.check_pressed(Keyboard::W));
+        assert!(input_state.check_released(Keyboard::S));
+
+        controls.handle_touch(
+            3,
+            TouchPhase::Cancelled,
+            vec2(100.0, 400.0),
+            SCREEN,
+            &mut input_state,
+        );
+        assert!(controls.joystick(joystick).axis() == Vec2::ZERO);
+        assert!(input_state.check_released(Keyboard::W));
+    }
+
+    #[test]
+    fn test_joystick_keeps_other_presses() {
+        let (mut controls, _, _) = controls();
+        let mut input_state = InputState::new();
+        input_state.signal_press_of(Keyboard::D);
+
+        // Push the stick up, which shouldn't release D held on the keyboard.
+        controls.handle_touch(
+            0,
+            TouchPhase::Started,
+            vec2(100.0, 500.0),
+            SCREEN,
+            &mut input_state,
+        );
+        controls.handle_touch(
+            0,
+            TouchPhase::Moved,
+            vec2(100.0, 400.0),
+            SCREEN,
+            &mut input_state,
+        );
+        assert!(input_state.check_pressed(Keyboard::W));
+        assert!(input_state.check_pressed(Keyboard::D));
+
+        controls.handle_touch(
+            0,
+            TouchPhase::Ended,
+            vec2(100.0, 400.0),
+            SCREEN,
+            &mut input_state,
+        );
+        assert!(input_state.check_released(Keyboard::W));
+        assert!(input_state.check_pressed(Keyboard::D));
+    }
+}
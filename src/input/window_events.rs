@@ -0,0 +1,99 @@
+use glam::Vec2;
+use num::FromPrimitive;
+use winit::event::{ElementState, WindowEvent};
+
+use super::{InputEventKind, InputState, Keyboard, Mouse, Scancode, ScrollDelta, VirtualControls};
+
+/// Signals the input of a window event to the [InputState] and [VirtualControls],
+/// through [InputState::signal_event] where it has an [InputEventKind].
+///
+/// `window_size` is the inner size of the window in pixels. Events other than input
+/// are ignored.
+pub(crate) fn handle_window_event(
+    event: &WindowEvent,
+    window_size: Vec2,
+    input_state: &mut InputState,
+    virtual_controls: &mut VirtualControls,
+) {
+    match event {
+        WindowEvent::KeyboardInput {
+            input:
+                winit::event::KeyboardInput {
+                    scancode,
+                    virtual_keycode,
+                    state,
+                    ..
+                },
+            is_synthetic: false,
+            ..
+        } => {
+            input_state.signal_event(InputEventKind::Key {
+                scancode: Scancode(*scancode),
+                // Keys mirror winit's, but skip any a newer winit adds rather than panic.
+                key: virtual_keycode.and_then(|keycode| Keyboard::from_u32(keycode as u32)),
+                pressed: *state == ElementState::Pressed,
+            });
+        }
+        WindowEvent::ReceivedCharacter(character) => {
+            input_state.signal_event(InputEventKind::Text(character.to_string()));
+        }
+        WindowEvent::Ime(ime) => match ime {
+            winit::event::Ime::Preedit(text, selection) => {
+                input_state.signal_ime_preedit(Some((text.clone(), *selection)))
+            }
+            winit::event::Ime::Commit(text) => {
+                input_state.signal_ime_preedit(None);
+                input_state.signal_event(InputEventKind::Text(text.clone()));
+            }
+            winit::event::Ime::Enabled | winit::event::Ime::Disabled => {
+                input_state.signal_ime_preedit(None)
+            }
+        },
+        WindowEvent::MouseInput { button, state, .. } => {
+            let button = match button {
+                winit::event::MouseButton::Left => Mouse::Left,
+                winit::event::MouseButton::Right => Mouse::Right,
+                winit::event::MouseButton::Middle => Mouse::Middle,
+                winit::event::MouseButton::Other(_) => return,
+            };
+            input_state.signal_event(InputEventKind::MouseButton {
+                button,
+                pressed: *state == ElementState::Pressed,
+            });
+        }
+        WindowEvent::CursorMoved { position, .. } => {
+            let position = glam::dvec2(position.x, position.y).as_vec2();
+            input_state.signal_event(InputEventKind::CursorMoved(Some(position)));
+        }
+        WindowEvent::CursorLeft { .. } => {
+            input_state.signal_event(InputEventKind::CursorMoved(None));
+        }
+        WindowEvent::MouseWheel { delta, .. } => {
+            let delta = match delta {
+                winit::event::MouseScrollDelta::LineDelta(x, y) => {
+                    ScrollDelta::Lines(glam::vec2(*x, *y))
+                }
+                winit::event::MouseScrollDelta::PixelDelta(position) => {
+                    ScrollDelta::Pixels(glam::dvec2(position.x, position.y).as_vec2())
+                }
+            };
+            input_state.signal_event(InputEventKind::Wheel(delta));
+        }
+        WindowEvent::Touch(winit::event::Touch {
+            id,
+            phase,
+            location,
+            ..
+        }) => {
+            virtual_controls.handle_touch(
+                *id,
+                (*phase).into(),
+                glam::dvec2(location.x, location.y).as_vec2(),
+                window_size,
+                input_state,
+            );
+        }
+        WindowEvent::Focused(false) => virtual_controls.release_all(input_state),
+        _ => (),
+    }
+}
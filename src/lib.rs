@@ -1,7 +1,18 @@
 //! Small game engine written in rust mainly for personal use.
 
+mod config;
+mod config_file;
 mod engine;
+mod error;
+mod logging;
+mod timestep;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
+/// Frame timing and renderer statistics.
+pub mod diagnostics;
+/// Minimal entity component system for game objects.
+pub mod ecs;
 /// Keyboard input, mouse input, and etc.
 pub mod input;
 /// Rendering.
@@ -10,5 +21,14 @@ pub mod graphics;
 /// be better if custom built. For example, [util::camera::Camera] is a class
 /// that manages exporting a view projection matrix for rendering.
 pub mod util;
+/// Debug UI built on [egui](https://github.com/emilk/egui), enabled with the `ui`
+/// feature. Draw it with [Engine::ui_ctx] during [Application::update].
+#[cfg(feature = "ui")]
+pub mod ui;
 
-pub use engine::{ Engine, Application, run };
+pub use config::{ BackgroundBehavior, CursorGrab, EngineConfig, Fullscreen, WindowConfig, WindowIcon };
+pub use config_file::CONFIG_FILE_PATH;
+pub use engine::{ Engine, Application, run, run_with_config };
+pub use error::ClockworkError;
+pub use logging::{ span, LevelFilter, LogConfig, Span };
+pub use timestep::{ simulate, SimulationReport };
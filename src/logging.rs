@@ -0,0 +1,259 @@
+use std::{fmt, fs::File, io::Write, path::PathBuf, sync::Mutex};
+
+use anyhow::{Context, Result};
+use instant::Instant;
+pub use log::LevelFilter;
+
+/// Target spans are logged under, see [span].
+const SPAN_TARGET: &str = "clockwork::span";
+
+/// Configuration of the logger the [crate::Engine] installs at startup when set with
+/// [crate::EngineConfig::with_log].
+///
+/// Messages from the engine, wgpu and the application are logged through the [log]
+/// crate, so applications can instead install any other logger.
+#[derive(Clone, Debug)]
+pub struct LogConfig {
+    /// Most verbose level logged by modules without a filter.
+    pub level: LevelFilter,
+    /// Levels of modules and everything within them, such as `wgpu_core`. The longest
+    /// matching module wins.
+    pub filters: Vec<(String, LevelFilter)>,
+    /// Whether messages are printed to stderr, or the browser console on the web.
+    pub console: bool,
+    /// File messages are written to, replacing it if it exists.
+    pub file: Option<PathBuf>,
+}
+
+/// Measures part of a frame, logging when it begins and how long it took when dropped,
+/// at the trace level under the `clockwork::span` target. See [span].
+#[must_use = "the span ends when dropped"]
+pub struct Span {
+    name: &'static str,
+    /// When the span began, or `None` if spans aren't logged.
+    start: Option<Instant>,
+}
+
+/// Logger installed from a [LogConfig].
+struct Logger {
+    level: LevelFilter,
+    filters: Vec<(String, LevelFilter)>,
+    console: bool,
+    file: Option<Mutex<File>>,
+    start: Instant,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            // The graphics libraries are chatty below warnings.
+            filters: vec![
+                ("wgpu_core".to_string(), LevelFilter::Warn),
+                ("wgpu_hal".to_string(), LevelFilter::Warn),
+                ("naga".to_string(), LevelFilter::Warn),
+            ],
+            console: true,
+            file: None,
+        }
+    }
+}
+
+impl LogConfig {
+    /// Creates a [LogConfig] logging info and above to the console.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the most verbose level logged by modules without a filter.
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the level of a module and everything within it, such as
+    /// `clockwork::graphics`.
+    pub fn with_filter<S: Into<String>>(mut self, module: S, level: LevelFilter) -> Self {
+        let module = module.into();
+        self.filters.retain(|(filtered, _)| *filtered != module);
+        self.filters.push((module, level));
+        self
+    }
+
+    /// Sets levels from a comma separated list in the format of `RUST_LOG`, such as
+    /// `info,clockwork=debug,wgpu_core=off`, where a level on its own sets
+    /// [LogConfig::level].
+    pub fn with_filters(mut self, spec: &str) -> Result<Self> {
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let parse_level = |level: &str| {
+                level
+                    .parse::<LevelFilter>()
+                    .with_context(|| format!("invalid log level in '{directive}'"))
+            };
+            self = match directive.split_once('=') {
+                Some((module, level)) => {
+                    self.with_filter(module.trim(), parse_level(level.trim())?)
+                }
+                None => self.with_level(parse_level(directive)?),
+            };
+        }
+        Ok(self)
+    }
+
+    /// Sets whether messages are printed to stderr, or the browser console on the web.
+    pub fn with_console(mut self, console: bool) -> Self {
+        self.console = console;
+        self
+    }
+
+    /// Writes messages to a file, replacing it if it exists.
+    pub fn with_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Gets the level messages from a target are logged at, from its longest matching
+    /// filter.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        level_for(self.level, &self.filters, target)
+    }
+
+    /// Installs a logger with this configuration, which fails if one already is.
+    pub(crate) fn install(&self) -> Result<()> {
+        let file = match &self.file {
+            Some(path) => Some(Mutex::new(File::create(path).with_context(|| {
+                format!("failed to create log file {}", path.display())
+            })?)),
+            None => None,
+        };
+        let max_level = self
+            .filters
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, Ord::max);
+
+        log::set_boxed_logger(Box::new(Logger {
+            level: self.level,
+            filters: self.filters.clone(),
+            console: self.console,
+            file,
+            start: Instant::now(),
+        }))
+        .context("a logger is already installed")?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+/// Gets the level of the longest module matching a target, where a module matches
+/// itself and the modules within it.
+fn level_for(level: LevelFilter, filters: &[(String, LevelFilter)], target: &str) -> LevelFilter {
+    filters
+        .iter()
+        .filter(|(module, _)| {
+            target
+                .strip_prefix(module.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+        .max_by_key(|(module, _)| module.len())
+        .map_or(level, |(_, level)| *level)
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= level_for(self.level, &self.filters, metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "[{:>9.3}s {:<5} {}] {}",
+            self.start.elapsed().as_secs_f64(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if self.console {
+            #[cfg(not(target_arch = "wasm32"))]
+            eprintln!("{line}");
+            #[cfg(target_arch = "wasm32")]
+            crate::web::log_message(&line);
+        }
+        if let Some(file) = &self.file {
+            let _ = writeln!(file.lock().unwrap(), "{line}");
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Begins a [Span] measuring part of a frame, such as an update or render pass, which
+/// ends when dropped.
+///
+/// Spans are only measured while the trace level is logged for `clockwork::span`.
+pub fn span(name: &'static str) -> Span {
+    let enabled = log::log_enabled!(target: SPAN_TARGET, log::Level::Trace);
+    if enabled {
+        log::trace!(target: SPAN_TARGET, "begin {name}");
+    }
+    Span {
+        name,
+        start: enabled.then(Instant::now),
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            log::trace!(
+                target: SPAN_TARGET,
+                "end {} ({:.3} ms)",
+                self.name,
+                start.elapsed().as_secs_f64() * 1000.0
+            );
+        }
+    }
+}
+
+/// Logs an error, or prints it if no logger is installed so it isn't lost.
+pub(crate) fn report_error(message: impl fmt::Display) {
+    match log::max_level() == LevelFilter::Off {
+        #[cfg(not(target_arch = "wasm32"))]
+        true => eprintln!("{message}"),
+        #[cfg(target_arch = "wasm32")]
+        true => crate::web::log_error(&message.to_string()),
+        false => log::error!("{message}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        let config = LogConfig::new()
+            .with_filters("debug, clockwork=warn ,clockwork::graphics=trace,wgpu_core=off")
+            .unwrap();
+        assert_eq!(config.level, LevelFilter::Debug);
+        assert_eq!(config.level_for("clockwork"), LevelFilter::Warn);
+        assert_eq!(config.level_for("clockwork::engine"), LevelFilter::Warn);
+        assert_eq!(
+            config.level_for("clockwork::graphics::render_context"),
+            LevelFilter::Trace
+        );
+        // Only whole modules match, not names starting the same way.
+        assert_eq!(config.level_for("clockworks"), LevelFilter::Debug);
+        assert_eq!(config.level_for("wgpu_core::device"), LevelFilter::Off);
+        assert_eq!(config.level_for("wgpu_hal"), LevelFilter::Warn);
+
+        assert!(LogConfig::new().with_filters("clockwork=loud").is_err());
+    }
+}
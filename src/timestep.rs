@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use instant::Instant;
+
+/// Most fixed updates run in a single frame. Time beyond this is dropped so a slow
+/// frame doesn't cause ever more fixed updates to catch up on.
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// How long before a frame [wait_until] stops sleeping and spins instead, as sleeps can
+/// overshoot by a millisecond or more.
+#[cfg(not(target_arch = "wasm32"))]
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+/// Accumulates frame time and splits it into fixed length ticks.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FixedTimestep {
+    /// Length of a tick in seconds.
+    tick_length: f64,
+    /// Time not yet consumed by a tick.
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    /// Creates a new [FixedTimestep] running at the given ticks per second.
+    pub(crate) fn new(tick_rate: f64) -> Self {
+        Self {
+            tick_length: 1.0 / tick_rate.max(f64::EPSILON),
+            accumulator: 0.0,
+        }
+    }
+
+    /// Gets the length of a tick in seconds.
+    pub(crate) fn tick_length(&self) -> f64 {
+        self.tick_length
+    }
+
+    /// Sets the ticks per second.
+    pub(crate) fn set_tick_rate(&mut self, tick_rate: f64) {
+        self.tick_length = 1.0 / tick_rate.max(f64::EPSILON);
+        self.accumulator = self.accumulator.min(self.tick_length);
+    }
+
+    /// Adds elapsed time and returns how many ticks should run.
+    pub(crate) fn advance(&mut self, elapsed: f64) -> u32 {
+        self.accumulator += elapsed.max(0.0);
+        let ticks = (self.accumulator / self.tick_length).floor() as u32;
+        let ticks = ticks.min(MAX_TICKS_PER_FRAME);
+        self.accumulator -= ticks as f64 * self.tick_length;
+        self.accumulator = self.accumulator.min(self.tick_length);
+        ticks
+    }
+
+    /// Gets how far between the last tick and the next one the current time is, from
+    /// 0 to 1, for interpolating between the last two fixed states.
+    pub(crate) fn alpha(&self) -> f64 {
+        (self.accumulator / self.tick_length).clamp(0.0, 1.0)
+    }
+}
+
+/// Holds frames back to a most frames per second, such as to save power or keep
+/// frame times steady while vsync is off.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FrameLimiter {
+    /// Shortest time between frames, or `None` for no limit.
+    frame_length: Option<Duration>,
+    /// When the next frame may start.
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// Creates a [FrameLimiter] allowing at most `max_fps` frames per second, or any
+    /// number for `None`.
+    pub(crate) fn new(max_fps: Option<f64>) -> Self {
+        let mut limiter = Self::default();
+        limiter.set_max_fps(max_fps);
+        limiter
+    }
+
+    /// Sets the most frames per second, or `None` for no limit.
+    pub(crate) fn set_max_fps(&mut self, max_fps: Option<f64>) {
+        self.frame_length =
+            max_fps.map(|max_fps| Duration::from_secs_f64(1.0 / max_fps.max(f64::EPSILON)));
+        self.next_frame = None;
+    }
+
+    /// Gets the most frames per second, or `None` if there is no limit.
+    pub(crate) fn max_fps(&self) -> Option<f64> {
+        self.frame_length
+            .map(|frame_length| 1.0 / frame_length.as_secs_f64())
+    }
+
+    /// Gets how long to wait before starting a frame at `now`, and schedules the frame
+    /// after it.
+    pub(crate) fn frame_start(&mut self, now: Instant) -> Duration {
+        let Some(frame_length) = self.frame_length else {
+            return Duration::ZERO;
+        };
+        // Late frames start right away instead of rushing the following ones to catch
+        // up.
+        let start = self
+            .next_frame
+            .map_or(now, |next_frame| next_frame.max(now));
+        self.next_frame = Some(start + frame_length);
+        start - now
+    }
+}
+
+/// Blocks until `deadline`, sleeping for most of the wait then spinning for the rest so
+/// frames start on time.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn wait_until(deadline: Instant) {
+    let sleep = deadline
+        .checked_duration_since(Instant::now())
+        .and_then(|wait| wait.checked_sub(SPIN_MARGIN));
+    if let Some(sleep) = sleep {
+        std::thread::sleep(sleep);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+/// Result of running fixed ticks as fast as possible with [simulate] or
+/// [crate::Engine::fast_forward].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimulationReport {
+    /// Ticks that ran.
+    pub ticks: u64,
+    /// Seconds of game time the ticks covered.
+    pub simulated: f64,
+    /// Real time the ticks took.
+    pub elapsed: Duration,
+}
+
+impl SimulationReport {
+    /// Gets the ticks run per second of real time.
+    pub fn ticks_per_second(&self) -> f64 {
+        self.ticks as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Gets how many times faster than real time the simulation ran.
+    pub fn speedup(&self) -> f64 {
+        self.simulated / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Runs `tick` back to back for a number of ticks at a tick rate, with the fixed time
+/// step in seconds, without waiting for real time to pass.
+///
+/// This runs a simulation with no window or renderer, such as for balancing or soak
+/// tests. [crate::Engine::fast_forward] does the same for an application.
+pub fn simulate(ticks: u64, tick_rate: f64, mut tick: impl FnMut(f64)) -> SimulationReport {
+    let delta = FixedTimestep::new(tick_rate).tick_length();
+    let start = Instant::now();
+    for _ in 0..ticks {
+        tick(delta);
+    }
+    SimulationReport {
+        ticks,
+        simulated: ticks as f64 * delta,
+        elapsed: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let mut timestep = FixedTimestep::new(10.0);
+        assert_eq!(timestep.advance(0.05), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+        assert_eq!(timestep.advance(0.26), 3);
+        assert!((timestep.alpha() - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_advance_drops_excess_time() {
+        let mut timestep = FixedTimestep::new(10.0);
+        assert_eq!(timestep.advance(100.0), MAX_TICKS_PER_FRAME);
+        assert!(timestep.alpha() <= 1.0);
+        assert!(timestep.advance(0.0) <= 1);
+    }
+
+    #[test]
+    fn test_frame_limiter() {
+        let start = Instant::now();
+        let mut limiter = FrameLimiter::new(Some(10.0));
+        assert_eq!(limiter.frame_start(start), Duration::ZERO);
+        let wait = limiter.frame_start(start + Duration::from_millis(30));
+        assert!(wait.abs_diff(Duration::from_millis(70)) < Duration::from_micros(1));
+
+        // A late frame waits for nothing, and the next one is a whole frame after it.
+        let late = start + Duration::from_millis(500);
+        assert_eq!(limiter.frame_start(late), Duration::ZERO);
+        let wait = limiter.frame_start(late);
+        assert!(wait.abs_diff(Duration::from_millis(100)) < Duration::from_micros(1));
+
+        let mut unlimited = FrameLimiter::new(None);
+        assert_eq!(unlimited.frame_start(start), Duration::ZERO);
+        assert_eq!(unlimited.frame_start(start), Duration::ZERO);
+        assert!((limiter.max_fps().unwrap() - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_wait_until() {
+        let deadline = Instant::now() + Duration::from_millis(5);
+        wait_until(deadline);
+        assert!(Instant::now() >= deadline);
+        wait_until(deadline - Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_simulate() {
+        let mut time = 0.0;
+        let report = simulate(600, 60.0, |delta| time += delta);
+        assert_eq!(report.ticks, 600);
+        assert!((time - 10.0).abs() < 1e-9);
+        assert!((report.simulated - 10.0).abs() < 1e-9);
+        assert!(report.ticks_per_second() > 0.0);
+    }
+}
@@ -0,0 +1,313 @@
+use instant::Instant;
+
+pub use egui;
+
+mod workspace;
+
+pub use workspace::{DebugWorkspace, Dock, WorkspacePanel};
+
+/// Collects window events into the input egui reads each frame.
+pub(crate) struct UiInput {
+    raw_input: egui::RawInput,
+    start: Instant,
+    /// Latest pointer position in points, which mouse button events don't include.
+    pointer_position: Option<egui::Pos2>,
+    scale_factor: f32,
+    /// Cursor icon last requested by egui, so the window's is only set when it changes.
+    cursor_icon: egui::CursorIcon,
+}
+
+impl UiInput {
+    /// Creates a new [UiInput].
+    pub(crate) fn new(scale_factor: f64) -> Self {
+        Self {
+            raw_input: egui::RawInput {
+                focused: true,
+                ..Default::default()
+            },
+            start: Instant::now(),
+            pointer_position: None,
+            scale_factor: scale_factor as f32,
+            cursor_icon: egui::CursorIcon::Default,
+        }
+    }
+
+    /// Translates a window event into egui input.
+    pub(crate) fn handle_window_event(&mut self, event: &winit::event::WindowEvent) {
+        use winit::event::{ElementState, MouseScrollDelta, WindowEvent};
+
+        let modifiers = self.raw_input.modifiers;
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = egui::pos2(
+                    position.x as f32 / self.scale_factor,
+                    position.y as f32 / self.scale_factor,
+                );
+                self.pointer_position = Some(position);
+                self.raw_input
+                    .events
+                    .push(egui::Event::PointerMoved(position));
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.pointer_position = None;
+                self.raw_input.events.push(egui::Event::PointerGone);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    winit::event::MouseButton::Left => egui::PointerButton::Primary,
+                    winit::event::MouseButton::Right => egui::PointerButton::Secondary,
+                    winit::event::MouseButton::Middle => egui::PointerButton::Middle,
+                    winit::event::MouseButton::Other(_) => return,
+                };
+                if let Some(pos) = self.pointer_position {
+                    self.raw_input.events.push(egui::Event::PointerButton {
+                        pos,
+                        button,
+                        pressed: *state == ElementState::Pressed,
+                        modifiers,
+                    });
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match *delta {
+                    // Roughly how far a line scrolls in points.
+                    MouseScrollDelta::LineDelta(x, y) => egui::vec2(x, y) * 50.0,
+                    MouseScrollDelta::PixelDelta(delta) => {
+                        egui::vec2(delta.x as f32, delta.y as f32) / self.scale_factor
+                    }
+                };
+                let event = match modifiers.ctrl || modifiers.command {
+                    true => egui::Event::Zoom((delta.y / 200.0).exp()),
+                    false => egui::Event::Scroll(delta),
+                };
+                self.raw_input.events.push(event);
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    winit::event::KeyboardInput {
+                        virtual_keycode: Some(keycode),
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(key) = key_from_keycode(*keycode) {
+                    self.raw_input.events.push(egui::Event::Key {
+                        key,
+                        pressed: *state == ElementState::Pressed,
+                        repeat: false,
+                        modifiers,
+                    });
+                }
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.raw_input.modifiers = egui::Modifiers {
+                    alt: state.alt(),
+                    ctrl: state.ctrl(),
+                    shift: state.shift(),
+                    mac_cmd: cfg!(target_os = "macos") && state.logo(),
+                    command: if cfg!(target_os = "macos") {
+                        state.logo()
+                    } else {
+                        state.ctrl()
+                    },
+                };
+            }
+            WindowEvent::ReceivedCharacter(character) => {
+                // Shortcuts and keys like backspace are sent as key events instead.
+                let is_shortcut = modifiers.ctrl || modifiers.mac_cmd;
+                if !is_shortcut && !character.is_control() {
+                    self.raw_input
+                        .events
+                        .push(egui::Event::Text(character.to_string()));
+                }
+            }
+            WindowEvent::Ime(ime) => {
+                let event = match ime {
+                    winit::event::Ime::Enabled => egui::Event::CompositionStart,
+                    winit::event::Ime::Preedit(text, _) => {
+                        egui::Event::CompositionUpdate(text.clone())
+                    }
+                    winit::event::Ime::Commit(text) => egui::Event::CompositionEnd(text.clone()),
+                    winit::event::Ime::Disabled => return,
+                };
+                self.raw_input.events.push(event);
+            }
+            WindowEvent::Focused(focused) => self.raw_input.focused = *focused,
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.scale_factor = *scale_factor as f32;
+            }
+            _ => (),
+        }
+    }
+
+    /// Takes the input gathered since the last frame.
+    pub(crate) fn take(&mut self, window: &winit::window::Window) -> egui::RawInput {
+        let size = window.inner_size();
+        self.raw_input.screen_rect = Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(size.width as f32, size.height as f32) / self.scale_factor,
+        ));
+        self.raw_input.pixels_per_point = Some(self.scale_factor);
+        self.raw_input.time = Some(self.start.elapsed().as_secs_f64());
+        self.raw_input.take()
+    }
+
+    /// Applies what egui asked of the window, such as changing the cursor.
+    pub(crate) fn apply_platform_output(
+        &mut self,
+        window: &winit::window::Window,
+        platform_output: egui::PlatformOutput,
+    ) {
+        if platform_output.cursor_icon == self.cursor_icon {
+            return;
+        }
+        self.cursor_icon = platform_output.cursor_icon;
+
+        match cursor_icon_from_egui(platform_output.cursor_icon) {
+            Some(icon) => {
+                window.set_cursor_visible(true);
+                window.set_cursor_icon(icon);
+            }
+            None => window.set_cursor_visible(false),
+        }
+    }
+}
+
+fn key_from_keycode(keycode: winit::event::VirtualKeyCode) -> Option<egui::Key> {
+    use egui::Key;
+    use winit::event::VirtualKeyCode;
+
+    Some(match keycode {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Insert => Key::Insert,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::PageUp => Key::PageUp,
+        VirtualKeyCode::PageDown => Key::PageDown,
+        VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => Key::Minus,
+        VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+            Key::PlusEquals
+        }
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => Key::Num0,
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => Key::Num1,
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => Key::Num2,
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => Key::Num3,
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => Key::Num4,
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => Key::Num5,
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => Key::Num6,
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Key::Num7,
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Key::Num8,
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Key::Num9,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::B => Key::B,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::D => Key::D,
+        VirtualKeyCode::E => Key::E,
+        VirtualKeyCode::F => Key::F,
+        VirtualKeyCode::G => Key::G,
+        VirtualKeyCode::H => Key::H,
+        VirtualKeyCode::I => Key::I,
+        VirtualKeyCode::J => Key::J,
+        VirtualKeyCode::K => Key::K,
+        VirtualKeyCode::L => Key::L,
+        VirtualKeyCode::M => Key::M,
+        VirtualKeyCode::N => Key::N,
+        VirtualKeyCode::O => Key::O,
+        VirtualKeyCode::P => Key::P,
+        VirtualKeyCode::Q => Key::Q,
+        VirtualKeyCode::R => Key::R,
+        VirtualKeyCode::S => Key::S,
+        VirtualKeyCode::T => Key::T,
+        VirtualKeyCode::U => Key::U,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::W => Key::W,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Y => Key::Y,
+        VirtualKeyCode::Z => Key::Z,
+        VirtualKeyCode::F1 => Key::F1,
+        VirtualKeyCode::F2 => Key::F2,
+        VirtualKeyCode::F3 => Key::F3,
+        VirtualKeyCode::F4 => Key::F4,
+        VirtualKeyCode::F5 => Key::F5,
+        VirtualKeyCode::F6 => Key::F6,
+        VirtualKeyCode::F7 => Key::F7,
+        VirtualKeyCode::F8 => Key::F8,
+        VirtualKeyCode::F9 => Key::F9,
+        VirtualKeyCode::F10 => Key::F10,
+        VirtualKeyCode::F11 => Key::F11,
+        VirtualKeyCode::F12 => Key::F12,
+        _ => return None,
+    })
+}
+
+/// Gets the window's cursor for an egui cursor, or `None` to hide it.
+fn cursor_icon_from_egui(icon: egui::CursorIcon) -> Option<winit::window::CursorIcon> {
+    use egui::CursorIcon;
+    use winit::window::CursorIcon as WinitCursorIcon;
+
+    Some(match icon {
+        CursorIcon::None => return None,
+        CursorIcon::ContextMenu => WinitCursorIcon::ContextMenu,
+        CursorIcon::Help => WinitCursorIcon::Help,
+        CursorIcon::PointingHand => WinitCursorIcon::Hand,
+        CursorIcon::Progress => WinitCursorIcon::Progress,
+        CursorIcon::Wait => WinitCursorIcon::Wait,
+        CursorIcon::Cell => WinitCursorIcon::Cell,
+        CursorIcon::Crosshair => WinitCursorIcon::Crosshair,
+        CursorIcon::Text => WinitCursorIcon::Text,
+        CursorIcon::VerticalText => WinitCursorIcon::VerticalText,
+        CursorIcon::Alias => WinitCursorIcon::Alias,
+        CursorIcon::Copy => WinitCursorIcon::Copy,
+        CursorIcon::Move => WinitCursorIcon::Move,
+        CursorIcon::NoDrop => WinitCursorIcon::NoDrop,
+        CursorIcon::NotAllowed => WinitCursorIcon::NotAllowed,
+        CursorIcon::Grab => WinitCursorIcon::Grab,
+        CursorIcon::Grabbing => WinitCursorIcon::Grabbing,
+        CursorIcon::AllScroll => WinitCursorIcon::AllScroll,
+        CursorIcon::ZoomIn => WinitCursorIcon::ZoomIn,
+        CursorIcon::ZoomOut => WinitCursorIcon::ZoomOut,
+        CursorIcon::ResizeHorizontal | CursorIcon::ResizeColumn => WinitCursorIcon::EwResize,
+        CursorIcon::ResizeVertical | CursorIcon::ResizeRow => WinitCursorIcon::NsResize,
+        CursorIcon::ResizeNeSw => WinitCursorIcon::NeswResize,
+        CursorIcon::ResizeNwSe => WinitCursorIcon::NwseResize,
+        CursorIcon::ResizeEast => WinitCursorIcon::EResize,
+        CursorIcon::ResizeSouthEast => WinitCursorIcon::SeResize,
+        CursorIcon::ResizeSouth => WinitCursorIcon::SResize,
+        CursorIcon::ResizeSouthWest => WinitCursorIcon::SwResize,
+        CursorIcon::ResizeWest => WinitCursorIcon::WResize,
+        CursorIcon::ResizeNorthWest => WinitCursorIcon::NwResize,
+        CursorIcon::ResizeNorth => WinitCursorIcon::NResize,
+        CursorIcon::ResizeNorthEast => WinitCursorIcon::NeResize,
+        CursorIcon::Default => WinitCursorIcon::Default,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_text() {
+        let mut input = UiInput::new(2.0);
+        input.handle_window_event(&winit::event::WindowEvent::ReceivedCharacter('a'));
+        // Backspace arrives as a key event, not text.
+        input.handle_window_event(&winit::event::WindowEvent::ReceivedCharacter('\u{8}'));
+        assert_eq!(input.raw_input.events, vec![egui::Event::Text("a".into())]);
+
+        input.handle_window_event(&winit::event::WindowEvent::ModifiersChanged(
+            winit::event::ModifiersState::CTRL,
+        ));
+        input.handle_window_event(&winit::event::WindowEvent::ReceivedCharacter('c'));
+        assert_eq!(input.raw_input.events.len(), 1);
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::VecDeque;
+
+use super::egui;
+use crate::{
+    diagnostics::{self, FrameStats},
+    ecs::{Entity, World},
+    graphics::RenderContext,
+};
+
+/// Most lines the console keeps before dropping the oldest.
+const CONSOLE_HISTORY: usize = 500;
+
+/// Engine panel of the [DebugWorkspace].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkspacePanel {
+    /// Frame times and renderer work, as in [crate::Engine::stats].
+    Stats,
+    /// Lines from [DebugWorkspace::log], with a line for typing commands.
+    Console,
+    /// Loaded textures and meshes.
+    Assets,
+    /// Entities of the [World] given to [DebugWorkspace::inspect_world].
+    Inspector,
+}
+
+/// Edge of the screen panels of a [DebugWorkspace] are docked to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dock {
+    Left,
+    Right,
+    Bottom,
+}
+
+const DOCKS: [Dock; 3] = [Dock::Left, Dock::Right, Dock::Bottom];
+
+/// Debug workspace with engine panels docked around the edges of the screen, enabled
+/// with [crate::Engine::set_debug_workspace].
+///
+/// Panels sharing a dock are shown as tabs, and can be moved with
+/// [DebugWorkspace::dock].
+pub struct DebugWorkspace {
+    /// Panels docked to each of [DOCKS] in tab order, along with the open tab.
+    docks: [(Vec<WorkspacePanel>, usize); 3],
+    console: VecDeque<String>,
+    console_input: String,
+    /// Commands typed into the console, see [DebugWorkspace::take_commands].
+    commands: Vec<String>,
+    /// Entities and their component names as of the last
+    /// [DebugWorkspace::inspect_world].
+    entities: Vec<(Entity, Vec<&'static str>)>,
+    selected_entity: Option<Entity>,
+}
+
+impl Default for DebugWorkspace {
+    fn default() -> Self {
+        Self {
+            docks: [
+                (vec![WorkspacePanel::Inspector], 0),
+                (vec![WorkspacePanel::Stats, WorkspacePanel::Assets], 0),
+                (vec![WorkspacePanel::Console], 0),
+            ],
+            console: VecDeque::new(),
+            console_input: String::new(),
+            commands: Vec::new(),
+            entities: Vec::new(),
+            selected_entity: None,
+        }
+    }
+}
+
+impl DebugWorkspace {
+    /// Creates a new [DebugWorkspace] with the inspector on the left, stats and assets
+    /// on the right and the console along the bottom.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves a panel to the end of a dock's tabs and opens it.
+    pub fn dock(&mut self, panel: WorkspacePanel, dock: Dock) {
+        for (panels, open) in self.docks.iter_mut() {
+            panels.retain(|docked| *docked != panel);
+            *open = (*open).min(panels.len().saturating_sub(1));
+        }
+        let (panels, open) = &mut self.docks[dock_index(dock)];
+        panels.push(panel);
+        *open = panels.len() - 1;
+    }
+
+    /// Gets the dock a panel is in.
+    pub fn dock_of(&self, panel: WorkspacePanel) -> Option<Dock> {
+        DOCKS
+            .into_iter()
+            .zip(self.docks.iter())
+            .find(|(_, (panels, _))| panels.contains(&panel))
+            .map(|(dock, _)| dock)
+    }
+
+    /// Adds a line to the console.
+    pub fn log(&mut self, line: impl Into<String>) {
+        if self.console.len() == CONSOLE_HISTORY {
+            self.console.pop_front();
+        }
+        self.console.push_back(line.into());
+    }
+
+    /// Takes the commands typed into the console since the last call, for the game to
+    /// run.
+    pub fn take_commands(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.commands)
+    }
+
+    /// Lists the entities of a world in the inspector. Call this each frame the world
+    /// changes to keep the inspector up to date.
+    pub fn inspect_world(&mut self, world: &World) {
+        self.entities = world
+            .entities()
+            .map(|entity| (entity, world.component_names(entity)))
+            .collect();
+        if let Some(selected_entity) = self.selected_entity {
+            if !world.is_alive(selected_entity) {
+                self.selected_entity = None;
+            }
+        }
+    }
+
+    /// Draws the workspace's panels.
+    pub(crate) fn show(
+        &mut self,
+        ctx: &egui::Context,
+        stats: &FrameStats,
+        render_context: &RenderContext,
+    ) {
+        for (index, dock) in DOCKS.into_iter().enumerate() {
+            if self.docks[index].0.is_empty() {
+                continue;
+            }
+
+            let add_contents = |ui: &mut egui::Ui| {
+                let (panels, open) = &mut self.docks[index];
+                if panels.len() > 1 {
+                    ui.horizontal(|ui| {
+                        for (tab, panel) in panels.iter().enumerate() {
+                            if ui
+                                .selectable_label(*open == tab, panel_title(*panel))
+                                .clicked()
+                            {
+                                *open = tab;
+                            }
+                        }
+                    });
+                    ui.separator();
+                }
+                let panel = panels[*open];
+                self.show_panel(ui, panel, stats, render_context);
+            };
+            let id = egui::Id::new(("clockwork debug workspace", index));
+            match dock {
+                Dock::Left => {
+                    egui::SidePanel::left(id)
+                        .default_width(220.0)
+                        .show(ctx, add_contents);
+                }
+                Dock::Right => {
+                    egui::SidePanel::right(id)
+                        .default_width(220.0)
+                        .show(ctx, add_contents);
+                }
+                Dock::Bottom => {
+                    egui::TopBottomPanel::bottom(id)
+                        .resizable(true)
+                        .default_height(160.0)
+                        .show(ctx, add_contents);
+                }
+            }
+        }
+    }
+
+    fn show_panel(
+        &mut self,
+        ui: &mut egui::Ui,
+        panel: WorkspacePanel,
+        stats: &FrameStats,
+        render_context: &RenderContext,
+    ) {
+        match panel {
+            WorkspacePanel::Stats => diagnostics::stats_grid(ui, stats),
+            WorkspacePanel::Console => self.show_console(ui),
+            WorkspacePanel::Assets => show_assets(ui, render_context),
+            WorkspacePanel::Inspector => self.show_inspector(ui),
+        }
+    }
+
+    fn show_console(&mut self, ui: &mut egui::Ui) {
+        let mut submitted = false;
+        ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.console_input)
+                    .desired_width(f32::INFINITY)
+                    .hint_text("command"),
+            );
+            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                submitted = true;
+                response.request_focus();
+            }
+
+            egui::ScrollArea::vertical()
+                .auto_shrink([false; 2])
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
+                        for line in self.console.iter() {
+                            ui.monospace(line);
+                        }
+                    });
+                });
+        });
+
+        if submitted {
+            self.submit_command();
+        }
+    }
+
+    /// Moves the typed command into the queue, echoing it to the console.
+    fn submit_command(&mut self) {
+        let command = std::mem::take(&mut self.console_input);
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        self.log(format!("> {command}"));
+        self.commands.push(command.to_string());
+    }
+
+    fn show_inspector(&mut self, ui: &mut egui::Ui) {
+        if self.entities.is_empty() {
+            ui.label("No entities, see DebugWorkspace::inspect_world.");
+            return;
+        }
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                for (entity, components) in self.entities.iter() {
+                    let selected = self.selected_entity == Some(*entity);
+                    let label = format!("{}v{}", entity.index, entity.generation);
+                    if ui.selectable_label(selected, label).clicked() {
+                        self.selected_entity = match selected {
+                            true => None,
+                            false => Some(*entity),
+                        };
+                    }
+                    if selected {
+                        ui.indent(entity, |ui| {
+                            for component in components {
+                                ui.monospace(*component);
+                            }
+                        });
+                    }
+                }
+            });
+    }
+}
+
+fn show_assets(ui: &mut egui::Ui, render_context: &RenderContext) {
+    egui::ScrollArea::vertical()
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            let textures: Vec<_> = render_context.texture_sizes().collect();
+            egui::CollapsingHeader::new(format!("Textures ({})", textures.len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::Grid::new("clockwork workspace textures")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (texture_id, size) in textures {
+                                ui.monospace(texture_id.index.to_string());
+                                ui.label(format!("{}x{}", size.x, size.y));
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            let meshes: Vec<_> = render_context.mesh_ids().collect();
+            egui::CollapsingHeader::new(format!("Meshes ({})", meshes.len())).show(ui, |ui| {
+                for mesh_id in meshes {
+                    ui.monospace(mesh_id.index.to_string());
+                }
+            });
+        });
+}
+
+fn panel_title(panel: WorkspacePanel) -> &'static str {
+    match panel {
+        WorkspacePanel::Stats => "Stats",
+        WorkspacePanel::Console => "Console",
+        WorkspacePanel::Assets => "Assets",
+        WorkspacePanel::Inspector => "Inspector",
+    }
+}
+
+fn dock_index(dock: Dock) -> usize {
+    match dock {
+        Dock::Left => 0,
+        Dock::Right => 1,
+        Dock::Bottom => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dock() {
+        let mut workspace = DebugWorkspace::new();
+        assert_eq!(workspace.dock_of(WorkspacePanel::Assets), Some(Dock::Right));
+
+        workspace.dock(WorkspacePanel::Assets, Dock::Left);
+        assert_eq!(workspace.dock_of(WorkspacePanel::Assets), Some(Dock::Left));
+        assert_eq!(
+            workspace.docks[0],
+            (vec![WorkspacePanel::Inspector, WorkspacePanel::Assets], 1)
+        );
+        assert_eq!(workspace.docks[1], (vec![WorkspacePanel::Stats], 0));
+    }
+
+    #[test]
+    fn test_console_commands() {
+        let mut workspace = DebugWorkspace::new();
+        workspace.console_input = "  spawn enemy ".into();
+        workspace.submit_command();
+        workspace.submit_command();
+        assert_eq!(workspace.take_commands(), vec!["spawn enemy".to_string()]);
+        assert!(workspace.take_commands().is_empty());
+        assert_eq!(
+            workspace.console.back().map(String::as_str),
+            Some("> spawn enemy")
+        );
+
+        for line in 0..CONSOLE_HISTORY {
+            workspace.log(line.to_string());
+        }
+        assert_eq!(workspace.console.len(), CONSOLE_HISTORY);
+        assert_eq!(workspace.console.front().map(String::as_str), Some("0"));
+    }
+
+    #[test]
+    fn test_inspect_world() {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, 1.0f32).unwrap();
+
+        let mut workspace = DebugWorkspace::new();
+        workspace.selected_entity = Some(entity);
+        workspace.inspect_world(&world);
+        assert_eq!(workspace.entities, vec![(entity, vec!["f32"])]);
+
+        world.despawn(entity);
+        workspace.inspect_world(&world);
+        assert!(workspace.entities.is_empty());
+        assert_eq!(workspace.selected_entity, None);
+    }
+}
@@ -0,0 +1,197 @@
+use std::{fmt, time::Duration};
+
+use anyhow::Context;
+use glam::{UVec2, Vec4};
+use serde::{de, Deserialize, Deserializer};
+
+use crate::graphics::texture::Texture;
+
+use super::{
+    repository::ResourceId,
+    sprite::{Sprite, SpriteFrame},
+};
+
+/// Sprites parsed from an aseprite sprite sheet.
+pub(crate) struct AsepriteSheet {
+    /// Filename of the image the sprites came from.
+    pub image: String,
+    /// Sprites paired with the tag they came from, or `None` if the sheet has no tags.
+    pub sprites: Vec<(Option<String>, Sprite)>,
+}
+
+// ####################################
+// For deserializing the Aseprite file
+// ####################################
+#[derive(Deserialize)]
+struct RawSheet {
+    frames: RawFrames,
+    meta: RawMeta,
+}
+
+#[derive(Deserialize)]
+struct RawMeta {
+    image: String,
+    size: RawSize,
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<RawTag>,
+}
+
+#[derive(Deserialize)]
+struct RawTag {
+    name: Option<String>,
+    from: usize,
+    to: usize,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct RawSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct RawRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawFrame {
+    frame: RawRect,
+    #[serde(default)]
+    rotated: bool,
+    sprite_source_size: Option<RawRect>,
+    source_size: Option<RawSize>,
+    duration: Option<u64>,
+}
+
+/// Frames in file order, from either the array or the hash export format.
+struct RawFrames(Vec<RawFrame>);
+
+impl<'de> Deserialize<'de> for RawFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> de::Visitor<'de> for FramesVisitor {
+            type Value = RawFrames;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an array or map of frames")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<RawFrames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element()? {
+                    frames.push(frame);
+                }
+                Ok(RawFrames(frames))
+            }
+
+            // Visiting the map directly keeps the frames in file order, which a
+            // `serde_json::Map` would sort by name.
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<RawFrames, A::Error> {
+                let mut frames = Vec::new();
+                while let Some((_, frame)) = map.next_entry::<de::IgnoredAny, RawFrame>()? {
+                    frames.push(frame);
+                }
+                Ok(RawFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+// ####################################
+
+/// Aseprite's frame duration when none is given.
+const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(100);
+
+/// Parses json content generated by aseprite into one [Sprite] per tag.
+pub(crate) fn parse_aseprite_sheet(
+    raw_json: &str,
+    texture: ResourceId<Texture>,
+) -> anyhow::Result<AsepriteSheet> {
+    let sheet: RawSheet =
+        serde_json::from_str(raw_json).context("failed to parse aseprite json")?;
+    let RawFrames(frames) = sheet.frames;
+    anyhow::ensure!(!frames.is_empty(), "aseprite json has no frames");
+
+    let texture_dims = UVec2::new(sheet.meta.size.w, sheet.meta.size.h).as_vec2();
+    anyhow::ensure!(
+        texture_dims.cmpgt(glam::Vec2::ZERO).all(),
+        "aseprite json has an empty texture size"
+    );
+
+    let frames: Vec<SpriteFrame> = frames
+        .iter()
+        .map(|raw| {
+            let RawRect { x, y, w, h } = raw.frame;
+            // Rotated frames are packed 90 degrees clockwise, swapping their width and
+            // height within the texture.
+            let packed_dims = match raw.rotated {
+                true => UVec2::new(h, w),
+                false => UVec2::new(w, h),
+            };
+            let source_rect = raw
+                .sprite_source_size
+                .unwrap_or(RawRect { x: 0, y: 0, w, h });
+            let source_size = raw.source_size.unwrap_or(RawSize { w, h });
+
+            SpriteFrame {
+                uv_window: Vec4::new(
+                    x as f32 / texture_dims.x,
+                    y as f32 / texture_dims.y,
+                    packed_dims.x as f32 / texture_dims.x,
+                    packed_dims.y as f32 / texture_dims.y,
+                ),
+                duration: raw
+                    .duration
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_FRAME_DURATION),
+                rotated: raw.rotated,
+                trim_offset: UVec2::new(source_rect.x, source_rect.y),
+                trim_size: UVec2::new(source_rect.w, source_rect.h),
+                source_size: UVec2::new(source_size.w, source_size.h),
+            }
+        })
+        .collect();
+
+    let tags = match sheet.meta.frame_tags.is_empty() {
+        true => vec![RawTag {
+            name: None,
+            from: 0,
+            to: frames.len() - 1,
+        }],
+        false => sheet.meta.frame_tags,
+    };
+
+    let sprites = tags
+        .into_iter()
+        .map(|tag| {
+            let tag_frames = frames.get(tag.from..=tag.to).with_context(|| {
+                format!(
+                    "tag {:?} covers frames {}..={} but there are only {} frames",
+                    tag.name,
+                    tag.from,
+                    tag.to,
+                    frames.len()
+                )
+            })?;
+
+            let sprite = Sprite {
+                texture,
+                sprite_dims: tag_frames[0].source_size,
+                frames: tag_frames.to_vec(),
+            };
+            Ok((tag.name, sprite))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(AsepriteSheet {
+        image: sheet.meta.image,
+        sprites,
+    })
+}
@@ -1,5 +1,9 @@
 use std::cell::RefCell;
 
+use crate::graphics::RenderLayers;
+
+use super::coordinates::{self, ScreenPosition};
+
 /// Fields regarding the projection of a [Camera].
 #[derive(Clone, Copy)]
 pub enum Projection {
@@ -13,12 +17,26 @@ pub enum Projection {
         /// How far objects can be before they are clipped.
         zfar: f32,
     },
+    /// Projection without perspective, so things stay the same size however far away
+    /// they are, such as for 2d games and level editors.
+    Orthographic {
+        /// Aspect ratio of the target display.
+        aspect: f32,
+        /// Height of the view in world units, with the width following from the aspect.
+        height: f32,
+        /// How close objects can get before they are clipped.
+        znear: f32,
+        /// How far objects can be before they are clipped.
+        zfar: f32,
+    },
 }
 
 /// Helper for generating a view projection matrix (the model comes later)
 pub struct Camera {
     /// Transformation of the [Camera].
     pub affine: glam::Affine3A,
+    /// Layers the [Camera] draws, see [crate::graphics::RenderPassDescriptor::from_camera].
+    pub layers: RenderLayers,
     projection: Projection,
     projection_mat: RefCell<Option<glam::Mat4>>,
 }
@@ -28,6 +46,10 @@ impl Projection {
         match self {
             Projection::Perspective { aspect, fov, znear, zfar } =>
                 glam::Mat4::perspective_rh(fov, aspect, znear, zfar),
+            Projection::Orthographic { aspect, height, znear, zfar } => {
+                let half = glam::Vec2::new(height * aspect, height) * 0.5;
+                glam::Mat4::orthographic_rh(-half.x, half.x, -half.y, half.y, znear, zfar)
+            }
         }
     }
 }
@@ -37,11 +59,18 @@ impl Camera {
     pub fn new(affine: glam::Affine3A, projection: Projection) -> Self {
         Self {
             affine,
+            layers: RenderLayers::ALL,
             projection,
             projection_mat: RefCell::new(None),
         }
     }
 
+    /// Sets the layers the [Camera] draws.
+    pub fn with_layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
     /// Mutates the [Projection] of this [Camera].
     ///
     /// Since generating the projection matrix takes work, it is only regenerated if
@@ -52,6 +81,12 @@ impl Camera {
         &mut self.projection
     }
 
+    /// Gets the view matrix for this [Camera], such as to turn billboards toward it with
+    /// [crate::graphics::Billboard].
+    pub fn get_view_matrix(&self) -> glam::Mat4 {
+        self.affine.inverse().into()
+    }
+
     /// Gets the current view projection matrix for this [Camera].
     pub fn get_view_projection_matrix(&self) -> glam::Mat4 {
         let projection_mat = *self.projection_mat
@@ -59,4 +94,16 @@ impl Camera {
             .get_or_insert_with(|| self.projection.to_matrix());
         projection_mat * self.affine.inverse()
     }
+
+    /// Projects a world position onto a screen of the given size, or `None` if it's
+    /// behind this [Camera].
+    pub fn world_to_screen(&self, position: glam::Vec3, screen_size: glam::Vec2) -> Option<ScreenPosition> {
+        coordinates::world_to_screen(self.get_view_projection_matrix(), position, screen_size)
+    }
+
+    /// Gets the ray from this [Camera] through a screen position, as its origin and
+    /// normalized direction.
+    pub fn screen_to_world_ray(&self, position: ScreenPosition, screen_size: glam::Vec2) -> (glam::Vec3, glam::Vec3) {
+        coordinates::screen_to_world_ray(self.get_view_projection_matrix(), position, screen_size)
+    }
 }
@@ -0,0 +1,354 @@
+use glam::{Affine3A, Quat, Vec2, Vec3};
+
+use crate::input::{InputEventKind, InputState, Keyboard, Mouse, ScrollDelta};
+
+use super::camera::Camera;
+
+/// Furthest the pitch of a controller gets from level, just short of straight up or
+/// down so the view never flips over.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// Pixels scrolled by a touchpad that count as one line of a mouse wheel.
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/// Free flying camera moved with WASD, Space and Left Shift, and turned with the mouse.
+///
+/// Mouse motion is read from [InputState::mouse_motion], so lock the cursor with
+/// [crate::Engine::set_cursor_grab] while flying.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlyCamera {
+    pub position: Vec3,
+    /// Rotation around the y axis in radians, where 0 looks down -z.
+    pub yaw: f32,
+    /// Rotation up from level in radians.
+    pub pitch: f32,
+    /// Units moved per second.
+    pub speed: f32,
+    /// Radians turned per unit of mouse motion.
+    pub sensitivity: f32,
+}
+
+/// Camera turned around and zoomed toward a target, such as to inspect a model or for
+/// strategy games.
+///
+/// [OrbitCamera::update] orbits while the right mouse button is held and zooms with the
+/// mouse wheel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitCamera {
+    /// Point orbited around and looked at.
+    pub target: Vec3,
+    /// Rotation around the y axis in radians, where 0 looks down -z.
+    pub yaw: f32,
+    /// Rotation up from level in radians, where positive looks down on the target.
+    pub pitch: f32,
+    /// Distance from the target.
+    pub distance: f32,
+    /// Closest the camera zooms to the target.
+    pub min_distance: f32,
+    /// Furthest the camera zooms from the target.
+    pub max_distance: f32,
+    /// Radians turned per unit of mouse motion.
+    pub sensitivity: f32,
+    /// Fraction of the distance zoomed per line scrolled.
+    pub zoom_speed: f32,
+}
+
+/// 2d camera that smoothly follows a target, such as the player, without leaving the
+/// bounds of the world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FollowCamera2d {
+    /// Center of the view.
+    pub position: Vec2,
+    /// How quickly the camera catches up, as the fraction of the way to the target
+    /// closed per second on a log scale. 0 never moves, higher is snappier.
+    pub smoothing: f32,
+    /// Half size of the region around the center the target can move in without the
+    /// camera following.
+    pub deadzone: Vec2,
+    /// Minimum and maximum corners of the world the view stays within, if any.
+    pub bounds: Option<(Vec2, Vec2)>,
+}
+
+/// Gets the rotation of a camera looking in the direction of a yaw and pitch.
+fn look_rotation(yaw: f32, pitch: f32) -> Quat {
+    Quat::from_rotation_y(yaw) * Quat::from_rotation_x(pitch)
+}
+
+/// Gets how far to move toward a target this frame to close it at a rate per second,
+/// independently of the frame rate.
+fn smoothing_factor(smoothing: f32, delta: f32) -> f32 {
+    1.0 - (-smoothing * delta).exp()
+}
+
+impl FlyCamera {
+    /// Creates a [FlyCamera] at a position looking down -z.
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 5.0,
+            sensitivity: 0.003,
+        }
+    }
+
+    /// Sets the units moved per second.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Sets the radians turned per unit of mouse motion.
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    /// Turns by an amount of mouse motion, with y pointing down.
+    pub fn turn(&mut self, motion: Vec2) {
+        self.yaw -= motion.x * self.sensitivity;
+        self.pitch = (self.pitch - motion.y * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves relative to where the camera faces, with x to the right, y up and z
+    /// forward, scaled by the speed over `delta` seconds.
+    pub fn fly(&mut self, direction: Vec3, delta: f32) {
+        let direction = direction.clamp_length_max(1.0);
+        let forward = Quat::from_rotation_y(self.yaw) * Vec3::NEG_Z;
+        let right = Quat::from_rotation_y(self.yaw) * Vec3::X;
+        self.position += (right * direction.x + Vec3::Y * direction.y + forward * direction.z)
+            * self.speed
+            * delta;
+    }
+
+    /// Turns with the mouse motion and moves with the keys held this frame.
+    pub fn update(&mut self, input_state: &InputState, delta: f32) {
+        self.turn(input_state.mouse_motion());
+
+        let axis = |positive, negative| match (
+            input_state.check_pressed(positive),
+            input_state.check_pressed(negative),
+        ) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        };
+        let direction = Vec3::new(
+            axis(Keyboard::D, Keyboard::A),
+            axis(Keyboard::Space, Keyboard::LShift),
+            axis(Keyboard::W, Keyboard::S),
+        );
+        self.fly(direction, delta);
+    }
+
+    /// Gets the transformation of a camera at this position and rotation.
+    pub fn affine(&self) -> Affine3A {
+        Affine3A::from_rotation_translation(look_rotation(self.yaw, self.pitch), self.position)
+    }
+
+    /// Moves a [Camera] to this position and rotation.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.affine = self.affine();
+    }
+}
+
+impl OrbitCamera {
+    /// Creates an [OrbitCamera] a distance behind a target, looking slightly down on
+    /// it.
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            yaw: 0.0,
+            pitch: 0.5,
+            distance,
+            min_distance: 0.5,
+            max_distance: 100.0,
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+        }
+    }
+
+    /// Sets the closest and furthest the camera zooms from the target.
+    pub fn with_distance_limits(mut self, min_distance: f32, max_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self.max_distance = max_distance;
+        self.distance = self.distance.clamp(min_distance, max_distance);
+        self
+    }
+
+    /// Sets the radians turned per unit of mouse motion.
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    /// Orbits around the target by an amount of mouse motion, with y pointing down.
+    pub fn orbit(&mut self, motion: Vec2) {
+        self.yaw -= motion.x * self.sensitivity;
+        self.pitch = (self.pitch + motion.y * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Zooms by a number of lines scrolled, where positive moves toward the target.
+    pub fn zoom(&mut self, lines: f32) {
+        self.distance = (self.distance * (1.0 - self.zoom_speed).powf(lines))
+            .clamp(self.min_distance, self.max_distance);
+    }
+
+    /// Orbits with the mouse motion while the right mouse button is held, and zooms
+    /// with the wheel scrolled this frame.
+    pub fn update(&mut self, input_state: &InputState) {
+        if input_state.check_pressed(Mouse::Right) {
+            self.orbit(input_state.mouse_motion());
+        }
+        let lines: f32 = input_state
+            .events()
+            .iter()
+            .map(|event| match event.kind {
+                InputEventKind::Wheel(ScrollDelta::Lines(lines)) => lines.y,
+                InputEventKind::Wheel(ScrollDelta::Pixels(pixels)) => pixels.y / PIXELS_PER_LINE,
+                _ => 0.0,
+            })
+            .sum();
+        self.zoom(lines);
+    }
+
+    /// Gets the position of the camera.
+    pub fn position(&self) -> Vec3 {
+        self.target + look_rotation(self.yaw, -self.pitch) * Vec3::Z * self.distance
+    }
+
+    /// Gets the transformation of a camera looking at the target from the orbit.
+    pub fn affine(&self) -> Affine3A {
+        Affine3A::from_rotation_translation(look_rotation(self.yaw, -self.pitch), self.position())
+    }
+
+    /// Moves a [Camera] onto the orbit, looking at the target.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.affine = self.affine();
+    }
+}
+
+impl FollowCamera2d {
+    /// Creates a [FollowCamera2d] centered on a position.
+    pub fn new(position: Vec2) -> Self {
+        Self {
+            position,
+            smoothing: 8.0,
+            deadzone: Vec2::ZERO,
+            bounds: None,
+        }
+    }
+
+    /// Sets how quickly the camera catches up, see [FollowCamera2d::smoothing].
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Sets the half size of the region the target moves in without being followed.
+    pub fn with_deadzone(mut self, deadzone: Vec2) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    /// Sets the corners of the world the view stays within.
+    pub fn with_bounds(mut self, min: Vec2, max: Vec2) -> Self {
+        self.bounds = Some((min, max));
+        self
+    }
+
+    /// Moves toward a target over `delta` seconds, keeping a view of `view_size` world
+    /// units within the bounds.
+    pub fn update(&mut self, target: Vec2, view_size: Vec2, delta: f32) {
+        // Only follow the part of the offset that's outside the deadzone.
+        let offset = target - self.position;
+        let outside = offset - offset.clamp(-self.deadzone, self.deadzone);
+        self.position += outside * smoothing_factor(self.smoothing, delta);
+        self.position = self.clamped(self.position, view_size);
+    }
+
+    /// Jumps straight to a target, such as when a level loads.
+    pub fn snap_to(&mut self, target: Vec2, view_size: Vec2) {
+        self.position = self.clamped(target, view_size);
+    }
+
+    /// Clamps the center of a view so it stays within the bounds, centering it on
+    /// them along axes they're smaller than the view on.
+    fn clamped(&self, position: Vec2, view_size: Vec2) -> Vec2 {
+        let Some((min, max)) = self.bounds else {
+            return position;
+        };
+        let half = view_size * 0.5;
+        let (low, high) = (min + half, max - half);
+        let center = (min + max) * 0.5;
+        Vec2::new(
+            match low.x <= high.x {
+                true => position.x.clamp(low.x, high.x),
+                false => center.x,
+            },
+            match low.y <= high.y {
+                true => position.y.clamp(low.y, high.y),
+                false => center.y,
+            },
+        )
+    }
+
+    /// Moves a [Camera] to center on this position, keeping its depth and rotation.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.affine.translation.x = self.position.x;
+        camera.affine.translation.y = self.position.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fly_camera_moves_where_it_faces() {
+        let mut camera = FlyCamera::new(Vec3::ZERO).with_speed(2.0);
+        camera.fly(Vec3::Z, 1.0);
+        assert!(camera.position.abs_diff_eq(Vec3::new(0.0, 0.0, -2.0), 1e-5));
+
+        // Turned a quarter to the left, forward is -x.
+        camera.yaw = std::f32::consts::FRAC_PI_2;
+        camera.position = Vec3::ZERO;
+        camera.fly(Vec3::Z, 1.0);
+        assert!(camera.position.abs_diff_eq(Vec3::new(-2.0, 0.0, 0.0), 1e-5));
+
+        camera.turn(Vec2::new(0.0, -1.0e6));
+        assert_eq!(camera.pitch, MAX_PITCH);
+    }
+
+    #[test]
+    fn test_orbit_camera_looks_at_target() {
+        let mut camera = OrbitCamera::new(Vec3::new(1.0, 2.0, 3.0), 10.0);
+        let affine = camera.affine();
+        let forward = affine.transform_vector3(Vec3::NEG_Z);
+        let to_target = (camera.target - camera.position()).normalize();
+        assert!(forward.abs_diff_eq(to_target, 1e-5));
+        assert!(camera.position().y > camera.target.y);
+        assert!((camera.position().distance(camera.target) - 10.0).abs() < 1e-4);
+
+        camera.zoom(100.0);
+        assert_eq!(camera.distance, camera.min_distance);
+    }
+
+    #[test]
+    fn test_follow_camera_deadzone_and_bounds() {
+        let mut camera = FollowCamera2d::new(Vec2::ZERO)
+            .with_deadzone(Vec2::splat(2.0))
+            .with_bounds(Vec2::ZERO, Vec2::new(100.0, 10.0));
+        let view = Vec2::new(16.0, 20.0);
+
+        // Within the deadzone, only the bounds move it.
+        camera.position = Vec2::new(50.0, 5.0);
+        camera.update(Vec2::new(51.0, 5.0), view, 1.0);
+        assert_eq!(camera.position, Vec2::new(50.0, 5.0));
+
+        // Follows the target out of the deadzone, but not past the edge of the world,
+        // and centers along y where the world is smaller than the view.
+        camera.update(Vec2::new(200.0, 5.0), view, 10.0);
+        assert_eq!(camera.position, Vec2::new(92.0, 5.0));
+    }
+}
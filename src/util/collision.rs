@@ -0,0 +1,348 @@
+use glam::{IVec2, Vec2};
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    /// Corner with the smallest coordinates.
+    pub min: Vec2,
+    /// Corner with the largest coordinates.
+    pub max: Vec2,
+}
+
+/// Axis aligned bounding box on an integer grid, such as for pixels or tiles.
+///
+/// Unlike [Aabb], `max` is exclusive, so a box from `(0, 0)` to `(2, 2)` covers four cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IAabb {
+    /// Smallest cell in the box.
+    pub min: IVec2,
+    /// Cell just past the largest cell in the box.
+    pub max: IVec2,
+}
+
+/// Ray starting at a point and heading in a direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    /// Where the ray starts.
+    pub origin: Vec2,
+    /// Direction of the ray. It doesn't need to be normalized, in which case distances
+    /// along the ray are in multiples of its length.
+    pub direction: Vec2,
+}
+
+/// Where something hit an [Aabb].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    /// How far along the ray or movement the hit happened, from 0 at the start.
+    /// For [Aabb::sweep] this is the time of impact, from 0 to 1.
+    pub time: f32,
+    /// Normal of the face that was hit, pointing away from the box.
+    pub normal: Vec2,
+}
+
+impl Aabb {
+    /// Creates a new [Aabb] from its corners.
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    /// Creates a new [Aabb] from its center and size.
+    pub fn from_center_size(center: Vec2, size: Vec2) -> Self {
+        Self::new(center - size * 0.5, center + size * 0.5)
+    }
+
+    /// Gets the center of the box.
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Gets the width and height of the box.
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    /// Moves the box by an offset.
+    pub fn translated(&self, offset: Vec2) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Grows the box by `amount` on every side.
+    pub fn expanded(&self, amount: Vec2) -> Self {
+        Self::new(self.min - amount, self.max + amount)
+    }
+
+    /// Gets the smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Checks if two boxes overlap. Boxes that only touch along an edge don't overlap.
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
+    /// Checks if a point is inside the box.
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.min.cmple(point).all() && point.cmple(self.max).all()
+    }
+
+    /// Gets the smallest offset that moves this box out of `other`, or `None` if they
+    /// don't overlap.
+    pub fn penetration(&self, other: &Aabb) -> Option<Vec2> {
+        if !self.overlaps(other) {
+            return None;
+        }
+
+        // Push out along whichever axis needs the least movement.
+        let push_left = other.min.x - self.max.x;
+        let push_right = other.max.x - self.min.x;
+        let push_down = other.min.y - self.max.y;
+        let push_up = other.max.y - self.min.y;
+
+        let x = if -push_left < push_right {
+            push_left
+        } else {
+            push_right
+        };
+        let y = if -push_down < push_up {
+            push_down
+        } else {
+            push_up
+        };
+
+        Some(if x.abs() < y.abs() {
+            Vec2::new(x, 0.0)
+        } else {
+            Vec2::new(0.0, y)
+        })
+    }
+
+    /// Moves this box out of every box in `others` it overlaps, one at a time, and returns
+    /// the total offset applied.
+    pub fn resolve_overlaps<'a>(&mut self, others: impl IntoIterator<Item = &'a Aabb>) -> Vec2 {
+        let mut total = Vec2::ZERO;
+        for other in others {
+            if let Some(offset) = self.penetration(other) {
+                *self = self.translated(offset);
+                total += offset;
+            }
+        }
+        total
+    }
+
+    /// Finds where a ray first enters the box, if it does. Rays starting inside the box
+    /// hit at time 0 with a zero normal, while rays starting on its edge hit at time 0
+    /// with the edge's normal if they head into the box.
+    pub fn ray_intersection(&self, ray: &Ray) -> Option<Hit> {
+        if self.min.cmplt(ray.origin).all() && ray.origin.cmplt(self.max).all() {
+            return Some(Hit {
+                time: 0.0,
+                normal: Vec2::ZERO,
+            });
+        }
+
+        let inverse = ray.direction.recip();
+        let to_min = (self.min - ray.origin) * inverse;
+        let to_max = (self.max - ray.origin) * inverse;
+        let near = to_min.min(to_max);
+        let far = to_min.max(to_max);
+
+        // Axes the ray doesn't move along produce NaN when it starts on the box's edge.
+        let near = Vec2::select(near.is_nan_mask(), Vec2::NEG_INFINITY, near);
+        let far = Vec2::select(far.is_nan_mask(), Vec2::INFINITY, far);
+
+        let enter = near.max_element();
+        let exit = far.min_element();
+        // Rays starting on the edge and heading away or along it enter before time 0.
+        if enter > exit || enter < 0.0 {
+            return None;
+        }
+
+        let normal = if near.x > near.y {
+            Vec2::new(-ray.direction.x.signum(), 0.0)
+        } else {
+            Vec2::new(0.0, -ray.direction.y.signum())
+        };
+        Some(Hit {
+            time: enter,
+            normal,
+        })
+    }
+
+    /// Finds when this box, moving by `movement`, first touches `other`.
+    ///
+    /// The returned time is between 0 and 1, so the box can be moved by
+    /// `movement * hit.time` to end up against `other`, then slide along the normal.
+    /// Boxes that already overlap don't hit, see [Aabb::penetration] for those.
+    pub fn sweep(&self, movement: Vec2, other: &Aabb) -> Option<Hit> {
+        // Sweeping a box is the same as casting a ray from its center against the
+        // other box grown by its size.
+        let expanded = other.expanded(self.size() * 0.5);
+        let ray = Ray {
+            origin: self.center(),
+            direction: movement,
+        };
+
+        if self.overlaps(other) {
+            return None;
+        }
+        let hit = expanded.ray_intersection(&ray)?;
+        (hit.time <= 1.0 && movement.dot(hit.normal) < 0.0).then_some(hit)
+    }
+}
+
+impl IAabb {
+    /// Creates a new [IAabb] from its smallest cell and the cell past its largest.
+    pub fn new(min: IVec2, max: IVec2) -> Self {
+        Self {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    /// Creates a new [IAabb] from its smallest cell and size.
+    pub fn from_position_size(position: IVec2, size: IVec2) -> Self {
+        Self::new(position, position + size)
+    }
+
+    /// Gets the width and height of the box.
+    pub fn size(&self) -> IVec2 {
+        self.max - self.min
+    }
+
+    /// Moves the box by an offset.
+    pub fn translated(&self, offset: IVec2) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Checks if two boxes share any cells.
+    pub fn overlaps(&self, other: &IAabb) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
+    /// Checks if a cell is inside the box.
+    pub fn contains(&self, cell: IVec2) -> bool {
+        self.min.cmple(cell).all() && cell.cmplt(self.max).all()
+    }
+
+    /// Gets the cells both boxes share, if any.
+    pub fn intersection(&self, other: &IAabb) -> Option<Self> {
+        let intersection = Self {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        };
+        intersection
+            .min
+            .cmplt(intersection.max)
+            .all()
+            .then_some(intersection)
+    }
+
+    /// Iterates over every cell in the box, row by row.
+    pub fn cells(&self) -> impl Iterator<Item = IVec2> {
+        let (min, max) = (self.min, self.max);
+        (min.y..max.y).flat_map(move |y| (min.x..max.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    /// Converts to an [Aabb] covering the same area.
+    pub fn as_aabb(&self) -> Aabb {
+        Aabb::new(self.min.as_vec2(), self.max.as_vec2())
+    }
+}
+
+impl From<IAabb> for Aabb {
+    fn from(value: IAabb) -> Self {
+        value.as_aabb()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlaps() {
+        let a = Aabb::new(Vec2::ZERO, Vec2::ONE);
+        assert!(a.overlaps(&Aabb::from_center_size(Vec2::ONE, Vec2::ONE)));
+        assert!(!a.overlaps(&Aabb::new(Vec2::new(1.0, 0.0), Vec2::new(2.0, 1.0))));
+    }
+
+    #[test]
+    fn test_penetration() {
+        let ground = Aabb::new(Vec2::new(-10.0, -1.0), Vec2::new(10.0, 0.0));
+        let mut player = Aabb::from_center_size(Vec2::new(0.0, 0.4), Vec2::ONE);
+
+        let offset = player.penetration(&ground).unwrap();
+        assert!(offset.abs_diff_eq(Vec2::new(0.0, 0.1), 1e-6));
+        player.resolve_overlaps([&ground]);
+        assert!(!player.overlaps(&ground));
+    }
+
+    #[test]
+    fn test_ray_intersection() {
+        let aabb = Aabb::new(Vec2::new(2.0, -1.0), Vec2::new(4.0, 1.0));
+        let hit = aabb
+            .ray_intersection(&Ray {
+                origin: Vec2::ZERO,
+                direction: Vec2::X,
+            })
+            .unwrap();
+        assert_eq!(hit.time, 2.0);
+        assert_eq!(hit.normal, Vec2::NEG_X);
+
+        let miss = aabb.ray_intersection(&Ray {
+            origin: Vec2::ZERO,
+            direction: Vec2::NEG_X,
+        });
+        assert_eq!(miss, None);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let wall = Aabb::new(Vec2::new(3.0, -5.0), Vec2::new(4.0, 5.0));
+        let player = Aabb::from_center_size(Vec2::ZERO, Vec2::splat(2.0));
+
+        let hit = player.sweep(Vec2::new(4.0, 1.0), &wall).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, Vec2::NEG_X);
+
+        assert_eq!(player.sweep(Vec2::new(1.0, 0.0), &wall), None);
+        assert_eq!(player.sweep(Vec2::new(-4.0, 0.0), &wall), None);
+
+        let touching = player.translated(Vec2::new(2.0, 0.0));
+        assert_eq!(
+            touching.sweep(Vec2::new(1.0, 0.0), &wall),
+            Some(Hit {
+                time: 0.0,
+                normal: Vec2::NEG_X,
+            })
+        );
+        assert_eq!(touching.sweep(Vec2::new(-1.0, 0.0), &wall), None);
+        assert_eq!(touching.sweep(Vec2::new(0.0, 1.0), &wall), None);
+    }
+
+    #[test]
+    fn test_integer_aabb() {
+        let a = IAabb::from_position_size(IVec2::ZERO, IVec2::new(2, 2));
+        let b = IAabb::from_position_size(IVec2::new(1, 1), IVec2::new(2, 2));
+
+        assert_eq!(a.cells().count(), 4);
+        assert_eq!(
+            a.intersection(&b),
+            Some(IAabb::new(IVec2::ONE, IVec2::new(2, 2)))
+        );
+        assert!(!a.overlaps(&a.translated(IVec2::new(2, 0))));
+    }
+}
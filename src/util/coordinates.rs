@@ -0,0 +1,138 @@
+use glam::{vec2, Mat4, Vec2, Vec3};
+
+/// Up in world space.
+///
+/// The world is right handed, so with [WORLD_RIGHT] to the right and [WORLD_UP] up,
+/// positive z points towards the viewer. Tilemaps and 2d scenes lie on the xy plane.
+pub const WORLD_UP: Vec3 = Vec3::Y;
+
+/// Right in world space, see [WORLD_UP].
+pub const WORLD_RIGHT: Vec3 = Vec3::X;
+
+/// Direction a [super::camera::Camera] without rotation looks in, see [WORLD_UP].
+pub const WORLD_FORWARD: Vec3 = Vec3::NEG_Z;
+
+/// Position on the screen or a render target in pixels, from the top left with y
+/// pointing down, as windows report cursor and touch positions.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ScreenPosition(pub Vec2);
+
+/// Position in normalized device coordinates, from -1 at the bottom left to 1 at the
+/// top right with y pointing up.
+///
+/// This is the opposite vertical direction to [ScreenPosition] and [Uv], which is the
+/// flip to watch for when going between them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NdcPosition(pub Vec2);
+
+/// Texture coordinate, from 0 at the top left to 1 at the bottom right with v pointing
+/// down, as used by uv windows and meshes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Uv(pub Vec2);
+
+impl ScreenPosition {
+    /// Converts to normalized device coordinates on a screen of the given size.
+    pub fn to_ndc(self, screen_size: Vec2) -> NdcPosition {
+        let fraction = self.0 / screen_size;
+        NdcPosition(vec2(fraction.x * 2.0 - 1.0, 1.0 - fraction.y * 2.0))
+    }
+
+    /// Gets the position in pixels from the bottom left instead, as virtual controls
+    /// are laid out.
+    pub fn to_bottom_left(self, screen_size: Vec2) -> Vec2 {
+        vec2(self.0.x, screen_size.y - self.0.y)
+    }
+}
+
+impl NdcPosition {
+    /// Converts to pixels on a screen of the given size.
+    pub fn to_screen(self, screen_size: Vec2) -> ScreenPosition {
+        ScreenPosition(vec2(self.0.x + 1.0, 1.0 - self.0.y) * 0.5 * screen_size)
+    }
+
+    /// Gets the uv of a render target at this position.
+    pub fn to_uv(self) -> Uv {
+        Uv(vec2(self.0.x + 1.0, 1.0 - self.0.y) * 0.5)
+    }
+}
+
+impl Uv {
+    /// Gets the uv of a position in pixels from the top left of a texture of the given
+    /// size.
+    pub fn from_pixel(pixel: Vec2, texture_size: Vec2) -> Self {
+        Self(pixel / texture_size)
+    }
+
+    /// Gets the position in normalized device coordinates of a render target at this uv.
+    pub fn to_ndc(self) -> NdcPosition {
+        NdcPosition(vec2(self.0.x * 2.0 - 1.0, 1.0 - self.0.y * 2.0))
+    }
+
+    /// Gets the position on a unit quad such as
+    /// [crate::graphics::default_meshes::QUAD_MESH_DATA] showing this uv, which is
+    /// centered on the origin with y pointing up.
+    pub fn to_quad(self) -> Vec2 {
+        vec2(self.0.x - 0.5, 0.5 - self.0.y)
+    }
+}
+
+/// Projects a world position onto a screen of the given size through a view
+/// projection matrix, or `None` if it's behind the camera.
+pub fn world_to_screen(
+    view_projection: Mat4,
+    position: Vec3,
+    screen_size: Vec2,
+) -> Option<ScreenPosition> {
+    let clip = view_projection * position.extend(1.0);
+    (clip.w > 0.0).then(|| NdcPosition(clip.truncate().truncate() / clip.w).to_screen(screen_size))
+}
+
+/// Gets the ray through a screen position into the world, as its origin on the near
+/// plane and its normalized direction, such as for picking what's under the cursor.
+pub fn screen_to_world_ray(
+    view_projection: Mat4,
+    position: ScreenPosition,
+    screen_size: Vec2,
+) -> (Vec3, Vec3) {
+    let ndc = position.to_ndc(screen_size).0;
+    let inverse = view_projection.inverse();
+    let near = inverse.project_point3(ndc.extend(0.0));
+    let far = inverse.project_point3(ndc.extend(1.0));
+    (near, (far - near).normalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Vec2 = vec2(800.0, 600.0);
+
+    #[test]
+    fn test_screen_y_points_down() {
+        let top_left = ScreenPosition(Vec2::ZERO);
+        assert_eq!(top_left.to_ndc(SCREEN), NdcPosition(vec2(-1.0, 1.0)));
+        assert_eq!(top_left.to_ndc(SCREEN).to_uv(), Uv(Vec2::ZERO));
+        assert_eq!(Uv(Vec2::ZERO).to_quad(), vec2(-0.5, 0.5));
+        assert_eq!(top_left.to_bottom_left(SCREEN), vec2(0.0, 600.0));
+
+        let position = ScreenPosition(vec2(200.0, 450.0));
+        assert_eq!(position.to_ndc(SCREEN).to_screen(SCREEN), position);
+        assert_eq!(Uv(vec2(0.25, 0.75)).to_ndc().to_uv(), Uv(vec2(0.25, 0.75)));
+    }
+
+    #[test]
+    fn test_world_to_screen_and_back() {
+        let view_projection = Mat4::perspective_rh(1.0, 800.0 / 600.0, 0.1, 100.0)
+            * Mat4::look_at_rh(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO, WORLD_UP);
+
+        // Up in the world is up on the screen, which is towards smaller y.
+        let above = world_to_screen(view_projection, WORLD_UP, SCREEN).unwrap();
+        assert!((above.0.x - 400.0).abs() < 1e-3);
+        assert!(above.0.y < 300.0);
+        assert!(world_to_screen(view_projection, Vec3::new(0.0, 0.0, 20.0), SCREEN).is_none());
+
+        let (origin, direction) = screen_to_world_ray(view_projection, above, SCREEN);
+        let hit = origin + direction * ((0.0 - origin.z) / direction.z);
+        assert!(hit.abs_diff_eq(WORLD_UP, 1e-3));
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+
+/// Loads the bytes of an asset, such as a texture for
+/// [crate::graphics::RenderContext::load_texture].
+///
+/// Natively `path` is a file path. On the web it's a url fetched relative to the page,
+/// since there is no file system to read from.
+pub async fn fetch_bytes(path: &str) -> Result<Vec<u8>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return read_file(path);
+    #[cfg(target_arch = "wasm32")]
+    return fetch_url(path).await;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    use anyhow::Context;
+
+    std::fs::read(path).with_context(|| format!("failed to read {path}"))
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch_url(path: &str) -> Result<Vec<u8>> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let js_error =
+        |error: wasm_bindgen::JsValue| anyhow::anyhow!("failed to fetch {path}: {error:?}");
+
+    let window = web_sys::window().ok_or_else(|| anyhow::anyhow!("no browser window"))?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(path))
+        .await
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(js_error)?;
+    anyhow::ensure!(
+        response.ok(),
+        "failed to fetch {path}: {} {}",
+        response.status(),
+        response.status_text()
+    );
+
+    let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+        .await
+        .map_err(js_error)?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_file() {
+        let bytes = pollster::block_on(fetch_bytes("Cargo.toml")).unwrap();
+        assert!(bytes.starts_with(b"[package]"));
+        assert!(pollster::block_on(fetch_bytes("missing.png")).is_err());
+    }
+}
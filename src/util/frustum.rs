@@ -0,0 +1,124 @@
+use glam::{Mat4, Vec3, Vec4};
+
+/// Sphere enclosing a set of points, such as the vertices of a mesh.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoundingSphere {
+    /// Center of the sphere.
+    pub center: Vec3,
+    /// Distance from the center to the furthest point.
+    pub radius: f32,
+}
+
+/// Planes of a view frustum as `(normal, distance)`, with normals pointing inward, so
+/// points inside are in front of every plane.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes.
+    pub planes: [Vec4; 6],
+}
+
+impl BoundingSphere {
+    /// Gets a sphere around points, centered on their bounding box.
+    pub fn from_points(points: impl Iterator<Item = Vec3> + Clone) -> Self {
+        let (min, max) = points.clone().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), point| (min.min(point), max.max(point)),
+        );
+        if min.x > max.x {
+            return Self::default();
+        }
+        let center = (min + max) * 0.5;
+        let radius = points
+            .map(|point| point.distance_squared(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+        Self { center, radius }
+    }
+
+    /// Gets the sphere moved by a transform, growing it by the transform's largest
+    /// scale so it still encloses the points.
+    pub fn transformed(&self, transform: Mat4) -> Self {
+        let scale = transform
+            .x_axis
+            .truncate()
+            .length()
+            .max(transform.y_axis.truncate().length())
+            .max(transform.z_axis.truncate().length());
+        Self {
+            center: transform.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+impl Frustum {
+    /// Extracts the planes of the frustum a view projection matrix sees, with depth
+    /// from 0 to 1 as wgpu uses.
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let rows = [
+            view_projection.row(0),
+            view_projection.row(1),
+            view_projection.row(2),
+            view_projection.row(3),
+        ];
+        let planes = [
+            rows[3] + rows[0],
+            rows[3] - rows[0],
+            rows[3] + rows[1],
+            rows[3] - rows[1],
+            rows[2],
+            rows[3] - rows[2],
+        ]
+        .map(|plane| plane / plane.truncate().length().max(f32::EPSILON));
+        Self { planes }
+    }
+
+    /// Checks whether any part of a sphere could be inside the frustum.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounding_sphere() {
+        let points = [Vec3::new(-1.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0), Vec3::Y];
+        let sphere = BoundingSphere::from_points(points.into_iter());
+        assert_eq!(sphere.center, Vec3::new(1.0, 0.5, 0.0));
+        assert!(points
+            .iter()
+            .all(|point| point.distance(sphere.center) <= sphere.radius + 1e-5));
+
+        let moved = sphere.transformed(Mat4::from_scale_rotation_translation(
+            Vec3::new(1.0, 2.0, 1.0),
+            glam::Quat::IDENTITY,
+            Vec3::Z,
+        ));
+        assert_eq!(moved.center, Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(moved.radius, sphere.radius * 2.0);
+        assert_eq!(
+            BoundingSphere::from_points(std::iter::empty()),
+            BoundingSphere::default()
+        );
+    }
+
+    #[test]
+    fn test_frustum_intersects_sphere() {
+        // Looking down -z from the origin.
+        let frustum = Frustum::from_view_projection(Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0));
+        let sphere = |center: Vec3, radius: f32| BoundingSphere { center, radius };
+
+        assert!(frustum.intersects_sphere(&sphere(Vec3::new(0.0, 0.0, -10.0), 1.0)));
+        // Behind the camera, and past the far plane.
+        assert!(!frustum.intersects_sphere(&sphere(Vec3::new(0.0, 0.0, 10.0), 1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(Vec3::new(0.0, 0.0, -200.0), 1.0)));
+        // Off to the side, unless it's large enough to reach into view.
+        assert!(!frustum.intersects_sphere(&sphere(Vec3::new(20.0, 0.0, -10.0), 1.0)));
+        assert!(frustum.intersects_sphere(&sphere(Vec3::new(20.0, 0.0, -10.0), 20.0)));
+    }
+}
@@ -0,0 +1,512 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use glam::{Mat4, Quat, UVec2, Vec2, Vec3, Vec4};
+
+use crate::graphics::{
+    texture::{self, SamplerSettings, TextureLoadOptions},
+    AnimationChannel, AnimationClip, AnimationProperty, Interpolation, Joint, JointTransform,
+    ModelData, ModelImage, ModelMaterial, Skeleton, SkinWeights, SubmeshData, SubmeshSkin, Vertex,
+};
+
+/// Version of the cache's format, which is part of every key so entries written by
+/// older versions are never read.
+const CACHE_VERSION: u32 = 3;
+
+/// Extension of cache entries.
+const ENTRY_EXTENSION: &str = "cache";
+
+/// Caches the results of expensive imports on disk, such as decoded and mipmapped
+/// textures or parsed glTF models, so start up stays fast as a project grows.
+///
+/// Entries are keyed by a hash of the imported file's contents, so editing a file
+/// imports it again. Nothing else invalidates entries, so the cache can be removed
+/// at any time with [ImportCache::clear].
+#[derive(Clone, Debug)]
+pub struct ImportCache {
+    directory: PathBuf,
+}
+
+impl ImportCache {
+    /// Creates a new [ImportCache] storing entries in `directory`, which is created if
+    /// it doesn't exist.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)
+            .with_context(|| format!("failed to create {}", directory.display()))?;
+        Ok(Self { directory })
+    }
+
+    /// Gets the directory entries are stored in.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Removes every entry.
+    pub fn clear(&self) -> Result<()> {
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some(ENTRY_EXTENSION) {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Imports a model from the contents of a glTF or glb file like
+    /// [ModelData::from_gltf_bytes], reusing the result of an earlier import of the same
+    /// contents.
+    pub fn import_gltf(&self, bytes: &[u8]) -> Result<ModelData> {
+        self.get_or_import(
+            "gltf",
+            content_hash(&[bytes]),
+            || ModelData::from_gltf_bytes(bytes),
+            write_model,
+            read_model,
+        )
+    }
+
+    /// Decodes an image and prepares its mip levels as `sampler` and `load_options` ask
+    /// for, reusing the result of an earlier import of the same contents and settings.
+    pub(crate) fn import_texture(
+        &self,
+        bytes: &[u8],
+        sampler: SamplerSettings,
+        load_options: TextureLoadOptions,
+    ) -> Result<(UVec2, Vec<Vec<u8>>)> {
+        let settings = [sampler.mipmaps as u8, load_options.premultiply_alpha as u8];
+        self.get_or_import(
+            "texture",
+            content_hash(&[&settings, bytes]),
+            || {
+                let image = image::load_from_memory(bytes)?.to_rgba8();
+                let size = UVec2::new(image.width(), image.height());
+                Ok((
+                    size,
+                    texture::prepare_mip_levels(size, &image, sampler, load_options),
+                ))
+            },
+            write_texture,
+            read_texture,
+        )
+    }
+
+    /// Reads the entry for `hash`, or imports and writes it if it's missing or
+    /// unreadable.
+    ///
+    /// The cache only speeds imports up, so failing to write an entry isn't an error.
+    fn get_or_import<T>(
+        &self,
+        kind: &str,
+        hash: u64,
+        import: impl FnOnce() -> Result<T>,
+        write: fn(&mut Writer, &T),
+        read: fn(&mut Reader) -> Option<T>,
+    ) -> Result<T> {
+        let path = self
+            .directory
+            .join(format!("{kind}-{hash:016x}.{ENTRY_EXTENSION}"));
+        if let Ok(bytes) = std::fs::read(&path) {
+            let mut reader = Reader(&bytes);
+            if let Some(value) = read(&mut reader).filter(|_| reader.0.is_empty()) {
+                return Ok(value);
+            }
+        }
+
+        let value = import()?;
+        let mut writer = Writer(Vec::new());
+        write(&mut writer, &value);
+        // Write then rename, so an interrupted write never leaves a partial entry.
+        let partial_path = path.with_extension("partial");
+        if std::fs::write(&partial_path, &writer.0).is_ok() {
+            let _ = std::fs::rename(&partial_path, &path);
+        }
+        Ok(value)
+    }
+}
+
+/// Hashes the parts of an entry's key with 64 bit FNV-1a, which unlike the standard
+/// library's hasher is stable between runs and compiler versions.
+fn content_hash(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut add = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    add(&CACHE_VERSION.to_le_bytes());
+    for part in parts {
+        // Include lengths so moving bytes between parts changes the hash.
+        add(&(part.len() as u64).to_le_bytes());
+        add(part);
+    }
+    hash
+}
+
+/// Writes values of an entry in little endian.
+struct Writer(Vec<u8>);
+
+/// Reads values written by a [Writer], returning `None` once it runs out of bytes.
+struct Reader<'a>(&'a [u8]);
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        for value in values {
+            self.0.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn optional<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        self.0.push(value.is_some() as u8);
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.0.get(..len)?;
+        self.0 = &self.0[len..];
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Option<[f32; N]> {
+        let mut values = [0.0; N];
+        for value in values.iter_mut() {
+            *value = f32::from_le_bytes(self.take(4)?.try_into().ok()?);
+        }
+        Some(values)
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn optional<T>(&mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        match self.take(1)?[0] {
+            0 => Some(None),
+            _ => read(self).map(Some),
+        }
+    }
+
+    /// Reads a count followed by that many values.
+    fn list<T>(&mut self, mut read: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let len = self.u32()? as usize;
+        // Don't trust the count for the allocation, since the entry could be corrupt.
+        let mut values = Vec::with_capacity(len.min(self.0.len()));
+        for _ in 0..len {
+            values.push(read(self)?);
+        }
+        Some(values)
+    }
+}
+
+fn write_texture(writer: &mut Writer, (size, mip_levels): &(UVec2, Vec<Vec<u8>>)) {
+    writer.u32(size.x);
+    writer.u32(size.y);
+    writer.u32(mip_levels.len() as u32);
+    for level in mip_levels {
+        writer.bytes(level);
+    }
+}
+
+fn read_texture(reader: &mut Reader) -> Option<(UVec2, Vec<Vec<u8>>)> {
+    let size = UVec2::new(reader.u32()?, reader.u32()?);
+    let mip_levels = reader.list(|reader| reader.bytes().map(<[u8]>::to_vec))?;
+    Some((size, mip_levels))
+}
+
+fn write_model(writer: &mut Writer, model: &ModelData) {
+    let name = |writer: &mut Writer, name: &Option<String>| {
+        writer.optional(name.as_deref(), |writer, name| {
+            writer.bytes(name.as_bytes())
+        })
+    };
+    let index = |writer: &mut Writer, index: Option<usize>| {
+        writer.optional(index, |writer, index| writer.u32(index as u32))
+    };
+
+    writer.u32(model.submeshes.len() as u32);
+    for submesh in &model.submeshes {
+        name(writer, &submesh.name);
+        writer.u32(submesh.vertices.len() as u32);
+        for vertex in &submesh.vertices {
+            writer.f32s(&vertex.position.to_array());
+            writer.f32s(&vertex.normal.to_array());
+            writer.f32s(&vertex.texture_coordinates.to_array());
+        }
+        writer.u32(submesh.indices.len() as u32);
+        for &index in &submesh.indices {
+            writer.u32(index);
+        }
+        writer.f32s(&submesh.transform.to_cols_array());
+        index(writer, submesh.material);
+        writer.optional(submesh.skin.as_ref(), |writer, skin| {
+            writer.u32(skin.skeleton as u32);
+            writer.u32(skin.weights.len() as u32);
+            for weights in &skin.weights {
+                for joint in weights.joints {
+                    writer.u32(joint);
+                }
+                writer.f32s(&weights.weights);
+            }
+        });
+    }
+
+    writer.u32(model.materials.len() as u32);
+    for material in &model.materials {
+        name(writer, &material.name);
+        writer.f32s(&material.base_color.to_array());
+        index(writer, material.texture);
+        writer.f32s(&[material.metallic, material.roughness]);
+        index(writer, material.metallic_roughness_texture);
+        index(writer, material.normal_texture);
+        writer.f32s(&[material.normal_scale]);
+        index(writer, material.occlusion_texture);
+        writer.f32s(&[material.occlusion_strength]);
+        writer.f32s(&material.emissive.to_array());
+        index(writer, material.emissive_texture);
+    }
+
+    writer.u32(model.images.len() as u32);
+    for image in &model.images {
+        writer.u32(image.size.x);
+        writer.u32(image.size.y);
+        writer.bytes(&image.rgba);
+    }
+
+    writer.u32(model.skeletons.len() as u32);
+    for skeleton in &model.skeletons {
+        writer.f32s(&skeleton.root.to_cols_array());
+        writer.u32(skeleton.joints.len() as u32);
+        for joint in &skeleton.joints {
+            name(writer, &joint.name);
+            index(writer, joint.parent);
+            writer.f32s(&joint.inverse_bind.to_cols_array());
+            writer.f32s(&joint.rest.translation.to_array());
+            writer.f32s(&joint.rest.rotation.to_array());
+            writer.f32s(&joint.rest.scale.to_array());
+        }
+    }
+
+    writer.u32(model.animations.len() as u32);
+    for animation in &model.animations {
+        name(writer, &animation.name);
+        writer.u32(animation.skeleton as u32);
+        writer.f32s(&[animation.duration]);
+        writer.u32(animation.channels.len() as u32);
+        for channel in &animation.channels {
+            writer.u32(channel.joint as u32);
+            writer.u32(channel.property as u32);
+            writer.u32(channel.interpolation as u32);
+            writer.u32(channel.times.len() as u32);
+            writer.f32s(&channel.times);
+            writer.u32(channel.values.len() as u32);
+            for value in &channel.values {
+                writer.f32s(&value.to_array());
+            }
+        }
+    }
+}
+
+fn read_model(reader: &mut Reader) -> Option<ModelData> {
+    let name = |reader: &mut Reader| {
+        reader.optional(|reader| String::from_utf8(reader.bytes()?.to_vec()).ok())
+    };
+    let index = |reader: &mut Reader| reader.optional(|reader| Some(reader.u32()? as usize));
+
+    let submeshes = reader.list(|reader| {
+        Some(SubmeshData {
+            name: name(reader)?,
+            vertices: reader.list(|reader| {
+                Some(Vertex {
+                    position: Vec3::from_array(reader.f32s()?),
+                    normal: Vec3::from_array(reader.f32s()?),
+                    texture_coordinates: Vec2::from_array(reader.f32s()?),
+                })
+            })?,
+            indices: reader.list(Reader::u32)?,
+            transform: Mat4::from_cols_array(&reader.f32s()?),
+            material: index(reader)?,
+            skin: reader.optional(|reader| {
+                Some(SubmeshSkin {
+                    skeleton: reader.u32()? as usize,
+                    weights: reader.list(|reader| {
+                        Some(SkinWeights {
+                            joints: [reader.u32()?, reader.u32()?, reader.u32()?, reader.u32()?],
+                            weights: reader.f32s()?,
+                        })
+                    })?,
+                })
+            })?,
+        })
+    })?;
+    let materials = reader.list(|reader| {
+        let name = name(reader)?;
+        let base_color = Vec4::from_array(reader.f32s()?);
+        let texture = index(reader)?;
+        let [metallic, roughness] = reader.f32s()?;
+        let metallic_roughness_texture = index(reader)?;
+        let normal_texture = index(reader)?;
+        let [normal_scale] = reader.f32s()?;
+        let occlusion_texture = index(reader)?;
+        let [occlusion_strength] = reader.f32s()?;
+        Some(ModelMaterial {
+            name,
+            base_color,
+            texture,
+            metallic,
+            roughness,
+            metallic_roughness_texture,
+            normal_texture,
+            normal_scale,
+            occlusion_texture,
+            occlusion_strength,
+            emissive: Vec3::from_array(reader.f32s()?),
+            emissive_texture: index(reader)?,
+        })
+    })?;
+    let images = reader.list(|reader| {
+        Some(ModelImage {
+            size: UVec2::new(reader.u32()?, reader.u32()?),
+            rgba: reader.bytes()?.to_vec(),
+        })
+    })?;
+    let skeletons = reader.list(|reader| {
+        let root = Mat4::from_cols_array(&reader.f32s()?);
+        let joints = reader.list(|reader| {
+            Some(Joint {
+                name: name(reader)?,
+                parent: index(reader)?,
+                inverse_bind: Mat4::from_cols_array(&reader.f32s()?),
+                rest: JointTransform {
+                    translation: Vec3::from_array(reader.f32s()?),
+                    rotation: Quat::from_array(reader.f32s()?),
+                    scale: Vec3::from_array(reader.f32s()?),
+                },
+            })
+        })?;
+        Some(Skeleton { joints, root })
+    })?;
+    let animations = reader.list(|reader| {
+        let name = name(reader)?;
+        let skeleton = reader.u32()? as usize;
+        let [duration] = reader.f32s()?;
+        let channels = reader.list(|reader| {
+            Some(AnimationChannel {
+                joint: reader.u32()? as usize,
+                property: match reader.u32()? {
+                    0 => AnimationProperty::Translation,
+                    1 => AnimationProperty::Rotation,
+                    2 => AnimationProperty::Scale,
+                    _ => return None,
+                },
+                interpolation: match reader.u32()? {
+                    0 => Interpolation::Step,
+                    1 => Interpolation::Linear,
+                    2 => Interpolation::CubicSpline,
+                    _ => return None,
+                },
+                times: reader.list(|reader| reader.f32s().map(|[time]| time))?,
+                values: reader.list(|reader| reader.f32s().map(Vec4::from_array))?,
+            })
+        })?;
+        Some(AnimationClip {
+            name,
+            skeleton,
+            duration,
+            channels,
+        })
+    })?;
+
+    Some(ModelData {
+        submeshes,
+        materials,
+        images,
+        skeletons,
+        animations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Creates a cache in an empty directory unique to the test.
+    fn test_cache(name: &str) -> ImportCache {
+        let directory = std::env::temp_dir().join(format!(
+            "clockwork-import-cache-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&directory);
+        ImportCache::new(directory).unwrap()
+    }
+
+    #[test]
+    fn test_model_round_trip() {
+        for bytes in [
+            &include_bytes!("../graphics/test_files/triangle.gltf")[..],
+            include_bytes!("../graphics/test_files/skinned.gltf"),
+        ] {
+            let model = ModelData::from_gltf_bytes(bytes).unwrap();
+            let mut writer = Writer(Vec::new());
+            write_model(&mut writer, &model);
+
+            let mut reader = Reader(&writer.0);
+            let read = read_model(&mut reader).unwrap();
+            assert!(reader.0.is_empty());
+            assert_eq!(format!("{read:?}"), format!("{model:?}"));
+
+            // Truncated entries are rejected rather than misread.
+            let mut reader = Reader(&writer.0[..writer.0.len() - 1]);
+            assert!(read_model(&mut reader).is_none());
+        }
+    }
+
+    #[test]
+    fn test_imports_are_reused_until_contents_change() {
+        let cache = test_cache("reuse");
+        let imports = Cell::new(0);
+        let import = |bytes: &[u8]| {
+            cache.get_or_import(
+                "test",
+                content_hash(&[bytes]),
+                || {
+                    imports.set(imports.get() + 1);
+                    Ok((UVec2::ONE, vec![bytes.to_vec()]))
+                },
+                write_texture,
+                read_texture,
+            )
+        };
+
+        assert_eq!(import(b"abcd").unwrap().1, vec![b"abcd".to_vec()]);
+        assert_eq!(import(b"abcd").unwrap().1, vec![b"abcd".to_vec()]);
+        assert_eq!(imports.get(), 1);
+        import(b"efgh").unwrap();
+        assert_eq!(imports.get(), 2);
+
+        cache.clear().unwrap();
+        import(b"abcd").unwrap();
+        assert_eq!(imports.get(), 3);
+        std::fs::remove_dir_all(cache.directory()).unwrap();
+    }
+}
@@ -1,3 +1,19 @@
+mod aseprite;
 pub mod camera;
+pub mod camera_controller;
+pub mod collision;
+pub mod coordinates;
+pub mod fetch;
+pub mod frustum;
+pub mod import_cache;
+pub mod persistence;
 pub mod repository;
+pub mod scene;
+pub mod shadow_frustum;
 pub mod sprite;
+pub mod storage;
+pub mod texture_atlas;
+pub mod tilemap;
+pub mod time;
+pub mod tween;
+pub mod ui;
@@ -0,0 +1,320 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::storage::validate_slot;
+
+/// Bytes every save written by a [SaveStore] starts with.
+const MAGIC: &[u8; 4] = b"CWSV";
+
+/// Size of the header before the contents of a save: the magic bytes, flags, and
+/// version.
+const HEADER_SIZE: usize = 9;
+
+/// Flag set when the contents of a save are compressed.
+const FLAG_COMPRESSED: u8 = 1;
+
+/// Saves game state to named slots in a directory, such as `slot1` or `settings`.
+///
+/// State is serialized with serde, tagged with [SaveStore::version] so older saves can
+/// be migrated with [SaveStore::load_with], and optionally compressed. Writes replace
+/// a slot atomically, so a crash while saving never leaves a corrupted save behind.
+///
+/// Slots are stored as `<slot>.sav` files, the same as
+/// [super::storage::LocalFileBackend], so a [super::storage::SaveSync] over the same
+/// directory syncs them to the cloud.
+#[derive(Clone, Debug)]
+pub struct SaveStore {
+    directory: PathBuf,
+    version: u32,
+    compressed: bool,
+}
+
+/// Gets the platform's directory for an application's save data, or `None` if there
+/// is no home directory, such as on the web.
+///
+/// This is `%APPDATA%\<app>` on Windows, `~/Library/Application Support/<app>` on
+/// macOS, and `$XDG_DATA_HOME/<app>` or `~/.local/share/<app>` elsewhere.
+pub fn save_directory(app_name: &str) -> Option<PathBuf> {
+    let env_dir = |name| std::env::var_os(name).filter(|dir| !dir.is_empty());
+    let base = if cfg!(target_os = "windows") {
+        PathBuf::from(env_dir("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(env_dir("HOME")?).join("Library/Application Support")
+    } else {
+        match env_dir("XDG_DATA_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env_dir("HOME")?).join(".local/share"),
+        }
+    };
+    Some(base.join(app_name))
+}
+
+impl SaveStore {
+    /// Creates a [SaveStore] saving to `directory`, which is created if it doesn't
+    /// exist.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)
+            .with_context(|| format!("failed to create {}", directory.display()))?;
+        Ok(Self {
+            directory,
+            version: 0,
+            compressed: false,
+        })
+    }
+
+    /// Creates a [SaveStore] in the platform's save directory for an application, see
+    /// [save_directory].
+    pub fn for_app(app_name: &str) -> Result<Self> {
+        match save_directory(app_name) {
+            Some(directory) => Self::new(directory),
+            None => bail!("no save directory on this platform"),
+        }
+    }
+
+    /// Sets the version saves are written with, which should be increased whenever the
+    /// saved state changes in a way older saves can't be loaded as.
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets whether saves are compressed, which shrinks large saves at the cost of
+    /// them no longer being readable JSON. Saves are read either way.
+    pub fn with_compression(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Gets the directory saves are written to.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Gets the version saves are written with.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Saves state to a slot, replacing what was in it.
+    pub fn save<T: Serialize>(&self, slot: &str, state: &T) -> Result<()> {
+        let path = self.path(slot)?;
+        let json = serde_json::to_vec(state)?;
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + json.len());
+        bytes.extend_from_slice(MAGIC);
+        match self.compressed {
+            true => {
+                bytes.push(FLAG_COMPRESSED);
+                bytes.extend_from_slice(&self.version.to_le_bytes());
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(bytes, flate2::Compression::default());
+                encoder.write_all(&json)?;
+                bytes = encoder.finish()?;
+            }
+            false => {
+                bytes.push(0);
+                bytes.extend_from_slice(&self.version.to_le_bytes());
+                bytes.extend_from_slice(&json);
+            }
+        }
+
+        write_atomically(&path, &bytes, None)
+            .with_context(|| format!("failed to save {}", path.display()))
+    }
+
+    /// Loads the state in a slot, or `None` if nothing was saved to it.
+    ///
+    /// Fails on saves written with a newer version. Saves from older versions are
+    /// loaded as is, see [SaveStore::load_with] to migrate them.
+    pub fn load<T: DeserializeOwned>(&self, slot: &str) -> Result<Option<T>> {
+        self.load_with(slot, |_, state| Ok(state))
+    }
+
+    /// Loads the state in a slot like [SaveStore::load], first passing saves from
+    /// older versions through `migrate` along with their version, such as to rename
+    /// fields or fill in new ones.
+    pub fn load_with<T: DeserializeOwned>(
+        &self,
+        slot: &str,
+        migrate: impl FnOnce(u32, serde_json::Value) -> Result<serde_json::Value>,
+    ) -> Result<Option<T>> {
+        let path = self.path(slot)?;
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+
+        let (version, json) =
+            decode(&bytes).with_context(|| format!("invalid save {}", path.display()))?;
+        if version > self.version {
+            bail!(
+                "save {} is from a newer version ({version}, expected at most {})",
+                path.display(),
+                self.version
+            );
+        }
+
+        let mut state = serde_json::from_slice(&json)?;
+        if version < self.version {
+            state = migrate(version, state)
+                .with_context(|| format!("failed to migrate save {}", path.display()))?;
+        }
+        Ok(Some(serde_json::from_value(state)?))
+    }
+
+    /// Checks if anything was saved to a slot.
+    pub fn exists(&self, slot: &str) -> Result<bool> {
+        Ok(self.path(slot)?.exists())
+    }
+
+    /// Deletes a slot if it exists.
+    pub fn delete(&self, slot: &str) -> Result<()> {
+        let path = self.path(slot)?;
+        match std::fs::remove_file(&path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(error).with_context(|| format!("failed to delete {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, slot: &str) -> Result<PathBuf> {
+        validate_slot(slot)?;
+        Ok(self.directory.join(format!("{slot}.sav")))
+    }
+}
+
+/// Splits a save into its version and serialized state, decompressing it if needed.
+fn decode(bytes: &[u8]) -> Result<(u32, Vec<u8>)> {
+    if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+        bail!("not a clockwork save");
+    }
+    let flags = bytes[4];
+    let version = u32::from_le_bytes(bytes[5..HEADER_SIZE].try_into()?);
+    let contents = &bytes[HEADER_SIZE..];
+
+    let json = match flags & FLAG_COMPRESSED != 0 {
+        true => {
+            let mut json = Vec::new();
+            flate2::read::DeflateDecoder::new(contents).read_to_end(&mut json)?;
+            json
+        }
+        false => contents.to_vec(),
+    };
+    Ok((version, json))
+}
+
+/// Writes a file then renames it over `path`, so an interrupted save never replaces
+/// the previous one. The file is given the `modified` time if there is one.
+pub(crate) fn write_atomically(
+    path: &Path,
+    bytes: &[u8],
+    modified: Option<SystemTime>,
+) -> std::io::Result<()> {
+    let partial_path = path.with_extension("partial");
+    let mut file = std::fs::File::create(&partial_path)?;
+    file.write_all(bytes)?;
+    if let Some(modified) = modified {
+        file.set_modified(modified)?;
+    }
+    // Otherwise the rename can reach the disk before the contents do, leaving an
+    // empty save after a power loss.
+    file.sync_all()?;
+    std::fs::rename(&partial_path, path)?;
+
+    // The rename itself is only on disk once the directory is synced.
+    #[cfg(unix)]
+    {
+        let directory = path
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        std::fs::File::open(directory)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct GameState {
+        level: u32,
+        name: String,
+    }
+
+    fn test_store(name: &str) -> SaveStore {
+        let directory = std::env::temp_dir().join(format!("clockwork_persistence_{name}"));
+        let _ = std::fs::remove_dir_all(&directory);
+        SaveStore::new(directory).unwrap()
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let state = GameState {
+            level: 3,
+            name: "hero".to_string(),
+        };
+        for compressed in [false, true] {
+            let store = test_store("save_and_load").with_compression(compressed);
+            assert_eq!(store.load::<GameState>("slot1").unwrap(), None);
+
+            store.save("slot1", &state).unwrap();
+            assert!(store.exists("slot1").unwrap());
+            assert_eq!(
+                store.load::<GameState>("slot1").unwrap(),
+                Some(state.clone())
+            );
+            assert!(!store.directory().join("slot1.partial").exists());
+
+            store.delete("slot1").unwrap();
+            assert!(!store.exists("slot1").unwrap());
+            std::fs::remove_dir_all(store.directory()).unwrap();
+        }
+        assert!(test_store("invalid_slot")
+            .save("../escape", &state)
+            .is_err());
+    }
+
+    #[test]
+    fn test_versions() {
+        let store = test_store("versions");
+        store
+            .save("slot1", &serde_json::json!({ "level": 3 }))
+            .unwrap();
+
+        let store = store.with_version(1);
+        assert!(store.load::<GameState>("slot1").is_err());
+        let state: GameState = store
+            .load_with("slot1", |version, mut state| {
+                assert_eq!(version, 0);
+                state["name"] = "migrated".into();
+                Ok(state)
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.name, "migrated");
+
+        // Newer saves can't be loaded by older versions of the game.
+        store.save("slot1", &state).unwrap();
+        assert!(store
+            .clone()
+            .with_version(0)
+            .load::<GameState>("slot1")
+            .is_err());
+        std::fs::remove_dir_all(store.directory()).unwrap();
+    }
+}
@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use std::hash::Hash;
+use std::sync::{mpsc, Arc, Weak};
 use std::{fmt::Debug, marker::PhantomData};
 
 /// References a resource within a [Repository].
@@ -98,6 +100,41 @@ impl<T> Repository<T> {
         self.resources.get_mut(id.index)?.0.as_mut()
     }
 
+    /// Removes a resource, returning it if it existed. Its generation is kept, so
+    /// adding a resource with the same id again bumps it.
+    pub fn remove(&mut self, id: ResourceId<T>) -> Option<T> {
+        self.resources.get_mut(id.index)?.0.take()
+    }
+
+    /// Gets the number of resources stored.
+    pub fn len(&self) -> usize {
+        self.resources
+            .iter()
+            .filter(|(resource, _)| resource.is_some())
+            .count()
+    }
+
+    /// Iterates over every stored resource and its [ResourceId].
+    pub fn iter(&self) -> impl Iterator<Item = (ResourceId<T>, &T)> {
+        self.resources
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (resource, _))| Some((ResourceId::new(index), resource.as_ref()?)))
+    }
+
+    /// Iterates mutably over every stored resource and its [ResourceId].
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ResourceId<T>, &mut T)> {
+        self.resources
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, (resource, _))| Some((ResourceId::new(index), resource.as_mut()?)))
+    }
+
+    /// Checks whether no resources are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Gets the generation of a resource given a [ResourceId].
     ///
     /// This can be useful to implement caching mechanisms.
@@ -123,6 +160,150 @@ impl<T> std::ops::IndexMut<ResourceId<T>> for Repository<T> {
     }
 }
 
+/// Reference counted [ResourceId], which has its resource removed once every clone of
+/// it is dropped. See [StrongResourceIds::make_strong].
+pub struct StrongResourceId<T> {
+    handle: Arc<StrongHandle<T>>,
+}
+
+/// Shared by the clones of a [StrongResourceId], queueing the id when dropped.
+struct StrongHandle<T> {
+    id: ResourceId<T>,
+    dropped: mpsc::Sender<ResourceId<T>>,
+    /// Where the first handle was made, captured when `RUST_BACKTRACE` is set.
+    #[cfg(debug_assertions)]
+    backtrace: std::backtrace::Backtrace,
+}
+
+impl<T> Drop for StrongHandle<T> {
+    fn drop(&mut self) {
+        // The receiver is only gone if its owner is, along with the resource.
+        let _ = self.dropped.send(self.id);
+    }
+}
+
+impl<T> StrongResourceId<T> {
+    /// Gets the id of the resource, which stays valid as long as this handle lives.
+    pub fn id(&self) -> ResourceId<T> {
+        self.handle.id
+    }
+}
+
+impl<T> Clone for StrongResourceId<T> {
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle.clone(),
+        }
+    }
+}
+
+impl<T> Debug for StrongResourceId<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("StrongResourceId").field(&self.id()).finish()
+    }
+}
+
+impl<T> From<&StrongResourceId<T>> for ResourceId<T> {
+    fn from(strong_id: &StrongResourceId<T>) -> Self {
+        strong_id.id()
+    }
+}
+
+/// [StrongResourceId] still alive, see [StrongResourceIds::outstanding].
+pub struct OutstandingHandle<T> {
+    /// Id of the resource the handle keeps alive.
+    pub id: ResourceId<T>,
+    /// Number of clones of the handle alive.
+    pub count: usize,
+    /// Where the handle was first made, in debug builds run with `RUST_BACKTRACE` set.
+    pub backtrace: Option<String>,
+}
+
+/// Hands out [StrongResourceId]s for the resources of a [Repository], and tracks which
+/// ones are no longer referenced.
+pub struct StrongResourceIds<T> {
+    handles: HashMap<ResourceId<T>, Weak<StrongHandle<T>>>,
+    sender: mpsc::Sender<ResourceId<T>>,
+    receiver: mpsc::Receiver<ResourceId<T>>,
+}
+
+impl<T> Default for StrongResourceIds<T> {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            handles: HashMap::new(),
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl<T> StrongResourceIds<T> {
+    /// Constructs a new [StrongResourceIds].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets a [StrongResourceId] for a resource, sharing the count of any handle to it
+    /// that is still alive.
+    pub fn make_strong(&mut self, id: ResourceId<T>) -> StrongResourceId<T> {
+        if let Some(handle) = self.handles.get(&id).and_then(Weak::upgrade) {
+            return StrongResourceId { handle };
+        }
+
+        let handle = Arc::new(StrongHandle {
+            id,
+            dropped: self.sender.clone(),
+            #[cfg(debug_assertions)]
+            backtrace: std::backtrace::Backtrace::capture(),
+        });
+        self.handles.insert(id, Arc::downgrade(&handle));
+        StrongResourceId { handle }
+    }
+
+    /// Takes the ids of resources whose handles have all been dropped since the last
+    /// call, which should then be removed.
+    ///
+    /// Resources made strong again in the meantime are skipped.
+    pub fn take_dropped(&mut self) -> Vec<ResourceId<T>> {
+        let mut dropped = Vec::new();
+        for id in self.receiver.try_iter() {
+            let alive = self
+                .handles
+                .get(&id)
+                .is_some_and(|handle| handle.strong_count() > 0);
+            if !alive && self.handles.remove(&id).is_some() {
+                dropped.push(id);
+            }
+        }
+        dropped
+    }
+
+    /// Gets the handles that are still alive, ordered by id, such as to find the ones
+    /// leaked when the application exits.
+    pub fn outstanding(&self) -> Vec<OutstandingHandle<T>> {
+        let mut outstanding: Vec<OutstandingHandle<T>> = self
+            .handles
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|handle| OutstandingHandle {
+                id: handle.id,
+                // Less the upgraded handle.
+                count: Arc::strong_count(&handle) - 1,
+                #[cfg(debug_assertions)]
+                backtrace: match handle.backtrace.status() {
+                    std::backtrace::BacktraceStatus::Captured => Some(handle.backtrace.to_string()),
+                    _ => None,
+                },
+                #[cfg(not(debug_assertions))]
+                backtrace: None,
+            })
+            .collect();
+        outstanding.sort_by_key(|handle| handle.id.index);
+        outstanding
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -210,6 +391,24 @@ mod tests {
         assert!(tool_ref.value == 100);
     }
 
+    #[test]
+    fn test_remove() {
+        let mut repository = Repository::<Tool>::new();
+        let axe = Tool {
+            tool_type: ToolType::Axe,
+            value: 5,
+        };
+        let tool_id = repository.add(axe, None);
+
+        assert_eq!(repository.remove(tool_id), Some(axe));
+        assert!(repository.get(tool_id).is_none());
+        assert!(repository.remove(tool_id).is_none());
+        assert!(repository.is_empty());
+
+        repository.add(axe, Some(tool_id));
+        assert!(repository.get_generation(tool_id) == 2);
+    }
+
     #[test]
     fn test_get_generation() {
         let mut repository = Repository::<Tool>::new();
@@ -236,4 +435,53 @@ mod tests {
 
         assert!(repository.get_generation(tool_id) == 2);
     }
+
+    #[test]
+    fn test_strong_ids() {
+        let mut repository = Repository::<Tool>::new();
+        let mut strong_ids = StrongResourceIds::new();
+        let tool_id = repository.add(
+            Tool {
+                tool_type: ToolType::Hoe,
+                value: 1,
+            },
+            None,
+        );
+
+        let strong_id = strong_ids.make_strong(tool_id);
+        let shared_id = strong_ids.make_strong(tool_id);
+        drop(strong_id);
+        assert!(strong_ids.take_dropped().is_empty());
+        assert_eq!(ResourceId::from(&shared_id), tool_id);
+
+        drop(shared_id);
+        assert_eq!(strong_ids.take_dropped(), vec![tool_id]);
+        assert!(strong_ids.take_dropped().is_empty());
+
+        // Made strong again before the drop is noticed, so it stays.
+        drop(strong_ids.make_strong(tool_id));
+        let revived_id = strong_ids.make_strong(tool_id);
+        assert!(strong_ids.take_dropped().is_empty());
+        drop(revived_id);
+        assert_eq!(strong_ids.take_dropped(), vec![tool_id]);
+    }
+
+    #[test]
+    fn test_outstanding_strong_ids() {
+        let mut strong_ids = StrongResourceIds::<Tool>::new();
+        let leaked = strong_ids.make_strong(ResourceId::new(3));
+        let _clone = leaked.clone();
+        let first = strong_ids.make_strong(ResourceId::new(1));
+        drop(strong_ids.make_strong(ResourceId::new(2)));
+
+        let outstanding = strong_ids.outstanding();
+        let ids: Vec<(usize, usize)> = outstanding
+            .iter()
+            .map(|handle| (handle.id.index, handle.count))
+            .collect();
+        assert_eq!(ids, vec![(1, 1), (3, 2)]);
+
+        drop(first);
+        assert_eq!(strong_ids.outstanding().len(), 1);
+    }
 }
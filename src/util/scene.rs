@@ -0,0 +1,306 @@
+use anyhow::Result;
+use glam::{Affine3A, Mat4};
+
+use crate::graphics::RenderOperation;
+
+use super::repository::{Repository, ResourceId};
+
+/// References a [Node] within a [Scene].
+pub type NodeId = ResourceId<Node>;
+
+/// Something placed in a [Scene], positioned relative to its parent, such as a player
+/// with a weapon attached or a camera rig.
+#[derive(Clone)]
+pub struct Node {
+    /// Transformation relative to the parent, or to the world for root nodes.
+    local: Affine3A,
+    /// Transformation relative to the world, as of the last update.
+    world: Affine3A,
+    /// Whether the local transformation changed since the last update.
+    dirty: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// Operations drawn at the node, with transforms relative to it.
+    pub operations: Vec<RenderOperation>,
+}
+
+/// Hierarchy of [Node]s, where moving a node moves everything attached to it.
+///
+/// World transformations are only recomputed for nodes whose own transformation or
+/// an ancestor's changed.
+#[derive(Default)]
+pub struct Scene {
+    nodes: Repository<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Node {
+    /// Gets the transformation relative to the parent.
+    pub fn local_transform(&self) -> Affine3A {
+        self.local
+    }
+
+    /// Gets the parent of the node, if it has one.
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// Gets the nodes attached to this one.
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+impl Scene {
+    /// Creates a new empty [Scene].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node with the given transformation relative to `parent`, or to the
+    /// world if there is no parent.
+    pub fn add_node(&mut self, local: Affine3A, parent: Option<NodeId>) -> Result<NodeId> {
+        if let Some(parent) = parent {
+            self.node(parent)?;
+        }
+
+        let id = self.nodes.add(
+            Node {
+                local,
+                world: local,
+                dirty: true,
+                parent,
+                children: Vec::new(),
+                operations: Vec::new(),
+            },
+            None,
+        );
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(id),
+            None => self.roots.push(id),
+        }
+        Ok(id)
+    }
+
+    /// Removes a node along with everything attached to it.
+    pub fn remove_node(&mut self, id: NodeId) -> Result<()> {
+        let parent = self.node(id)?.parent;
+        self.detach(id, parent);
+
+        let mut removed = vec![id];
+        while let Some(id) = removed.pop() {
+            if let Some(node) = self.nodes.remove(id) {
+                removed.extend(node.children);
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets a node.
+    pub fn node(&self, id: NodeId) -> Result<&Node> {
+        self.nodes
+            .get(id)
+            .ok_or_else(|| anyhow::anyhow!("no node {id:?}"))
+    }
+
+    /// Gets a node mutably, such as to change its operations.
+    pub fn node_mut(&mut self, id: NodeId) -> Result<&mut Node> {
+        self.nodes
+            .get_mut(id)
+            .ok_or_else(|| anyhow::anyhow!("no node {id:?}"))
+    }
+
+    /// Gets the nodes without a parent.
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Sets the transformation of a node relative to its parent.
+    pub fn set_local_transform(&mut self, id: NodeId, local: Affine3A) -> Result<()> {
+        let node = self.node_mut(id)?;
+        node.local = local;
+        node.dirty = true;
+        Ok(())
+    }
+
+    /// Gets the transformation of a node relative to the world.
+    pub fn world_transform(&mut self, id: NodeId) -> Result<Affine3A> {
+        self.node(id)?;
+        self.update_transforms();
+        Ok(self.nodes[id].world)
+    }
+
+    /// Attaches a node to a new parent, or to the world if there is none. The node
+    /// keeps its local transformation, so it moves with the new parent.
+    ///
+    /// Returns an error if the parent is the node itself or attached to it.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<()> {
+        let old_parent = self.node(id)?.parent;
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            anyhow::ensure!(
+                ancestor_id != id,
+                "node {id:?} can't be attached to itself or its descendants"
+            );
+            ancestor = self.node(ancestor_id)?.parent;
+        }
+
+        self.detach(id, old_parent);
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(id),
+            None => self.roots.push(id),
+        }
+        let node = &mut self.nodes[id];
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    /// Attaches an operation to a node, drawn relative to it.
+    pub fn attach(&mut self, id: NodeId, operation: RenderOperation) -> Result<()> {
+        self.node_mut(id)?.operations.push(operation);
+        Ok(())
+    }
+
+    /// Recomputes the world transformations of nodes that moved since the last update,
+    /// along with everything attached to them.
+    pub fn update_transforms(&mut self) {
+        let mut stack: Vec<(NodeId, Affine3A, bool)> = self
+            .roots
+            .iter()
+            .map(|&id| (id, Affine3A::IDENTITY, false))
+            .collect();
+        while let Some((id, parent_world, parent_moved)) = stack.pop() {
+            let node = &mut self.nodes[id];
+            let moved = parent_moved || node.dirty;
+            if moved {
+                node.world = parent_world * node.local;
+                node.dirty = false;
+            }
+            let world = node.world;
+            stack.extend(node.children.iter().map(|&child| (child, world, moved)));
+        }
+    }
+
+    /// Gets every attached operation along with the world transformation of its node,
+    /// updating transformations first.
+    pub fn iter_operations(&mut self) -> impl Iterator<Item = (Affine3A, &RenderOperation)> {
+        self.update_transforms();
+        let nodes = &self.nodes;
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            let node = &nodes[id];
+            stack.extend(node.children.iter().rev());
+            Some(node)
+        })
+        .flat_map(|node| {
+            node.operations
+                .iter()
+                .map(move |operation| (node.world, operation))
+        })
+    }
+
+    /// Gets every attached operation, transformed into world space and ready to draw.
+    pub fn render_operations(&mut self) -> Vec<RenderOperation> {
+        self.iter_operations()
+            .map(|(world, operation)| RenderOperation {
+                transform: Mat4::from(world) * operation.transform,
+                ..*operation
+            })
+            .collect()
+    }
+
+    /// Removes a node from its parent's children, or from the roots.
+    fn detach(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let siblings = match parent {
+            Some(parent) => &mut self.nodes[parent].children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&sibling| sibling != id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec3, Vec4};
+
+    use super::*;
+
+    fn translation(x: f32) -> Affine3A {
+        Affine3A::from_translation(Vec3::new(x, 0.0, 0.0))
+    }
+
+    fn world_x(scene: &mut Scene, id: NodeId) -> f32 {
+        scene.world_transform(id).unwrap().translation.x
+    }
+
+    #[test]
+    fn test_children_follow_parents() {
+        let mut scene = Scene::new();
+        let player = scene.add_node(translation(10.0), None).unwrap();
+        let weapon = scene.add_node(translation(1.0), Some(player)).unwrap();
+        let muzzle = scene.add_node(translation(0.5), Some(weapon)).unwrap();
+        assert_eq!(world_x(&mut scene, muzzle), 11.5);
+
+        scene
+            .set_local_transform(player, translation(20.0))
+            .unwrap();
+        assert_eq!(world_x(&mut scene, muzzle), 21.5);
+
+        // Reparenting keeps the local transformation.
+        scene.set_parent(weapon, None).unwrap();
+        assert_eq!(world_x(&mut scene, muzzle), 1.5);
+        assert_eq!(scene.roots(), &[player, weapon]);
+        assert!(scene.node(player).unwrap().children().is_empty());
+    }
+
+    #[test]
+    fn test_set_parent_rejects_cycles() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(Affine3A::IDENTITY, None).unwrap();
+        let child = scene.add_node(Affine3A::IDENTITY, Some(parent)).unwrap();
+        assert!(scene.set_parent(parent, Some(child)).is_err());
+        assert!(scene.set_parent(parent, Some(parent)).is_err());
+        assert_eq!(scene.node(child).unwrap().parent(), Some(parent));
+    }
+
+    #[test]
+    fn test_remove_node_removes_descendants() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(Affine3A::IDENTITY, None).unwrap();
+        let child = scene.add_node(Affine3A::IDENTITY, Some(parent)).unwrap();
+        let grandchild = scene.add_node(Affine3A::IDENTITY, Some(child)).unwrap();
+        let other = scene.add_node(Affine3A::IDENTITY, None).unwrap();
+
+        scene.remove_node(child).unwrap();
+        assert!(scene.node(grandchild).is_err());
+        assert!(scene.node(parent).unwrap().children().is_empty());
+
+        scene.remove_node(parent).unwrap();
+        assert_eq!(scene.roots(), &[other]);
+    }
+
+    #[test]
+    fn test_operations_are_placed_in_world() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(translation(2.0), None).unwrap();
+        let child = scene.add_node(translation(3.0), Some(parent)).unwrap();
+        let operation = |x: f32| {
+            RenderOperation::colored_mesh(
+                Mat4::from_translation(Vec3::new(x, 0.0, 0.0)),
+                ResourceId::new(0),
+                Vec4::ONE,
+            )
+        };
+        scene.attach(parent, operation(0.0)).unwrap();
+        scene.attach(child, operation(1.0)).unwrap();
+
+        let xs: Vec<f32> = scene
+            .render_operations()
+            .iter()
+            .map(|operation| operation.transform.w_axis.x)
+            .collect();
+        assert_eq!(xs, vec![2.0, 6.0]);
+    }
+}
@@ -0,0 +1,168 @@
+use glam::{Mat4, Vec3, Vec4Swizzles};
+
+use super::camera::Camera;
+
+/// Pairs of indices into [frustum_corners] that form the edges of a frustum, for
+/// drawing it as lines.
+pub const FRUSTUM_EDGES: [(usize, usize); 12] = [
+    // Near face.
+    (0, 1),
+    (1, 3),
+    (3, 2),
+    (2, 0),
+    // Far face.
+    (4, 5),
+    (5, 7),
+    (7, 6),
+    (6, 4),
+    // Near to far.
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// Orthographic frustum of a directional light's shadow map, fitted around the region a
+/// camera can see.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowFrustum {
+    /// View matrix of the light.
+    pub view: Mat4,
+    /// Orthographic projection of the light.
+    pub projection: Mat4,
+    /// Size of a shadow map texel in world units.
+    pub texel_size: f32,
+}
+
+/// Gets the world space corners of the frustum described by a view projection matrix.
+///
+/// Corners are ordered near then far, each as bottom left, bottom right, top left,
+/// top right.
+pub fn frustum_corners(view_projection: Mat4) -> [Vec3; 8] {
+    let inverse = view_projection.inverse();
+    let mut corners = [Vec3::ZERO; 8];
+    for (index, corner) in corners.iter_mut().enumerate() {
+        let x = if index & 1 == 0 { -1.0 } else { 1.0 };
+        let y = if index & 2 == 0 { -1.0 } else { 1.0 };
+        let z = if index & 4 == 0 { 0.0 } else { 1.0 };
+        let point = inverse * glam::vec4(x, y, z, 1.0);
+        *corner = point.xyz() / point.w;
+    }
+    corners
+}
+
+impl ShadowFrustum {
+    /// Fits a shadow frustum around the given corners, such as a camera's frustum or a
+    /// slice of it for a shadow cascade.
+    ///
+    /// The frustum is sized by the bounding sphere of the corners so it doesn't change
+    /// size as the camera rotates, and is snapped to whole shadow map texels so shadow
+    /// edges don't shimmer as the camera moves. It also extends one radius towards the
+    /// light so casters outside the view still cast into it.
+    pub fn fit(corners: &[Vec3; 8], light_direction: Vec3, resolution: u32) -> Self {
+        let center = corners.iter().copied().sum::<Vec3>() / corners.len() as f32;
+        let radius = corners
+            .iter()
+            .map(|corner| corner.distance(center))
+            .fold(0.0, f32::max)
+            .max(f32::EPSILON);
+
+        // The view is kept at the origin so snapping happens on a fixed grid.
+        let direction = light_direction.normalize_or_zero();
+        let direction = if direction == Vec3::ZERO {
+            Vec3::NEG_Y
+        } else {
+            direction
+        };
+        let up = if direction.y.abs() > 0.99 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let view = Mat4::look_to_rh(Vec3::ZERO, direction, up);
+
+        let texel_size = 2.0 * radius / resolution.max(1) as f32;
+        let light_center = view.transform_point3(center);
+        let snapped_x = (light_center.x / texel_size).round() * texel_size;
+        let snapped_y = (light_center.y / texel_size).round() * texel_size;
+
+        // Right handed views look down -z, so depths are negated.
+        let projection = Mat4::orthographic_rh(
+            snapped_x - radius,
+            snapped_x + radius,
+            snapped_y - radius,
+            snapped_y + radius,
+            -light_center.z - 2.0 * radius,
+            -light_center.z + radius,
+        );
+
+        Self {
+            view,
+            projection,
+            texel_size,
+        }
+    }
+
+    /// Fits a shadow frustum around everything the camera can see.
+    pub fn fit_camera(camera: &Camera, light_direction: Vec3, resolution: u32) -> Self {
+        Self::fit(
+            &frustum_corners(camera.get_view_projection_matrix()),
+            light_direction,
+            resolution,
+        )
+    }
+
+    /// Gets the view projection matrix to render the shadow map with.
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection * self.view
+    }
+
+    /// Gets the world space corners of the frustum, for visualizing it.
+    pub fn corners(&self) -> [Vec3; 8] {
+        frustum_corners(self.view_projection())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frustum_corners() {
+        let corners = frustum_corners(Mat4::IDENTITY);
+        assert_eq!(corners[0], Vec3::new(-1.0, -1.0, 0.0));
+        assert_eq!(corners[7], Vec3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_fit_contains_corners() {
+        let camera = Mat4::perspective_rh(1.0, 1.5, 0.1, 20.0)
+            * Mat4::look_at_rh(Vec3::new(3.0, 2.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let corners = frustum_corners(camera);
+        let shadow = ShadowFrustum::fit(&corners, Vec3::new(-1.0, -2.0, -0.5), 2048);
+
+        for corner in corners {
+            let clip = shadow.view_projection().project_point3(corner);
+            assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0, "{clip}");
+            assert!((0.0..=1.0).contains(&clip.z), "{clip}");
+        }
+    }
+
+    #[test]
+    fn test_fit_is_texel_snapped() {
+        let corners = frustum_corners(Mat4::orthographic_rh(-5.0, 5.0, -5.0, 5.0, 0.0, 10.0));
+        let light_direction = Vec3::new(0.3, -1.0, 0.2);
+        let shadow = ShadowFrustum::fit(&corners, light_direction, 1024);
+
+        // Nudging the view by less than a texel shouldn't move the shadow map at all.
+        let nudge = Vec3::splat(shadow.texel_size * 0.1);
+        let nudged = corners.map(|corner| corner + nudge);
+        let nudged_shadow = ShadowFrustum::fit(&nudged, light_direction, 1024);
+
+        let origin = shadow.view_projection().project_point3(Vec3::ZERO);
+        let nudged_origin = nudged_shadow.view_projection().project_point3(Vec3::ZERO);
+        assert!(origin
+            .truncate()
+            .abs_diff_eq(nudged_origin.truncate(), 1e-5));
+    }
+}
@@ -1,62 +1,334 @@
-use std::collections::HashMap;
+use std::{ collections::HashMap, time::Duration };
 
-use glam::Vec4;
+use glam::{ Mat4, Quat, UVec2, Vec2, Vec3, Vec4 };
 
-use crate::graphics::texture::Texture;
+use crate::graphics::{ texture::Texture, Billboard };
 
-use super::repository::ResourceId;
+use super::{ aseprite::parse_aseprite_sheet, coordinates::Uv, repository::ResourceId };
 
 /// Contains information on how to render a specific animation
 /// from a larger texture with multiple animations bundled together.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Sprite
 {
     /// Texture this sprite comes from.
     pub texture: ResourceId<Texture>,
-    /// Top left uv coordinate.
-    pub uv_topleft: glam::Vec2,
-    /// UV width and height.
-    pub uv_dims: glam::Vec2,
-    /// Sprite dimensions in pixels.
+    /// Sprite dimensions in pixels, before any trimming.
     pub sprite_dims: glam::UVec2,
-    /// Number of frames in this sprite.
-    pub frame_count: usize,
+    /// Frames of the animation in order.
+    pub frames: Vec<SpriteFrame>,
+}
+
+/// A single frame of a [Sprite].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteFrame
+{
+    /// Section of the texture holding the frame, as left, top, width and height [Uv]s.
+    pub uv_window: Vec4,
+    /// How long the frame is shown for.
+    pub duration: Duration,
+    /// Whether the frame is packed rotated 90 degrees clockwise in the texture.
+    pub rotated: bool,
+    /// Position of the trimmed frame within the untrimmed one, in pixels from the top left.
+    pub trim_offset: UVec2,
+    /// Size of the trimmed frame in pixels.
+    pub trim_size: UVec2,
+    /// Size of the untrimmed frame in pixels.
+    pub source_size: UVec2,
 }
 
 impl Sprite
 {
+    /// Gets the uv window of the given frame, wrapping around past the last frame.
     pub fn get_uv_window(&self, frame: usize) -> Vec4
     {
-        let modded_frame = frame % self.frame_count;
-        Vec4::new(
-            self.uv_topleft.x + (self.uv_dims.x * modded_frame as f32),
-            self.uv_topleft.y,
-            self.uv_dims.x,
-            self.uv_dims.y,
+        self.get_frame(frame).uv_window
+    }
+
+    /// Gets the given frame, wrapping around past the last frame.
+    pub fn get_frame(&self, frame: usize) -> &SpriteFrame
+    {
+        &self.frames[frame % self.frames.len()]
+    }
+
+    /// Number of frames in this sprite.
+    pub fn frame_count(&self) -> usize
+    {
+        self.frames.len()
+    }
+
+    /// Total duration of the animation.
+    pub fn duration(&self) -> Duration
+    {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    /// Gets the frame shown after the animation has been playing for `elapsed`,
+    /// looping once it reaches the end.
+    pub fn frame_at(&self, elapsed: Duration) -> usize
+    {
+        let duration = self.duration();
+        if duration.is_zero()
+        {
+            return 0;
+        }
+
+        let mut remaining = Duration::from_nanos((elapsed.as_nanos() % duration.as_nanos()) as u64);
+        for (index, frame) in self.frames.iter().enumerate()
+        {
+            if remaining < frame.duration
+            {
+                return index;
+            }
+            remaining -= frame.duration;
+        }
+        self.frames.len() - 1
+    }
+
+    /// Gets a time offset into the animation picked at random from `seed`, so instances
+    /// seeded differently, such as by their position or entity id, start at different
+    /// points in the animation instead of all animating in lockstep.
+    pub fn random_phase(&self, seed: u64) -> Duration
+    {
+        let duration = self.duration().as_nanos() as u64;
+        match duration
+        {
+            0 => Duration::ZERO,
+            duration => Duration::from_nanos(scramble(seed) % duration),
+        }
+    }
+}
+
+/// Where and how [crate::graphics::RenderOperation::sprite] draws a frame of a [Sprite].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpritePlacement
+{
+    /// Position of the pivot.
+    pub position: Vec3,
+    /// Point of the sprite placed at the position and rotated around, from (0, 0) at its
+    /// bottom left to (1, 1) at its top right.
+    pub pivot: Vec2,
+    /// Counter-clockwise rotation around the pivot in radians.
+    pub rotation: f32,
+    /// Rotation turning the sprite from facing +z, such as toward the camera with
+    /// [SpritePlacement::with_billboard].
+    pub facing: Quat,
+    /// Size of one of the sprite's pixels in world units.
+    pub pixel_size: f32,
+    /// Whether the sprite is mirrored left to right within its bounds.
+    pub flip_x: bool,
+    /// Whether the sprite is mirrored top to bottom within its bounds.
+    pub flip_y: bool,
+    /// Color the sprite is multiplied by.
+    pub color: Vec4,
+}
+
+impl SpritePlacement
+{
+    /// Creates a [SpritePlacement] centering the sprite on `position`, one world unit
+    /// per pixel.
+    pub fn at(position: Vec3) -> Self
+    {
+        Self {
+            position,
+            pivot: Vec2::splat(0.5),
+            rotation: 0.0,
+            facing: Quat::IDENTITY,
+            pixel_size: 1.0,
+            flip_x: false,
+            flip_y: false,
+            color: Vec4::ONE,
+        }
+    }
+
+    /// Sets the point of the sprite placed at the position and rotated around, such as
+    /// `(0.5, 0.0)` for a character's feet.
+    pub fn with_pivot(mut self, pivot: Vec2) -> Self
+    {
+        self.pivot = pivot;
+        self
+    }
+
+    /// Sets the counter-clockwise rotation around the pivot in radians.
+    pub fn with_rotation(mut self, rotation: f32) -> Self
+    {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Turns the sprite around its pivot to face a camera with the given view matrix,
+    /// such as to place it in a 3D world.
+    pub fn with_billboard(mut self, billboard: Billboard, view: Mat4) -> Self
+    {
+        self.facing = billboard.rotation(view);
+        self
+    }
+
+    /// Sets the size of one of the sprite's pixels in world units.
+    pub fn with_pixel_size(mut self, pixel_size: f32) -> Self
+    {
+        self.pixel_size = pixel_size;
+        self
+    }
+
+    /// Sets whether the sprite is mirrored, such as to face a character the other way.
+    pub fn with_flip(mut self, flip_x: bool, flip_y: bool) -> Self
+    {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Sets the color the sprite is multiplied by.
+    pub fn with_color(mut self, color: Vec4) -> Self
+    {
+        self.color = color;
+        self
+    }
+
+    /// Transformation to apply to a unit quad covering an untrimmed sprite of
+    /// `sprite_dims` pixels, see [SpriteFrame::quad_transform].
+    pub fn transform(&self, sprite_dims: UVec2) -> Mat4
+    {
+        let size = sprite_dims.as_vec2() * self.pixel_size;
+        Mat4::from_scale_rotation_translation(
+            size.extend(1.0),
+            self.facing * Quat::from_rotation_z(self.rotation),
+            self.position,
         )
+            * Mat4::from_translation((Vec2::splat(0.5) - self.pivot).extend(0.0))
     }
 }
 
-// ####################################
-// For deserializing the Aseprite file
-// ####################################
-#[derive(serde::Deserialize)]
-struct Tag
+/// Plays a [Sprite]'s animation for a single instance, such as one character in a crowd.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AnimationPlayer
 {
-    name: Option<String>,
-    from: usize,
-    to: usize,
+    /// How long the animation has been playing.
+    pub elapsed: Duration,
+    /// How far into the animation the instance started, so instances don't animate in
+    /// lockstep.
+    pub time_offset: Duration,
 }
 
-#[derive(serde::Deserialize)]
-struct Frame
+impl AnimationPlayer
 {
-    x: u32,
-    y: u32,
-    w: u32,
-    h: u32,
+    /// Creates an [AnimationPlayer] at the start of the animation.
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Sets how far into the animation the instance starts.
+    pub fn with_time_offset(mut self, time_offset: Duration) -> Self
+    {
+        self.time_offset = time_offset;
+        self
+    }
+
+    /// Starts the instance at a point in the sprite's animation picked at random from
+    /// `seed`, see [Sprite::random_phase].
+    pub fn with_random_phase(self, sprite: &Sprite, seed: u64) -> Self
+    {
+        self.with_time_offset(sprite.random_phase(seed))
+    }
+
+    /// Advances the animation by `delta`.
+    pub fn advance(&mut self, delta: Duration)
+    {
+        self.elapsed += delta;
+    }
+
+    /// Restarts the animation from the instance's time offset.
+    pub fn restart(&mut self)
+    {
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// Gets the frame of a sprite currently shown.
+    pub fn frame(&self, sprite: &Sprite) -> usize
+    {
+        sprite.frame_at(self.elapsed + self.time_offset)
+    }
+
+    /// Gets the uv window of the frame of a sprite currently shown.
+    pub fn uv_window(&self, sprite: &Sprite) -> Vec4
+    {
+        sprite.get_uv_window(self.frame(sprite))
+    }
+}
+
+/// Mixes the bits of a seed so nearby seeds, such as neighbouring tiles, land far
+/// apart (splitmix64).
+pub(crate) fn scramble(seed: u64) -> u64
+{
+    let mut value = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+impl SpriteFrame
+{
+    /// Transformation to apply to a unit quad so the frame lands where it would in the
+    /// untrimmed sprite, where the untrimmed sprite covers the unit quad.
+    ///
+    /// This undoes both trimming and rotation, so combine it with the sprite's own transform
+    /// when rendering frames of trimmed or rotated sheets.
+    pub fn quad_transform(&self) -> Mat4
+    {
+        let source_size = self.source_size.max(UVec2::ONE).as_vec2();
+        let trim_size = self.trim_size.as_vec2();
+        let center = self.trim_offset.as_vec2() + trim_size * 0.5;
+
+        let translation = Uv::from_pixel(center, source_size).to_quad().extend(0.0);
+        let to_source = Mat4::from_translation(translation) * Mat4::from_scale((1.0 / source_size).extend(1.0));
+
+        match self.rotated
+        {
+            true => to_source
+                * Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2)
+                * Mat4::from_scale(Vec3::new(trim_size.y, trim_size.x, 1.0)),
+            false => to_source * Mat4::from_scale(trim_size.extend(1.0)),
+        }
+    }
+
+    /// Gets the frame mirrored within the untrimmed sprite by flipping its uv window and
+    /// trim, so a single sheet can face both ways.
+    pub fn flipped(&self, flip_x: bool, flip_y: bool) -> SpriteFrame
+    {
+        let mut frame = *self;
+        let source_size = self.source_size.max(self.trim_size);
+        if flip_x
+        {
+            frame.trim_offset.x = source_size.x - self.trim_offset.x - self.trim_size.x;
+        }
+        if flip_y
+        {
+            frame.trim_offset.y = source_size.y - self.trim_offset.y - self.trim_size.y;
+        }
+
+        // Frames packed rotated run across the texture the other way.
+        let (flip_u, flip_v) = match self.rotated
+        {
+            true => (flip_y, flip_x),
+            false => (flip_x, flip_y),
+        };
+        let mut uv_window = self.uv_window;
+        if flip_u
+        {
+            uv_window.x += uv_window.z;
+            uv_window.z = -uv_window.z;
+        }
+        if flip_v
+        {
+            uv_window.y += uv_window.w;
+            uv_window.w = -uv_window.w;
+        }
+        frame.uv_window = uv_window;
+        frame
+    }
 }
-// ####################################
 
 /// Return type of [load_aseprite_sprites].
 pub struct LoadedSprites
@@ -68,78 +340,160 @@ pub struct LoadedSprites
 }
 
 /// Loads sprite information from a json content generated by aseprite.
+///
+/// Both the array and hash formats for frames are supported, along with per-frame
+/// durations and trimmed or rotated frames.
 pub fn load_aseprite_sprites(
     raw_json: &str,
     texture: ResourceId<Texture>,
 ) -> anyhow::Result<LoadedSprites>
 {
-    let json: serde_json::Value = serde_json::from_str(raw_json)?;
-    let meta = json.get("meta").unwrap();
-    let image = meta.get("image").unwrap().as_str().unwrap();
-    let texture_dims = meta
-        .get("size")
-        .map(|value| glam::DVec2 {
-            x: value.get("w").unwrap().as_u64().unwrap() as f64,
-            y: value.get("h").unwrap().as_u64().unwrap() as f64,
-        })
-        .unwrap();
-
-    let frames: Vec<Frame> = json
-        .get("frames")
-        .unwrap()
-        .as_array()
-        .unwrap()
-        .iter()
-        .map(|value| serde_json::from_value::<Frame>(value.get("frame").unwrap().clone()).unwrap())
-        .collect();
-
-    let tag_array = meta.get("frameTags").unwrap().as_array().unwrap();
-    let tags: Vec<Tag> = match tag_array.is_empty() {
-        true => vec![Tag {
-            name: None,
-            from: 0,
-            to: frames.len() - 1,
-        }],
-        false => tag_array
-            .iter()
-            .map(|value| serde_json::from_value::<Tag>(value.clone()).unwrap())
-            .collect(),
-    };
-
-    let sprites_and_tags = tags.iter().map(|tag| {
-        let first_frame = &frames[tag.from];
-
-        let uv_topleft =
-            (glam::dvec2(first_frame.x as f64, first_frame.y as f64) / texture_dims).as_vec2();
-
-        let sprite_dims = glam::uvec2(first_frame.w, first_frame.h);
-        let uv_dims = (sprite_dims.as_dvec2() / texture_dims).as_vec2();
-        let frame_count = tag.to + 1 - tag.from;
-
-        let sprite = Sprite {
-            texture,
-            uv_topleft,
-            uv_dims,
-            sprite_dims,
-            frame_count,
-        };
-        (tag.name.clone(), sprite)
-    });
+    let sheet = parse_aseprite_sheet(raw_json, texture)?;
 
     Ok(LoadedSprites {
-        image: image.to_string(),
-        sprites: HashMap::from_iter(sprites_and_tags),
+        image: sheet.image,
+        sprites: HashMap::from_iter(sheet.sprites),
     })
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_load_basic()
+    {
+        const RAW_JSON: &str = include_str!("./test_files/aseprite_basic.json");
+
+        let loaded_sprites = load_aseprite_sprites(RAW_JSON, ResourceId::new(0)).unwrap();
+        assert_eq!(loaded_sprites.image, "basic.png");
+
+        let sprite = &loaded_sprites.sprites[&None];
+        assert_eq!(sprite.frame_count(), 2);
+        assert_eq!(sprite.sprite_dims, glam::uvec2(32, 32));
+        assert_eq!(sprite.get_uv_window(1), Vec4::new(0.5, 0.0, 0.5, 1.0));
+        assert_eq!(sprite.get_uv_window(2), sprite.get_uv_window(0));
+    }
+
+    #[test]
+    fn test_load_tagged()
+    {
+        const RAW_JSON: &str = include_str!("./test_files/aseprite_tagged.json");
+
+        let loaded_sprites = load_aseprite_sprites(RAW_JSON, ResourceId::new(0)).unwrap();
+        assert_eq!(loaded_sprites.sprites.len(), 2);
+        assert_eq!(loaded_sprites.sprites[&Some("Tag0".to_string())].frame_count(), 2);
+        assert_eq!(loaded_sprites.sprites[&Some("Tag1".to_string())].frame_count(), 5);
+    }
+
+    #[test]
+    fn test_load_hash_format()
+    {
+        const RAW_JSON: &str = include_str!("./test_files/aseprite_hash.json");
+
+        let loaded_sprites = load_aseprite_sprites(RAW_JSON, ResourceId::new(0)).unwrap();
+        let sprite = &loaded_sprites.sprites[&Some("walk".to_string())];
+
+        // Frames keep file order even though "hash 10" sorts before "hash 2".
+        let durations: Vec<u64> = sprite.frames.iter().map(|frame| frame.duration.as_millis() as u64).collect();
+        assert_eq!(durations, vec![50, 150, 100]);
+        assert_eq!(sprite.frame_at(Duration::from_millis(120)), 1);
+        assert_eq!(sprite.frame_at(Duration::from_millis(310)), 0);
+
+        let rotated = sprite.get_frame(2);
+        assert!(rotated.rotated);
+        assert_eq!(rotated.uv_window, Vec4::new(0.5, 0.0, 0.25, 1.0));
+    }
+
+    #[test]
+    fn test_trimmed_quad_transform()
+    {
+        let frame = SpriteFrame {
+            uv_window: Vec4::ZERO,
+            duration: Duration::ZERO,
+            rotated: false,
+            trim_offset: glam::uvec2(16, 0),
+            trim_size: glam::uvec2(16, 16),
+            source_size: glam::uvec2(32, 32),
+        };
+
+        // The top right quarter of the sprite.
+        let transform = frame.quad_transform();
+        assert_eq!(transform.transform_point3(Vec3::ZERO), Vec3::new(0.25, 0.25, 0.0));
+        assert_eq!(transform.transform_point3(Vec3::new(0.5, 0.5, 0.0)), Vec3::new(0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_flipped_frame()
+    {
+        let frame = SpriteFrame {
+            uv_window: Vec4::new(0.5, 0.0, 0.5, 1.0),
+            duration: Duration::ZERO,
+            rotated: false,
+            trim_offset: glam::uvec2(16, 0),
+            trim_size: glam::uvec2(16, 16),
+            source_size: glam::uvec2(32, 32),
+        };
 
-//     #[test]
-//     fn test_load_basic() {
-//         const RAW_JSON: &str = include_str!("./test_files/aseprite_basic.json");
+        // The top right quarter mirrors to the top left one, sampled right to left.
+        let flipped = frame.flipped(true, false);
+        assert_eq!(flipped.uv_window, Vec4::new(1.0, 0.0, -0.5, 1.0));
+        assert_eq!(flipped.trim_offset, glam::uvec2(0, 0));
+        assert_eq!(flipped.quad_transform().transform_point3(Vec3::ZERO), Vec3::new(-0.25, 0.25, 0.0));
+        assert_eq!(frame.flipped(false, false), frame);
 
-//         // et loaded_sprites = load_aseprite_sprites(RAW_JSON);
-//     }
-// }
+        let rotated = SpriteFrame { rotated: true, ..frame }.flipped(true, false);
+        assert_eq!(rotated.uv_window, Vec4::new(0.5, 1.0, 0.5, -1.0));
+    }
+
+    #[test]
+    fn test_sprite_placement()
+    {
+        let placement = SpritePlacement::at(Vec3::new(10.0, 20.0, 0.0))
+            .with_pivot(glam::vec2(0.5, 0.0))
+            .with_pixel_size(2.0)
+            .with_rotation(std::f32::consts::PI);
+        let transform = placement.transform(glam::uvec2(16, 32));
+
+        // The pivot lands on the position, and the sprite hangs down from it when upside down.
+        assert!(transform.transform_point3(Vec3::new(0.0, -0.5, 0.0)).abs_diff_eq(placement.position, 1e-4));
+        assert!(transform.transform_point3(Vec3::new(0.0, 0.5, 0.0)).abs_diff_eq(Vec3::new(10.0, -44.0, 0.0), 1e-4));
+
+        // Billboards stand on their pivot, facing a camera looking down -x.
+        let view = Mat4::look_at_rh(Vec3::new(50.0, 20.0, 0.0), Vec3::new(0.0, 20.0, 0.0), Vec3::Y);
+        let transform = SpritePlacement::at(Vec3::new(10.0, 20.0, 0.0))
+            .with_pivot(glam::vec2(0.5, 0.0))
+            .with_billboard(Billboard::Cylindrical, view)
+            .transform(glam::uvec2(16, 32));
+        assert!(transform.transform_point3(Vec3::new(0.0, -0.5, 0.0)).abs_diff_eq(Vec3::new(10.0, 20.0, 0.0), 1e-4));
+        assert!(transform.transform_vector3(Vec3::Z).normalize().abs_diff_eq(Vec3::X, 1e-4));
+    }
+
+    #[test]
+    fn test_random_phase()
+    {
+        const RAW_JSON: &str = include_str!("./test_files/aseprite_hash.json");
+
+        let loaded_sprites = load_aseprite_sprites(RAW_JSON, ResourceId::new(0)).unwrap();
+        let sprite = &loaded_sprites.sprites[&Some("walk".to_string())];
+        let phases: Vec<Duration> = (0..8).map(|seed| sprite.random_phase(seed)).collect();
+        assert!(phases.iter().all(|phase| *phase < sprite.duration()));
+        assert!(phases.iter().any(|phase| *phase != phases[0]));
+        assert_eq!(sprite.random_phase(3), phases[3]);
+
+        let mut player = AnimationPlayer::new().with_time_offset(Duration::from_millis(60));
+        assert_eq!(player.frame(sprite), 1);
+        player.advance(Duration::from_millis(150));
+        assert_eq!(player.frame(sprite), 2);
+        player.restart();
+        assert_eq!(player.frame(sprite), 1);
+    }
+
+    #[test]
+    fn test_invalid_json()
+    {
+        assert!(load_aseprite_sprites("{}", ResourceId::new(0)).is_err());
+        assert!(load_aseprite_sprites("not json", ResourceId::new(0)).is_err());
+    }
+}
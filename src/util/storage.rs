@@ -0,0 +1,652 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+#[cfg(target_arch = "wasm32")]
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::persistence::write_atomically;
+
+/// Future returned by [SaveBackend] operations.
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Callback deciding how to resolve a conflict, given the local and remote records.
+pub type ConflictCallback = Box<dyn Fn(&SaveRecord, &SaveRecord) -> Resolution + Send + Sync>;
+
+/// Slot used by [SaveSync] to remember what was last synced.
+const SYNC_STATE_SLOT: &str = "__sync_state";
+
+/// Information about a save slot without its contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveMetadata {
+    /// Name of the slot.
+    pub slot: String,
+    /// When the save was last written.
+    pub modified: SystemTime,
+    /// Size of the save in bytes.
+    pub size: u64,
+}
+
+/// A save slot along with its contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveRecord {
+    /// Name of the slot.
+    pub slot: String,
+    /// When the save was last written.
+    pub modified: SystemTime,
+    /// Contents of the save.
+    pub data: Vec<u8>,
+}
+
+/// Somewhere saves can be stored, such as the local disk or a cloud service.
+///
+/// Operations are asynchronous so backends talking to a server don't block the
+/// game loop. Backends must preserve the `modified` time of written records, since
+/// it is how [SaveSync] detects changes.
+pub trait SaveBackend: Send + Sync {
+    /// Lists the metadata of every slot in this backend.
+    fn list(&self) -> BackendFuture<'_, Vec<SaveMetadata>>;
+
+    /// Reads a slot, or returns `None` if it doesn't exist.
+    fn read<'a>(&'a self, slot: &'a str) -> BackendFuture<'a, Option<SaveRecord>>;
+
+    /// Writes a record, replacing the slot if it exists.
+    fn write<'a>(&'a self, record: &'a SaveRecord) -> BackendFuture<'a, ()>;
+
+    /// Deletes a slot if it exists.
+    fn delete<'a>(&'a self, slot: &'a str) -> BackendFuture<'a, ()>;
+}
+
+/// [SaveBackend] storing each slot as a file in a directory.
+pub struct LocalFileBackend {
+    directory: PathBuf,
+}
+
+/// [SaveBackend] that keeps saves in memory, useful for tests.
+#[derive(Default)]
+pub struct MemoryBackend {
+    records: Mutex<HashMap<String, SaveRecord>>,
+}
+
+/// Which side to keep when a slot changed both locally and remotely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    KeepLocal,
+    KeepRemote,
+    /// Replace both sides with merged contents.
+    Merged(Vec<u8>),
+}
+
+/// How [SaveSync] resolves conflicts.
+pub enum ConflictResolution {
+    /// Keep whichever side was modified most recently.
+    NewestWins,
+    /// Let the game decide, given the local and remote records.
+    Callback(ConflictCallback),
+}
+
+/// What happened to a slot during [SaveSync::sync].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncAction {
+    Uploaded,
+    Downloaded,
+    Unchanged,
+    /// Both sides changed and a [ConflictResolution] was applied.
+    Resolved(SyncDirection),
+    /// The slot was deleted on one side since the last sync, so it was deleted on
+    /// the other side too.
+    Deleted,
+}
+
+/// Direction data moved when a conflict was resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncDirection {
+    Uploaded,
+    Downloaded,
+    Merged,
+}
+
+/// What [SaveSync] remembers between syncs, stored in a local slot.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncState {
+    /// When each slot was last modified as of the last sync.
+    synced: HashMap<String, SystemTime>,
+    /// Slots deleted with [SaveSync::delete] and when, which are deleted remotely
+    /// on the next sync.
+    tombstones: HashMap<String, SystemTime>,
+}
+
+/// Keeps saves in a local and a remote [SaveBackend] in sync.
+pub struct SaveSync {
+    local: Box<dyn SaveBackend>,
+    remote: Box<dyn SaveBackend>,
+    conflict_resolution: ConflictResolution,
+}
+
+impl LocalFileBackend {
+    /// Creates a new [LocalFileBackend] storing saves in `directory`, which is
+    /// created if it doesn't exist.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self { directory })
+    }
+
+    fn path(&self, slot: &str) -> Result<PathBuf> {
+        validate_slot(slot)?;
+        Ok(self.directory.join(format!("{slot}.sav")))
+    }
+}
+
+impl SaveBackend for LocalFileBackend {
+    fn list(&self) -> BackendFuture<'_, Vec<SaveMetadata>> {
+        Box::pin(async move {
+            let mut saves = Vec::new();
+            for entry in std::fs::read_dir(&self.directory)? {
+                let path = entry?.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("sav") {
+                    continue;
+                }
+
+                let Some(slot) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                let metadata = std::fs::metadata(&path)?;
+                saves.push(SaveMetadata {
+                    slot: slot.to_string(),
+                    modified: metadata.modified()?,
+                    size: metadata.len(),
+                });
+            }
+            Ok(saves)
+        })
+    }
+
+    fn read<'a>(&'a self, slot: &'a str) -> BackendFuture<'a, Option<SaveRecord>> {
+        Box::pin(async move {
+            let path = self.path(slot)?;
+            if !path.exists() {
+                return Ok(None);
+            }
+
+            let data = std::fs::read(&path)?;
+            let modified = std::fs::metadata(&path)?.modified()?;
+            Ok(Some(SaveRecord {
+                slot: slot.to_string(),
+                modified,
+                data,
+            }))
+        })
+    }
+
+    fn write<'a>(&'a self, record: &'a SaveRecord) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let path = self.path(&record.slot)?;
+            write_atomically(&path, &record.data, Some(record.modified))?;
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, slot: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let path = self.path(slot)?;
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl MemoryBackend {
+    /// Creates a new empty [MemoryBackend].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SaveBackend for MemoryBackend {
+    fn list(&self) -> BackendFuture<'_, Vec<SaveMetadata>> {
+        Box::pin(async move {
+            Ok(self
+                .records
+                .lock()
+                .unwrap()
+                .values()
+                .map(|record| SaveMetadata {
+                    slot: record.slot.clone(),
+                    modified: record.modified,
+                    size: record.data.len() as u64,
+                })
+                .collect())
+        })
+    }
+
+    fn read<'a>(&'a self, slot: &'a str) -> BackendFuture<'a, Option<SaveRecord>> {
+        Box::pin(async move { Ok(self.records.lock().unwrap().get(slot).cloned()) })
+    }
+
+    fn write<'a>(&'a self, record: &'a SaveRecord) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            validate_slot(&record.slot)?;
+            self.records
+                .lock()
+                .unwrap()
+                .insert(record.slot.clone(), record.clone());
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, slot: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            self.records.lock().unwrap().remove(slot);
+            Ok(())
+        })
+    }
+}
+
+impl SaveSync {
+    /// Creates a new [SaveSync] between a local and a remote [SaveBackend].
+    pub fn new(
+        local: Box<dyn SaveBackend>,
+        remote: Box<dyn SaveBackend>,
+        conflict_resolution: ConflictResolution,
+    ) -> Self {
+        Self {
+            local,
+            remote,
+            conflict_resolution,
+        }
+    }
+
+    /// Gets the local [SaveBackend].
+    pub fn local(&self) -> &dyn SaveBackend {
+        self.local.as_ref()
+    }
+
+    /// Gets the remote [SaveBackend].
+    pub fn remote(&self) -> &dyn SaveBackend {
+        self.remote.as_ref()
+    }
+
+    /// Deletes a slot locally and remembers the deletion, so the next
+    /// [SaveSync::sync] deletes it remotely rather than downloading it again.
+    pub async fn delete(&self, slot: &str) -> Result<()> {
+        let mut state = self.read_sync_state().await?;
+        self.local.delete(slot).await?;
+        state.tombstones.insert(slot.to_string(), now());
+        self.write_sync_state(&state).await
+    }
+
+    /// Synchronizes every slot between the local and remote backends.
+    ///
+    /// A slot that only changed on one side since the last sync is copied to the
+    /// other side. A slot that changed on both sides is a conflict and is handled
+    /// by the [ConflictResolution].
+    ///
+    /// A slot deleted on one side is deleted on the other, unless it changed there
+    /// since the last sync, in which case it's copied back. Slots deleted with
+    /// [SaveSync::delete] are deleted remotely unless they changed after the delete.
+    pub async fn sync(&self) -> Result<Vec<(String, SyncAction)>> {
+        let local: HashMap<String, SaveMetadata> = self
+            .local
+            .list()
+            .await?
+            .into_iter()
+            .filter(|metadata| metadata.slot != SYNC_STATE_SLOT)
+            .map(|metadata| (metadata.slot.clone(), metadata))
+            .collect();
+        let remote: HashMap<String, SaveMetadata> = self
+            .remote
+            .list()
+            .await?
+            .into_iter()
+            .filter(|metadata| metadata.slot != SYNC_STATE_SLOT)
+            .map(|metadata| (metadata.slot.clone(), metadata))
+            .collect();
+        let SyncState {
+            mut synced,
+            tombstones,
+        } = self.read_sync_state().await?;
+
+        let slots: BTreeSet<&String> = local.keys().chain(remote.keys()).collect();
+        let mut actions = Vec::new();
+        for slot in slots {
+            let last = synced.get(slot).copied();
+            let action = match (local.get(slot), remote.get(slot)) {
+                (Some(local), None) if last == Some(local.modified) => {
+                    self.local.delete(slot).await?;
+                    SyncAction::Deleted
+                }
+                (Some(_), None) => self.upload(slot).await?,
+                (None, Some(remote))
+                    if last == Some(remote.modified)
+                        || tombstones
+                            .get(slot)
+                            .is_some_and(|deleted| *deleted >= remote.modified) =>
+                {
+                    self.remote.delete(slot).await?;
+                    SyncAction::Deleted
+                }
+                (None, Some(_)) => self.download(slot).await?,
+                (Some(local), Some(remote)) if local.modified == remote.modified => {
+                    SyncAction::Unchanged
+                }
+                (Some(local), Some(remote)) => match last {
+                    Some(last) if last == remote.modified => self.upload(slot).await?,
+                    Some(last) if last == local.modified => self.download(slot).await?,
+                    _ => self.resolve(slot).await?,
+                },
+                (None, None) => unreachable!("slot came from one of the listings"),
+            };
+
+            match self.local.read(slot).await? {
+                Some(record) => synced.insert(slot.clone(), record.modified),
+                None => synced.remove(slot),
+            };
+            actions.push((slot.clone(), action));
+        }
+
+        // Every tombstone was either pushed to the remote or overridden by a newer
+        // remote save, so none are needed anymore.
+        self.write_sync_state(&SyncState {
+            synced,
+            tombstones: HashMap::new(),
+        })
+        .await?;
+        Ok(actions)
+    }
+
+    async fn upload(&self, slot: &str) -> Result<SyncAction> {
+        copy(&*self.local, &*self.remote, slot).await?;
+        Ok(SyncAction::Uploaded)
+    }
+
+    async fn download(&self, slot: &str) -> Result<SyncAction> {
+        copy(&*self.remote, &*self.local, slot).await?;
+        Ok(SyncAction::Downloaded)
+    }
+
+    async fn resolve(&self, slot: &str) -> Result<SyncAction> {
+        let (Some(local), Some(remote)) =
+            (self.local.read(slot).await?, self.remote.read(slot).await?)
+        else {
+            bail!("save slot '{slot}' disappeared during sync");
+        };
+
+        let resolution = match &self.conflict_resolution {
+            ConflictResolution::NewestWins => match local.modified >= remote.modified {
+                true => Resolution::KeepLocal,
+                false => Resolution::KeepRemote,
+            },
+            ConflictResolution::Callback(callback) => callback(&local, &remote),
+        };
+
+        let direction = match resolution {
+            Resolution::KeepLocal => {
+                self.remote.write(&local).await?;
+                SyncDirection::Uploaded
+            }
+            Resolution::KeepRemote => {
+                self.local.write(&remote).await?;
+                SyncDirection::Downloaded
+            }
+            Resolution::Merged(data) => {
+                let merged = SaveRecord {
+                    slot: slot.to_string(),
+                    modified: now(),
+                    data,
+                };
+                self.local.write(&merged).await?;
+                self.remote.write(&merged).await?;
+                SyncDirection::Merged
+            }
+        };
+
+        Ok(SyncAction::Resolved(direction))
+    }
+
+    async fn read_sync_state(&self) -> Result<SyncState> {
+        Ok(match self.local.read(SYNC_STATE_SLOT).await? {
+            Some(record) => serde_json::from_slice(&record.data)?,
+            None => SyncState::default(),
+        })
+    }
+
+    async fn write_sync_state(&self, state: &SyncState) -> Result<()> {
+        self.local
+            .write(&SaveRecord {
+                slot: SYNC_STATE_SLOT.to_string(),
+                modified: now(),
+                data: serde_json::to_vec(state)?,
+            })
+            .await
+    }
+}
+
+/// Copies a slot from one [SaveBackend] to another.
+async fn copy(from: &dyn SaveBackend, to: &dyn SaveBackend, slot: &str) -> Result<()> {
+    let Some(record) = from.read(slot).await? else {
+        bail!("save slot '{slot}' disappeared during sync");
+    };
+    to.write(&record).await
+}
+
+/// Ensures a slot name is safe to use as a file name.
+pub(crate) fn validate_slot(slot: &str) -> Result<()> {
+    let valid = !slot.is_empty()
+        && slot
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    match valid {
+        true => Ok(()),
+        false => bail!("invalid save slot name '{slot}', use letters, digits, '-' or '_'"),
+    }
+}
+
+/// Gets the current time, which browsers only provide through JavaScript.
+fn now() -> SystemTime {
+    #[cfg(target_arch = "wasm32")]
+    return SystemTime::UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1000.0);
+    #[cfg(not(target_arch = "wasm32"))]
+    SystemTime::now()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pollster::block_on;
+    use std::{sync::Arc, time::Duration};
+
+    /// Lets a test keep a handle on a backend that was moved into a [SaveSync].
+    struct Shared(Arc<MemoryBackend>);
+
+    impl SaveBackend for Shared {
+        fn list(&self) -> BackendFuture<'_, Vec<SaveMetadata>> {
+            self.0.list()
+        }
+
+        fn read<'a>(&'a self, slot: &'a str) -> BackendFuture<'a, Option<SaveRecord>> {
+            self.0.read(slot)
+        }
+
+        fn write<'a>(&'a self, record: &'a SaveRecord) -> BackendFuture<'a, ()> {
+            self.0.write(record)
+        }
+
+        fn delete<'a>(&'a self, slot: &'a str) -> BackendFuture<'a, ()> {
+            self.0.delete(slot)
+        }
+    }
+
+    fn record(slot: &str, seconds: u64, data: &[u8]) -> SaveRecord {
+        SaveRecord {
+            slot: slot.to_string(),
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(seconds),
+            data: data.to_vec(),
+        }
+    }
+
+    fn sync_with(
+        conflict_resolution: ConflictResolution,
+    ) -> (SaveSync, Arc<MemoryBackend>, Arc<MemoryBackend>) {
+        let local = Arc::new(MemoryBackend::new());
+        let remote = Arc::new(MemoryBackend::new());
+        let sync = SaveSync::new(
+            Box::new(Shared(local.clone())),
+            Box::new(Shared(remote.clone())),
+            conflict_resolution,
+        );
+        (sync, local, remote)
+    }
+
+    #[test]
+    fn test_sync_copies_missing() {
+        let (sync, local, remote) = sync_with(ConflictResolution::NewestWins);
+        block_on(local.write(&record("slot1", 10, b"local"))).unwrap();
+        block_on(remote.write(&record("slot2", 10, b"remote"))).unwrap();
+
+        let actions = block_on(sync.sync()).unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                ("slot1".to_string(), SyncAction::Uploaded),
+                ("slot2".to_string(), SyncAction::Downloaded),
+            ]
+        );
+        assert_eq!(
+            block_on(remote.read("slot1")).unwrap().unwrap().data,
+            b"local"
+        );
+        assert_eq!(
+            block_on(local.read("slot2")).unwrap().unwrap().data,
+            b"remote"
+        );
+    }
+
+    #[test]
+    fn test_sync_one_sided_change_is_not_a_conflict() {
+        let (sync, local, remote) = sync_with(ConflictResolution::Callback(Box::new(|_, _| {
+            panic!("should not be a conflict")
+        })));
+        block_on(local.write(&record("slot1", 10, b"old"))).unwrap();
+        block_on(sync.sync()).unwrap();
+
+        // Only the remote changes, even though it's older than "now".
+        block_on(remote.write(&record("slot1", 5, b"new"))).unwrap();
+        let actions = block_on(sync.sync()).unwrap();
+
+        assert_eq!(actions, vec![("slot1".to_string(), SyncAction::Downloaded)]);
+        assert_eq!(block_on(local.read("slot1")).unwrap().unwrap().data, b"new");
+    }
+
+    #[test]
+    fn test_sync_conflict_newest_wins() {
+        let (sync, local, remote) = sync_with(ConflictResolution::NewestWins);
+        block_on(local.write(&record("slot1", 20, b"local"))).unwrap();
+        block_on(remote.write(&record("slot1", 10, b"remote"))).unwrap();
+
+        let actions = block_on(sync.sync()).unwrap();
+        assert_eq!(
+            actions,
+            vec![(
+                "slot1".to_string(),
+                SyncAction::Resolved(SyncDirection::Uploaded)
+            )]
+        );
+        assert_eq!(
+            block_on(remote.read("slot1")).unwrap().unwrap().data,
+            b"local"
+        );
+    }
+
+    #[test]
+    fn test_sync_conflict_callback_merge() {
+        let (sync, local, remote) =
+            sync_with(ConflictResolution::Callback(Box::new(|local, remote| {
+                Resolution::Merged([local.data.as_slice(), remote.data.as_slice()].concat())
+            })));
+        block_on(local.write(&record("slot1", 20, b"ab"))).unwrap();
+        block_on(remote.write(&record("slot1", 10, b"cd"))).unwrap();
+
+        block_on(sync.sync()).unwrap();
+        assert_eq!(
+            block_on(local.read("slot1")).unwrap().unwrap().data,
+            b"abcd"
+        );
+        assert_eq!(
+            block_on(remote.read("slot1")).unwrap().unwrap().data,
+            b"abcd"
+        );
+    }
+
+    #[test]
+    fn test_sync_local_delete_is_pushed() {
+        let (sync, local, remote) = sync_with(ConflictResolution::NewestWins);
+        block_on(local.write(&record("slot1", 10, b"data"))).unwrap();
+        block_on(local.write(&record("slot2", 10, b"data"))).unwrap();
+        block_on(sync.sync()).unwrap();
+
+        // One slot is deleted behind the sync's back, the other through it.
+        block_on(local.delete("slot1")).unwrap();
+        block_on(sync.delete("slot2")).unwrap();
+        let actions = block_on(sync.sync()).unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                ("slot1".to_string(), SyncAction::Deleted),
+                ("slot2".to_string(), SyncAction::Deleted),
+            ]
+        );
+        assert!(block_on(remote.read("slot1")).unwrap().is_none());
+        assert!(block_on(remote.read("slot2")).unwrap().is_none());
+
+        // The deletes stay deleted.
+        assert!(block_on(sync.sync()).unwrap().is_empty());
+        assert!(block_on(local.read("slot1")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sync_delete_loses_to_newer_remote_change() {
+        let (sync, local, remote) = sync_with(ConflictResolution::NewestWins);
+        block_on(sync.delete("slot1")).unwrap();
+        block_on(remote.write(&record("slot1", u32::MAX as u64, b"newer"))).unwrap();
+
+        let actions = block_on(sync.sync()).unwrap();
+        assert_eq!(actions, vec![("slot1".to_string(), SyncAction::Downloaded)]);
+        assert_eq!(
+            block_on(local.read("slot1")).unwrap().unwrap().data,
+            b"newer"
+        );
+    }
+
+    #[test]
+    fn test_local_file_backend_keeps_modified() {
+        let directory = std::env::temp_dir().join("clockwork_storage_local_file");
+        let _ = std::fs::remove_dir_all(&directory);
+        let backend = LocalFileBackend::new(&directory).unwrap();
+
+        block_on(backend.write(&record("slot1", 10, b"first"))).unwrap();
+        block_on(backend.write(&record("slot1", 20, b"second"))).unwrap();
+        assert_eq!(
+            block_on(backend.read("slot1")).unwrap(),
+            Some(record("slot1", 20, b"second"))
+        );
+        assert_eq!(block_on(backend.list()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_slot_name() {
+        let backend = MemoryBackend::new();
+        assert!(block_on(backend.write(&record("../escape", 0, b""))).is_err());
+    }
+}
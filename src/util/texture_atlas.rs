@@ -1,9 +1,7 @@
 use crate::graphics::texture::Texture;
-use serde::Deserialize;
-use serde_json::Value;
 use std::{cell::Cell, collections::HashMap};
 
-use super::repository::ResourceId;
+use super::{aseprite::parse_aseprite_sheet, repository::ResourceId, sprite::Sprite};
 
 /// Id for accessing a sprite from a [TextureAtlas].
 #[derive(Debug, Clone, Copy)]
@@ -21,16 +19,6 @@ enum LazySpriteIdState {
     Cached(SpriteId),
 }
 
-/// Contains information on how to render a specific animation
-/// from a larger texture with multiple animations bundled together.
-#[derive(Debug, Clone, Copy)]
-pub struct Sprite {
-    pub texture: ResourceId<Texture>,
-    pub uv_topleft: [f32; 2],
-    pub uv_dims: [f32; 2],
-    pub frames: usize,
-}
-
 /// Mapping from [SpriteId] to [Sprite].
 #[derive(Debug, Default)]
 pub struct TextureAtlas {
@@ -38,25 +26,6 @@ pub struct TextureAtlas {
     sprites: Vec<Sprite>,
 }
 
-// ####################################
-// For deserializing the Aseprite file
-// ####################################
-#[derive(Deserialize)]
-struct Tag {
-    name: Option<String>,
-    from: usize,
-    to: usize,
-}
-
-#[derive(Deserialize)]
-struct Frame {
-    x: usize,
-    y: usize,
-    w: usize,
-    h: usize,
-}
-// ####################################
-
 impl LazySpriteId {
     /// Constructs a new [LazySpriteId].
     pub fn new(image: &'static str, tag: Option<&'static str>) -> Self {
@@ -73,80 +42,21 @@ impl TextureAtlas {
         }
     }
 
-    /// Adds sprites from an aseprite generated json document.
+    /// Adds sprites from an aseprite generated json document, one for each tag or a
+    /// single untagged sprite if the document has no tags.
     ///
-    /// Note this currently assumes two things:
-    /// - Animations are marked by tags
-    /// - Animations are oriented in row fasion
+    /// Both the array and hash formats for frames are supported, along with per-frame
+    /// durations and trimmed or rotated frames.
     pub fn add_aseprite_sprites(
         &mut self,
         aseprite_json_context: &str,
         texture: ResourceId<Texture>,
-    ) {
-        let aseprite_json: Value = serde_json::from_str(aseprite_json_context).unwrap();
-        let meta = aseprite_json.get("meta").unwrap();
-
-        let image = meta.get("image").unwrap().as_str().unwrap();
-        let dimensions = meta
-            .get("size")
-            .map(|value| {
-                (
-                    value.get("w").unwrap().as_u64().unwrap() as f64,
-                    value.get("h").unwrap().as_u64().unwrap() as f64,
-                )
-            })
-            .unwrap();
-
-        let frames: Vec<Frame> = aseprite_json
-            .get("frames")
-            .unwrap()
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|value| {
-                serde_json::from_value::<Frame>(value.get("frame").unwrap().clone()).unwrap()
-            })
-            .collect();
-
-        let tag_array = meta.get("frameTags").unwrap().as_array().unwrap();
-
-        let tags: Vec<Tag> = match tag_array.is_empty() {
-            true => vec![Tag {
-                name: None,
-                from: 0,
-                to: frames.len() - 1,
-            }],
-            false => tag_array
-                .iter()
-                .map(|value| serde_json::from_value::<Tag>(value.clone()).unwrap())
-                .collect(),
-        };
-
-        let sprites_and_tags = tags.iter().map(|tag| {
-            let first_frame = &frames[tag.from];
-            let uv_topleft = [
-                ((first_frame.x as f64) / dimensions.0) as f32,
-                ((first_frame.y as f64) / dimensions.1) as f32,
-            ];
-            let uv_dims = [
-                ((first_frame.w as f64) / dimensions.0) as f32,
-                ((first_frame.h as f64) / dimensions.1) as f32,
-            ];
-            let frames = tag.to + 1 - tag.from;
-
-            let sprite = Sprite {
-                texture,
-                uv_topleft,
-                uv_dims,
-                frames,
-            };
-
-            (tag.name.clone(), sprite)
-        });
-
-        sprites_and_tags.for_each(|(tag, sprite)| {
-            self.add_sprite(sprite, image, tag.as_deref());
-        });
+    ) -> anyhow::Result<()> {
+        let sheet = parse_aseprite_sheet(aseprite_json_context, texture)?;
+        for (tag, sprite) in sheet.sprites {
+            self.add_sprite(sprite, &sheet.image, tag.as_deref());
+        }
+        Ok(())
     }
 
     /// Gets a [Sprite] from this [TextureAtlas] given the [SpriteId].
@@ -189,13 +99,3 @@ impl TextureAtlas {
         SpriteId(index)
     }
 }
-
-impl Sprite {
-    /// Gets the appropriate uv coordinate window within the texture to render the
-    /// corresponding frame for this sprite.
-    pub fn get_uv_window(&self, frame: usize) -> [f32; 4] {
-        let left = self.uv_topleft[0] + self.uv_dims[0] * ((frame % self.frames) as f32);
-
-        [left, self.uv_topleft[1], self.uv_dims[0], self.uv_dims[1]]
-    }
-}
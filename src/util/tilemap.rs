@@ -0,0 +1,447 @@
+use std::time::Duration;
+
+use glam::{IVec2, Mat4, UVec2, Vec2, Vec3, Vec4};
+
+use crate::graphics::{Mesh, RenderOperation, TextureArrayInstance};
+
+use super::{
+    camera::Camera,
+    collision::Aabb,
+    coordinates::{self, ScreenPosition},
+    repository::ResourceId,
+    shadow_frustum::{frustum_corners, FRUSTUM_EDGES},
+    sprite::{scramble, Sprite},
+    texture_atlas::{SpriteId, TextureAtlas},
+};
+
+/// Id of a kind of tile defined with [Tilemap::define_tile].
+pub type TileId = u32;
+
+/// Describes a kind of tile.
+#[derive(Debug, Clone, Copy)]
+pub struct TileDefinition {
+    /// Sprite the tile is drawn with.
+    pub sprite: SpriteId,
+    /// Whether the tile blocks movement.
+    pub solid: bool,
+}
+
+/// Grid of tiles laid out on the xy plane, with tile `(0, 0)` in the bottom left and
+/// rows going up along [super::coordinates::WORLD_UP].
+#[derive(Debug, Clone)]
+pub struct Tilemap {
+    size: UVec2,
+    /// Size of each tile in world units.
+    pub tile_size: Vec2,
+    /// World position of the bottom left corner of the map. The z coordinate is the
+    /// depth the map is drawn at.
+    pub origin: Vec3,
+    /// Seed animated tiles pick a random starting point in their animation from, so a
+    /// field of the same tile doesn't animate in lockstep, or `None` to play them all in
+    /// sync. See [crate::util::sprite::Sprite::random_phase].
+    pub animation_seed: Option<u64>,
+    tiles: Vec<Option<TileId>>,
+    definitions: Vec<TileDefinition>,
+}
+
+impl Tilemap {
+    /// Creates a new empty [Tilemap] with the given number of tiles across and up.
+    pub fn new(size: UVec2, tile_size: Vec2, origin: Vec3) -> Self {
+        Self {
+            size,
+            tile_size,
+            origin,
+            animation_seed: None,
+            tiles: vec![None; (size.x * size.y) as usize],
+            definitions: Vec::new(),
+        }
+    }
+
+    /// Sets the seed animated tiles pick a random starting point in their animation
+    /// from, see [Tilemap::animation_seed].
+    pub fn with_animation_seed(mut self, seed: u64) -> Self {
+        self.animation_seed = Some(seed);
+        self
+    }
+
+    /// Gets how far into its animation the tile at a coordinate is, after the map has
+    /// been animating for `elapsed`.
+    fn tile_elapsed(&self, sprite: &Sprite, coordinate: UVec2, elapsed: Duration) -> Duration {
+        match self.animation_seed {
+            Some(seed) => {
+                let tile = (u64::from(coordinate.y) << 32) | u64::from(coordinate.x);
+                elapsed + sprite.random_phase(scramble(seed) ^ tile)
+            }
+            None => elapsed,
+        }
+    }
+
+    /// Defines a kind of tile and returns the [TileId] to place it with.
+    pub fn define_tile(&mut self, definition: TileDefinition) -> TileId {
+        self.definitions.push(definition);
+        (self.definitions.len() - 1) as TileId
+    }
+
+    /// Gets the definition of a kind of tile.
+    pub fn definition(&self, tile_id: TileId) -> Option<&TileDefinition> {
+        self.definitions.get(tile_id as usize)
+    }
+
+    /// Gets the number of tiles across and up.
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// Gets the tile at the given coordinate, if there is one.
+    pub fn get(&self, coordinate: UVec2) -> Option<TileId> {
+        *self.tiles.get(self.index(coordinate)?)?
+    }
+
+    /// Places a tile at the given coordinate, or clears it with `None`.
+    ///
+    /// Coordinates outside of the map are ignored.
+    pub fn set(&mut self, coordinate: UVec2, tile: Option<TileId>) {
+        if let Some(index) = self.index(coordinate) {
+            self.tiles[index] = tile;
+        }
+    }
+
+    /// Iterates over every placed tile and its coordinate.
+    pub fn iter(&self) -> impl Iterator<Item = (UVec2, TileId)> + '_ {
+        self.tiles.iter().enumerate().filter_map(|(index, tile)| {
+            let coordinate = UVec2::new(index as u32 % self.size.x, index as u32 / self.size.x);
+            tile.map(|tile| (coordinate, tile))
+        })
+    }
+
+    /// Gets the coordinate of the tile containing a world position, if it's on the map.
+    pub fn coordinate_at(&self, position: Vec2) -> Option<UVec2> {
+        let coordinate = self.unclamped_coordinate_at(position);
+        (coordinate.cmpge(IVec2::ZERO).all() && coordinate.cmplt(self.size.as_ivec2()).all())
+            .then(|| coordinate.as_uvec2())
+    }
+
+    /// Gets the coordinate of the tile under a screen position, such as the cursor,
+    /// through a camera with the given view projection matrix.
+    pub fn coordinate_at_screen(
+        &self,
+        view_projection: Mat4,
+        position: ScreenPosition,
+        screen_size: Vec2,
+    ) -> Option<UVec2> {
+        let (origin, direction) =
+            coordinates::screen_to_world_ray(view_projection, position, screen_size);
+        let distance = (self.origin.z - origin.z) / direction.z;
+        if !distance.is_finite() || distance < 0.0 {
+            return None;
+        }
+        self.coordinate_at((origin + direction * distance).truncate())
+    }
+
+    /// Gets the coordinate of the tile under a screen position, such as the cursor, as
+    /// seen through a perspective or orthographic [Camera].
+    pub fn coordinate_under(
+        &self,
+        camera: &Camera,
+        position: ScreenPosition,
+        screen_size: Vec2,
+    ) -> Option<UVec2> {
+        self.coordinate_at_screen(camera.get_view_projection_matrix(), position, screen_size)
+    }
+
+    /// Gets the bounding box of a tile in world units.
+    pub fn tile_aabb(&self, coordinate: UVec2) -> Aabb {
+        let min = self.origin.truncate() + coordinate.as_vec2() * self.tile_size;
+        Aabb::new(min, min + self.tile_size)
+    }
+
+    /// Iterates over the coordinates of solid tiles overlapping a box.
+    pub fn solid_tiles_overlapping(&self, aabb: &Aabb) -> impl Iterator<Item = UVec2> + '_ {
+        let aabb = *aabb;
+        self.coordinates_within(aabb.min, aabb.max)
+            .filter(move |&coordinate| {
+                self.get(coordinate)
+                    .and_then(|tile| self.definition(tile))
+                    .is_some_and(|definition| definition.solid)
+                    && self.tile_aabb(coordinate).overlaps(&aabb)
+            })
+    }
+
+    /// Checks if a box overlaps any solid tile.
+    pub fn check_collision(&self, aabb: &Aabb) -> bool {
+        self.solid_tiles_overlapping(aabb).next().is_some()
+    }
+
+    /// Gets the range of tile coordinates, inclusive, that could be visible through a
+    /// camera with the given view projection matrix, or `None` if the map is off screen.
+    pub fn visible_range(&self, view_projection: Mat4) -> Option<(UVec2, UVec2)> {
+        // Find where the edges of the view frustum cross the map's plane.
+        let corners = frustum_corners(view_projection);
+        let mut min = Vec2::splat(f32::INFINITY);
+        let mut max = Vec2::splat(f32::NEG_INFINITY);
+        for (start, end) in FRUSTUM_EDGES {
+            let (start, end) = (corners[start], corners[end]);
+            let start_depth = start.z - self.origin.z;
+            let end_depth = end.z - self.origin.z;
+
+            let crossing = if start_depth == 0.0 && end_depth == 0.0 {
+                min = min.min(start.truncate().min(end.truncate()));
+                max = max.max(start.truncate().max(end.truncate()));
+                continue;
+            } else if start_depth.signum() != end_depth.signum() || start_depth == 0.0 {
+                start.lerp(end, start_depth / (start_depth - end_depth))
+            } else {
+                continue;
+            };
+
+            min = min.min(crossing.truncate());
+            max = max.max(crossing.truncate());
+        }
+
+        if !min.cmple(max).all() {
+            return None;
+        }
+
+        let min = self.unclamped_coordinate_at(min).max(IVec2::ZERO);
+        let max = self
+            .unclamped_coordinate_at(max)
+            .min(self.size.as_ivec2() - IVec2::ONE);
+        min.cmple(max)
+            .all()
+            .then(|| (min.as_uvec2(), max.as_uvec2()))
+    }
+
+    /// Creates render operations for the tiles visible through a camera with the given
+    /// view projection matrix, skipping everything off screen.
+    ///
+    /// Tiles are drawn with `quad_mesh_id`, which should be a unit quad such as
+    /// [crate::graphics::default_meshes::QUAD_MESH_DATA], and animated tiles show the frame
+    /// for `elapsed`, offset per tile if [Tilemap::animation_seed] is set.
+    pub fn render_operations<'a>(
+        &'a self,
+        atlas: &'a TextureAtlas,
+        quad_mesh_id: ResourceId<Mesh>,
+        view_projection: Mat4,
+        elapsed: Duration,
+    ) -> impl Iterator<Item = RenderOperation> + 'a {
+        self.visible_tiles(view_projection)
+            .filter_map(move |(coordinate, tile_id)| {
+                let definition = self.definition(tile_id)?;
+                let sprite = atlas.get_sprite(definition.sprite);
+                let frame = sprite.frame_at(self.tile_elapsed(sprite, coordinate, elapsed));
+
+                Some(RenderOperation::textured_mesh(
+                    self.tile_transform(coordinate),
+                    quad_mesh_id,
+                    sprite.texture,
+                    Some(sprite.get_uv_window(frame)),
+                    Vec4::ONE,
+                ))
+            })
+    }
+
+    /// Creates instances for the tiles visible through a camera with the given view
+    /// projection matrix, to draw them all at once with
+    /// [crate::graphics::RenderContext::render_texture_array].
+    ///
+    /// Each tile shows the layer matching its [TileId], so the array's layers should be
+    /// loaded in the order the tiles were defined.
+    pub fn texture_array_instances(
+        &self,
+        view_projection: Mat4,
+    ) -> impl Iterator<Item = TextureArrayInstance> + '_ {
+        self.visible_tiles(view_projection)
+            .map(|(coordinate, tile_id)| {
+                TextureArrayInstance::new(self.tile_transform(coordinate), tile_id)
+            })
+    }
+
+    /// Iterates over the tiles visible through a camera with the given view projection
+    /// matrix.
+    fn visible_tiles(&self, view_projection: Mat4) -> impl Iterator<Item = (UVec2, TileId)> + '_ {
+        self.visible_range(view_projection)
+            .into_iter()
+            .flat_map(|(min, max)| {
+                (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| UVec2::new(x, y)))
+            })
+            .filter_map(|coordinate| Some((coordinate, self.get(coordinate)?)))
+    }
+
+    /// Gets the transformation of a unit quad to cover the tile at a coordinate.
+    fn tile_transform(&self, coordinate: UVec2) -> Mat4 {
+        let center = self.tile_aabb(coordinate).center();
+        Mat4::from_translation(center.extend(self.origin.z))
+            * Mat4::from_scale(self.tile_size.extend(1.0))
+    }
+
+    fn index(&self, coordinate: UVec2) -> Option<usize> {
+        coordinate
+            .cmplt(self.size)
+            .all()
+            .then(|| (coordinate.y * self.size.x + coordinate.x) as usize)
+    }
+
+    fn unclamped_coordinate_at(&self, position: Vec2) -> IVec2 {
+        ((position - self.origin.truncate()) / self.tile_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    /// Iterates over the coordinates on the map between two world positions.
+    fn coordinates_within(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = UVec2> {
+        let min = self.unclamped_coordinate_at(min).max(IVec2::ZERO);
+        let max = self
+            .unclamped_coordinate_at(max)
+            .min(self.size.as_ivec2() - IVec2::ONE);
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| UVec2::new(x as u32, y as u32)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Affine3A;
+
+    use super::*;
+    use crate::util::camera::Projection;
+
+    fn tilemap() -> Tilemap {
+        let mut atlas = TextureAtlas::new();
+        let sprite = atlas.add_sprite(
+            crate::util::sprite::Sprite {
+                texture: ResourceId::new(0),
+                sprite_dims: UVec2::ONE,
+                frames: Vec::new(),
+            },
+            "tiles.png",
+            None,
+        );
+
+        let mut tilemap = Tilemap::new(UVec2::new(10, 10), Vec2::splat(2.0), Vec3::ZERO);
+        let wall = tilemap.define_tile(TileDefinition {
+            sprite,
+            solid: true,
+        });
+        let grass = tilemap.define_tile(TileDefinition {
+            sprite,
+            solid: false,
+        });
+        tilemap.set(UVec2::new(1, 0), Some(wall));
+        tilemap.set(UVec2::new(2, 0), Some(grass));
+        tilemap
+    }
+
+    #[test]
+    fn test_coordinates() {
+        let tilemap = tilemap();
+        assert_eq!(
+            tilemap.coordinate_at(Vec2::new(3.5, 1.0)),
+            Some(UVec2::new(1, 0))
+        );
+        assert_eq!(tilemap.coordinate_at(Vec2::new(-0.5, 1.0)), None);
+        assert_eq!(tilemap.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_check_collision() {
+        let tilemap = tilemap();
+        assert!(tilemap.check_collision(&Aabb::from_center_size(Vec2::new(2.0, 1.0), Vec2::ONE)));
+        // Touching the wall's edge or standing on grass isn't a collision.
+        assert!(!tilemap.check_collision(&Aabb::new(Vec2::new(0.0, 0.0), Vec2::new(2.0, 2.0))));
+        assert!(!tilemap.check_collision(&Aabb::from_center_size(Vec2::new(5.0, 1.0), Vec2::ONE)));
+    }
+
+    #[test]
+    fn test_visible_range() {
+        let tilemap = tilemap();
+        let view_projection = Mat4::orthographic_rh(1.0, 7.0, -3.0, 3.0, -10.0, 10.0);
+        assert_eq!(
+            tilemap.visible_range(view_projection),
+            Some((UVec2::new(0, 0), UVec2::new(3, 1)))
+        );
+
+        let off_screen = Mat4::orthographic_rh(-7.0, -1.0, -3.0, 3.0, -10.0, 10.0);
+        assert_eq!(tilemap.visible_range(off_screen), None);
+    }
+
+    #[test]
+    fn test_texture_array_instances() {
+        let tilemap = tilemap();
+        let view_projection = Mat4::orthographic_rh(1.0, 7.0, -3.0, 3.0, -10.0, 10.0);
+        let instances: Vec<TextureArrayInstance> =
+            tilemap.texture_array_instances(view_projection).collect();
+        assert_eq!(instances.len(), 2);
+        // The wall was defined first, so it shows the first layer.
+        assert_eq!(instances[0].layer, 0);
+        assert_eq!(instances[1].layer, 1);
+        assert_eq!(
+            instances[1]
+                .transform
+                .transform_point3(Vec3::new(0.5, 0.5, 0.0)),
+            Vec3::new(6.0, 2.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_coordinate_at_screen() {
+        let tilemap = tilemap();
+        let view_projection = Mat4::orthographic_rh(0.0, 8.0, 0.0, 6.0, -10.0, 10.0);
+        let screen_size = Vec2::new(800.0, 600.0);
+
+        // The bottom of the screen is the bottom row of the map.
+        assert_eq!(
+            tilemap.coordinate_at_screen(
+                view_projection,
+                ScreenPosition(Vec2::new(350.0, 550.0)),
+                screen_size
+            ),
+            Some(UVec2::new(1, 0))
+        );
+        assert_eq!(
+            tilemap.coordinate_at_screen(
+                view_projection,
+                ScreenPosition(Vec2::new(350.0, 50.0)),
+                screen_size
+            ),
+            Some(UVec2::new(1, 2))
+        );
+    }
+
+    #[test]
+    fn test_coordinate_under() {
+        let tilemap = tilemap();
+        let screen_size = Vec2::new(800.0, 600.0);
+
+        let perspective = Camera::new(
+            Affine3A::from_translation(Vec3::new(5.0, 5.0, 10.0)),
+            Projection::Perspective {
+                aspect: 800.0 / 600.0,
+                fov: 1.0,
+                znear: 0.1,
+                zfar: 100.0,
+            },
+        );
+        assert_eq!(
+            tilemap.coordinate_under(&perspective, ScreenPosition(screen_size / 2.0), screen_size),
+            Some(UVec2::new(2, 2))
+        );
+
+        let orthographic = Camera::new(
+            Affine3A::from_translation(Vec3::new(4.0, 3.0, 5.0)),
+            Projection::Orthographic {
+                aspect: 800.0 / 600.0,
+                height: 6.0,
+                znear: 0.1,
+                zfar: 100.0,
+            },
+        );
+        assert_eq!(
+            tilemap.coordinate_under(
+                &orthographic,
+                ScreenPosition(Vec2::new(350.0, 550.0)),
+                screen_size
+            ),
+            Some(UVec2::new(1, 0))
+        );
+    }
+}
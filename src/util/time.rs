@@ -0,0 +1,131 @@
+/// Game time the [crate::Engine] advances each frame, which can be slowed down, sped
+/// up or paused independently of real time. Get it with [crate::Engine::time].
+///
+/// [crate::Application::update] and [crate::Application::fixed_update] run on game
+/// time, so slow motion also slows fixed updates, and pausing stops them. Menus that
+/// keep animating while paused can use [GameClock::unscaled_delta].
+#[derive(Clone, Copy, Debug)]
+pub struct GameClock {
+    /// Seconds of game time since the engine started.
+    elapsed: f64,
+    /// Seconds of real time since the engine started.
+    unscaled_elapsed: f64,
+    /// Seconds of game time the latest frame advanced.
+    delta: f64,
+    /// Seconds of real time the latest frame took.
+    unscaled_delta: f64,
+    time_scale: f64,
+    paused: bool,
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        Self {
+            elapsed: 0.0,
+            unscaled_elapsed: 0.0,
+            delta: 0.0,
+            unscaled_delta: 0.0,
+            time_scale: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl GameClock {
+    /// Creates a [GameClock] at zero running at normal speed.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Gets the seconds of game time since the engine started, which stops while
+    /// paused.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Gets the seconds of game time the current frame advanced, which is zero while
+    /// paused.
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    /// Gets the seconds of real time since the engine started.
+    pub fn unscaled_elapsed(&self) -> f64 {
+        self.unscaled_elapsed
+    }
+
+    /// Gets the seconds of real time the current frame took, regardless of the time
+    /// scale or pausing.
+    pub fn unscaled_delta(&self) -> f64 {
+        self.unscaled_delta
+    }
+
+    /// Gets how fast game time passes compared to real time.
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Sets how fast game time passes compared to real time, such as 0.25 for slow
+    /// motion. Negative scales are treated as zero.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    /// Checks if game time is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses game time from the next frame, which also freezes how long ago inputs
+    /// were pressed for [crate::input::InputState::check_pressed_within].
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes game time from the next frame.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Pauses or resumes game time from the next frame.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Advances the clock by a frame that took `unscaled_delta` seconds of real time.
+    pub(crate) fn advance(&mut self, unscaled_delta: f64) {
+        self.unscaled_delta = unscaled_delta;
+        self.unscaled_elapsed += unscaled_delta;
+        self.delta = match self.paused {
+            true => 0.0,
+            false => unscaled_delta * self.time_scale,
+        };
+        self.elapsed += self.delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_and_pause() {
+        let mut clock = GameClock::new();
+        clock.advance(0.5);
+        assert_eq!((clock.elapsed(), clock.delta()), (0.5, 0.5));
+
+        clock.set_time_scale(0.25);
+        clock.advance(1.0);
+        assert_eq!((clock.elapsed(), clock.delta()), (0.75, 0.25));
+
+        clock.pause();
+        clock.advance(2.0);
+        assert_eq!((clock.elapsed(), clock.delta()), (0.75, 0.0));
+        assert_eq!(clock.unscaled_delta(), 2.0);
+        assert_eq!(clock.unscaled_elapsed(), 3.5);
+
+        clock.resume();
+        clock.set_time_scale(-1.0);
+        assert_eq!(clock.time_scale(), 0.0);
+    }
+}
@@ -0,0 +1,463 @@
+use std::f32::consts::PI;
+
+use glam::{Quat, Vec2, Vec3, Vec4};
+
+/// Values that can be blended between, such as for a [Tween].
+pub trait Lerp: Clone {
+    /// Blends from `self` at `t` = 0 to `other` at `t` = 1. Easings like
+    /// [Easing::BackOut] can overshoot past either end.
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for f64 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t as f64
+    }
+}
+
+impl Lerp for Vec2 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vec2::lerp(*self, *other, t)
+    }
+}
+
+impl Lerp for Vec3 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vec3::lerp(*self, *other, t)
+    }
+}
+
+/// Colors are blended per channel.
+impl Lerp for Vec4 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vec4::lerp(*self, *other, t)
+    }
+}
+
+/// Rotations are blended along the shortest arc between them.
+impl Lerp for Quat {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self.slerp(*other, t)
+    }
+}
+
+/// Vertex colors are blended per channel, clamped to the channel's range.
+impl Lerp for [u8; 4] {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        std::array::from_fn(|channel| {
+            (self[channel] as f32)
+                .lerp(&(other[channel] as f32), t)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        })
+    }
+}
+
+/// Curve a [Tween] follows from start to end, see <https://easings.net> for how each
+/// looks.
+///
+/// `In` curves start slowly, `Out` curves end slowly, and `InOut` curves do both.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    /// Pulls back before starting.
+    BackIn,
+    /// Overshoots the end and settles back.
+    BackOut,
+    BackInOut,
+    /// Springs away from the start.
+    ElasticIn,
+    /// Springs around the end before settling.
+    ElasticOut,
+    ElasticInOut,
+    /// Bounces off the start.
+    BounceIn,
+    /// Bounces off the end like a dropped ball.
+    BounceOut,
+    BounceInOut,
+}
+
+impl Easing {
+    /// Maps linear progress from 0 to 1 onto the curve. Every curve starts at 0 and
+    /// ends at 1.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => out(Easing::QuadIn, t),
+            Easing::QuadInOut => in_out(Easing::QuadIn, t),
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => out(Easing::CubicIn, t),
+            Easing::CubicInOut => in_out(Easing::CubicIn, t),
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => out(Easing::SineIn, t),
+            Easing::SineInOut => in_out(Easing::SineIn, t),
+            Easing::ExpoIn => match t == 0.0 {
+                true => 0.0,
+                false => 2f32.powf(10.0 * t - 10.0),
+            },
+            Easing::ExpoOut => out(Easing::ExpoIn, t),
+            Easing::ExpoInOut => in_out(Easing::ExpoIn, t),
+            Easing::BackIn => {
+                const OVERSHOOT: f32 = 1.70158;
+                t * t * ((OVERSHOOT + 1.0) * t - OVERSHOOT)
+            }
+            Easing::BackOut => out(Easing::BackIn, t),
+            Easing::BackInOut => in_out(Easing::BackIn, t),
+            Easing::ElasticIn => match t == 0.0 || t == 1.0 {
+                true => t,
+                false => {
+                    -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * (2.0 * PI / 3.0)).sin()
+                }
+            },
+            Easing::ElasticOut => out(Easing::ElasticIn, t),
+            Easing::ElasticInOut => in_out(Easing::ElasticIn, t),
+            Easing::BounceIn => out(Easing::BounceOut, t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => in_out(Easing::BounceIn, t),
+        }
+    }
+}
+
+/// Flips an `In` curve so it ends slowly instead.
+fn out(easing: Easing, t: f32) -> f32 {
+    1.0 - easing.apply(1.0 - t)
+}
+
+/// Runs an `In` curve over the first half and its `Out` curve over the second.
+fn in_out(easing: Easing, t: f32) -> f32 {
+    match t < 0.5 {
+        true => easing.apply(t * 2.0) / 2.0,
+        false => 1.0 - easing.apply((1.0 - t) * 2.0) / 2.0,
+    }
+}
+
+/// Bounces off the end with shrinking bounces.
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// Something that changes over time, such as a [Tween], advanced each frame by the
+/// seconds passed.
+pub trait Animation {
+    /// Value the animation produces.
+    type Output;
+
+    /// Advances the animation by `delta` seconds, and returns the seconds left over
+    /// if it finished partway through.
+    fn advance(&mut self, delta: f64) -> f64;
+
+    /// Gets the current value.
+    fn value(&self) -> Self::Output;
+
+    /// Gets the seconds the animation lasts.
+    fn duration(&self) -> f64;
+
+    /// Checks if the animation reached its end.
+    fn is_finished(&self) -> bool;
+
+    /// Restarts the animation from the beginning.
+    fn reset(&mut self);
+
+    /// Advances the animation by `delta` seconds and gets its value, such as with the
+    /// `delta` given to [crate::Application::update].
+    fn update(&mut self, delta: f64) -> Self::Output {
+        self.advance(delta);
+        self.value()
+    }
+
+    /// Plays the animation and another one at the same time, producing both values.
+    fn with<B: Animation>(self, other: B) -> Parallel<Self, B>
+    where
+        Self: Sized,
+    {
+        Parallel(self, other)
+    }
+}
+
+/// Blends a value from a start to an end over time along an [Easing] curve.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tween<T: Lerp> {
+    pub from: T,
+    pub to: T,
+    /// Seconds from start to end.
+    pub duration: f64,
+    pub easing: Easing,
+    /// Seconds since the start.
+    elapsed: f64,
+}
+
+impl<T: Lerp> Tween<T> {
+    /// Creates a linear [Tween] from one value to another over `duration` seconds.
+    pub fn new(from: T, to: T, duration: f64) -> Self {
+        Self {
+            from,
+            to,
+            duration: duration.max(0.0),
+            easing: Easing::Linear,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Creates a [Tween] holding a value for `duration` seconds, such as for a pause
+    /// within a [Sequence].
+    pub fn hold(value: T, duration: f64) -> Self {
+        Self::new(value.clone(), value, duration)
+    }
+
+    /// Sets the curve the [Tween] follows.
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Gets how far the [Tween] is from start to end, from 0 to 1, before easing.
+    pub fn progress(&self) -> f64 {
+        match self.duration > 0.0 {
+            true => (self.elapsed / self.duration).min(1.0),
+            false => 1.0,
+        }
+    }
+
+    /// Jumps to a point from 0 to 1 between start and end.
+    pub fn set_progress(&mut self, progress: f64) {
+        self.elapsed = progress.clamp(0.0, 1.0) * self.duration;
+    }
+}
+
+impl<T: Lerp> Animation for Tween<T> {
+    type Output = T;
+
+    fn advance(&mut self, delta: f64) -> f64 {
+        let elapsed = self.elapsed + delta.max(0.0);
+        self.elapsed = elapsed.min(self.duration);
+        elapsed - self.elapsed
+    }
+
+    fn value(&self) -> T {
+        self.from
+            .lerp(&self.to, self.easing.apply(self.progress() as f32))
+    }
+
+    fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+/// Animations of the same kind played one after another, such as a camera moving
+/// through several points.
+///
+/// Time left over when one finishes carries into the next, so a sequence stays in
+/// step however long frames are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sequence<A: Animation> {
+    animations: Vec<A>,
+    /// Index of the animation playing.
+    current: usize,
+}
+
+impl<A: Animation> Sequence<A> {
+    /// Creates a [Sequence] starting with an animation.
+    pub fn new(first: A) -> Self {
+        Self {
+            animations: vec![first],
+            current: 0,
+        }
+    }
+
+    /// Adds an animation to play after the others.
+    pub fn then(mut self, next: A) -> Self {
+        self.animations.push(next);
+        self
+    }
+
+    /// Gets the index of the animation playing.
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+}
+
+impl<A: Animation> Animation for Sequence<A> {
+    type Output = A::Output;
+
+    fn advance(&mut self, delta: f64) -> f64 {
+        let mut delta = self.animations[self.current].advance(delta);
+        while self.animations[self.current].is_finished()
+            && self.current + 1 < self.animations.len()
+        {
+            self.current += 1;
+            delta = self.animations[self.current].advance(delta);
+        }
+        delta
+    }
+
+    fn value(&self) -> A::Output {
+        self.animations[self.current].value()
+    }
+
+    fn duration(&self) -> f64 {
+        self.animations.iter().map(Animation::duration).sum()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.current + 1 == self.animations.len() && self.animations[self.current].is_finished()
+    }
+
+    fn reset(&mut self) {
+        self.animations.iter_mut().for_each(Animation::reset);
+        self.current = 0;
+    }
+}
+
+/// Two animations played at the same time, producing both of their values, made with
+/// [Animation::with]. Nest them to play more at once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parallel<A: Animation, B: Animation>(pub A, pub B);
+
+impl<A: Animation, B: Animation> Animation for Parallel<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn advance(&mut self, delta: f64) -> f64 {
+        self.0.advance(delta).min(self.1.advance(delta))
+    }
+
+    fn value(&self) -> Self::Output {
+        (self.0.value(), self.1.value())
+    }
+
+    fn duration(&self) -> f64 {
+        self.0.duration().max(self.1.duration())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.0.is_finished() && self.1.is_finished()
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+        self.1.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 22] = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineIn,
+        Easing::SineOut,
+        Easing::SineInOut,
+        Easing::ExpoIn,
+        Easing::ExpoOut,
+        Easing::ExpoInOut,
+        Easing::BackIn,
+        Easing::BackOut,
+        Easing::BackInOut,
+        Easing::ElasticIn,
+        Easing::ElasticOut,
+        Easing::ElasticInOut,
+        Easing::BounceIn,
+        Easing::BounceOut,
+        Easing::BounceInOut,
+    ];
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in EASINGS {
+            assert!(easing.apply(0.0).abs() < 1e-3, "{easing:?} starts at 0");
+            assert!(
+                (easing.apply(1.0) - 1.0).abs() < 1e-3,
+                "{easing:?} ends at 1"
+            );
+        }
+        for easing in [Easing::QuadInOut, Easing::CubicInOut, Easing::SineInOut] {
+            assert!((easing.apply(0.5) - 0.5).abs() < 1e-6);
+        }
+        assert!(Easing::QuadIn.apply(0.25) < 0.25 && Easing::QuadOut.apply(0.25) > 0.25);
+        assert!(Easing::BackOut.apply(0.7) > 1.0);
+    }
+
+    #[test]
+    fn test_tween() {
+        let mut tween = Tween::new(Vec2::ZERO, Vec2::new(10.0, 20.0), 2.0);
+        assert_eq!(tween.update(0.5), Vec2::new(2.5, 5.0));
+        assert_eq!(tween.advance(2.0), 0.5);
+        assert!(tween.is_finished());
+        assert_eq!(tween.value(), Vec2::new(10.0, 20.0));
+
+        tween.reset();
+        assert_eq!(tween.value(), Vec2::ZERO);
+        assert_eq!(
+            [0u8, 100, 255, 255].lerp(&[255, 200, 0, 255], 0.5),
+            [128, 150, 128, 255]
+        );
+    }
+
+    #[test]
+    fn test_sequence_and_parallel() {
+        let mut sequence = Sequence::new(Tween::new(0.0f32, 1.0, 1.0))
+            .then(Tween::hold(1.0, 1.0))
+            .then(Tween::new(1.0, 0.0, 2.0));
+        assert_eq!(sequence.duration(), 4.0);
+
+        // Time past the end of one animation carries into the next.
+        assert_eq!(sequence.update(2.5), 0.75);
+        assert_eq!(sequence.current_index(), 2);
+        assert_eq!(sequence.advance(2.0), 0.5);
+        assert!(sequence.is_finished());
+
+        let mut parallel = Tween::new(0.0f32, 1.0, 1.0).with(Tween::new(Vec3::ZERO, Vec3::X, 2.0));
+        assert_eq!(parallel.update(1.0), (1.0, Vec3::new(0.5, 0.0, 0.0)));
+        assert!(!parallel.is_finished());
+        assert_eq!(parallel.advance(1.5), 0.5);
+        assert!(parallel.is_finished());
+    }
+}
@@ -0,0 +1,947 @@
+use glam::{vec2, Mat4, UVec2, Vec2, Vec4};
+
+use crate::{
+    graphics::{texture::Texture, Mesh, RenderOperation},
+    input::{InputState, Mouse},
+};
+
+use super::repository::ResourceId;
+
+/// Point of a container a child is positioned relative to, see
+/// [Container::anchored].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// Where children of a stack line up across it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    /// Left of vertical stacks, top of horizontal ones.
+    #[default]
+    Start,
+    Center,
+    /// Right of vertical stacks, bottom of horizontal ones.
+    End,
+}
+
+/// How a [Container] positions its children.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// Each child at its own [Anchor] within the container.
+    Anchored,
+    /// Children top to bottom, with space between them.
+    Vertical { spacing: f32, align: Align },
+    /// Children left to right, with space between them.
+    Horizontal { spacing: f32, align: Align },
+}
+
+/// Rectangle in virtual screen units, with the origin at the bottom left of the
+/// screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    /// Bottom left corner.
+    pub min: Vec2,
+    pub size: Vec2,
+}
+
+/// Texture of fixed size glyphs in a grid, in character order from the top left, such
+/// as an ASCII sheet starting at `' '`.
+#[derive(Clone, Copy, Debug)]
+pub struct BitmapFont {
+    pub texture: ResourceId<Texture>,
+    /// Size of the texture in pixels.
+    pub texture_size: UVec2,
+    /// Size of each glyph in pixels.
+    pub glyph_size: UVec2,
+    /// Character of the first glyph.
+    pub first_char: char,
+}
+
+/// Picture, such as an icon or a frame of a [super::sprite::Sprite].
+#[derive(Clone, Copy, Debug)]
+pub struct Image {
+    pub texture: ResourceId<Texture>,
+    /// Section of the texture shown, or `None` for all of it.
+    pub uv_window: Option<Vec4>,
+    pub size: Vec2,
+    /// Color the image is multiplied by.
+    pub color: Vec4,
+}
+
+/// Text drawn with the [Ui]'s [BitmapFont].
+#[derive(Clone, Debug)]
+pub struct Text {
+    /// Text shown, where `\n` starts a new line.
+    pub text: String,
+    /// Height of a line in virtual units.
+    pub font_size: f32,
+    pub color: Vec4,
+}
+
+/// Clickable box with a label, see [Ui::clicked].
+#[derive(Clone, Debug)]
+pub struct Button {
+    pub label: String,
+    pub size: Vec2,
+    /// Texture drawn behind the label, or `None` for a solid color.
+    pub texture: Option<ResourceId<Texture>>,
+    /// Color while the pointer isn't over the button.
+    pub color: Vec4,
+    /// Color while the pointer is over the button.
+    pub hovered_color: Vec4,
+    /// Color while the button is held down.
+    pub pressed_color: Vec4,
+    pub label_color: Vec4,
+    /// Height of the label in virtual units.
+    pub font_size: f32,
+}
+
+/// Box holding other widgets, see [Layout].
+#[derive(Clone, Debug)]
+pub struct Container {
+    pub layout: Layout,
+    pub children: Vec<Child>,
+    /// Space between the edges of the container and its children.
+    pub padding: f32,
+    /// Color filling the container, if any.
+    pub background: Option<Vec4>,
+    /// Size of the container, or `None` to fit its children.
+    pub size: Option<Vec2>,
+}
+
+/// Element of a [Ui].
+#[derive(Clone, Debug)]
+pub enum Widget {
+    Image(Image),
+    Text(Text),
+    Button(Button),
+    Container(Container),
+}
+
+/// Widget within a [Container], along with where it goes in anchored containers and
+/// the id it's found by.
+#[derive(Clone, Debug)]
+pub struct Child {
+    pub widget: Widget,
+    /// Name to find the widget by with [Ui::widget_mut], and to check buttons with
+    /// [Ui::clicked].
+    pub id: Option<String>,
+    /// Point of an anchored container the child is positioned relative to.
+    pub anchor: Anchor,
+    /// Offset in virtual units from the anchor, with y up.
+    pub offset: Vec2,
+}
+
+/// Retained user interface for menus and HUDs, laid out in virtual screen units and
+/// drawn with a quad mesh.
+///
+/// Each frame:
+/// 1. [Ui::update] lays the widgets out for the window and routes the mouse to them.
+/// 2. [Ui::clicked] checks which buttons were clicked.
+/// 3. [Ui::render_operations] draws them, rendered with [Ui::view_projection].
+///
+/// The virtual screen is [Ui::resolution] in size, widened or heightened to match the
+/// window's aspect ratio, so anchored widgets stay at the edges of any window.
+pub struct Ui {
+    root: Container,
+    resolution: Vec2,
+    font: Option<BitmapFont>,
+    /// Size of the virtual screen as of the latest update.
+    virtual_size: Vec2,
+    /// Widgets as of the latest update, in drawing order.
+    laid_out: Vec<LaidOut>,
+    hovered: Option<String>,
+    pressed: Option<String>,
+    clicked: Option<String>,
+    /// Whether the pointer was over any widget as of the latest update.
+    pointer_over: bool,
+}
+
+/// Something drawn by a [Ui] at a position.
+#[derive(Clone, Debug)]
+struct LaidOut {
+    rect: Rect,
+    visual: Visual,
+}
+
+#[derive(Clone, Debug)]
+enum Visual {
+    Fill(Vec4),
+    Image {
+        texture: ResourceId<Texture>,
+        uv_window: Option<Vec4>,
+        color: Vec4,
+    },
+    Button {
+        id: Option<String>,
+        texture: Option<ResourceId<Texture>>,
+        colors: [Vec4; 3],
+    },
+}
+
+impl Anchor {
+    /// Gets where the anchor is from the bottom left (0, 0) to the top right (1, 1).
+    fn factor(self) -> Vec2 {
+        match self {
+            Anchor::TopLeft => vec2(0.0, 1.0),
+            Anchor::Top => vec2(0.5, 1.0),
+            Anchor::TopRight => vec2(1.0, 1.0),
+            Anchor::Left => vec2(0.0, 0.5),
+            Anchor::Center => vec2(0.5, 0.5),
+            Anchor::Right => vec2(1.0, 0.5),
+            Anchor::BottomLeft => vec2(0.0, 0.0),
+            Anchor::Bottom => vec2(0.5, 0.0),
+            Anchor::BottomRight => vec2(1.0, 0.0),
+        }
+    }
+}
+
+impl Align {
+    /// Gets how far along the free space a child goes, where 0 is the left or bottom.
+    fn factor(self, vertical: bool) -> f32 {
+        match (self, vertical) {
+            (Align::Center, _) => 0.5,
+            (Align::Start, true) | (Align::End, false) => 0.0,
+            (Align::End, true) | (Align::Start, false) => 1.0,
+        }
+    }
+}
+
+impl Rect {
+    /// Creates a [Rect] from its bottom left corner and size.
+    pub fn new(min: Vec2, size: Vec2) -> Self {
+        Self { min, size }
+    }
+
+    /// Gets the top right corner.
+    pub fn max(&self) -> Vec2 {
+        self.min + self.size
+    }
+
+    /// Gets the center.
+    pub fn center(&self) -> Vec2 {
+        self.min + self.size * 0.5
+    }
+
+    /// Checks if a point is within the rectangle.
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmplt(self.max()).all()
+    }
+}
+
+impl BitmapFont {
+    /// Creates a [BitmapFont] from a texture of glyphs in a grid.
+    pub fn new(
+        texture: ResourceId<Texture>,
+        texture_size: UVec2,
+        glyph_size: UVec2,
+        first_char: char,
+    ) -> Self {
+        Self {
+            texture,
+            texture_size,
+            glyph_size: glyph_size.max(UVec2::ONE),
+            first_char,
+        }
+    }
+
+    /// Gets the section of the texture holding a character as left, top, width and
+    /// height uvs, or `None` if the font doesn't have it.
+    pub fn glyph_uv_window(&self, character: char) -> Option<Vec4> {
+        let grid = self.texture_size / self.glyph_size;
+        let index = (character as u32).checked_sub(self.first_char as u32)?;
+        if grid.x == 0 || index >= grid.x * grid.y {
+            return None;
+        }
+
+        let glyph_size = self.glyph_size.as_vec2() / self.texture_size.as_vec2();
+        let cell = UVec2::new(index % grid.x, index / grid.x).as_vec2();
+        Some(
+            (cell * glyph_size)
+                .extend(glyph_size.x)
+                .extend(glyph_size.y),
+        )
+    }
+
+    /// Gets the width of a glyph when a line is `font_size` tall.
+    pub fn advance(&self, font_size: f32) -> f32 {
+        font_size * self.glyph_size.x as f32 / self.glyph_size.y as f32
+    }
+
+    /// Gets the size of text when a line is `font_size` tall.
+    pub fn measure(&self, text: &str, font_size: f32) -> Vec2 {
+        let columns = text.lines().map(|line| line.chars().count()).max();
+        let lines = text.lines().count().max(1);
+        vec2(
+            columns.unwrap_or(0) as f32 * self.advance(font_size),
+            lines as f32 * font_size,
+        )
+    }
+}
+
+impl Image {
+    /// Creates an [Image] showing all of a texture.
+    pub fn new(texture: ResourceId<Texture>, size: Vec2) -> Self {
+        Self {
+            texture,
+            uv_window: None,
+            size,
+            color: Vec4::ONE,
+        }
+    }
+
+    /// Sets the section of the texture shown, such as a frame of a sprite.
+    pub fn with_uv_window(mut self, uv_window: Vec4) -> Self {
+        self.uv_window = Some(uv_window);
+        self
+    }
+
+    /// Sets the color the image is multiplied by.
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Text {
+    /// Creates white [Text] with lines `font_size` tall.
+    pub fn new<S: Into<String>>(text: S, font_size: f32) -> Self {
+        Self {
+            text: text.into(),
+            font_size,
+            color: Vec4::ONE,
+        }
+    }
+
+    /// Sets the color of the text.
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+impl Button {
+    /// Creates a gray [Button] with a white label.
+    pub fn new<S: Into<String>>(label: S, size: Vec2) -> Self {
+        Self {
+            label: label.into(),
+            size,
+            texture: None,
+            color: Vec4::new(0.25, 0.25, 0.25, 1.0),
+            hovered_color: Vec4::new(0.35, 0.35, 0.35, 1.0),
+            pressed_color: Vec4::new(0.15, 0.15, 0.15, 1.0),
+            label_color: Vec4::ONE,
+            font_size: size.y * 0.5,
+        }
+    }
+
+    /// Sets the texture drawn behind the label, which is multiplied by the colors.
+    pub fn with_texture(mut self, texture: ResourceId<Texture>) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    /// Sets the colors of the button normally, while hovered, and while pressed.
+    pub fn with_colors(mut self, color: Vec4, hovered_color: Vec4, pressed_color: Vec4) -> Self {
+        self.color = color;
+        self.hovered_color = hovered_color;
+        self.pressed_color = pressed_color;
+        self
+    }
+
+    /// Sets the color and height of the label.
+    pub fn with_label_style(mut self, label_color: Vec4, font_size: f32) -> Self {
+        self.label_color = label_color;
+        self.font_size = font_size;
+        self
+    }
+}
+
+impl Container {
+    /// Creates a [Container] positioning each child at its own [Anchor].
+    pub fn anchored() -> Self {
+        Self::new(Layout::Anchored)
+    }
+
+    /// Creates a [Container] stacking children top to bottom.
+    pub fn vertical(spacing: f32) -> Self {
+        Self::new(Layout::Vertical {
+            spacing,
+            align: Align::Start,
+        })
+    }
+
+    /// Creates a [Container] stacking children left to right.
+    pub fn horizontal(spacing: f32) -> Self {
+        Self::new(Layout::Horizontal {
+            spacing,
+            align: Align::Start,
+        })
+    }
+
+    fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            children: Vec::new(),
+            padding: 0.0,
+            background: None,
+            size: None,
+        }
+    }
+
+    /// Sets where the children of a stack line up across it.
+    pub fn with_align(mut self, align: Align) -> Self {
+        match &mut self.layout {
+            Layout::Vertical { align: current, .. } | Layout::Horizontal { align: current, .. } => {
+                *current = align
+            }
+            Layout::Anchored => (),
+        }
+        self
+    }
+
+    /// Sets the space between the edges of the container and its children.
+    pub fn with_padding(mut self, padding: f32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets the color filling the container.
+    pub fn with_background(mut self, background: Vec4) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    /// Sets the size of the container instead of fitting its children.
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Adds a child, such as a [Widget] or a [Child] with an id.
+    pub fn with_child<C: Into<Child>>(mut self, child: C) -> Self {
+        self.children.push(child.into());
+        self
+    }
+
+    /// Gets the size the container takes up with the given font.
+    fn measure(&self, font: Option<&BitmapFont>) -> Vec2 {
+        if let Some(size) = self.size {
+            return size;
+        }
+
+        let sizes = self.children.iter().map(|child| child.widget.measure(font));
+        let content = match self.layout {
+            Layout::Anchored => sizes.fold(Vec2::ZERO, Vec2::max),
+            Layout::Vertical { spacing, .. } => {
+                let (size, count) = sizes.fold((Vec2::ZERO, 0), |(total, count), size| {
+                    (vec2(total.x.max(size.x), total.y + size.y), count + 1)
+                });
+                size + vec2(0.0, spacing * (count as f32 - 1.0).max(0.0))
+            }
+            Layout::Horizontal { spacing, .. } => {
+                let (size, count) = sizes.fold((Vec2::ZERO, 0), |(total, count), size| {
+                    (vec2(total.x + size.x, total.y.max(size.y)), count + 1)
+                });
+                size + vec2(spacing * (count as f32 - 1.0).max(0.0), 0.0)
+            }
+        };
+        content + Vec2::splat(self.padding * 2.0)
+    }
+
+    /// Lays the container and its children out within `rect`.
+    fn arrange(&self, rect: Rect, font: Option<&BitmapFont>, out: &mut Vec<LaidOut>) {
+        if let Some(background) = self.background {
+            out.push(LaidOut {
+                rect,
+                visual: Visual::Fill(background),
+            });
+        }
+
+        let inner = Rect::new(
+            rect.min + self.padding,
+            (rect.size - self.padding * 2.0).max(Vec2::ZERO),
+        );
+        match self.layout {
+            Layout::Anchored => {
+                for child in &self.children {
+                    let size = child.widget.measure(font);
+                    let min =
+                        inner.min + (inner.size - size) * child.anchor.factor() + child.offset;
+                    child.arrange(Rect::new(min, size), font, out);
+                }
+            }
+            Layout::Vertical { spacing, align } => {
+                let mut top = inner.max().y;
+                for child in &self.children {
+                    let size = child.widget.measure(font);
+                    let x = inner.min.x + (inner.size.x - size.x) * align.factor(true);
+                    top -= size.y;
+                    child.arrange(Rect::new(vec2(x, top), size), font, out);
+                    top -= spacing;
+                }
+            }
+            Layout::Horizontal { spacing, align } => {
+                let mut left = inner.min.x;
+                for child in &self.children {
+                    let size = child.widget.measure(font);
+                    let y = inner.min.y + (inner.size.y - size.y) * align.factor(false);
+                    child.arrange(Rect::new(vec2(left, y), size), font, out);
+                    left += size.x + spacing;
+                }
+            }
+        }
+    }
+
+    /// Finds a descendant by id.
+    fn find_mut(&mut self, id: &str) -> Option<&mut Widget> {
+        self.children.iter_mut().find_map(|child| {
+            match (child.id.as_deref() == Some(id), &mut child.widget) {
+                (true, widget) => Some(widget),
+                (false, Widget::Container(container)) => container.find_mut(id),
+                (false, _) => None,
+            }
+        })
+    }
+}
+
+impl Widget {
+    /// Gets the size the widget takes up with the given font.
+    fn measure(&self, font: Option<&BitmapFont>) -> Vec2 {
+        match self {
+            Widget::Image(image) => image.size,
+            Widget::Text(text) => {
+                font.map_or(Vec2::ZERO, |font| font.measure(&text.text, text.font_size))
+            }
+            Widget::Button(button) => button.size,
+            Widget::Container(container) => container.measure(font),
+        }
+    }
+}
+
+impl Child {
+    /// Creates a [Child] without an id, centered in anchored containers.
+    pub fn new<W: Into<Widget>>(widget: W) -> Self {
+        Self {
+            widget: widget.into(),
+            id: None,
+            anchor: Anchor::Center,
+            offset: Vec2::ZERO,
+        }
+    }
+
+    /// Sets the name to find the widget by.
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets where the child goes in anchored containers, offset in virtual units with
+    /// y up.
+    pub fn with_anchor(mut self, anchor: Anchor, offset: Vec2) -> Self {
+        self.anchor = anchor;
+        self.offset = offset;
+        self
+    }
+
+    fn arrange(&self, rect: Rect, font: Option<&BitmapFont>, out: &mut Vec<LaidOut>) {
+        match &self.widget {
+            Widget::Image(image) => out.push(LaidOut {
+                rect,
+                visual: Visual::Image {
+                    texture: image.texture,
+                    uv_window: image.uv_window,
+                    color: image.color,
+                },
+            }),
+            Widget::Text(text) => {
+                if let Some(font) = font {
+                    arrange_text(font, &text.text, text.font_size, text.color, rect, out);
+                }
+            }
+            Widget::Button(button) => {
+                out.push(LaidOut {
+                    rect,
+                    visual: Visual::Button {
+                        id: self.id.clone(),
+                        texture: button.texture,
+                        colors: [button.color, button.hovered_color, button.pressed_color],
+                    },
+                });
+                if let Some(font) = font {
+                    let size = font.measure(&button.label, button.font_size);
+                    let label = Rect::new(rect.center() - size * 0.5, size);
+                    arrange_text(
+                        font,
+                        &button.label,
+                        button.font_size,
+                        button.label_color,
+                        label,
+                        out,
+                    );
+                }
+            }
+            Widget::Container(container) => container.arrange(rect, font, out),
+        }
+    }
+}
+
+/// Lays out a quad for each glyph of some text, from the top left of `rect`.
+fn arrange_text(
+    font: &BitmapFont,
+    text: &str,
+    font_size: f32,
+    color: Vec4,
+    rect: Rect,
+    out: &mut Vec<LaidOut>,
+) {
+    let advance = font.advance(font_size);
+    for (row, line) in text.lines().enumerate() {
+        let y = rect.max().y - (row + 1) as f32 * font_size;
+        for (column, character) in line.chars().enumerate() {
+            if let Some(uv_window) = font.glyph_uv_window(character) {
+                out.push(LaidOut {
+                    rect: Rect::new(
+                        vec2(rect.min.x + column as f32 * advance, y),
+                        vec2(advance, font_size),
+                    ),
+                    visual: Visual::Image {
+                        texture: font.texture,
+                        uv_window: Some(uv_window),
+                        color,
+                    },
+                });
+            }
+        }
+    }
+}
+
+impl From<Image> for Widget {
+    fn from(image: Image) -> Self {
+        Widget::Image(image)
+    }
+}
+
+impl From<Text> for Widget {
+    fn from(text: Text) -> Self {
+        Widget::Text(text)
+    }
+}
+
+impl From<Button> for Widget {
+    fn from(button: Button) -> Self {
+        Widget::Button(button)
+    }
+}
+
+impl From<Container> for Widget {
+    fn from(container: Container) -> Self {
+        Widget::Container(container)
+    }
+}
+
+impl<W: Into<Widget>> From<W> for Child {
+    fn from(widget: W) -> Self {
+        Child::new(widget)
+    }
+}
+
+impl Ui {
+    /// Creates an empty [Ui] with a virtual screen of at least `resolution` units.
+    pub fn new(resolution: Vec2) -> Self {
+        let resolution = resolution.max(Vec2::ONE);
+        Self {
+            root: Container::anchored(),
+            resolution,
+            font: None,
+            virtual_size: resolution,
+            laid_out: Vec::new(),
+            hovered: None,
+            pressed: None,
+            clicked: None,
+            pointer_over: false,
+        }
+    }
+
+    /// Sets the font text is drawn with. Text isn't drawn without one.
+    pub fn with_font(mut self, font: BitmapFont) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    /// Sets the container filling the screen that every widget is within.
+    pub fn with_root(mut self, root: Container) -> Self {
+        self.root = root;
+        self
+    }
+
+    /// Gets the container filling the screen, such as to add widgets to it.
+    pub fn root_mut(&mut self) -> &mut Container {
+        &mut self.root
+    }
+
+    /// Finds a widget by the id of its [Child], such as to change the text of a score.
+    /// Changes show from the next [Ui::update].
+    pub fn widget_mut(&mut self, id: &str) -> Option<&mut Widget> {
+        self.root.find_mut(id)
+    }
+
+    /// Gets the size of the virtual screen as of the latest update.
+    pub fn virtual_size(&self) -> Vec2 {
+        self.virtual_size
+    }
+
+    /// Lays the widgets out for the window's size, and routes the mouse to them so
+    /// buttons are hovered, pressed and clicked.
+    pub fn update(&mut self, input_state: &InputState, window_size: Vec2) {
+        let window_size = window_size.max(Vec2::ONE);
+        let scale = (window_size / self.resolution).min_element();
+        self.virtual_size = window_size / scale;
+
+        let font = self.font;
+        self.laid_out.clear();
+        let mut root = self.root.clone();
+        root.size = Some(self.virtual_size);
+        root.arrange(
+            Rect::new(Vec2::ZERO, self.virtual_size),
+            font.as_ref(),
+            &mut self.laid_out,
+        );
+
+        // The mouse is in window pixels from the top left.
+        let pointer = input_state
+            .mouse_position()
+            .map(|position| vec2(position.x, window_size.y - position.y) / scale);
+        self.route_pointer(
+            pointer,
+            input_state.just_pressed(Mouse::Left),
+            input_state.just_released(Mouse::Left),
+        );
+    }
+
+    fn route_pointer(&mut self, pointer: Option<Vec2>, pressed: bool, released: bool) {
+        let over =
+            |laid_out: &&LaidOut| pointer.is_some_and(|pointer| laid_out.rect.contains(pointer));
+        self.pointer_over = self.laid_out.iter().any(|laid_out| over(&laid_out));
+        self.hovered = self
+            .laid_out
+            .iter()
+            .rev()
+            .filter(over)
+            .find_map(|laid_out| match &laid_out.visual {
+                Visual::Button { id, .. } => Some(id.clone().unwrap_or_default()),
+                _ => None,
+            });
+
+        self.clicked = None;
+        if pressed {
+            self.pressed = self.hovered.clone();
+        }
+        if released {
+            if let Some(pressed) = self.pressed.take() {
+                if self.hovered.as_ref() == Some(&pressed) {
+                    self.clicked = Some(pressed);
+                }
+            }
+        }
+    }
+
+    /// Checks if the button with an id was clicked during the latest update.
+    pub fn clicked(&self, id: &str) -> bool {
+        self.clicked.as_deref() == Some(id)
+    }
+
+    /// Gets the id of the button under the pointer, which is empty for buttons without
+    /// one.
+    pub fn hovered(&self) -> Option<&str> {
+        self.hovered.as_deref()
+    }
+
+    /// Checks if the pointer is over any widget, such as to keep clicks on menus from
+    /// also reaching the game.
+    pub fn is_pointer_over(&self) -> bool {
+        self.pointer_over
+    }
+
+    /// Gets the projection the operations from [Ui::render_operations] are meant to be
+    /// rendered with.
+    pub fn view_projection(&self) -> Mat4 {
+        Mat4::orthographic_rh(
+            0.0,
+            self.virtual_size.x,
+            0.0,
+            self.virtual_size.y,
+            -1.0,
+            1.0,
+        )
+    }
+
+    /// Creates transparent [RenderOperation]s that draw the widgets as of the latest
+    /// update with a quad mesh, each on a higher sort layer than the last.
+    pub fn render_operations(&self, quad_mesh_id: ResourceId<Mesh>) -> Vec<RenderOperation> {
+        self.laid_out
+            .iter()
+            .enumerate()
+            .map(|(index, laid_out)| {
+                let transform = Mat4::from_scale_rotation_translation(
+                    laid_out.rect.size.extend(1.0),
+                    Default::default(),
+                    laid_out.rect.center().extend(0.0),
+                );
+                let (texture, uv_window, color) = match &laid_out.visual {
+                    Visual::Fill(color) => (None, None, *color),
+                    Visual::Image {
+                        texture,
+                        uv_window,
+                        color,
+                    } => (Some(*texture), *uv_window, *color),
+                    Visual::Button {
+                        id,
+                        texture,
+                        colors,
+                    } => {
+                        let id = id.clone().unwrap_or_default();
+                        let state = match (self.pressed.as_ref(), self.hovered.as_ref()) {
+                            (Some(pressed), _) if *pressed == id => 2,
+                            (_, Some(hovered)) if *hovered == id => 1,
+                            _ => 0,
+                        };
+                        (*texture, None, colors[state])
+                    }
+                };
+
+                // Colors are premultiplied.
+                let color = (color.truncate() * color.w).extend(color.w);
+                match texture {
+                    Some(texture) => RenderOperation::textured_mesh(
+                        transform,
+                        quad_mesh_id,
+                        texture,
+                        uv_window,
+                        color,
+                    ),
+                    None => RenderOperation::colored_mesh(transform, quad_mesh_id, color),
+                }
+                .with_transparent(true)
+                .with_sort_layer(index as i32)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu() -> Ui {
+        let font = BitmapFont::new(
+            ResourceId::new(0),
+            UVec2::new(128, 64),
+            UVec2::new(8, 16),
+            ' ',
+        );
+        Ui::new(vec2(800.0, 600.0)).with_font(font).with_root(
+            Container::anchored()
+                .with_child(
+                    Child::new(
+                        Container::vertical(10.0)
+                            .with_padding(5.0)
+                            .with_align(Align::Center)
+                            .with_child(
+                                Child::new(Button::new("Play", vec2(200.0, 50.0))).with_id("play"),
+                            )
+                            .with_child(
+                                Child::new(Button::new("Quit", vec2(100.0, 50.0))).with_id("quit"),
+                            ),
+                    )
+                    .with_id("menu"),
+                )
+                .with_child(
+                    Child::new(Text::new("0", 20.0))
+                        .with_id("score")
+                        .with_anchor(Anchor::TopRight, vec2(-10.0, -10.0)),
+                ),
+        )
+    }
+
+    #[test]
+    fn test_layout() {
+        let font = BitmapFont::new(
+            ResourceId::new(0),
+            UVec2::new(128, 64),
+            UVec2::new(8, 16),
+            ' ',
+        );
+        assert_eq!(font.measure("ab\nc", 20.0), vec2(20.0, 40.0));
+        assert_eq!(
+            font.glyph_uv_window('!'),
+            Some(Vec4::new(1.0 / 16.0, 0.0, 1.0 / 16.0, 0.25))
+        );
+        assert_eq!(font.glyph_uv_window('\u{7f}'), None);
+
+        let mut ui = menu();
+        ui.update(&InputState::new(), vec2(1600.0, 1200.0));
+        assert_eq!(ui.virtual_size(), vec2(800.0, 600.0));
+
+        // A wider window widens the virtual screen.
+        ui.update(&InputState::new(), vec2(1200.0, 600.0));
+        assert_eq!(ui.virtual_size(), vec2(1200.0, 600.0));
+
+        let button_rects: Vec<Rect> = ui
+            .laid_out
+            .iter()
+            .filter(|laid_out| matches!(laid_out.visual, Visual::Button { .. }))
+            .map(|laid_out| laid_out.rect)
+            .collect();
+        // The 210x120 menu is centered, with its buttons centered within it.
+        assert_eq!(
+            button_rects[0],
+            Rect::new(vec2(500.0, 305.0), vec2(200.0, 50.0))
+        );
+        assert_eq!(
+            button_rects[1],
+            Rect::new(vec2(550.0, 245.0), vec2(100.0, 50.0))
+        );
+
+        // The score is in the top right corner.
+        let glyph = ui.laid_out.last().unwrap().rect;
+        assert_eq!(glyph, Rect::new(vec2(1180.0, 570.0), vec2(10.0, 20.0)));
+        assert_eq!(
+            ui.render_operations(ResourceId::new(0)).len(),
+            ui.laid_out.len()
+        );
+    }
+
+    #[test]
+    fn test_clicks() {
+        let mut ui = menu();
+        ui.update(&InputState::new(), vec2(800.0, 600.0));
+
+        ui.route_pointer(Some(vec2(400.0, 330.0)), true, false);
+        assert_eq!(ui.hovered(), Some("play"));
+        assert!(!ui.clicked("play"));
+        ui.route_pointer(Some(vec2(400.0, 330.0)), false, true);
+        assert!(ui.clicked("play") && !ui.clicked("quit"));
+
+        // Releasing somewhere else cancels the click.
+        ui.route_pointer(Some(vec2(400.0, 270.0)), true, false);
+        ui.route_pointer(Some(vec2(10.0, 10.0)), false, true);
+        assert!(!ui.clicked("quit") && !ui.is_pointer_over());
+
+        match ui.widget_mut("score") {
+            Some(Widget::Text(text)) => text.text = "100".to_string(),
+            _ => panic!("score should be text"),
+        }
+        assert!(ui.widget_mut("missing").is_none());
+    }
+}
@@ -0,0 +1,46 @@
+use wasm_bindgen::JsCast;
+use web_sys::HtmlCanvasElement;
+use winit::platform::web::WindowExtWebSys;
+
+use crate::{config::WindowConfig, error::ClockworkError};
+
+/// Finds a canvas element on the page by its id.
+pub(crate) fn find_canvas(id: &str) -> Option<HtmlCanvasElement> {
+    web_sys::window()?
+        .document()?
+        .get_element_by_id(id)?
+        .dyn_into()
+        .ok()
+}
+
+/// Adds the canvas of a window to the end of the page's body, unless it renders to a
+/// canvas already on the page, see [WindowConfig::canvas_id].
+pub(crate) fn attach_canvas(
+    window: &winit::window::Window,
+    config: &WindowConfig,
+) -> Result<(), ClockworkError> {
+    if let Some(id) = &config.canvas_id {
+        return match find_canvas(id) {
+            Some(_) => Ok(()),
+            None => Err(ClockworkError::Canvas(format!("no canvas with id {id:?}"))),
+        };
+    }
+
+    let body = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.body())
+        .ok_or_else(|| ClockworkError::Canvas("the page has no body".to_string()))?;
+    body.append_child(&window.canvas())
+        .map_err(|error| ClockworkError::Canvas(format!("{error:?}")))?;
+    Ok(())
+}
+
+/// Logs a message to the browser console.
+pub(crate) fn log_message(message: &str) {
+    web_sys::console::log_1(&message.into());
+}
+
+/// Logs an error to the browser console.
+pub(crate) fn log_error(message: &str) {
+    web_sys::console::error_1(&message.into());
+}